@@ -0,0 +1,131 @@
+use figment::providers::{Format, Serialized, Toml};
+use figment::Figment;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+// ==============================================================================
+// CONFIG: Layered settings for every service
+// ==============================================================================
+//
+// Each service describes its settings as one struct, and load() fills it in
+// from three layers, later ones winning:
+//
+//   1. the struct's Default
+//   2. a TOML file: CONFIG_FILE if set (it must exist), otherwise
+//      config/<service>.toml when there is one
+//   3. environment variables, named after the fields in upper case
+//      (database_url <- DATABASE_URL)
+//
+// Then the service's own checks run (Validate). Every problem found is
+// reported at once, by setting name, instead of failing on the first one:
+//
+//   Invalid configuration for api-service:
+//     - PORT: invalid type: found string "abc", expected u16
+//     - DATABASE_URL: required
+//
+// Settings read by a module's own from_env (payload transport, Svix routing,
+// ...) stay there; this covers what the services' main.rs reads.
+
+/// Checks serde can't express; one message per problem
+pub trait Validate {
+    fn validate(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigError {
+    service: String,
+    problems: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration for {}:", self.service)?;
+        for problem in &self.problems {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+pub fn load<T>(service: &str) -> Result<T, ConfigError>
+where
+    T: Default + Serialize + DeserializeOwned + Validate,
+{
+    let error = |problems| ConfigError {
+        service: service.to_string(),
+        problems,
+    };
+
+    let file = match std::env::var("CONFIG_FILE") {
+        Ok(path) if !path.is_empty() => {
+            let path = PathBuf::from(path);
+            if !path.is_file() {
+                return Err(error(vec![format!(
+                    "CONFIG_FILE: {} does not exist",
+                    path.display()
+                )]));
+            }
+            path
+        }
+        _ => PathBuf::from(format!("config/{}.toml", service)),
+    };
+
+    // Kept as text and converted where the field needs a number or a bool,
+    // so INSTANCE_NAME=123 is still a string. Empty variables count as unset.
+    let mut env: HashMap<String, String> = std::env::vars()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| (key.to_lowercase(), value))
+        .collect();
+
+    // serde stops at the first bad value: drop it and go again, so every bad
+    // variable is reported in one go
+    let mut problems = Vec::new();
+    let settings = loop {
+        let figment = Figment::from(Serialized::defaults(T::default()))
+            .merge(Toml::file(&file))
+            .merge(Serialized::defaults(&env));
+        match figment.extract_lossy::<T>() {
+            Ok(settings) => break settings,
+            Err(e) => {
+                let mut dropped = false;
+                for e in e {
+                    let key = e.path.join(".");
+                    problems.push(if key.is_empty() {
+                        e.kind.to_string()
+                    } else {
+                        format!("{}: {}", key.to_uppercase(), e.kind)
+                    });
+                    dropped |= env.remove(&key).is_some();
+                }
+                if !dropped {
+                    return Err(error(problems));
+                }
+            }
+        }
+    };
+
+    problems.extend(settings.validate());
+    if problems.is_empty() {
+        Ok(settings)
+    } else {
+        Err(error(problems))
+    }
+}
+
+/// load, printing the problems and exiting when there are any
+pub fn load_or_exit<T>(service: &str) -> T
+where
+    T: Default + Serialize + DeserializeOwned + Validate,
+{
+    load(service).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    })
+}
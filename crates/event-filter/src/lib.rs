@@ -0,0 +1,785 @@
+use serde_json::{Number, Value};
+use std::cmp::Ordering;
+
+// ==============================================================================
+// EVENT FILTER: Which events an endpoint wants, as an expression
+// ==============================================================================
+//
+// A small, CEL-like expression over the webhook body the endpoint would get,
+// e.g.
+//
+//   payment.amount > 10000 && payment.currency == 'USD'
+//   event_type.startsWith('payment.') && !(payment.status in ['failed', 'cancelled'])
+//   has(payment.amount_decimal) || size(payment.id) == 0
+//
+//   fields      dot-separated keys from the body's root; [0] or ['key']
+//               for array indexes and keys that aren't identifiers. A
+//               missing field is null.
+//   literals    numbers, 'strings' or "strings", true, false, null, [lists]
+//   operators   == != < <= > >=, in (a list's items or an object's keys),
+//               &&, ||, !, parentheses
+//   functions   has(field) (present and not null), size(x) (characters,
+//               items or keys); x.startsWith(s), x.endsWith(s), x.contains(s)
+//
+// Evaluating never fails: comparing values of different types is false (so
+// payment.amount_decimal, a string, is never > 100), ordering only applies
+// to two numbers or two strings, and anything but true counts as false.
+// Integers compare exactly, however large (ids, amounts in minor units);
+// only a fraction makes a comparison go through f64. An
+// expression is parsed once when it's saved (api-service, merchant-portal)
+// so mistakes are reported there, and again where events are delivered.
+
+/// Longest expression accepted
+pub const MAX_LEN: usize = 1024;
+
+/// Deepest nesting of parentheses, lists and negations
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct Filter {
+    source: String,
+    expr: Expr,
+}
+
+impl Filter {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        if source.len() > MAX_LEN {
+            return Err(format!("longer than {} characters", MAX_LEN));
+        }
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            next: 0,
+            depth: 0,
+        };
+        let expr = parser.expression()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!(
+                "unexpected {} at column {}",
+                token.kind.describe(),
+                token.column
+            ));
+        }
+        Ok(Filter {
+            source: source.trim().to_string(),
+            expr,
+        })
+    }
+
+    /// Whether the expression is true for this webhook body
+    pub fn matches(&self, body: &Value) -> bool {
+        eval(&self.expr, body) == Value::Bool(true)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Has,
+    Size,
+    StartsWith,
+    EndsWith,
+    Contains,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Value),
+    Field(Vec<Segment>),
+    List(Vec<Expr>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Op, Box<Expr>, Box<Expr>),
+    In(Box<Expr>, Box<Expr>),
+    /// Methods take their receiver as the first argument
+    Call(Function, Vec<Expr>),
+}
+
+// ------------------------------------------------------------------------------
+// Tokens
+// ------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Number(Value),
+    Str(String),
+    Ident(String),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Dot,
+    Minus,
+    Not,
+    And,
+    Or,
+    Op(Op),
+}
+
+impl TokenKind {
+    fn describe(&self) -> String {
+        match self {
+            TokenKind::Number(number) => format!("number {}", number),
+            TokenKind::Str(string) => format!("string {:?}", string),
+            TokenKind::Ident(ident) => format!("{:?}", ident),
+            TokenKind::LParen => "'('".to_string(),
+            TokenKind::RParen => "')'".to_string(),
+            TokenKind::LBracket => "'['".to_string(),
+            TokenKind::RBracket => "']'".to_string(),
+            TokenKind::Comma => "','".to_string(),
+            TokenKind::Dot => "'.'".to_string(),
+            TokenKind::Minus => "'-'".to_string(),
+            TokenKind::Not => "'!'".to_string(),
+            TokenKind::And => "'&&'".to_string(),
+            TokenKind::Or => "'||'".to_string(),
+            TokenKind::Op(_) => "comparison".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    /// 1-based, in characters
+    column: usize,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        let next = chars.get(i + 1).copied();
+        let (kind, len) = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => (TokenKind::LParen, 1),
+            ')' => (TokenKind::RParen, 1),
+            '[' => (TokenKind::LBracket, 1),
+            ']' => (TokenKind::RBracket, 1),
+            ',' => (TokenKind::Comma, 1),
+            '.' => (TokenKind::Dot, 1),
+            '-' => (TokenKind::Minus, 1),
+            '&' if next == Some('&') => (TokenKind::And, 2),
+            '|' if next == Some('|') => (TokenKind::Or, 2),
+            '=' if next == Some('=') => (TokenKind::Op(Op::Eq), 2),
+            '!' if next == Some('=') => (TokenKind::Op(Op::Ne), 2),
+            '!' => (TokenKind::Not, 1),
+            '<' if next == Some('=') => (TokenKind::Op(Op::Le), 2),
+            '<' => (TokenKind::Op(Op::Lt), 1),
+            '>' if next == Some('=') => (TokenKind::Op(Op::Ge), 2),
+            '>' => (TokenKind::Op(Op::Gt), 1),
+            '\'' | '"' => {
+                let mut string = String::new();
+                let mut end = i + 1;
+                loop {
+                    match chars.get(end) {
+                        None => return Err(format!("unterminated string at column {}", column)),
+                        Some(&quote) if quote == c => break,
+                        Some('\\') => {
+                            let escaped = match chars.get(end + 1) {
+                                Some('n') => '\n',
+                                Some('t') => '\t',
+                                Some(&other @ ('\\' | '\'' | '"')) => other,
+                                _ => return Err(format!("invalid escape at column {}", end + 1)),
+                            };
+                            string.push(escaped);
+                            end += 2;
+                        }
+                        Some(&other) => {
+                            string.push(other);
+                            end += 1;
+                        }
+                    }
+                }
+                (TokenKind::Str(string), end + 1 - i)
+            }
+            c if c.is_ascii_digit() => {
+                let mut end = i;
+                while chars
+                    .get(end)
+                    .is_some_and(|c| c.is_ascii_digit() || *c == '.')
+                {
+                    end += 1;
+                }
+                let text: String = chars[i..end].iter().collect();
+                let number = match (text.parse::<i64>(), text.parse::<u64>()) {
+                    (Ok(integer), _) => Value::from(integer),
+                    (_, Ok(integer)) => Value::from(integer),
+                    _ => text
+                        .parse::<f64>()
+                        .ok()
+                        .and_then(Number::from_f64)
+                        .map(Value::Number)
+                        .ok_or_else(|| format!("invalid number {} at column {}", text, column))?,
+                };
+                (TokenKind::Number(number), end - i)
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut end = i;
+                while chars
+                    .get(end)
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_')
+                {
+                    end += 1;
+                }
+                (TokenKind::Ident(chars[i..end].iter().collect()), end - i)
+            }
+            other => return Err(format!("unexpected {:?} at column {}", other, column)),
+        };
+        tokens.push(Token { kind, column });
+        i += len;
+    }
+    Ok(tokens)
+}
+
+// ------------------------------------------------------------------------------
+// Parser
+// ------------------------------------------------------------------------------
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if self.peek().is_some_and(|token| token.kind == *kind) {
+            self.next += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<(), String> {
+        if self.eat(&kind) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&kind.describe()))
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> String {
+        match self.peek() {
+            Some(token) => format!(
+                "expected {} at column {}, found {}",
+                expected,
+                token.column,
+                token.kind.describe()
+            ),
+            None => format!("expected {} at the end", expected),
+        }
+    }
+
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, String>,
+    ) -> Result<T, String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(format!("nested deeper than {}", MAX_DEPTH));
+        }
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn expression(&mut self) -> Result<Expr, String> {
+        let mut left = self.and()?;
+        while self.eat(&TokenKind::Or) {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        while self.eat(&TokenKind::And) {
+            left = Expr::And(Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat(&TokenKind::Not) {
+            return self.nested(|parser| Ok(Expr::Not(Box::new(parser.unary()?))));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.postfix()?;
+        match self.peek().map(|token| token.kind.clone()) {
+            Some(TokenKind::Op(op)) => {
+                self.next += 1;
+                Ok(Expr::Compare(op, Box::new(left), Box::new(self.postfix()?)))
+            }
+            Some(TokenKind::Ident(ident)) if ident == "in" => {
+                self.next += 1;
+                Ok(Expr::In(Box::new(left), Box::new(self.postfix()?)))
+            }
+            _ => Ok(left),
+        }
+    }
+
+    /// A primary followed by field accesses, indexes and method calls
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat(&TokenKind::Dot) {
+                let name = match self.peek().map(|token| token.kind.clone()) {
+                    Some(TokenKind::Ident(name)) => name,
+                    _ => return Err(self.unexpected("a field or method name")),
+                };
+                self.next += 1;
+                if self.eat(&TokenKind::LParen) {
+                    let function = match name.as_str() {
+                        "startsWith" => Function::StartsWith,
+                        "endsWith" => Function::EndsWith,
+                        "contains" => Function::Contains,
+                        other => return Err(format!("unknown method {}", other)),
+                    };
+                    let mut args = vec![expr];
+                    args.extend(self.arguments(1)?);
+                    expr = Expr::Call(function, args);
+                } else {
+                    expr = field(expr, Segment::Key(name))?;
+                }
+            } else if self.eat(&TokenKind::LBracket) {
+                let segment = match self.peek().map(|token| token.kind.clone()) {
+                    Some(TokenKind::Number(Value::Number(index))) if index.is_u64() => {
+                        Segment::Index(index.as_u64().unwrap_or_default() as usize)
+                    }
+                    Some(TokenKind::Str(key)) => Segment::Key(key),
+                    _ => return Err(self.unexpected("an index or a 'key'")),
+                };
+                self.next += 1;
+                self.expect(TokenKind::RBracket)?;
+                expr = field(expr, segment)?;
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    /// After the opening parenthesis, through the closing one
+    fn arguments(&mut self, count: usize) -> Result<Vec<Expr>, String> {
+        let mut args = Vec::with_capacity(count);
+        for i in 0..count {
+            if i > 0 {
+                self.expect(TokenKind::Comma)?;
+            }
+            args.push(self.nested(Parser::expression)?);
+        }
+        self.expect(TokenKind::RParen)?;
+        Ok(args)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.unexpected("a value"));
+        };
+        self.next += 1;
+        match token.kind {
+            TokenKind::Number(number) => Ok(Expr::Literal(number)),
+            TokenKind::Minus => match self.peek().map(|token| token.kind.clone()) {
+                Some(TokenKind::Number(number)) => {
+                    self.next += 1;
+                    // -9223372036854775808 too, though its digits are a u64
+                    let exact = number.as_number().and_then(integer);
+                    let negated = match exact.and_then(|integer| i64::try_from(-integer).ok()) {
+                        Some(integer) => Value::from(integer),
+                        None => Value::from(-number.as_f64().unwrap_or_default()),
+                    };
+                    Ok(Expr::Literal(negated))
+                }
+                _ => Err(self.unexpected("a number")),
+            },
+            TokenKind::Str(string) => Ok(Expr::Literal(Value::String(string))),
+            TokenKind::LParen => {
+                let expr = self.nested(Parser::expression)?;
+                self.expect(TokenKind::RParen)?;
+                Ok(expr)
+            }
+            TokenKind::LBracket => self.nested(|parser| {
+                let mut items = Vec::new();
+                if !parser.eat(&TokenKind::RBracket) {
+                    loop {
+                        items.push(parser.expression()?);
+                        if parser.eat(&TokenKind::RBracket) {
+                            break;
+                        }
+                        parser.expect(TokenKind::Comma)?;
+                    }
+                }
+                Ok(Expr::List(items))
+            }),
+            TokenKind::Ident(ident) => match ident.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                "in" => Err(format!("unexpected \"in\" at column {}", token.column)),
+                "has" | "size" if self.eat(&TokenKind::LParen) => {
+                    let args = self.arguments(1)?;
+                    if ident == "has" {
+                        if !matches!(args[0], Expr::Field(_)) {
+                            return Err("has() takes a field".to_string());
+                        }
+                        Ok(Expr::Call(Function::Has, args))
+                    } else {
+                        Ok(Expr::Call(Function::Size, args))
+                    }
+                }
+                _ if self
+                    .peek()
+                    .is_some_and(|next| next.kind == TokenKind::LParen) =>
+                {
+                    Err(format!("unknown function {}", ident))
+                }
+                _ => Ok(Expr::Field(vec![Segment::Key(ident)])),
+            },
+            other => Err(format!(
+                "expected a value at column {}, found {}",
+                token.column,
+                other.describe()
+            )),
+        }
+    }
+}
+
+/// `base.key` or `base[index]`: only fields have fields
+fn field(base: Expr, segment: Segment) -> Result<Expr, String> {
+    match base {
+        Expr::Field(mut path) => {
+            path.push(segment);
+            Ok(Expr::Field(path))
+        }
+        _ => Err("only fields have fields or indexes".to_string()),
+    }
+}
+
+// ------------------------------------------------------------------------------
+// Evaluation
+// ------------------------------------------------------------------------------
+
+fn lookup<'a>(body: &'a Value, path: &[Segment]) -> Option<&'a Value> {
+    path.iter().try_fold(body, |value, segment| match segment {
+        Segment::Key(key) => value.get(key.as_str()),
+        Segment::Index(index) => value.get(*index),
+    })
+}
+
+fn eval(expr: &Expr, body: &Value) -> Value {
+    match expr {
+        Expr::Literal(value) => value.clone(),
+        Expr::Field(path) => lookup(body, path).cloned().unwrap_or(Value::Null),
+        Expr::List(items) => Value::Array(items.iter().map(|item| eval(item, body)).collect()),
+        Expr::Not(inner) => Value::Bool(!truthy(&eval(inner, body))),
+        Expr::And(left, right) => {
+            Value::Bool(truthy(&eval(left, body)) && truthy(&eval(right, body)))
+        }
+        Expr::Or(left, right) => {
+            Value::Bool(truthy(&eval(left, body)) || truthy(&eval(right, body)))
+        }
+        Expr::Compare(op, left, right) => {
+            Value::Bool(compare(*op, &eval(left, body), &eval(right, body)))
+        }
+        Expr::In(item, container) => {
+            let item = eval(item, body);
+            Value::Bool(match eval(container, body) {
+                Value::Array(items) => items.iter().any(|candidate| equal(&item, candidate)),
+                Value::Object(map) => item.as_str().is_some_and(|key| map.contains_key(key)),
+                _ => false,
+            })
+        }
+        Expr::Call(Function::Has, args) => Value::Bool(match &args[0] {
+            Expr::Field(path) => lookup(body, path).is_some_and(|value| !value.is_null()),
+            _ => false,
+        }),
+        Expr::Call(Function::Size, args) => match eval(&args[0], body) {
+            Value::String(string) => Value::from(string.chars().count()),
+            Value::Array(items) => Value::from(items.len()),
+            Value::Object(map) => Value::from(map.len()),
+            _ => Value::Null,
+        },
+        Expr::Call(function, args) => {
+            let (receiver, argument) = (eval(&args[0], body), eval(&args[1], body));
+            let (Some(receiver), Some(argument)) = (receiver.as_str(), argument.as_str()) else {
+                return Value::Bool(false);
+            };
+            Value::Bool(match function {
+                Function::StartsWith => receiver.starts_with(argument),
+                Function::EndsWith => receiver.ends_with(argument),
+                _ => receiver.contains(argument),
+            })
+        }
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    *value == Value::Bool(true)
+}
+
+/// Numbers by value, so 1 == 1.0
+fn equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(left), Value::Number(right)) => {
+            compare_numbers(left, right) == Some(Ordering::Equal)
+        }
+        _ => left == right,
+    }
+}
+
+/// A whole number's exact value: any i64 or u64, or a float without a
+/// fraction in i128's range
+fn integer(number: &Number) -> Option<i128> {
+    match (number.as_i64(), number.as_u64(), number.as_f64()) {
+        (Some(integer), _, _) => Some(integer.into()),
+        (_, Some(integer), _) => Some(integer.into()),
+        (_, _, Some(float)) if float.fract() == 0.0 && float.abs() < 1e38 => Some(float as i128),
+        _ => None,
+    }
+}
+
+/// Exact for two whole numbers, through f64 otherwise
+fn compare_numbers(left: &Number, right: &Number) -> Option<Ordering> {
+    match (integer(left), integer(right)) {
+        (Some(left), Some(right)) => Some(left.cmp(&right)),
+        _ => left
+            .as_f64()
+            .zip(right.as_f64())
+            .and_then(|(left, right)| left.partial_cmp(&right)),
+    }
+}
+
+fn compare(op: Op, left: &Value, right: &Value) -> bool {
+    let ordering = match (left, right) {
+        (Value::Number(left), Value::Number(right)) => compare_numbers(left, right),
+        (Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+        _ => None,
+    };
+    match op {
+        Op::Eq => equal(left, right),
+        Op::Ne => !equal(left, right),
+        Op::Lt => ordering.is_some_and(|ordering| ordering.is_lt()),
+        Op::Le => ordering.is_some_and(|ordering| ordering.is_le()),
+        Op::Gt => ordering.is_some_and(|ordering| ordering.is_gt()),
+        Op::Ge => ordering.is_some_and(|ordering| ordering.is_ge()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn body() -> Value {
+        json!({
+            "event_type": "payment.succeeded",
+            "payment": {
+                "id": "pay_123",
+                "amount": 12500,
+                "amount_decimal": "125.00",
+                "currency": "USD",
+                "status": "succeeded",
+                "refunded": false,
+                "captured_at": null,
+                "metadata": {"order-id": "A-1", "tier": "gold"},
+                "items": [{"sku": "tee"}, {"sku": "mug"}],
+                "big": 18446744073709551615u64,
+                "ledger_id": 9007199254740993i64,
+                "rate": 1.5
+            }
+        })
+    }
+
+    fn matches(source: &str) -> bool {
+        Filter::parse(source)
+            .unwrap_or_else(|e| panic!("{}: {}", source, e))
+            .matches(&body())
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert!(matches("true || false && false"));
+        assert!(!matches("(true || false) && false"));
+        assert!(matches("false && false || true"));
+        assert!(matches(
+            "payment.currency == 'EUR' && payment.amount > 1 || payment.status == 'succeeded'"
+        ));
+    }
+
+    #[test]
+    fn not_applies_to_the_comparison_or_group_after_it() {
+        assert!(matches("!false && true"));
+        assert!(!matches("!(false || true)"));
+        assert!(matches("!!true"));
+        assert!(matches("!(payment.status in ['failed', 'cancelled'])"));
+    }
+
+    #[test]
+    fn in_checks_list_items_and_object_keys() {
+        assert!(matches("payment.currency in ['USD', 'EUR']"));
+        assert!(!matches("payment.currency in ['GBP']"));
+        assert!(!matches("payment.currency in []"));
+        assert!(matches("payment.amount in [12500.0, 1]"));
+        assert!(matches("'tier' in payment.metadata"));
+        assert!(!matches("'gold' in payment.metadata"));
+        assert!(!matches("'USD' in payment.currency"));
+    }
+
+    #[test]
+    fn has_is_present_and_not_null() {
+        assert!(matches("has(payment.amount)"));
+        assert!(matches("has(payment.refunded)"));
+        assert!(!matches("has(payment.captured_at)"));
+        assert!(!matches("has(payment.missing)"));
+        assert!(!matches("has(payment.items[5])"));
+        assert!(Filter::parse("has('payment')").is_err());
+    }
+
+    #[test]
+    fn size_counts_characters_items_and_keys() {
+        assert!(matches("size(payment.id) == 7"));
+        assert!(matches("size('héllo') == 5"));
+        assert!(matches("size(payment.items) == 2"));
+        assert!(matches("size(payment.metadata) == 2"));
+        assert!(!matches("size(payment.amount) == 0"));
+        assert!(matches("size(payment.amount) == null"));
+    }
+
+    #[test]
+    fn fields_indexes_and_quoted_keys() {
+        assert!(matches("payment.items[1].sku == 'mug'"));
+        assert!(matches("payment.metadata['order-id'] == 'A-1'"));
+        assert!(matches("payment['currency'] == 'USD'"));
+        assert!(matches("payment.items[2] == null"));
+        assert!(matches("missing.deeper.still == null"));
+    }
+
+    #[test]
+    fn string_methods() {
+        assert!(matches("event_type.startsWith('payment.')"));
+        assert!(matches("event_type.endsWith('.succeeded')"));
+        assert!(matches("payment.id.contains('_12')"));
+        assert!(!matches("event_type.startsWith('refund.')"));
+        // Only on strings
+        assert!(!matches("payment.amount.startsWith('1')"));
+        assert!(!matches("event_type.contains(1)"));
+        assert!(Filter::parse("event_type.matches('x')").is_err());
+        assert!(Filter::parse("lower(event_type)").is_err());
+    }
+
+    #[test]
+    fn mismatched_types_are_never_equal_or_ordered() {
+        assert!(!matches("payment.amount_decimal > 100"));
+        assert!(!matches("payment.amount_decimal < 100"));
+        assert!(!matches("payment.amount_decimal == 125"));
+        assert!(matches("payment.amount_decimal != 125"));
+        assert!(!matches("payment.refunded == 0"));
+        assert!(!matches("payment.captured_at < 1"));
+        assert!(!matches("payment.missing >= 0"));
+        assert!(!matches("payment.items > payment.metadata"));
+        // Only true is true
+        assert!(!matches("payment.amount"));
+        assert!(!matches("payment.id && true"));
+    }
+
+    #[test]
+    fn strings_order_and_numbers_compare_by_value() {
+        assert!(matches(
+            "payment.currency < 'ZAR' && payment.currency >= 'USD'"
+        ));
+        assert!(matches("payment.amount == 12500.0"));
+        assert!(matches("payment.rate > 1 && payment.rate < 2"));
+        assert!(matches("-1 < 0 && -1.5 < -1"));
+    }
+
+    #[test]
+    fn large_integers_compare_exactly() {
+        // Both round to 9007199254740992 as f64
+        assert!(!matches("payment.ledger_id == 9007199254740992"));
+        assert!(matches("payment.ledger_id == 9007199254740993"));
+        assert!(matches("payment.ledger_id > 9007199254740992"));
+        // Beyond i64
+        assert!(matches("payment.big == 18446744073709551615"));
+        assert!(!matches("payment.big == 18446744073709551614"));
+        assert!(matches("payment.big > 9223372036854775807"));
+        assert!(matches("-9223372036854775808 < -9223372036854775807"));
+    }
+
+    #[test]
+    fn syntax_errors_say_where() {
+        assert_eq!(
+            Filter::parse("payment.amount >").unwrap_err(),
+            "expected a value at the end"
+        );
+        assert_eq!(
+            Filter::parse("payment.amount > 1 1").unwrap_err(),
+            "unexpected number 1 at column 20"
+        );
+        assert!(Filter::parse("payment.id == 'open").is_err());
+        assert!(Filter::parse("payment.id == 'a\\q'").is_err());
+        assert!(Filter::parse("payment.amount = 1").is_err());
+        assert!(Filter::parse("1.2.3 == 1").is_err());
+        assert!(Filter::parse("(payment.amount > 1").is_err());
+        assert!(Filter::parse("'a'.b == 1").is_err());
+        assert!(Filter::parse("").is_err());
+    }
+
+    #[test]
+    fn length_and_depth_are_limited() {
+        let long = format!("event_type == '{}'", "x".repeat(MAX_LEN));
+        assert!(Filter::parse(&long).unwrap_err().contains("longer than"));
+
+        let nested = |depth: usize| format!("{}true{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(Filter::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(Filter::parse(&nested(MAX_DEPTH + 1))
+            .unwrap_err()
+            .contains("nested deeper"));
+        assert!(Filter::parse(&format!("{}true", "!".repeat(MAX_DEPTH + 1))).is_err());
+        let lists = format!(
+            "1 in {}1{}",
+            "[".repeat(MAX_DEPTH + 1),
+            "]".repeat(MAX_DEPTH + 1)
+        );
+        assert!(Filter::parse(&lists).is_err());
+    }
+
+    #[test]
+    fn the_source_is_kept_trimmed() {
+        let filter = Filter::parse("  payment.amount > 1 \n").unwrap();
+        assert_eq!(filter.as_str(), "payment.amount > 1");
+    }
+}
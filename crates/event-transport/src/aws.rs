@@ -0,0 +1,392 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use http_client::{HttpClient, HttpClientConfig, HttpMetrics};
+use reqwest::{Method, Url};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+use crate::TransportError;
+
+// ==============================================================================
+// AWS: Signed calls to SQS, SNS and S3, without the SDK
+// ==============================================================================
+//
+// The APIs the sqs module and the event archiver need, over the shared
+// http-client (pooling, metrics) with Signature Version 4:
+//
+//   json()        AWS JSON 1.0 (SQS): POST /, X-Amz-Target: AmazonSQS.<Action>
+//   query()       the Query protocol (SNS): a form-encoded POST, XML back
+//   put_object()  S3 PutObject, path-style (/<bucket>/<key>), which MinIO
+//   get_object()  and LocalStack also take; GetObject the same way
+//
+// Credentials are AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY and, for
+// temporary ones, AWS_SESSION_TOKEN, read with each service's settings
+// however they get into the environment (`aws configure export-credentials
+// --format env`, a secrets manager); AwsConfig::new checks them at startup.
+// Instance and task role lookups aren't implemented. An endpoint URL points
+// every call at LocalStack (or any other endpoint) instead of
+// <service>.<region>.amazonaws.com.
+
+#[derive(Clone, Debug)]
+pub struct AwsConfig {
+    pub region: String,
+    /// Overrides https://<service>.<region>.amazonaws.com for every service
+    pub endpoint_url: Option<String>,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl AwsConfig {
+    /// From a service's settings; every problem with them, in the config
+    /// crate's format, if they don't make a usable config
+    pub fn new(
+        region: &str,
+        endpoint_url: Option<&str>,
+        access_key_id: Option<&str>,
+        secret_access_key: Option<&str>,
+        session_token: Option<&str>,
+    ) -> Result<Self, Vec<String>> {
+        let set = |value: Option<&str>| value.filter(|value| !value.is_empty()).map(str::to_string);
+        let mut problems = Vec::new();
+        let endpoint_url = set(endpoint_url);
+        if let Some(url) = &endpoint_url {
+            match Url::parse(url) {
+                Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+                Ok(_) => problems.push(format!("AWS_ENDPOINT_URL: {:?} is not http(s)", url)),
+                Err(e) => problems.push(format!("AWS_ENDPOINT_URL: {:?}: {}", url, e)),
+            }
+        }
+        let access_key_id = set(access_key_id);
+        if access_key_id.is_none() {
+            problems.push("AWS_ACCESS_KEY_ID: required".to_string());
+        }
+        let secret_access_key = set(secret_access_key);
+        if secret_access_key.is_none() {
+            problems.push("AWS_SECRET_ACCESS_KEY: required".to_string());
+        }
+        match (access_key_id, secret_access_key) {
+            (Some(access_key_id), Some(secret_access_key)) if problems.is_empty() => {
+                Ok(AwsConfig {
+                    region: region.to_string(),
+                    endpoint_url,
+                    access_key_id,
+                    secret_access_key,
+                    session_token: set(session_token),
+                })
+            }
+            _ => Err(problems),
+        }
+    }
+
+    /// Where `service` is called
+    pub fn endpoint(&self, service: &str) -> String {
+        match &self.endpoint_url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => format!("https://{}.{}.amazonaws.com", service, self.region),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AwsClient {
+    http: HttpClient,
+    config: AwsConfig,
+}
+
+impl AwsClient {
+    pub fn new(config: AwsConfig, metrics: &HttpMetrics) -> Result<Self, String> {
+        // Calls are POSTs, so never retried by the client; callers retry.
+        // Long enough for a ReceiveMessage long poll
+        let http = HttpClient::new(
+            HttpClientConfig {
+                timeout: Duration::from_secs(30),
+                ..HttpClientConfig::new("aws")
+            },
+            metrics,
+        )?;
+        Ok(AwsClient { http, config })
+    }
+
+    pub fn region(&self) -> &str {
+        &self.config.region
+    }
+
+    /// An AWS JSON 1.0 action: sqs, "AmazonSQS.SendMessage", {...}
+    pub async fn json(
+        &self,
+        service: &str,
+        target: &str,
+        body: &serde_json::Value,
+    ) -> Result<serde_json::Value, TransportError> {
+        let body = body.to_string();
+        let response = self
+            .send(
+                service,
+                &[
+                    ("content-type", "application/x-amz-json-1.0"),
+                    ("x-amz-target", target),
+                ],
+                body,
+            )
+            .await?;
+        if response.is_empty() {
+            return Ok(serde_json::Value::Null);
+        }
+        serde_json::from_str(&response)
+            .map_err(|e| TransportError(format!("{}: unreadable response: {}", target, e)))
+    }
+
+    /// A Query protocol action: sns, [("Action", "Publish"), ...]; the XML
+    /// response as text
+    pub async fn query(
+        &self,
+        service: &str,
+        params: &[(&str, &str)],
+    ) -> Result<String, TransportError> {
+        let body = params
+            .iter()
+            .map(|(name, value)| format!("{}={}", uri_encode(name), uri_encode(value)))
+            .collect::<Vec<_>>()
+            .join("&");
+        self.send(
+            service,
+            &[(
+                "content-type",
+                "application/x-www-form-urlencoded; charset=utf-8",
+            )],
+            body,
+        )
+        .await
+    }
+
+    /// S3 PutObject. PUTs are idempotent, so http-client retries them.
+    pub async fn put_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        content_type: &str,
+        content_encoding: Option<&str>,
+        body: Vec<u8>,
+    ) -> Result<(), TransportError> {
+        let path = format!("/{}/{}", uri_encode(bucket), uri_encode_path(key));
+        let payload_hash = sha256_hex(&body);
+        let mut headers = vec![
+            ("content-type", content_type),
+            ("x-amz-content-sha256", payload_hash.as_str()),
+        ];
+        if let Some(encoding) = content_encoding {
+            headers.push(("content-encoding", encoding));
+        }
+        self.call("s3", Method::PUT, &path, &headers, body)
+            .await
+            .map(|_| ())
+            .map_err(|e| TransportError(format!("PutObject {}/{}: {}", bucket, key, e)))
+    }
+
+    /// S3 GetObject: the stored bytes, as they were put (a gzipped object
+    /// comes back gzipped)
+    pub async fn get_object(&self, bucket: &str, key: &str) -> Result<Vec<u8>, TransportError> {
+        let path = format!("/{}/{}", uri_encode(bucket), uri_encode_path(key));
+        let payload_hash = sha256_hex(&[]);
+        self.call(
+            "s3",
+            Method::GET,
+            &path,
+            &[("x-amz-content-sha256", payload_hash.as_str())],
+            Vec::new(),
+        )
+        .await
+        .map_err(|e| TransportError(format!("GetObject {}/{}: {}", bucket, key, e)))
+    }
+
+    async fn send(
+        &self,
+        service: &str,
+        headers: &[(&str, &str)],
+        body: String,
+    ) -> Result<String, TransportError> {
+        let response = self
+            .call(service, Method::POST, "/", headers, body.into_bytes())
+            .await?;
+        Ok(String::from_utf8_lossy(&response).into_owned())
+    }
+
+    /// A signed request to `path` (already URI-encoded); the response body,
+    /// or an error for anything but a 2xx
+    async fn call(
+        &self,
+        service: &str,
+        method: Method,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<Vec<u8>, TransportError> {
+        let endpoint = self.config.endpoint(service);
+        let url = Url::parse(&format!("{}{}", endpoint, path))
+            .map_err(|e| TransportError(format!("Invalid endpoint {}: {}", endpoint, e)))?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let signed = sign(
+            &self.config,
+            service,
+            &Canonical {
+                method: method.as_str(),
+                path,
+                host: &host,
+                headers,
+                payload_hash: &sha256_hex(&body),
+            },
+            Utc::now(),
+        );
+        let mut request = self.http.request(method, url.as_str()).body(body);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        for (name, value) in signed {
+            request = request.header(name, value);
+        }
+        let response = self
+            .http
+            .send(request)
+            .await
+            .map_err(|e| TransportError(format!("{} unreachable: {}", service, e)))?;
+        let status = response.status();
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| TransportError(format!("{} response unreadable: {}", service, e)))?;
+        if status.is_success() {
+            Ok(body.to_vec())
+        } else {
+            Err(TransportError(format!(
+                "{} returned {}: {}",
+                service,
+                status,
+                String::from_utf8_lossy(&body)
+            )))
+        }
+    }
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+/// RFC 3986 unreserved characters pass, everything else is %XX (what SigV4
+/// and the Query protocol expect)
+fn uri_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// uri_encode for each segment of an S3 key, keeping the slashes
+fn uri_encode_path(path: &str) -> String {
+    path.split('/')
+        .map(uri_encode)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// What the signature covers of a request
+struct Canonical<'a> {
+    method: &'a str,
+    /// URI-encoded, as sent
+    path: &'a str,
+    host: &'a str,
+    headers: &'a [(&'a str, &'a str)],
+    payload_hash: &'a str,
+}
+
+/// The x-amz-date, x-amz-security-token and authorization headers for a
+/// request without a query string (Signature Version 4)
+fn sign(
+    config: &AwsConfig,
+    service: &str,
+    request: &Canonical,
+    now: DateTime<Utc>,
+) -> Vec<(&'static str, String)> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+
+    let mut signed = vec![("x-amz-date", amz_date.clone())];
+    if let Some(token) = &config.session_token {
+        signed.push(("x-amz-security-token", token.clone()));
+    }
+
+    // Lower-case names, sorted, host included
+    let mut canonical: Vec<(String, String)> = request
+        .headers
+        .iter()
+        .map(|(name, value)| (name.to_ascii_lowercase(), value.trim().to_string()))
+        .chain(
+            signed
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone())),
+        )
+        .chain([("host".to_string(), request.host.to_string())])
+        .collect();
+    canonical.sort();
+    let canonical_headers: String = canonical
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value))
+        .collect();
+    let signed_headers = canonical
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let canonical_request = format!(
+        "{}\n{}\n\n{}\n{}\n{}",
+        request.method, request.path, canonical_headers, signed_headers, request.payload_hash
+    );
+    let scope = format!("{}/{}/{}/aws4_request", date, config.region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        sha256_hex(canonical_request.as_bytes())
+    );
+
+    let key = hmac(
+        format!("AWS4{}", config.secret_access_key).as_bytes(),
+        &date,
+    );
+    let key = hmac(&key, &config.region);
+    let key = hmac(&key, service);
+    let key = hmac(&key, "aws4_request");
+    let signature = hex(&hmac(&key, &string_to_sign));
+
+    signed.push((
+        "authorization",
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            config.access_key_id, scope, signed_headers, signature
+        ),
+    ));
+    signed
+}
@@ -0,0 +1,29 @@
+use futures::future::BoxFuture;
+use kafka_producer::{PartitionKey, Producer};
+
+use crate::{EventPublisher, OutgoingMessage, TransportError};
+
+// The producer already dedups its own retries (idempotence), so the message
+// id isn't needed; keys are merchant keys, as SvixCaller expects
+impl EventPublisher for Producer {
+    fn transport(&self) -> &'static str {
+        "kafka"
+    }
+
+    fn publish<'a>(
+        &'a self,
+        message: OutgoingMessage<'a>,
+    ) -> BoxFuture<'a, Result<(), TransportError>> {
+        Box::pin(async move {
+            self.send(
+                message.topic,
+                PartitionKey::Merchant(message.key),
+                message.payload,
+                message.headers,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|e| TransportError(e.to_string()))
+        })
+    }
+}
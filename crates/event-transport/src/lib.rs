@@ -0,0 +1,112 @@
+use futures::future::BoxFuture;
+use std::fmt;
+use std::time::Duration;
+
+#[cfg(feature = "amqp")]
+pub mod amqp;
+#[cfg(feature = "aws")]
+pub mod aws;
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "nats")]
+pub mod nats;
+#[cfg(feature = "redis")]
+pub mod redis_streams;
+#[cfg(feature = "aws")]
+pub mod sqs;
+
+// ==============================================================================
+// EVENT TRANSPORT: Kafka, NATS, SQS/SNS, RabbitMQ or Redis, behind one trait
+// ==============================================================================
+//
+// What a publisher needs from the broker, so a service can be pointed at
+// any of them with TRANSPORT and nothing else changes:
+//
+//   EventPublisher  publish one message to a topic under a key, and resolve
+//                   once the broker has it
+//
+//   kafka   kafka_producer::Producer (acks=all, idempotent). The key picks
+//           the partition, so a key's messages stay in order
+//   nats    nats::JetStreamPublisher. A topic is a stream, TOPIC_NAME, over
+//           the subjects topic.>; the key becomes the last subject token
+//           (topic.<key>). The id, when given, is the Nats-Msg-Id, so a
+//           message published twice within the duplicate window is stored
+//           once
+//   sqs     sqs::SqsPublisher. A topic is a FIFO queue, topic.fifo, with a
+//           topic-dlq.fifo behind it; the key is the message group (in
+//           order per key) and the id the deduplication id
+//   sns     sqs::SnsPublisher. The same, to a FIFO topic that queues
+//           subscribe to (sqs::SubscriberConfig::sns_topic)
+//   amqp    amqp::AmqpPublisher. A topic is a RabbitMQ topic exchange and
+//           the key the routing key; publishes are confirmed, and fail if
+//           no queue is bound yet
+//   redis   redis_streams::RedisPublisher. A topic is a stream (XADD), with
+//           the key, id and payload as fields; no broker-side dedup
+//
+// Consuming differs more: Kafka topics are read by Restate's subscription,
+// the others by an EventSubscriber that webhook-consumer hands on to the
+// same Restate handler. Each message it returns is acked once handled,
+// retried after a delay (a JetStream nak, an SQS visibility timeout, an
+// AMQP requeue, a pending Redis entry) or rejected (terminated, or moved to the dead-letter queue).
+
+/// One message on its way out
+#[derive(Clone, Copy, Debug)]
+pub struct OutgoingMessage<'a> {
+    pub topic: &'a str,
+    /// Messages with the same key stay in order (merchant_id, usually)
+    pub key: &'a str,
+    /// Stable across retries of the same message, for broker-side dedup
+    pub id: Option<&'a str>,
+    pub payload: &'a [u8],
+    pub headers: &'a [(&'a str, &'a str)],
+}
+
+pub trait EventPublisher: Send + Sync {
+    /// "kafka", "nats", "sqs", "sns", "amqp" or "redis", for logs
+    fn transport(&self) -> &'static str;
+
+    /// Resolves once the broker acknowledged the message, or gave up on it
+    fn publish<'a>(
+        &'a self,
+        message: OutgoingMessage<'a>,
+    ) -> BoxFuture<'a, Result<(), TransportError>>;
+}
+
+#[derive(Debug)]
+pub struct TransportError(pub String);
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+pub trait EventSubscriber: Send {
+    /// The next message, waiting as long as it takes; None once the
+    /// subscription has ended and needs connecting again
+    fn next(&mut self) -> BoxFuture<'_, Option<Result<Box<dyn ReceivedMessage>, TransportError>>>;
+}
+
+/// A delivered message, to be settled one way or another
+pub trait ReceivedMessage: Send + Sync {
+    fn key(&self) -> &str;
+    fn payload(&self) -> &[u8];
+    /// The publisher's message id, if it gave one
+    fn id(&self) -> Option<&str>;
+    /// Where it is in the broker (stream sequence, message id), for logs
+    fn reference(&self) -> String;
+    /// 1 on first delivery
+    fn deliveries(&self) -> i64;
+
+    /// Handled; never delivered again
+    fn ack(&self) -> BoxFuture<'_, Result<(), TransportError>>;
+    /// Not handled; deliver again after `delay`
+    fn retry(&self, delay: Duration) -> BoxFuture<'_, Result<(), TransportError>>;
+    /// Can never be handled; stop delivering it
+    fn reject(&self) -> BoxFuture<'_, Result<(), TransportError>>;
+}
+
+/// The TRANSPORT values a service accepts, for its settings validation
+pub const TRANSPORTS: &[&str] = &["kafka", "nats", "sqs", "sns", "amqp", "redis"];
@@ -0,0 +1,310 @@
+use async_nats::jetstream::consumer::{pull, AckPolicy};
+use async_nats::jetstream::context::Publish;
+use async_nats::jetstream::message::AckKind;
+use async_nats::jetstream::{self, stream};
+use async_nats::HeaderMap;
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use prometheus::{IntCounterVec, Opts, Registry};
+use std::time::Duration;
+
+use crate::{EventPublisher, EventSubscriber, OutgoingMessage, ReceivedMessage, TransportError};
+
+// ==============================================================================
+// NATS: JetStream publisher and durable subscriber
+// ==============================================================================
+//
+// A topic maps to a stream named after it (webhook-events -> WEBHOOK_EVENTS)
+// over the subjects topic.>, created on connect if it doesn't exist; a
+// message's key is the last subject token. Streams keep DUPLICATE_WINDOW of
+// message ids, so a publisher retrying after a lost ack doesn't store the
+// message twice.
+//
+// Metrics, per topic, in the service's registry:
+//
+//   nats_messages_published_total{topic, outcome}    ok, duplicate or error
+
+/// How long a stream remembers message ids for dedup
+pub const DUPLICATE_WINDOW: Duration = Duration::from_secs(120);
+
+/// The stream a topic is stored in; stream names can't contain . * > or spaces
+pub fn stream_name(topic: &str) -> String {
+    topic
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect()
+}
+
+/// topic.<key>, with the characters a subject token can't hold replaced
+pub fn subject(topic: &str, key: &str) -> String {
+    let token: String = key
+        .chars()
+        .map(|c| match c {
+            '.' | '*' | '>' | ' ' | '\t' => '_',
+            _ => c,
+        })
+        .collect();
+    if token.is_empty() {
+        format!("{}._", topic)
+    } else {
+        format!("{}.{}", topic, token)
+    }
+}
+
+async fn ensure_stream(
+    context: &jetstream::Context,
+    topic: &str,
+) -> Result<stream::Stream, String> {
+    context
+        .get_or_create_stream(stream::Config {
+            name: stream_name(topic),
+            subjects: vec![format!("{}.>", topic)],
+            duplicate_window: DUPLICATE_WINDOW,
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| format!("Failed to create JetStream stream for {}: {}", topic, e))
+}
+
+/// Registered once per service, shared by its publishers
+#[derive(Clone)]
+pub struct NatsMetrics {
+    published: IntCounterVec,
+}
+
+impl NatsMetrics {
+    pub fn new(registry: &Registry) -> Self {
+        let published = IntCounterVec::new(
+            Opts::new(
+                "nats_messages_published_total",
+                "JetStream messages published, by acknowledgement outcome",
+            ),
+            &["topic", "outcome"],
+        )
+        .unwrap();
+        registry.register(Box::new(published.clone())).unwrap();
+        NatsMetrics { published }
+    }
+}
+
+#[derive(Clone)]
+pub struct JetStreamPublisher {
+    context: jetstream::Context,
+    metrics: NatsMetrics,
+}
+
+impl JetStreamPublisher {
+    /// Connect, and create the streams for `topics` if they don't exist yet
+    pub async fn connect(
+        url: &str,
+        topics: &[&str],
+        metrics: &NatsMetrics,
+    ) -> Result<Self, String> {
+        let client = async_nats::connect(url)
+            .await
+            .map_err(|e| format!("Failed to connect to NATS at {}: {}", url, e))?;
+        let context = jetstream::new(client);
+        for topic in topics {
+            ensure_stream(&context, topic).await?;
+        }
+        tracing::info!("JetStream publisher for {} ({})", url, topics.join(", "));
+        Ok(JetStreamPublisher {
+            context,
+            metrics: metrics.clone(),
+        })
+    }
+
+    fn count(&self, topic: &str, outcome: &str) {
+        self.metrics
+            .published
+            .with_label_values(&[topic, outcome])
+            .inc();
+    }
+}
+
+impl EventPublisher for JetStreamPublisher {
+    fn transport(&self) -> &'static str {
+        "nats"
+    }
+
+    fn publish<'a>(
+        &'a self,
+        message: OutgoingMessage<'a>,
+    ) -> BoxFuture<'a, Result<(), TransportError>> {
+        Box::pin(async move {
+            let mut headers = HeaderMap::new();
+            for (name, value) in message.headers {
+                headers.insert(*name, *value);
+            }
+            let mut publish = Publish::build()
+                .payload(Bytes::copy_from_slice(message.payload))
+                .headers(headers);
+            if let Some(id) = message.id {
+                publish = publish.message_id(id);
+            }
+
+            let subject = subject(message.topic, message.key);
+            let result = async {
+                self.context
+                    .send_publish(subject.clone(), publish)
+                    .await?
+                    .await
+            }
+            .await;
+            match result {
+                Ok(ack) => {
+                    self.count(
+                        message.topic,
+                        if ack.duplicate { "duplicate" } else { "ok" },
+                    );
+                    Ok(())
+                }
+                Err(e) => {
+                    self.count(message.topic, "error");
+                    tracing::warn!("Failed to publish to {}: {}", subject, e);
+                    Err(TransportError(e.to_string()))
+                }
+            }
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SubscriberConfig {
+    pub url: String,
+    pub topic: String,
+    /// The consumer's name on the server; its position survives restarts
+    pub durable_name: String,
+    /// Unacknowledged for this long, a message is delivered again
+    pub ack_wait: Duration,
+    /// Deliveries before the server stops trying; -1 for no limit
+    pub max_deliver: i64,
+    /// Messages out at once. 1 keeps every key's messages in order, a nak
+    /// included; more lets a redelivered message fall behind later ones
+    pub max_ack_pending: i64,
+}
+
+/// A durable pull consumer over one topic's stream
+pub struct JetStreamSubscriber {
+    messages: pull::Stream,
+    prefix: String,
+}
+
+impl JetStreamSubscriber {
+    pub async fn connect(config: &SubscriberConfig) -> Result<Self, String> {
+        let client = async_nats::connect(&config.url)
+            .await
+            .map_err(|e| format!("Failed to connect to NATS at {}: {}", config.url, e))?;
+        let context = jetstream::new(client);
+        let stream = ensure_stream(&context, &config.topic).await?;
+        let consumer = stream
+            .get_or_create_consumer(
+                &config.durable_name,
+                pull::Config {
+                    durable_name: Some(config.durable_name.clone()),
+                    ack_policy: AckPolicy::Explicit,
+                    ack_wait: config.ack_wait,
+                    max_deliver: config.max_deliver,
+                    max_ack_pending: config.max_ack_pending,
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| format!("Failed to create consumer {}: {}", config.durable_name, e))?;
+        let messages = consumer
+            .messages()
+            .await
+            .map_err(|e| format!("Failed to pull from {}: {}", config.durable_name, e))?;
+        tracing::info!(
+            "JetStream consumer {} on {}",
+            config.durable_name,
+            stream_name(&config.topic)
+        );
+        Ok(JetStreamSubscriber {
+            messages,
+            prefix: format!("{}.", config.topic),
+        })
+    }
+}
+
+impl EventSubscriber for JetStreamSubscriber {
+    fn next(&mut self) -> BoxFuture<'_, Option<Result<Box<dyn ReceivedMessage>, TransportError>>> {
+        Box::pin(async move {
+            let message = match self.messages.next().await? {
+                Ok(message) => message,
+                Err(e) => return Some(Err(TransportError(e.to_string()))),
+            };
+            let key = message
+                .subject
+                .as_str()
+                .strip_prefix(&self.prefix)
+                .unwrap_or_default()
+                .to_string();
+            Some(Ok(
+                Box::new(Received { message, key }) as Box<dyn ReceivedMessage>
+            ))
+        })
+    }
+}
+
+struct Received {
+    message: jetstream::Message,
+    key: String,
+}
+
+impl Received {
+    async fn ack_with(&self, kind: AckKind) -> Result<(), TransportError> {
+        self.message
+            .ack_with(kind)
+            .await
+            .map_err(|e| TransportError(e.to_string()))
+    }
+}
+
+impl ReceivedMessage for Received {
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn payload(&self) -> &[u8] {
+        &self.message.payload
+    }
+
+    /// Nats-Msg-Id
+    fn id(&self) -> Option<&str> {
+        self.message
+            .headers
+            .as_ref()?
+            .get("Nats-Msg-Id")
+            .map(|value| value.as_str())
+    }
+
+    fn reference(&self) -> String {
+        match self.message.info() {
+            Ok(info) => format!("{} #{}", info.stream, info.stream_sequence),
+            Err(_) => self.message.subject.to_string(),
+        }
+    }
+
+    fn deliveries(&self) -> i64 {
+        self.message.info().map(|info| info.delivered).unwrap_or(1)
+    }
+
+    fn ack(&self) -> BoxFuture<'_, Result<(), TransportError>> {
+        Box::pin(self.ack_with(AckKind::Ack))
+    }
+
+    /// A nak with a delay
+    fn retry(&self, delay: Duration) -> BoxFuture<'_, Result<(), TransportError>> {
+        Box::pin(self.ack_with(AckKind::Nak(Some(delay))))
+    }
+
+    /// Term: the server stops redelivering it
+    fn reject(&self) -> BoxFuture<'_, Result<(), TransportError>> {
+        Box::pin(self.ack_with(AckKind::Term))
+    }
+}
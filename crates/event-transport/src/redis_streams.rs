@@ -0,0 +1,435 @@
+use futures::future::BoxFuture;
+use redis::aio::ConnectionManager;
+use redis::Value;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::{EventPublisher, EventSubscriber, OutgoingMessage, ReceivedMessage, TransportError};
+
+// ==============================================================================
+// REDIS STREAMS: Consumer groups, for local development and small deployments
+// ==============================================================================
+//
+// A topic is a stream of the same name; each entry holds the message's key,
+// id and payload, and its headers as h:<name>. Nothing beyond the Redis the
+// stack already runs is needed.
+//
+// Consumers read through a consumer group (XREADGROUP), so each entry goes
+// to one of them and stays in the group's pending entries list (PEL) until
+// acked. Retries are PEL-based:
+//
+//   retry(delay)   the entry stays pending; its idle time is set so that it
+//                  becomes claimable once `delay` has passed
+//   next()         claims entries idle for ACK_WAIT first (XAUTOCLAIM: due
+//                  retries, and entries of a consumer that died), then reads
+//                  new ones. Claiming counts a delivery; past MAX_DELIVER an
+//                  entry moves to <topic>:dead instead
+//   reject()       copies the entry to <topic>:dead and acks it
+//
+// Delays are capped at ACK_WAIT. Redis doesn't deduplicate: a publish
+// retried after a lost reply is stored twice, and the message id (Restate's
+// idempotency key downstream) is what keeps it from being handled twice.
+// Publishers trim each stream to about MAX_LEN entries; keep that well above
+// any backlog, since trimming doesn't spare pending entries.
+
+/// Where rejected entries, and entries past MAX_DELIVER, are kept
+pub fn dead_letter_stream(topic: &str) -> String {
+    format!("{}:dead", topic)
+}
+
+async fn connect(url: &str) -> Result<ConnectionManager, String> {
+    let client =
+        redis::Client::open(url).map_err(|e| format!("Invalid Redis URL {}: {}", url, e))?;
+    client
+        .get_connection_manager()
+        .await
+        .map_err(|e| format!("Failed to connect to Redis at {}: {}", url, e))
+}
+
+fn error(e: redis::RedisError) -> TransportError {
+    TransportError(e.to_string())
+}
+
+#[derive(Clone)]
+pub struct RedisPublisher {
+    conn: ConnectionManager,
+    /// Approximate entries kept per stream; 0 keeps everything
+    max_len: usize,
+}
+
+impl RedisPublisher {
+    pub async fn connect(url: &str, max_len: usize) -> Result<Self, String> {
+        let conn = connect(url).await?;
+        tracing::info!("Redis Streams publisher for {}", url);
+        Ok(RedisPublisher { conn, max_len })
+    }
+}
+
+impl EventPublisher for RedisPublisher {
+    fn transport(&self) -> &'static str {
+        "redis"
+    }
+
+    fn publish<'a>(
+        &'a self,
+        message: OutgoingMessage<'a>,
+    ) -> BoxFuture<'a, Result<(), TransportError>> {
+        Box::pin(async move {
+            let mut command = redis::cmd("XADD");
+            command.arg(message.topic);
+            if self.max_len > 0 {
+                command.arg("MAXLEN").arg("~").arg(self.max_len);
+            }
+            command.arg("*").arg("key").arg(message.key);
+            if let Some(id) = message.id {
+                command.arg("id").arg(id);
+            }
+            command.arg("payload").arg(message.payload);
+            for (name, value) in message.headers {
+                command.arg(format!("h:{}", name)).arg(*value);
+            }
+            let mut conn = self.conn.clone();
+            command
+                .query_async::<_, String>(&mut conn)
+                .await
+                .map(|_| ())
+                .map_err(|e| {
+                    tracing::warn!("Failed to publish to {}: {}", message.topic, e);
+                    error(e)
+                })
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SubscriberConfig {
+    pub url: String,
+    pub topic: String,
+    /// The consumer group; instances sharing it share the work
+    pub group: String,
+    /// This instance's name within the group; must be unique
+    pub consumer: String,
+    /// Pending this long, an entry is claimed and delivered again
+    pub ack_wait: Duration,
+    /// Deliveries before an entry is dead-lettered; -1 for no limit
+    pub max_deliver: i64,
+    /// Entries read at once. 1 keeps every key's entries in order, a retry
+    /// included
+    pub batch: usize,
+}
+
+/// A consumer in a topic's consumer group, created (with the stream) on
+/// connect
+pub struct RedisSubscriber {
+    conn: ConnectionManager,
+    config: SubscriberConfig,
+    buffer: VecDeque<Received>,
+}
+
+impl RedisSubscriber {
+    pub async fn connect(config: &SubscriberConfig) -> Result<Self, String> {
+        let mut conn = connect(&config.url).await?;
+        // From the start of the stream, so nothing published before the
+        // first consumer is missed
+        let created = redis::cmd("XGROUP")
+            .arg("CREATE")
+            .arg(&config.topic)
+            .arg(&config.group)
+            .arg("0")
+            .arg("MKSTREAM")
+            .query_async::<_, ()>(&mut conn)
+            .await;
+        match created {
+            Err(e) if e.code() != Some("BUSYGROUP") => {
+                return Err(format!(
+                    "Failed to create consumer group {}: {}",
+                    config.group, e
+                ))
+            }
+            _ => {}
+        }
+        tracing::info!(
+            "Redis Streams consumer {} in {} on {} (dead letters: {})",
+            config.consumer,
+            config.group,
+            config.topic,
+            dead_letter_stream(&config.topic)
+        );
+        Ok(RedisSubscriber {
+            conn,
+            config: config.clone(),
+            buffer: VecDeque::new(),
+        })
+    }
+
+    fn received(&self, id: String, fields: Fields, deliveries: i64) -> Received {
+        Received {
+            conn: self.conn.clone(),
+            config: self.config.clone(),
+            id,
+            fields,
+            deliveries,
+        }
+    }
+
+    /// Due retries and abandoned entries first, then new ones
+    async fn fill(&mut self) -> Result<(), TransportError> {
+        let mut conn = self.conn.clone();
+        let claimed: Value = redis::cmd("XAUTOCLAIM")
+            .arg(&self.config.topic)
+            .arg(&self.config.group)
+            .arg(&self.config.consumer)
+            .arg(self.config.ack_wait.as_millis() as u64)
+            .arg("0-0")
+            .arg("COUNT")
+            .arg(self.config.batch)
+            .query_async(&mut conn)
+            .await
+            .map_err(error)?;
+        let claimed = match claimed {
+            Value::Bulk(mut parts) if parts.len() >= 2 => entries(parts.swap_remove(1)),
+            _ => Vec::new(),
+        };
+        if !claimed.is_empty() {
+            for (id, fields) in claimed {
+                let deliveries = self.deliveries(&id).await?;
+                let message = self.received(id, fields, deliveries);
+                if self.config.max_deliver > 0 && deliveries > self.config.max_deliver {
+                    tracing::warn!(
+                        "Entry {} delivered {} times, dead-lettering it",
+                        message.id,
+                        deliveries
+                    );
+                    message.dead_letter().await?;
+                } else {
+                    self.buffer.push_back(message);
+                }
+            }
+            return Ok(());
+        }
+
+        // Short, so due retries aren't kept waiting behind the block
+        let read: Value = redis::cmd("XREADGROUP")
+            .arg("GROUP")
+            .arg(&self.config.group)
+            .arg(&self.config.consumer)
+            .arg("COUNT")
+            .arg(self.config.batch)
+            .arg("BLOCK")
+            .arg(1000)
+            .arg("STREAMS")
+            .arg(&self.config.topic)
+            .arg(">")
+            .query_async(&mut conn)
+            .await
+            .map_err(error)?;
+        if let Value::Bulk(streams) = read {
+            for stream in streams {
+                if let Value::Bulk(mut parts) = stream {
+                    if parts.len() == 2 {
+                        for (id, fields) in entries(parts.swap_remove(1)) {
+                            let message = self.received(id, fields, 1);
+                            self.buffer.push_back(message);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The entry's delivery count, from the PEL
+    async fn deliveries(&self, id: &str) -> Result<i64, TransportError> {
+        let mut conn = self.conn.clone();
+        let pending: Value = redis::cmd("XPENDING")
+            .arg(&self.config.topic)
+            .arg(&self.config.group)
+            .arg(id)
+            .arg(id)
+            .arg(1)
+            .query_async(&mut conn)
+            .await
+            .map_err(error)?;
+        // [[id, consumer, idle, deliveries]]
+        Ok(match pending {
+            Value::Bulk(rows) => match rows.first() {
+                Some(Value::Bulk(row)) => row.get(3).and_then(int).unwrap_or(1),
+                _ => 1,
+            },
+            _ => 1,
+        })
+    }
+}
+
+/// An entry's field names and values
+type Fields = Vec<(String, Vec<u8>)>;
+
+/// [[id, [field, value, ...]], ...]; entries deleted while pending come back
+/// with no fields and are skipped
+fn entries(value: Value) -> Vec<(String, Fields)> {
+    let Value::Bulk(entries) = value else {
+        return Vec::new();
+    };
+    entries
+        .into_iter()
+        .filter_map(|entry| {
+            let Value::Bulk(mut entry) = entry else {
+                return None;
+            };
+            if entry.len() != 2 {
+                return None;
+            }
+            let Value::Bulk(values) = entry.pop()? else {
+                return None;
+            };
+            let id = text(&entry.pop()?)?;
+            let fields = values
+                .chunks(2)
+                .filter_map(|pair| match pair {
+                    [name, Value::Data(value)] => Some((text(name)?, value.clone())),
+                    _ => None,
+                })
+                .collect();
+            Some((id, fields))
+        })
+        .collect()
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::Data(bytes) => String::from_utf8(bytes.clone()).ok(),
+        Value::Status(status) => Some(status.clone()),
+        _ => None,
+    }
+}
+
+fn int(value: &Value) -> Option<i64> {
+    match value {
+        Value::Int(n) => Some(*n),
+        _ => text(value)?.parse().ok(),
+    }
+}
+
+impl EventSubscriber for RedisSubscriber {
+    fn next(&mut self) -> BoxFuture<'_, Option<Result<Box<dyn ReceivedMessage>, TransportError>>> {
+        Box::pin(async move {
+            loop {
+                if let Some(message) = self.buffer.pop_front() {
+                    return Some(Ok(Box::new(message) as Box<dyn ReceivedMessage>));
+                }
+                if let Err(e) = self.fill().await {
+                    return Some(Err(e));
+                }
+            }
+        })
+    }
+}
+
+struct Received {
+    conn: ConnectionManager,
+    config: SubscriberConfig,
+    id: String,
+    fields: Fields,
+    deliveries: i64,
+}
+
+impl Received {
+    fn field(&self, name: &str) -> Option<&[u8]> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    async fn xack(&self) -> Result<(), TransportError> {
+        let mut conn = self.conn.clone();
+        redis::cmd("XACK")
+            .arg(&self.config.topic)
+            .arg(&self.config.group)
+            .arg(&self.id)
+            .query_async::<_, i64>(&mut conn)
+            .await
+            .map(|_| ())
+            .map_err(error)
+    }
+
+    /// Claimable by next() once `delay` has passed: the entry's idle time
+    /// is set to ACK_WAIT less the delay. JUSTID leaves the delivery count
+    /// alone; the claim in next() counts the redelivery
+    async fn reschedule(&self, delay: Duration) -> Result<(), TransportError> {
+        let idle = self.config.ack_wait.saturating_sub(delay);
+        let mut conn = self.conn.clone();
+        redis::cmd("XCLAIM")
+            .arg(&self.config.topic)
+            .arg(&self.config.group)
+            .arg(&self.config.consumer)
+            .arg(0)
+            .arg(&self.id)
+            .arg("IDLE")
+            .arg(idle.as_millis() as u64)
+            .arg("JUSTID")
+            .query_async::<_, Value>(&mut conn)
+            .await
+            .map(|_| ())
+            .map_err(error)
+    }
+
+    /// Copied to <topic>:dead with where it came from, then acked
+    async fn dead_letter(&self) -> Result<(), TransportError> {
+        let mut command = redis::cmd("XADD");
+        command
+            .arg(dead_letter_stream(&self.config.topic))
+            .arg("*")
+            .arg("source_id")
+            .arg(&self.id)
+            .arg("deliveries")
+            .arg(self.deliveries);
+        for (name, value) in &self.fields {
+            command.arg(name).arg(value.as_slice());
+        }
+        let mut conn = self.conn.clone();
+        command
+            .query_async::<_, String>(&mut conn)
+            .await
+            .map_err(error)?;
+        self.xack().await
+    }
+}
+
+impl ReceivedMessage for Received {
+    fn key(&self) -> &str {
+        self.field("key")
+            .and_then(|key| std::str::from_utf8(key).ok())
+            .unwrap_or_default()
+    }
+
+    fn payload(&self) -> &[u8] {
+        self.field("payload").unwrap_or_default()
+    }
+
+    fn id(&self) -> Option<&str> {
+        self.field("id").and_then(|id| std::str::from_utf8(id).ok())
+    }
+
+    fn reference(&self) -> String {
+        format!("{} {}", self.config.topic, self.id)
+    }
+
+    fn deliveries(&self) -> i64 {
+        self.deliveries
+    }
+
+    fn ack(&self) -> BoxFuture<'_, Result<(), TransportError>> {
+        Box::pin(self.xack())
+    }
+
+    /// Left pending, and claimed again after `delay` (at most ACK_WAIT)
+    fn retry(&self, delay: Duration) -> BoxFuture<'_, Result<(), TransportError>> {
+        Box::pin(self.reschedule(delay))
+    }
+
+    /// To <topic>:dead
+    fn reject(&self) -> BoxFuture<'_, Result<(), TransportError>> {
+        Box::pin(self.dead_letter())
+    }
+}
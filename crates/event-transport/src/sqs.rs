@@ -0,0 +1,604 @@
+use futures::future::BoxFuture;
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use crate::aws::{sha256_hex, AwsClient};
+use crate::{EventPublisher, EventSubscriber, OutgoingMessage, ReceivedMessage, TransportError};
+
+// ==============================================================================
+// SQS: Queues with a dead-letter queue, and SNS topics in front of them
+// ==============================================================================
+//
+// A topic is a queue named after it, with a dead-letter queue beside it:
+//
+//   webhook-events.fifo       the intake queue
+//   webhook-events-dlq.fifo   where SQS moves a message after
+//                             MAX_RECEIVE_COUNT receives (the redrive
+//                             policy), kept for 14 days
+//
+// Retries are the visibility timeout's: a message that isn't deleted comes
+// back once it expires, and retry() shortens or stretches that to the
+// backoff the consumer asked for. FIFO queues (the default) keep each
+// message group (the key: merchant_id) in order, and hold a group's later
+// messages back while one of its messages is in flight or waiting for a
+// retry, which is what ordering per merchant needs.
+//
+// SNS: a FIFO topic of the same name. Publishing there instead of to the
+// queue lets other queues subscribe too; the intake queue subscribes itself
+// when sns_topic is set (raw delivery, so the body and attributes arrive
+// as published).
+//
+// Key, id and headers travel as message attributes (key, id, and the header
+// names), at most ten in all.
+
+const API_VERSION_SNS: &str = "2010-03-31";
+const DEAD_LETTER_RETENTION_SECS: u32 = 14 * 24 * 3600;
+/// The longest SQS allows, 12 hours
+const MAX_VISIBILITY_SECS: u64 = 43_200;
+
+/// A queue or topic name: letters, digits, - and _, with .fifo for FIFO
+pub fn queue_name(topic: &str, fifo: bool) -> String {
+    let base: String = topic
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .take(75)
+        .collect();
+    if fifo {
+        format!("{}.fifo", base)
+    } else {
+        base
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct QueueConfig {
+    pub topic: String,
+    pub fifo: bool,
+    /// How long a received message stays hidden before it's delivered again
+    pub visibility_timeout: Duration,
+    /// Receives before SQS moves a message to the dead-letter queue
+    pub max_receive_count: u32,
+}
+
+/// The URLs and ARN of a topic's queues
+#[derive(Clone, Debug)]
+pub struct Queues {
+    pub url: String,
+    pub arn: String,
+    pub dead_letter_url: String,
+}
+
+/// Create the queue and its dead-letter queue, or bring the existing ones'
+/// visibility timeout and redrive policy in line with `config`
+pub async fn ensure_queues(
+    client: &AwsClient,
+    config: &QueueConfig,
+) -> Result<Queues, TransportError> {
+    let dead_letter_name = queue_name(&format!("{}-dlq", config.topic), config.fifo);
+    let dead_letter_url = create_queue(client, &dead_letter_name, config.fifo).await?;
+    set_queue_attributes(
+        client,
+        &dead_letter_url,
+        json!({ "MessageRetentionPeriod": DEAD_LETTER_RETENTION_SECS.to_string() }),
+    )
+    .await?;
+    let dead_letter_arn = queue_arn(client, &dead_letter_url).await?;
+
+    let url = create_queue(client, &queue_name(&config.topic, config.fifo), config.fifo).await?;
+    let redrive = json!({
+        "deadLetterTargetArn": dead_letter_arn,
+        "maxReceiveCount": config.max_receive_count,
+    });
+    set_queue_attributes(
+        client,
+        &url,
+        json!({
+            "VisibilityTimeout": config.visibility_timeout.as_secs().to_string(),
+            "RedrivePolicy": redrive.to_string(),
+        }),
+    )
+    .await?;
+    let arn = queue_arn(client, &url).await?;
+
+    Ok(Queues {
+        url,
+        arn,
+        dead_letter_url,
+    })
+}
+
+/// CreateQueue returns the existing queue's URL when the attributes given
+/// match, so only FifoQueue (which can't change) is passed
+async fn create_queue(
+    client: &AwsClient,
+    name: &str,
+    fifo: bool,
+) -> Result<String, TransportError> {
+    let mut request = json!({ "QueueName": name });
+    if fifo {
+        request["Attributes"] = json!({ "FifoQueue": "true" });
+    }
+    let response = client
+        .json("sqs", "AmazonSQS.CreateQueue", &request)
+        .await?;
+    response["QueueUrl"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| TransportError(format!("CreateQueue {}: no QueueUrl", name)))
+}
+
+async fn set_queue_attributes(
+    client: &AwsClient,
+    url: &str,
+    attributes: Value,
+) -> Result<(), TransportError> {
+    client
+        .json(
+            "sqs",
+            "AmazonSQS.SetQueueAttributes",
+            &json!({ "QueueUrl": url, "Attributes": attributes }),
+        )
+        .await
+        .map(|_| ())
+}
+
+async fn queue_arn(client: &AwsClient, url: &str) -> Result<String, TransportError> {
+    let response = client
+        .json(
+            "sqs",
+            "AmazonSQS.GetQueueAttributes",
+            &json!({ "QueueUrl": url, "AttributeNames": ["QueueArn"] }),
+        )
+        .await?;
+    response["Attributes"]["QueueArn"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| TransportError(format!("GetQueueAttributes {}: no QueueArn", url)))
+}
+
+/// CreateTopic is idempotent, so this is also how a topic's ARN is found
+async fn create_topic(
+    client: &AwsClient,
+    topic: &str,
+    fifo: bool,
+) -> Result<String, TransportError> {
+    let name = queue_name(topic, fifo);
+    let mut params = vec![
+        ("Action", "CreateTopic"),
+        ("Version", API_VERSION_SNS),
+        ("Name", name.as_str()),
+    ];
+    if fifo {
+        params.extend([
+            ("Attributes.entry.1.key", "FifoTopic"),
+            ("Attributes.entry.1.value", "true"),
+        ]);
+    }
+    let response = client.query("sns", &params).await?;
+    xml_text(&response, "TopicArn")
+        .ok_or_else(|| TransportError(format!("CreateTopic {}: no TopicArn", name)))
+}
+
+/// The text of the first <tag> element; the Query responses are small and flat
+fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    Some(xml[start..end].trim().to_string())
+}
+
+/// Key, id and headers as SQS message attributes; empty values are left out,
+/// SQS rejects them
+fn message_attributes(message: &OutgoingMessage<'_>) -> Map<String, Value> {
+    let mut attributes = Map::new();
+    let pairs = [("key", message.key), ("id", message.id.unwrap_or_default())]
+        .into_iter()
+        .chain(message.headers.iter().copied());
+    for (name, value) in pairs.filter(|(_, value)| !value.is_empty()).take(10) {
+        attributes.insert(
+            name.to_string(),
+            json!({ "DataType": "String", "StringValue": value }),
+        );
+    }
+    attributes
+}
+
+/// The deduplication id FIFO queues and topics require
+fn deduplication_id(message: &OutgoingMessage<'_>) -> String {
+    match message.id {
+        Some(id) => id.chars().take(128).collect(),
+        None => sha256_hex(message.payload),
+    }
+}
+
+/// Message groups are at most 128 characters, and can't be empty
+fn group_id(key: &str) -> String {
+    if key.is_empty() {
+        "_".to_string()
+    } else {
+        key.chars().take(128).collect()
+    }
+}
+
+#[derive(Clone)]
+pub struct SqsPublisher {
+    client: AwsClient,
+    /// Queue URL per topic
+    queues: HashMap<String, String>,
+    fifo: bool,
+}
+
+impl SqsPublisher {
+    /// Create each topic's queue if it doesn't exist. Its visibility timeout
+    /// and redrive policy are the consumer's to set (SqsSubscriber)
+    pub async fn connect(client: AwsClient, topics: &[&str], fifo: bool) -> Result<Self, String> {
+        let mut queues = HashMap::new();
+        for topic in topics {
+            let url = create_queue(&client, &queue_name(topic, fifo), fifo)
+                .await
+                .map_err(|e| format!("Failed to set up the queue for {}: {}", topic, e))?;
+            queues.insert(topic.to_string(), url);
+        }
+        tracing::info!(
+            "SQS publisher in {} ({})",
+            client.region(),
+            topics.join(", ")
+        );
+        Ok(SqsPublisher {
+            client,
+            queues,
+            fifo,
+        })
+    }
+}
+
+impl EventPublisher for SqsPublisher {
+    fn transport(&self) -> &'static str {
+        "sqs"
+    }
+
+    fn publish<'a>(
+        &'a self,
+        message: OutgoingMessage<'a>,
+    ) -> BoxFuture<'a, Result<(), TransportError>> {
+        Box::pin(async move {
+            let url = self
+                .queues
+                .get(message.topic)
+                .ok_or_else(|| TransportError(format!("No queue for topic {}", message.topic)))?;
+            let mut request = json!({
+                "QueueUrl": url,
+                "MessageBody": String::from_utf8_lossy(message.payload),
+                "MessageAttributes": message_attributes(&message),
+            });
+            if self.fifo {
+                request["MessageGroupId"] = group_id(message.key).into();
+                request["MessageDeduplicationId"] = deduplication_id(&message).into();
+            }
+            self.client
+                .json("sqs", "AmazonSQS.SendMessage", &request)
+                .await
+                .map(|_| ())
+                .inspect_err(|e| tracing::warn!("Failed to send to {}: {}", url, e))
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct SnsPublisher {
+    client: AwsClient,
+    /// Topic ARN per topic
+    topics: HashMap<String, String>,
+    fifo: bool,
+}
+
+impl SnsPublisher {
+    /// Create (or look up) each topic
+    pub async fn connect(client: AwsClient, topics: &[&str], fifo: bool) -> Result<Self, String> {
+        let mut arns = HashMap::new();
+        for topic in topics {
+            let arn = create_topic(&client, topic, fifo)
+                .await
+                .map_err(|e| format!("Failed to set up the SNS topic for {}: {}", topic, e))?;
+            arns.insert(topic.to_string(), arn);
+        }
+        tracing::info!(
+            "SNS publisher in {} ({})",
+            client.region(),
+            topics.join(", ")
+        );
+        Ok(SnsPublisher {
+            client,
+            topics: arns,
+            fifo,
+        })
+    }
+}
+
+impl EventPublisher for SnsPublisher {
+    fn transport(&self) -> &'static str {
+        "sns"
+    }
+
+    fn publish<'a>(
+        &'a self,
+        message: OutgoingMessage<'a>,
+    ) -> BoxFuture<'a, Result<(), TransportError>> {
+        Box::pin(async move {
+            let arn = self
+                .topics
+                .get(message.topic)
+                .ok_or_else(|| TransportError(format!("No SNS topic for {}", message.topic)))?;
+            let body = String::from_utf8_lossy(message.payload);
+            let mut params: Vec<(String, String)> = vec![
+                ("Action".into(), "Publish".into()),
+                ("Version".into(), API_VERSION_SNS.into()),
+                ("TopicArn".into(), arn.clone()),
+                ("Message".into(), body.into_owned()),
+            ];
+            if self.fifo {
+                params.push(("MessageGroupId".into(), group_id(message.key)));
+                params.push(("MessageDeduplicationId".into(), deduplication_id(&message)));
+            }
+            for (n, (name, attribute)) in message_attributes(&message).iter().enumerate() {
+                let prefix = format!("MessageAttributes.entry.{}", n + 1);
+                let value = attribute["StringValue"].as_str().unwrap_or_default();
+                params.push((format!("{}.Name", prefix), name.clone()));
+                params.push((format!("{}.Value.DataType", prefix), "String".into()));
+                params.push((format!("{}.Value.StringValue", prefix), value.to_string()));
+            }
+            let params: Vec<(&str, &str)> = params
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str()))
+                .collect();
+            self.client
+                .query("sns", &params)
+                .await
+                .map(|_| ())
+                .inspect_err(|e| tracing::warn!("Failed to publish to {}: {}", arn, e))
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SubscriberConfig {
+    pub queue: QueueConfig,
+    /// Subscribe the queue to the SNS topic of the same name (created if
+    /// need be), for publishers using TRANSPORT=sns
+    pub sns_topic: bool,
+}
+
+/// Long-polls a topic's queue
+pub struct SqsSubscriber {
+    client: AwsClient,
+    queues: Queues,
+    fifo: bool,
+    buffer: VecDeque<Received>,
+}
+
+impl SqsSubscriber {
+    pub async fn connect(client: AwsClient, config: &SubscriberConfig) -> Result<Self, String> {
+        let queues = ensure_queues(&client, &config.queue).await.map_err(|e| {
+            format!(
+                "Failed to set up the queue for {}: {}",
+                config.queue.topic, e
+            )
+        })?;
+        if config.sns_topic {
+            subscribe(&client, &config.queue, &queues)
+                .await
+                .map_err(|e| format!("Failed to subscribe to the SNS topic: {}", e))?;
+        }
+        tracing::info!(
+            "SQS consumer on {} (dead letters: {})",
+            queues.url,
+            queues.dead_letter_url
+        );
+        Ok(SqsSubscriber {
+            client,
+            queues,
+            fifo: config.queue.fifo,
+            buffer: VecDeque::new(),
+        })
+    }
+
+    async fn receive(&mut self) -> Result<(), TransportError> {
+        let response = self
+            .client
+            .json(
+                "sqs",
+                "AmazonSQS.ReceiveMessage",
+                &json!({
+                    "QueueUrl": self.queues.url,
+                    "MaxNumberOfMessages": 10,
+                    "WaitTimeSeconds": 20,
+                    "AttributeNames": ["ApproximateReceiveCount", "MessageGroupId"],
+                    "MessageSystemAttributeNames": ["ApproximateReceiveCount", "MessageGroupId"],
+                    "MessageAttributeNames": ["All"],
+                }),
+            )
+            .await?;
+        for message in response["Messages"].as_array().into_iter().flatten() {
+            let text = |value: &Value| value.as_str().map(str::to_string);
+            let attribute = |name: &str| text(&message["MessageAttributes"][name]["StringValue"]);
+            let key = attribute("key")
+                .or_else(|| text(&message["Attributes"]["MessageGroupId"]))
+                .unwrap_or_default();
+            self.buffer.push_back(Received {
+                client: self.client.clone(),
+                queue_url: self.queues.url.clone(),
+                dead_letter_url: self.queues.dead_letter_url.clone(),
+                fifo: self.fifo,
+                message_id: text(&message["MessageId"]).unwrap_or_default(),
+                receipt_handle: text(&message["ReceiptHandle"]).unwrap_or_default(),
+                body: text(&message["Body"]).unwrap_or_default(),
+                key,
+                id: attribute("id"),
+                deliveries: message["Attributes"]["ApproximateReceiveCount"]
+                    .as_str()
+                    .and_then(|count| count.parse().ok())
+                    .unwrap_or(1),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Let the topic send to the queue, and subscribe it with raw delivery
+async fn subscribe(
+    client: &AwsClient,
+    config: &QueueConfig,
+    queues: &Queues,
+) -> Result<(), TransportError> {
+    let topic_arn = create_topic(client, &config.topic, config.fifo).await?;
+    let policy = json!({
+        "Version": "2012-10-17",
+        "Statement": [{
+            "Effect": "Allow",
+            "Principal": { "Service": "sns.amazonaws.com" },
+            "Action": "sqs:SendMessage",
+            "Resource": queues.arn,
+            "Condition": { "ArnEquals": { "aws:SourceArn": topic_arn } },
+        }],
+    });
+    set_queue_attributes(client, &queues.url, json!({ "Policy": policy.to_string() })).await?;
+    client
+        .query(
+            "sns",
+            &[
+                ("Action", "Subscribe"),
+                ("Version", API_VERSION_SNS),
+                ("TopicArn", &topic_arn),
+                ("Protocol", "sqs"),
+                ("Endpoint", &queues.arn),
+                ("Attributes.entry.1.key", "RawMessageDelivery"),
+                ("Attributes.entry.1.value", "true"),
+            ],
+        )
+        .await?;
+    tracing::info!("{} subscribed to {}", queues.arn, topic_arn);
+    Ok(())
+}
+
+impl EventSubscriber for SqsSubscriber {
+    fn next(&mut self) -> BoxFuture<'_, Option<Result<Box<dyn ReceivedMessage>, TransportError>>> {
+        Box::pin(async move {
+            loop {
+                if let Some(message) = self.buffer.pop_front() {
+                    return Some(Ok(Box::new(message) as Box<dyn ReceivedMessage>));
+                }
+                if let Err(e) = self.receive().await {
+                    return Some(Err(e));
+                }
+            }
+        })
+    }
+}
+
+struct Received {
+    client: AwsClient,
+    queue_url: String,
+    dead_letter_url: String,
+    fifo: bool,
+    message_id: String,
+    receipt_handle: String,
+    body: String,
+    key: String,
+    id: Option<String>,
+    deliveries: i64,
+}
+
+impl Received {
+    async fn delete(&self) -> Result<(), TransportError> {
+        self.client
+            .json(
+                "sqs",
+                "AmazonSQS.DeleteMessage",
+                &json!({ "QueueUrl": self.queue_url, "ReceiptHandle": self.receipt_handle }),
+            )
+            .await
+            .map(|_| ())
+    }
+
+    async fn change_visibility(&self, delay: Duration) -> Result<(), TransportError> {
+        let seconds = delay.as_secs_f64().ceil() as u64;
+        self.client
+            .json(
+                "sqs",
+                "AmazonSQS.ChangeMessageVisibility",
+                &json!({
+                    "QueueUrl": self.queue_url,
+                    "ReceiptHandle": self.receipt_handle,
+                    "VisibilityTimeout": seconds.min(MAX_VISIBILITY_SECS),
+                }),
+            )
+            .await
+            .map(|_| ())
+    }
+
+    /// What the redrive policy does after MAX_RECEIVE_COUNT, now
+    async fn dead_letter(&self) -> Result<(), TransportError> {
+        let mut attributes = Map::new();
+        for (name, value) in [("key", Some(self.key.as_str())), ("id", self.id.as_deref())] {
+            if let Some(value) = value.filter(|value| !value.is_empty()) {
+                attributes.insert(
+                    name.to_string(),
+                    json!({ "DataType": "String", "StringValue": value }),
+                );
+            }
+        }
+        let mut request = json!({
+            "QueueUrl": self.dead_letter_url,
+            "MessageBody": self.body,
+            "MessageAttributes": attributes,
+        });
+        if self.fifo {
+            request["MessageGroupId"] = group_id(&self.key).into();
+            request["MessageDeduplicationId"] = self.message_id.clone().into();
+        }
+        self.client
+            .json("sqs", "AmazonSQS.SendMessage", &request)
+            .await?;
+        self.delete().await
+    }
+}
+
+impl ReceivedMessage for Received {
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn payload(&self) -> &[u8] {
+        self.body.as_bytes()
+    }
+
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn reference(&self) -> String {
+        self.message_id.clone()
+    }
+
+    fn deliveries(&self) -> i64 {
+        self.deliveries
+    }
+
+    fn ack(&self) -> BoxFuture<'_, Result<(), TransportError>> {
+        Box::pin(self.delete())
+    }
+
+    /// Back on the queue once `delay` has passed
+    fn retry(&self, delay: Duration) -> BoxFuture<'_, Result<(), TransportError>> {
+        Box::pin(self.change_visibility(delay))
+    }
+
+    /// Straight to the dead-letter queue
+    fn reject(&self) -> BoxFuture<'_, Result<(), TransportError>> {
+        Box::pin(self.dead_letter())
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::Instant;
+
+use crate::CheckResult;
+
+// Ready-made checks, each behind the feature for its client library. Register
+// them with a closure so the check owns what it needs:
+//
+//   registry.readiness("database", move || checks::postgres(pool.clone()))
+
+fn elapsed(started: Instant) -> String {
+    format!("{}ms", started.elapsed().as_millis())
+}
+
+/// The database answers SELECT 1
+#[cfg(feature = "postgres")]
+pub async fn postgres(pool: sqlx::PgPool) -> CheckResult {
+    let started = Instant::now();
+    match sqlx::query("SELECT 1").execute(&pool).await {
+        Ok(_) => CheckResult::pass(elapsed(started)),
+        Err(e) => CheckResult::fail(format!("SELECT 1 failed: {}", e)),
+    }
+}
+
+/// Fewer than this fraction of the pool's connections are checked out
+#[cfg(feature = "postgres")]
+pub async fn pool(pool: sqlx::PgPool, max_utilization: f64) -> CheckResult {
+    let max_connections = pool.options().get_max_connections();
+    let in_use = pool.size() as usize - pool.num_idle();
+    let utilization = in_use as f64 / max_connections as f64;
+    let detail = format!("{}/{} connections in use", in_use, max_connections);
+
+    if utilization < max_utilization {
+        CheckResult::pass(detail)
+    } else {
+        CheckResult::fail(detail)
+    }
+}
+
+/// Every table exists; a missing one means init.sql (our migration) hasn't
+/// been applied to this database yet
+#[cfg(feature = "postgres")]
+pub async fn tables(pool: sqlx::PgPool, tables: &'static [&'static str]) -> CheckResult {
+    let missing = sqlx::query_as::<_, (String,)>(
+        "SELECT t FROM UNNEST($1::TEXT[]) AS t WHERE to_regclass(t) IS NULL",
+    )
+    .bind(tables)
+    .fetch_all(&pool)
+    .await;
+
+    match missing {
+        Ok(missing) if missing.is_empty() => CheckResult::ok(),
+        Ok(missing) => {
+            let tables: Vec<String> = missing.into_iter().map(|(t,)| t).collect();
+            CheckResult::fail(format!("missing tables: {}", tables.join(", ")))
+        }
+        Err(e) => CheckResult::fail(format!("schema check failed: {}", e)),
+    }
+}
+
+/// Redis answers PING
+#[cfg(feature = "redis")]
+pub async fn redis(mut connection: redis::aio::ConnectionManager) -> CheckResult {
+    let started = Instant::now();
+    match redis::cmd("PING")
+        .query_async::<_, String>(&mut connection)
+        .await
+    {
+        Ok(_) => CheckResult::pass(elapsed(started)),
+        Err(e) => CheckResult::fail(format!("PING failed: {}", e)),
+    }
+}
+
+/// GET url answers with a 2xx: a downstream service's own /readyz or /health
+#[cfg(feature = "http")]
+pub async fn http(client: reqwest::Client, url: String) -> CheckResult {
+    let started = Instant::now();
+    match client.get(&url).send().await {
+        Ok(response) if response.status().is_success() => CheckResult::pass(elapsed(started)),
+        Ok(response) => CheckResult::fail(format!("{} returned {}", url, response.status())),
+        Err(e) => CheckResult::fail(format!("{}: {}", url, e)),
+    }
+}
+
+/// A TCP connection opens to any of the addresses (host:port): Kafka
+/// brokers, or anything else without a client library here
+pub async fn tcp(addresses: Vec<String>) -> CheckResult {
+    let started = Instant::now();
+    let mut errors = Vec::new();
+    for address in &addresses {
+        match tokio::net::TcpStream::connect(address).await {
+            Ok(_) => return CheckResult::pass(format!("{} in {}", address, elapsed(started))),
+            Err(e) => errors.push(format!("{}: {}", address, e)),
+        }
+    }
+    if errors.is_empty() {
+        CheckResult::fail("no address given")
+    } else {
+        CheckResult::fail(errors.join("; "))
+    }
+}
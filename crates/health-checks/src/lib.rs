@@ -0,0 +1,273 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub mod checks;
+
+// ==============================================================================
+// HEALTH CHECKS: The same three probes, and the same JSON, in every service
+// ==============================================================================
+//
+// A service registers its dependency checks once, at startup, and mounts
+// router() next to its own routes:
+//
+//   /livez    the process answers; runs no check, so an outage of a
+//             dependency doesn't get the pod restarted in a loop
+//   /readyz   every readiness() check passes; optional() checks are run and
+//             reported but never fail it (a cache the service works without)
+//   /startupz the startup() and readiness() checks have all passed once
+//             (a schema that's there, a first connection); after that it is
+//             200 without running anything, so a slow dependency later on
+//             shows up in /readyz, not as a restart
+//
+// Checks run concurrently, each bounded by the registry's timeout (3s by
+// default), and every response lists each one:
+//
+//   {"status": "ready", "checks": {"database": {"ok": true, "detail": "2ms",
+//    "duration_ms": 2}, "redis": {"ok": false, "optional": true, ...}}}
+//
+// checks.rs has ready-made ones for Postgres, Redis, HTTP dependencies and
+// plain TCP (Kafka brokers); anything else (Svix) is a closure returning a
+// CheckResult.
+
+const DEFAULT_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// What one check found
+#[derive(Clone, Debug, Serialize)]
+pub struct CheckResult {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl CheckResult {
+    pub fn pass(detail: impl Into<String>) -> Self {
+        CheckResult {
+            ok: true,
+            detail: Some(detail.into()),
+        }
+    }
+
+    pub fn fail(detail: impl Into<String>) -> Self {
+        CheckResult {
+            ok: false,
+            detail: Some(detail.into()),
+        }
+    }
+
+    /// Passed, with nothing worth saying
+    pub fn ok() -> Self {
+        CheckResult {
+            ok: true,
+            detail: None,
+        }
+    }
+}
+
+type CheckFuture = Pin<Box<dyn Future<Output = CheckResult> + Send>>;
+type CheckFn = Arc<dyn Fn() -> CheckFuture + Send + Sync>;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Probe {
+    Readiness,
+    Optional,
+    Startup,
+}
+
+#[derive(Clone)]
+struct Check {
+    name: String,
+    probe: Probe,
+    run: CheckFn,
+}
+
+/// A check as reported in the JSON
+#[derive(Serialize)]
+pub struct CheckReport {
+    #[serde(flatten)]
+    result: CheckResult,
+    duration_ms: u64,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    optional: bool,
+}
+
+#[derive(Serialize)]
+pub struct HealthResponse {
+    status: &'static str,
+    checks: BTreeMap<String, CheckReport>,
+}
+
+#[derive(Clone)]
+pub struct HealthRegistry {
+    checks: Arc<Vec<Check>>,
+    timeout: Duration,
+    started: Arc<AtomicBool>,
+}
+
+impl Default for HealthRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthRegistry {
+    pub fn new() -> Self {
+        HealthRegistry {
+            checks: Arc::new(Vec::new()),
+            timeout: DEFAULT_CHECK_TIMEOUT,
+            started: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// How long a check may take before it counts as failed
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Must pass for /readyz (and once for /startupz)
+    pub fn readiness<F, Fut>(self, name: &str, check: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = CheckResult> + Send + 'static,
+    {
+        self.register(name, Probe::Readiness, check)
+    }
+
+    /// Reported on /readyz, never fails it
+    pub fn optional<F, Fut>(self, name: &str, check: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = CheckResult> + Send + 'static,
+    {
+        self.register(name, Probe::Optional, check)
+    }
+
+    /// Must pass once for /startupz, and isn't run again after
+    pub fn startup<F, Fut>(self, name: &str, check: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = CheckResult> + Send + 'static,
+    {
+        self.register(name, Probe::Startup, check)
+    }
+
+    fn register<F, Fut>(mut self, name: &str, probe: Probe, check: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = CheckResult> + Send + 'static,
+    {
+        let run: CheckFn = Arc::new(move || Box::pin(check()) as CheckFuture);
+        Arc::make_mut(&mut self.checks).push(Check {
+            name: name.to_string(),
+            probe,
+            run,
+        });
+        self
+    }
+
+    /// Run the checks for /readyz; true when the service should get traffic
+    pub async fn ready(&self) -> (bool, BTreeMap<String, CheckReport>) {
+        self.run(&[Probe::Readiness, Probe::Optional]).await
+    }
+
+    /// Run the checks for /startupz until they have all passed once
+    pub async fn started(&self) -> (bool, BTreeMap<String, CheckReport>) {
+        if self.started.load(Ordering::Relaxed) {
+            return (true, BTreeMap::new());
+        }
+        let (started, reports) = self.run(&[Probe::Startup, Probe::Readiness]).await;
+        if started && !self.started.swap(true, Ordering::Relaxed) {
+            tracing::info!("Startup checks passed");
+        }
+        (started, reports)
+    }
+
+    async fn run(&self, probes: &[Probe]) -> (bool, BTreeMap<String, CheckReport>) {
+        let runs = self
+            .checks
+            .iter()
+            .filter(|check| probes.contains(&check.probe))
+            .map(|check| async move {
+                let started = Instant::now();
+                let result = match tokio::time::timeout(self.timeout, (check.run)()).await {
+                    Ok(result) => result,
+                    Err(_) => CheckResult::fail(format!("timed out after {:?}", self.timeout)),
+                };
+                let report = CheckReport {
+                    result,
+                    duration_ms: started.elapsed().as_millis() as u64,
+                    optional: check.probe == Probe::Optional,
+                };
+                (check.name.clone(), report)
+            });
+        let reports: BTreeMap<_, _> = futures::future::join_all(runs).await.into_iter().collect();
+
+        let passed = reports
+            .values()
+            .all(|report| report.optional || report.result.ok);
+        for (name, report) in &reports {
+            if !report.result.ok {
+                tracing::warn!(
+                    "Health check {} failed: {}",
+                    name,
+                    report.result.detail.as_deref().unwrap_or("")
+                );
+            }
+        }
+        (passed, reports)
+    }
+}
+
+pub async fn livez() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "alive",
+        checks: BTreeMap::new(),
+    })
+}
+
+pub async fn readyz(State(registry): State<HealthRegistry>) -> (StatusCode, Json<HealthResponse>) {
+    let (ready, checks) = registry.ready().await;
+    respond(ready, if ready { "ready" } else { "not_ready" }, checks)
+}
+
+pub async fn startupz(
+    State(registry): State<HealthRegistry>,
+) -> (StatusCode, Json<HealthResponse>) {
+    let (started, checks) = registry.started().await;
+    respond(
+        started,
+        if started { "started" } else { "starting" },
+        checks,
+    )
+}
+
+fn respond(
+    ok: bool,
+    status: &'static str,
+    checks: BTreeMap<String, CheckReport>,
+) -> (StatusCode, Json<HealthResponse>) {
+    let code = if ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, Json(HealthResponse { status, checks }))
+}
+
+/// /livez, /readyz and /startupz, to merge into the service's router
+pub fn router<S>(registry: HealthRegistry) -> Router<S> {
+    Router::new()
+        .route("/livez", get(livez))
+        .route("/readyz", get(readyz))
+        .route("/startupz", get(startupz))
+        .with_state(registry)
+}
@@ -0,0 +1,329 @@
+use ipnet::IpNet;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::Url;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+// ==============================================================================
+// EGRESS POLICY: Where a client for merchant-supplied URLs may connect
+// ==============================================================================
+//
+// Merchants choose the URLs their webhooks go to; without a policy, one
+// pointing at 127.0.0.1, the cloud metadata service or a Postgres on the
+// private network would have us POST there. A client built with one
+// (HttpClientConfig::egress) only reaches:
+//
+//   schemes     http and https
+//   ports       ALLOWED_PORTS by default: 80, 443, and 1024 and above
+//   addresses   public ones: none in BLOCKED_NETWORKS (loopback, private,
+//               link-local with the metadata service, CGNAT, multicast,
+//               reserved), unless inside an allowed network; IPv6
+//               addresses that carry an IPv4 one (::ffff: mapped, NAT64
+//               64:ff9b::/96, 6to4 2002::/16) are checked as that too
+//
+// URLs are checked before each request, and host names every time they're
+// resolved (the client's DNS resolver), so a name that moves to a blocked
+// address after the endpoint was registered stops working as soon as it
+// does. Any blocked address in an answer fails the whole lookup. Redirects
+// aren't followed: a 3xx is the endpoint's answer.
+//
+// Behind a proxy (HttpClientConfig::proxy) the proxy's own host is exempt,
+// and the proxy resolves the destination: it needs a policy of its own.
+
+/// Ports merchant URLs may use unless configured otherwise
+pub const ALLOWED_PORTS: &str = "80,443,1024-65535";
+
+const ALLOWED_SCHEMES: &[&str] = &["http", "https"];
+
+/// Never public, whatever DNS says
+const BLOCKED_NETWORKS: &[&str] = &[
+    "0.0.0.0/8",       // "this network"
+    "10.0.0.0/8",      // private
+    "100.64.0.0/10",   // carrier-grade NAT
+    "127.0.0.0/8",     // loopback
+    "169.254.0.0/16",  // link-local, cloud metadata (169.254.169.254)
+    "172.16.0.0/12",   // private
+    "192.0.0.0/24",    // IETF protocol assignments
+    "192.0.2.0/24",    // documentation
+    "192.168.0.0/16",  // private
+    "198.18.0.0/15",   // benchmarking
+    "198.51.100.0/24", // documentation
+    "203.0.113.0/24",  // documentation
+    "224.0.0.0/4",     // multicast
+    "240.0.0.0/4",     // reserved, broadcast
+    "::/128",          // unspecified
+    "::1/128",         // loopback
+    "64:ff9b:1::/48",  // local-use NAT64
+    "100::/64",        // discard
+    "2001:db8::/32",   // documentation
+    "fc00::/7",        // unique local, AWS metadata (fd00:ec2::254)
+    "fe80::/10",       // link-local
+    "ff00::/8",        // multicast
+];
+
+#[derive(Debug, Clone)]
+pub struct EgressPolicy {
+    blocked: Vec<IpNet>,
+    /// Exceptions to `blocked`, e.g. a local network in development
+    allowed: Vec<IpNet>,
+    ports: Vec<RangeInclusive<u16>>,
+    /// Resolved without checks: the client's proxy
+    exempt_hosts: HashSet<String>,
+}
+
+impl EgressPolicy {
+    /// `allowed_networks`: comma-separated CIDRs let through anyway (empty
+    /// for none); `allowed_ports`: comma-separated ports and ranges, as in
+    /// ALLOWED_PORTS
+    pub fn parse(allowed_networks: &str, allowed_ports: &str) -> Result<Self, String> {
+        let allowed = split(allowed_networks)
+            .map(|network| {
+                network
+                    .parse()
+                    .map_err(|_| format!("invalid network {:?}", network))
+            })
+            .collect::<Result<_, _>>()?;
+        let ports = split(allowed_ports)
+            .map(|ports| {
+                let (first, last) = ports.split_once('-').unwrap_or((ports, ports));
+                match (first.trim().parse::<u16>(), last.trim().parse::<u16>()) {
+                    (Ok(first), Ok(last)) if first <= last => Ok(first..=last),
+                    _ => Err(format!("invalid port or range {:?}", ports)),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if ports.is_empty() {
+            return Err("no ports allowed".to_string());
+        }
+        Ok(EgressPolicy {
+            blocked: BLOCKED_NETWORKS
+                .iter()
+                .map(|network| network.parse().expect("valid blocked network"))
+                .collect(),
+            allowed,
+            ports,
+            exempt_hosts: HashSet::new(),
+        })
+    }
+
+    /// The URL parsed, if its scheme, port and (when it's an address) host
+    /// are allowed; host names are checked when they're resolved
+    pub fn check_url(&self, url: &str) -> Result<Url, String> {
+        let parsed = Url::parse(url).map_err(|e| format!("invalid URL {}: {}", url, e))?;
+        self.check_request(&parsed)?;
+        Ok(parsed)
+    }
+
+    /// check_url, and every address the host resolves to now
+    pub async fn check_destination(&self, url: &str) -> Result<(), String> {
+        let parsed = self.check_url(url)?;
+        if let Some(url::Host::Domain(host)) = parsed.host() {
+            let addrs = lookup(host).await?;
+            self.check_addrs(host, &addrs)?;
+        }
+        Ok(())
+    }
+
+    pub fn check_ip(&self, ip: IpAddr) -> Result<(), String> {
+        // ::ffff:10.0.0.1 is 10.0.0.1
+        let ip = ip.to_canonical();
+        let blocked = |ip: IpAddr| {
+            let within = |networks: &[IpNet]| networks.iter().any(|network| network.contains(&ip));
+            within(&self.blocked) && !within(&self.allowed)
+        };
+        if blocked(ip) {
+            return Err(format!("{} isn't a public address", ip));
+        }
+        if let Some(embedded) = embedded_ipv4(ip).filter(|&embedded| blocked(embedded.into())) {
+            return Err(format!(
+                "{} reaches {}, which isn't a public address",
+                ip, embedded
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn exempt(&mut self, host: &str) {
+        self.exempt_hosts.insert(host.to_ascii_lowercase());
+    }
+
+    pub(crate) fn check_request(&self, url: &Url) -> Result<(), String> {
+        if !ALLOWED_SCHEMES.contains(&url.scheme()) {
+            return Err(format!("scheme {} isn't allowed", url.scheme()));
+        }
+        let port = url.port_or_known_default().unwrap_or_default();
+        if !self.ports.iter().any(|ports| ports.contains(&port)) {
+            return Err(format!("port {} isn't allowed", port));
+        }
+        match url.host() {
+            Some(url::Host::Ipv4(ip)) => self.check_ip(ip.into()),
+            Some(url::Host::Ipv6(ip)) => self.check_ip(ip.into()),
+            Some(url::Host::Domain(_)) => Ok(()),
+            None => Err("URL has no host".to_string()),
+        }
+    }
+
+    fn check_addrs(&self, host: &str, addrs: &[SocketAddr]) -> Result<(), String> {
+        if self.exempt_hosts.contains(&host.to_ascii_lowercase()) {
+            return Ok(());
+        }
+        for addr in addrs {
+            self.check_ip(addr.ip())
+                .map_err(|e| format!("{} resolves to a blocked address: {}", host, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// The IPv4 address an IPv6 one leads to: NAT64's well-known prefix
+/// translates to the last 32 bits, 6to4 tunnels to the 32 after the prefix
+fn embedded_ipv4(ip: IpAddr) -> Option<Ipv4Addr> {
+    let IpAddr::V6(ip) = ip else {
+        return None;
+    };
+    let octets = ip.octets();
+    match ip.segments() {
+        [0x64, 0xff9b, 0, 0, 0, 0, _, _] => Some(Ipv4Addr::new(
+            octets[12], octets[13], octets[14], octets[15],
+        )),
+        [0x2002, ..] => Some(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5])),
+        _ => None,
+    }
+}
+
+async fn lookup(host: &str) -> Result<Vec<SocketAddr>, String> {
+    Ok(tokio::net::lookup_host((host, 0))
+        .await
+        .map_err(|e| format!("{} doesn't resolve: {}", host, e))?
+        .collect())
+}
+
+fn split(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+/// The client's DNS resolver when it has a policy
+pub(crate) struct GuardedResolver(pub Arc<EgressPolicy>);
+
+impl Resolve for GuardedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy = self.0.clone();
+        Box::pin(async move {
+            let addrs = lookup(name.as_str()).await?;
+            // reqwest reports this as a plain connect error, so say why here
+            policy
+                .check_addrs(name.as_str(), &addrs)
+                .inspect_err(|e| tracing::warn!("Egress policy: {}", e))?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allowed_networks: &str) -> EgressPolicy {
+        EgressPolicy::parse(allowed_networks, ALLOWED_PORTS).unwrap()
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_accepts_networks_ports_and_ranges() {
+        let policy = EgressPolicy::parse(" 10.0.0.0/8, fd00::/8 ", "443, 8000-8100").unwrap();
+        assert_eq!(policy.allowed.len(), 2);
+        assert_eq!(policy.ports, vec![443..=443, 8000..=8100]);
+        assert!(EgressPolicy::parse("", ALLOWED_PORTS).is_ok());
+    }
+
+    #[test]
+    fn parse_rejects_invalid_lists() {
+        assert!(EgressPolicy::parse("10.0.0.0", ALLOWED_PORTS).is_err());
+        assert!(EgressPolicy::parse("not-a-network", ALLOWED_PORTS).is_err());
+        assert!(EgressPolicy::parse("", "").is_err());
+        assert!(EgressPolicy::parse("", "http").is_err());
+        assert!(EgressPolicy::parse("", "70000").is_err());
+        assert!(EgressPolicy::parse("", "9000-8000").is_err());
+    }
+
+    #[test]
+    fn check_ip_blocks_non_public_addresses() {
+        let policy = policy("");
+        for blocked in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "224.0.0.1",
+            "::1",
+            "::",
+            "fd00:ec2::254",
+            "fe80::1",
+        ] {
+            assert!(policy.check_ip(ip(blocked)).is_err(), "{}", blocked);
+        }
+        for public in ["93.184.216.34", "8.8.8.8", "2606:4700::1111"] {
+            assert!(policy.check_ip(ip(public)).is_ok(), "{}", public);
+        }
+    }
+
+    #[test]
+    fn check_ip_sees_through_mapped_addresses() {
+        let policy = policy("");
+        assert!(policy.check_ip(ip("::ffff:10.0.0.1")).is_err());
+        assert!(policy.check_ip(ip("::ffff:127.0.0.1")).is_err());
+        assert!(policy.check_ip(ip("::ffff:8.8.8.8")).is_ok());
+    }
+
+    #[test]
+    fn check_ip_sees_through_nat64_and_6to4() {
+        let policy = policy("");
+        // 10.0.0.1 and 169.254.169.254
+        assert!(policy.check_ip(ip("64:ff9b::a00:1")).is_err());
+        assert!(policy.check_ip(ip("64:ff9b::a9fe:a9fe")).is_err());
+        assert!(policy.check_ip(ip("2002:a00:1::1")).is_err());
+        assert!(policy.check_ip(ip("2002:7f00:1::")).is_err());
+        // 8.8.8.8
+        assert!(policy.check_ip(ip("64:ff9b::808:808")).is_ok());
+        assert!(policy.check_ip(ip("2002:808:808::1")).is_ok());
+    }
+
+    #[test]
+    fn allowed_networks_are_exceptions() {
+        let policy = policy("10.0.0.0/8,::1/128");
+        assert!(policy.check_ip(ip("10.1.2.3")).is_ok());
+        assert!(policy.check_ip(ip("::ffff:10.1.2.3")).is_ok());
+        assert!(policy.check_ip(ip("64:ff9b::a00:1")).is_ok());
+        assert!(policy.check_ip(ip("::1")).is_ok());
+        assert!(policy.check_ip(ip("192.168.1.1")).is_err());
+        assert!(policy.check_ip(ip("127.0.0.1")).is_err());
+    }
+
+    #[test]
+    fn check_url_applies_ports_and_schemes() {
+        let policy = policy("");
+        assert!(policy.check_url("https://example.com/hook").is_ok());
+        assert!(policy.check_url("http://example.com/hook").is_ok());
+        assert!(policy.check_url("https://example.com:8443/hook").is_ok());
+        assert!(policy.check_url("https://example.com:22/hook").is_err());
+        assert!(policy.check_url("ftp://example.com/hook").is_err());
+        assert!(policy.check_url("http://127.0.0.1:8080/hook").is_err());
+        assert!(policy.check_url("http://[64:ff9b::a00:1]/hook").is_err());
+
+        let ranged = EgressPolicy::parse("", "443,9000-9001").unwrap();
+        assert!(ranged.check_url("https://example.com/hook").is_ok());
+        assert!(ranged.check_url("http://example.com/hook").is_err());
+        assert!(ranged.check_url("http://example.com:9001/hook").is_ok());
+        assert!(ranged.check_url("http://example.com:9002/hook").is_err());
+    }
+}
@@ -0,0 +1,563 @@
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry};
+use rand::Rng;
+use reqwest::{Method, Request, RequestBuilder, Response, StatusCode};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+mod egress;
+
+pub use egress::{EgressPolicy, ALLOWED_PORTS};
+
+// ==============================================================================
+// HTTP CLIENT: One outbound client for every service
+// ==============================================================================
+//
+// Wraps reqwest::Client with what each call site used to do by hand, or not
+// at all:
+//
+//   pooling          idle connections kept per host, and for how long
+//   timeouts         connect and whole-request
+//   retries          idempotent requests only (GET, HEAD, PUT, DELETE,
+//                    OPTIONS): connection errors, timeouts, 429, 502, 503
+//                    and 504 are retried up to max_retries times, with full
+//                    jitter on exponential backoff. POSTs are never retried
+//                    here; their callers own that (Restate, retry.rs)
+//   circuit breaking per destination (scheme://host:port): after
+//                    breaker_threshold consecutive failures (no response, or
+//                    a 5xx) calls fail fast for breaker_open_for, then one
+//                    trial request decides whether it closes again. A trial
+//                    whose caller gives up on it (the future is dropped)
+//                    hands the slot to the next request
+//   proxying         every request through one HTTP(S) or SOCKS5 proxy, so
+//                    the destination sees the proxy's address; the breaker
+//                    and metrics still key on the destination itself
+//   egress policy    for clients that reach merchant-supplied URLs: only
+//                    public addresses on allowed schemes and ports, checked
+//                    on every request and DNS lookup (egress.rs)
+//   client certs     client_identity() turns a PEM certificate and key into
+//                    the identity presented for mutual TLS; pass it in
+//                    through with_builder
+//   metrics          per client and destination, in the service's registry:
+//
+//     http_client_requests_total{client, destination, outcome}
+//     http_client_request_duration_seconds{client, destination}
+//     http_client_circuit_open{client, destination}          1 while open
+//
+// Every attempt is counted, retries included. Responses come back as they
+// are, whatever the status: callers keep deciding what a 4xx means to them.
+
+#[derive(Clone, Debug)]
+pub struct HttpClientConfig {
+    /// The `client` label: which caller inside the service
+    pub name: String,
+    pub pool_max_idle_per_host: usize,
+    pub pool_idle_timeout: Duration,
+    pub connect_timeout: Duration,
+    pub timeout: Duration,
+    /// Further attempts for idempotent requests; 0 disables retries
+    pub max_retries: u32,
+    /// First retry waits up to this long, doubled for every further one
+    pub retry_backoff: Duration,
+    /// Consecutive failures that open a destination's circuit; 0 disables
+    pub breaker_threshold: u32,
+    pub breaker_open_for: Duration,
+    /// http://, https://, socks5:// or socks5h:// URL, credentials inline
+    pub proxy: Option<String>,
+    /// Where requests may go; None lets them go anywhere
+    pub egress: Option<EgressPolicy>,
+}
+
+impl HttpClientConfig {
+    pub fn new(name: &str) -> Self {
+        HttpClientConfig {
+            name: name.to_string(),
+            pool_max_idle_per_host: 32,
+            pool_idle_timeout: Duration::from_secs(90),
+            connect_timeout: Duration::from_secs(2),
+            timeout: Duration::from_secs(10),
+            max_retries: 2,
+            retry_backoff: Duration::from_millis(100),
+            breaker_threshold: 5,
+            breaker_open_for: Duration::from_secs(30),
+            proxy: None,
+            egress: None,
+        }
+    }
+}
+
+/// Registered once per service, shared by all of its clients
+#[derive(Clone)]
+pub struct HttpMetrics {
+    requests: IntCounterVec,
+    duration: HistogramVec,
+    circuit_open: IntGaugeVec,
+}
+
+impl HttpMetrics {
+    pub fn new(registry: &Registry) -> Self {
+        let requests = IntCounterVec::new(
+            Opts::new(
+                "http_client_requests_total",
+                "Outbound HTTP attempts, by outcome (a status class, error, circuit_open or blocked)",
+            ),
+            &["client", "destination", "outcome"],
+        )
+        .unwrap();
+        let duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_client_request_duration_seconds",
+                "Outbound HTTP attempt latency",
+            )
+            .buckets(vec![
+                0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+            ]),
+            &["client", "destination"],
+        )
+        .unwrap();
+        let circuit_open = IntGaugeVec::new(
+            Opts::new(
+                "http_client_circuit_open",
+                "1 while calls to the destination fail fast",
+            ),
+            &["client", "destination"],
+        )
+        .unwrap();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(duration.clone())).unwrap();
+        registry.register(Box::new(circuit_open.clone())).unwrap();
+        HttpMetrics {
+            requests,
+            duration,
+            circuit_open,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum HttpError {
+    /// The destination's circuit is open; nothing was sent
+    CircuitOpen {
+        destination: String,
+        retry_in: Duration,
+    },
+    /// No response: connect failure, timeout, or the request couldn't be built
+    Request(reqwest::Error),
+    /// The egress policy doesn't allow the URL; nothing was sent
+    Blocked(String),
+}
+
+impl HttpError {
+    /// Worth trying again later, as opposed to a request that can never work
+    pub fn is_transient(&self) -> bool {
+        match self {
+            HttpError::CircuitOpen { .. } => true,
+            HttpError::Request(e) => !e.is_builder(),
+            HttpError::Blocked(_) => false,
+        }
+    }
+
+    /// Sent, and no response within the timeout
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, HttpError::Request(e) if e.is_timeout())
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::CircuitOpen {
+                destination,
+                retry_in,
+            } => write!(
+                f,
+                "circuit open for {}, next try in {:.1}s",
+                destination,
+                retry_in.as_secs_f64()
+            ),
+            HttpError::Request(e) => write!(f, "{}", e),
+            HttpError::Blocked(reason) => write!(f, "blocked by egress policy: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+#[derive(Default)]
+struct Breaker {
+    failures: u32,
+    open_until: Option<Instant>,
+    /// Half-open: the one request let through to test the destination
+    trial_in_flight: bool,
+}
+
+/// For mutual TLS: a PEM certificate (chain) and its PKCS#8 PEM private key,
+/// checked to belong together
+pub fn client_identity(
+    certificate_pem: &str,
+    private_key_pem: &str,
+) -> Result<reqwest::Identity, String> {
+    let identity =
+        reqwest::Identity::from_pkcs8_pem(certificate_pem.as_bytes(), private_key_pem.as_bytes())
+            .map_err(|e| format!("Invalid client certificate or key: {}", e))?;
+    // The key is only matched against the certificate when a TLS connector
+    // is built with them
+    reqwest::Client::builder()
+        .identity(identity.clone())
+        .build()
+        .map_err(|e| format!("Client key doesn't match the certificate: {}", e))?;
+    Ok(identity)
+}
+
+#[derive(Clone)]
+pub struct HttpClient {
+    http: reqwest::Client,
+    config: Arc<HttpClientConfig>,
+    breakers: Arc<Mutex<HashMap<String, Breaker>>>,
+    metrics: HttpMetrics,
+    egress: Option<Arc<EgressPolicy>>,
+}
+
+impl HttpClient {
+    pub fn new(config: HttpClientConfig, metrics: &HttpMetrics) -> Result<Self, String> {
+        Self::with_builder(config, reqwest::Client::builder(), metrics)
+    }
+
+    /// For settings this crate doesn't cover (compression, TLS identity);
+    /// the pool and timeouts from `config` are applied on top
+    pub fn with_builder(
+        config: HttpClientConfig,
+        builder: reqwest::ClientBuilder,
+        metrics: &HttpMetrics,
+    ) -> Result<Self, String> {
+        let builder = match &config.proxy {
+            Some(proxy) => builder
+                .proxy(reqwest::Proxy::all(proxy).map_err(|e| {
+                    format!("Invalid proxy for HTTP client {}: {}", config.name, e)
+                })?),
+            None => builder,
+        };
+        let egress = config.egress.clone().map(|mut policy| {
+            if let Some(host) = config
+                .proxy
+                .as_deref()
+                .and_then(|proxy| reqwest::Url::parse(proxy).ok())
+                .and_then(|proxy| proxy.host_str().map(str::to_string))
+            {
+                policy.exempt(&host);
+            }
+            Arc::new(policy)
+        });
+        let builder = match &egress {
+            Some(policy) => builder
+                .dns_resolver(Arc::new(egress::GuardedResolver(policy.clone())))
+                .redirect(reqwest::redirect::Policy::none()),
+            None => builder,
+        };
+        let http = builder
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client {}: {}", config.name, e))?;
+        Ok(HttpClient {
+            http,
+            config: Arc::new(config),
+            breakers: Arc::new(Mutex::new(HashMap::new())),
+            metrics: metrics.clone(),
+            egress,
+        })
+    }
+
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.http.get(url)
+    }
+
+    pub fn post(&self, url: &str) -> RequestBuilder {
+        self.http.post(url)
+    }
+
+    pub fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        self.http.request(method, url)
+    }
+
+    /// Send a request built from get/post/request, with retries (when
+    /// idempotent) and the destination's circuit breaker
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, HttpError> {
+        let request = request.build().map_err(HttpError::Request)?;
+        let destination = destination(&request);
+        if let Some(policy) = &self.egress {
+            if let Err(reason) = policy.check_request(request.url()) {
+                self.count(&destination, "blocked");
+                return Err(HttpError::Blocked(reason));
+            }
+        }
+        let idempotent = matches!(
+            *request.method(),
+            Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS
+        );
+
+        let mut attempt = 0;
+        let mut pending = Some(request);
+        loop {
+            let request = pending.take().expect("request for this attempt");
+            // Kept back before sending, for a retry; bodies that stream can't be
+            let retry_copy = if idempotent && attempt < self.config.max_retries {
+                request.try_clone()
+            } else {
+                None
+            };
+
+            let result = self.attempt(&destination, request).await;
+            let retryable = match &result {
+                Ok(response) => matches!(
+                    response.status(),
+                    StatusCode::TOO_MANY_REQUESTS
+                        | StatusCode::BAD_GATEWAY
+                        | StatusCode::SERVICE_UNAVAILABLE
+                        | StatusCode::GATEWAY_TIMEOUT
+                ),
+                Err(HttpError::Request(e)) => !e.is_builder(),
+                Err(HttpError::CircuitOpen { .. } | HttpError::Blocked(_)) => false,
+            };
+            match retry_copy {
+                Some(copy) if retryable => {
+                    let delay = self.backoff(attempt);
+                    tracing::debug!(
+                        "{} request to {} failed, retry {} in {:?}",
+                        self.config.name,
+                        destination,
+                        attempt + 1,
+                        delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                    pending = Some(copy);
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    async fn attempt(&self, destination: &str, request: Request) -> Result<Response, HttpError> {
+        let labels = [self.config.name.as_str(), destination];
+        let admission = match self.admit(destination) {
+            Ok(admission) => admission,
+            Err(retry_in) => {
+                self.count(destination, "circuit_open");
+                return Err(HttpError::CircuitOpen {
+                    destination: destination.to_string(),
+                    retry_in,
+                });
+            }
+        };
+
+        let started = Instant::now();
+        let result = self.http.execute(request).await;
+        self.metrics
+            .duration
+            .with_label_values(&labels)
+            .observe(started.elapsed().as_secs_f64());
+
+        match result {
+            Ok(response) => {
+                let status = response.status();
+                self.count(destination, status_class(status));
+                admission.settle(!status.is_server_error());
+                Ok(response)
+            }
+            Err(e) => {
+                self.count(
+                    destination,
+                    if e.is_timeout() { "timeout" } else { "error" },
+                );
+                admission.settle(false);
+                Err(HttpError::Request(e))
+            }
+        }
+    }
+
+    /// Whether the destination's circuit lets a request through now
+    fn admit<'a>(&'a self, destination: &'a str) -> Result<Admission<'a>, Duration> {
+        let admission = |trial| Admission {
+            client: self,
+            destination,
+            trial,
+            settled: false,
+        };
+        if self.config.breaker_threshold == 0 {
+            return Ok(admission(false));
+        }
+        let mut breakers = self.breakers.lock().unwrap();
+        let breaker = breakers.entry(destination.to_string()).or_default();
+        match breaker.open_until {
+            None => Ok(admission(false)),
+            Some(until) if Instant::now() < until => Err(until - Instant::now()),
+            // Half-open: one trial at a time
+            Some(_) if breaker.trial_in_flight => Err(Duration::ZERO),
+            Some(_) => {
+                breaker.trial_in_flight = true;
+                Ok(admission(true))
+            }
+        }
+    }
+
+    /// A trial that ended without an answer: the circuit stays open as it
+    /// was, and the next request becomes the trial
+    fn abandon_trial(&self, destination: &str) {
+        if let Some(breaker) = self.breakers.lock().unwrap().get_mut(destination) {
+            breaker.trial_in_flight = false;
+        }
+    }
+
+    fn settle(&self, destination: &str, ok: bool) {
+        if self.config.breaker_threshold == 0 {
+            return;
+        }
+        let mut breakers = self.breakers.lock().unwrap();
+        let breaker = breakers.entry(destination.to_string()).or_default();
+        let was_open = breaker.open_until.is_some();
+        breaker.trial_in_flight = false;
+        if ok {
+            breaker.failures = 0;
+            breaker.open_until = None;
+        } else {
+            breaker.failures += 1;
+            // A failed trial opens it again straight away
+            if was_open || breaker.failures >= self.config.breaker_threshold {
+                breaker.open_until = Some(Instant::now() + self.config.breaker_open_for);
+            }
+        }
+
+        let open = breaker.open_until.is_some();
+        if open != was_open {
+            if open {
+                tracing::warn!(
+                    "{} circuit for {} opened after {} failures",
+                    self.config.name,
+                    destination,
+                    breaker.failures
+                );
+            } else {
+                tracing::info!("{} circuit for {} closed", self.config.name, destination);
+            }
+            self.metrics
+                .circuit_open
+                .with_label_values(&[self.config.name.as_str(), destination])
+                .set(open as i64);
+        }
+    }
+
+    fn count(&self, destination: &str, outcome: &str) {
+        self.metrics
+            .requests
+            .with_label_values(&[self.config.name.as_str(), destination, outcome])
+            .inc();
+    }
+
+    /// Full jitter: anywhere between 0 and the exponential step
+    fn backoff(&self, attempt: u32) -> Duration {
+        let step = self
+            .config
+            .retry_backoff
+            .saturating_mul(2u32.saturating_pow(attempt));
+        step.mul_f64(rand::thread_rng().gen::<f64>())
+    }
+}
+
+/// A request let through by admit(), to be settled with its outcome
+struct Admission<'a> {
+    client: &'a HttpClient,
+    destination: &'a str,
+    /// The half-open trial, which must not stay in flight forever
+    trial: bool,
+    settled: bool,
+}
+
+impl Admission<'_> {
+    fn settle(mut self, ok: bool) {
+        self.settled = true;
+        self.client.settle(self.destination, ok);
+    }
+}
+
+impl Drop for Admission<'_> {
+    /// Dropped unsettled when the caller's future is cancelled mid-request
+    fn drop(&mut self) {
+        if self.trial && !self.settled {
+            self.client.abandon_trial(self.destination);
+        }
+    }
+}
+
+/// scheme://host:port, so paths and query strings don't multiply the labels
+fn destination(request: &Request) -> String {
+    let url = request.url();
+    format!(
+        "{}://{}:{}",
+        url.scheme(),
+        url.host_str().unwrap_or("unknown"),
+        url.port_or_known_default().unwrap_or_default()
+    )
+}
+
+fn status_class(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        _ => "5xx",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESTINATION: &str = "http://merchant.example:80";
+
+    /// Opens on the first failure and goes half-open straight away
+    fn client() -> HttpClient {
+        let mut config = HttpClientConfig::new("test");
+        config.breaker_threshold = 1;
+        config.breaker_open_for = Duration::ZERO;
+        HttpClient::new(config, &HttpMetrics::new(&Registry::new())).unwrap()
+    }
+
+    #[test]
+    fn one_trial_at_a_time_when_half_open() {
+        let client = client();
+        client.admit(DESTINATION).unwrap().settle(false);
+
+        let trial = client.admit(DESTINATION).unwrap();
+        assert_eq!(client.admit(DESTINATION).err(), Some(Duration::ZERO));
+        trial.settle(true);
+        client.admit(DESTINATION).unwrap().settle(true);
+    }
+
+    #[test]
+    fn dropped_trial_hands_the_slot_on() {
+        let client = client();
+        client.admit(DESTINATION).unwrap().settle(false);
+
+        drop(client.admit(DESTINATION).unwrap());
+        let trial = client.admit(DESTINATION).expect("slot freed by the dropped trial");
+        assert!(client.admit(DESTINATION).is_err());
+        trial.settle(true);
+    }
+
+    #[test]
+    fn dropped_closed_admission_leaves_the_trial_alone() {
+        let client = client();
+        let closed = client.admit(DESTINATION).unwrap();
+        client.admit(DESTINATION).unwrap().settle(false);
+
+        let trial = client.admit(DESTINATION).unwrap();
+        drop(closed);
+        assert!(client.admit(DESTINATION).is_err());
+        trial.settle(true);
+    }
+}
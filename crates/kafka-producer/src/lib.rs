@@ -0,0 +1,261 @@
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry};
+use rdkafka::config::ClientConfig;
+use rdkafka::error::KafkaError;
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer as _};
+use serde::Serialize;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// ==============================================================================
+// KAFKA PRODUCER: One way to publish, with the guarantees set once
+// ==============================================================================
+//
+// Every service that publishes (retry, dead-letter and delivery-event topics)
+// goes through this instead of assembling its own rdkafka ClientConfig:
+//
+//   acks=all            a write counts once every in-sync replica has it
+//   idempotence         broker-side dedup of the producer's own retries, so
+//                       a retried batch neither duplicates nor reorders
+//   keyed partitioning  PartitionKey: by merchant (the order svix-caller's
+//                       Virtual Objects need, see SEQUIN_SETUP.md) or by
+//                       object, when only one payment's events must stay in
+//                       order and the load should spread
+//   delivery reports    send() resolves once the broker acknowledged the
+//                       record (or librdkafka gave up after message_timeout),
+//                       and says where it landed or why it didn't
+//   metrics             per topic, in the service's registry:
+//
+//     kafka_messages_produced_total{topic, outcome}    ok or error
+//     kafka_produce_duration_seconds{topic}            until the report
+
+#[derive(Clone, Debug)]
+pub struct ProducerConfig {
+    /// bootstrap.servers, comma separated
+    pub brokers: String,
+    pub client_id: String,
+    /// How long librdkafka keeps retrying a record before reporting failure
+    pub message_timeout: Duration,
+    /// Wait this long to batch records together
+    pub linger: Duration,
+    /// none, gzip, snappy, lz4 or zstd
+    pub compression: String,
+}
+
+impl ProducerConfig {
+    pub fn new(brokers: &str, client_id: &str) -> Self {
+        ProducerConfig {
+            brokers: brokers.to_string(),
+            client_id: client_id.to_string(),
+            message_timeout: Duration::from_secs(30),
+            linger: Duration::from_millis(5),
+            compression: "lz4".to_string(),
+        }
+    }
+}
+
+/// Registered once per service, shared by its producers
+#[derive(Clone)]
+pub struct ProducerMetrics {
+    produced: IntCounterVec,
+    duration: HistogramVec,
+}
+
+impl ProducerMetrics {
+    pub fn new(registry: &Registry) -> Self {
+        let produced = IntCounterVec::new(
+            Opts::new(
+                "kafka_messages_produced_total",
+                "Kafka records published, by delivery report outcome",
+            ),
+            &["topic", "outcome"],
+        )
+        .unwrap();
+        let duration = HistogramVec::new(
+            HistogramOpts::new(
+                "kafka_produce_duration_seconds",
+                "Time from send to the record's delivery report",
+            )
+            .buckets(vec![
+                0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0, 30.0,
+            ]),
+            &["topic"],
+        )
+        .unwrap();
+        registry.register(Box::new(produced.clone())).unwrap();
+        registry.register(Box::new(duration.clone())).unwrap();
+        ProducerMetrics { produced, duration }
+    }
+}
+
+/// What a record is keyed by, and so which records stay in order
+#[derive(Clone, Copy, Debug)]
+pub enum PartitionKey<'a> {
+    /// All of a merchant's records, in order; what SvixCaller expects
+    Merchant(&'a str),
+    /// One object's (payment's) records in order, spread across partitions
+    Object(&'a str),
+}
+
+impl PartitionKey<'_> {
+    pub fn as_str(&self) -> &str {
+        match self {
+            PartitionKey::Merchant(key) | PartitionKey::Object(key) => key,
+        }
+    }
+}
+
+/// Where an acknowledged record landed
+#[derive(Clone, Copy, Debug)]
+pub struct Delivery {
+    pub partition: i32,
+    pub offset: i64,
+}
+
+#[derive(Debug)]
+pub enum ProduceError {
+    /// The value couldn't be serialized; nothing was sent
+    Serialize(String),
+    /// Rejected or not acknowledged within message_timeout
+    Kafka(KafkaError),
+}
+
+impl ProduceError {
+    /// Worth sending again (broker unavailable, timed out), as opposed to a
+    /// record that can never be accepted (too large, unknown topic)
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            ProduceError::Serialize(_) => false,
+            ProduceError::Kafka(e) => e.rdkafka_error_code().is_some_and(|code| {
+                !matches!(
+                    code,
+                    rdkafka::types::RDKafkaErrorCode::MessageSizeTooLarge
+                        | rdkafka::types::RDKafkaErrorCode::UnknownTopicOrPartition
+                        | rdkafka::types::RDKafkaErrorCode::InvalidRecord
+                        | rdkafka::types::RDKafkaErrorCode::TopicAuthorizationFailed
+                )
+            }),
+        }
+    }
+}
+
+impl fmt::Display for ProduceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProduceError::Serialize(e) => write!(f, "failed to serialize record: {}", e),
+            ProduceError::Kafka(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProduceError {}
+
+#[derive(Clone)]
+pub struct Producer {
+    producer: FutureProducer,
+    metrics: ProducerMetrics,
+}
+
+impl Producer {
+    pub fn new(config: &ProducerConfig, metrics: &ProducerMetrics) -> Result<Self, String> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", &config.brokers)
+            .set("client.id", &config.client_id)
+            .set("acks", "all")
+            .set("enable.idempotence", "true")
+            // The most idempotence allows while still keeping order
+            .set("max.in.flight.requests.per.connection", "5")
+            .set(
+                "message.timeout.ms",
+                config.message_timeout.as_millis().to_string(),
+            )
+            .set("linger.ms", config.linger.as_millis().to_string())
+            .set("compression.type", &config.compression)
+            .create::<FutureProducer>()
+            .map_err(|e| format!("Failed to create Kafka producer: {}", e))?;
+
+        tracing::info!(
+            "Kafka producer {} for {} (acks=all, idempotent)",
+            config.client_id,
+            config.brokers
+        );
+        Ok(Producer {
+            producer,
+            metrics: metrics.clone(),
+        })
+    }
+
+    /// Publish one record and wait for its delivery report
+    pub async fn send(
+        &self,
+        topic: &str,
+        key: PartitionKey<'_>,
+        payload: &[u8],
+        headers: &[(&str, &str)],
+    ) -> Result<Delivery, ProduceError> {
+        let mut record_headers = OwnedHeaders::new_with_capacity(headers.len());
+        for (name, value) in headers {
+            record_headers = record_headers.insert(Header {
+                key: name,
+                value: Some(*value),
+            });
+        }
+        let record = FutureRecord::to(topic)
+            .key(key.as_str())
+            .payload(payload)
+            .headers(record_headers);
+
+        let started = Instant::now();
+        // A full local queue waits for room instead of failing at once; the
+        // report still comes within message_timeout
+        let result = self.producer.send(record, Duration::from_secs(5)).await;
+        self.metrics
+            .duration
+            .with_label_values(&[topic])
+            .observe(started.elapsed().as_secs_f64());
+
+        match result {
+            Ok((partition, offset)) => {
+                self.count(topic, "ok");
+                Ok(Delivery { partition, offset })
+            }
+            Err((e, _)) => {
+                self.count(topic, "error");
+                tracing::warn!(
+                    "Failed to publish to {} (key {}): {}",
+                    topic,
+                    key.as_str(),
+                    e
+                );
+                Err(ProduceError::Kafka(e))
+            }
+        }
+    }
+
+    /// send() with the value as JSON
+    pub async fn send_json<T: Serialize>(
+        &self,
+        topic: &str,
+        key: PartitionKey<'_>,
+        value: &T,
+        headers: &[(&str, &str)],
+    ) -> Result<Delivery, ProduceError> {
+        let payload =
+            serde_json::to_vec(value).map_err(|e| ProduceError::Serialize(e.to_string()))?;
+        self.send(topic, key, &payload, headers).await
+    }
+
+    /// Wait for records still queued, on shutdown
+    pub fn flush(&self, timeout: Duration) -> Result<(), String> {
+        self.producer
+            .flush(timeout)
+            .map_err(|e| format!("Kafka flush failed: {}", e))
+    }
+
+    fn count(&self, topic: &str, outcome: &str) {
+        self.metrics
+            .produced
+            .with_label_values(&[topic, outcome])
+            .inc();
+    }
+}
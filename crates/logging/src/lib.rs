@@ -0,0 +1,156 @@
+use serde_json::{Map, Value};
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+// ==============================================================================
+// LOGGING: One JSON line per log event, from every service
+// ==============================================================================
+//
+// A payment's webhook passes through api-service, Sequin/Kafka, svix-caller,
+// data-service and the merchant; joining those logs needs the same field
+// names everywhere. Every line carries:
+//
+//   timestamp, level, target, message   as usual
+//   service, version                    which binary wrote it
+//   correlation_id                      the payment (or other object) the
+//                                       webhook is about: known at every hop
+//   event_id, merchant_id               where the service knows them
+//
+// plus any other field of the event. The correlation fields come from the
+// spans the event is in, flattened into the line (inner spans win), so a
+// handler opens one span and everything logged below it is tagged:
+//
+//   let span = tracing::info_span!("payment", correlation_id = %payment.id);
+//
+// LOG_FORMAT=text switches to tracing's human-readable output for local
+// runs. RUST_LOG sets the level (trace, debug, info, warn, error; default
+// info).
+
+/// Install the subscriber; call once, first thing in main
+pub fn init(service: &'static str, version: &'static str) {
+    let level = std::env::var("RUST_LOG")
+        .ok()
+        .and_then(|v| LevelFilter::from_str(&v).ok())
+        .unwrap_or(LevelFilter::INFO);
+
+    let registry = tracing_subscriber::registry().with(level);
+    if std::env::var("LOG_FORMAT").as_deref() == Ok("text") {
+        registry.with(tracing_subscriber::fmt::layer()).init();
+    } else {
+        registry.with(JsonLayer { service, version }).init();
+    }
+}
+
+struct JsonLayer {
+    service: &'static str,
+    version: &'static str,
+}
+
+/// A span's fields, kept in its extensions until it closes
+struct SpanFields(Map<String, Value>);
+
+impl<S> Layer<S> for JsonLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Map::new();
+        attrs.record(&mut JsonVisitor(&mut fields));
+        span.extensions_mut().insert(SpanFields(fields));
+    }
+
+    /// Fields declared Empty and filled in later
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(fields) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut JsonVisitor(&mut fields.0));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut line = Map::new();
+        line.insert(
+            "timestamp".to_string(),
+            Value::String(chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)),
+        );
+        line.insert(
+            "level".to_string(),
+            Value::String(metadata.level().to_string()),
+        );
+        line.insert(
+            "service".to_string(),
+            Value::String(self.service.to_string()),
+        );
+        line.insert(
+            "version".to_string(),
+            Value::String(self.version.to_string()),
+        );
+        line.insert(
+            "target".to_string(),
+            Value::String(metadata.target().to_string()),
+        );
+
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(fields) = span.extensions().get::<SpanFields>() {
+                    line.extend(fields.0.clone());
+                }
+            }
+        }
+        event.record(&mut JsonVisitor(&mut line));
+
+        let mut stdout = std::io::stdout().lock();
+        if serde_json::to_writer(&mut stdout, &line).is_ok() {
+            let _ = stdout.write_all(b"\n");
+        }
+    }
+}
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0
+            .insert(field.name().to_string(), Value::String(value.to_string()));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    /// The message and %/? fields
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(
+            field.name().to_string(),
+            Value::String(format!("{:?}", value)),
+        );
+    }
+}
@@ -0,0 +1,387 @@
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand::RngCore;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+mod master;
+
+pub use master::LocalMasterKey;
+
+// ==============================================================================
+// PAYLOAD CRYPTO: Envelope encryption of payloads kept at rest
+// ==============================================================================
+//
+// Each merchant has a data key (AES-256) that encrypts its payloads. Data
+// keys are stored in merchant_data_keys only wrapped by a master key, which
+// never leaves its MasterKey: LocalMasterKey holds it in the process, a KMS
+// would hold it behind its Encrypt/Decrypt calls. Unwrapped data keys are
+// cached per process, so the master key is asked once per merchant.
+//
+// A sealed payload takes the place of the JSON value it encrypts:
+//
+//   {"$sealed": "v1", "kid": <merchant_data_keys.id>,
+//    "nonce": "<base64>", "ciphertext": "<base64>"}
+//
+// with the merchant id as associated data, so a payload copied under another
+// merchant fails to open. open() hands anything that isn't sealed back as it
+// is: rows written before encryption was turned on stay readable, and a
+// service without keys sees which payloads it can't read.
+//
+// What gets sealed is up to the services: svix-caller seals the payloads it
+// journals in Restate and writes to svix_dead_letters, and opens them where
+// it delivers. domain_events payloads are written by the payments trigger in
+// the same transaction as the payments row they copy, which is itself in the
+// clear, so they aren't.
+//
+// Rotating the master key: add the new key first in PAYLOAD_MASTER_KEYS and
+// keep the old ones after it; new data keys are wrapped with the first,
+// existing ones open with whichever wrapped them. Rotating a merchant's data
+// key sets retired_at on its row; the next seal creates a new one, and
+// payloads sealed before keep opening with the retired key.
+
+const SEALED_VERSION: &str = "v1";
+
+/// AES-GCM's standard nonce
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug)]
+pub struct CryptoError(pub String);
+
+impl std::fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for CryptoError {}
+
+/// Where data keys are wrapped and unwrapped. A KMS-backed one makes a call
+/// per method, which is why both are async.
+pub trait MasterKey: Send + Sync {
+    /// The key new data keys are wrapped with, stored next to them
+    fn current_id(&self) -> &str;
+
+    fn wrap(&self, data_key: &[u8]) -> impl Future<Output = Result<Vec<u8>, CryptoError>> + Send;
+
+    /// `master_key_id` is the one current_id() returned at wrap time
+    fn unwrap(
+        &self,
+        master_key_id: &str,
+        wrapped: &[u8],
+    ) -> impl Future<Output = Result<Vec<u8>, CryptoError>> + Send;
+}
+
+/// Whether `value` is a sealed payload
+pub fn is_sealed(value: &Value) -> bool {
+    value.get("$sealed").is_some()
+}
+
+/// Per-merchant data keys, created on first use and cached
+pub struct Keyring<M> {
+    db: PgPool,
+    master: M,
+    /// merchant_id -> the data key new payloads are sealed with
+    active: Mutex<HashMap<String, (i64, Vec<u8>)>>,
+    /// merchant_data_keys.id -> data key, for opening
+    by_id: Mutex<HashMap<i64, Vec<u8>>>,
+}
+
+impl<M: MasterKey> Keyring<M> {
+    pub fn new(db: PgPool, master: M) -> Self {
+        Keyring {
+            db,
+            master,
+            active: Mutex::new(HashMap::new()),
+            by_id: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `value` encrypted with the merchant's data key
+    pub async fn seal(&self, merchant_id: &str, value: &Value) -> Result<Value, CryptoError> {
+        if is_sealed(value) {
+            return Ok(value.clone());
+        }
+        let (key_id, key) = self.active_key(merchant_id).await?;
+        seal_with(key_id, &key, merchant_id, value)
+    }
+
+    /// The payload `value` seals, or `value` itself when it isn't sealed
+    pub async fn open(&self, merchant_id: &str, value: &Value) -> Result<Value, CryptoError> {
+        if !is_sealed(value) {
+            return Ok(value.clone());
+        }
+        let key_id = sealed_key_id(value)?;
+        let key = self.key_by_id(key_id).await?;
+        open_with(key_id, &key, merchant_id, value)
+    }
+
+    /// The merchant's unretired data key, created (and wrapped) if it has none
+    async fn active_key(&self, merchant_id: &str) -> Result<(i64, Vec<u8>), CryptoError> {
+        if let Some(key) = self.active.lock().unwrap().get(merchant_id) {
+            return Ok(key.clone());
+        }
+
+        let mut row = self.load_active(merchant_id).await?;
+        if row.is_none() {
+            let mut data_key = vec![0u8; 32];
+            rand::thread_rng().fill_bytes(&mut data_key);
+            let wrapped = self.master.wrap(&data_key).await?;
+            // Another instance may create one at the same time: one wins
+            // (the partial unique index), both use it
+            sqlx::query(
+                r#"
+                INSERT INTO merchant_data_keys (merchant_id, master_key_id, wrapped_key)
+                VALUES ($1::UUID, $2, $3)
+                ON CONFLICT (merchant_id) WHERE retired_at IS NULL DO NOTHING
+                "#,
+            )
+            .bind(merchant_id)
+            .bind(self.master.current_id())
+            .bind(&wrapped)
+            .execute(&self.db)
+            .await
+            .map_err(|e| CryptoError(format!("Failed to store data key: {}", e)))?;
+            tracing::info!("Created data key for merchant {}", merchant_id);
+            row = self.load_active(merchant_id).await?;
+        }
+        let (key_id, master_key_id, wrapped) =
+            row.ok_or_else(|| CryptoError(format!("No data key for merchant {}", merchant_id)))?;
+
+        let key = self.master.unwrap(&master_key_id, &wrapped).await?;
+        self.by_id.lock().unwrap().insert(key_id, key.clone());
+        self.active
+            .lock()
+            .unwrap()
+            .insert(merchant_id.to_string(), (key_id, key.clone()));
+        Ok((key_id, key))
+    }
+
+    async fn load_active(
+        &self,
+        merchant_id: &str,
+    ) -> Result<Option<(i64, String, Vec<u8>)>, CryptoError> {
+        sqlx::query_as(
+            r#"
+            SELECT id, master_key_id, wrapped_key FROM merchant_data_keys
+            WHERE merchant_id = $1::UUID AND retired_at IS NULL
+            "#,
+        )
+        .bind(merchant_id)
+        .fetch_optional(&self.db)
+        .await
+        .map_err(|e| CryptoError(format!("Failed to load data key: {}", e)))
+    }
+
+    /// Any data key, retired ones included
+    async fn key_by_id(&self, key_id: i64) -> Result<Vec<u8>, CryptoError> {
+        if let Some(key) = self.by_id.lock().unwrap().get(&key_id) {
+            return Ok(key.clone());
+        }
+        let (master_key_id, wrapped): (String, Vec<u8>) = sqlx::query_as(
+            "SELECT master_key_id, wrapped_key FROM merchant_data_keys WHERE id = $1",
+        )
+        .bind(key_id)
+        .fetch_optional(&self.db)
+        .await
+        .map_err(|e| CryptoError(format!("Failed to load data key {}: {}", key_id, e)))?
+        .ok_or_else(|| CryptoError(format!("Data key {} not found", key_id)))?;
+
+        let key = self.master.unwrap(&master_key_id, &wrapped).await?;
+        self.by_id.lock().unwrap().insert(key_id, key.clone());
+        Ok(key)
+    }
+}
+
+/// `value` sealed with data key `key_id`
+fn seal_with(
+    key_id: i64,
+    key: &[u8],
+    merchant_id: &str,
+    value: &Value,
+) -> Result<Value, CryptoError> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let plaintext = serde_json::to_vec(value)
+        .map_err(|e| CryptoError(format!("Failed to serialize payload: {}", e)))?;
+    let ciphertext = cipher(key)?
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &plaintext,
+                aad: merchant_id.as_bytes(),
+            },
+        )
+        .map_err(|_| CryptoError(format!("Failed to seal payload for {}", merchant_id)))?;
+
+    Ok(json!({
+        "$sealed": SEALED_VERSION,
+        "kid": key_id,
+        "nonce": STANDARD.encode(nonce),
+        "ciphertext": STANDARD.encode(ciphertext),
+    }))
+}
+
+/// The data key a sealed payload needs
+fn sealed_key_id(value: &Value) -> Result<i64, CryptoError> {
+    if value["$sealed"] != SEALED_VERSION {
+        return Err(CryptoError(format!(
+            "Unknown sealed payload version {}",
+            value["$sealed"]
+        )));
+    }
+    value["kid"]
+        .as_i64()
+        .ok_or_else(|| CryptoError("Sealed payload without kid".to_string()))
+}
+
+/// The payload a sealed `value` holds, opened with data key `key_id`
+fn open_with(
+    key_id: i64,
+    key: &[u8],
+    merchant_id: &str,
+    value: &Value,
+) -> Result<Value, CryptoError> {
+    let decode = |field: &str| {
+        value[field]
+            .as_str()
+            .and_then(|encoded| STANDARD.decode(encoded).ok())
+            .ok_or_else(|| CryptoError(format!("Sealed payload with invalid {}", field)))
+    };
+    let nonce = decode("nonce")?;
+    let ciphertext = decode("ciphertext")?;
+    if nonce.len() != NONCE_LEN {
+        return Err(CryptoError("Sealed payload with invalid nonce".to_string()));
+    }
+
+    let plaintext = cipher(key)?
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad: merchant_id.as_bytes(),
+            },
+        )
+        .map_err(|_| {
+            CryptoError(format!(
+                "Payload sealed with key {} doesn't open for merchant {}",
+                key_id, merchant_id
+            ))
+        })?;
+    serde_json::from_slice(&plaintext)
+        .map_err(|e| CryptoError(format!("Opened payload isn't JSON: {}", e)))
+}
+
+fn cipher(key: &[u8]) -> Result<Aes256Gcm, CryptoError> {
+    if key.len() != 32 {
+        return Err(CryptoError(format!(
+            "Data key is {} bytes, expected 32",
+            key.len()
+        )));
+    }
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MERCHANT: &str = "5f0c8f4e-8a57-4a43-9f3e-2b1d0c6a7e11";
+
+    fn payload() -> Value {
+        json!({"id": "pay_1", "amount": 1250, "card": {"last4": "4242"}})
+    }
+
+    #[test]
+    fn sealed_payloads_open_with_their_data_key() {
+        let key = [7u8; 32];
+        let sealed = seal_with(3, &key, MERCHANT, &payload()).unwrap();
+
+        assert!(is_sealed(&sealed));
+        assert_eq!(sealed["kid"], 3);
+        assert!(!sealed.to_string().contains("4242"));
+        assert_eq!(sealed_key_id(&sealed).unwrap(), 3);
+        assert_eq!(open_with(3, &key, MERCHANT, &sealed).unwrap(), payload());
+    }
+
+    #[test]
+    fn every_seal_uses_a_new_nonce() {
+        let key = [7u8; 32];
+        let first = seal_with(1, &key, MERCHANT, &payload()).unwrap();
+        let second = seal_with(1, &key, MERCHANT, &payload()).unwrap();
+        assert_ne!(first["nonce"], second["nonce"]);
+        assert_ne!(first["ciphertext"], second["ciphertext"]);
+    }
+
+    #[test]
+    fn a_payload_doesnt_open_for_another_merchant() {
+        let key = [7u8; 32];
+        let sealed = seal_with(1, &key, MERCHANT, &payload()).unwrap();
+        let other = "0b7e2a4c-1d3f-4e5a-8b6c-9d0e1f2a3b4c";
+        assert!(open_with(1, &key, other, &sealed).is_err());
+    }
+
+    #[test]
+    fn a_payload_doesnt_open_with_another_data_key() {
+        let sealed = seal_with(1, &[7u8; 32], MERCHANT, &payload()).unwrap();
+        assert!(open_with(1, &[8u8; 32], MERCHANT, &sealed).is_err());
+    }
+
+    #[test]
+    fn payloads_sealed_before_a_data_key_rotation_keep_opening() {
+        let (retired, active) = ([1u8; 32], [2u8; 32]);
+        let before = seal_with(1, &retired, MERCHANT, &payload()).unwrap();
+        let after = seal_with(2, &active, MERCHANT, &json!({"id": "pay_2"})).unwrap();
+
+        // Each names the key it needs
+        assert_eq!(sealed_key_id(&before).unwrap(), 1);
+        assert_eq!(sealed_key_id(&after).unwrap(), 2);
+        assert_eq!(
+            open_with(1, &retired, MERCHANT, &before).unwrap(),
+            payload()
+        );
+        assert_eq!(
+            open_with(2, &active, MERCHANT, &after).unwrap(),
+            json!({"id": "pay_2"})
+        );
+    }
+
+    #[test]
+    fn tampered_or_malformed_payloads_are_errors() {
+        let key = [7u8; 32];
+        let sealed = seal_with(1, &key, MERCHANT, &payload()).unwrap();
+
+        let mut tampered = sealed.clone();
+        let mut ciphertext = STANDARD
+            .decode(tampered["ciphertext"].as_str().unwrap())
+            .unwrap();
+        ciphertext[0] ^= 1;
+        tampered["ciphertext"] = json!(STANDARD.encode(ciphertext));
+        assert!(open_with(1, &key, MERCHANT, &tampered).is_err());
+
+        let mut short_nonce = sealed.clone();
+        short_nonce["nonce"] = json!(STANDARD.encode([0u8; 8]));
+        assert!(open_with(1, &key, MERCHANT, &short_nonce).is_err());
+
+        let mut not_base64 = sealed.clone();
+        not_base64["ciphertext"] = json!("not base64!");
+        assert!(open_with(1, &key, MERCHANT, &not_base64).is_err());
+
+        let mut future_version = sealed.clone();
+        future_version["$sealed"] = json!("v2");
+        assert!(sealed_key_id(&future_version).is_err());
+
+        let mut no_kid = sealed;
+        no_kid.as_object_mut().unwrap().remove("kid");
+        assert!(sealed_key_id(&no_kid).is_err());
+    }
+
+    #[test]
+    fn data_keys_must_be_32_bytes() {
+        assert!(seal_with(1, &[7u8; 16], MERCHANT, &payload()).is_err());
+    }
+}
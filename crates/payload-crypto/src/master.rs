@@ -0,0 +1,174 @@
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand::RngCore;
+use std::collections::HashMap;
+
+use crate::{CryptoError, MasterKey, NONCE_LEN};
+
+/// Master keys held by the process, parsed from PAYLOAD_MASTER_KEYS as the
+/// service loads it (api-service's settings, svix-caller's secrets):
+///
+///   PAYLOAD_MASTER_KEYS=2024-06:<base64 32 bytes>,2024-01:<base64 32 bytes>
+///
+/// The first wraps new data keys, the rest only unwrap the ones they
+/// wrapped. A wrapped key is nonce || AES-256-GCM ciphertext, with the
+/// master key's id as associated data.
+pub struct LocalMasterKey {
+    current: String,
+    keys: HashMap<String, Vec<u8>>,
+}
+
+impl LocalMasterKey {
+    pub fn parse(entries: &str) -> Result<Self, String> {
+        let mut current = None;
+        let mut keys = HashMap::new();
+        for entry in entries.split(',').filter(|entry| !entry.trim().is_empty()) {
+            let (id, key) = entry.trim().split_once(':').ok_or_else(|| {
+                "Invalid PAYLOAD_MASTER_KEYS entry, expected <id>:<base64 key>".to_string()
+            })?;
+            let key = STANDARD
+                .decode(key.trim())
+                .map_err(|e| format!("Invalid master key {}: {}", id, e))?;
+            if key.len() != 32 {
+                return Err(format!(
+                    "Master key {} is {} bytes, expected 32",
+                    id,
+                    key.len()
+                ));
+            }
+            current.get_or_insert_with(|| id.trim().to_string());
+            keys.insert(id.trim().to_string(), key);
+        }
+        let current = current.ok_or("PAYLOAD_MASTER_KEYS has no keys")?;
+        Ok(LocalMasterKey { current, keys })
+    }
+
+    fn cipher(&self, id: &str) -> Result<Aes256Gcm, CryptoError> {
+        let key = self
+            .keys
+            .get(id)
+            .ok_or_else(|| CryptoError(format!("Master key {} not configured", id)))?;
+        Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)))
+    }
+}
+
+impl MasterKey for LocalMasterKey {
+    fn current_id(&self) -> &str {
+        &self.current
+    }
+
+    async fn wrap(&self, data_key: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher(&self.current)?
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: data_key,
+                    aad: self.current.as_bytes(),
+                },
+            )
+            .map_err(|_| CryptoError("Failed to wrap data key".to_string()))?;
+        let mut wrapped = nonce.to_vec();
+        wrapped.extend(ciphertext);
+        Ok(wrapped)
+    }
+
+    async fn unwrap(&self, master_key_id: &str, wrapped: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        if wrapped.len() <= NONCE_LEN {
+            return Err(CryptoError("Wrapped data key too short".to_string()));
+        }
+        let (nonce, ciphertext) = wrapped.split_at(NONCE_LEN);
+        self.cipher(master_key_id)?
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: master_key_id.as_bytes(),
+                },
+            )
+            .map_err(|_| CryptoError(format!("Failed to unwrap data key with {}", master_key_id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    /// LocalMasterKey's futures never wait on anything
+    fn ready<T>(future: impl Future<Output = T>) -> T {
+        match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(value) => value,
+            Poll::Pending => panic!("LocalMasterKey future pending"),
+        }
+    }
+
+    fn entry(id: &str, byte: u8) -> String {
+        format!("{}:{}", id, STANDARD.encode([byte; 32]))
+    }
+
+    #[test]
+    fn wrapped_data_keys_unwrap_with_the_key_that_wrapped_them() {
+        let master = LocalMasterKey::parse(&entry("2024-01", 1)).unwrap();
+        let data_key = [9u8; 32];
+        let wrapped = ready(master.wrap(&data_key)).unwrap();
+
+        assert_eq!(master.current_id(), "2024-01");
+        assert_ne!(&wrapped[NONCE_LEN..], &data_key[..]);
+        assert_eq!(ready(master.unwrap("2024-01", &wrapped)).unwrap(), data_key);
+    }
+
+    #[test]
+    fn master_key_rotation_round_trip() {
+        let data_key = [9u8; 32];
+        let old = LocalMasterKey::parse(&entry("2024-01", 1)).unwrap();
+        let wrapped_before = ready(old.wrap(&data_key)).unwrap();
+
+        // The new key first, the old one kept after it
+        let rotated =
+            LocalMasterKey::parse(&format!("{}, {}", entry("2024-06", 2), entry("2024-01", 1)))
+                .unwrap();
+        assert_eq!(rotated.current_id(), "2024-06");
+        assert_eq!(
+            ready(rotated.unwrap("2024-01", &wrapped_before)).unwrap(),
+            data_key
+        );
+        let wrapped_after = ready(rotated.wrap(&data_key)).unwrap();
+        assert_eq!(
+            ready(rotated.unwrap("2024-06", &wrapped_after)).unwrap(),
+            data_key
+        );
+
+        // Dropping the old key too soon strands what it wrapped
+        let dropped = LocalMasterKey::parse(&entry("2024-06", 2)).unwrap();
+        assert!(ready(dropped.unwrap("2024-01", &wrapped_before)).is_err());
+    }
+
+    #[test]
+    fn a_wrapped_key_doesnt_unwrap_under_another_id_or_tampered() {
+        let master =
+            LocalMasterKey::parse(&format!("{},{}", entry("a", 1), entry("b", 1))).unwrap();
+        let mut wrapped = ready(master.wrap(&[9u8; 32])).unwrap();
+
+        // Same key bytes, but the id is associated data
+        assert!(ready(master.unwrap("b", &wrapped)).is_err());
+        assert!(ready(master.unwrap("a", &wrapped[..NONCE_LEN])).is_err());
+        let last = wrapped.len() - 1;
+        wrapped[last] ^= 1;
+        assert!(ready(master.unwrap("a", &wrapped)).is_err());
+    }
+
+    #[test]
+    fn malformed_key_lists_are_rejected() {
+        assert!(LocalMasterKey::parse("").is_err());
+        assert!(LocalMasterKey::parse("2024-01").is_err());
+        assert!(LocalMasterKey::parse("2024-01:not base64!").is_err());
+        let short = format!("2024-01:{}", STANDARD.encode([1u8; 16]));
+        assert!(LocalMasterKey::parse(&short).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Dynamic, Engine, EvalAltResult, Scope, AST};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// ==============================================================================
+// PAYLOAD SCRIPT: Reshaping a webhook body with a Rhai script
+// ==============================================================================
+//
+// Where a payload template (svix-caller's transform.rs) maps fields, a script
+// can compute: convert units, rename and drop keys, branch on the event type.
+// It gets the webhook body as `payload` and returns the body to send, or
+// changes `payload` in place and returns nothing:
+//
+//   payload.amount_major = payload.payment.amount / 100.0;
+//   payload.remove("schema_version");
+//   if payload.event_type == "payment.failed" { payload.retryable = true; }
+//
+// The result must be an object. Scripts run in a sandbox: no modules, files
+// or eval; print and debug are collected instead of written (dry runs return
+// them). Every run is capped at MAX_OPERATIONS steps and MAX_RUN_TIME, and
+// strings, arrays and objects at the sizes below, so a script that loops or
+// grows without end fails instead of holding up delivery.
+
+/// Longest script accepted
+pub const MAX_SCRIPT_LEN: usize = 16 * 1024;
+
+pub const MAX_OPERATIONS: u64 = 100_000;
+pub const MAX_RUN_TIME: Duration = Duration::from_millis(50);
+
+const MAX_CALL_LEVELS: usize = 16;
+const MAX_EXPR_DEPTH: usize = 64;
+const MAX_FUNCTION_EXPR_DEPTH: usize = 32;
+const MAX_STRING_SIZE: usize = 256 * 1024;
+const MAX_ARRAY_SIZE: usize = 10_000;
+const MAX_MAP_SIZE: usize = 10_000;
+
+/// Lines of print/debug output kept per run
+const MAX_PRINTED: usize = 100;
+
+#[derive(Clone)]
+pub struct Script {
+    ast: AST,
+}
+
+/// A run's result and what the script printed along the way
+#[derive(Debug)]
+pub struct Output {
+    pub body: Value,
+    pub printed: Vec<String>,
+}
+
+/// Why a run failed, and what the script printed before it did
+#[derive(Debug)]
+pub struct Failure {
+    pub error: String,
+    pub printed: Vec<String>,
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.error)
+    }
+}
+
+impl Script {
+    pub fn compile(source: &str) -> Result<Self, String> {
+        if source.len() > MAX_SCRIPT_LEN {
+            return Err(format!("longer than {} bytes", MAX_SCRIPT_LEN));
+        }
+        let ast = sandbox(None).compile(source).map_err(|e| e.to_string())?;
+        Ok(Script { ast })
+    }
+
+    /// The body the script makes of `payload`
+    pub fn run(&self, payload: &Value) -> Result<Output, Failure> {
+        let printed = Arc::new(Mutex::new(Vec::new()));
+        let result = self.eval(payload, sandbox(Some(printed.clone())));
+        let printed = std::mem::take(&mut *printed.lock().unwrap());
+        match result {
+            Ok(body) => Ok(Output { body, printed }),
+            Err(error) => Err(Failure { error, printed }),
+        }
+    }
+
+    fn eval(&self, payload: &Value, engine: Engine) -> Result<Value, String> {
+        let mut scope = Scope::new();
+        let payload = rhai::serde::to_dynamic(payload).map_err(|e| e.to_string())?;
+        scope.push_dynamic("payload", payload);
+        let result = engine
+            .eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast)
+            .map_err(|e| match *e {
+                // Rhai's message leaves out why on_progress stopped it
+                EvalAltResult::ErrorTerminated(reason, position) => {
+                    format!("{} ({})", reason, position)
+                }
+                e => e.to_string(),
+            })?;
+        let result = if result.is_unit() {
+            scope.get_value::<Dynamic>("payload").unwrap_or_default()
+        } else {
+            result
+        };
+
+        let body: Value = rhai::serde::from_dynamic(&result).map_err(|e| e.to_string())?;
+        if !body.is_object() {
+            return Err(format!(
+                "the script must return an object, not {}",
+                result.type_name()
+            ));
+        }
+        Ok(body)
+    }
+}
+
+fn sandbox(printed: Option<Arc<Mutex<Vec<String>>>>) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .set_max_modules(0)
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_expr_depths(MAX_EXPR_DEPTH, MAX_FUNCTION_EXPR_DEPTH)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_ARRAY_SIZE)
+        .set_max_map_size(MAX_MAP_SIZE)
+        .disable_symbol("eval");
+
+    let started = Instant::now();
+    engine.on_progress(move |_| {
+        (started.elapsed() > MAX_RUN_TIME)
+            .then(|| Dynamic::from(format!("ran longer than {:?}", MAX_RUN_TIME)))
+    });
+
+    let collect = move |line: String| {
+        if let Some(printed) = &printed {
+            let mut printed = printed.lock().unwrap();
+            if printed.len() < MAX_PRINTED {
+                printed.push(line);
+            }
+        }
+    };
+    let on_debug = collect.clone();
+    engine.on_print(move |text| collect(text.to_string()));
+    engine.on_debug(move |text, _, position| on_debug(format!("{} {}", position, text)));
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn payload() -> Value {
+        json!({
+            "event_type": "payment.succeeded",
+            "schema_version": 2,
+            "payment": {"id": "pay_123", "amount": 12500}
+        })
+    }
+
+    fn run(source: &str) -> Result<Output, Failure> {
+        Script::compile(source)
+            .unwrap_or_else(|e| panic!("{}: {}", source, e))
+            .run(&payload())
+    }
+
+    #[test]
+    fn scripts_return_a_body_or_change_payload() {
+        let returned =
+            run(r#"#{ id: payload.payment.id, major: payload.payment.amount / 100 }"#).unwrap();
+        assert_eq!(returned.body, json!({"id": "pay_123", "major": 125}));
+
+        let changed = run(r#"
+            payload.remove("schema_version");
+            if payload.event_type == "payment.succeeded" { payload.ok = true; }
+        "#)
+        .unwrap();
+        assert_eq!(
+            changed.body,
+            json!({
+                "event_type": "payment.succeeded",
+                "payment": {"id": "pay_123", "amount": 12500},
+                "ok": true
+            })
+        );
+    }
+
+    #[test]
+    fn the_result_must_be_an_object() {
+        for source in ["42", r#""text""#, "[1, 2]", "payload = 1;", "true"] {
+            let failure = run(source).unwrap_err();
+            assert!(
+                failure.error.contains("must return an object"),
+                "{}: {}",
+                source,
+                failure.error
+            );
+        }
+    }
+
+    #[test]
+    fn endless_loops_hit_the_operation_cap() {
+        let failure = run("let n = 0; loop { n += 1; }").unwrap_err();
+        assert!(
+            failure.error.to_lowercase().contains("operations"),
+            "{}",
+            failure.error
+        );
+    }
+
+    #[test]
+    fn slow_scripts_hit_the_time_cap() {
+        // Few operations, each expensive
+        let failure = run(r#"
+            let items = [];
+            items.pad(10000, 0);
+            loop { items.reverse(); items.sort(); }
+        "#)
+        .unwrap_err();
+        assert!(
+            failure.error.contains("ran longer than"),
+            "{}",
+            failure.error
+        );
+    }
+
+    #[test]
+    fn growing_without_end_hits_the_size_caps() {
+        assert!(run(r#"let s = "x"; loop { s += s; }"#).is_err());
+        assert!(run("let a = []; loop { a.push(1); }").is_err());
+        assert!(run("let m = #{}; let n = 0; loop { m[`k${n}`] = n; n += 1; }").is_err());
+    }
+
+    #[test]
+    fn eval_and_modules_are_disabled() {
+        assert!(Script::compile(r#"eval("1 + 1")"#).is_err());
+        assert!(Script::compile(r#"import "fs" as fs;"#)
+            .map_err(|e| e.to_string())
+            .and_then(|script| script.run(&payload()).map_err(|e| e.error))
+            .is_err());
+    }
+
+    #[test]
+    fn runaway_recursion_is_an_error() {
+        assert!(run("fn f(n) { f(n + 1) } f(0)").is_err());
+    }
+
+    #[test]
+    fn prints_are_collected_even_when_the_run_fails() {
+        let output = run(r#"print("hello"); debug(1); payload"#).unwrap();
+        assert_eq!(output.printed.len(), 2);
+        assert_eq!(output.printed[0], "hello");
+
+        let failure = run(r#"print("before"); throw "broken";"#).unwrap_err();
+        assert_eq!(failure.printed, vec!["before".to_string()]);
+        assert!(failure.error.contains("broken"));
+
+        let flood = run("for n in 0..1000 { print(n); } payload").unwrap();
+        assert_eq!(flood.printed.len(), MAX_PRINTED);
+    }
+
+    #[test]
+    fn long_scripts_are_rejected() {
+        let source = format!("payload // {}", "x".repeat(MAX_SCRIPT_LEN));
+        assert!(Script::compile(&source).is_err());
+    }
+}
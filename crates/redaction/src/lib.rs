@@ -0,0 +1,409 @@
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::LazyLock;
+
+// ==============================================================================
+// REDACTION: Card-holder data out of payloads before they're stored anywhere
+// ==============================================================================
+//
+// Rules come from a TOML file (REDACTION_RULES in the services that use it),
+// each applying to the event types it names:
+//
+//   replacement = "[REDACTED]"          # optional, the default
+//
+//   [[rules]]
+//   event_types = ["*"]                 # the default; "payment.*" matches a prefix
+//   detect = ["pan", "email"]           # built-in detectors, in every string
+//
+//   [[rules]]
+//   event_types = ["payment.*"]
+//   fields = ["payment.customer.name", "payment.billing.*", "**.cvc"]
+//   patterns = ['\bIBAN[A-Z0-9 ]+\b']   # regexes, in every string
+//
+// A field path is dot-separated keys (array indexes are keys too) from the
+// payload's root; `*` is any one key, `**` any number of them. A matched
+// field's whole value is replaced, whatever its type. Detectors and patterns
+// replace only the matching part of each string, so "card 4242 4242 4242
+// 4242 declined" keeps its words. The built-in detectors:
+//
+//   pan     card numbers: 13-19 digits, spaces or dashes between, that pass
+//           the Luhn check (so order numbers mostly don't), in strings and
+//           as bare JSON numbers
+//   email   email addresses
+//   ssn     US social security numbers, 123-45-6789
+//
+// Redacting is idempotent: a payload redacted upstream (outbox-relay) and
+// again downstream (svix-caller) comes out the same, and the second pass
+// counts nothing.
+
+pub const DEFAULT_REPLACEMENT: &str = "[REDACTED]";
+
+/// Built-in detector names, for error messages
+pub const DETECTORS: &[&str] = &["pan", "email", "ssn"];
+
+static PAN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b\d(?:[ -]?\d){12,18}\b").unwrap());
+static EMAIL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap());
+static SSN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b\d{3}-\d{2}-\d{4}\b").unwrap());
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    replacement: Option<String>,
+    #[serde(default)]
+    rules: Vec<RuleSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    #[serde(default = "every_event_type")]
+    event_types: Vec<String>,
+    #[serde(default)]
+    fields: Vec<String>,
+    #[serde(default)]
+    detect: Vec<String>,
+    #[serde(default)]
+    patterns: Vec<String>,
+}
+
+fn every_event_type() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+enum Segment {
+    Key(String),
+    /// `*`
+    Any,
+    /// `**`
+    Deep,
+}
+
+enum Detector {
+    /// Matches are candidates; only Luhn-valid ones are replaced
+    Pan(Regex),
+    Regex(Regex),
+}
+
+struct Rule {
+    event_types: Vec<String>,
+    fields: Vec<Vec<Segment>>,
+    detectors: Vec<Detector>,
+}
+
+impl Rule {
+    fn applies_to(&self, event_type: &str) -> bool {
+        self.event_types
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => event_type.starts_with(prefix),
+                None => pattern == event_type,
+            })
+    }
+}
+
+pub struct Redactor {
+    rules: Vec<Rule>,
+    replacement: String,
+}
+
+impl Redactor {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file: RulesFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        let mut rules = Vec::with_capacity(file.rules.len());
+        for (i, spec) in file.rules.into_iter().enumerate() {
+            rules.push(compile(spec).map_err(|e| format!("rule {}: {}", i + 1, e))?);
+        }
+        Ok(Redactor {
+            rules,
+            replacement: file
+                .replacement
+                .unwrap_or_else(|| DEFAULT_REPLACEMENT.to_string()),
+        })
+    }
+
+    /// Redact `payload` in place with the rules for `event_type`; how many
+    /// values or matches were replaced
+    pub fn apply(&self, event_type: &str, payload: &mut Value) -> usize {
+        let mut redacted = 0;
+        for rule in self.rules.iter().filter(|rule| rule.applies_to(event_type)) {
+            for path in &rule.fields {
+                redacted += redact_path(payload, path, &self.replacement);
+            }
+            if !rule.detectors.is_empty() {
+                redacted += scrub(payload, &rule.detectors, &self.replacement);
+            }
+        }
+        redacted
+    }
+}
+
+fn compile(spec: RuleSpec) -> Result<Rule, String> {
+    if spec.fields.is_empty() && spec.detect.is_empty() && spec.patterns.is_empty() {
+        return Err("needs fields, detect or patterns".to_string());
+    }
+    if spec.event_types.is_empty() {
+        return Err("event_types is empty".to_string());
+    }
+    let fields = spec
+        .fields
+        .iter()
+        .map(|path| {
+            if path.is_empty() || path.split('.').any(str::is_empty) {
+                return Err(format!("invalid field path {:?}", path));
+            }
+            Ok(path
+                .split('.')
+                .map(|segment| match segment {
+                    "*" => Segment::Any,
+                    "**" => Segment::Deep,
+                    key => Segment::Key(key.to_string()),
+                })
+                .collect())
+        })
+        .collect::<Result<_, String>>()?;
+
+    let mut detectors = Vec::new();
+    for name in &spec.detect {
+        let detector = match name.as_str() {
+            "pan" => Detector::Pan(PAN.clone()),
+            "email" => Detector::Regex(EMAIL.clone()),
+            "ssn" => Detector::Regex(SSN.clone()),
+            other => {
+                return Err(format!(
+                    "unknown detector {:?}, expected one of {}",
+                    other,
+                    DETECTORS.join(", ")
+                ))
+            }
+        };
+        detectors.push(detector);
+    }
+    for pattern in &spec.patterns {
+        let regex =
+            Regex::new(pattern).map_err(|e| format!("invalid pattern {:?}: {}", pattern, e))?;
+        detectors.push(Detector::Regex(regex));
+    }
+
+    Ok(Rule {
+        event_types: spec.event_types,
+        fields,
+        detectors,
+    })
+}
+
+fn redact_path(value: &mut Value, path: &[Segment], replacement: &str) -> usize {
+    let Some((segment, rest)) = path.split_first() else {
+        if value.is_null() || value.as_str() == Some(replacement) {
+            return 0;
+        }
+        *value = Value::String(replacement.to_string());
+        return 1;
+    };
+    match segment {
+        Segment::Key(key) => match value {
+            Value::Object(map) => map
+                .get_mut(key)
+                .map_or(0, |child| redact_path(child, rest, replacement)),
+            Value::Array(items) => key
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| items.get_mut(index))
+                .map_or(0, |child| redact_path(child, rest, replacement)),
+            _ => 0,
+        },
+        Segment::Any => children(value)
+            .into_iter()
+            .map(|child| redact_path(child, rest, replacement))
+            .sum(),
+        // Zero keys here, or one more and still deep
+        Segment::Deep => {
+            let here = redact_path(value, rest, replacement);
+            here + children(value)
+                .into_iter()
+                .map(|child| redact_path(child, path, replacement))
+                .sum::<usize>()
+        }
+    }
+}
+
+fn children(value: &mut Value) -> Vec<&mut Value> {
+    match value {
+        Value::Object(map) => map.values_mut().collect(),
+        Value::Array(items) => items.iter_mut().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Every string (and, for pan, every number) anywhere in `value`
+fn scrub(value: &mut Value, detectors: &[Detector], replacement: &str) -> usize {
+    match value {
+        Value::String(text) => {
+            let mut redacted = 0;
+            for detector in detectors {
+                let (regex, luhn_only) = match detector {
+                    Detector::Pan(regex) => (regex, true),
+                    Detector::Regex(regex) => (regex, false),
+                };
+                let replaced = regex.replace_all(text, |caps: &Captures| {
+                    let found = &caps[0];
+                    if luhn_only && !luhn(found) {
+                        found.to_string()
+                    } else {
+                        redacted += 1;
+                        replacement.to_string()
+                    }
+                });
+                if let std::borrow::Cow::Owned(replaced) = replaced {
+                    *text = replaced;
+                }
+            }
+            redacted
+        }
+        Value::Number(number) => {
+            let digits = number.to_string();
+            let is_pan = detectors
+                .iter()
+                .any(|detector| matches!(detector, Detector::Pan(_)));
+            if is_pan && (13..=19).contains(&digits.len()) && luhn(&digits) {
+                *value = Value::String(replacement.to_string());
+                1
+            } else {
+                0
+            }
+        }
+        Value::Object(_) | Value::Array(_) => children(value)
+            .into_iter()
+            .map(|child| scrub(child, detectors, replacement))
+            .sum(),
+        Value::Null | Value::Bool(_) => 0,
+    }
+}
+
+/// The Luhn checksum over the digits of `candidate`, separators skipped
+fn luhn(candidate: &str) -> bool {
+    let digits: Vec<u32> = candidate.chars().filter_map(|c| c.to_digit(10)).collect();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &digit)| {
+            if i % 2 == 1 {
+                let doubled = digit * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                digit
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn detecting(detectors: &str) -> Redactor {
+        Redactor::parse(&format!("[[rules]]\ndetect = [{}]\n", detectors)).unwrap()
+    }
+
+    #[test]
+    fn luhn_accepts_valid_card_numbers() {
+        assert!(luhn("4242424242424242"));
+        assert!(luhn("4242 4242 4242 4242"));
+        assert!(luhn("5555-5555-5555-4444"));
+        assert!(luhn("378282246310005"));
+    }
+
+    #[test]
+    fn luhn_rejects_invalid_numbers() {
+        assert!(!luhn("4242424242424241"));
+        assert!(!luhn("1234567812345678"));
+        // Valid checksum, too short to be a card
+        assert!(!luhn("424242424242"));
+    }
+
+    #[test]
+    fn pan_redacts_card_numbers_in_strings() {
+        let redactor = detecting(r#""pan""#);
+        let mut payload = json!({"note": "card 4242 4242 4242 4242 declined"});
+        assert_eq!(redactor.apply("payment.failed", &mut payload), 1);
+        assert_eq!(payload, json!({"note": "card [REDACTED] declined"}));
+    }
+
+    #[test]
+    fn pan_redacts_card_numbers_as_json_numbers() {
+        let redactor = detecting(r#""pan""#);
+        let mut payload = json!({"card": 4242424242424242u64});
+        assert_eq!(redactor.apply("payment.failed", &mut payload), 1);
+        assert_eq!(payload, json!({"card": "[REDACTED]"}));
+    }
+
+    #[test]
+    fn pan_keeps_luhn_invalid_numbers() {
+        let redactor = detecting(r#""pan""#);
+        let mut payload = json!({"order": "order 1234567812345678", "amount": 1234567812345678u64});
+        let before = payload.clone();
+        assert_eq!(redactor.apply("payment.succeeded", &mut payload), 0);
+        assert_eq!(payload, before);
+    }
+
+    #[test]
+    fn email_redacts_addresses() {
+        let redactor = detecting(r#""email""#);
+        let mut payload =
+            json!({"customer": {"contact": "reach jane.doe+shop@example.co.uk today"}});
+        assert_eq!(redactor.apply("payment.succeeded", &mut payload), 1);
+        assert_eq!(
+            payload,
+            json!({"customer": {"contact": "reach [REDACTED] today"}})
+        );
+    }
+
+    #[test]
+    fn email_ignores_text_without_addresses() {
+        let redactor = detecting(r#""email""#);
+        let mut payload = json!({"note": "paid @ checkout", "handle": "user@localhost"});
+        assert_eq!(redactor.apply("payment.succeeded", &mut payload), 0);
+    }
+
+    #[test]
+    fn ssn_redacts_social_security_numbers() {
+        let redactor = detecting(r#""ssn""#);
+        let mut payload = json!({"ssn": "123-45-6789", "phone": "555-123-4567"});
+        assert_eq!(redactor.apply("payment.succeeded", &mut payload), 1);
+        assert_eq!(
+            payload,
+            json!({"ssn": "[REDACTED]", "phone": "555-123-4567"})
+        );
+    }
+
+    #[test]
+    fn redacting_twice_counts_nothing_the_second_time() {
+        let redactor = detecting(r#""pan", "email""#);
+        let mut payload = json!({"note": "4242424242424242 for a@b.io"});
+        assert_eq!(redactor.apply("payment.succeeded", &mut payload), 2);
+        assert_eq!(redactor.apply("payment.succeeded", &mut payload), 0);
+    }
+
+    #[test]
+    fn unknown_detector_is_an_error() {
+        assert!(Redactor::parse("[[rules]]\ndetect = [\"phone\"]\n").is_err());
+    }
+}
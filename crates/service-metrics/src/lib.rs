@@ -0,0 +1,270 @@
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::header;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use prometheus::{
+    Encoder, Gauge, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+// ==============================================================================
+// SERVICE METRICS: The families every service exposes on /metrics
+// ==============================================================================
+//
+// Each service registers its own metrics (data-service's cache, svix-caller's
+// Svix calls, ...) in the registry from here, so one scrape returns both. The
+// common families have the same names everywhere, so one dashboard covers
+// every service:
+//
+//   service_info{service, version}        always 1
+//   process_*                             CPU, memory, open fds, start time;
+//                                         read from /proc at scrape time, so
+//                                         0 outside Linux
+//   http_requests_total                   by method, route and status, from
+//   http_request_duration_seconds         track_http; routes are the axum
+//                                         patterns (/payload/:payment_id), to
+//                                         keep the label set bounded
+//   kafka_messages_consumed_total         for a service that reads Kafka
+//   kafka_consumer_lag                    itself (Restate does today), see
+//                                         KafkaMetrics
+//
+// Scrape on /metrics: mount router() or call render() from a handler that
+// samples something first.
+
+/// Kernel clock ticks per second, for the CPU times in /proc/self/stat
+const CLOCK_TICKS: f64 = 100.0;
+
+#[derive(Clone)]
+pub struct ServiceMetrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    process: ProcessMetrics,
+    kafka: KafkaMetrics,
+}
+
+#[derive(Clone)]
+struct ProcessMetrics {
+    cpu_seconds: Gauge,
+    resident_memory: IntGauge,
+    open_fds: IntGauge,
+}
+
+/// Consumption counters for a Kafka consumer; registered with the rest, so
+/// they only show up once something is recorded
+#[derive(Clone)]
+pub struct KafkaMetrics {
+    consumed: IntCounterVec,
+    lag: IntGaugeVec,
+}
+
+impl ServiceMetrics {
+    pub fn new(service: &str, version: &str) -> Self {
+        let registry = Registry::new();
+
+        let info = IntGaugeVec::new(
+            Opts::new(
+                "service_info",
+                "Always 1, labelled with the service and its version",
+            ),
+            &["service", "version"],
+        )
+        .unwrap();
+        info.with_label_values(&[service, version]).set(1);
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time to respond to HTTP requests",
+            )
+            .buckets(vec![
+                0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+            ]),
+            &["method", "route"],
+        )
+        .unwrap();
+
+        let start_time = Gauge::new(
+            "process_start_time_seconds",
+            "Start time of the process since the Unix epoch",
+        )
+        .unwrap();
+        start_time.set(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0.0, |since| since.as_secs_f64()),
+        );
+        let process = ProcessMetrics {
+            cpu_seconds: Gauge::new(
+                "process_cpu_seconds_total",
+                "User and system CPU time spent",
+            )
+            .unwrap(),
+            resident_memory: IntGauge::new("process_resident_memory_bytes", "Resident memory size")
+                .unwrap(),
+            open_fds: IntGauge::new("process_open_fds", "Open file descriptors").unwrap(),
+        };
+
+        let kafka = KafkaMetrics {
+            consumed: IntCounterVec::new(
+                Opts::new(
+                    "kafka_messages_consumed_total",
+                    "Kafka messages consumed, by topic",
+                ),
+                &["topic"],
+            )
+            .unwrap(),
+            lag: IntGaugeVec::new(
+                Opts::new(
+                    "kafka_consumer_lag",
+                    "Messages between the committed offset and the end of the partition",
+                ),
+                &["topic", "partition"],
+            )
+            .unwrap(),
+        };
+
+        registry.register(Box::new(info)).unwrap();
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_duration.clone())).unwrap();
+        registry.register(Box::new(start_time)).unwrap();
+        registry
+            .register(Box::new(process.cpu_seconds.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(process.resident_memory.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(process.open_fds.clone()))
+            .unwrap();
+        registry.register(Box::new(kafka.consumed.clone())).unwrap();
+        registry.register(Box::new(kafka.lag.clone())).unwrap();
+
+        ServiceMetrics {
+            registry,
+            http_requests,
+            http_duration,
+            process,
+            kafka,
+        }
+    }
+
+    /// Where the service registers its own metrics
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    pub fn kafka(&self) -> &KafkaMetrics {
+        &self.kafka
+    }
+
+    /// Everything in the registry, in Prometheus' text format
+    pub fn render(&self) -> Response {
+        self.process.sample();
+
+        let encoder = TextEncoder::new();
+        let mut buffer = Vec::new();
+        if let Err(e) = encoder.encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!("Failed to encode metrics: {}", e);
+        }
+        (
+            [(header::CONTENT_TYPE, encoder.format_type().to_string())],
+            buffer,
+        )
+            .into_response()
+    }
+}
+
+impl ProcessMetrics {
+    fn sample(&self) {
+        // Fields 14 and 15 are utime and stime; the command name before them
+        // (in parentheses) may contain spaces
+        if let Ok(stat) = std::fs::read_to_string("/proc/self/stat") {
+            let fields: Vec<&str> = stat
+                .rsplit_once(')')
+                .map_or(Vec::new(), |(_, rest)| rest.split_whitespace().collect());
+            let ticks: u64 = fields.get(11..13).map_or(0, |times| {
+                times.iter().filter_map(|t| t.parse::<u64>().ok()).sum()
+            });
+            self.cpu_seconds.set(ticks as f64 / CLOCK_TICKS);
+        }
+        if let Ok(status) = std::fs::read_to_string("/proc/self/status") {
+            let rss_kb = status
+                .lines()
+                .find_map(|line| line.strip_prefix("VmRSS:"))
+                .and_then(|value| {
+                    value
+                        .trim()
+                        .trim_end_matches("kB")
+                        .trim()
+                        .parse::<i64>()
+                        .ok()
+                });
+            if let Some(rss_kb) = rss_kb {
+                self.resident_memory.set(rss_kb * 1024);
+            }
+        }
+        if let Ok(fds) = std::fs::read_dir("/proc/self/fd") {
+            self.open_fds.set(fds.count() as i64);
+        }
+    }
+}
+
+impl KafkaMetrics {
+    pub fn consumed(&self, topic: &str) {
+        self.consumed.with_label_values(&[topic]).inc();
+    }
+
+    pub fn lag(&self, topic: &str, partition: i32, lag: i64) {
+        self.lag
+            .with_label_values(&[topic, &partition.to_string()])
+            .set(lag);
+    }
+}
+
+/// Middleware counting requests and timing responses; add it with
+/// `route_layer`, which is where axum knows the matched route
+pub async fn track_http(
+    State(metrics): State<ServiceMetrics>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str())
+        .to_string();
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+
+    metrics
+        .http_duration
+        .with_label_values(&[&method, &route])
+        .observe(started.elapsed().as_secs_f64());
+    metrics
+        .http_requests
+        .with_label_values(&[&method, &route, response.status().as_str()])
+        .inc();
+    response
+}
+
+async fn metrics_handler(State(metrics): State<ServiceMetrics>) -> Response {
+    metrics.render()
+}
+
+/// GET /metrics, to merge into the service's router
+pub fn router<S>(metrics: ServiceMetrics) -> Router<S> {
+    Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(metrics)
+}
@@ -0,0 +1,319 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::time::Duration;
+
+// ==============================================================================
+// WEBHOOK SIGNING: Standard Webhooks scheme (same as Svix), both ends
+// ==============================================================================
+//
+//   signature = base64(HMAC-SHA256(key, "{msg_id}.{timestamp}.{body}"))
+//   header    = "v1,<sig>" entries separated by spaces, one per active secret
+//   secret    = "whsec_<base64 key>"
+//
+// Senders (api-service's test webhooks, svix-caller's direct fallback,
+// old-architecture when given a secret) call sign() with active_secrets(), so
+// during a rotation window a message carries a signature for the new and the
+// previous secret and the receiver accepts it whichever one it has
+// configured. Receivers (merchant-simulator, svix-caller's operational
+// webhooks, and any real merchant in Rust) build a Verifier, which:
+//
+//   - rejects timestamps further than the tolerance from now (replays)
+//   - accepts the message if any v1 entry matches any of its keys, so a
+//     receiver can hold both secrets while it rotates too
+//   - compares MACs in constant time (hmac's verify_slice), so response
+//     timing gives nothing away about the expected signature
+
+pub const SECRET_PREFIX: &str = "whsec_";
+
+/// How far a signed timestamp may be from now, either way
+pub const DEFAULT_TOLERANCE: Duration = Duration::from_secs(5 * 60);
+
+/// The three headers a signed message travels with
+pub struct HeaderNames {
+    pub id: &'static str,
+    pub timestamp: &'static str,
+    pub signature: &'static str,
+}
+
+/// What our own deliveries send
+pub const WEBHOOK_HEADERS: HeaderNames = HeaderNames {
+    id: "webhook-id",
+    timestamp: "webhook-timestamp",
+    signature: "webhook-signature",
+};
+
+/// What Svix sends (its operational webhooks, and messages to merchants)
+pub const SVIX_HEADERS: HeaderNames = HeaderNames {
+    id: "svix-id",
+    timestamp: "svix-timestamp",
+    signature: "svix-signature",
+};
+
+/// New random secret in "whsec_<base64>" form
+pub fn generate_secret() -> String {
+    let mut key = [0u8; 24];
+    rand::thread_rng().fill_bytes(&mut key);
+    format!("{}{}", SECRET_PREFIX, STANDARD.encode(key))
+}
+
+/// The HMAC key inside a secret; the whsec_ prefix is optional
+pub fn decode_secret(secret: &str) -> Result<Vec<u8>, String> {
+    STANDARD
+        .decode(secret.trim_start_matches(SECRET_PREFIX))
+        .map_err(|e| format!("Invalid signing secret: {}", e))
+}
+
+/// Secrets a delivery must be signed with right now: the current one, plus the
+/// previous one until its rotation window closes
+pub fn active_secrets<'a>(
+    current: &'a str,
+    previous: Option<&'a str>,
+    previous_expires_at: Option<DateTime<Utc>>,
+) -> Vec<&'a str> {
+    let mut secrets = vec![current];
+    if let (Some(previous), Some(expires_at)) = (previous, previous_expires_at) {
+        if expires_at > Utc::now() {
+            secrets.push(previous);
+        }
+    }
+    secrets
+}
+
+/// Value for the signature header, signed with every secret given
+pub fn sign(secrets: &[&str], msg_id: &str, timestamp: i64, body: &[u8]) -> Result<String, String> {
+    let mut signatures = Vec::with_capacity(secrets.len());
+
+    for secret in secrets {
+        let key = decode_secret(secret)?;
+        let mac = mac(&key, msg_id, &timestamp.to_string(), body)?;
+        signatures.push(format!(
+            "v1,{}",
+            STANDARD.encode(mac.finalize().into_bytes())
+        ));
+    }
+
+    Ok(signatures.join(" "))
+}
+
+/// Checks signed messages against one or more secrets
+#[derive(Clone)]
+pub struct Verifier {
+    keys: Vec<Vec<u8>>,
+    tolerance: Duration,
+}
+
+impl Verifier {
+    /// Accepts messages signed with any of the secrets
+    pub fn new(secrets: &[&str]) -> Result<Self, String> {
+        if secrets.is_empty() {
+            return Err("No signing secret given".to_string());
+        }
+        let keys = secrets
+            .iter()
+            .map(|secret| decode_secret(secret))
+            .collect::<Result<_, _>>()?;
+        Ok(Verifier {
+            keys,
+            tolerance: DEFAULT_TOLERANCE,
+        })
+    }
+
+    pub fn with_tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Verify from the request's headers; `header` looks one up by name
+    pub fn verify_headers<'h>(
+        &self,
+        names: &HeaderNames,
+        header: impl Fn(&str) -> Option<&'h str>,
+        body: &[u8],
+    ) -> Result<(), String> {
+        let get = |name: &str| header(name).ok_or_else(|| format!("Missing {} header", name));
+        self.verify(
+            get(names.id)?,
+            get(names.timestamp)?,
+            get(names.signature)?,
+            body,
+        )
+    }
+
+    /// Verify from the three header values
+    pub fn verify(
+        &self,
+        msg_id: &str,
+        timestamp: &str,
+        signatures: &str,
+        body: &[u8],
+    ) -> Result<(), String> {
+        let sent_at: i64 = timestamp
+            .parse()
+            .map_err(|_| format!("Invalid timestamp {:?}", timestamp))?;
+        let skew = Utc::now().timestamp().abs_diff(sent_at);
+        if skew > self.tolerance.as_secs() {
+            return Err(format!(
+                "Timestamp {}s from now, tolerance is {}s",
+                skew,
+                self.tolerance.as_secs()
+            ));
+        }
+
+        let signatures: Vec<Vec<u8>> = signatures
+            .split(' ')
+            .filter_map(|entry| entry.strip_prefix("v1,"))
+            .filter_map(|signature| STANDARD.decode(signature).ok())
+            .collect();
+
+        let valid = self.keys.iter().any(|key| {
+            signatures.iter().any(|signature| {
+                mac(key, msg_id, timestamp, body)
+                    .is_ok_and(|mac| mac.verify_slice(signature).is_ok())
+            })
+        });
+
+        if valid {
+            Ok(())
+        } else {
+            Err("No matching signature".to_string())
+        }
+    }
+}
+
+fn mac(key: &[u8], msg_id: &str, timestamp: &str, body: &[u8]) -> Result<Hmac<Sha256>, String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key)
+        .map_err(|e| format!("Invalid signing secret: {}", e))?;
+    mac.update(format!("{}.{}.", msg_id, timestamp).as_bytes());
+    mac.update(body);
+    Ok(mac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // From the Standard Webhooks (and Svix) reference test suite
+    const REFERENCE_SECRET: &str = "whsec_MfKQ9r8GKYqrTwjUPD8ILPZIo2LaLaSw";
+    const REFERENCE_MSG_ID: &str = "msg_p5jXN8AQM9LWM0D4loKWxJek";
+    const REFERENCE_TIMESTAMP: i64 = 1614265330;
+    const REFERENCE_BODY: &[u8] = br#"{"test": 2432232314}"#;
+    const REFERENCE_SIGNATURE: &str = "v1,g0hM9SsE+OTPJTGt/tmIKtSyZlE3uFJELVlNIOLJ1OE=";
+
+    fn now() -> i64 {
+        Utc::now().timestamp()
+    }
+
+    #[test]
+    fn sign_matches_the_reference_vector() {
+        let signature = sign(
+            &[REFERENCE_SECRET],
+            REFERENCE_MSG_ID,
+            REFERENCE_TIMESTAMP,
+            REFERENCE_BODY,
+        )
+        .unwrap();
+        assert_eq!(signature, REFERENCE_SIGNATURE);
+    }
+
+    #[test]
+    fn verify_accepts_the_reference_vector() {
+        let verifier = Verifier::new(&[REFERENCE_SECRET])
+            .unwrap()
+            .with_tolerance(Duration::from_secs(u64::MAX));
+        verifier
+            .verify(
+                REFERENCE_MSG_ID,
+                &REFERENCE_TIMESTAMP.to_string(),
+                REFERENCE_SIGNATURE,
+                REFERENCE_BODY,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn sign_then_verify_round_trips() {
+        let secret = generate_secret();
+        let timestamp = now();
+        let signature = sign(&[&secret], "msg_1", timestamp, b"{}").unwrap();
+        let verifier = Verifier::new(&[&secret]).unwrap();
+        verifier
+            .verify("msg_1", &timestamp.to_string(), &signature, b"{}")
+            .unwrap();
+
+        // Any change to what was signed breaks it
+        assert!(verifier
+            .verify("msg_2", &timestamp.to_string(), &signature, b"{}")
+            .is_err());
+        assert!(verifier
+            .verify("msg_1", &timestamp.to_string(), &signature, b"{ }")
+            .is_err());
+        assert!(Verifier::new(&[&generate_secret()])
+            .unwrap()
+            .verify("msg_1", &timestamp.to_string(), &signature, b"{}")
+            .is_err());
+    }
+
+    #[test]
+    fn verify_rejects_timestamps_outside_the_tolerance() {
+        let secret = generate_secret();
+        let verifier = Verifier::new(&[&secret])
+            .unwrap()
+            .with_tolerance(Duration::from_secs(60));
+        for timestamp in [now() - 120, now() + 120] {
+            let signature = sign(&[&secret], "msg_1", timestamp, b"{}").unwrap();
+            assert!(verifier
+                .verify("msg_1", &timestamp.to_string(), &signature, b"{}")
+                .is_err());
+        }
+        assert!(verifier.verify("msg_1", "soon", "v1,AAAA", b"{}").is_err());
+    }
+
+    #[test]
+    fn previous_secret_is_accepted_until_it_expires() {
+        let current = generate_secret();
+        let previous = generate_secret();
+        let timestamp = now();
+        // A sender that still only has the previous secret
+        let signature = sign(&[&previous], "msg_1", timestamp, b"{}").unwrap();
+
+        let rotating = active_secrets(
+            &current,
+            Some(&previous),
+            Some(Utc::now() + chrono::Duration::hours(1)),
+        );
+        assert_eq!(rotating.len(), 2);
+        Verifier::new(&rotating)
+            .unwrap()
+            .verify("msg_1", &timestamp.to_string(), &signature, b"{}")
+            .unwrap();
+
+        let expired = active_secrets(
+            &current,
+            Some(&previous),
+            Some(Utc::now() - chrono::Duration::seconds(1)),
+        );
+        assert_eq!(expired, vec![current.as_str()]);
+        assert!(Verifier::new(&expired)
+            .unwrap()
+            .verify("msg_1", &timestamp.to_string(), &signature, b"{}")
+            .is_err());
+    }
+
+    #[test]
+    fn signing_during_rotation_satisfies_either_receiver() {
+        let current = generate_secret();
+        let previous = generate_secret();
+        let timestamp = now();
+        let signature = sign(&[&current, &previous], "msg_1", timestamp, b"{}").unwrap();
+        assert_eq!(signature.split(' ').count(), 2);
+        for secret in [&current, &previous] {
+            Verifier::new(&[secret.as_str()])
+                .unwrap()
+                .verify("msg_1", &timestamp.to_string(), &signature, b"{}")
+                .unwrap();
+        }
+    }
+}
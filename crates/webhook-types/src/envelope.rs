@@ -0,0 +1,118 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// ==============================================================================
+// ENVELOPE: Schema versions, and what a consumer does with one it doesn't know
+// ==============================================================================
+//
+// Every event message carries `schema_version`. Producers stamp
+// SCHEMA_VERSION (domain_events has the column, so the rows Sequin publishes
+// carry it; services serializing a message set it from here). Consumers read
+// messages through negotiate() instead of deserializing straight away:
+//
+//   missing            written before versioning; the v1 shape
+//   older than current upgraded step by step (upgrade()), then read
+//   current            read
+//   newer than current VersionError::Unsupported: the producer was deployed
+//                      ahead of this consumer. Rejecting it explicitly (not
+//                      retrying, recorded and visible) beats a generic
+//                      deserialization error on a field that changed shape
+//
+// Adding a field with a serde default doesn't need a new version; renaming,
+// removing or re-typing one does, together with a step in upgrade().
+//
+// EventEnvelope is the version-independent form of an event (id, type,
+// occurred_at, data, metadata), for consumers that don't need the outbox row.
+
+/// The version this build writes and reads natively
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Messages without a version predate it and have the v1 shape
+pub fn default_schema_version() -> u32 {
+    1
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct EventEnvelope {
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    /// RFC 3339; None when the producer didn't say
+    #[serde(default)]
+    pub occurred_at: Option<String>,
+    pub data: serde_json::Value,
+    /// Routing and tracing details: merchant_id, object_id, mode, replay_of
+    #[serde(default)]
+    pub metadata: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug)]
+pub enum VersionError {
+    /// schema_version isn't a version number at all
+    Invalid(String),
+    /// Written by a newer producer than this consumer understands
+    Unsupported { found: u32, newest: u32 },
+    /// A version we know, but the message doesn't match it
+    Malformed { version: u32, error: String },
+}
+
+impl fmt::Display for VersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VersionError::Invalid(version) => write!(f, "invalid schema_version {}", version),
+            VersionError::Unsupported { found, newest } => write!(
+                f,
+                "unsupported schema_version {} (this consumer reads up to {})",
+                found, newest
+            ),
+            VersionError::Malformed { version, error } => {
+                write!(f, "invalid schema_version {} message: {}", version, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VersionError {}
+
+/// The message's schema_version, without reading anything else
+pub fn schema_version(message: &serde_json::Value) -> Result<u32, VersionError> {
+    match message.get("schema_version") {
+        None | Some(serde_json::Value::Null) => Ok(default_schema_version()),
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .filter(|version| *version >= 1)
+            .ok_or_else(|| VersionError::Invalid(version.to_string())),
+    }
+}
+
+/// Read a versioned message as the current version of T, or say why not
+pub fn negotiate<T: DeserializeOwned>(message: serde_json::Value) -> Result<T, VersionError> {
+    let version = schema_version(&message)?;
+    if version > SCHEMA_VERSION {
+        return Err(VersionError::Unsupported {
+            found: version,
+            newest: SCHEMA_VERSION,
+        });
+    }
+    let message = upgrade(version, message);
+    serde_json::from_value(message).map_err(|e| VersionError::Malformed {
+        version,
+        error: e.to_string(),
+    })
+}
+
+/// Bring an older message up to SCHEMA_VERSION. There is only v1 so far;
+/// v2 will add `1 => ...` here, converting the v1 shape.
+fn upgrade(version: u32, mut message: serde_json::Value) -> serde_json::Value {
+    if version < SCHEMA_VERSION {
+        if let Some(fields) = message.as_object_mut() {
+            fields.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+        }
+    }
+    message
+}
@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+mod envelope;
+
+pub use envelope::{
+    default_schema_version, negotiate, schema_version, EventEnvelope, VersionError, SCHEMA_VERSION,
+};
+
+// ==============================================================================
+// WEBHOOK TYPES: The messages passed between services
+// ==============================================================================
+//
+// One definition of each message that crosses a service boundary, so producer
+// and consumer can't drift apart:
+//
+//   DomainEvent     domain_events row, as Sequin publishes it to Kafka and
+//                   Restate hands it to svix-caller
+//   WebhookPayload  the body merchants receive (svix-caller, old-architecture;
+//                   read by merchant-simulator)
+//   PaymentPayload  the v1 payment inside it (data-service, old-architecture)
+//   PayloadVersion  which shape of it a merchant receives (api-service sets
+//                   merchants.payload_version, data-service renders it)
+//   EventEnvelope   an event without the outbox details (envelope.rs)
+//   DeliveryEvent   one delivery attempt's outcome, as svix-caller publishes
+//                   it to Kafka (KAFKA_DELIVERY_EVENTS_TOPIC)
+//
+// Fields are only ever added, with a serde default, so a service built
+// against an older version keeps reading newer messages. Anything more is a
+// new schema_version, which consumers negotiate (envelope.rs).
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct DomainEvent {
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
+    pub id: u64,
+    pub event_type: String,
+    pub object_id: String,
+    pub merchant_id: String,
+    /// "test" or "live"; rows written before the mode column existed are live
+    #[serde(default = "default_mode")]
+    pub mode: String,
+    pub payload: serde_json::Value,
+    /// Set on rows re-inserted by api-service's POST /events/replay
+    #[serde(default)]
+    pub replay_of: Option<u64>,
+    /// RFC 3339, as Sequin publishes the column
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+impl DomainEvent {
+    pub fn envelope(&self) -> EventEnvelope {
+        let mut metadata = serde_json::Map::new();
+        metadata.insert("object_id".to_string(), self.object_id.clone().into());
+        metadata.insert("merchant_id".to_string(), self.merchant_id.clone().into());
+        metadata.insert("mode".to_string(), self.mode.clone().into());
+        if let Some(replay_of) = self.replay_of {
+            metadata.insert("replay_of".to_string(), replay_of.into());
+        }
+        EventEnvelope {
+            schema_version: self.schema_version,
+            id: format!("evt_{}", self.id),
+            event_type: self.event_type.clone(),
+            occurred_at: self.created_at.clone(),
+            data: self.payload.clone(),
+            metadata,
+        }
+    }
+}
+
+/// One attempt at delivering a DomainEvent, mirroring its delivery_attempts
+/// row; with endpoint_id set, one endpoint's part in a direct delivery
+/// (delivery_path 'direct_endpoint'); keyed by merchant_id on the topic
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeliveryEvent {
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
+    pub event_id: u64,
+    pub event_type: String,
+    pub object_id: String,
+    pub merchant_id: String,
+    /// svix, direct, svix_endpoint or merchant
+    pub delivery_path: String,
+    /// succeeded, failed, skipped, dry_run or dead_lettered
+    pub status: String,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// What the merchant's endpoint answered, when the path saw it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_status: Option<u16>,
+    /// From the event's creation to this attempt's outcome
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    /// The merchant_endpoints row the attempt went to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint_id: Option<String>,
+    /// How long the endpoint took to answer, when it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_ms: Option<u64>,
+    /// No answer before the delivery timeout
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+}
+
+/// "live", the mode of anything that doesn't say
+pub fn default_mode() -> String {
+    "live".to_string()
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct WebhookPayload {
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
+    /// A UUID, as text
+    pub event_id: String,
+    pub event_type: String,
+    /// Rendered by data-service in the merchant's payload version (and with
+    /// its field policy applied), forwarded as-is; PaymentPayload for v1
+    pub payment: serde_json::Value,
+}
+
+/// The v1 payment payload. Money fields are absent when the merchant's field
+/// policy strips them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct PaymentPayload {
+    pub id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    pub status: String,
+    #[serde(default = "default_mode")]
+    pub mode: String,
+    /// amount in major units, e.g. "25.00" for 2500 USD
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount_decimal: Option<String>,
+}
+
+/// Payload shape delivered to the merchant. New versions are opt-in so
+/// existing integrations keep receiving the shape they were built against.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadVersion {
+    #[default]
+    V1,
+    V2,
+}
+
+impl PayloadVersion {
+    /// Lenient, for headers and columns: "v2", "V2" and "2" are all v2
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "v1" | "1" => Some(PayloadVersion::V1),
+            "v2" | "2" => Some(PayloadVersion::V2),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PayloadVersion::V1 => "v1",
+            PayloadVersion::V2 => "v2",
+        }
+    }
+}
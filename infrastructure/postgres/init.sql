@@ -2,12 +2,157 @@
 
 -- TABLES
 
+-- mode ('test' | 'live') is carried by merchants, payments, and domain_events.
+-- Test payments flow through the same pipeline but are tagged so they can be
+-- delivered to separate Svix applications. merchants.mode is the default for
+-- payments created without an explicit mode.
+CREATE TABLE IF NOT EXISTS merchants (
+    id UUID PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    mode VARCHAR(4) NOT NULL DEFAULT 'live' CHECK (mode IN ('test', 'live')),
+    -- NULL means unlimited; enforced by api-service before emitting events
+    monthly_event_quota BIGINT,
+    -- 'current': webhooks carry the payment as it is when delivered
+    -- 'snapshot': webhooks carry the payment as it was when the event was created
+    payload_mode VARCHAR(10) NOT NULL DEFAULT 'current' CHECK (payload_mode IN ('current', 'snapshot')),
+    -- Field policy applied by data-service: NULL allowlist means every field,
+    -- redacted fields are stripped after the allowlist
+    payload_allowed_fields TEXT[],
+    payload_redacted_fields TEXT[],
+    -- Payload shape delivered to this merchant; bumped when they migrate
+    payload_version VARCHAR(2) NOT NULL DEFAULT 'v1' CHECK (payload_version IN ('v1', 'v2')),
+    -- Merchant's own body shape, rendered by svix-caller (transform.rs) before
+    -- delivery through Svix or directly; NULL delivers the standard body
+    payload_template JSONB,
+    -- Set for merchants that acknowledge each webhook through POST /confirm;
+    -- svix-caller waits this long for it (confirmation.rs). NULL: no waiting
+    confirmation_timeout_secs INTEGER CHECK (confirmation_timeout_secs > 0),
+    -- Svix region/account holding the merchant's applications (a key of
+    -- svix-caller's SVIX_REGION_TOKENS); NULL uses SVIX_AUTH_TOKEN
+    svix_region VARCHAR(16),
+    -- Proxy svix-caller's direct deliveries leave through (direct.rs):
+    -- NULL uses EGRESS_PROXY_URL, 'none' connects directly
+    egress_proxy TEXT,
+    -- Daily window svix-caller hands the merchant's events over in
+    -- (windows.rs), in local time of delivery_window_timezone (an IANA name);
+    -- an end before the start wraps past midnight. NULL: any time
+    delivery_window_start TIME,
+    delivery_window_end TIME,
+    delivery_window_timezone TEXT,
+    -- Set while the merchant's deliveries are paused: svix-caller holds its
+    -- events until it's cleared, then sends them in order. NULL: not paused
+    delivery_paused_at TIMESTAMPTZ,
+    delivery_pause_reason TEXT,
+    -- Event types svix-caller collects into one digest.summary webhook every
+    -- digest_interval_secs (digest.rs) instead of sending each. NULL: none
+    digest_event_types TEXT[],
+    digest_interval_secs INTEGER CHECK (digest_interval_secs BETWEEN 60 AND 86400),
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    CHECK ((delivery_window_start IS NULL) = (delivery_window_end IS NULL)
+       AND (delivery_window_start IS NULL) = (delivery_window_timezone IS NULL)
+       AND delivery_window_start <> delivery_window_end),
+    CHECK ((digest_event_types IS NULL) = (digest_interval_secs IS NULL))
+);
+
+-- Events per merchant per calendar month (UTC), maintained by trigger.
+-- quota_exceeded_at makes sure quota.exceeded is emitted once per period.
+CREATE TABLE IF NOT EXISTS merchant_usage (
+    merchant_id UUID NOT NULL,
+    period DATE NOT NULL,
+    event_count BIGINT NOT NULL DEFAULT 0,
+    quota_exceeded_at TIMESTAMPTZ,
+    PRIMARY KEY (merchant_id, period)
+);
+
+-- Planned maintenance on the merchant's side: svix-caller holds its events
+-- from starts_at until ends_at (windows.rs)
+CREATE TABLE IF NOT EXISTS merchant_maintenance_windows (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    merchant_id UUID NOT NULL REFERENCES merchants(id),
+    starts_at TIMESTAMPTZ NOT NULL,
+    ends_at TIMESTAMPTZ NOT NULL,
+    reason TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (ends_at > starts_at)
+);
+
+-- Secrets use the Standard Webhooks "whsec_<base64>" format. After a rotation
+-- the previous secret stays valid until previous_secret_expires_at, and
+-- deliveries are signed with both so merchants can switch without downtime.
+CREATE TABLE IF NOT EXISTS merchant_endpoints (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    merchant_id UUID NOT NULL REFERENCES merchants(id),
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    previous_secret TEXT,
+    previous_secret_expires_at TIMESTAMPTZ,
+    disabled BOOLEAN NOT NULL DEFAULT FALSE,
+    -- For endpoints behind mutual TLS: a PEM certificate, and its private key
+    -- sealed with the merchant's data key (payload-crypto); set together
+    client_certificate TEXT,
+    client_key JSONB,
+    -- Expression over the webhook body (event-filter crate), e.g.
+    -- payment.amount > 10000; only events it's true for are delivered here.
+    -- NULL: every event
+    filter TEXT,
+    -- The endpoint_transformations version its deliveries are reshaped with;
+    -- NULL sends the body as it is
+    transformation_version INTEGER,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW(),
+    CHECK ((client_certificate IS NULL) = (client_key IS NULL))
+);
+
+-- Rhai scripts reshaping an endpoint's webhook body (payload-script crate),
+-- every saved version kept so an endpoint can go back to an earlier one
+CREATE TABLE IF NOT EXISTS endpoint_transformations (
+    endpoint_id UUID NOT NULL REFERENCES merchant_endpoints(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    script TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (endpoint_id, version)
+);
+
+-- Keys merchants present to merchant-portal, issued by api-service. Only the
+-- SHA-256 of the key is stored; the key itself is shown once, on creation.
+CREATE TABLE IF NOT EXISTS merchant_api_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    merchant_id UUID NOT NULL REFERENCES merchants(id),
+    key_hash TEXT NOT NULL UNIQUE,
+    -- The first characters of the key, so merchants can tell keys apart
+    key_prefix TEXT NOT NULL,
+    name TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ
+);
+
+-- Amounts are stored in minor units (cents for USD, yen for JPY).
+-- min_amount/max_amount bound a single payment, also in minor units.
+CREATE TABLE IF NOT EXISTS currencies (
+    code VARCHAR(3) PRIMARY KEY,
+    minor_units SMALLINT NOT NULL CHECK (minor_units BETWEEN 0 AND 4),
+    min_amount BIGINT NOT NULL DEFAULT 1,
+    max_amount BIGINT NOT NULL
+);
+
+-- external_ref is the merchant's own id for the customer, echoed in webhooks
+-- so they can match payments without storing our ids
+CREATE TABLE IF NOT EXISTS customers (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    merchant_id UUID NOT NULL REFERENCES merchants(id),
+    external_ref TEXT,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
 CREATE TABLE IF NOT EXISTS payments (
     id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
     amount BIGINT NOT NULL,
-    currency VARCHAR(3) NOT NULL DEFAULT 'USD',
+    currency VARCHAR(3) NOT NULL DEFAULT 'USD' REFERENCES currencies(code),
     status VARCHAR(50) NOT NULL DEFAULT 'pending',
     merchant_id UUID NOT NULL,
+    customer_id UUID REFERENCES customers(id),
+    mode VARCHAR(4) NOT NULL DEFAULT 'live' CHECK (mode IN ('test', 'live')),
     created_at TIMESTAMPTZ DEFAULT NOW(),
     updated_at TIMESTAMPTZ DEFAULT NOW()
 );
@@ -17,40 +162,348 @@ CREATE TABLE IF NOT EXISTS domain_events (
     event_type VARCHAR(100) NOT NULL,
     object_id UUID NOT NULL,
     merchant_id UUID NOT NULL,
+    mode VARCHAR(4) NOT NULL DEFAULT 'live',
     payload JSONB,
+    -- Set on rows re-inserted by POST /events/replay, points at the original
+    -- event. No foreign key: replays from the archive (event-archiver's POST
+    -- /replays) point at events no longer in this table.
+    replay_of BIGINT,
+    -- Shape of the row as a message (webhook-types' SCHEMA_VERSION); published
+    -- by Sequin with the row, so consumers can reject versions they don't know
+    schema_version INT NOT NULL DEFAULT 1,
+    -- Set by outbox-relay once Kafka acknowledged the row; NULL: not yet published
+    published_at TIMESTAMPTZ,
     created_at TIMESTAMPTZ DEFAULT NOW()
 );
 
+CREATE TABLE IF NOT EXISTS delivery_attempts (
+    id BIGSERIAL PRIMARY KEY,
+    event_id BIGINT NOT NULL REFERENCES domain_events(id),
+    merchant_id UUID NOT NULL,
+    -- 'svix' | 'svix_endpoint' | 'direct' | 'direct_endpoint' | 'merchant' |
+    -- 'digest'
+    delivery_path VARCHAR(20) NOT NULL,
+    -- 'succeeded' | 'failed' | 'skipped' | 'rate_limited' | 'dry_run', and
+    -- 'confirmed' | 'unconfirmed' on the 'merchant' path; on the 'digest'
+    -- path 'digested' when set aside, 'succeeded' once in a digest
+    status VARCHAR(20) NOT NULL,
+    error TEXT,
+    -- What the merchant's endpoint answered, when the path saw it:
+    -- 'direct_endpoint' records both, 'svix_endpoint' only the status Svix
+    -- reports
+    response_status INTEGER,
+    response_body TEXT,
+    -- The merchant_endpoints row of a 'direct_endpoint' attempt. No foreign
+    -- key: attempts outlive deleted endpoints.
+    endpoint_id UUID,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+-- Events set aside for the merchant's next digest (svix-caller's digest.rs),
+-- with the body they'd have been delivered with; digest_event_id is the
+-- digest.summary event that carried them. event-archiver deletes the rows
+-- with their events.
+CREATE TABLE IF NOT EXISTS digest_items (
+    event_id BIGINT PRIMARY KEY REFERENCES domain_events(id),
+    merchant_id UUID NOT NULL,
+    mode VARCHAR(4) NOT NULL,
+    event_type VARCHAR(100) NOT NULL,
+    body JSONB NOT NULL,
+    digest_event_id BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Where each endpoint stands with a direct delivery (svix-caller's direct.rs):
+-- a retry only goes to the endpoints that haven't had the event yet, once
+-- their next_attempt_at comes. event-archiver deletes the rows with their
+-- events.
+CREATE TABLE IF NOT EXISTS endpoint_deliveries (
+    event_id BIGINT NOT NULL REFERENCES domain_events(id),
+    endpoint_id UUID NOT NULL,
+    merchant_id UUID NOT NULL,
+    status VARCHAR(20) NOT NULL,  -- 'succeeded' | 'failed'
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    response_status INTEGER,
+    -- NULL on a failed endpoint without a health score: due on the next retry
+    next_attempt_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (event_id, endpoint_id)
+);
+
+-- Payment state captured in the same transaction as each event, served by
+-- data-service to merchants using payload_mode = 'snapshot'
+CREATE TABLE IF NOT EXISTS payload_snapshots (
+    event_id BIGINT PRIMARY KEY REFERENCES domain_events(id),
+    payment_id UUID NOT NULL,
+    payload JSONB NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+-- Which service read which payment's payload, written by data-service.
+-- No foreign key to payments: the trail has to outlive the data it covers.
+CREATE TABLE IF NOT EXISTS payload_access_log (
+    id BIGSERIAL PRIMARY KEY,
+    caller_service VARCHAR(100) NOT NULL,
+    payment_id UUID NOT NULL,
+    event_id BIGINT,
+    transport VARCHAR(10) NOT NULL,
+    stale BOOLEAN NOT NULL DEFAULT FALSE,
+    accessed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Events svix-caller gave up on (payload rejected, payment or Svix
+-- application gone), written with everything needed to send them again.
+-- redriven_at is set when DeadLetters/redrive sends the event again,
+-- purged_at when an operator drops it; neither is re-driven after that.
+CREATE TABLE IF NOT EXISTS svix_dead_letters (
+    id BIGSERIAL PRIMARY KEY,
+    event_id BIGINT NOT NULL,
+    merchant_id UUID NOT NULL,
+    -- The DomainEvent as svix-caller received it
+    event JSONB NOT NULL,
+    -- The message body, when it got that far
+    payload JSONB,
+    error TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    redriven_at TIMESTAMPTZ,
+    purged_at TIMESTAMPTZ
+);
+
+-- Per-merchant data keys sealing payloads at rest (payload-crypto crate),
+-- only ever stored wrapped by a master key. One unretired key per merchant
+-- seals new payloads; retired ones still open what they sealed.
+CREATE TABLE IF NOT EXISTS merchant_data_keys (
+    id BIGSERIAL PRIMARY KEY,
+    merchant_id UUID NOT NULL,
+    -- Which PAYLOAD_MASTER_KEYS entry (or KMS key) wrapped it
+    master_key_id VARCHAR(64) NOT NULL,
+    wrapped_key BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    retired_at TIMESTAMPTZ
+);
+
+-- Who re-drove or purged which dead letters through api-service, and why
+CREATE TABLE IF NOT EXISTS dead_letter_actions (
+    id BIGSERIAL PRIMARY KEY,
+    -- redrive or purge
+    action VARCHAR(20) NOT NULL,
+    -- The X-Operator header of the request
+    operator VARCHAR(255) NOT NULL,
+    reason TEXT,
+    -- The selection as requested: ids, merchant_id, from, to, limit
+    selection JSONB NOT NULL,
+    -- The entries it actually applied to
+    dead_letter_ids BIGINT[] NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Objects event-archiver wrote to S3: delivered events past its cutoff, with
+-- their delivery_attempts and payload_snapshots, deleted from those tables
+-- once listed here. One gzipped JSONL object per day per batch.
+CREATE TABLE IF NOT EXISTS archived_objects (
+    key TEXT PRIMARY KEY,
+    -- UTC day of the events' created_at
+    day DATE NOT NULL,
+    first_event_id BIGINT NOT NULL,
+    last_event_id BIGINT NOT NULL,
+    events INT NOT NULL,
+    attempts INT NOT NULL,
+    -- Compressed
+    bytes BIGINT NOT NULL,
+    -- Every merchant with an event in the object
+    merchant_ids UUID[] NOT NULL,
+    archived_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Replays event-archiver hydrated from archived_objects back into
+-- domain_events: who asked, for which merchant and range, and what it wrote
+CREATE TABLE IF NOT EXISTS archive_replays (
+    id BIGSERIAL PRIMARY KEY,
+    -- The X-Operator header of the request
+    operator VARCHAR(255) NOT NULL,
+    reason TEXT,
+    merchant_id UUID NOT NULL,
+    -- created_at of the archived events, from inclusive, to exclusive
+    from_time TIMESTAMPTZ NOT NULL,
+    to_time TIMESTAMPTZ NOT NULL,
+    event_types TEXT[],
+    -- archived_objects read
+    objects INT NOT NULL,
+    -- The new domain_events rows, replay_of set to the archived ids
+    event_ids BIGINT[] NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Every change to merchant configuration (merchants, endpoints and their
+-- secrets, filters and transformations, API keys, maintenance windows) and
+-- every replay, written by the audit triggers below. Append-only: rows are
+-- never updated or deleted.
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    -- audit.actor of the transaction: an X-Operator header, or
+    -- 'api_key:<id>' for merchant-portal; the database user when unset
+    actor VARCHAR(255) NOT NULL,
+    -- audit.source: the service the change came through
+    source VARCHAR(64),
+    merchant_id UUID,
+    -- merchant, endpoint, endpoint_transformation, api_key,
+    -- maintenance_window, replay, archive_replay, dead_letter_action
+    entity_type VARCHAR(40) NOT NULL,
+    entity_id TEXT NOT NULL,
+    -- create, update, delete; replay, redrive or purge for actions
+    action VARCHAR(20) NOT NULL,
+    -- The row before and after the change (NULL when created or deleted),
+    -- secrets and keys replaced by a fingerprint
+    before JSONB,
+    after JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Hourly rollups of webhook-delivery-events, kept by delivery-analytics.
+-- Latency and status codes are counted per bucket in tables of their own, so
+-- percentiles and breakdowns can be summed over any range of hours.
+CREATE TABLE IF NOT EXISTS delivery_rollups (
+    merchant_id UUID NOT NULL,
+    hour TIMESTAMPTZ NOT NULL,
+    event_type VARCHAR(100) NOT NULL,
+    delivery_path VARCHAR(20) NOT NULL,
+    attempts BIGINT NOT NULL DEFAULT 0,
+    succeeded BIGINT NOT NULL DEFAULT 0,
+    failed BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (merchant_id, hour, event_type, delivery_path)
+);
+
+-- Attempts whose latency fell at or under le_ms (and over the next bucket down)
+CREATE TABLE IF NOT EXISTS delivery_rollup_latency (
+    merchant_id UUID NOT NULL,
+    hour TIMESTAMPTZ NOT NULL,
+    event_type VARCHAR(100) NOT NULL,
+    delivery_path VARCHAR(20) NOT NULL,
+    le_ms BIGINT NOT NULL,
+    count BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (merchant_id, hour, event_type, delivery_path, le_ms)
+);
+
+-- Failed attempts by the endpoint's response status
+CREATE TABLE IF NOT EXISTS delivery_rollup_status_codes (
+    merchant_id UUID NOT NULL,
+    hour TIMESTAMPTZ NOT NULL,
+    event_type VARCHAR(100) NOT NULL,
+    delivery_path VARCHAR(20) NOT NULL,
+    status_code INTEGER NOT NULL,
+    count BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (merchant_id, hour, event_type, delivery_path, status_code)
+);
+
+-- Where delivery-analytics resumes each partition; written in the same
+-- transaction as the rollups, so no record is counted twice
+CREATE TABLE IF NOT EXISTS delivery_analytics_offsets (
+    topic VARCHAR(255) NOT NULL,
+    partition INTEGER NOT NULL,
+    next_offset BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (topic, partition)
+);
+
+-- Rolling health per merchant endpoint, kept by delivery-analytics from
+-- direct deliveries. The counters decay (halving every HEALTH_HALF_LIFE_SECS
+-- since updated_at), so they're weights rather than counts; score is 0-100
+-- and svix-caller spaces its direct-delivery retries by it.
+CREATE TABLE IF NOT EXISTS endpoint_health (
+    endpoint_id UUID PRIMARY KEY,
+    merchant_id UUID NOT NULL,
+    attempts DOUBLE PRECISION NOT NULL DEFAULT 0,
+    succeeded DOUBLE PRECISION NOT NULL DEFAULT 0,
+    timed_out DOUBLE PRECISION NOT NULL DEFAULT 0,
+    responses DOUBLE PRECISION NOT NULL DEFAULT 0,
+    response_ms_sum DOUBLE PRECISION NOT NULL DEFAULT 0,
+    score DOUBLE PRECISION,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
 -- INDEXES
 
+CREATE INDEX IF NOT EXISTS idx_merchant_endpoints_merchant_id ON merchant_endpoints(merchant_id);
+CREATE INDEX IF NOT EXISTS idx_merchant_api_keys_merchant_id ON merchant_api_keys(merchant_id);
+CREATE INDEX IF NOT EXISTS idx_merchant_maintenance_windows_merchant ON merchant_maintenance_windows(merchant_id, ends_at);
 CREATE INDEX IF NOT EXISTS idx_payments_merchant_id ON payments(merchant_id);
 CREATE INDEX IF NOT EXISTS idx_payments_status ON payments(status);
-CREATE INDEX IF NOT EXISTS idx_domain_events_merchant_id ON domain_events(merchant_id);
+CREATE INDEX IF NOT EXISTS idx_payments_mode ON payments(mode);
+CREATE INDEX IF NOT EXISTS idx_payload_access_log_payment ON payload_access_log(payment_id, accessed_at);
+CREATE INDEX IF NOT EXISTS idx_payload_access_log_caller ON payload_access_log(caller_service, accessed_at);
+CREATE INDEX IF NOT EXISTS idx_payments_updated_at ON payments(updated_at, id);
+CREATE INDEX IF NOT EXISTS idx_domain_events_merchant_id ON domain_events(merchant_id, id);
 CREATE INDEX IF NOT EXISTS idx_domain_events_created_at ON domain_events(created_at);
+CREATE INDEX IF NOT EXISTS idx_domain_events_object_id ON domain_events(object_id);
+-- outbox-relay's queue: only the rows still to publish
+CREATE INDEX IF NOT EXISTS idx_domain_events_unpublished ON domain_events(id) WHERE published_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_domain_events_event_type ON domain_events(event_type);
+-- event-archiver's check that no replay still points at an event
+CREATE INDEX IF NOT EXISTS idx_domain_events_replay_of ON domain_events(replay_of) WHERE replay_of IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_delivery_attempts_event_id ON delivery_attempts(event_id);
+CREATE INDEX IF NOT EXISTS idx_delivery_rollups_hour ON delivery_rollups(hour);
+CREATE INDEX IF NOT EXISTS idx_delivery_rollup_latency_hour ON delivery_rollup_latency(hour);
+CREATE INDEX IF NOT EXISTS idx_delivery_rollup_status_codes_hour ON delivery_rollup_status_codes(hour);
+CREATE INDEX IF NOT EXISTS idx_digest_items_pending ON digest_items(merchant_id, event_id) WHERE digest_event_id IS NULL;
+CREATE INDEX IF NOT EXISTS idx_svix_dead_letters_pending ON svix_dead_letters(merchant_id, id) WHERE redriven_at IS NULL AND purged_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_dead_letter_actions_ids ON dead_letter_actions USING GIN (dead_letter_ids);
+CREATE INDEX IF NOT EXISTS idx_archived_objects_day ON archived_objects(day);
+CREATE INDEX IF NOT EXISTS idx_archived_objects_merchants ON archived_objects USING GIN (merchant_ids);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_merchant_data_keys_active ON merchant_data_keys(merchant_id) WHERE retired_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_archive_replays_merchant ON archive_replays(merchant_id, id);
+CREATE INDEX IF NOT EXISTS idx_audit_log_merchant ON audit_log(merchant_id, id);
+CREATE INDEX IF NOT EXISTS idx_audit_log_entity ON audit_log(entity_type, entity_id, id);
+CREATE INDEX IF NOT EXISTS idx_endpoint_health_merchant ON endpoint_health(merchant_id, score);
 
 -- PUBLICATION FOR CDC (Sequin)
 
 DROP PUBLICATION IF EXISTS domain_events_pub CASCADE;
 CREATE PUBLICATION domain_events_pub FOR TABLE domain_events;
 
+-- cdc-consumer's (services/new-architecture/cdc-consumer); its slot is
+-- created on first start
+DROP PUBLICATION IF EXISTS cdc_pub CASCADE;
+CREATE PUBLICATION cdc_pub FOR TABLE domain_events, payments;
+
 -- TRIGGERS
 
 CREATE OR REPLACE FUNCTION notify_payment_status_change()
 RETURNS TRIGGER AS $$
+DECLARE
+    new_event_id BIGINT;
 BEGIN
     -- Insert event on INSERT or if status changed on UPDATE
     IF (TG_OP = 'INSERT') OR (OLD.status IS DISTINCT FROM NEW.status) THEN
-        INSERT INTO domain_events (event_type, object_id, merchant_id, payload)
+        INSERT INTO domain_events (event_type, object_id, merchant_id, mode, payload)
         VALUES (
             'payment.' || LOWER(NEW.status),
             NEW.id,
             NEW.merchant_id,
+            NEW.mode,
             jsonb_build_object(
                 'payment_id', NEW.id,
                 'amount', NEW.amount,
                 'currency', NEW.currency,
                 'status', NEW.status,
-                'merchant_id', NEW.merchant_id
+                'merchant_id', NEW.merchant_id,
+                'mode', NEW.mode
+            )
+        )
+        RETURNING id INTO new_event_id;
+
+        INSERT INTO payload_snapshots (event_id, payment_id, payload)
+        VALUES (
+            new_event_id,
+            NEW.id,
+            jsonb_build_object(
+                'id', NEW.id,
+                'amount', NEW.amount,
+                'currency', NEW.currency,
+                'status', NEW.status,
+                'mode', NEW.mode,
+                'updated_at', NEW.updated_at
             )
         );
     END IF;
@@ -64,14 +517,312 @@ AFTER INSERT OR UPDATE ON payments
 FOR EACH ROW
 EXECUTE FUNCTION notify_payment_status_change();
 
+-- data-service LISTENs on this channel to evict cached payloads
+CREATE OR REPLACE FUNCTION notify_payment_updated()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('payment_updated', NEW.id::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS payment_updated_notify_trigger ON payments;
+CREATE TRIGGER payment_updated_notify_trigger
+AFTER UPDATE ON payments
+FOR EACH ROW
+EXECUTE FUNCTION notify_payment_updated();
+
+-- ...and on this one to evict every payload of a merchant whose field policy
+-- changed, since cached payloads have the old policy applied
+CREATE OR REPLACE FUNCTION notify_merchant_policy_updated()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('merchant_policy_updated', NEW.id::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS merchant_policy_updated_notify_trigger ON merchants;
+CREATE TRIGGER merchant_policy_updated_notify_trigger
+AFTER UPDATE OF payload_allowed_fields, payload_redacted_fields ON merchants
+FOR EACH ROW
+WHEN (OLD.payload_allowed_fields IS DISTINCT FROM NEW.payload_allowed_fields
+      OR OLD.payload_redacted_fields IS DISTINCT FROM NEW.payload_redacted_fields)
+EXECUTE FUNCTION notify_merchant_policy_updated();
+
+-- Operational events (quota.*) are not billable, everything else counts
+CREATE OR REPLACE FUNCTION track_merchant_usage()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.event_type NOT LIKE 'quota.%' THEN
+        INSERT INTO merchant_usage (merchant_id, period, event_count)
+        VALUES (NEW.merchant_id, date_trunc('month', NOW() AT TIME ZONE 'UTC')::DATE, 1)
+        ON CONFLICT (merchant_id, period)
+        DO UPDATE SET event_count = merchant_usage.event_count + 1;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS domain_event_usage_trigger ON domain_events;
+CREATE TRIGGER domain_event_usage_trigger
+AFTER INSERT ON domain_events
+FOR EACH ROW
+EXECUTE FUNCTION track_merchant_usage();
+
+-- outbox-relay LISTENs on this channel to publish new events without waiting
+-- for its next poll; once per statement, the relay reads every new row anyway
+CREATE OR REPLACE FUNCTION notify_domain_event_created()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('domain_event_created', '');
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS domain_event_created_notify_trigger ON domain_events;
+CREATE TRIGGER domain_event_created_notify_trigger
+AFTER INSERT ON domain_events
+FOR EACH STATEMENT
+EXECUTE FUNCTION notify_domain_event_created();
+
+-- Configuration changes into audit_log. Services set who is acting for the
+-- transaction (SELECT set_config('audit.actor', ..., true)); the columns
+-- that only track activity (updated_at, last_used_at) don't count as a
+-- change, secrets go in as the start of their SHA-256, enough to see that
+-- one was rotated, and proxy credentials are masked as api-service shows them.
+CREATE OR REPLACE FUNCTION audit_row(row_data JSONB)
+RETURNS JSONB AS $$
+DECLARE
+    field TEXT;
+BEGIN
+    row_data := row_data - 'updated_at' - 'last_used_at';
+    FOREACH field IN ARRAY ARRAY['secret', 'previous_secret', 'client_key', 'key_hash'] LOOP
+        IF jsonb_typeof(row_data -> field) <> 'null' THEN
+            row_data := jsonb_set(row_data, ARRAY[field], to_jsonb(
+                'sha256:' || left(encode(sha256(convert_to(row_data ->> field, 'UTF8')), 'hex'), 12)
+            ));
+        END IF;
+    END LOOP;
+    IF jsonb_typeof(row_data -> 'egress_proxy') = 'string' THEN
+        row_data := jsonb_set(row_data, '{egress_proxy}', to_jsonb(
+            regexp_replace(row_data ->> 'egress_proxy', '^([^:/]+://).*@', '\1***@')
+        ));
+    END IF;
+    RETURN row_data;
+END;
+$$ LANGUAGE plpgsql IMMUTABLE;
+
+CREATE OR REPLACE FUNCTION record_audit(
+    entity_type TEXT,
+    entity_id TEXT,
+    merchant_id UUID,
+    action TEXT,
+    before JSONB,
+    after JSONB,
+    actor TEXT DEFAULT NULL
+)
+RETURNS VOID AS $$
+    INSERT INTO audit_log (actor, source, merchant_id, entity_type, entity_id, action, before, after)
+    VALUES (
+        COALESCE($7, NULLIF(current_setting('audit.actor', true), ''), session_user),
+        NULLIF(current_setting('audit.source', true), ''),
+        $3, $1, $2, $4, $5, $6
+    );
+$$ LANGUAGE sql;
+
+-- TG_ARGV[0] is the entity_type
+CREATE OR REPLACE FUNCTION audit_config_change()
+RETURNS TRIGGER AS $$
+DECLARE
+    before JSONB;
+    after JSONB;
+    row_data JSONB;
+    merchant UUID;
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        before := audit_row(to_jsonb(OLD));
+    END IF;
+    IF TG_OP <> 'DELETE' THEN
+        after := audit_row(to_jsonb(NEW));
+    END IF;
+    IF before = after THEN
+        RETURN NULL;
+    END IF;
+
+    row_data := COALESCE(after, before);
+    merchant := CASE
+        WHEN TG_TABLE_NAME = 'merchants' THEN (row_data ->> 'id')::UUID
+        WHEN row_data ? 'merchant_id' THEN (row_data ->> 'merchant_id')::UUID
+        ELSE (SELECT e.merchant_id FROM merchant_endpoints e
+              WHERE e.id = (row_data ->> 'endpoint_id')::UUID)
+    END;
+    PERFORM record_audit(
+        TG_ARGV[0],
+        COALESCE(row_data ->> 'id', (row_data ->> 'endpoint_id') || '/v' || (row_data ->> 'version')),
+        merchant,
+        CASE TG_OP WHEN 'INSERT' THEN 'create' WHEN 'UPDATE' THEN 'update' ELSE 'delete' END,
+        before,
+        after
+    );
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS merchants_audit_trigger ON merchants;
+CREATE TRIGGER merchants_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON merchants
+FOR EACH ROW
+EXECUTE FUNCTION audit_config_change('merchant');
+
+DROP TRIGGER IF EXISTS merchant_endpoints_audit_trigger ON merchant_endpoints;
+CREATE TRIGGER merchant_endpoints_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON merchant_endpoints
+FOR EACH ROW
+EXECUTE FUNCTION audit_config_change('endpoint');
+
+-- Versions are never changed, and go with their endpoint
+DROP TRIGGER IF EXISTS endpoint_transformations_audit_trigger ON endpoint_transformations;
+CREATE TRIGGER endpoint_transformations_audit_trigger
+AFTER INSERT ON endpoint_transformations
+FOR EACH ROW
+EXECUTE FUNCTION audit_config_change('endpoint_transformation');
+
+DROP TRIGGER IF EXISTS merchant_api_keys_audit_trigger ON merchant_api_keys;
+CREATE TRIGGER merchant_api_keys_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON merchant_api_keys
+FOR EACH ROW
+EXECUTE FUNCTION audit_config_change('api_key');
+
+DROP TRIGGER IF EXISTS merchant_maintenance_windows_audit_trigger ON merchant_maintenance_windows;
+CREATE TRIGGER merchant_maintenance_windows_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON merchant_maintenance_windows
+FOR EACH ROW
+EXECUTE FUNCTION audit_config_change('maintenance_window');
+
+-- Re-drives, purges and archive replays already name their operator; they
+-- go into audit_log as they're recorded, with what they applied to
+CREATE OR REPLACE FUNCTION audit_dead_letter_action()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM record_audit(
+        'dead_letter_action',
+        NEW.id::TEXT,
+        (SELECT CASE WHEN COUNT(DISTINCT d.merchant_id) = 1 THEN MIN(d.merchant_id::TEXT)::UUID END
+         FROM svix_dead_letters d WHERE d.id = ANY(NEW.dead_letter_ids)),
+        NEW.action,
+        NULL,
+        to_jsonb(NEW),
+        NEW.operator
+    );
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS dead_letter_actions_audit_trigger ON dead_letter_actions;
+CREATE TRIGGER dead_letter_actions_audit_trigger
+AFTER INSERT ON dead_letter_actions
+FOR EACH ROW
+EXECUTE FUNCTION audit_dead_letter_action();
+
+CREATE OR REPLACE FUNCTION audit_archive_replay()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM record_audit(
+        'archive_replay',
+        NEW.id::TEXT,
+        NEW.merchant_id,
+        'replay',
+        NULL,
+        to_jsonb(NEW) - 'event_ids' || jsonb_build_object('events', cardinality(NEW.event_ids)),
+        NEW.operator
+    );
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS archive_replays_audit_trigger ON archive_replays;
+CREATE TRIGGER archive_replays_audit_trigger
+AFTER INSERT ON archive_replays
+FOR EACH ROW
+EXECUTE FUNCTION audit_archive_replay();
+
+CREATE OR REPLACE FUNCTION reject_audit_log_change()
+RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS audit_log_append_only_trigger ON audit_log;
+CREATE TRIGGER audit_log_append_only_trigger
+BEFORE UPDATE OR DELETE ON audit_log
+FOR EACH ROW
+EXECUTE FUNCTION reject_audit_log_change();
+
+DROP TRIGGER IF EXISTS audit_log_truncate_trigger ON audit_log;
+CREATE TRIGGER audit_log_truncate_trigger
+BEFORE TRUNCATE ON audit_log
+FOR EACH STATEMENT
+EXECUTE FUNCTION reject_audit_log_change();
+
 -- PERMISSIONS
 
+GRANT ALL ON currencies TO dodo;
+GRANT ALL ON merchants TO dodo;
+GRANT ALL ON merchant_endpoints TO dodo;
+GRANT ALL ON endpoint_transformations TO dodo;
+GRANT ALL ON merchant_api_keys TO dodo;
+GRANT ALL ON merchant_usage TO dodo;
+GRANT ALL ON merchant_maintenance_windows TO dodo;
+GRANT ALL ON customers TO dodo;
 GRANT ALL ON payments TO dodo;
 GRANT ALL ON domain_events TO dodo;
 GRANT ALL ON SEQUENCE domain_events_id_seq TO dodo;
+GRANT ALL ON delivery_attempts TO dodo;
+GRANT ALL ON payload_snapshots TO dodo;
+GRANT ALL ON SEQUENCE delivery_attempts_id_seq TO dodo;
+GRANT ALL ON payload_access_log TO dodo;
+GRANT ALL ON SEQUENCE payload_access_log_id_seq TO dodo;
+GRANT ALL ON svix_dead_letters TO dodo;
+GRANT ALL ON SEQUENCE svix_dead_letters_id_seq TO dodo;
+GRANT ALL ON dead_letter_actions TO dodo;
+GRANT ALL ON SEQUENCE dead_letter_actions_id_seq TO dodo;
+GRANT ALL ON delivery_rollups TO dodo;
+GRANT ALL ON delivery_rollup_latency TO dodo;
+GRANT ALL ON delivery_rollup_status_codes TO dodo;
+GRANT ALL ON delivery_analytics_offsets TO dodo;
+GRANT ALL ON merchant_data_keys TO dodo;
+GRANT ALL ON SEQUENCE merchant_data_keys_id_seq TO dodo;
+GRANT ALL ON archived_objects TO dodo;
+GRANT ALL ON archive_replays TO dodo;
+GRANT ALL ON SEQUENCE archive_replays_id_seq TO dodo;
+GRANT ALL ON endpoint_health TO dodo;
+GRANT ALL ON endpoint_deliveries TO dodo;
+GRANT ALL ON digest_items TO dodo;
+-- Appended to and read, never changed
+GRANT SELECT, INSERT ON audit_log TO dodo;
+GRANT USAGE ON SEQUENCE audit_log_id_seq TO dodo;
 
 -- INITIAL DATA
 
+INSERT INTO currencies (code, minor_units, min_amount, max_amount) VALUES
+    ('USD', 2, 50, 99999999),
+    ('EUR', 2, 50, 99999999),
+    ('GBP', 2, 30, 99999999),
+    ('INR', 2, 100, 9999999999),
+    ('CAD', 2, 50, 99999999),
+    ('AUD', 2, 50, 99999999),
+    ('JPY', 0, 50, 9999999999),
+    ('KRW', 0, 100, 99999999999),
+    ('KWD', 3, 100, 9999999999),
+    ('BHD', 3, 100, 9999999999)
+ON CONFLICT DO NOTHING;
+
+INSERT INTO merchants (id, name, mode)
+VALUES ('bc1852a0-6e4d-5399-a35a-391ceaf44f80'::UUID, 'Test Merchant', 'live')
+ON CONFLICT DO NOTHING;
+
 INSERT INTO payments (merchant_id, amount, currency, status)
 VALUES ('bc1852a0-6e4d-5399-a35a-391ceaf44f80'::UUID, 1000, 'USD', 'pending')
 ON CONFLICT DO NOTHING;
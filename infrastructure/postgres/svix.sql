@@ -0,0 +1,3 @@
+-- Database for the self-hosted Svix server (docker compose --profile svix-local).
+-- Svix creates its own tables on startup.
+CREATE DATABASE svix OWNER dodo;
@@ -0,0 +1,18 @@
+#!/bin/bash
+
+# Build the service images the e2e tests (tests/e2e) start, tagged :e2e.
+# old-api gets the crash-injection feature, the tests crash it on request.
+
+set -e
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+PROJECT_ROOT="$(dirname "$SCRIPT_DIR")"
+cd "$PROJECT_ROOT"
+
+docker build -f services/old-architecture/Dockerfile \
+    --build-arg CARGO_FEATURES=crash-injection -t webhook-old-api:e2e .
+docker build -f services/merchant-simulator/Dockerfile -t webhook-merchant-simulator:e2e .
+docker build -f services/new-architecture/api-service/Dockerfile -t webhook-api-service:e2e .
+docker build -f services/new-architecture/data-service/Dockerfile -t webhook-data-service:e2e .
+
+echo "E2E images built. Run: cargo test -p e2e -- --ignored"
@@ -0,0 +1,119 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::AppState;
+
+// ==============================================================================
+// CHAOS: A merchant endpoint that misbehaves on request
+// ==============================================================================
+//
+// Real merchant endpoints are slow, time out and fail. PUT /chaos makes this
+// one do the same, for the resilience demos (tools/chaos-orchestrator):
+//
+//   {"failure_rate": 0.3, "status": 503, "delay_ms": 2000}
+//
+// delays every webhook by 2s, then answers 30% of them with 503 without
+// recording them, as a merchant that failed to process them would.
+// GET /chaos shows the current setting, DELETE /chaos turns it off.
+//
+// The simulator keeps no database, so changes aren't in audit_log; each one
+// is logged as a `config_audit` event instead, with the setting before and
+// after and who changed it (the X-Operator header, or "anonymous").
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Chaos {
+    /// Share of webhooks answered with `status`, between 0 and 1
+    pub failure_rate: f64,
+    pub status: u16,
+    /// Wait this long before answering any webhook
+    pub delay_ms: u64,
+}
+
+impl Default for Chaos {
+    fn default() -> Self {
+        Chaos {
+            failure_rate: 0.0,
+            status: 500,
+            delay_ms: 0,
+        }
+    }
+}
+
+impl Chaos {
+    /// Apply the delay; the status to fail this webhook with, if any
+    pub async fn strike(&self) -> Option<StatusCode> {
+        if self.delay_ms > 0 {
+            tokio::time::sleep(Duration::from_millis(self.delay_ms)).await;
+        }
+        let fail = self.failure_rate > 0.0 && rand::thread_rng().gen_bool(self.failure_rate);
+        fail.then(|| StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR))
+    }
+}
+
+pub async fn get_chaos(State(state): State<AppState>) -> Json<Chaos> {
+    Json(state.chaos.read().clone())
+}
+
+/// Who is changing chaos, for the config_audit event
+fn actor(headers: &HeaderMap) -> &str {
+    headers
+        .get("x-operator")
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|operator| !operator.is_empty())
+        .unwrap_or("anonymous")
+}
+
+fn audit(headers: &HeaderMap, before: &Chaos, after: &Chaos) {
+    info!(
+        target: "config_audit",
+        actor = actor(headers),
+        entity_type = "chaos",
+        before = %serde_json::json!(before),
+        after = %serde_json::json!(after),
+        "chaos changed"
+    );
+}
+
+pub async fn set_chaos(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(chaos): Json<Chaos>,
+) -> Result<Json<Chaos>, (StatusCode, String)> {
+    if !(0.0..=1.0).contains(&chaos.failure_rate) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "failure_rate must be between 0 and 1".to_string(),
+        ));
+    }
+    if !(400..=599).contains(&chaos.status) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "status must be a 4xx or 5xx code".to_string(),
+        ));
+    }
+    warn!(
+        "Chaos on: {:.0}% of webhooks fail with {}, {}ms delay",
+        chaos.failure_rate * 100.0,
+        chaos.status,
+        chaos.delay_ms
+    );
+    let before = std::mem::replace(&mut *state.chaos.write(), chaos.clone());
+    audit(&headers, &before, &chaos);
+    Ok(Json(chaos))
+}
+
+pub async fn clear_chaos(State(state): State<AppState>, headers: HeaderMap) -> StatusCode {
+    let before = std::mem::take(&mut *state.chaos.write());
+    audit(&headers, &before, &Chaos::default());
+    info!("Chaos off");
+    StatusCode::NO_CONTENT
+}
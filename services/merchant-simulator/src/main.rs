@@ -1,15 +1,26 @@
 use axum::{
-    extract::{Json, State},
-    http::StatusCode,
+    body::Bytes,
+    extract::{Json, Query, State},
+    http::{HeaderMap, StatusCode},
+    middleware,
     routing::{get, post},
     Router,
 };
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
+use service_metrics::ServiceMetrics;
 use std::collections::HashSet;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{info, warn};
 use uuid::Uuid;
+use webhook_signing::{Verifier, WEBHOOK_HEADERS};
+use webhook_types::{VersionError, WebhookPayload};
+
+mod chaos;
+mod settings;
+
+use chaos::Chaos;
+use settings::Settings;
 
 // ==============================================================================
 // MERCHANT SIMULATOR: Mock webhook endpoint that tracks received webhooks
@@ -18,23 +29,29 @@ use uuid::Uuid;
 #[derive(Clone)]
 struct AppState {
     received_webhooks: Arc<RwLock<Vec<ReceivedWebhook>>>,
+    /// Failures and delays to inject (chaos.rs)
+    chaos: Arc<RwLock<Chaos>>,
+    /// Signature check for /webhooks when WEBHOOK_SECRET is set
+    verifier: Option<Arc<Verifier>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct ReceivedWebhook {
-    event_id: Uuid,
+    event_id: String,
     event_type: String,
     payment_id: Uuid,
     amount: i64,
     status: String,
+    /// "test" or "live", taken from the payment; older payloads have none
+    mode: String,
     received_at: String,
+    /// Same moment as Unix milliseconds, for latency measurements (sla-monitor)
+    received_at_ms: i64,
 }
 
-#[derive(Serialize, Deserialize)]
-struct WebhookPayload {
-    event_id: Uuid,
-    event_type: String,
-    payment: serde_json::Value,
+#[derive(Deserialize)]
+struct StatsQuery {
+    mode: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -46,21 +63,44 @@ struct StatsResponse {
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    logging::init(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    let settings: Settings = config::load_or_exit("merchant-simulator");
+    let verifier = settings
+        .verifier()
+        .expect("checked by Settings::validate")
+        .map(Arc::new);
+    if verifier.is_some() {
+        info!("Verifying webhook signatures");
+    }
 
     let state = AppState {
         received_webhooks: Arc::new(RwLock::new(Vec::new())),
+        chaos: Arc::new(RwLock::new(Chaos::default())),
+        verifier,
     };
+    let metrics = ServiceMetrics::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
 
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/webhooks", post(receive_webhook))
         .route("/stats", get(get_stats))
         .route("/reset", post(reset_webhooks))
+        .route(
+            "/chaos",
+            get(chaos::get_chaos)
+                .put(chaos::set_chaos)
+                .delete(chaos::clear_chaos),
+        )
+        .merge(service_metrics::router(metrics.clone()))
+        .route_layer(middleware::from_fn_with_state(
+            metrics,
+            service_metrics::track_http,
+        ))
         .with_state(state);
 
-    let port = std::env::var("PORT").unwrap_or_else(|_| "4000".to_string());
-    let instance = std::env::var("INSTANCE_NAME").unwrap_or_else(|_| "merchant".to_string());
+    let port = settings.port;
+    let instance = settings.instance_name;
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port))
         .await
@@ -75,10 +115,53 @@ async fn health_check() -> &'static str {
     "OK"
 }
 
+/// Check the signature (when configured) before anything is parsed, as a real
+/// merchant would, then read the body in a schema_version this build knows
 async fn receive_webhook(
     State(state): State<AppState>,
-    Json(payload): Json<WebhookPayload>,
+    headers: HeaderMap,
+    body: Bytes,
 ) -> (StatusCode, String) {
+    if let Some(verifier) = &state.verifier {
+        let verified = verifier.verify_headers(
+            &WEBHOOK_HEADERS,
+            |name| headers.get(name).and_then(|value| value.to_str().ok()),
+            &body,
+        );
+        if let Err(e) = verified {
+            warn!("Rejected webhook: {}", e);
+            return (StatusCode::UNAUTHORIZED, e);
+        }
+    }
+
+    let message: serde_json::Value = match serde_json::from_slice(&body) {
+        Ok(message) => message,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid webhook: {}", e)),
+    };
+    match webhook_types::negotiate::<WebhookPayload>(message) {
+        Ok(payload) => record_webhook(state, payload).await,
+        // A version this build can't read: refuse it rather than record a
+        // half-understood webhook
+        Err(e @ VersionError::Unsupported { .. }) => {
+            warn!("Rejected webhook: {}", e);
+            (StatusCode::UNPROCESSABLE_ENTITY, e.to_string())
+        }
+        Err(e) => (StatusCode::BAD_REQUEST, format!("Invalid webhook: {}", e)),
+    }
+}
+
+#[tracing::instrument(skip_all, fields(
+    event_id = %payload.event_id,
+    correlation_id = payload.payment["id"].as_str(),
+))]
+async fn record_webhook(state: AppState, payload: WebhookPayload) -> (StatusCode, String) {
+    let chaos = state.chaos.read().clone();
+    if let Some(status) = chaos.strike().await {
+        info!("Chaos: failing webhook {} with {}", payload.event_id, status);
+        return (status, "Chaos: webhook failed".to_string());
+    }
+
+    let now = chrono::Local::now();
     let webhook = ReceivedWebhook {
         event_id: payload.event_id,
         event_type: payload.event_type.clone(),
@@ -93,7 +176,12 @@ async fn receive_webhook(
             .as_str()
             .unwrap_or("unknown")
             .to_string(),
-        received_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+        mode: payload.payment["mode"]
+            .as_str()
+            .unwrap_or("live")
+            .to_string(),
+        received_at: now.format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+        received_at_ms: now.timestamp_millis(),
     };
 
     let payment_id = webhook.payment_id;
@@ -104,8 +192,17 @@ async fn receive_webhook(
     (StatusCode::OK, "Webhook received".to_string())
 }
 
-async fn get_stats(State(state): State<AppState>) -> Json<StatsResponse> {
-    let webhooks = state.received_webhooks.read().clone();
+async fn get_stats(
+    State(state): State<AppState>,
+    Query(query): Query<StatsQuery>,
+) -> Json<StatsResponse> {
+    let webhooks: Vec<ReceivedWebhook> = state
+        .received_webhooks
+        .read()
+        .iter()
+        .filter(|w| query.mode.as_ref().is_none_or(|mode| &w.mode == mode))
+        .cloned()
+        .collect();
 
     let unique_payments: HashSet<Uuid> = webhooks
         .iter()
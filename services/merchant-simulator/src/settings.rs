@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use webhook_signing::Verifier;
+
+// ==============================================================================
+// SETTINGS: Loaded through the shared config crate
+// ==============================================================================
+//
+// Defaults below, then config/merchant-simulator.toml (or CONFIG_FILE), then
+// PORT / INSTANCE_NAME / WEBHOOK_SECRET / ... from the environment.
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub port: u16,
+    /// Shown in the logs, to tell the simulators apart
+    pub instance_name: String,
+    /// Verify webhook signatures with this endpoint secret ("whsec_...");
+    /// unset accepts unsigned webhooks, as old-architecture sends by default
+    pub webhook_secret: Option<String>,
+    /// Also accepted while a rotation is in progress
+    pub previous_webhook_secret: Option<String>,
+    /// How far a webhook's timestamp may be from now
+    pub signature_tolerance_secs: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            port: 4000,
+            instance_name: "merchant".to_string(),
+            webhook_secret: None,
+            previous_webhook_secret: None,
+            signature_tolerance_secs: 300,
+        }
+    }
+}
+
+impl Settings {
+    /// The signature check for /webhooks, when a secret is configured
+    pub fn verifier(&self) -> Result<Option<Verifier>, String> {
+        let Some(secret) = &self.webhook_secret else {
+            return Ok(None);
+        };
+        let mut secrets = vec![secret.as_str()];
+        secrets.extend(self.previous_webhook_secret.as_deref());
+        let verifier = Verifier::new(&secrets)?
+            .with_tolerance(Duration::from_secs(self.signature_tolerance_secs));
+        Ok(Some(verifier))
+    }
+}
+
+impl config::Validate for Settings {
+    fn validate(&self) -> Vec<String> {
+        match self.verifier() {
+            Ok(_) => Vec::new(),
+            Err(e) => vec![format!("WEBHOOK_SECRET: {}", e)],
+        }
+    }
+}
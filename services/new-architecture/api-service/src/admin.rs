@@ -0,0 +1,183 @@
+use axum::{
+    extract::{Json, Path, Query, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{resolve_merchant_id, AppState};
+
+// ==============================================================================
+// ADMIN EVENT BROWSER: Read-only view over domain_events + delivery_attempts
+// ==============================================================================
+//
+// Lets operators see exactly what the trigger wrote to the outbox and whether
+// the delivery path recorded a successful attempt, without psql access. One
+// endpoint's success on the direct path doesn't count: the event is
+// delivered once the path records it for all of them.
+// Dead letters have their own module (dead_letters.rs).
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+#[derive(Deserialize)]
+pub struct EventFilter {
+    event_type: Option<String>,
+    merchant_id: Option<String>,
+    mode: Option<String>,
+    delivered: Option<bool>,
+    /// Keyset pagination: only return events with id < before_id
+    before_id: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct EventRow {
+    id: i64,
+    event_type: String,
+    object_id: Uuid,
+    merchant_id: Uuid,
+    mode: String,
+    payload: Option<serde_json::Value>,
+    replay_of: Option<i64>,
+    created_at: Option<DateTime<Utc>>,
+    delivered: bool,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct DeliveryAttemptRow {
+    id: i64,
+    delivery_path: String,
+    status: String,
+    error: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct EventListResponse {
+    events: Vec<EventRow>,
+    next_before_id: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct EventDetailResponse {
+    #[serde(flatten)]
+    event: EventRow,
+    attempts: Vec<DeliveryAttemptRow>,
+}
+
+pub async fn list_events(
+    State(state): State<AppState>,
+    Query(filter): Query<EventFilter>,
+) -> Result<Json<EventListResponse>, (StatusCode, String)> {
+    let limit = filter
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let merchant_id = filter.merchant_id.as_deref().map(resolve_merchant_id);
+
+    let events = sqlx::query_as::<_, EventRow>(
+        r#"
+        SELECT * FROM (
+            SELECT e.id, e.event_type, e.object_id, e.merchant_id, e.mode, e.payload,
+                   e.replay_of, e.created_at,
+                   EXISTS (
+                       SELECT 1 FROM delivery_attempts a
+                       WHERE a.event_id = e.id AND a.status = 'succeeded'
+                         AND a.delivery_path <> 'direct_endpoint'
+                   ) AS delivered
+            FROM domain_events e
+            WHERE ($1::TEXT IS NULL OR e.event_type = $1)
+              AND ($2::UUID IS NULL OR e.merchant_id = $2)
+              AND ($3::TEXT IS NULL OR e.mode = $3)
+              AND ($4::BIGINT IS NULL OR e.id < $4)
+        ) events
+        WHERE ($5::BOOL IS NULL OR delivered = $5)
+        ORDER BY id DESC
+        LIMIT $6
+        "#,
+    )
+    .bind(&filter.event_type)
+    .bind(merchant_id)
+    .bind(&filter.mode)
+    .bind(filter.before_id)
+    .bind(filter.delivered)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to list events: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to list events: {}", e),
+        )
+    })?;
+
+    let next_before_id = if events.len() as i64 == limit {
+        events.last().map(|e| e.id)
+    } else {
+        None
+    };
+
+    Ok(Json(EventListResponse {
+        events,
+        next_before_id,
+    }))
+}
+
+pub async fn get_event(
+    State(state): State<AppState>,
+    Path(event_id): Path<i64>,
+) -> Result<Json<EventDetailResponse>, (StatusCode, String)> {
+    let event = sqlx::query_as::<_, EventRow>(
+        r#"
+        SELECT e.id, e.event_type, e.object_id, e.merchant_id, e.mode, e.payload,
+               e.replay_of, e.created_at,
+               EXISTS (
+                   SELECT 1 FROM delivery_attempts a
+                   WHERE a.event_id = e.id AND a.status = 'succeeded'
+                     AND a.delivery_path <> 'direct_endpoint'
+               ) AS delivered
+        FROM domain_events e
+        WHERE e.id = $1
+        "#,
+    )
+    .bind(event_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to fetch event {}: {}", event_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to fetch event: {}", e),
+        )
+    })?
+    .ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Event not found: {}", event_id),
+        )
+    })?;
+
+    let attempts = sqlx::query_as::<_, DeliveryAttemptRow>(
+        r#"
+        SELECT id, delivery_path, status, error, created_at
+        FROM delivery_attempts
+        WHERE event_id = $1
+        ORDER BY id
+        "#,
+    )
+    .bind(event_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to fetch attempts for event {}: {}", event_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to fetch delivery attempts: {}", e),
+        )
+    })?;
+
+    Ok(Json(EventDetailResponse { event, attempts }))
+}
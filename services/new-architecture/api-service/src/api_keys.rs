@@ -0,0 +1,180 @@
+use axum::{
+    extract::{Json, Path, State},
+    http::{HeaderMap, StatusCode},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{audit, resolve_merchant_id, AppState};
+
+// ==============================================================================
+// MERCHANT API KEYS: What merchants sign in to merchant-portal with
+// ==============================================================================
+//
+// Keys are "mpk_" plus 256 random bits. Only their SHA-256 is stored, which is
+// what merchant-portal looks them up by, so the key is returned once, on
+// creation, and can't be recovered afterwards; a lost key is revoked and
+// replaced. key_prefix lets merchants tell their keys apart in a listing.
+
+const KEY_PREFIX: &str = "mpk_";
+
+/// Characters of the key kept in key_prefix
+const SHOWN_KEY_CHARS: usize = 12;
+
+#[derive(Deserialize)]
+pub struct CreateApiKeyRequest {
+    name: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CreateApiKeyResponse {
+    id: Uuid,
+    key: String,
+    name: Option<String>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct ApiKeyResponse {
+    id: Uuid,
+    key_prefix: String,
+    name: Option<String>,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Hex SHA-256 of a key, as stored in merchant_api_keys.key_hash
+fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<String>,
+    headers: HeaderMap,
+    req: Option<Json<CreateApiKeyRequest>>,
+) -> Result<(StatusCode, Json<CreateApiKeyResponse>), (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+    let name = req.and_then(|Json(req)| req.name);
+    let key = format!(
+        "{}{}{}",
+        KEY_PREFIX,
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    );
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+    let result = sqlx::query_as::<_, (Uuid,)>(
+        r#"
+        INSERT INTO merchant_api_keys (merchant_id, key_hash, key_prefix, name)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(hash_key(&key))
+    .bind(&key[..SHOWN_KEY_CHARS])
+    .bind(name.as_deref())
+    .fetch_one(&mut *tx)
+    .await;
+
+    match result {
+        Ok((id,)) => {
+            audit::commit(tx).await?;
+            info!("API key {} created for merchant {}", id, merchant_id);
+            Ok((
+                StatusCode::CREATED,
+                Json(CreateApiKeyResponse { id, key, name }),
+            ))
+        }
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => Err((
+            StatusCode::NOT_FOUND,
+            format!("Merchant not found: {}", merchant_id),
+        )),
+        Err(e) => {
+            tracing::error!("Failed to create API key: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to create API key: {}", e),
+            ))
+        }
+    }
+}
+
+pub async fn list_api_keys(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<String>,
+) -> Result<Json<Vec<ApiKeyResponse>>, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let keys = sqlx::query_as::<_, ApiKeyResponse>(
+        r#"
+        SELECT id, key_prefix, name, created_at, last_used_at, revoked_at
+        FROM merchant_api_keys
+        WHERE merchant_id = $1
+        ORDER BY created_at
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!(
+            "Failed to list API keys for merchant {}: {}",
+            merchant_id,
+            e
+        );
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to list API keys: {}", e),
+        )
+    })?;
+
+    Ok(Json(keys))
+}
+
+/// Takes effect on the key's next request; revoking twice is a no-op
+pub async fn revoke_api_key(
+    State(state): State<AppState>,
+    Path((merchant_id, key_id)): Path<(String, Uuid)>,
+    headers: HeaderMap,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+    let result = sqlx::query(
+        r#"
+        UPDATE merchant_api_keys
+        SET revoked_at = COALESCE(revoked_at, NOW())
+        WHERE id = $1 AND merchant_id = $2
+        "#,
+    )
+    .bind(key_id)
+    .bind(merchant_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to revoke API key {}: {}", key_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to revoke API key: {}", e),
+        )
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("API key not found: {}", key_id),
+        ));
+    }
+
+    audit::commit(tx).await?;
+    info!("Revoked API key {} of merchant {}", key_id, merchant_id);
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,161 @@
+use axum::{
+    extract::{Json, Query, State},
+    http::{HeaderMap, StatusCode},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{resolve_merchant_id, AppState};
+
+// ==============================================================================
+// AUDIT LOG: Who changed which merchant's configuration, and from what
+// ==============================================================================
+//
+// Triggers in init.sql append a row to audit_log for every change to
+// merchants, endpoints (secrets, filters, certificates), transformation
+// versions, API keys and maintenance windows, with the row before and after
+// (secrets only as fingerprints), and for every replay, re-drive and purge.
+// Changes are made in a transaction tagged with who is making them: the
+// X-Operator header, as the dead-letter actions require, or "anonymous".
+// merchant-portal tags its own with the API key used.
+//
+//   GET /admin/audit-log    newest first, by merchant_id, entity_type,
+//                           entity_id, actor, action and from/to
+//
+// The table only takes inserts; nothing here or elsewhere changes or
+// deletes its rows.
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+const OPERATOR_HEADER: &str = "x-operator";
+
+/// Who a change is recorded as when the request doesn't say
+const ANONYMOUS: &str = "anonymous";
+
+#[derive(Deserialize)]
+pub struct AuditFilter {
+    merchant_id: Option<String>,
+    entity_type: Option<String>,
+    entity_id: Option<String>,
+    actor: Option<String>,
+    action: Option<String>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    before_id: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct AuditRow {
+    id: i64,
+    actor: String,
+    source: Option<String>,
+    merchant_id: Option<Uuid>,
+    entity_type: String,
+    entity_id: String,
+    action: String,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct AuditListResponse {
+    entries: Vec<AuditRow>,
+    next_before_id: Option<i64>,
+}
+
+fn internal_error(what: &'static str) -> impl FnOnce(sqlx::Error) -> (StatusCode, String) {
+    move |e| {
+        tracing::error!("Failed to {}: {}", what, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to {}: {}", what, e),
+        )
+    }
+}
+
+/// The X-Operator header, if the request names who is acting
+pub fn actor(headers: &HeaderMap) -> String {
+    headers
+        .get(OPERATOR_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|operator| !operator.is_empty() && operator.len() <= 255)
+        .unwrap_or(ANONYMOUS)
+        .to_string()
+}
+
+/// A transaction whose changes the audit triggers record as `actor`'s
+pub async fn begin(
+    db: &PgPool,
+    actor: &str,
+) -> Result<Transaction<'static, Postgres>, (StatusCode, String)> {
+    let mut tx = db.begin().await.map_err(internal_error("begin change"))?;
+    sqlx::query("SELECT set_config('audit.actor', $1, true), set_config('audit.source', $2, true)")
+        .bind(actor)
+        .bind(env!("CARGO_PKG_NAME"))
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error("begin change"))?;
+    Ok(tx)
+}
+
+pub async fn commit(tx: Transaction<'static, Postgres>) -> Result<(), (StatusCode, String)> {
+    tx.commit().await.map_err(internal_error("commit change"))
+}
+
+pub async fn list_audit_log(
+    State(state): State<AppState>,
+    Query(filter): Query<AuditFilter>,
+) -> Result<Json<AuditListResponse>, (StatusCode, String)> {
+    let limit = filter
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let merchant_id = filter.merchant_id.as_deref().map(resolve_merchant_id);
+
+    let entries = sqlx::query_as::<_, AuditRow>(
+        r#"
+        SELECT id, actor, source, merchant_id, entity_type, entity_id, action,
+               before, after, created_at
+        FROM audit_log
+        WHERE ($1::UUID IS NULL OR merchant_id = $1)
+          AND ($2::TEXT IS NULL OR entity_type = $2)
+          AND ($3::TEXT IS NULL OR entity_id = $3)
+          AND ($4::TEXT IS NULL OR actor = $4)
+          AND ($5::TEXT IS NULL OR action = $5)
+          AND ($6::TIMESTAMPTZ IS NULL OR created_at >= $6)
+          AND ($7::TIMESTAMPTZ IS NULL OR created_at < $7)
+          AND ($8::BIGINT IS NULL OR id < $8)
+        ORDER BY id DESC
+        LIMIT $9
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(&filter.entity_type)
+    .bind(&filter.entity_id)
+    .bind(&filter.actor)
+    .bind(&filter.action)
+    .bind(filter.from)
+    .bind(filter.to)
+    .bind(filter.before_id)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await
+    .map_err(internal_error("list audit log"))?;
+
+    let next_before_id = if entries.len() as i64 == limit {
+        entries.last().map(|entry| entry.id)
+    } else {
+        None
+    };
+
+    Ok(Json(AuditListResponse {
+        entries,
+        next_before_id,
+    }))
+}
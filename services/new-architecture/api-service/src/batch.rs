@@ -0,0 +1,143 @@
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{quota, AppState, PAYMENT_STATUSES};
+
+// ==============================================================================
+// BATCH STATUS UPDATES: Many transitions, one transaction
+// ==============================================================================
+//
+// A single UPDATE ... FROM UNNEST statement, so the whole batch commits or
+// rolls back together. The status trigger still fires per row, which gives
+// one domain event per changed payment - a realistic burst for the pipeline.
+
+const MAX_BATCH_SIZE: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct StatusUpdate {
+    payment_id: Uuid,
+    status: String,
+}
+
+#[derive(Deserialize)]
+pub struct StatusBatchRequest {
+    updates: Vec<StatusUpdate>,
+}
+
+#[derive(Serialize)]
+pub struct UpdatedPayment {
+    id: Uuid,
+    status: String,
+}
+
+#[derive(Serialize)]
+pub struct StatusBatchResponse {
+    updated: Vec<UpdatedPayment>,
+    /// Payments that don't exist or already had the requested status
+    skipped: Vec<Uuid>,
+}
+
+pub async fn update_status_batch(
+    State(state): State<AppState>,
+    Json(req): Json<StatusBatchRequest>,
+) -> Result<Json<StatusBatchResponse>, (StatusCode, String)> {
+    if req.updates.is_empty() || req.updates.len() > MAX_BATCH_SIZE {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Batch must contain between 1 and {} updates", MAX_BATCH_SIZE),
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    for update in &req.updates {
+        if !PAYMENT_STATUSES.contains(&update.status.as_str()) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Invalid status '{}' for payment {}", update.status, update.payment_id),
+            ));
+        }
+        // UPDATE ... FROM picks an arbitrary row for duplicate ids
+        if !seen.insert(update.payment_id) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Duplicate payment in batch: {}", update.payment_id),
+            ));
+        }
+    }
+
+    let ids: Vec<Uuid> = req.updates.iter().map(|u| u.payment_id).collect();
+    let statuses: Vec<String> = req.updates.iter().map(|u| u.status.clone()).collect();
+
+    // A batch can span merchants, each one's quota covers its own transitions
+    let events_per_merchant = sqlx::query_as::<_, (Uuid, i64)>(
+        r#"
+        SELECT p.merchant_id, COUNT(*)
+        FROM payments p
+        JOIN UNNEST($1::UUID[], $2::TEXT[]) AS u(id, status) ON p.id = u.id
+        WHERE p.status IS DISTINCT FROM u.status
+        GROUP BY p.merchant_id
+        "#,
+    )
+    .bind(&ids)
+    .bind(&statuses)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to count batch events per merchant: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to apply status batch: {}", e),
+        )
+    })?;
+
+    for (merchant_id, events) in events_per_merchant {
+        quota::enforce_quota(&state, merchant_id, events).await?;
+    }
+
+    let rows = sqlx::query_as::<_, (Uuid, String)>(
+        r#"
+        UPDATE payments
+        SET status = u.status, updated_at = NOW()
+        FROM UNNEST($1::UUID[], $2::TEXT[]) AS u(id, status)
+        WHERE payments.id = u.id
+          AND payments.status IS DISTINCT FROM u.status
+        RETURNING payments.id, payments.status
+        "#,
+    )
+    .bind(&ids)
+    .bind(&statuses)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to apply status batch: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to apply status batch: {}", e),
+        )
+    })?;
+
+    let updated_ids: HashSet<Uuid> = rows.iter().map(|(id, _)| *id).collect();
+    let skipped = ids
+        .into_iter()
+        .filter(|id| !updated_ids.contains(id))
+        .collect();
+
+    info!(
+        "Status batch applied: {} payments updated (one event each via trigger)",
+        rows.len()
+    );
+
+    Ok(Json(StatusBatchResponse {
+        updated: rows
+            .into_iter()
+            .map(|(id, status)| UpdatedPayment { id, status })
+            .collect(),
+        skipped,
+    }))
+}
@@ -0,0 +1,87 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+};
+use chrono::Utc;
+use tracing::info;
+
+use crate::AppState;
+
+// ==============================================================================
+// CONFIRMATIONS: Merchants acknowledging the webhooks they processed
+// ==============================================================================
+//
+// Webhooks for merchants with confirmation_timeout_secs set carry a
+// `confirmation_id`, the id of a Restate awakeable svix-caller is waiting on
+// (see svix-caller's confirmation.rs). POST /confirm/<confirmation_id>
+// resolves it through Restate's ingress (RESTATE_INGRESS_URL), so merchants
+// never talk to Restate directly. The id can't be guessed and is only ever
+// sent to the merchant, so it is the credential for confirming.
+
+/// Restate awakeable ids are URL-safe base64 with a prefix
+fn valid_confirmation_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 256
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+pub async fn confirm(
+    State(state): State<AppState>,
+    Path(confirmation_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if !valid_confirmation_id(&confirmation_id) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Invalid confirmation id: {}", confirmation_id),
+        ));
+    }
+
+    // svix-caller only checks that it resolved; the time is for the journal
+    let url = format!(
+        "{}/restate/awakeables/{}/resolve",
+        state.restate_ingress_url, confirmation_id
+    );
+    let request = state
+        .http
+        .post(&url)
+        .header("content-type", "application/json")
+        .body(serde_json::to_vec(&Utc::now().to_rfc3339()).unwrap_or_default())
+        .timeout(std::time::Duration::from_secs(5));
+    let response = state
+        .http
+        .send(request)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to resolve confirmation {}: {}", confirmation_id, e);
+            (
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to record confirmation: {}", e),
+            )
+        })?;
+
+    match response.status() {
+        status if status.is_success() => {
+            info!("Confirmation {} recorded", confirmation_id);
+            Ok(StatusCode::ACCEPTED)
+        }
+        StatusCode::NOT_FOUND => Err((
+            StatusCode::NOT_FOUND,
+            format!("Unknown confirmation id: {}", confirmation_id),
+        )),
+        status => {
+            let body = response.text().await.unwrap_or_default();
+            tracing::error!(
+                "Restate rejected confirmation {}: {} {}",
+                confirmation_id,
+                status,
+                body
+            );
+            Err((
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to record confirmation: {}", status),
+            ))
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use axum::http::StatusCode;
+use sqlx::PgPool;
+
+// ==============================================================================
+// CURRENCIES: Codes and amount bounds come from the currencies table
+// ==============================================================================
+//
+// Amounts are always minor units (2500 USD = $25.00, 2500 JPY = ¥2500), so the
+// only normalization needed is the code itself; data-service uses minor_units
+// to render the decimal amount in delivered payloads.
+
+/// Validate a payment's currency and amount, returning the normalized
+/// (upper-case ISO 4217) currency code to store.
+pub async fn validate_amount(
+    db: &PgPool,
+    currency: &str,
+    amount: i64,
+) -> Result<String, (StatusCode, String)> {
+    let code = currency.trim().to_ascii_uppercase();
+
+    let bounds = sqlx::query_as::<_, (i64, i64)>(
+        "SELECT min_amount, max_amount FROM currencies WHERE code = $1",
+    )
+    .bind(&code)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to look up currency {}: {}", code, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to look up currency: {}", e),
+        )
+    })?;
+
+    let Some((min_amount, max_amount)) = bounds else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported currency: {}", currency),
+        ));
+    };
+
+    if amount < min_amount || amount > max_amount {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Amount {} out of range for {}: must be between {} and {} (minor units)",
+                amount, code, min_amount, max_amount
+            ),
+        ));
+    }
+
+    Ok(code)
+}
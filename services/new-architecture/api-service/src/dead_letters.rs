@@ -0,0 +1,501 @@
+use axum::{
+    extract::{Json, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{resolve_merchant_id, AppState};
+
+// ==============================================================================
+// DEAD LETTERS: Browsing, re-driving and purging what svix-caller gave up on
+// ==============================================================================
+//
+//   GET  /admin/dead-letters               pending entries, newest first
+//   GET  /admin/dead-letters/:id           one entry and every action on it
+//   POST /admin/dead-letters/redrive       send selected entries again
+//   POST /admin/dead-letters/purge         drop selected entries
+//   GET  /admin/dead-letters/actions       the audit trail
+//
+// Entries are selected by ids, or by merchant_id and a from/to range of
+// created_at; only pending ones (neither re-driven nor purged) are ever acted
+// on. Re-driving goes through svix-caller's DeadLetters/redrive on Restate's
+// ingress (svix-caller's dead_letters.rs) with the ids picked here. Purging
+// only sets purged_at, so the entry and its error stay readable.
+//
+// Both need an X-Operator header naming who is acting, and are written to
+// dead_letter_actions with the selection, the reason given and the ids they
+// applied to.
+//
+// Where svix-caller seals payloads (PAYLOAD_MASTER_KEYS), entries are stored
+// sealed. The list shows them that way; one entry's detail is opened when
+// this service has the master keys too.
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// Entries a redrive or purge applies to when the request doesn't say
+const DEFAULT_BULK_LIMIT: i64 = 100;
+const MAX_BULK_LIMIT: i64 = 1000;
+
+const OPERATOR_HEADER: &str = "x-operator";
+
+#[derive(Deserialize)]
+pub struct DeadLetterFilter {
+    merchant_id: Option<String>,
+    /// Include entries already re-driven or purged
+    all: Option<bool>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    /// Case-insensitive substring of the error
+    error: Option<String>,
+    before_id: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct DeadLetterRow {
+    id: i64,
+    event_id: i64,
+    merchant_id: Uuid,
+    event: serde_json::Value,
+    payload: Option<serde_json::Value>,
+    error: String,
+    created_at: DateTime<Utc>,
+    redriven_at: Option<DateTime<Utc>>,
+    purged_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct DeadLetterListResponse {
+    dead_letters: Vec<DeadLetterRow>,
+    next_before_id: Option<i64>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct ActionRow {
+    id: i64,
+    action: String,
+    operator: String,
+    reason: Option<String>,
+    selection: serde_json::Value,
+    dead_letter_ids: Vec<i64>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct DeadLetterDetailResponse {
+    #[serde(flatten)]
+    dead_letter: DeadLetterRow,
+    actions: Vec<ActionRow>,
+}
+
+#[derive(Deserialize)]
+pub struct ActionFilter {
+    operator: Option<String>,
+    action: Option<String>,
+    before_id: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct ActionListResponse {
+    actions: Vec<ActionRow>,
+    next_before_id: Option<i64>,
+}
+
+/// Which pending entries a redrive or purge applies to
+#[derive(Deserialize, Serialize)]
+pub struct Selection {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ids: Option<Vec<i64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    merchant_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    from: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    to: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct ActionRequest {
+    #[serde(flatten)]
+    selection: Selection,
+    reason: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RedriveResponse {
+    action_id: i64,
+    redriven: Vec<i64>,
+}
+
+#[derive(Serialize)]
+pub struct PurgeResponse {
+    action_id: i64,
+    purged: Vec<i64>,
+}
+
+/// What svix-caller's DeadLetters/redrive answers
+#[derive(Deserialize)]
+struct RedriveResult {
+    redriven: Vec<i64>,
+}
+
+fn operator(headers: &HeaderMap) -> Result<String, (StatusCode, String)> {
+    headers
+        .get(OPERATOR_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|operator| !operator.is_empty() && operator.len() <= 255)
+        .map(str::to_string)
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                "X-Operator header naming who is acting is required".to_string(),
+            )
+        })
+}
+
+fn internal_error(what: &'static str) -> impl FnOnce(sqlx::Error) -> (StatusCode, String) {
+    move |e| {
+        tracing::error!("Failed to {}: {}", what, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to {}: {}", what, e),
+        )
+    }
+}
+
+pub async fn list_dead_letters(
+    State(state): State<AppState>,
+    Query(filter): Query<DeadLetterFilter>,
+) -> Result<Json<DeadLetterListResponse>, (StatusCode, String)> {
+    let limit = filter
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let merchant_id = filter.merchant_id.as_deref().map(resolve_merchant_id);
+
+    let dead_letters = sqlx::query_as::<_, DeadLetterRow>(
+        r#"
+        SELECT id, event_id, merchant_id, event, payload, error, created_at,
+               redriven_at, purged_at
+        FROM svix_dead_letters
+        WHERE ($1::UUID IS NULL OR merchant_id = $1)
+          AND ($2 OR (redriven_at IS NULL AND purged_at IS NULL))
+          AND ($3::TIMESTAMPTZ IS NULL OR created_at >= $3)
+          AND ($4::TIMESTAMPTZ IS NULL OR created_at < $4)
+          AND ($5::TEXT IS NULL OR error ILIKE '%' || $5 || '%')
+          AND ($6::BIGINT IS NULL OR id < $6)
+        ORDER BY id DESC
+        LIMIT $7
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(filter.all.unwrap_or(false))
+    .bind(filter.from)
+    .bind(filter.to)
+    .bind(&filter.error)
+    .bind(filter.before_id)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await
+    .map_err(internal_error("list dead letters"))?;
+
+    let next_before_id = if dead_letters.len() as i64 == limit {
+        dead_letters.last().map(|d| d.id)
+    } else {
+        None
+    };
+
+    Ok(Json(DeadLetterListResponse {
+        dead_letters,
+        next_before_id,
+    }))
+}
+
+pub async fn get_dead_letter(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<DeadLetterDetailResponse>, (StatusCode, String)> {
+    let dead_letter = sqlx::query_as::<_, DeadLetterRow>(
+        r#"
+        SELECT id, event_id, merchant_id, event, payload, error, created_at,
+               redriven_at, purged_at
+        FROM svix_dead_letters
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(internal_error("fetch dead letter"))?
+    .ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Dead letter not found: {}", id),
+        )
+    })?;
+    let dead_letter = open_dead_letter(&state, dead_letter).await;
+
+    let actions = sqlx::query_as::<_, ActionRow>(
+        r#"
+        SELECT id, action, operator, reason, selection, dead_letter_ids, created_at
+        FROM dead_letter_actions
+        WHERE dead_letter_ids @> ARRAY[$1::BIGINT]
+        ORDER BY id
+        "#,
+    )
+    .bind(id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(internal_error("fetch dead letter actions"))?;
+
+    Ok(Json(DeadLetterDetailResponse {
+        dead_letter,
+        actions,
+    }))
+}
+
+/// The entry with its sealed payloads opened; left sealed without the keys or
+/// when they don't open
+async fn open_dead_letter(state: &AppState, mut dead_letter: DeadLetterRow) -> DeadLetterRow {
+    let Some(keyring) = &state.keyring else {
+        return dead_letter;
+    };
+    let merchant_id = dead_letter.merchant_id.to_string();
+    if let Some(payload) = dead_letter.event.get_mut("payload") {
+        match keyring.open(&merchant_id, payload).await {
+            Ok(opened) => *payload = opened,
+            Err(e) => tracing::warn!("Dead letter {}: event payload: {}", dead_letter.id, e),
+        }
+    }
+    if let Some(payload) = &mut dead_letter.payload {
+        match keyring.open(&merchant_id, payload).await {
+            Ok(opened) => *payload = opened,
+            Err(e) => tracing::warn!("Dead letter {}: payload: {}", dead_letter.id, e),
+        }
+    }
+    dead_letter
+}
+
+pub async fn list_actions(
+    State(state): State<AppState>,
+    Query(filter): Query<ActionFilter>,
+) -> Result<Json<ActionListResponse>, (StatusCode, String)> {
+    let limit = filter
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let actions = sqlx::query_as::<_, ActionRow>(
+        r#"
+        SELECT id, action, operator, reason, selection, dead_letter_ids, created_at
+        FROM dead_letter_actions
+        WHERE ($1::TEXT IS NULL OR operator = $1)
+          AND ($2::TEXT IS NULL OR action = $2)
+          AND ($3::BIGINT IS NULL OR id < $3)
+        ORDER BY id DESC
+        LIMIT $4
+        "#,
+    )
+    .bind(&filter.operator)
+    .bind(&filter.action)
+    .bind(filter.before_id)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await
+    .map_err(internal_error("list dead letter actions"))?;
+
+    let next_before_id = if actions.len() as i64 == limit {
+        actions.last().map(|a| a.id)
+    } else {
+        None
+    };
+
+    Ok(Json(ActionListResponse {
+        actions,
+        next_before_id,
+    }))
+}
+
+/// Pending entries matching the selection, oldest first. An empty selection
+/// would match every merchant's entries, so one of its fields is required.
+async fn select_pending(
+    state: &AppState,
+    selection: &Selection,
+) -> Result<Vec<i64>, (StatusCode, String)> {
+    if selection.ids.is_none()
+        && selection.merchant_id.is_none()
+        && selection.from.is_none()
+        && selection.to.is_none()
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Select entries with ids, merchant_id, from or to".to_string(),
+        ));
+    }
+    let limit = selection
+        .limit
+        .unwrap_or(DEFAULT_BULK_LIMIT)
+        .clamp(1, MAX_BULK_LIMIT);
+    let merchant_id = selection.merchant_id.as_deref().map(resolve_merchant_id);
+
+    sqlx::query_scalar::<_, i64>(
+        r#"
+        SELECT id
+        FROM svix_dead_letters
+        WHERE redriven_at IS NULL AND purged_at IS NULL
+          AND ($1::BIGINT[] IS NULL OR id = ANY($1))
+          AND ($2::UUID IS NULL OR merchant_id = $2)
+          AND ($3::TIMESTAMPTZ IS NULL OR created_at >= $3)
+          AND ($4::TIMESTAMPTZ IS NULL OR created_at < $4)
+        ORDER BY id
+        LIMIT $5
+        "#,
+    )
+    .bind(&selection.ids)
+    .bind(merchant_id)
+    .bind(selection.from)
+    .bind(selection.to)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await
+    .map_err(internal_error("select dead letters"))
+}
+
+async fn record_action<'e>(
+    db: impl sqlx::PgExecutor<'e>,
+    action: &str,
+    operator: &str,
+    req: &ActionRequest,
+    ids: &[i64],
+) -> Result<i64, (StatusCode, String)> {
+    sqlx::query_scalar::<_, i64>(
+        r#"
+        INSERT INTO dead_letter_actions (action, operator, reason, selection, dead_letter_ids)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+    )
+    .bind(action)
+    .bind(operator)
+    .bind(&req.reason)
+    .bind(sqlx::types::Json(&req.selection))
+    .bind(ids)
+    .fetch_one(db)
+    .await
+    .map_err(internal_error("record dead letter action"))
+}
+
+pub async fn redrive_dead_letters(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<ActionRequest>,
+) -> Result<Json<RedriveResponse>, (StatusCode, String)> {
+    let operator = operator(&headers)?;
+    let selected = select_pending(&state, &req.selection).await?;
+
+    let redriven = if selected.is_empty() {
+        Vec::new()
+    } else {
+        let url = format!("{}/DeadLetters/redrive", state.restate_ingress_url);
+        let body = serde_json::json!({ "ids": selected, "limit": selected.len() });
+        let request = state
+            .http
+            .post(&url)
+            .header("content-type", "application/json")
+            .body(serde_json::to_vec(&body).unwrap_or_default())
+            .timeout(std::time::Duration::from_secs(30));
+        let response = state.http.send(request).await.map_err(|e| {
+            tracing::error!("Failed to re-drive dead letters: {}", e);
+            (
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to re-drive dead letters: {}", e),
+            )
+        })?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            tracing::error!("DeadLetters/redrive answered {}: {}", status, body);
+            return Err((
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to re-drive dead letters: {}", status),
+            ));
+        }
+        let body = response.bytes().await.map_err(|e| {
+            (
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to read DeadLetters/redrive response: {}", e),
+            )
+        })?;
+        serde_json::from_slice::<RedriveResult>(&body)
+            .map_err(|e| {
+                (
+                    StatusCode::BAD_GATEWAY,
+                    format!("Invalid response from DeadLetters/redrive: {}", e),
+                )
+            })?
+            .redriven
+    };
+
+    // The events are already on their way; a failure here loses only the record
+    let action_id = record_action(&state.db, "redrive", &operator, &req, &redriven).await?;
+    info!(
+        "{} re-drove {} dead letter(s) (action {})",
+        operator,
+        redriven.len(),
+        action_id
+    );
+
+    Ok(Json(RedriveResponse {
+        action_id,
+        redriven,
+    }))
+}
+
+pub async fn purge_dead_letters(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<ActionRequest>,
+) -> Result<Json<PurgeResponse>, (StatusCode, String)> {
+    let operator = operator(&headers)?;
+    let selected = select_pending(&state, &req.selection).await?;
+
+    let mut tx = state
+        .db
+        .begin()
+        .await
+        .map_err(internal_error("purge dead letters"))?;
+    // Re-checked, in case a redrive took some of them in the meantime
+    let purged = sqlx::query_scalar::<_, i64>(
+        r#"
+        UPDATE svix_dead_letters
+        SET purged_at = NOW()
+        WHERE id = ANY($1) AND redriven_at IS NULL AND purged_at IS NULL
+        RETURNING id
+        "#,
+    )
+    .bind(&selected)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(internal_error("purge dead letters"))?;
+    let action_id = record_action(&mut *tx, "purge", &operator, &req, &purged).await?;
+    tx.commit()
+        .await
+        .map_err(internal_error("purge dead letters"))?;
+
+    info!(
+        "{} purged {} dead letter(s) (action {})",
+        operator,
+        purged.len(),
+        action_id
+    );
+
+    Ok(Json(PurgeResponse { action_id, purged }))
+}
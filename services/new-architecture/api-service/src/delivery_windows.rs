@@ -0,0 +1,495 @@
+use axum::{
+    extract::{Json, Path, State},
+    http::{HeaderMap, StatusCode},
+};
+use chrono::{DateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{audit, resolve_merchant_id, AppState};
+
+// ==============================================================================
+// DELIVERY WINDOWS: When the merchant takes its events
+// ==============================================================================
+//
+// A daily window ("only between 02:00 and 04:00 Europe/Berlin"), one-off
+// maintenance windows and an open-ended pause ("stop sending while we migrate
+// our receiver"), enforced by svix-caller (windows.rs): outside them the
+// merchant's events are parked and go out in order once the window opens or
+// the merchant is resumed.
+// Time zones are IANA names, checked against the database's list since
+// that's where svix-caller does the arithmetic.
+
+#[derive(Deserialize)]
+pub struct SetDeliveryWindowRequest {
+    /// HH:MM local time; all three unset removes the window
+    start: Option<String>,
+    /// Before start for a window across midnight
+    end: Option<String>,
+    timezone: Option<String>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct DeliveryWindowResponse {
+    start: Option<NaiveTime>,
+    end: Option<NaiveTime>,
+    timezone: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateMaintenanceWindowRequest {
+    starts_at: DateTime<Utc>,
+    ends_at: DateTime<Utc>,
+    reason: Option<String>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct MaintenanceWindowResponse {
+    id: Uuid,
+    starts_at: DateTime<Utc>,
+    ends_at: DateTime<Utc>,
+    reason: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct PauseRequest {
+    reason: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PauseResponse {
+    paused: bool,
+    paused_at: Option<DateTime<Utc>>,
+    reason: Option<String>,
+    /// Events created since the pause that haven't gone out yet
+    backlog: i64,
+    oldest_pending_at: Option<DateTime<Utc>>,
+}
+
+fn parse_time(field: &str, value: &str) -> Result<NaiveTime, (StatusCode, String)> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(value.trim(), "%H:%M:%S"))
+        .map_err(|_| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("{} must be HH:MM, got {:?}", field, value),
+            )
+        })
+}
+
+pub async fn get_delivery_window(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<String>,
+) -> Result<Json<DeliveryWindowResponse>, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let window = sqlx::query_as::<_, DeliveryWindowResponse>(
+        r#"
+        SELECT delivery_window_start AS start, delivery_window_end AS "end",
+               delivery_window_timezone AS timezone
+        FROM merchants
+        WHERE id = $1
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!(
+            "Failed to fetch delivery window of merchant {}: {}",
+            merchant_id,
+            e
+        );
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to fetch delivery window: {}", e),
+        )
+    })?;
+
+    window.map(Json).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Merchant not found: {}", merchant_id),
+        )
+    })
+}
+
+/// Takes effect on the merchant's next event; parked ones see it within
+/// svix-caller's WINDOW_RECHECK
+pub async fn set_delivery_window(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<SetDeliveryWindowRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let timezone = req
+        .timezone
+        .as_deref()
+        .map(str::trim)
+        .filter(|timezone| !timezone.is_empty());
+    let window = match (req.start.as_deref(), req.end.as_deref(), timezone) {
+        (None, None, None) => None,
+        (Some(start), Some(end), Some(timezone)) => {
+            let (start, end) = (parse_time("start", start)?, parse_time("end", end)?);
+            if start == end {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "start and end must differ".to_string(),
+                ));
+            }
+            Some((start, end, timezone))
+        }
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Set start, end and timezone together, or none of them".to_string(),
+            ))
+        }
+    };
+
+    if let Some((_, _, timezone)) = window {
+        let known = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1)",
+        )
+        .bind(timezone)
+        .fetch_one(&state.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to look up time zone {}: {}", timezone, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to look up time zone: {}", e),
+            )
+        })?;
+        if !known {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Unknown time zone {:?}, use an IANA name such as Europe/Berlin",
+                    timezone
+                ),
+            ));
+        }
+    }
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+    let result = sqlx::query(
+        r#"
+        UPDATE merchants
+        SET delivery_window_start = $1, delivery_window_end = $2, delivery_window_timezone = $3
+        WHERE id = $4
+        "#,
+    )
+    .bind(window.map(|(start, _, _)| start))
+    .bind(window.map(|(_, end, _)| end))
+    .bind(window.map(|(_, _, timezone)| timezone))
+    .bind(merchant_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        tracing::error!(
+            "Failed to set delivery window for merchant {}: {}",
+            merchant_id,
+            e
+        );
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to set delivery window: {}", e),
+        )
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Merchant not found: {}", merchant_id),
+        ));
+    }
+
+    audit::commit(tx).await?;
+    match window {
+        Some((start, end, timezone)) => info!(
+            "Merchant {} takes events between {} and {} {}",
+            merchant_id, start, end, timezone
+        ),
+        None => info!("Merchant {} takes events at any time", merchant_id),
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Current and upcoming ones; past windows are left out
+pub async fn list_maintenance_windows(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<String>,
+) -> Result<Json<Vec<MaintenanceWindowResponse>>, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let windows = sqlx::query_as::<_, MaintenanceWindowResponse>(
+        r#"
+        SELECT id, starts_at, ends_at, reason, created_at
+        FROM merchant_maintenance_windows
+        WHERE merchant_id = $1 AND ends_at > NOW()
+        ORDER BY starts_at
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!(
+            "Failed to list maintenance windows for merchant {}: {}",
+            merchant_id,
+            e
+        );
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to list maintenance windows: {}", e),
+        )
+    })?;
+
+    Ok(Json(windows))
+}
+
+pub async fn create_maintenance_window(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<CreateMaintenanceWindowRequest>,
+) -> Result<(StatusCode, Json<MaintenanceWindowResponse>), (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    if req.ends_at <= req.starts_at {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "ends_at must be after starts_at".to_string(),
+        ));
+    }
+    if req.ends_at <= Utc::now() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "ends_at is in the past".to_string(),
+        ));
+    }
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+    let result = sqlx::query_as::<_, MaintenanceWindowResponse>(
+        r#"
+        INSERT INTO merchant_maintenance_windows (merchant_id, starts_at, ends_at, reason)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, starts_at, ends_at, reason, created_at
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(req.starts_at)
+    .bind(req.ends_at)
+    .bind(req.reason.as_deref())
+    .fetch_one(&mut *tx)
+    .await;
+
+    match result {
+        Ok(window) => {
+            audit::commit(tx).await?;
+            info!(
+                "Maintenance window {} for merchant {}: {} to {}",
+                window.id, merchant_id, window.starts_at, window.ends_at
+            );
+            Ok((StatusCode::CREATED, Json(window)))
+        }
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => Err((
+            StatusCode::NOT_FOUND,
+            format!("Merchant not found: {}", merchant_id),
+        )),
+        Err(e) => {
+            tracing::error!("Failed to create maintenance window: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to create maintenance window: {}", e),
+            ))
+        }
+    }
+}
+
+/// Ends maintenance early: parked events see it within svix-caller's
+/// WINDOW_RECHECK
+pub async fn delete_maintenance_window(
+    State(state): State<AppState>,
+    Path((merchant_id, window_id)): Path<(String, Uuid)>,
+    headers: HeaderMap,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+    let result =
+        sqlx::query("DELETE FROM merchant_maintenance_windows WHERE id = $1 AND merchant_id = $2")
+            .bind(window_id)
+            .bind(merchant_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to delete maintenance window {}: {}", window_id, e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to delete maintenance window: {}", e),
+                )
+            })?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Maintenance window not found: {}", window_id),
+        ));
+    }
+
+    audit::commit(tx).await?;
+    info!(
+        "Deleted maintenance window {} of merchant {}",
+        window_id, merchant_id
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_pause(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<String>,
+) -> Result<Json<PauseResponse>, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let row = sqlx::query_as::<
+        _,
+        (
+            Option<DateTime<Utc>>,
+            Option<String>,
+            i64,
+            Option<DateTime<Utc>>,
+        ),
+    >(
+        r#"
+        SELECT m.delivery_paused_at, m.delivery_pause_reason,
+               COUNT(e.id), MIN(e.created_at)
+        FROM merchants m
+        LEFT JOIN domain_events e
+               ON e.merchant_id = m.id
+              AND e.created_at >= m.delivery_paused_at
+              AND NOT EXISTS (
+                  SELECT 1 FROM delivery_attempts a
+                  WHERE a.event_id = e.id AND a.delivery_path <> 'direct_endpoint'
+              )
+        WHERE m.id = $1
+        GROUP BY m.id
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to fetch pause of merchant {}: {}", merchant_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to fetch pause: {}", e),
+        )
+    })?;
+
+    let (paused_at, reason, backlog, oldest_pending_at) = row.ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Merchant not found: {}", merchant_id),
+        )
+    })?;
+    Ok(Json(PauseResponse {
+        paused: paused_at.is_some(),
+        paused_at,
+        reason,
+        backlog,
+        oldest_pending_at,
+    }))
+}
+
+/// Stop handing over the merchant's events until it's resumed. Pausing a
+/// paused merchant keeps the original paused_at and updates the reason.
+pub async fn pause(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<String>,
+    headers: HeaderMap,
+    req: Option<Json<PauseRequest>>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+    let Json(req) = req.unwrap_or_default();
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+    let result = sqlx::query(
+        r#"
+        UPDATE merchants
+        SET delivery_paused_at = COALESCE(delivery_paused_at, NOW()),
+            delivery_pause_reason = $1
+        WHERE id = $2
+        "#,
+    )
+    .bind(req.reason.as_deref())
+    .bind(merchant_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to pause merchant {}: {}", merchant_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to pause deliveries: {}", e),
+        )
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Merchant not found: {}", merchant_id),
+        ));
+    }
+
+    audit::commit(tx).await?;
+    info!(
+        "Paused deliveries for merchant {} ({})",
+        merchant_id,
+        req.reason.as_deref().unwrap_or("no reason given")
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The parked events drain in order within svix-caller's PAUSE_RECHECK
+pub async fn resume(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+    let result = sqlx::query(
+        r#"
+        UPDATE merchants
+        SET delivery_paused_at = NULL, delivery_pause_reason = NULL
+        WHERE id = $1
+        "#,
+    )
+    .bind(merchant_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to resume merchant {}: {}", merchant_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to resume deliveries: {}", e),
+        )
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Merchant not found: {}", merchant_id),
+        ));
+    }
+
+    audit::commit(tx).await?;
+    info!("Resumed deliveries for merchant {}", merchant_id);
+    Ok(StatusCode::NO_CONTENT)
+}
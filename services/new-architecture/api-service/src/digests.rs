@@ -0,0 +1,212 @@
+use axum::{
+    extract::{Json, Path, State},
+    http::{HeaderMap, StatusCode},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{audit, resolve_merchant_id, AppState, PAYMENT_STATUSES};
+
+// ==============================================================================
+// DIGESTS: Low-priority event types as one periodic webhook
+// ==============================================================================
+//
+// A merchant that doesn't need every payment.processing the moment it
+// happens can take those types in a digest: svix-caller (digest.rs) sets
+// their events aside and sends a digest.summary every interval_secs, on the
+// multiples of it (hourly digests go out on the hour), with a count per type
+// and the events' bodies. Other types keep going out one by one.
+//
+// Turning digests off (or changing the types) applies to the next event;
+// what's already set aside still goes out with the flush scheduled for it,
+// or straight away through POST /merchants/:id/digest/flush.
+
+/// Shortest and longest digest interval
+const MIN_INTERVAL_SECS: i32 = 60;
+const MAX_INTERVAL_SECS: i32 = 86_400;
+
+#[derive(Deserialize)]
+pub struct SetDigestRequest {
+    /// Both unset (or no types) turns digests off
+    event_types: Option<Vec<String>>,
+    interval_secs: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct DigestResponse {
+    event_types: Option<Vec<String>>,
+    interval_secs: Option<i32>,
+    /// Events set aside for the next digest
+    pending: i64,
+    oldest_pending_at: Option<DateTime<Utc>>,
+}
+
+/// The types a digest can collect: payment events, which are the ones that
+/// come in volume. Operational events such as quota.exceeded always go out
+/// as they happen.
+fn digestible(event_type: &str) -> bool {
+    event_type
+        .strip_prefix("payment.")
+        .is_some_and(|status| PAYMENT_STATUSES.contains(&status))
+}
+
+pub async fn get_digest(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<String>,
+) -> Result<Json<DigestResponse>, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let row = sqlx::query_as::<_, (Option<Vec<String>>, Option<i32>, i64, Option<DateTime<Utc>>)>(
+        r#"
+        SELECT m.digest_event_types, m.digest_interval_secs,
+               COUNT(i.event_id), MIN(i.created_at)
+        FROM merchants m
+        LEFT JOIN digest_items i
+               ON i.merchant_id = m.id AND i.digest_event_id IS NULL
+        WHERE m.id = $1
+        GROUP BY m.id
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to fetch digest of merchant {}: {}", merchant_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to fetch digest: {}", e),
+        )
+    })?;
+
+    let Some((event_types, interval_secs, pending, oldest_pending_at)) = row else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Merchant not found: {}", merchant_id),
+        ));
+    };
+    Ok(Json(DigestResponse {
+        event_types,
+        interval_secs,
+        pending,
+        oldest_pending_at,
+    }))
+}
+
+pub async fn set_digest(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<SetDigestRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let mut event_types = req.event_types.unwrap_or_default();
+    event_types.sort();
+    event_types.dedup();
+    let digest = match (event_types.is_empty(), req.interval_secs) {
+        (true, None) => None,
+        (false, Some(interval_secs)) => {
+            if !(MIN_INTERVAL_SECS..=MAX_INTERVAL_SECS).contains(&interval_secs) {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!(
+                        "interval_secs must be between {} and {}",
+                        MIN_INTERVAL_SECS, MAX_INTERVAL_SECS
+                    ),
+                ));
+            }
+            if let Some(event_type) = event_types.iter().find(|t| !digestible(t)) {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!(
+                        "{} can't go in a digest; use payment.<status> with one of: {}",
+                        event_type,
+                        PAYMENT_STATUSES.join(", ")
+                    ),
+                ));
+            }
+            Some((event_types, interval_secs))
+        }
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Set event_types and interval_secs together, or neither".to_string(),
+            ))
+        }
+    };
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+    let result = sqlx::query(
+        "UPDATE merchants SET digest_event_types = $1, digest_interval_secs = $2 WHERE id = $3",
+    )
+    .bind(digest.as_ref().map(|(event_types, _)| event_types))
+    .bind(digest.as_ref().map(|(_, interval_secs)| *interval_secs))
+    .bind(merchant_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to set digest for merchant {}: {}", merchant_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to set digest: {}", e),
+        )
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Merchant not found: {}", merchant_id),
+        ));
+    }
+
+    audit::commit(tx).await?;
+    match digest {
+        Some((event_types, interval_secs)) => info!(
+            "Merchant {} takes {} in a digest every {}s",
+            merchant_id,
+            event_types.join(", "),
+            interval_secs
+        ),
+        None => info!("Merchant {} no longer takes digests", merchant_id),
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Send what's set aside now rather than at the next interval, through
+/// svix-caller's Digests/flush on Restate's ingress
+pub async fn flush_digest(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let url = format!(
+        "{}/Digests/{}/flush/send",
+        state.restate_ingress_url, merchant_id
+    );
+    let request = state
+        .http
+        .post(&url)
+        .timeout(std::time::Duration::from_secs(5));
+    let response = state.http.send(request).await.map_err(|e| {
+        tracing::error!("Failed to flush digest of merchant {}: {}", merchant_id, e);
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to flush digest: {}", e),
+        )
+    })?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        tracing::error!("Digests/flush answered {}: {}", status, body);
+        return Err((
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to flush digest: {}", status),
+        ));
+    }
+
+    info!("Digest of merchant {} flushing", merchant_id);
+    Ok(StatusCode::ACCEPTED)
+}
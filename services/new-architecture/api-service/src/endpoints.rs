@@ -0,0 +1,545 @@
+use axum::{
+    extract::{Json, Path, State},
+    http::{HeaderMap, StatusCode},
+};
+use chrono::{DateTime, Duration, Utc};
+use event_filter::Filter;
+use http_client::{EgressPolicy, HttpClient, HttpClientConfig};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+use webhook_signing::WEBHOOK_HEADERS;
+
+use crate::{audit, resolve_merchant_id, AppState};
+
+// ==============================================================================
+// MERCHANT ENDPOINTS: Webhook URLs and their signing secrets
+// ==============================================================================
+//
+// Endpoints behind mutual TLS also get a client certificate, presented by
+// svix-caller's direct deliveries and by test webhooks. Its private key is
+// sealed with the merchant's data key before it's stored (payload-crypto
+// crate), so setting one needs PAYLOAD_MASTER_KEYS; it's never returned.
+//
+// An endpoint with a filter (event-filter crate) only gets the events it's
+// true for, e.g. `payment.amount > 10000 && payment.currency == 'USD'`.
+// svix-caller evaluates it on every direct delivery; test webhooks ignore it.
+
+/// How long the old secret keeps signing after a rotation unless overridden
+const DEFAULT_GRACE_PERIOD_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Deserialize)]
+pub struct CreateEndpointRequest {
+    url: String,
+    #[serde(default)]
+    filter: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CreateEndpointResponse {
+    id: Uuid,
+    url: String,
+    secret: String,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct EndpointResponse {
+    id: Uuid,
+    url: String,
+    disabled: bool,
+    previous_secret_expires_at: Option<DateTime<Utc>>,
+    /// Has a client certificate
+    mutual_tls: bool,
+    filter: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct RotateSecretRequest {
+    grace_period_secs: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct RotateSecretResponse {
+    id: Uuid,
+    secret: String,
+    previous_secret_expires_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct SetFilterRequest {
+    /// Unset or empty: every event
+    filter: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SetClientCertificateRequest {
+    /// PEM, leaf first when it's a chain
+    certificate: String,
+    /// PKCS#8 PEM ("BEGIN PRIVATE KEY")
+    private_key: String,
+}
+
+#[derive(Serialize)]
+pub struct TestWebhookResponse {
+    status_code: u16,
+    signatures: usize,
+}
+
+/// The filter as it's stored; None for every event
+fn parse_filter(filter: Option<&str>) -> Result<Option<String>, (StatusCode, String)> {
+    match filter.map(str::trim).filter(|filter| !filter.is_empty()) {
+        None => Ok(None),
+        Some(filter) => Filter::parse(filter)
+            .map(|filter| Some(filter.as_str().to_string()))
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid filter: {}", e))),
+    }
+}
+
+pub async fn create_endpoint(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<CreateEndpointRequest>,
+) -> Result<(StatusCode, Json<CreateEndpointResponse>), (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+    // Checked again on every delivery, in case the name moves
+    state.egress.check_destination(&req.url).await.map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Endpoint URL not allowed: {}", e),
+        )
+    })?;
+    let filter = parse_filter(req.filter.as_deref())?;
+    let secret = webhook_signing::generate_secret();
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+    let result = sqlx::query_as::<_, (Uuid,)>(
+        r#"
+        INSERT INTO merchant_endpoints (merchant_id, url, secret, filter)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(&req.url)
+    .bind(&secret)
+    .bind(&filter)
+    .fetch_one(&mut *tx)
+    .await;
+
+    match result {
+        Ok((id,)) => {
+            audit::commit(tx).await?;
+            info!("Endpoint {} created for merchant {}", id, merchant_id);
+            Ok((
+                StatusCode::CREATED,
+                Json(CreateEndpointResponse {
+                    id,
+                    url: req.url,
+                    secret,
+                }),
+            ))
+        }
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => Err((
+            StatusCode::NOT_FOUND,
+            format!("Merchant not found: {}", merchant_id),
+        )),
+        Err(e) => {
+            tracing::error!("Failed to create endpoint: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to create endpoint: {}", e),
+            ))
+        }
+    }
+}
+
+pub async fn list_endpoints(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<String>,
+) -> Result<Json<Vec<EndpointResponse>>, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let endpoints = sqlx::query_as::<_, EndpointResponse>(
+        r#"
+        SELECT id, url, disabled, previous_secret_expires_at,
+               client_certificate IS NOT NULL AS mutual_tls, filter, created_at
+        FROM merchant_endpoints
+        WHERE merchant_id = $1
+        ORDER BY created_at
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to list endpoints for merchant {}: {}", merchant_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to list endpoints: {}", e),
+        )
+    })?;
+
+    Ok(Json(endpoints))
+}
+
+/// Rotating again inside the grace window replaces the previous secret, so only
+/// the two most recent secrets are ever valid at once.
+pub async fn rotate_secret(
+    State(state): State<AppState>,
+    Path((merchant_id, endpoint_id)): Path<(String, Uuid)>,
+    headers: HeaderMap,
+    req: Option<Json<RotateSecretRequest>>,
+) -> Result<Json<RotateSecretResponse>, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+    let grace_period_secs = req
+        .and_then(|Json(req)| req.grace_period_secs)
+        .unwrap_or(DEFAULT_GRACE_PERIOD_SECS);
+
+    if grace_period_secs < 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "grace_period_secs must not be negative".to_string(),
+        ));
+    }
+
+    let secret = webhook_signing::generate_secret();
+    let previous_secret_expires_at = Utc::now() + Duration::seconds(grace_period_secs);
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+    let result = sqlx::query(
+        r#"
+        UPDATE merchant_endpoints
+        SET previous_secret = secret,
+            secret = $1,
+            previous_secret_expires_at = $2,
+            updated_at = NOW()
+        WHERE id = $3 AND merchant_id = $4
+        "#,
+    )
+    .bind(&secret)
+    .bind(previous_secret_expires_at)
+    .bind(endpoint_id)
+    .bind(merchant_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to rotate secret for endpoint {}: {}", endpoint_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to rotate secret: {}", e),
+        )
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Endpoint not found: {}", endpoint_id),
+        ));
+    }
+
+    audit::commit(tx).await?;
+    info!(
+        "Rotated secret for endpoint {} (previous secret valid until {})",
+        endpoint_id, previous_secret_expires_at
+    );
+
+    Ok(Json(RotateSecretResponse {
+        id: endpoint_id,
+        secret,
+        previous_secret_expires_at,
+    }))
+}
+
+/// Takes effect on the next delivery, including retries of events the
+/// endpoint hasn't had yet
+pub async fn set_filter(
+    State(state): State<AppState>,
+    Path((merchant_id, endpoint_id)): Path<(String, Uuid)>,
+    headers: HeaderMap,
+    Json(req): Json<SetFilterRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+    let filter = parse_filter(req.filter.as_deref())?;
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+    let result = sqlx::query(
+        r#"
+        UPDATE merchant_endpoints
+        SET filter = $1, updated_at = NOW()
+        WHERE id = $2 AND merchant_id = $3
+        "#,
+    )
+    .bind(&filter)
+    .bind(endpoint_id)
+    .bind(merchant_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to set filter for endpoint {}: {}", endpoint_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to set filter: {}", e),
+        )
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Endpoint not found: {}", endpoint_id),
+        ));
+    }
+
+    audit::commit(tx).await?;
+    match &filter {
+        Some(filter) => info!("Endpoint {} filters events by {}", endpoint_id, filter),
+        None => info!("Endpoint {} gets every event", endpoint_id),
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Present this certificate to the endpoint from now on; replaces any earlier
+/// one. Checked to be a certificate and key that belong together.
+pub async fn set_client_certificate(
+    State(state): State<AppState>,
+    Path((merchant_id, endpoint_id)): Path<(String, Uuid)>,
+    headers: HeaderMap,
+    Json(req): Json<SetClientCertificateRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let Some(keyring) = &state.keyring else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Client keys are only stored sealed, and PAYLOAD_MASTER_KEYS isn't set".to_string(),
+        ));
+    };
+    http_client::client_identity(&req.certificate, &req.private_key)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let sealed_key = keyring
+        .seal(
+            &merchant_id.to_string(),
+            &serde_json::Value::String(req.private_key),
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to seal client key for endpoint {}: {}", endpoint_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to seal client key: {}", e),
+            )
+        })?;
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+    let result = sqlx::query(
+        r#"
+        UPDATE merchant_endpoints
+        SET client_certificate = $1, client_key = $2, updated_at = NOW()
+        WHERE id = $3 AND merchant_id = $4
+        "#,
+    )
+    .bind(&req.certificate)
+    .bind(&sealed_key)
+    .bind(endpoint_id)
+    .bind(merchant_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to set client certificate for endpoint {}: {}", endpoint_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to set client certificate: {}", e),
+        )
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Endpoint not found: {}", endpoint_id),
+        ));
+    }
+
+    audit::commit(tx).await?;
+    info!("Client certificate set for endpoint {}", endpoint_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn remove_client_certificate(
+    State(state): State<AppState>,
+    Path((merchant_id, endpoint_id)): Path<(String, Uuid)>,
+    headers: HeaderMap,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+    let result = sqlx::query(
+        r#"
+        UPDATE merchant_endpoints
+        SET client_certificate = NULL, client_key = NULL, updated_at = NOW()
+        WHERE id = $1 AND merchant_id = $2
+        "#,
+    )
+    .bind(endpoint_id)
+    .bind(merchant_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to remove client certificate for endpoint {}: {}", endpoint_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to remove client certificate: {}", e),
+        )
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Endpoint not found: {}", endpoint_id),
+        ));
+    }
+
+    audit::commit(tx).await?;
+    info!("Client certificate removed from endpoint {}", endpoint_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Deliver a signed `webhook.test` event straight to the endpoint, so a
+/// merchant can confirm their receiver accepts the signatures after rotating.
+pub async fn send_test_webhook(
+    State(state): State<AppState>,
+    Path((merchant_id, endpoint_id)): Path<(String, Uuid)>,
+) -> Result<Json<TestWebhookResponse>, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let endpoint = sqlx::query_as::<
+        _,
+        (
+            String,
+            String,
+            Option<String>,
+            Option<DateTime<Utc>>,
+            Option<String>,
+            Option<serde_json::Value>,
+        ),
+    >(
+        r#"
+        SELECT url, secret, previous_secret, previous_secret_expires_at,
+               client_certificate, client_key
+        FROM merchant_endpoints
+        WHERE id = $1 AND merchant_id = $2
+        "#,
+    )
+    .bind(endpoint_id)
+    .bind(merchant_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to fetch endpoint {}: {}", endpoint_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to fetch endpoint: {}", e),
+        )
+    })?;
+
+    let Some((
+        url,
+        secret,
+        previous_secret,
+        previous_secret_expires_at,
+        client_certificate,
+        client_key,
+    )) = endpoint
+    else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Endpoint not found: {}", endpoint_id),
+        ));
+    };
+
+    let msg_id = Uuid::new_v4();
+    let body = serde_json::to_vec(&serde_json::json!({
+        "event_id": msg_id,
+        "event_type": "webhook.test",
+        "payment": {},
+    }))
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let secrets = webhook_signing::active_secrets(
+        &secret,
+        previous_secret.as_deref(),
+        previous_secret_expires_at,
+    );
+    let timestamp = Utc::now().timestamp();
+    let signature = webhook_signing::sign(&secrets, &msg_id.to_string(), timestamp, &body)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    let http = match (client_certificate, client_key) {
+        (Some(certificate), Some(sealed_key)) => {
+            mutual_tls_client(&state, &merchant_id.to_string(), &certificate, &sealed_key)
+                .await
+                .map_err(|e| {
+                    tracing::error!("Client certificate of endpoint {}: {}", endpoint_id, e);
+                    (StatusCode::INTERNAL_SERVER_ERROR, e)
+                })?
+        }
+        _ => state.webhooks.clone(),
+    };
+
+    let request = http
+        .post(&url)
+        .header("content-type", "application/json")
+        .header(WEBHOOK_HEADERS.id, msg_id.to_string())
+        .header(WEBHOOK_HEADERS.timestamp, timestamp.to_string())
+        .header(WEBHOOK_HEADERS.signature, signature)
+        .body(body)
+        .timeout(std::time::Duration::from_secs(5));
+    let response = http.send(request).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to deliver test webhook: {}", e),
+        )
+    })?;
+
+    info!(
+        "Test webhook to endpoint {} returned {}",
+        endpoint_id,
+        response.status()
+    );
+
+    Ok(Json(TestWebhookResponse {
+        status_code: response.status().as_u16(),
+        signatures: secrets.len(),
+    }))
+}
+
+/// A client of its own for one test webhook, presenting the endpoint's
+/// client certificate
+async fn mutual_tls_client(
+    state: &AppState,
+    merchant_id: &str,
+    certificate: &str,
+    sealed_key: &serde_json::Value,
+) -> Result<HttpClient, String> {
+    let keyring = state
+        .keyring
+        .as_ref()
+        .ok_or("client key is sealed and PAYLOAD_MASTER_KEYS isn't set")?;
+    let private_key = keyring
+        .open(merchant_id, sealed_key)
+        .await
+        .map_err(|e| e.to_string())?;
+    let private_key = private_key
+        .as_str()
+        .ok_or("client key doesn't open to a PEM string")?;
+    HttpClient::with_builder(
+        HttpClientConfig {
+            egress: Some(EgressPolicy::clone(&state.egress)),
+            ..HttpClientConfig::new("endpoint_test")
+        },
+        reqwest::Client::builder()
+            .identity(http_client::client_identity(certificate, private_key)?),
+        &state.http_metrics,
+    )
+}
@@ -0,0 +1,151 @@
+use axum::{
+    extract::{Json, State},
+    http::{HeaderMap, StatusCode},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{audit, resolve_merchant_id, AppState};
+
+// ==============================================================================
+// EVENT REPLAY: Re-insert outbox rows so the pipeline delivers them again
+// ==============================================================================
+//
+// domain_events is the source of truth for what should have been delivered.
+// Replaying writes NEW rows (replay_of = original id) instead of touching the
+// originals, so Sequin picks them up from the WAL like any other event and the
+// audit trail of the first delivery stays intact. Each replay is recorded in
+// audit_log (audit.rs) with what was asked for and how many events it wrote.
+
+#[derive(Deserialize, Serialize)]
+pub struct ReplayRequest {
+    payment_id: Option<Uuid>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    merchant_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ReplayedEvent {
+    id: i64,
+    replay_of: i64,
+    event_type: String,
+    object_id: Uuid,
+}
+
+#[derive(Serialize)]
+pub struct ReplayResponse {
+    replayed: usize,
+    events: Vec<ReplayedEvent>,
+}
+
+pub async fn replay_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<ReplayRequest>,
+) -> Result<Json<ReplayResponse>, (StatusCode, String)> {
+    // Only original events are replayed; replaying a range twice must not
+    // fan out into replays of replays.
+    let query = match (req.payment_id, req.from, req.to) {
+        (Some(payment_id), None, None) => sqlx::query_as::<_, (i64, i64, String, Uuid, Uuid)>(
+            r#"
+            INSERT INTO domain_events (event_type, object_id, merchant_id, mode, payload, replay_of, schema_version)
+            SELECT event_type, object_id, merchant_id, mode, payload, id, schema_version
+            FROM domain_events
+            WHERE replay_of IS NULL AND object_id = $1
+            ORDER BY id
+            RETURNING id, replay_of, event_type, object_id, merchant_id
+            "#,
+        )
+        .bind(payment_id),
+        (None, Some(from), Some(to)) if from < to => {
+            let merchant_id = req.merchant_id.as_deref().map(resolve_merchant_id);
+            sqlx::query_as::<_, (i64, i64, String, Uuid, Uuid)>(
+                r#"
+                INSERT INTO domain_events (event_type, object_id, merchant_id, mode, payload, replay_of, schema_version)
+                SELECT event_type, object_id, merchant_id, mode, payload, id, schema_version
+                FROM domain_events
+                WHERE replay_of IS NULL
+                  AND created_at >= $1 AND created_at < $2
+                  AND ($3::UUID IS NULL OR merchant_id = $3)
+                ORDER BY id
+                RETURNING id, replay_of, event_type, object_id, merchant_id
+                "#,
+            )
+            .bind(from)
+            .bind(to)
+            .bind(merchant_id)
+        }
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Provide either payment_id or a time range with from < to".to_string(),
+            ))
+        }
+    };
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+    match query.fetch_all(&mut *tx).await {
+        Ok(rows) => {
+            // The merchant whose events they were, when it's one
+            let merchant_id = rows
+                .first()
+                .map(|row| row.4)
+                .filter(|first| rows.iter().all(|row| row.4 == *first));
+            let events: Vec<ReplayedEvent> = rows
+                .into_iter()
+                .map(|(id, replay_of, event_type, object_id, _)| ReplayedEvent {
+                    id,
+                    replay_of,
+                    event_type,
+                    object_id,
+                })
+                .collect();
+
+            record_replay(&mut tx, merchant_id, &req, &events).await?;
+            audit::commit(tx).await?;
+            info!("Replayed {} events from domain_events", events.len());
+
+            Ok(Json(ReplayResponse {
+                replayed: events.len(),
+                events,
+            }))
+        }
+        Err(e) => {
+            tracing::error!("Failed to replay events: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to replay events: {}", e),
+            ))
+        }
+    }
+}
+
+/// One audit_log entry per replay; the replayed event ids are the entity,
+/// as a replay has no id of its own
+async fn record_replay(
+    tx: &mut sqlx::Transaction<'static, sqlx::Postgres>,
+    merchant_id: Option<Uuid>,
+    req: &ReplayRequest,
+    events: &[ReplayedEvent],
+) -> Result<(), (StatusCode, String)> {
+    let (Some(first), Some(last)) = (events.first(), events.last()) else {
+        return Ok(());
+    };
+    sqlx::query("SELECT record_audit('replay', $1, $2, 'replay', NULL, $3)")
+        .bind(format!("{}-{}", first.id, last.id))
+        .bind(merchant_id)
+        .bind(serde_json::json!({ "request": req, "events": events.len() }))
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to record replay: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to record replay: {}", e),
+            )
+        })?;
+    Ok(())
+}
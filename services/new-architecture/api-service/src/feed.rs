@@ -0,0 +1,107 @@
+use axum::{
+    extract::{Json, Query, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{resolve_merchant_id, AppState};
+
+// ==============================================================================
+// EVENT FEED: Pull-based alternative to webhooks
+// ==============================================================================
+//
+// Merchants page through their domain_events in id order. The cursor is the
+// last event id they have seen, and polling with `after=<next_cursor>` never
+// repeats an event. Replays are excluded: they exist to re-push webhooks, a
+// polling client already has the original.
+//
+// Ids are taken when a row is inserted, not when it commits, so a transaction
+// that commits late can add an id below the cursor. The feed only returns
+// rows written before the oldest transaction still running (its xmin against
+// the snapshot's), holding back later ones until that transaction is over.
+// The payments trigger writes its event after the payment row, so the
+// transaction already has its xid when it takes the id; a lone INSERT gets
+// its xid just after, and only two of those racing within that instant can
+// still come out of order. The cost is that a long-running writing
+// transaction delays the feed for as long as it stays open.
+
+const DEFAULT_FEED_LIMIT: i64 = 100;
+const MAX_FEED_LIMIT: i64 = 1000;
+
+#[derive(Deserialize)]
+pub struct FeedQuery {
+    merchant_id: String,
+    after: Option<i64>,
+    limit: Option<i64>,
+    mode: Option<String>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct FeedEvent {
+    id: i64,
+    event_type: String,
+    object_id: Uuid,
+    mode: String,
+    payload: Option<serde_json::Value>,
+    created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct FeedResponse {
+    data: Vec<FeedEvent>,
+    /// Pass as `after` on the next poll; unchanged when there is nothing new
+    next_cursor: i64,
+    has_more: bool,
+}
+
+pub async fn list_feed(
+    State(state): State<AppState>,
+    Query(query): Query<FeedQuery>,
+) -> Result<Json<FeedResponse>, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&query.merchant_id);
+    let after = query.after.unwrap_or(0);
+    let limit = query.limit.unwrap_or(DEFAULT_FEED_LIMIT).clamp(1, MAX_FEED_LIMIT);
+
+    // Fetch one extra row to know whether another page exists
+    let mut data = sqlx::query_as::<_, FeedEvent>(
+        r#"
+        SELECT id, event_type, object_id, mode, payload, created_at
+        FROM domain_events
+        WHERE merchant_id = $1
+          AND id > $2
+          AND replay_of IS NULL
+          AND ($3::TEXT IS NULL OR mode = $3)
+          -- Written before every transaction still running; age() is
+          -- negative for rows frozen more than 2^31 transactions ago
+          AND (age(xmin) > age(pg_snapshot_xmin(pg_current_snapshot())::TEXT::XID)
+               OR age(xmin) < 0)
+        ORDER BY id
+        LIMIT $4
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(after)
+    .bind(&query.mode)
+    .bind(limit + 1)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to read event feed for merchant {}: {}", merchant_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read event feed: {}", e),
+        )
+    })?;
+
+    let has_more = data.len() as i64 > limit;
+    data.truncate(limit as usize);
+    let next_cursor = data.last().map(|e| e.id).unwrap_or(after);
+
+    Ok(Json(FeedResponse {
+        data,
+        next_cursor,
+        has_more,
+    }))
+}
@@ -0,0 +1,40 @@
+use health_checks::{checks, HealthRegistry};
+use sqlx::PgPool;
+
+// ==============================================================================
+// HEALTH: /livez for "is the process alive", /readyz for "should I get traffic"
+// ==============================================================================
+//
+// Served by the health-checks crate. Liveness never touches the database, so
+// a DB outage doesn't get the pod restarted in a loop. Readiness does, so
+// load balancers stop routing payments to an instance that can't write them:
+// the database answers, the pool has room, and the schema is there.
+
+/// Tables this service writes to or reads from; a missing one means init.sql
+/// (our migration) hasn't been applied to this database yet.
+const REQUIRED_TABLES: &[&str] = &[
+    "merchants",
+    "merchant_endpoints",
+    "endpoint_transformations",
+    "merchant_api_keys",
+    "merchant_maintenance_windows",
+    "svix_dead_letters",
+    "dead_letter_actions",
+    "payments",
+    "domain_events",
+    "delivery_attempts",
+    "digest_items",
+    "audit_log",
+];
+
+pub fn registry(db: &PgPool, max_pool_utilization: f64) -> HealthRegistry {
+    let (database, pool, migrations) = (db.clone(), db.clone(), db.clone());
+    HealthRegistry::new()
+        .readiness("database", move || checks::postgres(database.clone()))
+        .readiness("pool", move || {
+            checks::pool(pool.clone(), max_pool_utilization)
+        })
+        .readiness("migrations", move || {
+            checks::tables(migrations.clone(), REQUIRED_TABLES)
+        })
+}
@@ -1,18 +1,63 @@
 use axum::{
     extract::{Json, State},
     http::StatusCode,
-    routing::{get, post},
+    middleware,
+    routing::{delete, get, post, put},
     Router,
 };
+use http_client::{EgressPolicy, HttpClient, HttpClientConfig, HttpMetrics};
+use payload_crypto::{Keyring, LocalMasterKey};
 use serde::{Deserialize, Serialize};
+use service_metrics::ServiceMetrics;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
+use std::sync::Arc;
 use tracing::info;
 use uuid::Uuid;
 
+mod admin;
+mod api_keys;
+mod audit;
+mod batch;
+mod confirmations;
+mod currency;
+mod dead_letters;
+mod delivery_windows;
+mod digests;
+mod endpoints;
+mod events;
+mod feed;
+mod health;
+mod merchants;
+mod quota;
+mod settings;
+mod settlement;
+mod transformations;
+
+use settings::Settings;
+
+/// Statuses a payment can move through; each change emits `payment.<status>`
+const PAYMENT_STATUSES: &[&str] = &["pending", "processing", "succeeded", "failed", "refunded"];
+
 #[derive(Clone)]
 struct AppState {
     db: PgPool,
+    /// Restate ingress (http-client crate)
+    http: HttpClient,
+    /// Test webhooks to merchant endpoints, under `egress`
+    webhooks: HttpClient,
+    /// Where endpoint URLs may point
+    egress: Arc<EgressPolicy>,
+    /// 402 (billing-style hard limit) or 429 (retry next period)
+    quota_exceeded_status: StatusCode,
+    async_settlement: bool,
+    /// Where merchant confirmations are forwarded (confirmations.rs)
+    restate_ingress_url: String,
+    /// For clients built per request (endpoints.rs)
+    http_metrics: HttpMetrics,
+    /// Opens sealed dead-letter payloads and seals endpoint client keys; set
+    /// with PAYLOAD_MASTER_KEYS
+    keyring: Option<Arc<Keyring<LocalMasterKey>>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -23,11 +68,44 @@ struct Payment {
     status: String,
 }
 
+/// Test-mode payments go through the same pipeline as live ones, tagged so
+/// merchants can keep them out of production systems.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Mode {
+    Test,
+    #[default]
+    Live,
+}
+
+impl Mode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Mode::Test => "test",
+            Mode::Live => "live",
+        }
+    }
+
+    /// The column has a CHECK constraint, so anything but 'test' is live
+    fn from_db(value: &str) -> Self {
+        if value == "test" {
+            Mode::Test
+        } else {
+            Mode::Live
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct CreatePaymentRequest {
     merchant_id: Option<String>,
     amount: i64,
     currency: String,
+    /// Defaults to the merchant's mode, or live for unknown merchants
+    mode: Option<Mode>,
+    /// Create the payment pending and let the settlement worker finish it;
+    /// defaults to ASYNC_SETTLEMENT
+    async_settlement: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -36,14 +114,18 @@ struct PaymentResponse {
     amount: i64,
     currency: String,
     status: String,
+    mode: Mode,
 }
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    logging::init(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
 
-    let database_url = std::env::var("DATABASE_URL")
-        .expect("DATABASE_URL must be set");
+    let settings: Settings = config::load_or_exit("api-service");
+    // Validated to parse
+    let egress = settings.egress_policy().expect("Invalid egress policy");
+    // Validated to be set
+    let database_url = settings.database_url.unwrap_or_default();
 
     let pool = PgPoolOptions::new()
         .max_connections(50) // Increased for load testing
@@ -51,14 +133,186 @@ async fn main() {
         .await
         .expect("Failed to connect to database");
 
-    let state = AppState { db: pool };
+    let health = health::registry(&pool, settings.readiness_max_pool_utilization);
+
+    let quota_exceeded_status = match settings.quota_exceeded_status {
+        429 => StatusCode::TOO_MANY_REQUESTS,
+        _ => StatusCode::PAYMENT_REQUIRED,
+    };
+
+    let async_settlement = settings.async_settlement;
+
+    let restate_ingress_url = settings
+        .restate_ingress_url
+        .trim_end_matches('/')
+        .to_string();
+
+    // Runs regardless of ASYNC_SETTLEMENT, requests can opt in individually
+    if settings.settlement_worker {
+        tokio::spawn(settlement::run_settlement_worker(
+            pool.clone(),
+            settlement::SettlementConfig::from_env(),
+        ));
+    }
+
+    // Validated to parse
+    let keyring = settings
+        .payload_master_keys
+        .as_deref()
+        .and_then(|keys| LocalMasterKey::parse(keys).ok())
+        .map(|master| Arc::new(Keyring::new(pool.clone(), master)));
+
+    let metrics = ServiceMetrics::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    let http_metrics = HttpMetrics::new(metrics.registry());
+    let http = HttpClient::new(HttpClientConfig::new("outbound"), &http_metrics)
+        .expect("Failed to build HTTP client");
+    let webhooks = HttpClient::new(
+        HttpClientConfig {
+            egress: Some(egress.clone()),
+            ..HttpClientConfig::new("endpoint_test")
+        },
+        &http_metrics,
+    )
+    .expect("Failed to build HTTP client");
+
+    let state = AppState {
+        db: pool,
+        http,
+        webhooks,
+        egress: Arc::new(egress),
+        quota_exceeded_status,
+        async_settlement,
+        restate_ingress_url,
+        http_metrics,
+        keyring,
+    };
 
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/payments", post(create_payment))
+        .route("/payments/status-batch", post(batch::update_status_batch))
+        .route("/payments/:id/settle", post(settlement::settle_payment))
+        .route("/merchants", post(merchants::create_merchant))
+        .route("/merchants/:id", get(merchants::get_merchant))
+        .route("/merchants/:id/usage", get(quota::get_usage))
+        .route("/merchants/:id/quota", put(quota::set_quota))
+        .route(
+            "/merchants/:id/payload-mode",
+            put(merchants::set_payload_mode),
+        )
+        .route(
+            "/merchants/:id/payload-fields",
+            put(merchants::set_payload_fields),
+        )
+        .route(
+            "/merchants/:id/payload-version",
+            put(merchants::set_payload_version),
+        )
+        .route(
+            "/merchants/:id/payload-template",
+            put(merchants::set_payload_template),
+        )
+        .route(
+            "/merchants/:id/confirmation",
+            put(merchants::set_confirmation),
+        )
+        .route(
+            "/merchants/:id/egress-proxy",
+            put(merchants::set_egress_proxy),
+        )
+        .route(
+            "/merchants/:id/delivery-window",
+            get(delivery_windows::get_delivery_window).put(delivery_windows::set_delivery_window),
+        )
+        .route(
+            "/merchants/:id/maintenance-windows",
+            get(delivery_windows::list_maintenance_windows)
+                .post(delivery_windows::create_maintenance_window),
+        )
+        .route(
+            "/merchants/:id/maintenance-windows/:wid",
+            delete(delivery_windows::delete_maintenance_window),
+        )
+        .route(
+            "/merchants/:id/pause",
+            get(delivery_windows::get_pause).post(delivery_windows::pause),
+        )
+        .route("/merchants/:id/resume", post(delivery_windows::resume))
+        .route(
+            "/merchants/:id/digest",
+            get(digests::get_digest).put(digests::set_digest),
+        )
+        .route("/merchants/:id/digest/flush", post(digests::flush_digest))
+        .route(
+            "/merchants/:id/endpoints",
+            get(endpoints::list_endpoints).post(endpoints::create_endpoint),
+        )
+        .route(
+            "/merchants/:id/endpoints/:eid/rotate-secret",
+            post(endpoints::rotate_secret),
+        )
+        .route("/merchants/:id/endpoints/:eid/filter", put(endpoints::set_filter))
+        .route(
+            "/merchants/:id/endpoints/:eid/transformations",
+            get(transformations::list_transformations)
+                .post(transformations::create_transformation),
+        )
+        .route(
+            "/merchants/:id/endpoints/:eid/transformation",
+            put(transformations::activate_transformation),
+        )
+        .route(
+            "/merchants/:id/endpoints/:eid/transformations/dry-run",
+            post(transformations::dry_run_transformation),
+        )
+        .route(
+            "/merchants/:id/endpoints/:eid/client-certificate",
+            put(endpoints::set_client_certificate).delete(endpoints::remove_client_certificate),
+        )
+        .route(
+            "/merchants/:id/endpoints/:eid/test",
+            post(endpoints::send_test_webhook),
+        )
+        .route(
+            "/merchants/:id/api-keys",
+            get(api_keys::list_api_keys).post(api_keys::create_api_key),
+        )
+        .route(
+            "/merchants/:id/api-keys/:kid",
+            delete(api_keys::revoke_api_key),
+        )
+        .route("/confirm/:confirmation_id", post(confirmations::confirm))
+        .route("/events", get(feed::list_feed))
+        .route("/events/replay", post(events::replay_events))
+        .route("/admin/events", get(admin::list_events))
+        .route("/admin/events/:id", get(admin::get_event))
+        .route("/admin/audit-log", get(audit::list_audit_log))
+        .route("/admin/dead-letters", get(dead_letters::list_dead_letters))
+        .route(
+            "/admin/dead-letters/redrive",
+            post(dead_letters::redrive_dead_letters),
+        )
+        .route(
+            "/admin/dead-letters/purge",
+            post(dead_letters::purge_dead_letters),
+        )
+        .route(
+            "/admin/dead-letters/actions",
+            get(dead_letters::list_actions),
+        )
+        .route(
+            "/admin/dead-letters/:id",
+            get(dead_letters::get_dead_letter),
+        )
+        .merge(health_checks::router(health))
+        .merge(service_metrics::router(metrics.clone()))
+        .route_layer(middleware::from_fn_with_state(
+            metrics,
+            service_metrics::track_http,
+        ))
         .with_state(state);
 
-    let port = std::env::var("PORT").unwrap_or_else(|_| "3001".to_string());
+    let port = settings.port;
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port))
         .await
@@ -73,40 +327,82 @@ async fn health_check() -> &'static str {
     "OK"
 }
 
+/// Map a merchant_id to the UUID stored in the database:
+/// - If it's a valid UUID, use it directly
+/// - If it's a string, convert to deterministic UUID (for testing)
+fn resolve_merchant_id(id: &str) -> Uuid {
+    Uuid::parse_str(id).unwrap_or_else(|_| {
+        // Convert string to deterministic UUID using v5 (name-based)
+        Uuid::new_v5(&Uuid::NAMESPACE_DNS, id.as_bytes())
+    })
+}
+
+/// Merchants are optional (tests use ad-hoc merchant ids), unknown ones are live
+async fn merchant_mode(db: &PgPool, merchant_id: Uuid) -> Result<Mode, (StatusCode, String)> {
+    let row = sqlx::query_as::<_, (String,)>("SELECT mode FROM merchants WHERE id = $1")
+        .bind(merchant_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to look up merchant {}: {}", merchant_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to look up merchant: {}", e),
+            )
+        })?;
+
+    Ok(row.map(|(mode,)| Mode::from_db(&mode)).unwrap_or_default())
+}
+
+#[tracing::instrument(skip_all, fields(correlation_id, merchant_id))]
 async fn create_payment(
     State(state): State<AppState>,
     Json(req): Json<CreatePaymentRequest>,
 ) -> Result<(StatusCode, Json<PaymentResponse>), (StatusCode, String)> {
     let payment_id = Uuid::new_v4();
 
-    // Use provided merchant_id:
-    // - If it's a valid UUID, use it directly
-    // - If it's a string, convert to deterministic UUID (for testing)
-    // - Otherwise generate a new random UUID
+    // Use provided merchant_id, otherwise generate a new random UUID
     let merchant_id = match &req.merchant_id {
-        Some(id) => {
-            Uuid::parse_str(id).unwrap_or_else(|_| {
-                // Convert string to deterministic UUID using v5 (name-based)
-                Uuid::new_v5(&Uuid::NAMESPACE_DNS, id.as_bytes())
-            })
-        }
+        Some(id) => resolve_merchant_id(id),
         None => Uuid::new_v4(),
     };
 
+    // The payment id follows the webhook through every service's logs
+    let span = tracing::Span::current();
+    span.record("correlation_id", tracing::field::display(payment_id));
+    span.record("merchant_id", tracing::field::display(merchant_id));
+
+    let mode = match req.mode {
+        Some(mode) => mode,
+        None => merchant_mode(&state.db, merchant_id).await?,
+    };
+
+    let currency = currency::validate_amount(&state.db, &req.currency, req.amount).await?;
+
+    // The trigger below emits exactly one event for this payment
+    quota::enforce_quota(&state, merchant_id, 1).await?;
+
+    let status = if req.async_settlement.unwrap_or(state.async_settlement) {
+        "pending"
+    } else {
+        "succeeded"
+    };
+
     // ATOMIC OPERATION: INSERT payment, trigger creates event automatically
     // The PostgreSQL trigger fires automatically and creates the domain event
     // If this transaction fails, BOTH payment and event are rolled back
     let result = sqlx::query(
         r#"
-        INSERT INTO payments (id, merchant_id, amount, currency, status)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO payments (id, merchant_id, amount, currency, status, mode)
+        VALUES ($1, $2, $3, $4, $5, $6)
         "#,
     )
     .bind(payment_id)
     .bind(merchant_id)
     .bind(req.amount)
-    .bind(&req.currency)
-    .bind("succeeded")
+    .bind(&currency)
+    .bind(status)
+    .bind(mode.as_str())
     .execute(&state.db)
     .await;
 
@@ -124,8 +420,9 @@ async fn create_payment(
                 Json(PaymentResponse {
                     id: payment_id,
                     amount: req.amount,
-                    currency: req.currency,
-                    status: "succeeded".to_string(),
+                    currency,
+                    status: status.to_string(),
+                    mode,
                 }),
             ))
         }
@@ -0,0 +1,224 @@
+use axum::{
+    extract::{Json, Path, State},
+    http::{HeaderMap, StatusCode},
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{audit, resolve_merchant_id, AppState};
+
+// ==============================================================================
+// QUOTAS: Monthly event allowance per merchant
+// ==============================================================================
+//
+// Usage is counted by a trigger on domain_events (so replays and batch updates
+// count too) and checked here before a request would emit more events. The
+// check and the insert aren't one transaction, so concurrent requests can
+// overshoot a quota by a few events - fine for billing, not a hard guarantee.
+
+#[derive(Deserialize)]
+pub struct SetQuotaRequest {
+    /// None removes the quota
+    monthly_event_quota: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct UsageResponse {
+    merchant_id: Uuid,
+    period: NaiveDate,
+    event_count: i64,
+    monthly_event_quota: Option<i64>,
+}
+
+/// Reject the request if emitting `new_events` more events would put the
+/// merchant over its monthly quota. The first rejection in a period also
+/// emits a `quota.exceeded` event so the merchant finds out by webhook.
+pub async fn enforce_quota(
+    state: &AppState,
+    merchant_id: Uuid,
+    new_events: i64,
+) -> Result<(), (StatusCode, String)> {
+    let row = sqlx::query_as::<_, (Option<i64>, i64, String)>(
+        r#"
+        SELECT m.monthly_event_quota, COALESCE(u.event_count, 0), m.mode
+        FROM merchants m
+        LEFT JOIN merchant_usage u
+          ON u.merchant_id = m.id
+         AND u.period = date_trunc('month', NOW() AT TIME ZONE 'UTC')::DATE
+        WHERE m.id = $1
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to check quota for merchant {}: {}", merchant_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to check quota: {}", e),
+        )
+    })?;
+
+    // Unknown merchants and merchants without a quota are unlimited
+    let Some((Some(quota), used, mode)) = row else {
+        return Ok(());
+    };
+
+    if used + new_events <= quota {
+        return Ok(());
+    }
+
+    warn!(
+        "Merchant {} over quota: {} used + {} requested > {}",
+        merchant_id, used, new_events, quota
+    );
+
+    // Only the request that flips quota_exceeded_at emits the event
+    let notified = sqlx::query(
+        r#"
+        WITH flagged AS (
+            INSERT INTO merchant_usage (merchant_id, period, event_count, quota_exceeded_at)
+            VALUES ($1, date_trunc('month', NOW() AT TIME ZONE 'UTC')::DATE, 0, NOW())
+            ON CONFLICT (merchant_id, period)
+            DO UPDATE SET quota_exceeded_at = NOW()
+            WHERE merchant_usage.quota_exceeded_at IS NULL
+            RETURNING merchant_id, period, event_count
+        )
+        INSERT INTO domain_events (event_type, object_id, merchant_id, mode, payload)
+        SELECT 'quota.exceeded', merchant_id, merchant_id, $2,
+               jsonb_build_object(
+                   'merchant_id', merchant_id,
+                   'period', period,
+                   'event_count', event_count,
+                   'monthly_event_quota', $3::BIGINT
+               )
+        FROM flagged
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(&mode)
+    .bind(quota)
+    .execute(&state.db)
+    .await;
+
+    match notified {
+        Ok(result) if result.rows_affected() > 0 => {
+            info!("Emitted quota.exceeded for merchant {}", merchant_id);
+        }
+        Ok(_) => {}
+        Err(e) => warn!("Failed to emit quota.exceeded for merchant {}: {}", merchant_id, e),
+    }
+
+    Err((
+        state.quota_exceeded_status,
+        format!(
+            "Monthly event quota exceeded for merchant {}: {} of {} used",
+            merchant_id, used, quota
+        ),
+    ))
+}
+
+pub async fn get_usage(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<String>,
+) -> Result<Json<UsageResponse>, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let row = sqlx::query_as::<_, (NaiveDate, i64, Option<i64>)>(
+        r#"
+        SELECT date_trunc('month', NOW() AT TIME ZONE 'UTC')::DATE,
+               COALESCE(u.event_count, 0),
+               m.monthly_event_quota
+        FROM merchants m
+        LEFT JOIN merchant_usage u
+          ON u.merchant_id = m.id
+         AND u.period = date_trunc('month', NOW() AT TIME ZONE 'UTC')::DATE
+        WHERE m.id = $1
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to fetch usage for merchant {}: {}", merchant_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to fetch usage: {}", e),
+        )
+    })?;
+
+    match row {
+        Some((period, event_count, monthly_event_quota)) => Ok(Json(UsageResponse {
+            merchant_id,
+            period,
+            event_count,
+            monthly_event_quota,
+        })),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            format!("Merchant not found: {}", merchant_id),
+        )),
+    }
+}
+
+pub async fn set_quota(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<SetQuotaRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    if req.monthly_event_quota.is_some_and(|quota| quota < 0) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "monthly_event_quota must not be negative".to_string(),
+        ));
+    }
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+    let result = sqlx::query("UPDATE merchants SET monthly_event_quota = $1 WHERE id = $2")
+        .bind(req.monthly_event_quota)
+        .bind(merchant_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to set quota for merchant {}: {}", merchant_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to set quota: {}", e),
+            )
+        })?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Merchant not found: {}", merchant_id),
+        ));
+    }
+
+    audit::commit(tx).await?;
+
+    // A changed quota can be exceeded (and notified about) again this period
+    if let Err(e) = sqlx::query(
+        r#"
+        UPDATE merchant_usage SET quota_exceeded_at = NULL
+        WHERE merchant_id = $1
+          AND period = date_trunc('month', NOW() AT TIME ZONE 'UTC')::DATE
+        "#,
+    )
+    .bind(merchant_id)
+    .execute(&state.db)
+    .await
+    {
+        warn!("Failed to reset quota notification for merchant {}: {}", merchant_id, e);
+    }
+
+    info!(
+        "Quota for merchant {} set to {:?}",
+        merchant_id, req.monthly_event_quota
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
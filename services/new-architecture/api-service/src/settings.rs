@@ -0,0 +1,87 @@
+use http_client::EgressPolicy;
+use serde::{Deserialize, Serialize};
+
+// ==============================================================================
+// SETTINGS: Everything main.rs reads at startup
+// ==============================================================================
+//
+// Loaded through the shared config crate: defaults below, then
+// config/api-service.toml (or CONFIG_FILE), then the environment variable of
+// the same name in upper case. The settlement worker's own settings are read
+// by SettlementConfig::from_env (settlement.rs).
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Required
+    pub database_url: Option<String>,
+    /// /readyz fails once this fraction of the pool is checked out
+    pub readiness_max_pool_utilization: f64,
+    /// 402 (billing-style hard limit) or 429 (retry next period)
+    pub quota_exceeded_status: u16,
+    pub async_settlement: bool,
+    pub restate_ingress_url: String,
+    pub settlement_worker: bool,
+    pub port: u16,
+    /// Set where svix-caller seals payloads, to show dead letters opened
+    /// (payload-crypto crate)
+    pub payload_master_keys: Option<String>,
+    /// CIDRs endpoint URLs may reach although they aren't public, e.g. a
+    /// local network in development (http-client's egress policy)
+    pub egress_allowed_networks: String,
+    /// Ports and ranges endpoint URLs may use
+    pub egress_allowed_ports: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            database_url: None,
+            readiness_max_pool_utilization: 0.9,
+            quota_exceeded_status: 402,
+            async_settlement: false,
+            restate_ingress_url: "http://restate:8080".to_string(),
+            settlement_worker: true,
+            port: 3001,
+            payload_master_keys: None,
+            egress_allowed_networks: String::new(),
+            egress_allowed_ports: http_client::ALLOWED_PORTS.to_string(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn egress_policy(&self) -> Result<EgressPolicy, String> {
+        EgressPolicy::parse(&self.egress_allowed_networks, &self.egress_allowed_ports)
+    }
+}
+
+impl config::Validate for Settings {
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.database_url.is_none() {
+            problems.push("DATABASE_URL: required".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.readiness_max_pool_utilization) {
+            problems.push(format!(
+                "READINESS_MAX_POOL_UTILIZATION: {} is not between 0 and 1",
+                self.readiness_max_pool_utilization
+            ));
+        }
+        if !matches!(self.quota_exceeded_status, 402 | 429) {
+            problems.push(format!(
+                "QUOTA_EXCEEDED_STATUS: {} is neither 402 nor 429",
+                self.quota_exceeded_status
+            ));
+        }
+        if let Some(keys) = &self.payload_master_keys {
+            if let Err(e) = payload_crypto::LocalMasterKey::parse(keys) {
+                problems.push(format!("PAYLOAD_MASTER_KEYS: {}", e));
+            }
+        }
+        if let Err(e) = self.egress_policy() {
+            problems.push(format!("EGRESS_ALLOWED_NETWORKS/EGRESS_ALLOWED_PORTS: {}", e));
+        }
+        problems
+    }
+}
@@ -0,0 +1,181 @@
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::AppState;
+
+// ==============================================================================
+// ASYNC SETTLEMENT: pending -> processing -> succeeded | failed
+// ==============================================================================
+//
+// Payments created with async settlement start out pending. A background task
+// moves them forward one step per tick, and the status trigger emits an event
+// for every step, so each payment produces a multi-event stream in order.
+// Rows are claimed with FOR UPDATE SKIP LOCKED, so several api-service
+// instances can run the task without settling a payment twice.
+
+#[derive(Clone)]
+pub struct SettlementConfig {
+    pub interval: Duration,
+    /// Minimum time a payment spends in each step before moving on
+    pub delay: Duration,
+    /// Fraction of payments the simulated processor declines
+    pub failure_rate: f64,
+    pub batch_size: i64,
+}
+
+impl SettlementConfig {
+    pub fn from_env() -> Self {
+        let env_u64 = |name: &str, default: u64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+
+        SettlementConfig {
+            interval: Duration::from_millis(env_u64("SETTLEMENT_INTERVAL_MS", 2000)),
+            delay: Duration::from_millis(env_u64("SETTLEMENT_DELAY_MS", 3000)),
+            failure_rate: std::env::var("SETTLEMENT_FAILURE_RATE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.1),
+            batch_size: env_u64("SETTLEMENT_BATCH_SIZE", 100) as i64,
+        }
+    }
+}
+
+pub async fn run_settlement_worker(db: PgPool, config: SettlementConfig) {
+    info!(
+        "Settlement worker started (interval {:?}, delay {:?}, failure rate {})",
+        config.interval, config.delay, config.failure_rate
+    );
+
+    let mut ticker = tokio::time::interval(config.interval);
+    loop {
+        ticker.tick().await;
+        // Settle before advancing, so a payment spends at least one tick in
+        // processing even with a zero delay
+        if let Err(e) = settle_processing(&db, &config).await {
+            warn!("Settlement tick failed: {}", e);
+        }
+        if let Err(e) = start_processing(&db, &config).await {
+            warn!("Settlement tick failed: {}", e);
+        }
+    }
+}
+
+async fn start_processing(db: &PgPool, config: &SettlementConfig) -> Result<(), sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE payments SET status = 'processing', updated_at = NOW()
+        WHERE id IN (
+            SELECT id FROM payments
+            WHERE status = 'pending'
+              AND updated_at < NOW() - make_interval(secs => $1)
+            ORDER BY updated_at
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+        )
+        "#,
+    )
+    .bind(config.delay.as_secs_f64())
+    .bind(config.batch_size)
+    .execute(db)
+    .await?;
+
+    if result.rows_affected() > 0 {
+        info!("Settlement: {} payments now processing", result.rows_affected());
+    }
+    Ok(())
+}
+
+async fn settle_processing(db: &PgPool, config: &SettlementConfig) -> Result<(), sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE payments
+        SET status = CASE WHEN random() < $1 THEN 'failed' ELSE 'succeeded' END,
+            updated_at = NOW()
+        WHERE id IN (
+            SELECT id FROM payments
+            WHERE status = 'processing'
+              AND updated_at < NOW() - make_interval(secs => $2)
+            ORDER BY updated_at
+            LIMIT $3
+            FOR UPDATE SKIP LOCKED
+        )
+        "#,
+    )
+    .bind(config.failure_rate)
+    .bind(config.delay.as_secs_f64())
+    .bind(config.batch_size)
+    .execute(db)
+    .await?;
+
+    if result.rows_affected() > 0 {
+        info!("Settlement: {} payments settled", result.rows_affected());
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct SettleRequest {
+    status: String,
+}
+
+#[derive(Serialize)]
+pub struct SettleResponse {
+    id: Uuid,
+    status: String,
+}
+
+/// Settle one payment right away instead of waiting for the worker
+pub async fn settle_payment(
+    State(state): State<AppState>,
+    Path(payment_id): Path<Uuid>,
+    Json(req): Json<SettleRequest>,
+) -> Result<Json<SettleResponse>, (StatusCode, String)> {
+    if req.status != "succeeded" && req.status != "failed" {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "status must be 'succeeded' or 'failed'".to_string(),
+        ));
+    }
+
+    let result = sqlx::query(
+        r#"
+        UPDATE payments SET status = $1, updated_at = NOW()
+        WHERE id = $2 AND status IN ('pending', 'processing')
+        "#,
+    )
+    .bind(&req.status)
+    .bind(payment_id)
+    .execute(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to settle payment {}: {}", payment_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to settle payment: {}", e),
+        )
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Payment {} not found or already settled", payment_id),
+        ));
+    }
+
+    info!("Payment {} settled manually: {}", payment_id, req.status);
+    Ok(Json(SettleResponse {
+        id: payment_id,
+        status: req.status,
+    }))
+}
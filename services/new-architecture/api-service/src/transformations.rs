@@ -0,0 +1,339 @@
+use axum::{
+    extract::{Json, Path, State},
+    http::{HeaderMap, StatusCode},
+};
+use chrono::{DateTime, Utc};
+use payload_script::Script;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{audit, resolve_merchant_id, AppState};
+
+// ==============================================================================
+// ENDPOINT TRANSFORMATIONS: Rhai scripts reshaping an endpoint's webhooks
+// ==============================================================================
+//
+// Each save is a new version (1, 2, ...) of the endpoint's script, compiled
+// first so a broken one is rejected here rather than at delivery. One version
+// is active at a time: svix-caller runs it on every direct delivery to the
+// endpoint (after the merchant's payload template and the endpoint's filter).
+// Switching to an earlier version rolls back; none sends the body unchanged.
+//
+// A dry run executes a saved version, or a script not saved yet, on a sample
+// body (or one given), and returns the result with whatever the script
+// printed, without delivering anything.
+
+#[derive(Deserialize)]
+pub struct CreateTransformationRequest {
+    script: String,
+    /// Make it the endpoint's active version (default)
+    #[serde(default = "default_activate")]
+    activate: bool,
+}
+
+fn default_activate() -> bool {
+    true
+}
+
+#[derive(Serialize)]
+pub struct CreateTransformationResponse {
+    version: i32,
+    active: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ActivateTransformationRequest {
+    /// None: no transformation
+    version: Option<i32>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct TransformationVersion {
+    version: i32,
+    script: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct TransformationsResponse {
+    active_version: Option<i32>,
+    /// Newest first
+    versions: Vec<TransformationVersion>,
+}
+
+#[derive(Deserialize)]
+pub struct DryRunRequest {
+    /// Run this script instead of a saved version
+    script: Option<String>,
+    /// The saved version to run; the active one when neither is given
+    version: Option<i32>,
+    /// The webhook body to run it on; a sample payment.succeeded by default
+    payload: Option<serde_json::Value>,
+}
+
+#[derive(Serialize)]
+pub struct DryRunResponse {
+    /// None when the script failed
+    body: Option<serde_json::Value>,
+    error: Option<String>,
+    printed: Vec<String>,
+    duration_ms: u128,
+}
+
+fn database_error(action: &str, e: sqlx::Error) -> (StatusCode, String) {
+    tracing::error!("Failed to {}: {}", action, e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Failed to {}: {}", action, e),
+    )
+}
+
+fn endpoint_not_found(endpoint_id: Uuid) -> (StatusCode, String) {
+    (
+        StatusCode::NOT_FOUND,
+        format!("Endpoint not found: {}", endpoint_id),
+    )
+}
+
+/// A body shaped like the ones svix-caller delivers
+fn sample_payload() -> serde_json::Value {
+    json!({
+        "schema_version": 1,
+        "event_id": Uuid::new_v4().to_string(),
+        "event_type": "payment.succeeded",
+        "payment": {
+            "id": Uuid::new_v4().to_string(),
+            "amount": 2500,
+            "currency": "USD",
+            "status": "succeeded",
+            "mode": "live",
+            "amount_decimal": "25.00",
+        },
+    })
+}
+
+/// The endpoint's active version, if the endpoint is the merchant's
+async fn active_version(
+    state: &AppState,
+    merchant_id: Uuid,
+    endpoint_id: Uuid,
+) -> Result<Option<i32>, (StatusCode, String)> {
+    sqlx::query_scalar::<_, Option<i32>>(
+        r#"
+        SELECT transformation_version
+        FROM merchant_endpoints
+        WHERE id = $1 AND merchant_id = $2
+        "#,
+    )
+    .bind(endpoint_id)
+    .bind(merchant_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| database_error("fetch endpoint", e))?
+    .ok_or_else(|| endpoint_not_found(endpoint_id))
+}
+
+pub async fn list_transformations(
+    State(state): State<AppState>,
+    Path((merchant_id, endpoint_id)): Path<(String, Uuid)>,
+) -> Result<Json<TransformationsResponse>, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+    let active_version = active_version(&state, merchant_id, endpoint_id).await?;
+
+    let versions = sqlx::query_as::<_, TransformationVersion>(
+        r#"
+        SELECT version, script, created_at
+        FROM endpoint_transformations
+        WHERE endpoint_id = $1
+        ORDER BY version DESC
+        "#,
+    )
+    .bind(endpoint_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| database_error("list transformations", e))?;
+
+    Ok(Json(TransformationsResponse {
+        active_version,
+        versions,
+    }))
+}
+
+pub async fn create_transformation(
+    State(state): State<AppState>,
+    Path((merchant_id, endpoint_id)): Path<(String, Uuid)>,
+    headers: HeaderMap,
+    Json(req): Json<CreateTransformationRequest>,
+) -> Result<(StatusCode, Json<CreateTransformationResponse>), (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+    Script::compile(&req.script)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid script: {}", e)))?;
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+
+    // Locks the endpoint, so concurrent saves number their versions in turn
+    sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM merchant_endpoints WHERE id = $1 AND merchant_id = $2 FOR UPDATE",
+    )
+    .bind(endpoint_id)
+    .bind(merchant_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| database_error("save transformation", e))?
+    .ok_or_else(|| endpoint_not_found(endpoint_id))?;
+
+    let version = sqlx::query_scalar::<_, i32>(
+        r#"
+        INSERT INTO endpoint_transformations (endpoint_id, version, script)
+        SELECT $1, COALESCE(MAX(version), 0) + 1, $2
+        FROM endpoint_transformations
+        WHERE endpoint_id = $1
+        RETURNING version
+        "#,
+    )
+    .bind(endpoint_id)
+    .bind(&req.script)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| database_error("save transformation", e))?;
+
+    if req.activate {
+        sqlx::query(
+            r#"
+            UPDATE merchant_endpoints
+            SET transformation_version = $1, updated_at = NOW()
+            WHERE id = $2
+            "#,
+        )
+        .bind(version)
+        .bind(endpoint_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| database_error("activate transformation", e))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| database_error("save transformation", e))?;
+
+    info!(
+        "Transformation v{} saved for endpoint {}{}",
+        version,
+        endpoint_id,
+        if req.activate { " and activated" } else { "" }
+    );
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateTransformationResponse {
+            version,
+            active: req.activate,
+        }),
+    ))
+}
+
+/// Switch the endpoint to a saved version (or roll back to an earlier one),
+/// or to none. Takes effect on the next delivery.
+pub async fn activate_transformation(
+    State(state): State<AppState>,
+    Path((merchant_id, endpoint_id)): Path<(String, Uuid)>,
+    headers: HeaderMap,
+    Json(req): Json<ActivateTransformationRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+
+    let mut tx = audit::begin(&state.db, &audit::actor(&headers)).await?;
+    let result = sqlx::query(
+        r#"
+        UPDATE merchant_endpoints e
+        SET transformation_version = $1, updated_at = NOW()
+        WHERE e.id = $2 AND e.merchant_id = $3
+          AND ($1::INT IS NULL OR EXISTS (
+              SELECT 1 FROM endpoint_transformations t
+              WHERE t.endpoint_id = e.id AND t.version = $1
+          ))
+        "#,
+    )
+    .bind(req.version)
+    .bind(endpoint_id)
+    .bind(merchant_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| database_error("activate transformation", e))?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            match req.version {
+                Some(version) => format!(
+                    "Endpoint {} not found or has no version {}",
+                    endpoint_id, version
+                ),
+                None => format!("Endpoint not found: {}", endpoint_id),
+            },
+        ));
+    }
+
+    audit::commit(tx).await?;
+    match req.version {
+        Some(version) => info!("Endpoint {} transforms with v{}", endpoint_id, version),
+        None => info!("Endpoint {} no longer transforms", endpoint_id),
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Run a script without delivering anything. A script that fails is still
+/// a 200, with the error, as it would have failed the delivery.
+pub async fn dry_run_transformation(
+    State(state): State<AppState>,
+    Path((merchant_id, endpoint_id)): Path<(String, Uuid)>,
+    Json(req): Json<DryRunRequest>,
+) -> Result<Json<DryRunResponse>, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+    let active_version = active_version(&state, merchant_id, endpoint_id).await?;
+
+    let source = match (req.script, req.version.or(active_version)) {
+        (Some(script), _) => script,
+        (None, Some(version)) => sqlx::query_scalar::<_, String>(
+            "SELECT script FROM endpoint_transformations WHERE endpoint_id = $1 AND version = $2",
+        )
+        .bind(endpoint_id)
+        .bind(version)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| database_error("fetch transformation", e))?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("Endpoint {} has no version {}", endpoint_id, version),
+            )
+        })?,
+        (None, None) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Give a script or a version; the endpoint has no active one".to_string(),
+            ))
+        }
+    };
+    let script = Script::compile(&source)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid script: {}", e)))?;
+
+    let payload = req.payload.unwrap_or_else(sample_payload);
+    let started = std::time::Instant::now();
+    let response = match script.run(&payload) {
+        Ok(output) => DryRunResponse {
+            body: Some(output.body),
+            error: None,
+            printed: output.printed,
+            duration_ms: started.elapsed().as_millis(),
+        },
+        Err(failure) => DryRunResponse {
+            body: None,
+            error: Some(failure.error),
+            printed: failure.printed,
+            duration_ms: started.elapsed().as_millis(),
+        },
+    };
+    Ok(Json(response))
+}
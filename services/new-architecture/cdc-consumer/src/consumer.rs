@@ -0,0 +1,316 @@
+use kafka_producer::{PartitionKey, Producer};
+use redaction::Redactor;
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+use std::time::Duration;
+
+use crate::metrics::Metrics;
+use crate::pgoutput::{self, Action, Message, Relations};
+
+// ==============================================================================
+// CONSUMER: Replication slot -> Kafka, a transaction at a time
+// ==============================================================================
+//
+//   1. Read up to BATCH_SIZE changes from the slot without consuming them
+//      (pg_logical_slot_peek_binary_changes, pgoutput, PUBLICATION). Postgres
+//      only stops at a commit, so a batch holds whole transactions.
+//   2. Turn each change to a table we publish into its row as JSON (the
+//      record Sequin's extract_record transform produced) and send them all,
+//      keyed by merchant_id.
+//   3. Advance the slot (pg_replication_slot_advance) past the last
+//      transaction whose rows Kafka all acknowledged. The rest are read
+//      again after a backoff, and Postgres keeps their WAL until then.
+//
+// What goes where:
+//
+//   domain_events  inserts    -> EVENTS_TOPIC, the DomainEvent svix-caller
+//                                reads. The outbox-relay's published_at
+//                                updates (if both ever run) are not events.
+//   payments       all        -> PAYMENTS_TOPIC, when set
+//
+// With REDACTION_RULES set, a domain_events record's payload is redacted
+// (redaction crate, by the row's event_type) before it's sent; payments rows
+// carry no payload and go as they are.
+//
+// Every record carries x-cdc-table, x-cdc-action and x-cdc-lsn (its commit's
+// end LSN) headers. A crash between the acknowledgement and the advance
+// publishes a transaction again, so consumers dedupe, as with Sequin.
+//
+// The slot admits one reader at a time; a second consumer's peek fails with
+// "replication slot ... is active" and retries until the first one stops.
+
+pub struct Consumer {
+    pub db: PgPool,
+    pub producer: Producer,
+    pub slot: String,
+    pub publication: String,
+    pub events_topic: String,
+    pub payments_topic: Option<String>,
+    pub batch_size: i32,
+    pub poll_interval: Duration,
+    pub max_backoff: Duration,
+    /// Set with REDACTION_RULES
+    pub redactor: Option<Redactor>,
+    pub metrics: Metrics,
+}
+
+/// A row on its way to Kafka
+struct Record {
+    topic: String,
+    table: String,
+    action: Action,
+    record: Map<String, Value>,
+    /// Index of its transaction in the batch
+    transaction: usize,
+}
+
+/// A batch, decoded
+#[derive(Default)]
+struct Batch {
+    records: Vec<Record>,
+    /// end LSN of each complete transaction, in commit order
+    commits: Vec<u64>,
+}
+
+impl Consumer {
+    pub async fn run(self) {
+        let mut relations = Relations::default();
+        let mut failures = 0u32;
+        loop {
+            match self.round(&mut relations).await {
+                Ok(Some(_)) => failures = 0,
+                Ok(None) => {
+                    failures = 0;
+                    self.sample_lag().await;
+                    tokio::time::sleep(self.poll_interval).await;
+                }
+                Err(e) => {
+                    failures += 1;
+                    self.metrics.failed();
+                    let delay = self.backoff(failures);
+                    tracing::warn!("{}, retrying in {:?}", e, delay);
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+    }
+
+    /// Create the slot if this is the first start
+    pub async fn ensure_slot(&self) -> Result<(), sqlx::Error> {
+        let plugin: Option<String> =
+            sqlx::query_scalar("SELECT plugin FROM pg_replication_slots WHERE slot_name = $1")
+                .bind(&self.slot)
+                .fetch_optional(&self.db)
+                .await?;
+        match plugin.as_deref() {
+            Some("pgoutput") => {
+                tracing::info!("Resuming from replication slot {}", self.slot);
+            }
+            Some(other) => {
+                return Err(sqlx::Error::Configuration(
+                    format!(
+                        "replication slot {} uses {}, not pgoutput",
+                        self.slot, other
+                    )
+                    .into(),
+                ))
+            }
+            None => {
+                sqlx::query("SELECT pg_create_logical_replication_slot($1, 'pgoutput')")
+                    .bind(&self.slot)
+                    .execute(&self.db)
+                    .await?;
+                tracing::info!(
+                    "Created replication slot {}; changes from now on are published",
+                    self.slot
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// One batch. Ok(None): the slot had nothing new
+    async fn round(&self, relations: &mut Relations) -> Result<Option<usize>, String> {
+        let rows: Vec<(String, Vec<u8>)> = sqlx::query_as(
+            r#"
+            SELECT lsn::TEXT, data
+            FROM pg_logical_slot_peek_binary_changes(
+                $1, NULL, $2, 'proto_version', '1', 'publication_names', $3)
+            "#,
+        )
+        .bind(&self.slot)
+        .bind(self.batch_size)
+        .bind(&self.publication)
+        .fetch_all(&self.db)
+        .await
+        .map_err(|e| format!("Failed to read slot {}: {}", self.slot, e))?;
+        if rows.is_empty() {
+            return Ok(None);
+        }
+
+        let batch = self.decode(relations, &rows)?;
+        let published = self.publish(&batch).await;
+
+        // Every transaction wholly before the first unacknowledged record
+        let done = match batch.records.get(published) {
+            Some(failed) => failed.transaction,
+            None => batch.commits.len(),
+        };
+        if done > 0 {
+            let lsn = pgoutput::format_lsn(batch.commits[done - 1]);
+            sqlx::query("SELECT pg_replication_slot_advance($1, $2::TEXT::pg_lsn)")
+                .bind(&self.slot)
+                .bind(&lsn)
+                .execute(&self.db)
+                .await
+                .map_err(|e| format!("Failed to advance slot to {}: {}", lsn, e))?;
+        }
+        for record in &batch.records[..published] {
+            self.metrics.published(&record.table, record.action);
+        }
+
+        if published < batch.records.len() {
+            return Err(format!(
+                "Published {} of {} records, {} of {} transactions",
+                published,
+                batch.records.len(),
+                done,
+                batch.commits.len()
+            ));
+        }
+        Ok(Some(published))
+    }
+
+    fn decode(
+        &self,
+        relations: &mut Relations,
+        rows: &[(String, Vec<u8>)],
+    ) -> Result<Batch, String> {
+        let mut batch = Batch::default();
+        for (lsn, data) in rows {
+            let message =
+                pgoutput::decode(data).map_err(|e| format!("Failed to decode {}: {}", lsn, e))?;
+            match message {
+                Message::Relation(relation) => relations.insert(relation),
+                Message::Change(change) => {
+                    let relation = relations.get(change.relation_id).ok_or_else(|| {
+                        format!(
+                            "Change at {} to unknown relation {}",
+                            lsn, change.relation_id
+                        )
+                    })?;
+                    let Some(topic) =
+                        self.topic(&relation.namespace, &relation.name, change.action)
+                    else {
+                        continue;
+                    };
+                    let mut record = relation.record(&change.tuple);
+                    if relation.name == "domain_events" {
+                        self.redact(&mut record);
+                    }
+                    batch.records.push(Record {
+                        topic: topic.to_string(),
+                        table: relation.name.clone(),
+                        action: change.action,
+                        record,
+                        transaction: batch.commits.len(),
+                    });
+                }
+                Message::Commit { end_lsn } => batch.commits.push(end_lsn),
+                Message::Begin | Message::Other => {}
+            }
+        }
+        Ok(batch)
+    }
+
+    /// A domain_events record's payload, by its event_type
+    fn redact(&self, record: &mut Map<String, Value>) {
+        let Some(redactor) = &self.redactor else {
+            return;
+        };
+        let event_type = record
+            .get("event_type")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        if let Some(payload) = record.get_mut("payload") {
+            self.metrics.redacted(redactor.apply(&event_type, payload));
+        }
+    }
+
+    /// Where a change goes, if anywhere
+    fn topic(&self, namespace: &str, table: &str, action: Action) -> Option<&str> {
+        match (namespace, table, action) {
+            ("public", "domain_events", Action::Insert) => Some(&self.events_topic),
+            ("public", "payments", _) => self.payments_topic.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Send every record, then count the acknowledged ones before the first
+    /// failure
+    async fn publish(&self, batch: &Batch) -> usize {
+        let commit_lsns: Vec<String> = batch
+            .commits
+            .iter()
+            .map(|lsn| pgoutput::format_lsn(*lsn))
+            .collect();
+        let sends = batch.records.iter().map(|record| {
+            // A delete's key columns may not include merchant_id
+            let key = match record.record.get("merchant_id").and_then(Value::as_str) {
+                Some(merchant_id) => PartitionKey::Merchant(merchant_id),
+                None => PartitionKey::Object(
+                    record
+                        .record
+                        .get("id")
+                        .and_then(Value::as_str)
+                        .unwrap_or(""),
+                ),
+            };
+            let lsn = commit_lsns
+                .get(record.transaction)
+                .map(String::as_str)
+                .unwrap_or("");
+            let headers = [
+                ("x-cdc-table", record.table.as_str()),
+                ("x-cdc-action", record.action.as_str()),
+                ("x-cdc-lsn", lsn),
+            ];
+            async move {
+                self.producer
+                    .send_json(&record.topic, key, &record.record, &headers)
+                    .await
+            }
+        });
+        let results = futures::future::join_all(sends).await;
+        results
+            .iter()
+            .position(|result| result.is_err())
+            .unwrap_or(results.len())
+    }
+
+    async fn sample_lag(&self) {
+        let lag: Result<Option<f64>, _> = sqlx::query_scalar(
+            r#"
+            SELECT pg_wal_lsn_diff(pg_current_wal_lsn(), confirmed_flush_lsn)::FLOAT8
+            FROM pg_replication_slots
+            WHERE slot_name = $1
+            "#,
+        )
+        .bind(&self.slot)
+        .fetch_optional(&self.db)
+        .await
+        .map(Option::flatten);
+        match lag {
+            Ok(bytes) => self.metrics.lag(bytes.unwrap_or(0.0)),
+            Err(e) => tracing::warn!("Failed to read slot lag: {}", e),
+        }
+    }
+
+    /// 100ms doubling per consecutive failure, up to MAX_BACKOFF_MS
+    fn backoff(&self, failures: u32) -> Duration {
+        Duration::from_millis(100)
+            .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+}
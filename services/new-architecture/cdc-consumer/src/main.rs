@@ -0,0 +1,138 @@
+use axum::{middleware, Router};
+use health_checks::{checks, CheckResult, HealthRegistry};
+use kafka_producer::{Producer, ProducerConfig, ProducerMetrics};
+use redaction::Redactor;
+use service_metrics::ServiceMetrics;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::info;
+
+mod consumer;
+mod metrics;
+mod pgoutput;
+mod settings;
+
+use consumer::Consumer;
+use metrics::Metrics;
+use settings::Settings;
+
+// ==============================================================================
+// CDC CONSUMER: Postgres logical replication -> Kafka, without Sequin
+// ==============================================================================
+//
+// The third way domain_events reach Kafka, next to Sequin (SEQUIN_SETUP.md)
+// and outbox-relay's polling: this reads the WAL itself, through a pgoutput
+// replication slot over PUBLICATION (cdc_pub in init.sql: domain_events and
+// payments), and publishes each row as the JSON record Sequin did
+// (consumer.rs, pgoutput.rs). Nothing to configure in a browser, and the
+// tables need no published_at column or trigger.
+//
+// Run one of the three. Health and metrics are on PORT (default 3004).
+
+#[tokio::main]
+async fn main() {
+    logging::init(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    let settings: Settings = config::load_or_exit("cdc-consumer");
+    // Validated to be set
+    let database_url = settings.database_url.clone().unwrap_or_default();
+
+    let db = PgPoolOptions::new()
+        .max_connections(3)
+        .connect(&database_url)
+        .await
+        .expect("Failed to connect to database");
+
+    let metrics = Metrics::new(ServiceMetrics::new(
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+    ));
+    let producer = Producer::new(
+        &ProducerConfig::new(&settings.kafka_brokers, "cdc-consumer"),
+        &ProducerMetrics::new(metrics.service().registry()),
+    )
+    .expect("Invalid Kafka configuration");
+
+    // Validated to load; if the file broke since, stop rather than run
+    // without redaction
+    let redactor = settings
+        .redaction_rules
+        .as_deref()
+        .map(Redactor::load)
+        .transpose()
+        .expect("Invalid redaction rules");
+    if let Some(path) = &settings.redaction_rules {
+        info!("Redacting domain_events payloads with {}", path.display());
+    }
+
+    let consumer = Consumer {
+        db: db.clone(),
+        producer,
+        slot: settings.slot_name.clone(),
+        publication: settings.publication.clone(),
+        events_topic: settings.events_topic.clone(),
+        payments_topic: settings.payments_topic.clone(),
+        batch_size: settings.batch_size,
+        poll_interval: Duration::from_millis(settings.poll_interval_ms),
+        max_backoff: Duration::from_millis(settings.max_backoff_ms),
+        redactor,
+        metrics: metrics.clone(),
+    };
+    consumer
+        .ensure_slot()
+        .await
+        .expect("Failed to set up the replication slot (is wal_level=logical?)");
+    info!(
+        "Publishing {} via slot {}: domain_events to {}, payments to {}",
+        settings.publication,
+        settings.slot_name,
+        settings.events_topic,
+        settings.payments_topic.as_deref().unwrap_or("(off)")
+    );
+    tokio::spawn(consumer.run());
+
+    let brokers: Vec<String> = settings
+        .kafka_brokers
+        .split(',')
+        .map(|broker| broker.trim().to_string())
+        .collect();
+    let slot_db = db.clone();
+    let slot = settings.slot_name.clone();
+    let health = HealthRegistry::new()
+        .readiness("database", move || checks::postgres(db.clone()))
+        .readiness("kafka", move || checks::tcp(brokers.clone()))
+        .readiness("slot", move || slot_check(slot_db.clone(), slot.clone()));
+
+    let app = Router::new()
+        .merge(health_checks::router(health))
+        .merge(service_metrics::router(metrics.service().clone()))
+        .route_layer(middleware::from_fn_with_state(
+            metrics.service().clone(),
+            service_metrics::track_http,
+        ));
+
+    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", settings.port))
+        .await
+        .unwrap();
+
+    info!("CDC CONSUMER health and metrics on port {}", settings.port);
+
+    axum::serve(listener, app).await.unwrap();
+}
+
+/// The slot still exists; dropping it (or a failover without it) loses the
+/// position, and restarting creates a fresh one that skips what came between
+async fn slot_check(db: PgPool, slot: String) -> CheckResult {
+    let exists: Result<bool, _> = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM pg_replication_slots WHERE slot_name = $1)",
+    )
+    .bind(&slot)
+    .fetch_one(&db)
+    .await;
+    match exists {
+        Ok(true) => CheckResult::pass(slot),
+        Ok(false) => CheckResult::fail(format!("replication slot {} is gone", slot)),
+        Err(e) => CheckResult::fail(e.to_string()),
+    }
+}
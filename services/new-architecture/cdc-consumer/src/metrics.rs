@@ -0,0 +1,96 @@
+use prometheus::{Gauge, IntCounter, IntCounterVec, Opts};
+use service_metrics::ServiceMetrics;
+
+use crate::pgoutput::Action;
+
+// ==============================================================================
+// METRICS: What the slot published and how far behind it is
+// ==============================================================================
+//
+// Next to the process and HTTP metrics every service has (service-metrics
+// crate) and the producer's kafka_* families (kafka-producer crate):
+//
+//   cdc_changes_published_total{table, action}   rows sent and acknowledged
+//   cdc_failures_total                           rounds that stopped short
+//   cdc_slot_lag_bytes                           WAL the slot still holds,
+//                                                when last drained
+//   cdc_values_redacted_total                    payload values and matches
+//                                                replaced by REDACTION_RULES
+//
+// cdc_slot_lag_bytes is the one to alert on: Postgres keeps all WAL past the
+// slot's position, so a stalled consumer eventually fills the disk.
+
+#[derive(Clone)]
+pub struct Metrics {
+    service: ServiceMetrics,
+    published: IntCounterVec,
+    failures: IntCounter,
+    lag: Gauge,
+    redacted: IntCounter,
+}
+
+impl Metrics {
+    pub fn new(service: ServiceMetrics) -> Self {
+        let registry = service.registry();
+
+        let published = IntCounterVec::new(
+            Opts::new(
+                "cdc_changes_published_total",
+                "Row changes published to Kafka, by table and action",
+            ),
+            &["table", "action"],
+        )
+        .unwrap();
+        let failures = IntCounter::with_opts(Opts::new(
+            "cdc_failures_total",
+            "Rounds that failed to read, publish or advance the slot",
+        ))
+        .unwrap();
+        let lag = Gauge::with_opts(Opts::new(
+            "cdc_slot_lag_bytes",
+            "Bytes of WAL between the server's position and the slot's",
+        ))
+        .unwrap();
+
+        let redacted = IntCounter::with_opts(Opts::new(
+            "cdc_values_redacted_total",
+            "Payload values and matches replaced before publishing (REDACTION_RULES)",
+        ))
+        .unwrap();
+
+        registry.register(Box::new(published.clone())).unwrap();
+        registry.register(Box::new(failures.clone())).unwrap();
+        registry.register(Box::new(lag.clone())).unwrap();
+        registry.register(Box::new(redacted.clone())).unwrap();
+
+        Metrics {
+            service,
+            published,
+            failures,
+            lag,
+            redacted,
+        }
+    }
+
+    pub fn service(&self) -> &ServiceMetrics {
+        &self.service
+    }
+
+    pub fn published(&self, table: &str, action: Action) {
+        self.published
+            .with_label_values(&[table, action.as_str()])
+            .inc();
+    }
+
+    pub fn failed(&self) {
+        self.failures.inc();
+    }
+
+    pub fn lag(&self, bytes: f64) {
+        self.lag.set(bytes);
+    }
+
+    pub fn redacted(&self, values: usize) {
+        self.redacted.inc_by(values as u64);
+    }
+}
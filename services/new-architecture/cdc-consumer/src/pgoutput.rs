@@ -0,0 +1,485 @@
+use chrono::{DateTime, NaiveDateTime};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+// ==============================================================================
+// PGOUTPUT: Decoding the logical replication messages Postgres sends
+// ==============================================================================
+//
+// The binary format of the pgoutput plugin, protocol version 1 (the one every
+// supported Postgres speaks; see "Logical Replication Message Formats" in the
+// Postgres docs). Only what a CDC consumer needs is decoded:
+//
+//   B  Begin      a transaction starts
+//   R  Relation   a table's columns, sent before its first change in a session
+//   I  Insert     the new row
+//   U  Update     the new row (and the old key, unless REPLICA IDENTITY FULL)
+//   D  Delete     the old key (the whole old row with REPLICA IDENTITY FULL)
+//   C  Commit     the transaction ends; end_lsn is where the slot may advance to
+//
+// Everything else (Origin, Type, Truncate, logical messages) decodes to
+// Message::Other and is skipped.
+//
+// Column values arrive in Postgres' text format and are turned into JSON by
+// type (json_value), so a row reads the way Sequin published it: numbers as
+// numbers, jsonb as JSON, timestamps as RFC 3339.
+
+#[derive(Debug)]
+pub enum Message {
+    Begin,
+    Relation(Relation),
+    Change(Change),
+    Commit { end_lsn: u64 },
+    Other,
+}
+
+#[derive(Clone, Debug)]
+pub struct Relation {
+    pub id: u32,
+    pub namespace: String,
+    pub name: String,
+    pub columns: Vec<Column>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Column {
+    pub name: String,
+    pub type_oid: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Insert => "insert",
+            Action::Update => "update",
+            Action::Delete => "delete",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Change {
+    pub relation_id: u32,
+    pub action: Action,
+    /// The new row for inserts and updates, the old one (or its key) for deletes
+    pub tuple: Vec<Datum>,
+}
+
+#[derive(Debug)]
+pub enum Datum {
+    Null,
+    /// A TOASTed value the update didn't touch; Postgres doesn't resend it
+    Unchanged,
+    Text(String),
+}
+
+/// Relations seen so far, by id; Postgres only describes each one once per
+/// decoding session
+#[derive(Default)]
+pub struct Relations(HashMap<u32, Relation>);
+
+impl Relations {
+    pub fn insert(&mut self, relation: Relation) {
+        self.0.insert(relation.id, relation);
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Relation> {
+        self.0.get(&id)
+    }
+}
+
+impl Relation {
+    /// The row as a JSON object; unchanged TOAST columns are left out
+    pub fn record(&self, tuple: &[Datum]) -> Map<String, Value> {
+        let mut record = Map::new();
+        for (column, datum) in self.columns.iter().zip(tuple) {
+            let value = match datum {
+                Datum::Null => Value::Null,
+                Datum::Unchanged => continue,
+                Datum::Text(text) => json_value(column.type_oid, text),
+            };
+            record.insert(column.name.clone(), value);
+        }
+        record
+    }
+}
+
+pub fn decode(data: &[u8]) -> Result<Message, String> {
+    let mut reader = Reader { data, pos: 0 };
+    let tag = reader.u8()?;
+    let message = match tag {
+        b'B' => {
+            let _final_lsn = reader.u64()?;
+            let _commit_time = reader.u64()?;
+            let _xid = reader.u32()?;
+            Message::Begin
+        }
+        b'C' => {
+            let _flags = reader.u8()?;
+            let _commit_lsn = reader.u64()?;
+            let end_lsn = reader.u64()?;
+            let _commit_time = reader.u64()?;
+            Message::Commit { end_lsn }
+        }
+        b'R' => {
+            let id = reader.u32()?;
+            let namespace = reader.cstring()?;
+            let name = reader.cstring()?;
+            let _replica_identity = reader.u8()?;
+            let count = reader.u16()?;
+            let mut columns = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let _flags = reader.u8()?;
+                let name = reader.cstring()?;
+                let type_oid = reader.u32()?;
+                let _type_modifier = reader.u32()?;
+                columns.push(Column { name, type_oid });
+            }
+            Message::Relation(Relation {
+                id,
+                namespace,
+                name,
+                columns,
+            })
+        }
+        b'I' => {
+            let relation_id = reader.u32()?;
+            reader.expect(b'N')?;
+            Message::Change(Change {
+                relation_id,
+                action: Action::Insert,
+                tuple: reader.tuple()?,
+            })
+        }
+        b'U' => {
+            let relation_id = reader.u32()?;
+            // The old key ('K') or row ('O') comes first when the update
+            // changed it or the table is REPLICA IDENTITY FULL
+            if let b'K' | b'O' = reader.peek()? {
+                reader.u8()?;
+                reader.tuple()?;
+            }
+            reader.expect(b'N')?;
+            Message::Change(Change {
+                relation_id,
+                action: Action::Update,
+                tuple: reader.tuple()?,
+            })
+        }
+        b'D' => {
+            let relation_id = reader.u32()?;
+            match reader.u8()? {
+                b'K' | b'O' => {}
+                other => return Err(format!("unexpected delete tuple kind {:?}", other as char)),
+            }
+            Message::Change(Change {
+                relation_id,
+                action: Action::Delete,
+                tuple: reader.tuple()?,
+            })
+        }
+        _ => Message::Other,
+    };
+    Ok(message)
+}
+
+/// A column's text representation as JSON, by its type's oid
+fn json_value(type_oid: u32, text: &str) -> Value {
+    const BOOL: u32 = 16;
+    const INT8: u32 = 20;
+    const INT2: u32 = 21;
+    const INT4: u32 = 23;
+    const JSON: u32 = 114;
+    const FLOAT4: u32 = 700;
+    const FLOAT8: u32 = 701;
+    const TIMESTAMP: u32 = 1114;
+    const TIMESTAMPTZ: u32 = 1184;
+    const NUMERIC: u32 = 1700;
+    const JSONB: u32 = 3802;
+
+    let parsed = match type_oid {
+        BOOL => Some(Value::Bool(text == "t")),
+        INT2 | INT4 | INT8 => text.parse::<i64>().ok().map(Value::from),
+        FLOAT4 | FLOAT8 | NUMERIC => text
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number),
+        JSON | JSONB => serde_json::from_str(text).ok(),
+        TIMESTAMPTZ => DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z")
+            .ok()
+            .map(|at| Value::String(at.to_rfc3339())),
+        TIMESTAMP => NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")
+            .ok()
+            .map(|at| Value::String(at.format("%Y-%m-%dT%H:%M:%S%.f").to_string())),
+        _ => None,
+    };
+    // Anything else (uuid, text, varchar, ...) stays as Postgres printed it
+    parsed.unwrap_or_else(|| Value::String(text.to_string()))
+}
+
+/// An LSN as Postgres writes it (pg_lsn), e.g. 0/16B3748
+pub fn format_lsn(lsn: u64) -> String {
+    format!("{:X}/{:X}", lsn >> 32, lsn & 0xffff_ffff)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], String> {
+        let end = self.pos + len;
+        let bytes = self
+            .data
+            .get(self.pos..end)
+            .ok_or_else(|| format!("message truncated at byte {}", self.pos))?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn peek(&self) -> Result<u8, String> {
+        self.data
+            .get(self.pos)
+            .copied()
+            .ok_or_else(|| format!("message truncated at byte {}", self.pos))
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn expect(&mut self, tag: u8) -> Result<(), String> {
+        match self.u8()? {
+            found if found == tag => Ok(()),
+            found => Err(format!(
+                "expected {:?} at byte {}, found {:?}",
+                tag as char,
+                self.pos - 1,
+                found as char
+            )),
+        }
+    }
+
+    fn cstring(&mut self) -> Result<String, String> {
+        let rest = &self.data[self.pos..];
+        let len = rest
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| format!("unterminated string at byte {}", self.pos))?;
+        let text = String::from_utf8_lossy(&rest[..len]).into_owned();
+        self.pos += len + 1;
+        Ok(text)
+    }
+
+    fn tuple(&mut self) -> Result<Vec<Datum>, String> {
+        let count = self.u16()?;
+        let mut tuple = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let datum = match self.u8()? {
+                b'n' => Datum::Null,
+                b'u' => Datum::Unchanged,
+                b't' => {
+                    let len = self.u32()? as usize;
+                    Datum::Text(String::from_utf8_lossy(self.take(len)?).into_owned())
+                }
+                other => return Err(format!("unknown column kind {:?}", other as char)),
+            };
+            tuple.push(datum);
+        }
+        Ok(tuple)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const INT8: u32 = 20;
+    const TEXT: u32 = 25;
+    const TIMESTAMPTZ: u32 = 1184;
+    const JSONB: u32 = 3802;
+
+    fn cstring(bytes: &mut Vec<u8>, text: &str) {
+        bytes.extend_from_slice(text.as_bytes());
+        bytes.push(0);
+    }
+
+    fn begin() -> Vec<u8> {
+        let mut bytes = vec![b'B'];
+        bytes.extend_from_slice(&0x16B3748u64.to_be_bytes()); // final LSN
+        bytes.extend_from_slice(&0i64.to_be_bytes()); // commit timestamp
+        bytes.extend_from_slice(&742u32.to_be_bytes()); // xid
+        bytes
+    }
+
+    fn relation() -> Vec<u8> {
+        let mut bytes = vec![b'R'];
+        bytes.extend_from_slice(&16385u32.to_be_bytes());
+        cstring(&mut bytes, "public");
+        cstring(&mut bytes, "domain_events");
+        bytes.push(b'd');
+        let columns = [
+            ("id", INT8),
+            ("event_type", TEXT),
+            ("payload", JSONB),
+            ("replay_of", INT8),
+            ("created_at", TIMESTAMPTZ),
+        ];
+        bytes.extend_from_slice(&(columns.len() as u16).to_be_bytes());
+        for (name, type_oid) in columns {
+            bytes.push(1);
+            cstring(&mut bytes, name);
+            bytes.extend_from_slice(&type_oid.to_be_bytes());
+            bytes.extend_from_slice(&(-1i32).to_be_bytes());
+        }
+        bytes
+    }
+
+    fn text(bytes: &mut Vec<u8>, value: &str) {
+        bytes.push(b't');
+        bytes.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn insert() -> Vec<u8> {
+        let mut bytes = vec![b'I'];
+        bytes.extend_from_slice(&16385u32.to_be_bytes());
+        bytes.push(b'N');
+        bytes.extend_from_slice(&5u16.to_be_bytes());
+        text(&mut bytes, "42");
+        text(&mut bytes, "payment.succeeded");
+        text(&mut bytes, r#"{"amount": 2500}"#);
+        bytes.push(b'n');
+        text(&mut bytes, "2026-10-17 08:30:00.5+00");
+        bytes
+    }
+
+    fn commit() -> Vec<u8> {
+        let mut bytes = vec![b'C', 0];
+        bytes.extend_from_slice(&0x16B3748u64.to_be_bytes()); // commit LSN
+        bytes.extend_from_slice(&0x16B3778u64.to_be_bytes()); // end LSN
+        bytes.extend_from_slice(&0i64.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn decodes_a_transaction() {
+        assert!(matches!(decode(&begin()), Ok(Message::Begin)));
+
+        let Ok(Message::Relation(relation)) = decode(&relation()) else {
+            panic!("not a relation");
+        };
+        assert_eq!(relation.id, 16385);
+        assert_eq!(
+            (relation.namespace.as_str(), relation.name.as_str()),
+            ("public", "domain_events")
+        );
+        let columns: Vec<(&str, u32)> = relation
+            .columns
+            .iter()
+            .map(|column| (column.name.as_str(), column.type_oid))
+            .collect();
+        assert_eq!(columns[0], ("id", INT8));
+        assert_eq!(columns[4], ("created_at", TIMESTAMPTZ));
+
+        let Ok(Message::Change(change)) = decode(&insert()) else {
+            panic!("not a change");
+        };
+        assert_eq!((change.relation_id, change.action), (16385, Action::Insert));
+        assert_eq!(
+            Value::Object(relation.record(&change.tuple)),
+            json!({
+                "id": 42,
+                "event_type": "payment.succeeded",
+                "payload": {"amount": 2500},
+                "replay_of": null,
+                "created_at": "2026-10-17T08:30:00.500+00:00",
+            })
+        );
+
+        let Ok(Message::Commit { end_lsn }) = decode(&commit()) else {
+            panic!("not a commit");
+        };
+        assert_eq!(format_lsn(end_lsn), "0/16B3778");
+    }
+
+    #[test]
+    fn update_skips_the_old_key_and_unchanged_toast_is_left_out() {
+        let mut bytes = vec![b'U'];
+        bytes.extend_from_slice(&16385u32.to_be_bytes());
+        bytes.push(b'K');
+        bytes.extend_from_slice(&1u16.to_be_bytes());
+        text(&mut bytes, "41");
+        bytes.push(b'N');
+        bytes.extend_from_slice(&3u16.to_be_bytes());
+        text(&mut bytes, "42");
+        text(&mut bytes, "payment.failed");
+        bytes.push(b'u');
+
+        let Ok(Message::Relation(relation)) = decode(&relation()) else {
+            panic!("not a relation");
+        };
+        let Ok(Message::Change(change)) = decode(&bytes) else {
+            panic!("not a change");
+        };
+        assert_eq!(change.action, Action::Update);
+        assert_eq!(
+            Value::Object(relation.record(&change.tuple)),
+            json!({"id": 42, "event_type": "payment.failed"})
+        );
+    }
+
+    #[test]
+    fn truncated_messages_are_errors() {
+        for message in [begin(), relation(), insert(), commit()] {
+            for len in 0..message.len() {
+                assert!(
+                    decode(&message[..len]).is_err(),
+                    "{:?} cut at {}",
+                    message[0] as char,
+                    len
+                );
+            }
+        }
+        assert!(decode(&[]).is_err());
+    }
+
+    #[test]
+    fn malformed_tuples_are_errors() {
+        let mut unknown_kind = insert();
+        unknown_kind[8] = b'x'; // first column's kind
+        assert!(decode(&unknown_kind).is_err());
+
+        let mut no_new_tuple = insert();
+        no_new_tuple[5] = b'K';
+        assert!(decode(&no_new_tuple).is_err());
+    }
+
+    #[test]
+    fn other_messages_are_skipped() {
+        assert!(matches!(decode(b"O\0\0\0\0"), Ok(Message::Other)));
+        assert!(matches!(decode(b"T"), Ok(Message::Other)));
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// ==============================================================================
+// SETTINGS: Which slot to read and where its changes go
+// ==============================================================================
+//
+// Loaded through the shared config crate: defaults below, then
+// config/cdc-consumer.toml (or CONFIG_FILE), then the environment variable of
+// the same name in upper case.
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// /livez, /readyz, /startupz and /metrics
+    pub port: u16,
+    /// Required; the user needs the REPLICATION attribute (dodo is a superuser)
+    pub database_url: Option<String>,
+    /// Created on first start. Not Sequin's sequin_slot: a slot has one reader
+    pub slot_name: String,
+    /// Which tables the slot decodes (init.sql)
+    pub publication: String,
+    pub kafka_brokers: String,
+    /// domain_events inserts; what Restate's Kafka subscription reads
+    pub events_topic: String,
+    /// payments inserts, updates and deletes; unset: not published
+    pub payments_topic: Option<String>,
+    /// Changes read per round trip (whole transactions, so sometimes more)
+    pub batch_size: i32,
+    /// How often the slot is checked once it's drained
+    pub poll_interval_ms: u64,
+    /// Longest wait between attempts while Kafka or Postgres is failing
+    pub max_backoff_ms: u64,
+    /// TOML rules file; domain_events payloads are redacted before they're
+    /// published (redaction crate). Unset: published as written
+    pub redaction_rules: Option<PathBuf>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            port: 3004,
+            database_url: None,
+            slot_name: "cdc_slot".to_string(),
+            publication: "cdc_pub".to_string(),
+            kafka_brokers: "kafka:9092".to_string(),
+            events_topic: "webhook-events".to_string(),
+            payments_topic: None,
+            batch_size: 500,
+            poll_interval_ms: 500,
+            max_backoff_ms: 30_000,
+            redaction_rules: None,
+        }
+    }
+}
+
+impl config::Validate for Settings {
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.database_url.is_none() {
+            problems.push("DATABASE_URL: required".to_string());
+        }
+        if self.kafka_brokers.is_empty() {
+            problems.push("KAFKA_BROKERS: required".to_string());
+        }
+        // Slot names are lower case letters, digits and underscores
+        if self.slot_name.is_empty()
+            || !self
+                .slot_name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            problems.push(format!(
+                "SLOT_NAME: {:?} may only contain a-z, 0-9 and _",
+                self.slot_name
+            ));
+        }
+        if !(1..=100_000).contains(&self.batch_size) {
+            problems.push(format!(
+                "BATCH_SIZE: {} is not between 1 and 100000",
+                self.batch_size
+            ));
+        }
+        if self.poll_interval_ms == 0 {
+            problems.push("POLL_INTERVAL_MS: must be at least 1".to_string());
+        }
+        if let Some(path) = &self.redaction_rules {
+            if let Err(e) = redaction::Redactor::load(path) {
+                problems.push(format!("REDACTION_RULES: {}", e));
+            }
+        }
+        problems
+    }
+}
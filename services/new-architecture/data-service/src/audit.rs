@@ -0,0 +1,103 @@
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::auth::CallerService;
+use crate::AppState;
+
+// ==============================================================================
+// AUDIT LOG: Who read which payment's payload, and when
+// ==============================================================================
+//
+// Compliance questions ("which services saw payment X last month?") need a
+// record of every payload that left data-service. PAYLOAD_AUDIT_SINK picks
+// where it goes:
+//   table (default) - rows in payload_access_log
+//   log             - one structured `payload_audit` event per payload
+//   off             - nothing, for load tests
+// Table writes happen off the request path; if one fails, the access is logged
+// instead so it isn't lost silently. Only payloads actually served are
+// recorded - not-found ids and rejected requests aren't accesses.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditSink {
+    Table,
+    Log,
+    Off,
+}
+
+impl AuditSink {
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var("PAYLOAD_AUDIT_SINK").as_deref() {
+            Ok("table") | Err(_) => Ok(AuditSink::Table),
+            Ok("log") => Ok(AuditSink::Log),
+            Ok("off") => Ok(AuditSink::Off),
+            Ok(other) => Err(format!(
+                "Unknown PAYLOAD_AUDIT_SINK '{}', expected 'table', 'log' or 'off'",
+                other
+            )),
+        }
+    }
+}
+
+/// One request's worth of served payloads
+pub struct PayloadAccess {
+    pub caller: CallerService,
+    /// "http" or "grpc"
+    pub transport: &'static str,
+    pub event_id: Option<i64>,
+    /// Served from the last-known copy during a database outage
+    pub stale: bool,
+    pub payment_ids: Vec<Uuid>,
+}
+
+pub fn record(state: &AppState, access: PayloadAccess) {
+    if access.payment_ids.is_empty() {
+        return;
+    }
+
+    match state.audit_sink {
+        AuditSink::Off => {}
+        AuditSink::Log => log_access(&access),
+        AuditSink::Table => {
+            let db = state.db.clone();
+            tokio::spawn(async move {
+                if let Err(e) = insert_access(&db, &access).await {
+                    warn!("Failed to write payload audit rows, logging instead: {}", e);
+                    log_access(&access);
+                }
+            });
+        }
+    }
+}
+
+fn log_access(access: &PayloadAccess) {
+    for payment_id in &access.payment_ids {
+        info!(
+            target: "payload_audit",
+            caller_service = %access.caller.0,
+            payment_id = %payment_id,
+            event_id = ?access.event_id,
+            transport = access.transport,
+            stale = access.stale,
+            "payload accessed"
+        );
+    }
+}
+
+async fn insert_access(db: &sqlx::PgPool, access: &PayloadAccess) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO payload_access_log (caller_service, payment_id, event_id, transport, stale)
+        SELECT $1, payment_id, $3, $4, $5
+        FROM UNNEST($2::UUID[]) AS payment_id
+        "#,
+    )
+    .bind(&access.caller.0)
+    .bind(&access.payment_ids)
+    .bind(access.event_id)
+    .bind(access.transport)
+    .bind(access.stale)
+    .execute(db)
+    .await?;
+    Ok(())
+}
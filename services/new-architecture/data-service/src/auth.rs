@@ -0,0 +1,93 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use tracing::warn;
+
+use crate::AppState;
+
+// ==============================================================================
+// SERVICE AUTH: Only known services may read payment data
+// ==============================================================================
+//
+// Payload endpoints expose every payment to whoever can reach them, so callers
+// present a per-service bearer token. SERVICE_AUTH_TOKENS lists them as
+// "name:token" pairs, e.g. "svix-caller:s3cret,reconciler:0ther"; the name of
+// the authenticated service is attached to the request for logging. Without
+// SERVICE_AUTH_TOKENS auth is disabled, which is only meant for local runs.
+// The health probes and /metrics stay open for orchestration and scraping.
+
+/// The service a request was authenticated as
+#[derive(Debug, Clone)]
+pub struct CallerService(pub String);
+
+#[derive(Default)]
+pub struct ServiceTokens {
+    tokens: Vec<(String, String)>,
+}
+
+impl ServiceTokens {
+    pub fn from_env() -> Result<Self, String> {
+        let Ok(spec) = std::env::var("SERVICE_AUTH_TOKENS") else {
+            return Ok(Self::default());
+        };
+
+        let mut tokens = Vec::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (name, token) = entry
+                .split_once(':')
+                .filter(|(name, token)| !name.is_empty() && !token.is_empty())
+                .ok_or_else(|| "SERVICE_AUTH_TOKENS entries must be name:token".to_string())?;
+            tokens.push((name.to_string(), token.to_string()));
+        }
+        Ok(ServiceTokens { tokens })
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.tokens.is_empty()
+    }
+
+    /// The caller for an Authorization header value. Unauthenticated callers
+    /// are "anonymous" while auth is disabled and rejected otherwise.
+    pub fn authenticate(&self, authorization: Option<&str>) -> Option<CallerService> {
+        if !self.enabled() {
+            return Some(CallerService("anonymous".to_string()));
+        }
+
+        let presented = authorization?.strip_prefix("Bearer ")?.trim();
+        self.tokens
+            .iter()
+            .find(|(_, token)| constant_time_eq(token.as_bytes(), presented.as_bytes()))
+            .map(|(name, _)| CallerService(name.clone()))
+    }
+}
+
+/// Comparison time doesn't depend on where the inputs differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub async fn require_service_token(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let authorization = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+
+    let Some(caller) = state.service_tokens.authenticate(authorization) else {
+        warn!("Rejected unauthenticated request to {}", request.uri().path());
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            "Missing or invalid service token".to_string(),
+        ));
+    };
+
+    tracing::debug!("Request to {} from {}", request.uri().path(), caller.0);
+    request.extensions_mut().insert(caller);
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,216 @@
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+// ==============================================================================
+// PAYLOAD CACHE: Redis in front of the payments query
+// ==============================================================================
+//
+// Retries and fan-out fetch the same payload repeatedly within seconds. Entries
+// are dropped as soon as Postgres NOTIFYs a payment update, and the TTL bounds
+// staleness for anything missed while the listener was reconnecting.
+//
+// Cache errors never fail a request: Redis is an optimization, Postgres is the
+// source of truth.
+//
+// With stale fallback enabled, every write also keeps a "last known" copy that
+// invalidation doesn't touch and that lives much longer. It is only read when
+// Postgres is unreachable, so a short database outage doesn't turn into a wave
+// of failed deliveries.
+//
+// Both copies hold the payload with the merchant's field policy already
+// applied. When the policy changes, every entry of that merchant's payments is
+// dropped, last-known copies included, so neither a hit nor an outage can
+// serve fields the merchant has since stripped.
+
+pub const INVALIDATION_CHANNEL: &str = "payment_updated";
+pub const POLICY_CHANNEL: &str = "merchant_policy_updated";
+
+/// Keys per DEL when dropping a merchant's entries
+const EVICT_CHUNK: usize = 500;
+
+#[derive(Clone)]
+pub struct PayloadCache {
+    conn: ConnectionManager,
+    ttl_secs: u64,
+    /// None disables the last-known copies
+    stale_ttl_secs: Option<u64>,
+}
+
+fn key(payment_id: Uuid) -> String {
+    format!("payload:{}", payment_id)
+}
+
+fn stale_key(payment_id: Uuid) -> String {
+    format!("payload:stale:{}", payment_id)
+}
+
+impl PayloadCache {
+    pub async fn connect(
+        redis_url: &str,
+        ttl_secs: u64,
+        stale_ttl_secs: Option<u64>,
+    ) -> redis::RedisResult<Self> {
+        let client = redis::Client::open(redis_url)?;
+        let conn = client.get_connection_manager().await?;
+        Ok(PayloadCache {
+            conn,
+            ttl_secs,
+            stale_ttl_secs,
+        })
+    }
+
+    /// For the readiness report (health.rs)
+    pub fn connection(&self) -> ConnectionManager {
+        self.conn.clone()
+    }
+
+    pub async fn get(&self, payment_id: Uuid) -> Option<String> {
+        let mut conn = self.conn.clone();
+        match conn.get::<_, Option<String>>(key(payment_id)).await {
+            Ok(cached) => cached,
+            Err(e) => {
+                warn!("Cache read failed for {}: {}", payment_id, e);
+                None
+            }
+        }
+    }
+
+    /// One MGET for many payments, results in the same order as the ids
+    pub async fn get_many(&self, payment_ids: &[Uuid]) -> Vec<Option<String>> {
+        if payment_ids.is_empty() {
+            return Vec::new();
+        }
+        let keys: Vec<String> = payment_ids.iter().map(|id| key(*id)).collect();
+        let mut conn = self.conn.clone();
+        match redis::cmd("MGET")
+            .arg(&keys)
+            .query_async::<_, Vec<Option<String>>>(&mut conn)
+            .await
+        {
+            Ok(cached) => cached,
+            Err(e) => {
+                warn!("Cache read failed for {} payloads: {}", payment_ids.len(), e);
+                vec![None; payment_ids.len()]
+            }
+        }
+    }
+
+    pub async fn set(&self, payment_id: Uuid, payload: &str) {
+        let mut pipe = redis::pipe();
+        pipe.set_ex(key(payment_id), payload, self.ttl_secs).ignore();
+        if let Some(stale_ttl_secs) = self.stale_ttl_secs {
+            pipe.set_ex(stale_key(payment_id), payload, stale_ttl_secs)
+                .ignore();
+        }
+
+        let mut conn = self.conn.clone();
+        if let Err(e) = pipe.query_async::<_, ()>(&mut conn).await {
+            warn!("Cache write failed for {}: {}", payment_id, e);
+        }
+    }
+
+    /// The last payload written for this payment, however old. Only for when
+    /// Postgres can't answer.
+    pub async fn get_stale(&self, payment_id: Uuid) -> Option<String> {
+        self.stale_ttl_secs?;
+        let mut conn = self.conn.clone();
+        match conn.get::<_, Option<String>>(stale_key(payment_id)).await {
+            Ok(cached) => cached,
+            Err(e) => {
+                warn!("Stale cache read failed for {}: {}", payment_id, e);
+                None
+            }
+        }
+    }
+
+    pub async fn invalidate(&self, payment_id: Uuid) {
+        let mut conn = self.conn.clone();
+        if let Err(e) = conn.del::<_, ()>(key(payment_id)).await {
+            warn!("Cache invalidation failed for {}: {}", payment_id, e);
+        }
+    }
+
+    /// Drop both copies of these payments, for when what they hold is no
+    /// longer allowed out rather than just out of date
+    pub async fn evict(&self, payment_ids: &[Uuid]) {
+        let mut conn = self.conn.clone();
+        for chunk in payment_ids.chunks(EVICT_CHUNK) {
+            let keys: Vec<String> = chunk
+                .iter()
+                .flat_map(|id| [key(*id), stale_key(*id)])
+                .collect();
+            if let Err(e) = conn.del::<_, ()>(keys).await {
+                warn!("Cache eviction failed for {} payloads: {}", chunk.len(), e);
+            }
+        }
+    }
+}
+
+/// A merchant's field policy changed: evict all of its payments' entries
+async fn evict_merchant(db: &PgPool, cache: &PayloadCache, merchant_id: Uuid) {
+    let payment_ids = match sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM payments WHERE merchant_id = $1",
+    )
+    .bind(merchant_id)
+    .fetch_all(db)
+    .await
+    {
+        Ok(payment_ids) => payment_ids,
+        Err(e) => {
+            warn!("Failed to list payments of merchant {} for eviction: {}", merchant_id, e);
+            return;
+        }
+    };
+    cache.evict(&payment_ids).await;
+    info!(
+        "Field policy of merchant {} changed, evicted {} cached payloads",
+        merchant_id,
+        payment_ids.len()
+    );
+}
+
+/// LISTEN for payment updates and field policy changes and evict the matching
+/// entries. PgListener reconnects on its own; payment updates during the gap
+/// are covered by the TTL.
+pub async fn run_invalidation_listener(db: PgPool, cache: PayloadCache) {
+    let mut listener = match PgListener::connect_with(&db).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Cache invalidation disabled, failed to connect listener: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = listener
+        .listen_all([INVALIDATION_CHANNEL, POLICY_CHANNEL])
+        .await
+    {
+        warn!("Cache invalidation disabled, LISTEN failed: {}", e);
+        return;
+    }
+
+    info!(
+        "Listening for payment updates on '{}' and policy changes on '{}'",
+        INVALIDATION_CHANNEL, POLICY_CHANNEL
+    );
+
+    loop {
+        match listener.recv().await {
+            Ok(notification) => match Uuid::parse_str(notification.payload()) {
+                Ok(merchant_id) if notification.channel() == POLICY_CHANNEL => {
+                    evict_merchant(&db, &cache, merchant_id).await
+                }
+                Ok(payment_id) => cache.invalidate(payment_id).await,
+                Err(_) => warn!("Ignoring malformed notification: {}", notification.payload()),
+            },
+            Err(e) => {
+                warn!("Invalidation listener error (reconnecting): {}", e);
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
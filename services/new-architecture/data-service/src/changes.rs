@@ -0,0 +1,100 @@
+use axum::{
+    extract::{Json, Query, State},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{self, ApiError};
+use crate::AppState;
+
+// ==============================================================================
+// CHANGED-SINCE LISTING: Which payments changed after a point in time
+// ==============================================================================
+//
+// For reconciliation jobs and pull-based consumers that need to catch updates
+// a webhook may have missed. Pages are keyed on (updated_at, id), so payments
+// sharing a timestamp are never skipped between pages.
+//
+// updated_at is the writing transaction's start time, so a slow transaction can
+// commit a change that sorts before a page already read. Callers that must not
+// miss anything should start each run a little before the previous cursor.
+
+const DEFAULT_CHANGES_LIMIT: i64 = 100;
+const MAX_CHANGES_LIMIT: i64 = 1000;
+
+#[derive(Deserialize)]
+pub struct ChangesQuery {
+    since: DateTime<Utc>,
+    /// Tie-breaker for payments updated exactly at `since`, from next_cursor
+    after_id: Option<Uuid>,
+    merchant_id: Option<Uuid>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct ChangedPayment {
+    id: Uuid,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct ChangesCursor {
+    since: DateTime<Utc>,
+    after_id: Uuid,
+}
+
+#[derive(Serialize)]
+pub struct ChangesResponse {
+    data: Vec<ChangedPayment>,
+    /// Pass as `since` / `after_id` for the next page; None when nothing changed
+    next_cursor: Option<ChangesCursor>,
+    has_more: bool,
+}
+
+pub async fn list_changed(
+    State(state): State<AppState>,
+    Query(query): Query<ChangesQuery>,
+) -> Result<Json<ChangesResponse>, ApiError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_CHANGES_LIMIT)
+        .clamp(1, MAX_CHANGES_LIMIT);
+
+    // Fetch one extra row to know whether another page exists
+    let query_changes = || {
+        sqlx::query_as::<_, ChangedPayment>(
+            r#"
+        SELECT id, updated_at
+        FROM payments
+        WHERE (updated_at, id) > ($1, COALESCE($2, '00000000-0000-0000-0000-000000000000'::UUID))
+          AND ($3::UUID IS NULL OR merchant_id = $3)
+        ORDER BY updated_at, id
+        LIMIT $4
+        "#,
+        )
+        .bind(query.since)
+        .bind(query.after_id)
+        .bind(query.merchant_id)
+        .bind(limit + 1)
+        .fetch_all(&state.db)
+    };
+
+    let mut data = db::with_retry(&state.db_policy, "changes query", query_changes)
+        .await
+        .map_err(|e| ApiError::database(e, &state.db_policy))?;
+
+    let has_more = data.len() as i64 > limit;
+    data.truncate(limit as usize);
+
+    let next_cursor = data.last().map(|last| ChangesCursor {
+        since: last.updated_at,
+        after_id: last.id,
+    });
+
+    Ok(Json(ChangesResponse {
+        data,
+        next_cursor,
+        has_more,
+    }))
+}
@@ -0,0 +1,146 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use rand::Rng;
+use std::future::Future;
+use std::time::Duration;
+use tracing::warn;
+
+// ==============================================================================
+// DATABASE POLICY: Statement timeout, bounded retries, honest errors
+// ==============================================================================
+//
+// Every query runs under a statement timeout so a stuck query can't hold a
+// delivery (and a pool connection) indefinitely. Transient failures - dropped
+// connections, pool exhaustion, serialization failures, deadlocks - are
+// retried a couple of times with jittered backoff. Whatever still fails as
+// transient, or timed out, is reported as 503 with Retry-After so callers back
+// off instead of treating it as a bug.
+
+#[derive(Debug, Clone, Copy)]
+pub struct DbPolicy {
+    pub statement_timeout: Duration,
+    /// Retries after the first attempt
+    pub retries: u32,
+    pub backoff: Duration,
+    pub retry_after_secs: u64,
+}
+
+impl DbPolicy {
+    pub fn from_env() -> Self {
+        let env_u64 = |name: &str, default: u64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+
+        DbPolicy {
+            statement_timeout: Duration::from_millis(env_u64("DB_STATEMENT_TIMEOUT_MS", 2000)),
+            retries: env_u64("DB_RETRIES", 2) as u32,
+            backoff: Duration::from_millis(env_u64("DB_RETRY_BACKOFF_MS", 50)),
+            retry_after_secs: env_u64("DB_RETRY_AFTER_SECS", 1),
+        }
+    }
+}
+
+/// Worth another attempt: the same query may well succeed right away
+fn is_transient(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut => true,
+        sqlx::Error::Database(db) => matches!(
+            db.code().as_deref(),
+            // serialization_failure, deadlock_detected, admin_shutdown,
+            // cannot_connect_now, and the connection_exception class
+            Some("40001" | "40P01" | "57P01" | "57P03" | "08000" | "08003" | "08006")
+        ),
+        _ => false,
+    }
+}
+
+/// The statement ran into statement_timeout (query_canceled)
+fn is_timeout(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db) if db.code().as_deref() == Some("57014"))
+}
+
+/// The database is degraded rather than the request being wrong
+pub fn is_unavailable(e: &sqlx::Error) -> bool {
+    is_transient(e) || is_timeout(e)
+}
+
+/// Run `op`, retrying transient errors with full-jitter exponential backoff
+pub async fn with_retry<T, F, Fut>(
+    policy: &DbPolicy,
+    what: &str,
+    mut op: F,
+) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Err(e) if attempt < policy.retries && is_transient(&e) => {
+                attempt += 1;
+                let ceiling = policy.backoff * 2u32.pow(attempt - 1);
+                let delay = rand::thread_rng().gen_range(Duration::ZERO..=ceiling);
+                warn!(
+                    "Transient error in {} (attempt {}/{}), retrying in {:?}: {}",
+                    what,
+                    attempt,
+                    policy.retries + 1,
+                    delay,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Handler error: a plain status and message, or a database failure that
+/// maps to 503 + Retry-After when the database is degraded
+pub enum ApiError {
+    Status(StatusCode, String),
+    Database(sqlx::Error, u64),
+}
+
+impl ApiError {
+    pub fn database(e: sqlx::Error, policy: &DbPolicy) -> Self {
+        ApiError::Database(e, policy.retry_after_secs)
+    }
+}
+
+impl From<(StatusCode, String)> for ApiError {
+    fn from((status, message): (StatusCode, String)) -> Self {
+        ApiError::Status(status, message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        match self {
+            ApiError::Status(status, message) => (status, message).into_response(),
+            ApiError::Database(e, retry_after_secs) if is_unavailable(&e) => {
+                tracing::error!("Database unavailable: {}", e);
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                    format!("Database unavailable: {}", e),
+                )
+                    .into_response()
+            }
+            ApiError::Database(e, _) => {
+                tracing::error!("Database error: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Database error: {}", e),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
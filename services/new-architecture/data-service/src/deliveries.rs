@@ -0,0 +1,127 @@
+use axum::{
+    extract::{Json, Query, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+use crate::db::{self, ApiError};
+use crate::AppState;
+
+// ==============================================================================
+// DELIVERY ATTEMPTS: Every recorded attempt for a payment, whichever path
+// ==============================================================================
+//
+// Each delivery path writes its outcomes to delivery_attempts: svix-caller's
+// Svix submission ('svix'), its direct fallback ('direct', and per endpoint
+// 'direct_endpoint'), Svix's operational webhooks ('svix_endpoint') and
+// merchant confirmations ('merchant'). GET /deliveries?payment_id= joins them to the payment's
+// domain_events, replays included, so "was this payment delivered, and how"
+// is one query instead of a psql session per path.
+//
+// delivery_path narrows to one path; the summary counts attempts per path and
+// status over the rows returned.
+
+const DEFAULT_DELIVERIES_LIMIT: i64 = 200;
+const MAX_DELIVERIES_LIMIT: i64 = 1000;
+
+#[derive(Deserialize)]
+pub struct DeliveriesQuery {
+    payment_id: Option<Uuid>,
+    /// One event (original or replay) instead of a whole payment
+    event_id: Option<i64>,
+    delivery_path: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct DeliveryAttempt {
+    id: i64,
+    event_id: i64,
+    event_type: String,
+    /// Set when the attempt was for a replayed event
+    replay_of: Option<i64>,
+    delivery_path: String,
+    status: String,
+    error: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct DeliveriesSummary {
+    attempts: usize,
+    /// Attempts per delivery_path, then per status
+    by_path: BTreeMap<String, BTreeMap<String, usize>>,
+    /// Any attempt succeeded on any path; on the direct path, at every
+    /// endpoint rather than one
+    delivered: bool,
+}
+
+#[derive(Serialize)]
+pub struct DeliveriesResponse {
+    /// Oldest first
+    data: Vec<DeliveryAttempt>,
+    summary: DeliveriesSummary,
+}
+
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    Query(query): Query<DeliveriesQuery>,
+) -> Result<Json<DeliveriesResponse>, ApiError> {
+    if query.payment_id.is_none() && query.event_id.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Give payment_id or event_id".to_string(),
+        )
+            .into());
+    }
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_DELIVERIES_LIMIT)
+        .clamp(1, MAX_DELIVERIES_LIMIT);
+
+    let query_attempts = || {
+        sqlx::query_as::<_, DeliveryAttempt>(
+            r#"
+            SELECT a.id, a.event_id, e.event_type, e.replay_of, a.delivery_path,
+                   a.status, a.error, a.created_at
+            FROM delivery_attempts a
+            JOIN domain_events e ON e.id = a.event_id
+            WHERE ($1::UUID IS NULL OR e.object_id = $1)
+              AND ($2::BIGINT IS NULL OR a.event_id = $2)
+              AND ($3::TEXT IS NULL OR a.delivery_path = $3)
+            ORDER BY a.id
+            LIMIT $4
+            "#,
+        )
+        .bind(query.payment_id)
+        .bind(query.event_id)
+        .bind(query.delivery_path.as_deref())
+        .bind(limit)
+        .fetch_all(&state.db)
+    };
+
+    let data = db::with_retry(&state.db_policy, "deliveries query", query_attempts)
+        .await
+        .map_err(|e| ApiError::database(e, &state.db_policy))?;
+
+    let mut by_path: BTreeMap<String, BTreeMap<String, usize>> = BTreeMap::new();
+    for attempt in &data {
+        *by_path
+            .entry(attempt.delivery_path.clone())
+            .or_default()
+            .entry(attempt.status.clone())
+            .or_default() += 1;
+    }
+    let summary = DeliveriesSummary {
+        attempts: data.len(),
+        delivered: data.iter().any(|attempt| {
+            attempt.status == "succeeded" && attempt.delivery_path != "direct_endpoint"
+        }),
+        by_path,
+    };
+
+    Ok(Json(DeliveriesResponse { data, summary }))
+}
@@ -0,0 +1,61 @@
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+// ==============================================================================
+// ENCODING: JSON by default, MessagePack on request
+// ==============================================================================
+//
+// Callers on the hot path can send `Accept: application/msgpack` to skip JSON
+// text encoding. MessagePack is written with field names and in human-readable
+// mode, so ids and timestamps stay strings and the decoded value has exactly
+// the JSON shape.
+
+pub const MSGPACK: &str = "application/msgpack";
+
+pub fn wants_msgpack(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|media| media.split(';').next().unwrap_or("").trim())
+        .any(|media| media == MSGPACK || media == "application/x-msgpack")
+}
+
+fn to_msgpack<T: Serialize>(value: &T) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+    let mut buffer = Vec::new();
+    value.serialize(
+        &mut rmp_serde::Serializer::new(&mut buffer)
+            .with_struct_map()
+            .with_human_readable(),
+    )?;
+    Ok(buffer)
+}
+
+/// `value` in the representation the Accept header asks for
+pub fn encode<T: Serialize>(headers: &HeaderMap, value: &T) -> Response {
+    let mut response = if wants_msgpack(headers) {
+        match to_msgpack(value) {
+            Ok(body) => ([(header::CONTENT_TYPE, MSGPACK)], body).into_response(),
+            Err(e) => {
+                tracing::error!("Failed to encode MessagePack: {}", e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to encode MessagePack: {}", e),
+                )
+                    .into_response();
+            }
+        }
+    } else {
+        Json(value).into_response()
+    };
+
+    response
+        .headers_mut()
+        .insert(header::VARY, HeaderValue::from_static("accept, accept-version"));
+    response
+}
@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// ==============================================================================
+// ENRICHMENT: Joined merchant / customer data, chosen per event type
+// ==============================================================================
+//
+// The payload query always joins merchants and customers, so cached payloads
+// carry both; which of them a webhook includes depends on the event type being
+// delivered. Rules come from PAYLOAD_ENRICHMENTS, e.g.
+//
+//   payment.succeeded=merchant+customer,payment.*=merchant,*=none
+//
+// An exact event type wins over the longest matching "prefix.*" pattern, which
+// wins over "*". Without rules (or without a matching one) nothing is added,
+// which keeps payloads identical to the raw payments row.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MerchantInfo {
+    pub id: Uuid,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomerRef {
+    pub id: Uuid,
+    /// The merchant's own id for the customer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_ref: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Enrichment {
+    pub merchant: bool,
+    pub customer: bool,
+}
+
+#[derive(Default)]
+pub struct EnrichmentRules {
+    rules: Vec<(String, Enrichment)>,
+}
+
+impl EnrichmentRules {
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var("PAYLOAD_ENRICHMENTS") {
+            Ok(spec) => Self::parse(&spec),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    fn parse(spec: &str) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for rule in spec.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+            let (pattern, includes) = rule.split_once('=').ok_or_else(|| {
+                format!("Invalid enrichment rule '{}', expected type=includes", rule)
+            })?;
+
+            let mut enrichment = Enrichment::default();
+            for include in includes.split('+').map(str::trim) {
+                match include {
+                    "merchant" => enrichment.merchant = true,
+                    "customer" => enrichment.customer = true,
+                    "none" => {}
+                    other => {
+                        return Err(format!(
+                            "Unknown enrichment '{}', expected merchant, customer or none",
+                            other
+                        ))
+                    }
+                }
+            }
+            rules.push((pattern.trim().to_string(), enrichment));
+        }
+        Ok(EnrichmentRules { rules })
+    }
+
+    pub fn for_event_type(&self, event_type: Option<&str>) -> Enrichment {
+        let rule = |pattern: &str| {
+            self.rules
+                .iter()
+                .find(|(p, _)| p == pattern)
+                .map(|(_, enrichment)| *enrichment)
+        };
+
+        if let Some(event_type) = event_type {
+            if let Some(enrichment) = rule(event_type) {
+                return enrichment;
+            }
+            let prefix_match = self
+                .rules
+                .iter()
+                .filter_map(|(pattern, enrichment)| {
+                    let prefix = pattern.strip_suffix('*')?;
+                    (!prefix.is_empty() && event_type.starts_with(prefix))
+                        .then_some((prefix.len(), *enrichment))
+                })
+                .max_by_key(|(len, _)| *len);
+            if let Some((_, enrichment)) = prefix_match {
+                return enrichment;
+            }
+        }
+
+        rule("*").unwrap_or_default()
+    }
+}
@@ -0,0 +1,302 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tonic::codec::CompressionEncoding;
+use tonic::service::Interceptor;
+use tonic::metadata::MetadataValue;
+use tonic::{Request, Response, Status};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::audit::{self, PayloadAccess};
+use crate::auth::{CallerService, ServiceTokens};
+use crate::db;
+use crate::enrichment::Enrichment;
+use crate::versions::PayloadVersion;
+use crate::size::Oversized;
+use crate::{
+    fit_payload, load_payload, load_payloads, stale_payload, AppState, PaymentPayload,
+    MAX_BATCH_PAYLOADS, STALE_HEADER, TRUNCATED_HEADER,
+};
+
+// ==============================================================================
+// gRPC API: Same payloads as the HTTP API, over one long-lived HTTP/2 channel
+// ==============================================================================
+//
+// Every webhook delivery pays for a payload fetch, so the consumer can opt into
+// gRPC to skip JSON and connection setup. Both transports share the lookup
+// code (snapshots, cache, batch) in main.rs; only the framing differs. Payloads
+// are always the structured message, with `version` telling the caller which
+// JSON shape the merchant expects.
+
+pub mod proto {
+    tonic::include_proto!("payload.v1");
+}
+
+use proto::payload_service_server::{PayloadService, PayloadServiceServer};
+use proto::{
+    BatchGetPayloadsRequest, BatchGetPayloadsResponse, Customer, GetPayloadRequest, Merchant,
+    Payload,
+};
+
+fn to_proto(
+    payload: PaymentPayload,
+    requested: Option<PayloadVersion>,
+    enrichment: Enrichment,
+) -> Payload {
+    Payload {
+        id: payload.id.to_string(),
+        amount: payload.amount,
+        currency: payload.currency,
+        status: payload.status,
+        mode: payload.mode,
+        amount_decimal: payload.amount_decimal,
+        version: requested
+            .unwrap_or(payload.payload_version)
+            .as_str()
+            .to_string(),
+        updated_at: payload.updated_at.map(|updated_at| updated_at.to_rfc3339()),
+        merchant: payload
+            .merchant
+            .filter(|_| enrichment.merchant)
+            .map(|merchant| Merchant {
+                id: merchant.id.to_string(),
+                name: merchant.name,
+            }),
+        customer: payload
+            .customer
+            .filter(|_| enrichment.customer)
+            .map(|customer| Customer {
+                id: customer.id.to_string(),
+                external_ref: customer.external_ref,
+            }),
+    }
+}
+
+fn parse_version(version: Option<&str>) -> Result<Option<PayloadVersion>, String> {
+    version
+        .map(|v| {
+            PayloadVersion::parse(v)
+                .ok_or_else(|| format!("Unsupported version: {}, expected v1 or v2", v))
+        })
+        .transpose()
+}
+
+fn invalid_payment_id(value: &str) -> Status {
+    Status::invalid_argument(format!("Invalid payment id: {}", value))
+}
+
+fn database_error(e: sqlx::Error) -> Status {
+    tracing::error!("Database error: {}", e);
+    if db::is_unavailable(&e) {
+        Status::unavailable(format!("Database unavailable: {}", e))
+    } else {
+        Status::internal(format!("Database error: {}", e))
+    }
+}
+
+struct PayloadGrpc {
+    state: AppState,
+}
+
+#[tonic::async_trait]
+impl PayloadService for PayloadGrpc {
+    #[tracing::instrument(skip_all, fields(
+        correlation_id = %request.get_ref().payment_id,
+        event_id = request.get_ref().event_id,
+    ))]
+    async fn get_payload(
+        &self,
+        request: Request<GetPayloadRequest>,
+    ) -> Result<Response<Payload>, Status> {
+        let caller = caller(&request);
+        let request = request.into_inner();
+        let payment_id = Uuid::parse_str(&request.payment_id)
+            .map_err(|_| invalid_payment_id(&request.payment_id))?;
+        let requested =
+            parse_version(request.version.as_deref()).map_err(Status::invalid_argument)?;
+        let enrichment = self
+            .state
+            .enrichments
+            .for_event_type(request.event_type.as_deref());
+        let access = |stale| PayloadAccess {
+            caller,
+            transport: "grpc",
+            event_id: request.event_id,
+            stale,
+            payment_ids: vec![payment_id],
+        };
+
+        match load_payload(&self.state, payment_id, request.event_id).await {
+            Ok(Some(payload)) => {
+                let response = payload_response(&self.state, payload, requested, enrichment)
+                    .map_err(too_large)?;
+                audit::record(&self.state, access(false));
+                Ok(response)
+            }
+            Ok(None) => Err(Status::not_found(format!(
+                "Payment not found: {}",
+                payment_id
+            ))),
+            Err(e) => {
+                let status = database_error(e);
+                let Some(payload) = stale_payload(&self.state, payment_id).await else {
+                    return Err(status);
+                };
+                tracing::warn!("Serving stale payload for payment {}", payment_id);
+                let mut response = payload_response(&self.state, payload, requested, enrichment)
+                    .map_err(too_large)?;
+                audit::record(&self.state, access(true));
+                response
+                    .metadata_mut()
+                    .insert(STALE_HEADER, MetadataValue::from_static("true"));
+                Ok(response)
+            }
+        }
+    }
+
+    async fn batch_get_payloads(
+        &self,
+        request: Request<BatchGetPayloadsRequest>,
+    ) -> Result<Response<BatchGetPayloadsResponse>, Status> {
+        let caller = caller(&request);
+        let request = request.into_inner();
+        let requested =
+            parse_version(request.version.as_deref()).map_err(Status::invalid_argument)?;
+        let enrichment = self
+            .state
+            .enrichments
+            .for_event_type(request.event_type.as_deref());
+        if request.payment_ids.len() > MAX_BATCH_PAYLOADS {
+            return Err(Status::invalid_argument(format!(
+                "At most {} payment ids per request",
+                MAX_BATCH_PAYLOADS
+            )));
+        }
+
+        let payment_ids = request
+            .payment_ids
+            .iter()
+            .map(|id| Uuid::parse_str(id).map_err(|_| id))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|id| invalid_payment_id(id))?;
+
+        let (payloads, missing) = load_payloads(&self.state, payment_ids)
+            .await
+            .map_err(database_error)?;
+
+        let mut truncated = Vec::new();
+        let mut oversized = Vec::new();
+        let payloads: Vec<PaymentPayload> = payloads
+            .into_iter()
+            .filter_map(|mut payload| {
+                match fit_payload(&self.state, &mut payload, requested, enrichment) {
+                    Ok(true) => truncated.push(payload.id.to_string()),
+                    Ok(false) => {}
+                    Err(e) => {
+                        tracing::warn!("{}", e);
+                        oversized.push(payload.id.to_string());
+                        return None;
+                    }
+                }
+                Some(payload)
+            })
+            .collect();
+
+        audit::record(
+            &self.state,
+            PayloadAccess {
+                caller,
+                transport: "grpc",
+                event_id: None,
+                stale: false,
+                payment_ids: payloads.iter().map(|payload| payload.id).collect(),
+            },
+        );
+
+        Ok(Response::new(BatchGetPayloadsResponse {
+            payloads: payloads
+                .into_iter()
+                .map(|payload| to_proto(payload, requested, enrichment))
+                .collect(),
+            missing: missing.iter().map(Uuid::to_string).collect(),
+            oversized,
+            truncated,
+        }))
+    }
+}
+
+/// The payload fitted to PAYLOAD_MAX_BYTES, flagged in metadata if truncated
+fn payload_response(
+    state: &AppState,
+    mut payload: PaymentPayload,
+    requested: Option<PayloadVersion>,
+    enrichment: Enrichment,
+) -> Result<Response<Payload>, Oversized> {
+    let truncated = fit_payload(state, &mut payload, requested, enrichment)?;
+    let mut response = Response::new(to_proto(payload, requested, enrichment));
+    if truncated {
+        response
+            .metadata_mut()
+            .insert(TRUNCATED_HEADER, MetadataValue::from_static("true"));
+    }
+    Ok(response)
+}
+
+fn too_large(e: Oversized) -> Status {
+    tracing::warn!("{}", e);
+    Status::resource_exhausted(e.to_string())
+}
+
+/// Set by AuthInterceptor on every request that reaches the service
+fn caller<T>(request: &Request<T>) -> CallerService {
+    request
+        .extensions()
+        .get::<CallerService>()
+        .cloned()
+        .unwrap_or_else(|| CallerService("anonymous".to_string()))
+}
+
+/// Same service tokens as the HTTP API, read from the authorization metadata
+#[derive(Clone)]
+struct AuthInterceptor {
+    tokens: Arc<ServiceTokens>,
+}
+
+impl Interceptor for AuthInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let authorization = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok());
+
+        let caller = self
+            .tokens
+            .authenticate(authorization)
+            .ok_or_else(|| Status::unauthenticated("Missing or invalid service token"))?;
+
+        tracing::debug!("gRPC request from {}", caller.0);
+        request.extensions_mut().insert(caller);
+        Ok(request)
+    }
+}
+
+pub async fn serve(addr: SocketAddr, state: AppState) {
+    info!("DATA SERVICE gRPC listening on {}", addr);
+
+    let interceptor = AuthInterceptor {
+        tokens: state.service_tokens.clone(),
+    };
+    // Compressed responses only go to clients that advertise gzip
+    let service = PayloadServiceServer::new(PayloadGrpc { state })
+        .accept_compressed(CompressionEncoding::Gzip)
+        .send_compressed(CompressionEncoding::Gzip);
+    let service = tonic::service::interceptor::InterceptedService::new(service, interceptor);
+
+    if let Err(e) = tonic::transport::Server::builder()
+        .add_service(service)
+        .serve(addr)
+        .await
+    {
+        tracing::error!("gRPC server stopped: {}", e);
+    }
+}
@@ -0,0 +1,94 @@
+use health_checks::{checks, CheckResult, HealthRegistry};
+use sqlx::PgPool;
+use std::time::Duration;
+
+use crate::cache::PayloadCache;
+
+// ==============================================================================
+// HEALTH: /livez for "is the process alive", /readyz for "should I get traffic"
+// ==============================================================================
+//
+// Served by the health-checks crate; /health is /readyz under the name
+// svix-caller, docker-compose and the e2e tests already probe.
+//
+// Liveness never touches a dependency, so a database outage doesn't get the
+// pod restarted in a loop - a hung process is the only thing it catches.
+// Readiness checks what payload requests need: a database that answers, free
+// pool connections, and, with READINESS_MAX_REPLICA_LAG_MS set, replicas that
+// are caught up. Redis is reported but optional; payloads are served without
+// it.
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReadinessConfig {
+    /// /readyz fails once this fraction of the pool is checked out
+    max_pool_utilization: f64,
+    /// None skips the replication check
+    max_replica_lag: Option<Duration>,
+}
+
+impl ReadinessConfig {
+    pub fn from_env() -> Self {
+        ReadinessConfig {
+            max_pool_utilization: std::env::var("READINESS_MAX_POOL_UTILIZATION")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.9),
+            max_replica_lag: std::env::var("READINESS_MAX_REPLICA_LAG_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_millis),
+        }
+    }
+}
+
+pub fn registry(
+    db: &PgPool,
+    cache: Option<&PayloadCache>,
+    config: ReadinessConfig,
+) -> HealthRegistry {
+    let (database, pool) = (db.clone(), db.clone());
+    let mut registry = HealthRegistry::new()
+        .readiness("database", move || checks::postgres(database.clone()))
+        .readiness("pool", move || {
+            checks::pool(pool.clone(), config.max_pool_utilization)
+        });
+    if let Some(max_lag) = config.max_replica_lag {
+        let db = db.clone();
+        registry = registry.readiness("replication", move || {
+            check_replication(db.clone(), max_lag)
+        });
+    }
+    if let Some(cache) = cache {
+        let connection = cache.connection();
+        registry = registry.optional("redis", move || checks::redis(connection.clone()));
+    }
+    registry
+}
+
+/// Worst replay lag across the primary's streaming replicas. Lag columns are
+/// only visible to superusers and pg_monitor members; NULL lag means the
+/// replica is idle and caught up.
+async fn check_replication(db: PgPool, max_lag: Duration) -> CheckResult {
+    let lag = sqlx::query_as::<_, (i64, Option<f64>)>(
+        r#"
+        SELECT COUNT(*), MAX(EXTRACT(EPOCH FROM replay_lag))::FLOAT8
+        FROM pg_stat_replication
+        "#,
+    )
+    .fetch_one(&db)
+    .await;
+
+    match lag {
+        Ok((0, _)) => CheckResult::pass("no replicas connected"),
+        Ok((replicas, lag_secs)) => {
+            let lag = Duration::from_secs_f64(lag_secs.unwrap_or(0.0).max(0.0));
+            let detail = format!("{} replicas, max lag {}ms", replicas, lag.as_millis());
+            if lag <= max_lag {
+                CheckResult::pass(detail)
+            } else {
+                CheckResult::fail(detail)
+            }
+        }
+        Err(e) => CheckResult::fail(format!("replication check failed: {}", e)),
+    }
+}
@@ -1,54 +1,367 @@
 use axum::{
-    extract::{Json, Path, State},
-    http::StatusCode,
+    extract::{Extension, Json, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    middleware,
     routing::{get, post},
     Router,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::postgres::PgPoolOptions;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use sqlx::PgPool;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+use tower_http::compression::predicate::{NotForContentType, Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
 use tracing::info;
 use uuid::Uuid;
 
+mod audit;
+mod auth;
+mod cache;
+mod changes;
+mod db;
+mod deliveries;
+mod encoding;
+mod enrichment;
+mod grpc;
+mod health;
+mod metrics;
+mod redaction;
+mod settings;
+mod size;
+mod snapshot;
+mod versions;
+
+use audit::{AuditSink, PayloadAccess};
+use auth::{CallerService, ServiceTokens};
+use cache::PayloadCache;
+use db::{ApiError, DbPolicy};
+use service_metrics::ServiceMetrics;
+use settings::Settings;
+use enrichment::{CustomerRef, Enrichment, EnrichmentRules, MerchantInfo};
+use health::ReadinessConfig;
+use metrics::Metrics;
+use redaction::FieldPolicy;
+use size::SizeLimit;
+use versions::{PayloadVersion, VersionedPayload};
+
 #[derive(Clone)]
 struct AppState {
     db: PgPool,
+    /// None when REDIS_URL is unset
+    cache: Option<PayloadCache>,
+    metrics: Metrics,
+    enrichments: Arc<EnrichmentRules>,
+    service_tokens: Arc<ServiceTokens>,
+    db_policy: DbPolicy,
+    audit_sink: AuditSink,
+    /// None when PAYLOAD_MAX_BYTES is unset
+    size_limit: Option<SizeLimit>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct PayloadRequest {
-    payment_id: Uuid,
+const MAX_BATCH_PAYLOADS: usize = 500;
+
+/// Set on responses served from the last-known copy during a database outage
+const STALE_HEADER: &str = "x-payload-stale";
+
+/// Set on responses whose enrichment was cut to fit PAYLOAD_MAX_BYTES
+const TRUNCATED_HEADER: &str = "x-payload-truncated";
+
+#[derive(Deserialize)]
+struct PayloadQuery {
+    /// The event being delivered; lets snapshot-mode merchants get the
+    /// payment as of that event instead of its current state
+    event_id: Option<i64>,
+    /// Selects the merchant/customer data to include, see enrichment.rs
+    event_type: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
+struct BatchPayloadRequest {
+    payment_ids: Vec<Uuid>,
+    #[serde(default)]
+    event_type: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BatchPayloadResponse {
+    payloads: Vec<VersionedPayload>,
+    missing: Vec<Uuid>,
+    /// Served with enrichment cut to fit PAYLOAD_MAX_BYTES
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    truncated: Vec<Uuid>,
+    /// Too large to serve even after truncation
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    oversized: Vec<Uuid>,
+}
+
+/// Internal (and cached) form of a payload, rendered per version by
+/// versions::render. amount, currency and amount_decimal are None when the
+/// merchant's field policy strips them.
+#[derive(Clone, Serialize, Deserialize)]
 struct PaymentPayload {
     id: Uuid,
-    amount: i64,
-    currency: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    amount: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    currency: Option<String>,
     status: String,
+    mode: String,
+    /// amount rendered in major units, e.g. "25.00" for 2500 USD
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    amount_decimal: Option<String>,
+    /// Last change to the payment; the ETag is derived from it
+    #[serde(default)]
+    updated_at: Option<DateTime<Utc>>,
+    /// The merchant's configured version, used without Accept-Version
+    #[serde(default)]
+    payload_version: PayloadVersion,
+    /// Joined data, only delivered when the event type's enrichment asks
+    #[serde(default)]
+    merchant: Option<MerchantInfo>,
+    #[serde(default)]
+    customer: Option<CustomerRef>,
+}
+
+/// Strong validator for a payload: it changes whenever the payment row does,
+/// and differs between versions, enrichments, field policies and encodings
+/// since they are different representations
+fn payload_etag(
+    payload: &PaymentPayload,
+    version: PayloadVersion,
+    enrichment: Enrichment,
+    msgpack: bool,
+) -> Option<String> {
+    payload.updated_at.map(|updated_at| {
+        format!(
+            "\"{}-{}-{}{}{}-f{}{}\"",
+            payload.id.simple(),
+            updated_at.timestamp_micros(),
+            version.as_str(),
+            if enrichment.merchant { "m" } else { "" },
+            if enrichment.customer { "c" } else { "" },
+            kept_fields(payload),
+            if msgpack { "-msgpack" } else { "" },
+        )
+    })
+}
+
+/// Which of the fields a field policy may strip this payload still has, as a
+/// bitmask: a policy change alters the payload without touching the payment
+/// row, so the tag has to tell the two apart
+fn kept_fields(payload: &PaymentPayload) -> u8 {
+    [
+        payload.amount.is_some(),
+        payload.currency.is_some(),
+        payload.amount_decimal.is_some(),
+    ]
+    .iter()
+    .enumerate()
+    .filter(|(_, &kept)| kept)
+    .map(|(bit, _)| 1 << bit)
+    .sum()
+}
+
+/// The version asked for with Accept-Version, if any
+fn requested_version(headers: &HeaderMap) -> Result<Option<PayloadVersion>, (StatusCode, String)> {
+    let Some(value) = headers.get(versions::ACCEPT_VERSION_HEADER) else {
+        return Ok(None);
+    };
+    value
+        .to_str()
+        .ok()
+        .and_then(PayloadVersion::parse)
+        .map(Some)
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Unsupported Accept-Version: {:?}, expected v1 or v2", value),
+            )
+        })
+}
+
+/// If-None-Match is a comma-separated list of (possibly weak) tags, or "*"
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Apply PAYLOAD_MAX_BYTES to a payload about to be rendered. Ok(true) when
+/// its enrichment had to be cut to fit.
+fn fit_payload(
+    state: &AppState,
+    payload: &mut PaymentPayload,
+    requested: Option<PayloadVersion>,
+    enrichment: Enrichment,
+) -> Result<bool, size::Oversized> {
+    let Some(limit) = state.size_limit else {
+        return Ok(false);
+    };
+    let version = requested.unwrap_or(payload.payload_version);
+    limit.enforce(payload, version, enrichment)
+}
+
+/// The payload fitted to PAYLOAD_MAX_BYTES, or 413 if it can't be
+fn payload_response(
+    state: &AppState,
+    headers: &HeaderMap,
+    requested: Option<PayloadVersion>,
+    enrichment: Enrichment,
+    mut payload: PaymentPayload,
+) -> Result<Response, ApiError> {
+    let truncated = fit_payload(state, &mut payload, requested, enrichment).map_err(|e| {
+        tracing::warn!("{}", e);
+        ApiError::Status(StatusCode::PAYLOAD_TOO_LARGE, e.to_string())
+    })?;
+
+    let mut response = render_response(headers, requested, enrichment, payload);
+    if truncated {
+        response
+            .headers_mut()
+            .insert(TRUNCATED_HEADER, HeaderValue::from_static("true"));
+    }
+    Ok(response)
+}
+
+/// 304 when the client already has this version, otherwise the payload
+/// rendered as the requested (or the merchant's) version
+fn render_response(
+    headers: &HeaderMap,
+    requested: Option<PayloadVersion>,
+    enrichment: Enrichment,
+    payload: PaymentPayload,
+) -> Response {
+    let version = requested.unwrap_or(payload.payload_version);
+    let version_header = (versions::VERSION_HEADER, version.as_str());
+    let msgpack = encoding::wants_msgpack(headers);
+
+    let Some(etag) = payload_etag(&payload, version, enrichment, msgpack) else {
+        let rendered = versions::render(payload, version, enrichment);
+        return ([version_header], encoding::encode(headers, &rendered)).into_response();
+    };
+    if etag_matches(headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+    let rendered = versions::render(payload, version, enrichment);
+    (
+        [(header::ETAG, etag)],
+        [version_header],
+        encoding::encode(headers, &rendered),
+    )
+        .into_response()
 }
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    logging::init(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    let settings: Settings = config::load_or_exit("data-service");
+    // Validated to be set
+    let database_url = settings.database_url.clone().unwrap_or_default();
 
-    let database_url = std::env::var("DATABASE_URL")
-        .expect("DATABASE_URL must be set");
+    let db_policy = DbPolicy::from_env();
+    let connect_options = PgConnectOptions::from_str(&database_url)
+        .expect("Invalid DATABASE_URL")
+        .options([(
+            "statement_timeout",
+            db_policy.statement_timeout.as_millis().to_string(),
+        )]);
 
     let pool = PgPoolOptions::new()
         .max_connections(5)
-        .connect(&database_url)
+        .connect_with(connect_options)
         .await
         .expect("Failed to connect to database");
 
-    let state = AppState { db: pool };
+    let cache = match &settings.redis_url {
+        Some(redis_url) => {
+            let ttl_secs = settings.payload_cache_ttl_secs;
+            // Serve the last-known payload when Postgres is down, unless disabled
+            let stale_ttl_secs = settings
+                .payload_stale_fallback
+                .then_some(settings.payload_stale_ttl_secs);
+            let cache = PayloadCache::connect(redis_url, ttl_secs, stale_ttl_secs)
+                .await
+                .expect("Failed to connect to Redis");
+            tokio::spawn(cache::run_invalidation_listener(pool.clone(), cache.clone()));
+            info!(
+                "Payload cache enabled (TTL {}s, stale fallback {:?})",
+                ttl_secs, stale_ttl_secs
+            );
+            Some(cache)
+        }
+        None => {
+            info!("REDIS_URL not set - payload cache disabled");
+            None
+        }
+    };
 
-    let app = Router::new()
-        .route("/health", get(health_check))
+    let enrichments = EnrichmentRules::from_env().expect("Invalid PAYLOAD_ENRICHMENTS");
+
+    let service_tokens = ServiceTokens::from_env().expect("Invalid SERVICE_AUTH_TOKENS");
+    if !service_tokens.enabled() {
+        tracing::warn!("SERVICE_AUTH_TOKENS not set - payload endpoints are unauthenticated");
+    }
+
+    let size_limit = SizeLimit::from_env().expect("Invalid payload size limit");
+
+    let audit_sink = AuditSink::from_env().expect("Invalid PAYLOAD_AUDIT_SINK");
+    info!("Payload audit sink: {:?}", audit_sink);
+
+    let health = health::registry(&pool, cache.as_ref(), ReadinessConfig::from_env());
+
+    let state = AppState {
+        db: pool,
+        cache,
+        metrics: Metrics::new(ServiceMetrics::new(
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+        )),
+        enrichments: Arc::new(enrichments),
+        service_tokens: Arc::new(service_tokens),
+        db_policy,
+        audit_sink,
+        size_limit,
+    };
+
+    let grpc_addr = ([0, 0, 0, 0], settings.grpc_port).into();
+    tokio::spawn(grpc::serve(grpc_addr, state.clone()));
+
+    let payload_routes = Router::new()
         .route("/payload/:payment_id", get(get_payment_payload))
+        .route("/payloads", post(get_payment_payloads))
+        .route("/payloads/changed", get(changes::list_changed))
+        .route("/deliveries", get(deliveries::list_deliveries))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_service_token,
+        ));
+
+    let app = Router::new()
+        .route(
+            "/health",
+            get(health_checks::readyz).with_state(health.clone()),
+        )
+        .merge(health_checks::router(health))
+        .route("/metrics", get(metrics::metrics_handler))
+        .merge(payload_routes)
+        .route_layer(middleware::from_fn_with_state(
+            state.metrics.service().clone(),
+            service_metrics::track_http,
+        ))
+        .layer(compression_layer(settings.payload_compression_min_bytes))
         .with_state(state);
 
-    let port = std::env::var("PORT").unwrap_or_else(|_| "3002".to_string());
+    let port = settings.port;
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port))
         .await
@@ -59,43 +372,346 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
-async fn health_check() -> &'static str {
-    "OK"
+/// gzip/br for clients that send Accept-Encoding. Enriched payloads and
+/// batches compress well, but small single payloads aren't worth the CPU, so
+/// only bodies above PAYLOAD_COMPRESSION_MIN_BYTES are compressed.
+fn compression_layer(min_bytes: u16) -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new()
+        .gzip(true)
+        .br(true)
+        .compress_when(
+            SizeAbove::new(min_bytes)
+                .and(NotForContentType::GRPC)
+                .and(NotForContentType::IMAGES)
+                .and(NotForContentType::SSE),
+        )
 }
 
-async fn get_payment_payload(
-    State(state): State<AppState>,
-    Path(payment_id): Path<Uuid>,
-) -> Result<Json<PaymentPayload>, (StatusCode, String)> {
-    let query_result = sqlx::query_as::<_, (Uuid, i64, String, String)>(
-        "SELECT id, amount, currency, status FROM payments WHERE id = $1"
+/// 2500 with 2 minor units -> "25.00", 2500 with 0 -> "2500"
+fn format_minor_units(amount: i64, minor_units: i16) -> String {
+    if minor_units <= 0 {
+        return amount.to_string();
+    }
+    let scale = 10i64.pow(minor_units as u32);
+    let sign = if amount < 0 { "-" } else { "" };
+    let amount = amount.unsigned_abs();
+    format!(
+        "{}{}.{:0width$}",
+        sign,
+        amount / scale as u64,
+        amount % scale as u64,
+        width = minor_units as usize
     )
-    .bind(payment_id)
-    .fetch_optional(&state.db)
+}
+
+#[derive(sqlx::FromRow)]
+struct PayloadRow {
+    id: Uuid,
+    amount: i64,
+    currency: String,
+    status: String,
+    mode: String,
+    minor_units: i16,
+    updated_at: Option<DateTime<Utc>>,
+    merchant_id: Uuid,
+    merchant_name: Option<String>,
+    customer_id: Option<Uuid>,
+    customer_external_ref: Option<String>,
+    payload_allowed_fields: Option<Vec<String>>,
+    payload_redacted_fields: Option<Vec<String>>,
+    payload_version: Option<String>,
+}
+
+/// Current payloads for the given payments, with each merchant's field policy
+/// applied; missing ids are simply absent
+async fn fetch_payloads(db: &PgPool, payment_ids: &[Uuid]) -> Result<Vec<PaymentPayload>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, PayloadRow>(
+        r#"
+        SELECT p.id, p.amount, p.currency, p.status, p.mode,
+               COALESCE(c.minor_units, 2::SMALLINT) AS minor_units, p.updated_at,
+               p.merchant_id, m.name AS merchant_name,
+               cu.id AS customer_id, cu.external_ref AS customer_external_ref,
+               m.payload_allowed_fields, m.payload_redacted_fields, m.payload_version
+        FROM payments p
+        LEFT JOIN currencies c ON c.code = p.currency
+        LEFT JOIN merchants m ON m.id = p.merchant_id
+        LEFT JOIN customers cu ON cu.id = p.customer_id
+        WHERE p.id = ANY($1)
+        "#,
+    )
+    .bind(payment_ids)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let mut payload = PaymentPayload {
+                id: row.id,
+                amount: Some(row.amount),
+                currency: Some(row.currency),
+                status: row.status,
+                mode: row.mode,
+                amount_decimal: Some(format_minor_units(row.amount, row.minor_units)),
+                updated_at: row.updated_at,
+                payload_version: row
+                    .payload_version
+                    .as_deref()
+                    .and_then(PayloadVersion::parse)
+                    .unwrap_or_default(),
+                merchant: row.merchant_name.map(|name| MerchantInfo {
+                    id: row.merchant_id,
+                    name,
+                }),
+                customer: row.customer_id.map(|id| CustomerRef {
+                    id,
+                    external_ref: row.customer_external_ref,
+                }),
+            };
+            FieldPolicy::new(row.payload_allowed_fields, row.payload_redacted_fields)
+                .apply(&mut payload);
+            payload
+        })
+        .collect())
+}
+
+/// Last-known payload to serve instead of an error when the database fails
+async fn stale_payload(state: &AppState, payment_id: Uuid) -> Option<PaymentPayload> {
+    let cached = state.cache.as_ref()?.get_stale(payment_id).await?;
+    let payload = serde_json::from_str(&cached).ok()?;
+    state.metrics.stale_served();
+    Some(payload)
+}
+
+async fn cache_payload(state: &AppState, payload: &PaymentPayload) {
+    if let Some(cache) = &state.cache {
+        if let Ok(serialized) = serde_json::to_string(payload) {
+            cache.set(payload.id, &serialized).await;
+        }
+    }
+}
+
+/// Payload for one payment: the event-time snapshot when the merchant opted
+/// into snapshots, otherwise the current state (cache first). Shared by the
+/// HTTP and gRPC APIs.
+async fn load_payload(
+    state: &AppState,
+    payment_id: Uuid,
+    event_id: Option<i64>,
+) -> Result<Option<PaymentPayload>, sqlx::Error> {
+    // Snapshots are immutable, so they bypass the cache entirely
+    if let Some(event_id) = event_id {
+        let started = Instant::now();
+        let snapshot = db::with_retry(&state.db_policy, "snapshot query", || {
+            snapshot::fetch_snapshot(&state.db, payment_id, event_id)
+        })
+        .await;
+        state.metrics.observe_query("snapshot", started);
+        if let Some(payload) = snapshot? {
+            info!("Served snapshot payload for payment {} at event {}", payment_id, event_id);
+            return Ok(Some(payload));
+        }
+    }
+
+    if let Some(cache) = &state.cache {
+        if let Some(cached) = cache.get(payment_id).await {
+            if let Ok(payload) = serde_json::from_str::<PaymentPayload>(&cached) {
+                info!("Served cached payload for payment: {}", payment_id);
+                state.metrics.cache_hits(1);
+                return Ok(Some(payload));
+            }
+        }
+        state.metrics.cache_misses(1);
+    }
+
+    let started = Instant::now();
+    let ids = [payment_id];
+    let payloads = db::with_retry(&state.db_policy, "payload query", || {
+        fetch_payloads(&state.db, &ids)
+    })
     .await;
+    state.metrics.observe_query("payloads", started);
+    let mut payloads = payloads?;
+    if payloads.is_empty() {
+        state.metrics.not_found(1);
+        return Ok(None);
+    }
+
+    info!("Fetched fresh payload for payment: {}", payment_id);
+    let payload = payloads.swap_remove(0);
+    cache_payload(state, &payload).await;
+    Ok(Some(payload))
+}
+
+/// One round trip for many payloads: cache hits come from a single MGET, the
+/// misses from a single query. Returns the payloads found and the ids that
+/// weren't.
+async fn load_payloads(
+    state: &AppState,
+    payment_ids: Vec<Uuid>,
+) -> Result<(Vec<PaymentPayload>, Vec<Uuid>), sqlx::Error> {
+    let mut payloads = Vec::with_capacity(payment_ids.len());
+    let mut misses = Vec::new();
 
-    match query_result {
-        Ok(Some((id, amount, currency, status))) => {
-            info!("Fetched fresh payload for payment: {}", payment_id);
-            Ok(Json(PaymentPayload {
-                id,
-                amount,
-                currency,
-                status,
-            }))
+    match &state.cache {
+        Some(cache) => {
+            let cached = cache.get_many(&payment_ids).await;
+            for (payment_id, cached) in payment_ids.iter().zip(cached) {
+                match cached.and_then(|c| serde_json::from_str::<PaymentPayload>(&c).ok()) {
+                    Some(payload) => payloads.push(payload),
+                    None => misses.push(*payment_id),
+                }
+            }
         }
-        Ok(None) => {
-            Err((
-                StatusCode::NOT_FOUND,
-                format!("Payment not found: {}", payment_id),
-            ))
+        None => misses.extend_from_slice(&payment_ids),
+    }
+
+    let cache_hits = payloads.len();
+    if state.cache.is_some() {
+        state.metrics.cache_hits(cache_hits);
+        state.metrics.cache_misses(misses.len());
+    }
+
+    if !misses.is_empty() {
+        let started = Instant::now();
+        let fetched = db::with_retry(&state.db_policy, "payload query", || {
+            fetch_payloads(&state.db, &misses)
+        })
+        .await;
+        state.metrics.observe_query("payloads", started);
+        for payload in fetched? {
+            cache_payload(state, &payload).await;
+            payloads.push(payload);
+        }
+    }
+
+    let found: HashSet<Uuid> = payloads.iter().map(|p| p.id).collect();
+    let missing = payment_ids
+        .into_iter()
+        .filter(|id| !found.contains(id))
+        .collect::<Vec<_>>();
+    state.metrics.not_found(missing.len());
+
+    info!(
+        "Batch payload fetch: {} found ({} cached), {} missing",
+        payloads.len(),
+        cache_hits,
+        missing.len()
+    );
+
+    Ok((payloads, missing))
+}
+
+/// correlation_id/event_id tag the request's logs (logging crate)
+#[tracing::instrument(skip_all, fields(correlation_id = %payment_id, event_id = query.event_id))]
+async fn get_payment_payload(
+    State(state): State<AppState>,
+    Path(payment_id): Path<Uuid>,
+    Query(query): Query<PayloadQuery>,
+    Extension(caller): Extension<CallerService>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let requested = requested_version(&headers)?;
+    let enrichment = state.enrichments.for_event_type(query.event_type.as_deref());
+    let access = |stale| PayloadAccess {
+        caller,
+        transport: "http",
+        event_id: query.event_id,
+        stale,
+        payment_ids: vec![payment_id],
+    };
+
+    match load_payload(&state, payment_id, query.event_id).await {
+        Ok(Some(payload)) => {
+            let response = payload_response(&state, &headers, requested, enrichment, payload)?;
+            audit::record(&state, access(false));
+            Ok(response)
         }
+        Ok(None) => Err(ApiError::Status(
+            StatusCode::NOT_FOUND,
+            format!("Payment not found: {}", payment_id),
+        )),
         Err(e) => {
-            tracing::error!("Database error: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Database error: {}", e),
-            ))
+            if let Some(payload) = stale_payload(&state, payment_id).await {
+                tracing::warn!("Serving stale payload for payment {} after: {}", payment_id, e);
+                let mut response =
+                    payload_response(&state, &headers, requested, enrichment, payload)?;
+                audit::record(&state, access(true));
+                response
+                    .headers_mut()
+                    .insert(STALE_HEADER, HeaderValue::from_static("true"));
+                return Ok(response);
+            }
+            Err(ApiError::database(e, &state.db_policy))
         }
     }
 }
+
+async fn get_payment_payloads(
+    State(state): State<AppState>,
+    Extension(caller): Extension<CallerService>,
+    headers: HeaderMap,
+    Json(req): Json<BatchPayloadRequest>,
+) -> Result<Response, ApiError> {
+    let requested = requested_version(&headers)?;
+    let enrichment = state.enrichments.for_event_type(req.event_type.as_deref());
+
+    if req.payment_ids.len() > MAX_BATCH_PAYLOADS {
+        return Err(ApiError::Status(
+            StatusCode::BAD_REQUEST,
+            format!("At most {} payment ids per request", MAX_BATCH_PAYLOADS),
+        ));
+    }
+
+    let (payloads, missing) = load_payloads(&state, req.payment_ids)
+        .await
+        .map_err(|e| ApiError::database(e, &state.db_policy))?;
+
+    // One oversized payment is reported, not allowed to fail the whole batch
+    let mut truncated = Vec::new();
+    let mut oversized = Vec::new();
+    let payloads: Vec<PaymentPayload> = payloads
+        .into_iter()
+        .filter_map(|mut payload| {
+            match fit_payload(&state, &mut payload, requested, enrichment) {
+                Ok(true) => truncated.push(payload.id),
+                Ok(false) => {}
+                Err(e) => {
+                    tracing::warn!("{}", e);
+                    oversized.push(payload.id);
+                    return None;
+                }
+            }
+            Some(payload)
+        })
+        .collect();
+
+    audit::record(
+        &state,
+        PayloadAccess {
+            caller,
+            transport: "http",
+            event_id: None,
+            stale: false,
+            payment_ids: payloads.iter().map(|payload| payload.id).collect(),
+        },
+    );
+
+    let payloads = payloads
+        .into_iter()
+        .map(|payload| {
+            let version = requested.unwrap_or(payload.payload_version);
+            versions::render(payload, version, enrichment)
+        })
+        .collect();
+
+    Ok(encoding::encode(
+        &headers,
+        &BatchPayloadResponse {
+            payloads,
+            missing,
+            truncated,
+            oversized,
+        },
+    ))
+}
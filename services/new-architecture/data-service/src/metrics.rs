@@ -0,0 +1,125 @@
+use axum::{extract::State, response::IntoResponse};
+use prometheus::{HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts};
+use service_metrics::ServiceMetrics;
+use std::time::Instant;
+
+use crate::AppState;
+
+// ==============================================================================
+// METRICS: Prometheus exposition on /metrics
+// ==============================================================================
+//
+// Every webhook delivery waits on a payload fetch, so enrichment latency adds
+// directly to delivery latency. Query latency, cache effectiveness and pool
+// pressure are the numbers that explain a slow fetch. The hit ratio is
+// cache_lookups_total{result="hit"} over all cache lookups. They sit next to
+// the process and HTTP metrics every service has (service-metrics crate).
+
+#[derive(Clone)]
+pub struct Metrics {
+    service: ServiceMetrics,
+    query_duration: HistogramVec,
+    cache_lookups: IntCounterVec,
+    not_found: IntCounter,
+    stale_served: IntCounter,
+    pool_connections: IntGauge,
+    pool_idle: IntGauge,
+}
+
+impl Metrics {
+    pub fn new(service: ServiceMetrics) -> Self {
+        let registry = service.registry();
+
+        let query_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "data_service_query_duration_seconds",
+                "Latency of payload queries against Postgres",
+            )
+            .buckets(vec![0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]),
+            &["query"],
+        )
+        .unwrap();
+        let cache_lookups = IntCounterVec::new(
+            Opts::new("data_service_cache_lookups_total", "Payload cache lookups by result"),
+            &["result"],
+        )
+        .unwrap();
+        let not_found = IntCounter::new(
+            "data_service_payloads_not_found_total",
+            "Payloads requested for payments that don't exist",
+        )
+        .unwrap();
+        let stale_served = IntCounter::new(
+            "data_service_payloads_stale_total",
+            "Last-known payloads served because the database failed",
+        )
+        .unwrap();
+        let pool_connections = IntGauge::new(
+            "data_service_db_pool_connections",
+            "Open connections in the Postgres pool",
+        )
+        .unwrap();
+        let pool_idle = IntGauge::new(
+            "data_service_db_pool_idle_connections",
+            "Idle connections in the Postgres pool",
+        )
+        .unwrap();
+
+        registry.register(Box::new(query_duration.clone())).unwrap();
+        registry.register(Box::new(cache_lookups.clone())).unwrap();
+        registry.register(Box::new(not_found.clone())).unwrap();
+        registry.register(Box::new(stale_served.clone())).unwrap();
+        registry.register(Box::new(pool_connections.clone())).unwrap();
+        registry.register(Box::new(pool_idle.clone())).unwrap();
+
+        Metrics {
+            service,
+            query_duration,
+            cache_lookups,
+            not_found,
+            stale_served,
+            pool_connections,
+            pool_idle,
+        }
+    }
+
+    /// The common metrics, for the HTTP middleware
+    pub fn service(&self) -> &ServiceMetrics {
+        &self.service
+    }
+
+    pub fn observe_query(&self, query: &str, started: Instant) {
+        self.query_duration
+            .with_label_values(&[query])
+            .observe(started.elapsed().as_secs_f64());
+    }
+
+    pub fn cache_hits(&self, count: usize) {
+        self.cache_lookups
+            .with_label_values(&["hit"])
+            .inc_by(count as u64);
+    }
+
+    pub fn cache_misses(&self, count: usize) {
+        self.cache_lookups
+            .with_label_values(&["miss"])
+            .inc_by(count as u64);
+    }
+
+    pub fn not_found(&self, count: usize) {
+        self.not_found.inc_by(count as u64);
+    }
+
+    pub fn stale_served(&self) {
+        self.stale_served.inc();
+    }
+}
+
+pub async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let metrics = &state.metrics;
+    // Pool stats are sampled at scrape time rather than tracked
+    metrics.pool_connections.set(state.db.size() as i64);
+    metrics.pool_idle.set(state.db.num_idle() as i64);
+
+    metrics.service.render()
+}
@@ -0,0 +1,46 @@
+use crate::PaymentPayload;
+
+// ==============================================================================
+// FIELD POLICY: Per-merchant allowlist / redaction of payload fields
+// ==============================================================================
+//
+// Merchants can keep fields they shouldn't see (or don't want to store) out of
+// their webhooks. The policy is applied before payloads are cached, so a policy
+// change takes effect within the cache TTL. id, status and mode identify the
+// payment and are never stripped; api-service validates policies against the
+// same list of redactable fields.
+
+#[derive(Default)]
+pub struct FieldPolicy {
+    /// None allows every field
+    allowed: Option<Vec<String>>,
+    redacted: Vec<String>,
+}
+
+impl FieldPolicy {
+    pub fn new(allowed: Option<Vec<String>>, redacted: Option<Vec<String>>) -> Self {
+        FieldPolicy {
+            allowed,
+            redacted: redacted.unwrap_or_default(),
+        }
+    }
+
+    fn keeps(&self, field: &str) -> bool {
+        self.allowed
+            .as_ref()
+            .is_none_or(|allowed| allowed.iter().any(|f| f == field))
+            && !self.redacted.iter().any(|f| f == field)
+    }
+
+    pub fn apply(&self, payload: &mut PaymentPayload) {
+        if !self.keeps("amount") {
+            payload.amount = None;
+        }
+        if !self.keeps("currency") {
+            payload.currency = None;
+        }
+        if !self.keeps("amount_decimal") {
+            payload.amount_decimal = None;
+        }
+    }
+}
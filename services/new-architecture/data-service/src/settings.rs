@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+// ==============================================================================
+// SETTINGS: Everything main.rs reads at startup
+// ==============================================================================
+//
+// Loaded through the shared config crate: defaults below, then
+// config/data-service.toml (or CONFIG_FILE), then the environment variable of
+// the same name in upper case. The modules with settings of their own (auth,
+// audit, db, enrichment, size, health) still read them with from_env.
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Required
+    pub database_url: Option<String>,
+    /// Unset disables the payload cache
+    pub redis_url: Option<String>,
+    pub payload_cache_ttl_secs: u64,
+    /// Serve the last-known payload when Postgres is down
+    pub payload_stale_fallback: bool,
+    pub payload_stale_ttl_secs: u64,
+    pub grpc_port: u16,
+    pub port: u16,
+    /// Smaller responses aren't compressed
+    pub payload_compression_min_bytes: u16,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            database_url: None,
+            redis_url: None,
+            payload_cache_ttl_secs: 30,
+            payload_stale_fallback: true,
+            payload_stale_ttl_secs: 86400,
+            grpc_port: 50051,
+            port: 3002,
+            payload_compression_min_bytes: 1024,
+        }
+    }
+}
+
+impl config::Validate for Settings {
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.database_url.is_none() {
+            problems.push("DATABASE_URL: required".to_string());
+        }
+        if self.payload_cache_ttl_secs == 0 {
+            problems.push("PAYLOAD_CACHE_TTL_SECS: must be at least 1".to_string());
+        }
+        problems
+    }
+}
@@ -0,0 +1,140 @@
+use uuid::Uuid;
+
+use crate::enrichment::Enrichment;
+use crate::versions::{self, PayloadVersion};
+use crate::PaymentPayload;
+
+// ==============================================================================
+// SIZE LIMIT: One oversized record must not break delivery
+// ==============================================================================
+//
+// Joined merchant/customer data is the only unbounded part of a payload, so
+// with PAYLOAD_MAX_BYTES set a rendered payload over the limit is shrunk in
+// steps until it fits: long enrichment strings are cut, then the customer is
+// dropped, then the merchant. The core payment fields are never touched - if
+// they alone don't fit, or PAYLOAD_OVERSIZE_POLICY=reject, the payload is
+// refused with 413 and the caller should deliver the payment by reference
+// (id only, fetched by the receiver) instead.
+//
+// Size is measured as JSON, which is never smaller than the MessagePack form.
+
+/// Characters kept from an enrichment string that gets truncated
+const TRUNCATED_CHARS: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SizeLimit {
+    max_bytes: usize,
+    truncate: bool,
+}
+
+/// A payload that can't be made to fit
+pub struct Oversized {
+    pub payment_id: Uuid,
+    pub bytes: usize,
+    pub max_bytes: usize,
+}
+
+impl std::fmt::Display for Oversized {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Payload for payment {} is {} bytes, over the {} byte limit; deliver it by \
+             reference (payment id only) and let the receiver fetch GET /payload/{}",
+            self.payment_id, self.bytes, self.max_bytes, self.payment_id
+        )
+    }
+}
+
+impl SizeLimit {
+    /// None when PAYLOAD_MAX_BYTES is unset
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(max_bytes) = std::env::var("PAYLOAD_MAX_BYTES") else {
+            return Ok(None);
+        };
+        let max_bytes = max_bytes
+            .parse()
+            .map_err(|_| format!("Invalid PAYLOAD_MAX_BYTES '{}'", max_bytes))?;
+
+        let truncate = match std::env::var("PAYLOAD_OVERSIZE_POLICY").as_deref() {
+            Ok("truncate") | Err(_) => true,
+            Ok("reject") => false,
+            Ok(other) => {
+                return Err(format!(
+                    "Unknown PAYLOAD_OVERSIZE_POLICY '{}', expected 'truncate' or 'reject'",
+                    other
+                ))
+            }
+        };
+
+        Ok(Some(SizeLimit {
+            max_bytes,
+            truncate,
+        }))
+    }
+
+    /// Make `payload` fit as rendered for `version` and `enrichment`.
+    /// Ok(true) means something was truncated or dropped.
+    pub fn enforce(
+        &self,
+        payload: &mut PaymentPayload,
+        version: PayloadVersion,
+        enrichment: Enrichment,
+    ) -> Result<bool, Oversized> {
+        let bytes = rendered_len(payload, version, enrichment);
+        if bytes <= self.max_bytes {
+            return Ok(false);
+        }
+        let payment_id = payload.id;
+        let oversized = |bytes| Oversized {
+            payment_id,
+            bytes,
+            max_bytes: self.max_bytes,
+        };
+        if !self.truncate {
+            return Err(oversized(bytes));
+        }
+
+        let steps: [fn(&mut PaymentPayload); 3] = [
+            |payload| {
+                if let Some(merchant) = payload.merchant.as_mut() {
+                    truncate(&mut merchant.name);
+                }
+                if let Some(external_ref) = payload
+                    .customer
+                    .as_mut()
+                    .and_then(|customer| customer.external_ref.as_mut())
+                {
+                    truncate(external_ref);
+                }
+            },
+            |payload| payload.customer = None,
+            |payload| payload.merchant = None,
+        ];
+
+        let mut bytes = bytes;
+        for step in steps {
+            step(payload);
+            bytes = rendered_len(payload, version, enrichment);
+            if bytes <= self.max_bytes {
+                return Ok(true);
+            }
+        }
+        Err(oversized(bytes))
+    }
+}
+
+fn rendered_len(
+    payload: &PaymentPayload,
+    version: PayloadVersion,
+    enrichment: Enrichment,
+) -> usize {
+    let rendered = versions::render(payload.clone(), version, enrichment);
+    serde_json::to_vec(&rendered).map_or(0, |body| body.len())
+}
+
+fn truncate(value: &mut String) {
+    if let Some((cut, _)) = value.char_indices().nth(TRUNCATED_CHARS) {
+        value.truncate(cut);
+        value.push('…');
+    }
+}
@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::enrichment::{CustomerRef, MerchantInfo};
+use crate::redaction::FieldPolicy;
+use crate::versions::PayloadVersion;
+use crate::{format_minor_units, PaymentPayload};
+
+// ==============================================================================
+// SNAPSHOT PAYLOADS: The payment as it was when the event was created
+// ==============================================================================
+//
+// By default webhooks carry the payment's current state ("fetch fresh"), so a
+// delayed payment.processing webhook may already show status succeeded. Some
+// merchants explicitly want the state at event time instead; for them
+// (merchants.payload_mode = 'snapshot') we serve the row the status trigger
+// wrote into payload_snapshots in the same transaction as the event.
+
+#[derive(Deserialize)]
+struct PaymentSnapshot {
+    id: Uuid,
+    amount: i64,
+    currency: String,
+    status: String,
+    mode: String,
+    /// Absent in snapshots written before it was captured
+    #[serde(default)]
+    updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(sqlx::FromRow)]
+struct SnapshotRow {
+    payload: serde_json::Value,
+    minor_units: i16,
+    merchant_id: Uuid,
+    merchant_name: String,
+    customer_id: Option<Uuid>,
+    customer_external_ref: Option<String>,
+    payload_allowed_fields: Option<Vec<String>>,
+    payload_redacted_fields: Option<Vec<String>>,
+    payload_version: String,
+}
+
+/// The snapshot for this event if the payment's merchant opted into snapshot
+/// payloads. None means "serve the current payload" - either the merchant
+/// uses current mode or the event predates snapshots.
+pub async fn fetch_snapshot(
+    db: &PgPool,
+    payment_id: Uuid,
+    event_id: i64,
+) -> Result<Option<PaymentPayload>, sqlx::Error> {
+    let row = sqlx::query_as::<_, SnapshotRow>(
+        r#"
+        SELECT s.payload, COALESCE(c.minor_units, 2::SMALLINT) AS minor_units,
+               m.id AS merchant_id, m.name AS merchant_name,
+               cu.id AS customer_id, cu.external_ref AS customer_external_ref,
+               m.payload_allowed_fields, m.payload_redacted_fields, m.payload_version
+        FROM payload_snapshots s
+        JOIN payments p ON p.id = s.payment_id
+        JOIN merchants m ON m.id = p.merchant_id
+        LEFT JOIN currencies c ON c.code = s.payload->>'currency'
+        LEFT JOIN customers cu ON cu.id = p.customer_id
+        WHERE s.event_id = $1
+          AND s.payment_id = $2
+          AND m.payload_mode = 'snapshot'
+        "#,
+    )
+    .bind(event_id)
+    .bind(payment_id)
+    .fetch_optional(db)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    let snapshot: PaymentSnapshot =
+        serde_json::from_value(row.payload).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+    let mut payload = PaymentPayload {
+        id: snapshot.id,
+        amount: Some(snapshot.amount),
+        currency: Some(snapshot.currency),
+        status: snapshot.status,
+        mode: snapshot.mode,
+        amount_decimal: Some(format_minor_units(snapshot.amount, row.minor_units)),
+        updated_at: snapshot.updated_at,
+        payload_version: PayloadVersion::parse(&row.payload_version).unwrap_or_default(),
+        // Merchant and customer are current data, only the payment is snapshotted
+        merchant: Some(MerchantInfo {
+            id: row.merchant_id,
+            name: row.merchant_name,
+        }),
+        customer: row.customer_id.map(|id| CustomerRef {
+            id,
+            external_ref: row.customer_external_ref,
+        }),
+    };
+    FieldPolicy::new(row.payload_allowed_fields, row.payload_redacted_fields).apply(&mut payload);
+    Ok(Some(payload))
+}
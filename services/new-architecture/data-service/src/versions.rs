@@ -0,0 +1,108 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::enrichment::{CustomerRef, Enrichment, MerchantInfo};
+use crate::PaymentPayload;
+
+// ==============================================================================
+// PAYLOAD VERSIONS: Evolve the payload shape without breaking integrations
+// ==============================================================================
+//
+// v1 is the original flat payload. v2 groups the money fields into an amount
+// object and adds updated_at. Merchants stay on their configured version
+// (merchants.payload_version, default v1) until they migrate; a caller can ask
+// for a specific version with the Accept-Version header.
+
+pub const ACCEPT_VERSION_HEADER: &str = "accept-version";
+pub const VERSION_HEADER: &str = "payload-version";
+
+pub use webhook_types::PayloadVersion;
+
+/// The shared v1 payment (webhook-types), plus enrichment
+#[derive(Serialize)]
+pub struct PayloadV1 {
+    #[serde(flatten)]
+    payment: webhook_types::PaymentPayload,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    merchant: Option<MerchantInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    customer: Option<CustomerRef>,
+}
+
+#[derive(Serialize)]
+pub struct AmountV2 {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    currency: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    decimal: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PayloadV2 {
+    object: &'static str,
+    id: Uuid,
+    status: String,
+    mode: String,
+    /// Absent when the field policy strips every amount field
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amount: Option<AmountV2>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    updated_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    merchant: Option<MerchantInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    customer: Option<CustomerRef>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum VersionedPayload {
+    V1(PayloadV1),
+    V2(PayloadV2),
+}
+
+pub fn render(
+    payload: PaymentPayload,
+    version: PayloadVersion,
+    enrichment: Enrichment,
+) -> VersionedPayload {
+    let merchant = payload.merchant.filter(|_| enrichment.merchant);
+    let customer = payload.customer.filter(|_| enrichment.customer);
+
+    match version {
+        PayloadVersion::V1 => VersionedPayload::V1(PayloadV1 {
+            payment: webhook_types::PaymentPayload {
+                id: payload.id,
+                amount: payload.amount,
+                currency: payload.currency,
+                status: payload.status,
+                mode: payload.mode,
+                amount_decimal: payload.amount_decimal,
+            },
+            merchant,
+            customer,
+        }),
+        PayloadVersion::V2 => {
+            let has_amount = payload.amount.is_some()
+                || payload.currency.is_some()
+                || payload.amount_decimal.is_some();
+            VersionedPayload::V2(PayloadV2 {
+                object: "payment",
+                id: payload.id,
+                status: payload.status,
+                mode: payload.mode,
+                amount: has_amount.then_some(AmountV2 {
+                    value: payload.amount,
+                    currency: payload.currency,
+                    decimal: payload.amount_decimal,
+                }),
+                updated_at: payload.updated_at,
+                merchant,
+                customer,
+            })
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use chrono::{DateTime, Utc};
+use http_client::HttpClient;
+use parking_lot::RwLock;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::conditions::{self, Firing};
+use crate::metrics::Metrics;
+use crate::notify::{self, Channel, Notification};
+use crate::rules::{Rule, Rules};
+
+// ==============================================================================
+// ALERTER: Rules evaluated every INTERVAL_SECS, notifications under cooldown
+// ==============================================================================
+//
+// Each round runs every rule's check (conditions.rs) and, per rule and subject:
+//
+//   newly firing        notify the rule's channels
+//   still firing        notify again once cooldown_secs have passed since
+//                       the last notification
+//   no longer firing    notify that it resolved
+//
+// A check that fails (Postgres or Kafka unreachable) changes nothing for its
+// rule that round: what was firing stays firing, rather than resolving and
+// firing again when the check comes back.
+//
+// What's firing lives in memory, so run one instance; a restart notifies
+// whatever is still firing once more. GET /alerts lists it.
+
+/// A subject of a rule that's firing
+#[derive(Clone, Serialize)]
+pub struct Active {
+    pub rule: String,
+    pub key: String,
+    pub severity: String,
+    pub summary: String,
+    pub value: f64,
+    pub threshold: f64,
+    pub since: DateTime<Utc>,
+    pub last_notified_at: DateTime<Utc>,
+    #[serde(skip)]
+    last_notified: Instant,
+}
+
+pub type ActiveAlerts = Arc<RwLock<BTreeMap<(String, String), Active>>>;
+
+pub struct Alerter {
+    pub db: PgPool,
+    pub kafka_brokers: Option<String>,
+    pub rules: Rules,
+    /// Channels whose secrets are set, by name
+    pub channels: HashMap<String, Channel>,
+    pub client: HttpClient,
+    pub interval: Duration,
+    pub active: ActiveAlerts,
+    pub metrics: Metrics,
+}
+
+impl Alerter {
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            interval.tick().await;
+            for rule in &self.rules.rules {
+                match conditions::evaluate(rule, &self.db, self.kafka_brokers.as_deref()).await {
+                    Ok(firing) => self.update(rule, firing).await,
+                    Err(e) => {
+                        self.metrics.check_failed(&rule.name);
+                        tracing::warn!("Check of rule {} failed: {}", rule.name, e);
+                    }
+                }
+            }
+            self.metrics.evaluated();
+        }
+    }
+
+    async fn update(&self, rule: &Rule, firing: Vec<Firing>) {
+        let now = Instant::now();
+        let previous: HashMap<String, Active> = self
+            .active
+            .read()
+            .iter()
+            .filter(|((name, _), _)| *name == rule.name)
+            .map(|((_, key), active)| (key.clone(), active.clone()))
+            .collect();
+
+        let mut still_firing = Vec::with_capacity(firing.len());
+        for alert in &firing {
+            let before = previous.get(&alert.key);
+            let since = before.map_or_else(Utc::now, |active| active.since);
+            let due = before
+                .is_none_or(|active| now.duration_since(active.last_notified) >= rule.cooldown());
+            if due {
+                let status = if before.is_some() {
+                    "still firing"
+                } else {
+                    "firing"
+                };
+                tracing::warn!("Alert {} {}: {}", rule.name, status, alert.summary);
+                self.notify(rule, &Notification::new(rule, alert, "firing", since))
+                    .await;
+            }
+            let (last_notified, last_notified_at) = match before {
+                Some(active) if !due => (active.last_notified, active.last_notified_at),
+                _ => (now, Utc::now()),
+            };
+            still_firing.push(Active {
+                rule: rule.name.clone(),
+                key: alert.key.clone(),
+                severity: rule.severity.clone(),
+                summary: alert.summary.clone(),
+                value: alert.value,
+                threshold: alert.threshold,
+                since,
+                last_notified_at,
+                last_notified,
+            });
+        }
+
+        for (key, active) in &previous {
+            if firing.iter().any(|alert| &alert.key == key) {
+                continue;
+            }
+            tracing::info!("Alert {} resolved: {}", rule.name, active.summary);
+            let resolved = Firing {
+                key: key.clone(),
+                summary: active.summary.clone(),
+                value: active.value,
+                threshold: active.threshold,
+            };
+            self.notify(
+                rule,
+                &Notification::new(rule, &resolved, "resolved", active.since),
+            )
+            .await;
+        }
+
+        let mut active = self.active.write();
+        active.retain(|(name, _), _| *name != rule.name);
+        for alert in still_firing {
+            active.insert((alert.rule.clone(), alert.key.clone()), alert);
+        }
+        self.metrics.firing(&rule.name, firing.len());
+    }
+
+    async fn notify(&self, rule: &Rule, notification: &Notification<'_>) {
+        for name in &rule.channels {
+            // Left off on start, for want of its secret
+            let Some(channel) = self.channels.get(name) else {
+                continue;
+            };
+            match notify::send(&self.client, channel, notification).await {
+                Ok(()) => self.metrics.notification(channel.kind(), "sent"),
+                Err(e) => {
+                    self.metrics.notification(channel.kind(), "failed");
+                    tracing::warn!("Failed to notify {} of alert {}: {}", name, rule.name, e);
+                }
+            }
+        }
+    }
+}
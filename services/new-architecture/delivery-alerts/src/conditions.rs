@@ -0,0 +1,235 @@
+use rdkafka::consumer::{BaseConsumer, Consumer};
+use rdkafka::{ClientConfig, Offset, TopicPartitionList};
+use sqlx::PgPool;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::rules::{Check, Rule};
+
+// ==============================================================================
+// CONDITIONS: Each rule's check, evaluated to what's firing now
+// ==============================================================================
+//
+// A check returns every subject over its threshold this round; the alerter
+// (alerter.rs) compares that with the last round to know what started,
+// continues or resolved. Subjects are keyed so the same one keeps its key
+// from round to round: the endpoint id, the merchant id, or the group.
+
+const KAFKA_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// One subject over a rule's threshold
+#[derive(Clone, Debug)]
+pub struct Firing {
+    pub key: String,
+    pub summary: String,
+    pub value: f64,
+    pub threshold: f64,
+}
+
+pub async fn evaluate(
+    rule: &Rule,
+    db: &PgPool,
+    kafka_brokers: Option<&str>,
+) -> Result<Vec<Firing>, String> {
+    match &rule.check {
+        Check::EndpointHealth {
+            below_score,
+            above_failure_rate,
+            min_attempts,
+        } => endpoint_health(db, *below_score, *above_failure_rate, *min_attempts).await,
+        Check::DeadLetterDepth {
+            above,
+            grown_by,
+            window_secs,
+            per_merchant,
+        } => dead_letter_depth(db, *above, *grown_by, *window_secs, *per_merchant).await,
+        Check::ConsumerLag {
+            group,
+            topic,
+            above,
+        } => {
+            let brokers = kafka_brokers
+                .ok_or("consumer_lag needs KAFKA_BROKERS")?
+                .to_string();
+            let (group, topic, above) = (group.clone(), topic.clone(), *above);
+            tokio::task::spawn_blocking(move || consumer_lag(&brokers, &group, &topic, above))
+                .await
+                .map_err(|e| format!("Lag check panicked: {}", e))?
+        }
+    }
+}
+
+async fn endpoint_health(
+    db: &PgPool,
+    below_score: Option<f64>,
+    above_failure_rate: Option<f64>,
+    min_attempts: f64,
+) -> Result<Vec<Firing>, String> {
+    let rows = sqlx::query_as::<_, (Uuid, Uuid, String, f64, f64)>(
+        r#"
+        SELECT h.endpoint_id, h.merchant_id, e.url, h.score, 1 - h.succeeded / h.attempts
+        FROM endpoint_health h
+        JOIN merchant_endpoints e ON e.id = h.endpoint_id
+        WHERE NOT e.disabled AND h.score IS NOT NULL AND h.attempts >= $1
+          AND (h.score < $2 OR 1 - h.succeeded / h.attempts >= $3)
+        ORDER BY h.score
+        "#,
+    )
+    .bind(min_attempts)
+    // A threshold that isn't set never matches
+    .bind(below_score.unwrap_or(f64::NEG_INFINITY))
+    .bind(above_failure_rate.unwrap_or(f64::INFINITY))
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to read endpoint health: {}", e))?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(endpoint_id, merchant_id, url, score, failure_rate)| {
+            // Whichever threshold it crossed, the score first
+            let (value, threshold) = match below_score {
+                Some(below) if score < below => (score, below),
+                _ => (failure_rate, above_failure_rate.unwrap_or_default()),
+            };
+            Firing {
+                key: endpoint_id.to_string(),
+                summary: format!(
+                    "Endpoint {} of merchant {} scores {:.0}/100, failing {:.0}% of deliveries",
+                    url,
+                    merchant_id,
+                    score,
+                    failure_rate * 100.0
+                ),
+                value,
+                threshold,
+            }
+        })
+        .collect())
+}
+
+async fn dead_letter_depth(
+    db: &PgPool,
+    above: Option<i64>,
+    grown_by: Option<i64>,
+    window_secs: u64,
+    per_merchant: bool,
+) -> Result<Vec<Firing>, String> {
+    let group = if per_merchant {
+        "merchant_id::TEXT"
+    } else {
+        "'all'"
+    };
+    let rows = sqlx::query_as::<_, (String, i64, i64)>(&format!(
+        r#"
+        SELECT {group}, COUNT(*),
+               COUNT(*) FILTER (WHERE created_at > NOW() - make_interval(secs => $1))
+        FROM svix_dead_letters
+        WHERE redriven_at IS NULL AND purged_at IS NULL
+        GROUP BY 1
+        "#,
+    ))
+    .bind(window_secs as f64)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to count dead letters: {}", e))?;
+
+    let whose = |key: &str| {
+        if per_merchant {
+            format!(" for merchant {}", key)
+        } else {
+            String::new()
+        }
+    };
+    Ok(rows
+        .into_iter()
+        .filter_map(|(key, depth, recent)| {
+            if let Some(above) = above.filter(|above| depth > *above) {
+                return Some(Firing {
+                    summary: format!(
+                        "{} dead letters pending{} (alerting above {})",
+                        depth,
+                        whose(&key),
+                        above
+                    ),
+                    key,
+                    value: depth as f64,
+                    threshold: above as f64,
+                });
+            }
+            let grown_by = grown_by.filter(|grown_by| recent >= *grown_by)?;
+            Some(Firing {
+                summary: format!(
+                    "{} new dead letters{} in the last {}s, {} pending",
+                    recent,
+                    whose(&key),
+                    window_secs,
+                    depth
+                ),
+                key,
+                value: recent as f64,
+                threshold: grown_by as f64,
+            })
+        })
+        .collect())
+}
+
+/// Blocking: the group's committed offsets against the high watermarks. A
+/// partition the group never committed counts from the low watermark.
+fn consumer_lag(
+    brokers: &str,
+    group: &str,
+    topic: &str,
+    above: i64,
+) -> Result<Vec<Firing>, String> {
+    // Only reads the group's offsets; it never joins the group
+    let consumer: BaseConsumer = ClientConfig::new()
+        .set("bootstrap.servers", brokers)
+        .set("group.id", group)
+        .set("enable.auto.commit", "false")
+        .create()
+        .map_err(|e| format!("Invalid Kafka configuration: {}", e))?;
+
+    let metadata = consumer
+        .fetch_metadata(Some(topic), KAFKA_TIMEOUT)
+        .map_err(|e| format!("Failed to fetch metadata for {}: {}", topic, e))?;
+    let mut partitions = TopicPartitionList::new();
+    for partition in metadata
+        .topics()
+        .iter()
+        .filter(|found| found.name() == topic)
+        .flat_map(|found| found.partitions())
+    {
+        partitions.add_partition(topic, partition.id());
+    }
+    if partitions.count() == 0 {
+        return Err(format!("Topic {} has no partitions", topic));
+    }
+
+    let committed = consumer
+        .committed_offsets(partitions, KAFKA_TIMEOUT)
+        .map_err(|e| format!("Failed to read offsets of group {}: {}", group, e))?;
+    let mut lag = 0;
+    for element in committed.elements() {
+        let (low, high) = consumer
+            .fetch_watermarks(topic, element.partition(), KAFKA_TIMEOUT)
+            .map_err(|e| format!("Failed to read watermarks of {}: {}", topic, e))?;
+        let position = match element.offset() {
+            Offset::Offset(offset) => offset,
+            _ => low,
+        };
+        lag += (high - position).max(0);
+    }
+
+    if lag <= above {
+        return Ok(Vec::new());
+    }
+    Ok(vec![Firing {
+        key: format!("{}/{}", group, topic),
+        summary: format!(
+            "Consumer group {} is {} records behind on {} (alerting above {})",
+            group, lag, topic, above
+        ),
+        value: lag as f64,
+        threshold: above as f64,
+    }])
+}
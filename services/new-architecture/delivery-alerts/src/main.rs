@@ -0,0 +1,144 @@
+use axum::{extract::State, middleware, routing::get, Json, Router};
+use health_checks::{checks, HealthRegistry};
+use http_client::{HttpClient, HttpClientConfig, HttpMetrics};
+use service_metrics::ServiceMetrics;
+use sqlx::postgres::PgPoolOptions;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{info, warn};
+
+mod alerter;
+mod conditions;
+mod metrics;
+mod notify;
+mod rules;
+mod settings;
+
+use alerter::{Active, ActiveAlerts, Alerter};
+use metrics::Metrics;
+use notify::Channel;
+use rules::Rules;
+use settings::Settings;
+
+// ==============================================================================
+// DELIVERY ALERTS: Slack, PagerDuty and webhook alerts on delivery trouble
+// ==============================================================================
+//
+// Every INTERVAL_SECS, checks the rules in ALERT_RULES (rules.rs) against
+// endpoint health, the dead-letter queue and Kafka consumer lag (conditions.rs),
+// and notifies each rule's channels of what starts firing, keeps firing past
+// its cooldown, or resolves (alerter.rs, notify.rs).
+//
+// GET /alerts lists what's firing; health and metrics are on PORT too
+// (default 3010).
+
+/// A missing one means init.sql hasn't been applied to this database yet
+const REQUIRED_TABLES: &[&str] = &["endpoint_health", "merchant_endpoints", "svix_dead_letters"];
+
+#[tokio::main]
+async fn main() {
+    logging::init(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    let settings: Settings = config::load_or_exit("delivery-alerts");
+    // Validated to be set and to load
+    let database_url = settings.database_url.clone().unwrap_or_default();
+    let rules_path = settings.alert_rules.clone().unwrap_or_default();
+    let rules = Rules::load(&rules_path).expect("Invalid alert rules");
+
+    let db = PgPoolOptions::new()
+        .max_connections(4)
+        .connect(&database_url)
+        .await
+        .expect("Failed to connect to database");
+
+    let metrics = Metrics::new(ServiceMetrics::new(
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+    ));
+    // POSTs aren't retried by the client; the cooldown is the retry
+    let client = HttpClient::new(
+        HttpClientConfig::new("alert_notifications"),
+        &HttpMetrics::new(metrics.service().registry()),
+    )
+    .expect("Failed to build HTTP client");
+
+    let mut channels = HashMap::new();
+    for (name, spec) in &rules.channels {
+        // Validated with the settings
+        match Channel::from_spec(name, spec).expect("Invalid alert channel") {
+            Some(channel) => {
+                channels.insert(name.clone(), channel);
+            }
+            None => warn!(
+                "Channel {} is off: its environment variable isn't set",
+                name
+            ),
+        }
+    }
+    info!(
+        "Checking {} alert rules from {} every {}s, notifying {} of {} channels",
+        rules.rules.len(),
+        rules_path.display(),
+        settings.interval_secs,
+        channels.len(),
+        rules.channels.len()
+    );
+
+    let active = ActiveAlerts::default();
+    let mut health = HealthRegistry::new();
+    if rules.needs_kafka() {
+        // Validated to be set
+        let brokers: Vec<String> = settings
+            .kafka_brokers
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|broker| broker.trim().to_string())
+            .collect();
+        health = health.readiness("kafka", move || checks::tcp(brokers.clone()));
+    }
+
+    let alerter = Alerter {
+        db: db.clone(),
+        kafka_brokers: settings.kafka_brokers.clone(),
+        rules,
+        channels,
+        client,
+        interval: Duration::from_secs(settings.interval_secs),
+        active: active.clone(),
+        metrics: metrics.clone(),
+    };
+    tokio::spawn(alerter.run());
+
+    let migrations = db.clone();
+    let health = health
+        .readiness("database", move || checks::postgres(db.clone()))
+        .readiness("migrations", move || {
+            checks::tables(migrations.clone(), REQUIRED_TABLES)
+        });
+
+    let app = Router::new()
+        .route("/alerts", get(list_alerts))
+        .with_state(active)
+        .merge(health_checks::router(health))
+        .merge(service_metrics::router(metrics.service().clone()))
+        .route_layer(middleware::from_fn_with_state(
+            metrics.service().clone(),
+            service_metrics::track_http,
+        ));
+
+    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", settings.port))
+        .await
+        .unwrap();
+
+    info!("DELIVERY ALERTS listening on port {}", settings.port);
+
+    axum::serve(listener, app).await.unwrap();
+}
+
+/// What's firing, oldest first
+async fn list_alerts(State(active): State<ActiveAlerts>) -> Json<Vec<Active>> {
+    let mut alerts: Vec<Active> = active.read().values().cloned().collect();
+    alerts.sort_by_key(|alert| alert.since);
+    Json(alerts)
+}
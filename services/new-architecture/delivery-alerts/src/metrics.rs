@@ -0,0 +1,96 @@
+use prometheus::{IntCounterVec, IntGauge, IntGaugeVec, Opts};
+use service_metrics::ServiceMetrics;
+
+// ==============================================================================
+// METRICS: What's firing, and whether notifications get out
+// ==============================================================================
+//
+// Next to the process and HTTP metrics every service has (service-metrics
+// crate) and the http_client_* family of the notification calls:
+//
+//   alerts_firing{rule}                          subjects firing per rule
+//   alerts_notifications_total{channel, result}  sent or failed, by channel type
+//   alerts_check_failures_total{rule}            checks that couldn't run
+//   alerts_last_evaluation_seconds               unix time of the last round
+//
+// Failed notifications are the ones to watch here: an alert nobody receives
+// looks the same as no alert.
+
+#[derive(Clone)]
+pub struct Metrics {
+    service: ServiceMetrics,
+    firing: IntGaugeVec,
+    notifications: IntCounterVec,
+    check_failures: IntCounterVec,
+    last_evaluation: IntGauge,
+}
+
+impl Metrics {
+    pub fn new(service: ServiceMetrics) -> Self {
+        let registry = service.registry();
+
+        let firing = IntGaugeVec::new(
+            Opts::new("alerts_firing", "Subjects currently firing, by rule"),
+            &["rule"],
+        )
+        .unwrap();
+        let notifications = IntCounterVec::new(
+            Opts::new(
+                "alerts_notifications_total",
+                "Notifications by channel type and result",
+            ),
+            &["channel", "result"],
+        )
+        .unwrap();
+        let check_failures = IntCounterVec::new(
+            Opts::new(
+                "alerts_check_failures_total",
+                "Rule checks that failed to run, by rule",
+            ),
+            &["rule"],
+        )
+        .unwrap();
+        let last_evaluation = IntGauge::with_opts(Opts::new(
+            "alerts_last_evaluation_seconds",
+            "Unix time at the end of the last round of checks",
+        ))
+        .unwrap();
+
+        registry.register(Box::new(firing.clone())).unwrap();
+        registry.register(Box::new(notifications.clone())).unwrap();
+        registry.register(Box::new(check_failures.clone())).unwrap();
+        registry
+            .register(Box::new(last_evaluation.clone()))
+            .unwrap();
+
+        Metrics {
+            service,
+            firing,
+            notifications,
+            check_failures,
+            last_evaluation,
+        }
+    }
+
+    pub fn service(&self) -> &ServiceMetrics {
+        &self.service
+    }
+
+    pub fn firing(&self, rule: &str, subjects: usize) {
+        self.firing.with_label_values(&[rule]).set(subjects as i64);
+    }
+
+    pub fn notification(&self, channel: &str, result: &str) {
+        self.notifications
+            .with_label_values(&[channel, result])
+            .inc();
+    }
+
+    pub fn check_failed(&self, rule: &str) {
+        self.check_failures.with_label_values(&[rule]).inc();
+    }
+
+    pub fn evaluated(&self) {
+        self.last_evaluation.set(chrono::Utc::now().timestamp());
+    }
+}
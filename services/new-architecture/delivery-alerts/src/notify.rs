@@ -0,0 +1,177 @@
+use chrono::{DateTime, Utc};
+use http_client::HttpClient;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::conditions::Firing;
+use crate::rules::{ChannelSpec, Rule};
+
+// ==============================================================================
+// NOTIFY: Slack, PagerDuty and plain webhooks
+// ==============================================================================
+//
+//   slack       an incoming webhook; one message per alert and resolve
+//   pagerduty   Events API v2: trigger with dedup_key <rule>/<key>, so the
+//               same alert sent again after its cooldown stays one incident,
+//               and resolve closes it
+//   webhook     POST of a Notification as JSON
+//
+// Sends aren't retried here (the http-client doesn't retry POSTs): a failed
+// one is counted and logged, and a firing alert goes out again after its
+// cooldown.
+
+const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// A channel ready to send, its secret read from the environment
+pub enum Channel {
+    Slack { url: String },
+    PagerDuty { url: String, routing_key: String },
+    Webhook { url: String },
+}
+
+impl Channel {
+    /// None when the environment variable it needs isn't set; an error, in
+    /// the config crate's format, when a URL it would send to isn't one.
+    /// Settings::validate runs it for every channel, so main's can't fail.
+    pub fn from_spec(name: &str, spec: &ChannelSpec) -> Result<Option<Self>, String> {
+        let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let channel = match spec {
+            ChannelSpec::Slack { url_env } => env(url_env)
+                .map(|url| check_url(&url, url_env))
+                .transpose()?
+                .map(|url| Channel::Slack { url }),
+            ChannelSpec::Pagerduty {
+                routing_key_env,
+                url,
+            } => {
+                let url = match url {
+                    Some(url) => check_url(url, &format!("channel {}: url", name))?,
+                    None => PAGERDUTY_EVENTS_URL.to_string(),
+                };
+                env(routing_key_env).map(|routing_key| Channel::PagerDuty { url, routing_key })
+            }
+            ChannelSpec::Webhook { url, url_env } => match (url, url_env) {
+                (Some(url), _) => Some(check_url(url, &format!("channel {}: url", name))?),
+                (None, Some(url_env)) => env(url_env)
+                    .map(|url| check_url(&url, url_env))
+                    .transpose()?,
+                (None, None) => None,
+            }
+            .map(|url| Channel::Webhook { url }),
+        };
+        Ok(channel)
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Channel::Slack { .. } => "slack",
+            Channel::PagerDuty { .. } => "pagerduty",
+            Channel::Webhook { .. } => "webhook",
+        }
+    }
+}
+
+/// The URL back if it's an absolute http(s) one; `what` names where it came
+/// from in the error
+fn check_url(url: &str, what: &str) -> Result<String, String> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(url.to_string()),
+        Ok(parsed) => Err(format!(
+            "{}: {} is not an http(s) URL",
+            what,
+            parsed.scheme()
+        )),
+        Err(e) => Err(format!("{}: invalid URL: {}", what, e)),
+    }
+}
+
+/// What a webhook channel receives
+#[derive(Serialize)]
+pub struct Notification<'a> {
+    pub rule: &'a str,
+    pub key: &'a str,
+    /// firing or resolved
+    pub status: &'a str,
+    pub severity: &'a str,
+    pub summary: &'a str,
+    pub value: f64,
+    pub threshold: f64,
+    /// When it started firing
+    pub since: DateTime<Utc>,
+    pub at: DateTime<Utc>,
+}
+
+impl<'a> Notification<'a> {
+    pub fn new(rule: &'a Rule, firing: &'a Firing, status: &'a str, since: DateTime<Utc>) -> Self {
+        Notification {
+            rule: &rule.name,
+            key: &firing.key,
+            status,
+            severity: &rule.severity,
+            summary: &firing.summary,
+            value: firing.value,
+            threshold: firing.threshold,
+            since,
+            at: Utc::now(),
+        }
+    }
+
+    fn resolved(&self) -> bool {
+        self.status == "resolved"
+    }
+}
+
+pub async fn send(
+    client: &HttpClient,
+    channel: &Channel,
+    notification: &Notification<'_>,
+) -> Result<(), String> {
+    let request = match channel {
+        Channel::Slack { url } => {
+            let text = if notification.resolved() {
+                format!(
+                    ":white_check_mark: *{}* resolved: {}",
+                    notification.rule, notification.summary
+                )
+            } else {
+                format!(
+                    ":rotating_light: *{}* ({}): {}",
+                    notification.rule, notification.severity, notification.summary
+                )
+            };
+            client.post(url).json(&json!({ "text": text }))
+        }
+        Channel::PagerDuty { url, routing_key } => {
+            let dedup_key = format!("{}/{}", notification.rule, notification.key);
+            let body = if notification.resolved() {
+                json!({
+                    "routing_key": routing_key,
+                    "event_action": "resolve",
+                    "dedup_key": dedup_key,
+                })
+            } else {
+                json!({
+                    "routing_key": routing_key,
+                    "event_action": "trigger",
+                    "dedup_key": dedup_key,
+                    "payload": {
+                        "summary": notification.summary,
+                        "source": "delivery-alerts",
+                        "severity": notification.severity,
+                        "timestamp": notification.since,
+                        "custom_details": notification,
+                    },
+                })
+            };
+            client.post(url).json(&body)
+        }
+        Channel::Webhook { url } => client.post(url).json(notification),
+    };
+
+    let response = client.send(request).await.map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("responded {}", response.status()))
+    }
+}
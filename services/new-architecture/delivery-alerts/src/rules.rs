@@ -0,0 +1,241 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+// ==============================================================================
+// RULES: What to alert on, how often, and where to
+// ==============================================================================
+//
+// One TOML file (ALERT_RULES) names the channels and the rules:
+//
+//   [channels.oncall]
+//   type = "pagerduty"
+//   routing_key_env = "PAGERDUTY_ROUTING_KEY"
+//
+//   [channels.team]
+//   type = "slack"
+//   url_env = "SLACK_WEBHOOK_URL"
+//
+//   [channels.ops]
+//   type = "webhook"
+//   url = "http://ops-tools:8080/alerts"
+//
+//   [[rules]]
+//   name = "dead-letters"
+//   check = "dead_letter_depth"
+//   above = 100
+//   severity = "critical"             # critical, error, warning (default), info
+//   cooldown_secs = 900
+//   channels = ["oncall", "team"]
+//
+// Secrets (Slack webhook URLs, PagerDuty routing keys) stay out of the file:
+// a channel names the environment variable holding them. A channel whose
+// variable isn't set is left off, with a warning on start, so the same file
+// works where only some channels are configured.
+//
+// The checks, each firing once per subject (an endpoint, a merchant, the
+// whole queue):
+//
+//   endpoint_health     enabled endpoints whose health score (endpoint_health,
+//                       kept by delivery-analytics) is under below_score, or
+//                       whose failure rate is at least above_failure_rate,
+//                       over at least min_attempts (decayed) attempts
+//   dead_letter_depth   pending svix_dead_letters: more than `above`, or at
+//                       least grown_by new ones in the last window_secs;
+//                       per_merchant = true counts each merchant on its own
+//   consumer_lag        records of `topic` the Kafka consumer group `group`
+//                       hasn't committed, summed over partitions, above `above`
+//
+// A firing alert is sent to the rule's channels, and sent again while it
+// keeps firing only once cooldown_secs have passed. When it stops firing a
+// resolve goes out (PagerDuty closes the incident it opened).
+
+pub const SEVERITIES: &[&str] = &["critical", "error", "warning", "info"];
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    channels: BTreeMap<String, ChannelSpec>,
+    #[serde(default)]
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ChannelSpec {
+    Slack {
+        url_env: String,
+    },
+    Pagerduty {
+        routing_key_env: String,
+        /// The Events API v2 endpoint, for a proxy or a test double
+        #[serde(default)]
+        url: Option<String>,
+    },
+    Webhook {
+        #[serde(default)]
+        url: Option<String>,
+        #[serde(default)]
+        url_env: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "check", rename_all = "snake_case")]
+pub enum Check {
+    EndpointHealth {
+        #[serde(default)]
+        below_score: Option<f64>,
+        #[serde(default)]
+        above_failure_rate: Option<f64>,
+        #[serde(default = "default_min_attempts")]
+        min_attempts: f64,
+    },
+    DeadLetterDepth {
+        #[serde(default)]
+        above: Option<i64>,
+        #[serde(default)]
+        grown_by: Option<i64>,
+        #[serde(default = "default_window_secs")]
+        window_secs: u64,
+        #[serde(default)]
+        per_merchant: bool,
+    },
+    ConsumerLag {
+        group: String,
+        topic: String,
+        above: i64,
+    },
+}
+
+fn default_min_attempts() -> f64 {
+    5.0
+}
+
+fn default_window_secs() -> u64 {
+    3600
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    #[serde(flatten)]
+    pub check: Check,
+    #[serde(default = "default_severity")]
+    pub severity: String,
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+    pub channels: Vec<String>,
+}
+
+fn default_severity() -> String {
+    "warning".to_string()
+}
+
+fn default_cooldown_secs() -> u64 {
+    3600
+}
+
+impl Rule {
+    pub fn cooldown(&self) -> Duration {
+        Duration::from_secs(self.cooldown_secs)
+    }
+}
+
+pub struct Rules {
+    pub channels: BTreeMap<String, ChannelSpec>,
+    pub rules: Vec<Rule>,
+}
+
+impl Rules {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file: RulesFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        for (name, channel) in &file.channels {
+            if let ChannelSpec::Webhook { url, url_env } = channel {
+                if url.is_some() == url_env.is_some() {
+                    return Err(format!("channel {}: needs one of url or url_env", name));
+                }
+            }
+        }
+        let mut names = std::collections::BTreeSet::new();
+        for rule in &file.rules {
+            let problem = |what: String| format!("rule {}: {}", rule.name, what);
+            if rule.name.is_empty() {
+                return Err("every rule needs a name".to_string());
+            }
+            if !names.insert(rule.name.as_str()) {
+                return Err(problem("name used twice".to_string()));
+            }
+            if !SEVERITIES.contains(&rule.severity.as_str()) {
+                return Err(problem(format!(
+                    "severity {:?} is not one of {}",
+                    rule.severity,
+                    SEVERITIES.join(", ")
+                )));
+            }
+            if rule.channels.is_empty() {
+                return Err(problem("needs at least one channel".to_string()));
+            }
+            if let Some(unknown) = rule
+                .channels
+                .iter()
+                .find(|channel| !file.channels.contains_key(*channel))
+            {
+                return Err(problem(format!("unknown channel {}", unknown)));
+            }
+            match &rule.check {
+                Check::EndpointHealth {
+                    below_score,
+                    above_failure_rate,
+                    ..
+                } => {
+                    if below_score.is_none() && above_failure_rate.is_none() {
+                        return Err(problem(
+                            "needs below_score or above_failure_rate".to_string(),
+                        ));
+                    }
+                    if above_failure_rate.is_some_and(|rate| !(0.0..=1.0).contains(&rate)) {
+                        return Err(problem("above_failure_rate is outside 0-1".to_string()));
+                    }
+                }
+                Check::DeadLetterDepth {
+                    above,
+                    grown_by,
+                    window_secs,
+                    ..
+                } => {
+                    if above.is_none() && grown_by.is_none() {
+                        return Err(problem("needs above or grown_by".to_string()));
+                    }
+                    if *window_secs == 0 {
+                        return Err(problem("window_secs must be at least 1".to_string()));
+                    }
+                }
+                Check::ConsumerLag { group, topic, .. } => {
+                    if group.is_empty() || topic.is_empty() {
+                        return Err(problem("needs group and topic".to_string()));
+                    }
+                }
+            }
+        }
+        Ok(Rules {
+            channels: file.channels,
+            rules: file.rules,
+        })
+    }
+
+    /// Whether any rule reads from Kafka
+    pub fn needs_kafka(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| matches!(rule.check, Check::ConsumerLag { .. }))
+    }
+}
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::notify::Channel;
+use crate::rules::Rules;
+
+// ==============================================================================
+// SETTINGS: Where the rules are, and how often they're checked
+// ==============================================================================
+//
+// Loaded through the shared config crate: defaults below, then
+// config/delivery-alerts.toml (or CONFIG_FILE), then the environment
+// variable of the same name in upper case. The rules and channels themselves
+// are in their own file (rules.rs); the variables the channels name are
+// checked here too, so a malformed webhook URL stops the service on start
+// rather than failing every notification.
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// GET /alerts, /livez, /readyz, /startupz and /metrics
+    pub port: u16,
+    /// Required
+    pub database_url: Option<String>,
+    /// Required when a rule checks consumer lag
+    pub kafka_brokers: Option<String>,
+    /// Required: the rules file
+    pub alert_rules: Option<PathBuf>,
+    /// Between rounds of checks
+    pub interval_secs: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            port: 3010,
+            database_url: None,
+            kafka_brokers: None,
+            alert_rules: None,
+            interval_secs: 60,
+        }
+    }
+}
+
+impl config::Validate for Settings {
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.database_url.is_none() {
+            problems.push("DATABASE_URL: required".to_string());
+        }
+        match &self.alert_rules {
+            None => problems.push("ALERT_RULES: required".to_string()),
+            Some(path) => match Rules::load(path) {
+                Ok(rules) => {
+                    if rules.needs_kafka() && self.kafka_brokers.is_none() {
+                        problems.push("KAFKA_BROKERS: required by consumer_lag rules".to_string())
+                    }
+                    // The channels' own variables, named by the rules file
+                    problems.extend(
+                        rules
+                            .channels
+                            .iter()
+                            .filter_map(|(name, spec)| Channel::from_spec(name, spec).err()),
+                    );
+                }
+                Err(e) => problems.push(format!("ALERT_RULES: {}", e)),
+            },
+        }
+        if self.interval_secs == 0 {
+            problems.push("INTERVAL_SECS: must be at least 1".to_string());
+        }
+        problems
+    }
+}
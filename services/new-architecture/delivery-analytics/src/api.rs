@@ -0,0 +1,546 @@
+use axum::{
+    extract::{Json, Path, Query, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+use crate::rollup::LATENCY_BOUNDS_MS;
+
+// ==============================================================================
+// API: Rollups by merchant, event type, status code and hour
+// ==============================================================================
+//
+//   GET /merchants                  attempts and success rate of every merchant
+//   GET /merchants/:id/summary      totals, latency percentiles, a breakdown by
+//                                   event type and failures by status code
+//   GET /merchants/:id/hourly       the same totals, an hour at a time
+//   GET /merchants/:id/endpoints    health of the merchant's endpoints, worst
+//                                   first
+//   GET /endpoints/:id/health       health of one endpoint
+//
+// All take from and to (RFC 3339, default the last 24 hours, rounded out to
+// whole hours); the merchant ones also event_type and delivery_path. Without
+// a delivery_path the 'merchant' path is left out: its confirmations say the
+// merchant processed an event, not that it was delivered.
+//
+// success_rate is succeeded / (succeeded + failed); skipped and dry-run
+// attempts count towards attempts only. Percentiles are interpolated within
+// the histogram buckets (rollup.rs), so they're as precise as the buckets;
+// one in the open-ended last bucket is reported as its lower bound.
+//
+// Endpoint health (health.rs) has no window: its counts are decayed, so
+// attempts is a weight rather than a count, and the ratios are over it.
+
+const DEFAULT_WINDOW_HOURS: i64 = 24;
+const MAX_WINDOW_DAYS: i64 = 366;
+
+const DEFAULT_MERCHANTS: i64 = 100;
+const MAX_MERCHANTS: i64 = 1000;
+
+/// Leaves out 'merchant' unless asked for by name
+const FILTER_SQL: &str = r#"
+    merchant_id = $1 AND hour >= $2 AND hour < $3
+    AND ($4::TEXT IS NULL OR event_type = $4)
+    AND (delivery_path = $5 OR ($5::TEXT IS NULL AND delivery_path <> 'merchant'))
+"#;
+
+#[derive(Deserialize)]
+pub struct RollupQuery {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    event_type: Option<String>,
+    delivery_path: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MerchantsQuery {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct Totals {
+    attempts: i64,
+    succeeded: i64,
+    failed: i64,
+    success_rate: Option<f64>,
+}
+
+impl Totals {
+    fn new(attempts: i64, succeeded: i64, failed: i64) -> Self {
+        let decided = succeeded + failed;
+        Totals {
+            attempts,
+            succeeded,
+            failed,
+            success_rate: (decided > 0).then(|| succeeded as f64 / decided as f64),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Latency {
+    /// Attempts with a latency
+    samples: i64,
+    p50_ms: Option<i64>,
+    p95_ms: Option<i64>,
+    p99_ms: Option<i64>,
+}
+
+impl Latency {
+    fn new(buckets: &BTreeMap<i64, i64>) -> Self {
+        Latency {
+            samples: buckets.values().sum(),
+            p50_ms: percentile(buckets, 0.50),
+            p95_ms: percentile(buckets, 0.95),
+            p99_ms: percentile(buckets, 0.99),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct EventTypeSummary {
+    event_type: String,
+    #[serde(flatten)]
+    totals: Totals,
+    p95_latency_ms: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct StatusCodeCount {
+    status_code: i32,
+    count: i64,
+}
+
+#[derive(Serialize)]
+pub struct SummaryResponse {
+    merchant_id: Uuid,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    totals: Totals,
+    latency: Latency,
+    by_event_type: Vec<EventTypeSummary>,
+    failures_by_status: Vec<StatusCodeCount>,
+}
+
+#[derive(Serialize)]
+pub struct HourSummary {
+    hour: DateTime<Utc>,
+    #[serde(flatten)]
+    totals: Totals,
+    p95_latency_ms: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct HourlyResponse {
+    merchant_id: Uuid,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    hours: Vec<HourSummary>,
+}
+
+#[derive(Serialize)]
+pub struct MerchantSummary {
+    merchant_id: Uuid,
+    #[serde(flatten)]
+    totals: Totals,
+}
+
+/// Same as api-service: ids that aren't UUIDs map to a v5 UUID of themselves
+fn resolve_merchant_id(id: &str) -> Uuid {
+    Uuid::parse_str(id).unwrap_or_else(|_| Uuid::new_v5(&Uuid::NAMESPACE_DNS, id.as_bytes()))
+}
+
+/// [from, to) in whole hours
+fn window(
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), (StatusCode, String)> {
+    let to = to.unwrap_or_else(Utc::now);
+    let from = from.unwrap_or(to - TimeDelta::hours(DEFAULT_WINDOW_HOURS));
+    if from >= to {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "from must be before to".to_string(),
+        ));
+    }
+    if to - from > TimeDelta::days(MAX_WINDOW_DAYS) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("from and to can be at most {} days apart", MAX_WINDOW_DAYS),
+        ));
+    }
+    let hour = TimeDelta::hours(1);
+    let bad_time = |e| (StatusCode::BAD_REQUEST, format!("Invalid time: {}", e));
+    let from = from.duration_trunc(hour).map_err(bad_time)?;
+    let to = match to.duration_trunc(hour).map_err(bad_time)? {
+        truncated if truncated == to => to,
+        truncated => truncated + hour,
+    };
+    Ok((from, to))
+}
+
+/// The q-quantile of a latency histogram (bucket upper bound -> count)
+fn percentile(buckets: &BTreeMap<i64, i64>, q: f64) -> Option<i64> {
+    let total: i64 = buckets.values().sum();
+    if total == 0 {
+        return None;
+    }
+    let rank = q * total as f64;
+    let mut below = 0;
+    for (le_ms, count) in buckets {
+        if (below + count) as f64 >= rank {
+            let lower = LATENCY_BOUNDS_MS
+                .iter()
+                .copied()
+                .take_while(|bound| bound < le_ms)
+                .last()
+                .unwrap_or(0);
+            if *le_ms == i64::MAX {
+                return Some(lower);
+            }
+            let fraction = (rank - below as f64) / *count as f64;
+            return Some(lower + ((le_ms - lower) as f64 * fraction).round() as i64);
+        }
+        below += count;
+    }
+    buckets.keys().next_back().copied()
+}
+
+fn internal_error(what: &str) -> impl FnOnce(sqlx::Error) -> (StatusCode, String) + '_ {
+    move |e| {
+        tracing::error!("Failed to {}: {}", what, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to {}: {}", what, e),
+        )
+    }
+}
+
+/// Latency buckets of the filtered rollups, grouped by the `group` expression
+async fn latency_by(
+    db: &PgPool,
+    group: &str,
+    merchant_id: Uuid,
+    (from, to): (DateTime<Utc>, DateTime<Utc>),
+    query: &RollupQuery,
+) -> Result<BTreeMap<String, BTreeMap<i64, i64>>, (StatusCode, String)> {
+    let rows = sqlx::query_as::<_, (String, i64, i64)>(&format!(
+        r#"
+        SELECT {}::TEXT, le_ms, SUM(count)::BIGINT
+        FROM delivery_rollup_latency
+        WHERE {}
+        GROUP BY 1, 2
+        "#,
+        group, FILTER_SQL
+    ))
+    .bind(merchant_id)
+    .bind(from)
+    .bind(to)
+    .bind(query.event_type.as_deref())
+    .bind(query.delivery_path.as_deref())
+    .fetch_all(db)
+    .await
+    .map_err(internal_error("read latency rollups"))?;
+
+    let mut groups: BTreeMap<String, BTreeMap<i64, i64>> = BTreeMap::new();
+    for (key, le_ms, count) in rows {
+        groups.entry(key).or_default().insert(le_ms, count);
+    }
+    Ok(groups)
+}
+
+pub async fn merchant_summary(
+    State(db): State<PgPool>,
+    Path(merchant_id): Path<String>,
+    Query(query): Query<RollupQuery>,
+) -> Result<Json<SummaryResponse>, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+    let (from, to) = window(query.from, query.to)?;
+
+    let by_event_type = sqlx::query_as::<_, (String, i64, i64, i64)>(&format!(
+        r#"
+        SELECT event_type, SUM(attempts)::BIGINT, SUM(succeeded)::BIGINT, SUM(failed)::BIGINT
+        FROM delivery_rollups
+        WHERE {}
+        GROUP BY event_type
+        ORDER BY 2 DESC, event_type
+        "#,
+        FILTER_SQL
+    ))
+    .bind(merchant_id)
+    .bind(from)
+    .bind(to)
+    .bind(query.event_type.as_deref())
+    .bind(query.delivery_path.as_deref())
+    .fetch_all(&db)
+    .await
+    .map_err(internal_error("read rollups"))?;
+
+    let failures_by_status = sqlx::query_as::<_, (i32, i64)>(&format!(
+        r#"
+        SELECT status_code, SUM(count)::BIGINT
+        FROM delivery_rollup_status_codes
+        WHERE {}
+        GROUP BY status_code
+        ORDER BY 2 DESC, status_code
+        "#,
+        FILTER_SQL
+    ))
+    .bind(merchant_id)
+    .bind(from)
+    .bind(to)
+    .bind(query.event_type.as_deref())
+    .bind(query.delivery_path.as_deref())
+    .fetch_all(&db)
+    .await
+    .map_err(internal_error("read status code rollups"))?;
+
+    let latency = latency_by(&db, "event_type", merchant_id, (from, to), &query).await?;
+    let mut overall: BTreeMap<i64, i64> = BTreeMap::new();
+    for buckets in latency.values() {
+        for (le_ms, count) in buckets {
+            *overall.entry(*le_ms).or_default() += count;
+        }
+    }
+
+    let (mut attempts, mut succeeded, mut failed) = (0, 0, 0);
+    let by_event_type = by_event_type
+        .into_iter()
+        .map(|(event_type, type_attempts, type_succeeded, type_failed)| {
+            attempts += type_attempts;
+            succeeded += type_succeeded;
+            failed += type_failed;
+            EventTypeSummary {
+                p95_latency_ms: latency
+                    .get(&event_type)
+                    .and_then(|buckets| percentile(buckets, 0.95)),
+                event_type,
+                totals: Totals::new(type_attempts, type_succeeded, type_failed),
+            }
+        })
+        .collect();
+
+    Ok(Json(SummaryResponse {
+        merchant_id,
+        from,
+        to,
+        totals: Totals::new(attempts, succeeded, failed),
+        latency: Latency::new(&overall),
+        by_event_type,
+        failures_by_status: failures_by_status
+            .into_iter()
+            .map(|(status_code, count)| StatusCodeCount { status_code, count })
+            .collect(),
+    }))
+}
+
+/// Hours without attempts are left out
+pub async fn merchant_hourly(
+    State(db): State<PgPool>,
+    Path(merchant_id): Path<String>,
+    Query(query): Query<RollupQuery>,
+) -> Result<Json<HourlyResponse>, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+    let (from, to) = window(query.from, query.to)?;
+
+    let rows = sqlx::query_as::<_, (DateTime<Utc>, i64, i64, i64)>(&format!(
+        r#"
+        SELECT hour, SUM(attempts)::BIGINT, SUM(succeeded)::BIGINT, SUM(failed)::BIGINT
+        FROM delivery_rollups
+        WHERE {}
+        GROUP BY hour
+        ORDER BY hour
+        "#,
+        FILTER_SQL
+    ))
+    .bind(merchant_id)
+    .bind(from)
+    .bind(to)
+    .bind(query.event_type.as_deref())
+    .bind(query.delivery_path.as_deref())
+    .fetch_all(&db)
+    .await
+    .map_err(internal_error("read rollups"))?;
+
+    // Keyed by unix time, which doesn't depend on the session's time zone
+    let latency = latency_by(
+        &db,
+        "EXTRACT(EPOCH FROM hour)::BIGINT",
+        merchant_id,
+        (from, to),
+        &query,
+    )
+    .await?;
+
+    let hours = rows
+        .into_iter()
+        .map(|(hour, attempts, succeeded, failed)| HourSummary {
+            hour,
+            totals: Totals::new(attempts, succeeded, failed),
+            p95_latency_ms: latency
+                .get(&hour.timestamp().to_string())
+                .and_then(|buckets| percentile(buckets, 0.95)),
+        })
+        .collect();
+
+    Ok(Json(HourlyResponse {
+        merchant_id,
+        from,
+        to,
+        hours,
+    }))
+}
+
+/// Busiest first, 'merchant' confirmations left out
+pub async fn list_merchants(
+    State(db): State<PgPool>,
+    Query(query): Query<MerchantsQuery>,
+) -> Result<Json<Vec<MerchantSummary>>, (StatusCode, String)> {
+    let (from, to) = window(query.from, query.to)?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_MERCHANTS)
+        .clamp(1, MAX_MERCHANTS);
+
+    let rows = sqlx::query_as::<_, (Uuid, i64, i64, i64)>(
+        r#"
+        SELECT merchant_id, SUM(attempts)::BIGINT, SUM(succeeded)::BIGINT, SUM(failed)::BIGINT
+        FROM delivery_rollups
+        WHERE hour >= $1 AND hour < $2 AND delivery_path <> 'merchant'
+        GROUP BY merchant_id
+        ORDER BY 2 DESC, merchant_id
+        LIMIT $3
+        "#,
+    )
+    .bind(from)
+    .bind(to)
+    .bind(limit)
+    .fetch_all(&db)
+    .await
+    .map_err(internal_error("read rollups"))?;
+
+    Ok(Json(
+        rows.into_iter()
+            .map(
+                |(merchant_id, attempts, succeeded, failed)| MerchantSummary {
+                    merchant_id,
+                    totals: Totals::new(attempts, succeeded, failed),
+                },
+            )
+            .collect(),
+    ))
+}
+
+#[derive(Serialize)]
+pub struct EndpointHealth {
+    endpoint_id: Uuid,
+    merchant_id: Uuid,
+    /// None once the endpoint is deleted
+    url: Option<String>,
+    disabled: bool,
+    score: Option<f64>,
+    /// Decayed attempts the ratios are over
+    attempts: f64,
+    success_rate: Option<f64>,
+    timeout_rate: Option<f64>,
+    avg_response_ms: Option<f64>,
+    updated_at: DateTime<Utc>,
+}
+
+type EndpointHealthRow = (
+    Uuid,
+    Uuid,
+    Option<String>,
+    Option<bool>,
+    Option<f64>,
+    f64,
+    f64,
+    f64,
+    f64,
+    f64,
+    DateTime<Utc>,
+);
+
+const ENDPOINT_HEALTH_SQL: &str = r#"
+    SELECT h.endpoint_id, h.merchant_id, e.url, e.disabled, h.score, h.attempts,
+           h.succeeded, h.timed_out, h.responses, h.response_ms_sum, h.updated_at
+    FROM endpoint_health h
+    LEFT JOIN merchant_endpoints e ON e.id = h.endpoint_id
+"#;
+
+impl From<EndpointHealthRow> for EndpointHealth {
+    fn from(row: EndpointHealthRow) -> Self {
+        let (
+            endpoint_id,
+            merchant_id,
+            url,
+            disabled,
+            score,
+            attempts,
+            succeeded,
+            timed_out,
+            responses,
+            response_ms_sum,
+            updated_at,
+        ) = row;
+        let ratio = |part: f64, whole: f64| (whole > 0.0).then(|| part / whole);
+        EndpointHealth {
+            endpoint_id,
+            merchant_id,
+            url,
+            disabled: disabled.unwrap_or(false),
+            score,
+            attempts,
+            success_rate: ratio(succeeded, attempts),
+            timeout_rate: ratio(timed_out, attempts),
+            avg_response_ms: ratio(response_ms_sum, responses),
+            updated_at,
+        }
+    }
+}
+
+/// Lowest score first; endpoints never delivered to directly have no row
+pub async fn merchant_endpoints(
+    State(db): State<PgPool>,
+    Path(merchant_id): Path<String>,
+) -> Result<Json<Vec<EndpointHealth>>, (StatusCode, String)> {
+    let merchant_id = resolve_merchant_id(&merchant_id);
+    let rows = sqlx::query_as::<_, EndpointHealthRow>(&format!(
+        "{} WHERE h.merchant_id = $1 ORDER BY h.score NULLS LAST, h.endpoint_id",
+        ENDPOINT_HEALTH_SQL
+    ))
+    .bind(merchant_id)
+    .fetch_all(&db)
+    .await
+    .map_err(internal_error("read endpoint health"))?;
+
+    Ok(Json(rows.into_iter().map(EndpointHealth::from).collect()))
+}
+
+pub async fn endpoint_health(
+    State(db): State<PgPool>,
+    Path(endpoint_id): Path<Uuid>,
+) -> Result<Json<EndpointHealth>, (StatusCode, String)> {
+    let row = sqlx::query_as::<_, EndpointHealthRow>(&format!(
+        "{} WHERE h.endpoint_id = $1",
+        ENDPOINT_HEALTH_SQL
+    ))
+    .bind(endpoint_id)
+    .fetch_optional(&db)
+    .await
+    .map_err(internal_error("read endpoint health"))?
+    .ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("No health recorded for endpoint {}", endpoint_id),
+        )
+    })?;
+
+    Ok(Json(row.into()))
+}
@@ -0,0 +1,224 @@
+use chrono::{DateTime, Utc};
+use rdkafka::consumer::{Consumer as _, StreamConsumer};
+use rdkafka::{ClientConfig, Message, Offset, TopicPartitionList};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::time::Duration;
+use webhook_types::DeliveryEvent;
+
+use crate::health;
+use crate::metrics::Metrics;
+use crate::rollup::{self, Pending};
+
+// ==============================================================================
+// CONSUMER: Delivery events -> rollups, with offsets kept next to the counts
+// ==============================================================================
+//
+// Kafka's committed offsets aren't used. Each flush stores the next offset of
+// every partition in delivery_analytics_offsets in the same transaction as
+// the counts it covers, and on start every partition is assigned from there
+// (or START_FROM, for one never seen). A crash loses nothing and counts
+// nothing twice.
+//
+// That makes this a single instance: partitions are assigned, not balanced
+// through a consumer group, and two instances would count everything twice.
+// Partitions added to the topic later are picked up on restart.
+//
+// A record that doesn't parse is counted in the metrics and skipped. While a
+// flush keeps failing, reading stops once MAX_PENDING_RECORDS are waiting.
+// After a flush that touched endpoint health, the unhealthy endpoints are
+// counted again for the metrics.
+
+/// Between attempts to find the topic's partitions on start
+const METADATA_RETRY: Duration = Duration::from_secs(5);
+
+const METADATA_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often rollup hours past RETENTION_DAYS are deleted
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub struct Consumer {
+    pub db: PgPool,
+    pub brokers: String,
+    pub topic: String,
+    pub start_from: Offset,
+    pub flush_interval: Duration,
+    pub max_pending_records: usize,
+    pub retention_days: u32,
+    pub health_half_life: Duration,
+    /// Endpoints scoring under this are unhealthy
+    pub unhealthy_score: f64,
+    pub metrics: Metrics,
+}
+
+impl Consumer {
+    pub async fn run(self) {
+        let consumer = self.assign().await;
+        let mut pending = Pending::default();
+        let mut flush_failed = false;
+        let mut flush = tokio::time::interval(self.flush_interval);
+        let mut prune = tokio::time::interval(PRUNE_INTERVAL);
+
+        loop {
+            let backed_up = flush_failed && pending.records() >= self.max_pending_records;
+            tokio::select! {
+                message = consumer.recv(), if !backed_up => match message {
+                    Ok(message) => {
+                        let at = message
+                            .timestamp()
+                            .to_millis()
+                            .and_then(DateTime::<Utc>::from_timestamp_millis)
+                            .unwrap_or_else(Utc::now);
+                        let counted = serde_json::from_slice::<DeliveryEvent>(
+                            message.payload().unwrap_or_default(),
+                        )
+                        .map_err(|e| e.to_string())
+                        .and_then(|event| pending.add(&event, at));
+                        match counted {
+                            Ok(()) => self.metrics.record("counted"),
+                            Err(e) => {
+                                self.metrics.record("malformed");
+                                tracing::warn!(
+                                    "Skipping {}/{}@{}: {}",
+                                    self.topic,
+                                    message.partition(),
+                                    message.offset(),
+                                    e
+                                );
+                            }
+                        }
+                        pending.advance(message.partition(), message.offset());
+                        if !flush_failed && pending.records() >= self.max_pending_records {
+                            flush_failed = !self.flush(&mut pending).await;
+                        }
+                    }
+                    Err(e) => {
+                        self.metrics.consumer_error();
+                        tracing::warn!("Failed to read from {}: {}", self.topic, e);
+                    }
+                },
+                _ = flush.tick() => {
+                    flush_failed = !self.flush(&mut pending).await;
+                }
+                _ = prune.tick(), if self.retention_days > 0 => {
+                    match rollup::prune(&self.db, self.retention_days).await {
+                        Ok(0) => {}
+                        Ok(deleted) => tracing::info!(
+                            "Deleted {} rollup rows older than {} days",
+                            deleted,
+                            self.retention_days
+                        ),
+                        Err(e) => tracing::warn!("Failed to prune rollups: {}", e),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Write what's pending; false if it stays pending
+    async fn flush(&self, pending: &mut Pending) -> bool {
+        if pending.is_empty() {
+            return true;
+        }
+        match pending
+            .flush(&self.db, &self.topic, self.health_half_life)
+            .await
+        {
+            Ok(()) => {
+                tracing::debug!("Flushed {} records", pending.records());
+                let scored = pending.scored_endpoints();
+                *pending = Pending::default();
+                self.metrics.flushed(true);
+                if scored {
+                    match health::unhealthy_endpoints(&self.db, self.unhealthy_score).await {
+                        Ok(count) => self.metrics.unhealthy_endpoints(count),
+                        Err(e) => tracing::warn!("Failed to count unhealthy endpoints: {}", e),
+                    }
+                }
+                true
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to flush {} records, keeping them: {}",
+                    pending.records(),
+                    e
+                );
+                self.metrics.flushed(false);
+                false
+            }
+        }
+    }
+
+    /// Assign every partition of the topic from its stored offset, retrying
+    /// until Kafka and Postgres both answer
+    async fn assign(&self) -> StreamConsumer {
+        loop {
+            match self.try_assign().await {
+                Ok(consumer) => return consumer,
+                Err(e) => {
+                    self.metrics.consumer_error();
+                    tracing::warn!("{}, retrying in {:?}", e, METADATA_RETRY);
+                    tokio::time::sleep(METADATA_RETRY).await;
+                }
+            }
+        }
+    }
+
+    async fn try_assign(&self) -> Result<StreamConsumer, String> {
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", &self.brokers)
+            .set("group.id", "delivery-analytics")
+            .set("enable.auto.commit", "false")
+            .set("enable.auto.offset.store", "false")
+            .create()
+            .map_err(|e| format!("Invalid Kafka configuration: {}", e))?;
+
+        let metadata = tokio::task::block_in_place(|| {
+            consumer.fetch_metadata(Some(&self.topic), METADATA_TIMEOUT)
+        })
+        .map_err(|e| format!("Failed to fetch metadata for {}: {}", self.topic, e))?;
+        let partitions: Vec<i32> = metadata
+            .topics()
+            .iter()
+            .filter(|topic| topic.name() == self.topic)
+            .flat_map(|topic| topic.partitions().iter().map(|partition| partition.id()))
+            .collect();
+        if partitions.is_empty() {
+            return Err(format!("Topic {} has no partitions yet", self.topic));
+        }
+
+        let stored: HashMap<i32, i64> = sqlx::query_as::<_, (i32, i64)>(
+            "SELECT partition, next_offset FROM delivery_analytics_offsets WHERE topic = $1",
+        )
+        .bind(&self.topic)
+        .fetch_all(&self.db)
+        .await
+        .map_err(|e| format!("Failed to read stored offsets: {}", e))?
+        .into_iter()
+        .collect();
+
+        let mut assignment = TopicPartitionList::new();
+        for partition in &partitions {
+            let offset = stored
+                .get(partition)
+                .map_or(self.start_from, |next| Offset::Offset(*next));
+            assignment
+                .add_partition_offset(&self.topic, *partition, offset)
+                .map_err(|e| format!("Failed to assign partition {}: {}", partition, e))?;
+        }
+        consumer
+            .assign(&assignment)
+            .map_err(|e| format!("Failed to assign {}: {}", self.topic, e))?;
+
+        tracing::info!(
+            "Reading {} partitions of {} ({} with stored offsets)",
+            partitions.len(),
+            self.topic,
+            partitions
+                .iter()
+                .filter(|partition| stored.contains_key(partition))
+                .count()
+        );
+        Ok(consumer)
+    }
+}
@@ -0,0 +1,184 @@
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use std::time::Duration;
+use uuid::Uuid;
+use webhook_types::DeliveryEvent;
+
+// ==============================================================================
+// HEALTH: A rolling score per merchant endpoint
+// ==============================================================================
+//
+// svix-caller publishes each endpoint's part in a direct delivery as a
+// 'direct_endpoint' delivery event (endpoint_id set, with how long the
+// endpoint took to answer and whether it timed out). Those are counted here
+// instead of in the rollups, into endpoint_health (init.sql): attempts,
+// successes, timeouts and response time, each decayed by half every
+// HEALTH_HALF_LIFE_SECS so recent deliveries weigh the most. From them:
+//
+//   score = 100 * (0.60 * success ratio
+//                + 0.25 * (1 - timeout rate)
+//                + 0.15 * latency factor)
+//
+// where the latency factor is 1 for an average response at or under FAST_MS,
+// 0 at SLOW_MS and over (or with no response at all), linear in between.
+// The score only moves when the endpoint is delivered to; it doesn't recover
+// by itself while nothing is sent.
+//
+// svix-caller reads the scores to space out its retries (direct.rs), the API
+// serves them (api.rs), and delivery_analytics_unhealthy_endpoints counts
+// enabled endpoints under UNHEALTHY_SCORE, for alerting.
+
+/// Average response time that still counts as fully fast
+const FAST_MS: f64 = 500.0;
+
+/// Average response time that counts as not answering
+const SLOW_MS: f64 = 5_000.0;
+
+/// An endpoint's attempts since the last flush
+struct Counts {
+    merchant_id: Uuid,
+    attempts: f64,
+    succeeded: f64,
+    timed_out: f64,
+    /// Attempts the endpoint answered, and their total response time
+    responses: f64,
+    response_ms_sum: f64,
+}
+
+#[derive(Default)]
+pub struct Observed {
+    endpoints: HashMap<Uuid, Counts>,
+}
+
+impl Observed {
+    pub fn is_empty(&self) -> bool {
+        self.endpoints.is_empty()
+    }
+
+    pub fn add(
+        &mut self,
+        endpoint_id: &str,
+        merchant_id: Uuid,
+        event: &DeliveryEvent,
+    ) -> Result<(), String> {
+        let endpoint_id = Uuid::parse_str(endpoint_id)
+            .map_err(|e| format!("Invalid endpoint_id {}: {}", endpoint_id, e))?;
+        let counts = self.endpoints.entry(endpoint_id).or_insert(Counts {
+            merchant_id,
+            attempts: 0.0,
+            succeeded: 0.0,
+            timed_out: 0.0,
+            responses: 0.0,
+            response_ms_sum: 0.0,
+        });
+        counts.attempts += 1.0;
+        if event.status == "succeeded" {
+            counts.succeeded += 1.0;
+        }
+        if event.timed_out {
+            counts.timed_out += 1.0;
+        }
+        if let Some(response_ms) = event.response_ms {
+            counts.responses += 1.0;
+            counts.response_ms_sum += response_ms as f64;
+        }
+        Ok(())
+    }
+
+    /// Decay the stored counters, add these and score the endpoints again,
+    /// in the flush's transaction
+    pub async fn write(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        half_life: Duration,
+    ) -> Result<(), sqlx::Error> {
+        if self.endpoints.is_empty() {
+            return Ok(());
+        }
+        let mut endpoint_ids = Vec::new();
+        let mut merchant_ids = Vec::new();
+        let (mut attempts, mut succeeded, mut timed_out) = (Vec::new(), Vec::new(), Vec::new());
+        let (mut responses, mut response_ms_sums) = (Vec::new(), Vec::new());
+        for (endpoint_id, counts) in &self.endpoints {
+            endpoint_ids.push(*endpoint_id);
+            merchant_ids.push(counts.merchant_id);
+            attempts.push(counts.attempts);
+            succeeded.push(counts.succeeded);
+            timed_out.push(counts.timed_out);
+            responses.push(counts.responses);
+            response_ms_sums.push(counts.response_ms_sum);
+        }
+
+        let decayed = |column: &str| {
+            format!(
+                "{column} = endpoint_health.{column} * POWER(0.5::FLOAT8, \
+                 EXTRACT(EPOCH FROM NOW() - endpoint_health.updated_at)::FLOAT8 / $8) \
+                 + EXCLUDED.{column}"
+            )
+        };
+        sqlx::query(&format!(
+            r#"
+            INSERT INTO endpoint_health
+                (endpoint_id, merchant_id, attempts, succeeded, timed_out, responses, response_ms_sum)
+            SELECT * FROM UNNEST($1::UUID[], $2::UUID[], $3::FLOAT8[], $4::FLOAT8[],
+                                 $5::FLOAT8[], $6::FLOAT8[], $7::FLOAT8[])
+            ON CONFLICT (endpoint_id) DO UPDATE
+            SET merchant_id = EXCLUDED.merchant_id,
+                {}, {}, {}, {}, {},
+                updated_at = NOW()
+            "#,
+            decayed("attempts"),
+            decayed("succeeded"),
+            decayed("timed_out"),
+            decayed("responses"),
+            decayed("response_ms_sum"),
+        ))
+        .bind(&endpoint_ids)
+        .bind(&merchant_ids)
+        .bind(&attempts)
+        .bind(&succeeded)
+        .bind(&timed_out)
+        .bind(&responses)
+        .bind(&response_ms_sums)
+        .bind(half_life.as_secs_f64())
+        .execute(&mut **tx)
+        .await?;
+
+        sqlx::query(&format!(
+            "UPDATE endpoint_health SET score = {} WHERE endpoint_id = ANY($1) AND attempts > 0",
+            score_sql()
+        ))
+        .bind(&endpoint_ids)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+}
+
+/// The score from the (decayed) counters of an endpoint_health row
+fn score_sql() -> String {
+    format!(
+        r#"
+        100 * (0.60 * succeeded / attempts
+             + 0.25 * (1 - timed_out / attempts)
+             + 0.15 * COALESCE(1 - LEAST(GREATEST(response_ms_sum / NULLIF(responses, 0) - {fast}, 0)
+                                         / {range}, 1), 0))
+        "#,
+        fast = FAST_MS,
+        range = SLOW_MS - FAST_MS,
+    )
+}
+
+/// Enabled endpoints scoring under `below`
+pub async fn unhealthy_endpoints(db: &PgPool, below: f64) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM endpoint_health h
+        JOIN merchant_endpoints e ON e.id = h.endpoint_id
+        WHERE NOT e.disabled AND h.score < $1
+        "#,
+    )
+    .bind(below)
+    .fetch_one(db)
+    .await
+}
@@ -0,0 +1,119 @@
+use axum::{middleware, routing::get, Router};
+use health_checks::{checks, HealthRegistry};
+use rdkafka::Offset;
+use service_metrics::ServiceMetrics;
+use sqlx::postgres::PgPoolOptions;
+use std::time::Duration;
+use tracing::info;
+
+mod api;
+mod consumer;
+mod health;
+mod metrics;
+mod rollup;
+mod settings;
+
+use consumer::Consumer;
+use metrics::Metrics;
+use settings::Settings;
+
+// ==============================================================================
+// DELIVERY ANALYTICS: Per-merchant delivery rollups from the delivery events
+// ==============================================================================
+//
+// Reads the DeliveryEvents svix-caller publishes for every delivery_attempts
+// row (KAFKA_DELIVERY_EVENTS_TOPIC, which has to be set there) and keeps
+// hourly rollups per merchant, event type and delivery path: attempts,
+// successes and failures, a latency histogram and failures by the endpoint's
+// status code (rollup.rs, consumer.rs). The API over them (api.rs) answers
+// "how are my deliveries doing" without scanning delivery_attempts.
+//
+// The per-endpoint events of direct deliveries keep a rolling health score
+// per endpoint instead (health.rs), which svix-caller spaces its retries by.
+//
+// The API, health and metrics are on PORT (default 3008).
+
+/// A missing one means init.sql hasn't been applied to this database yet
+const REQUIRED_TABLES: &[&str] = &[
+    "delivery_rollups",
+    "delivery_rollup_latency",
+    "delivery_rollup_status_codes",
+    "delivery_analytics_offsets",
+    "endpoint_health",
+];
+
+#[tokio::main]
+async fn main() {
+    logging::init(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    let settings: Settings = config::load_or_exit("delivery-analytics");
+    // Validated to be set
+    let database_url = settings.database_url.clone().unwrap_or_default();
+
+    let db = PgPoolOptions::new()
+        .max_connections(10)
+        .connect(&database_url)
+        .await
+        .expect("Failed to connect to database");
+
+    let metrics = Metrics::new(ServiceMetrics::new(
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+    ));
+
+    let consumer = Consumer {
+        db: db.clone(),
+        brokers: settings.kafka_brokers.clone(),
+        topic: settings.topic.clone(),
+        start_from: match settings.start_from.as_str() {
+            "latest" => Offset::End,
+            _ => Offset::Beginning,
+        },
+        flush_interval: Duration::from_millis(settings.flush_interval_ms),
+        max_pending_records: settings.max_pending_records,
+        retention_days: settings.retention_days,
+        health_half_life: Duration::from_secs(settings.health_half_life_secs),
+        unhealthy_score: settings.unhealthy_score,
+        metrics: metrics.clone(),
+    };
+    info!(
+        "Rolling up {} (new partitions from {}, flushing every {}ms)",
+        settings.topic, settings.start_from, settings.flush_interval_ms
+    );
+    tokio::spawn(consumer.run());
+
+    let brokers: Vec<String> = settings
+        .kafka_brokers
+        .split(',')
+        .map(|broker| broker.trim().to_string())
+        .collect();
+    let (database, migrations) = (db.clone(), db.clone());
+    let health = HealthRegistry::new()
+        .readiness("database", move || checks::postgres(database.clone()))
+        .readiness("kafka", move || checks::tcp(brokers.clone()))
+        .readiness("migrations", move || {
+            checks::tables(migrations.clone(), REQUIRED_TABLES)
+        });
+
+    let app = Router::new()
+        .route("/merchants", get(api::list_merchants))
+        .route("/merchants/:id/summary", get(api::merchant_summary))
+        .route("/merchants/:id/hourly", get(api::merchant_hourly))
+        .route("/merchants/:id/endpoints", get(api::merchant_endpoints))
+        .route("/endpoints/:id/health", get(api::endpoint_health))
+        .merge(health_checks::router(health))
+        .merge(service_metrics::router(metrics.service().clone()))
+        .route_layer(middleware::from_fn_with_state(
+            metrics.service().clone(),
+            service_metrics::track_http,
+        ))
+        .with_state(db);
+
+    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", settings.port))
+        .await
+        .unwrap();
+
+    info!("DELIVERY ANALYTICS listening on port {}", settings.port);
+
+    axum::serve(listener, app).await.unwrap();
+}
@@ -0,0 +1,112 @@
+use prometheus::{Gauge, IntCounter, IntCounterVec, IntGauge, Opts};
+use service_metrics::ServiceMetrics;
+
+// ==============================================================================
+// METRICS: What was counted and whether it reached Postgres
+// ==============================================================================
+//
+// Next to the process and HTTP metrics every service has (service-metrics
+// crate):
+//
+//   delivery_analytics_records_total{outcome}   counted, or malformed and skipped
+//   delivery_analytics_consumer_errors_total    failed reads from Kafka
+//   delivery_analytics_flushes_total{result}    ok or failed
+//   delivery_analytics_last_flush_seconds       unix time of the last good flush
+//   delivery_analytics_unhealthy_endpoints      enabled endpoints scoring under
+//                                               UNHEALTHY_SCORE (health.rs)
+//
+// A last flush that falls behind means the rollups are going stale, whether
+// Postgres is refusing writes or nothing is being published. Unhealthy
+// endpoints are what to alert merchants (or on-call) about.
+
+#[derive(Clone)]
+pub struct Metrics {
+    service: ServiceMetrics,
+    records: IntCounterVec,
+    flushes: IntCounterVec,
+    consumer_errors: IntCounter,
+    last_flush: Gauge,
+    unhealthy_endpoints: IntGauge,
+}
+
+impl Metrics {
+    pub fn new(service: ServiceMetrics) -> Self {
+        let registry = service.registry();
+
+        let records = IntCounterVec::new(
+            Opts::new(
+                "delivery_analytics_records_total",
+                "Delivery events read from Kafka, by outcome",
+            ),
+            &["outcome"],
+        )
+        .unwrap();
+        let flushes = IntCounterVec::new(
+            Opts::new(
+                "delivery_analytics_flushes_total",
+                "Writes of counted records to Postgres, by result",
+            ),
+            &["result"],
+        )
+        .unwrap();
+        let consumer_errors = IntCounter::with_opts(Opts::new(
+            "delivery_analytics_consumer_errors_total",
+            "Errors reading from Kafka",
+        ))
+        .unwrap();
+        let last_flush = Gauge::with_opts(Opts::new(
+            "delivery_analytics_last_flush_seconds",
+            "Unix time of the last successful flush",
+        ))
+        .unwrap();
+        let unhealthy_endpoints = IntGauge::with_opts(Opts::new(
+            "delivery_analytics_unhealthy_endpoints",
+            "Enabled endpoints with a health score under UNHEALTHY_SCORE",
+        ))
+        .unwrap();
+
+        registry.register(Box::new(records.clone())).unwrap();
+        registry.register(Box::new(flushes.clone())).unwrap();
+        registry
+            .register(Box::new(consumer_errors.clone()))
+            .unwrap();
+        registry.register(Box::new(last_flush.clone())).unwrap();
+        registry
+            .register(Box::new(unhealthy_endpoints.clone()))
+            .unwrap();
+
+        Metrics {
+            service,
+            records,
+            flushes,
+            consumer_errors,
+            last_flush,
+            unhealthy_endpoints,
+        }
+    }
+
+    pub fn service(&self) -> &ServiceMetrics {
+        &self.service
+    }
+
+    pub fn record(&self, outcome: &str) {
+        self.records.with_label_values(&[outcome]).inc();
+    }
+
+    pub fn consumer_error(&self) {
+        self.consumer_errors.inc();
+    }
+
+    pub fn flushed(&self, ok: bool) {
+        self.flushes
+            .with_label_values(&[if ok { "ok" } else { "failed" }])
+            .inc();
+        if ok {
+            self.last_flush.set(chrono::Utc::now().timestamp() as f64);
+        }
+    }
+
+    pub fn unhealthy_endpoints(&self, count: i64) {
+        self.unhealthy_endpoints.set(count);
+    }
+}
@@ -0,0 +1,313 @@
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use uuid::Uuid;
+use webhook_types::DeliveryEvent;
+
+use crate::health::Observed;
+
+// ==============================================================================
+// ROLLUPS: Delivery events counted per merchant, hour, event type and path
+// ==============================================================================
+//
+// Records are counted in memory and added to the rollup tables (init.sql) in
+// one transaction per flush, together with the next offset of every partition
+// they came from. Counts are sums, so a flush that fails is simply retried
+// with whatever arrived in the meantime added on.
+//
+// An attempt's hour is when it was published (the Kafka record's timestamp).
+// Its latency, measured by svix-caller from the event's creation, goes into
+// the histogram bucket LATENCY_BOUNDS_MS says; the bounds may only be
+// appended to, since stored counts are keyed by them.
+//
+// Per-endpoint events ('direct_endpoint') go to endpoint health (health.rs)
+// in the same transaction instead: the delivery they're part of already has
+// its own 'direct' attempt here.
+
+/// Upper bounds of the latency buckets, in ms
+pub const LATENCY_BOUNDS_MS: &[i64] = &[
+    50,
+    100,
+    250,
+    500,
+    1_000,
+    2_500,
+    5_000,
+    10_000,
+    30_000,
+    60_000,
+    300_000,
+    900_000,
+    3_600_000,
+    21_600_000,
+    86_400_000,
+    i64::MAX,
+];
+
+/// What a status means for the success rate. Skipped and dry-run attempts
+/// are counted as attempts but neither succeed nor fail.
+fn outcome(status: &str) -> Option<bool> {
+    match status {
+        "succeeded" | "confirmed" => Some(true),
+        "failed" | "failing" | "exhausted" | "rate_limited" | "dead_lettered" | "unconfirmed" => {
+            Some(false)
+        }
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct RollupKey {
+    merchant_id: Uuid,
+    hour: DateTime<Utc>,
+    event_type: String,
+    delivery_path: String,
+}
+
+#[derive(Default)]
+struct Counts {
+    attempts: i64,
+    succeeded: i64,
+    failed: i64,
+    /// Bucket upper bound -> attempts
+    latency: BTreeMap<i64, i64>,
+    /// Failed attempts by response status
+    status_codes: BTreeMap<i32, i64>,
+}
+
+/// Counted since the last flush
+#[derive(Default)]
+pub struct Pending {
+    rollups: HashMap<RollupKey, Counts>,
+    health: Observed,
+    /// Partition -> the offset to resume from
+    offsets: BTreeMap<i32, i64>,
+    records: usize,
+}
+
+impl Pending {
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    /// Whether a flush will change endpoint health
+    pub fn scored_endpoints(&self) -> bool {
+        !self.health.is_empty()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rollups.is_empty() && self.health.is_empty() && self.offsets.is_empty()
+    }
+
+    /// Count a record. Err for one that can't be attributed to a merchant; it
+    /// is skipped, but its offset still advances.
+    pub fn add(&mut self, event: &DeliveryEvent, at: DateTime<Utc>) -> Result<(), String> {
+        let merchant_id = Uuid::parse_str(&event.merchant_id)
+            .map_err(|e| format!("Invalid merchant_id {}: {}", event.merchant_id, e))?;
+        if let Some(endpoint_id) = &event.endpoint_id {
+            self.health.add(endpoint_id, merchant_id, event)?;
+            self.records += 1;
+            return Ok(());
+        }
+        let hour = at
+            .duration_trunc(TimeDelta::hours(1))
+            .map_err(|e| format!("Invalid timestamp {}: {}", at, e))?;
+
+        let counts = self
+            .rollups
+            .entry(RollupKey {
+                merchant_id,
+                hour,
+                event_type: event.event_type.clone(),
+                delivery_path: event.delivery_path.clone(),
+            })
+            .or_default();
+        counts.attempts += 1;
+        match outcome(&event.status) {
+            Some(true) => counts.succeeded += 1,
+            Some(false) => {
+                counts.failed += 1;
+                if let Some(code) = event.response_status {
+                    *counts.status_codes.entry(i32::from(code)).or_default() += 1;
+                }
+            }
+            None => {}
+        }
+        if let Some(latency_ms) = event.latency_ms {
+            let latency_ms = i64::try_from(latency_ms).unwrap_or(i64::MAX);
+            let bound = LATENCY_BOUNDS_MS
+                .iter()
+                .find(|bound| latency_ms <= **bound)
+                .copied()
+                .unwrap_or(i64::MAX);
+            *counts.latency.entry(bound).or_default() += 1;
+        }
+        self.records += 1;
+        Ok(())
+    }
+
+    /// The record at `offset` has been dealt with
+    pub fn advance(&mut self, partition: i32, offset: i64) {
+        let next = self.offsets.entry(partition).or_default();
+        *next = (*next).max(offset + 1);
+    }
+
+    /// Add everything to the rollup tables and endpoint health and store the
+    /// offsets, atomically
+    pub async fn flush(
+        &self,
+        db: &PgPool,
+        topic: &str,
+        health_half_life: Duration,
+    ) -> Result<(), sqlx::Error> {
+        let mut merchant_ids = Vec::new();
+        let mut hours = Vec::new();
+        let mut event_types = Vec::new();
+        let mut paths = Vec::new();
+        let (mut attempts, mut succeeded, mut failed) = (Vec::new(), Vec::new(), Vec::new());
+        let mut latency = BucketRows::default();
+        let mut status_codes = BucketRows::default();
+
+        for (key, counts) in &self.rollups {
+            merchant_ids.push(key.merchant_id);
+            hours.push(key.hour);
+            event_types.push(key.event_type.clone());
+            paths.push(key.delivery_path.clone());
+            attempts.push(counts.attempts);
+            succeeded.push(counts.succeeded);
+            failed.push(counts.failed);
+            for (le_ms, count) in &counts.latency {
+                latency.push(key, *le_ms, *count);
+            }
+            for (status_code, count) in &counts.status_codes {
+                status_codes.push(key, i64::from(*status_code), *count);
+            }
+        }
+
+        let mut tx = db.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO delivery_rollups
+                (merchant_id, hour, event_type, delivery_path, attempts, succeeded, failed)
+            SELECT * FROM UNNEST($1::UUID[], $2::TIMESTAMPTZ[], $3::TEXT[], $4::TEXT[],
+                                 $5::BIGINT[], $6::BIGINT[], $7::BIGINT[])
+            ON CONFLICT (merchant_id, hour, event_type, delivery_path) DO UPDATE
+            SET attempts = delivery_rollups.attempts + EXCLUDED.attempts,
+                succeeded = delivery_rollups.succeeded + EXCLUDED.succeeded,
+                failed = delivery_rollups.failed + EXCLUDED.failed
+            "#,
+        )
+        .bind(&merchant_ids)
+        .bind(&hours)
+        .bind(&event_types)
+        .bind(&paths)
+        .bind(&attempts)
+        .bind(&succeeded)
+        .bind(&failed)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO delivery_rollup_latency
+                (merchant_id, hour, event_type, delivery_path, le_ms, count)
+            SELECT * FROM UNNEST($1::UUID[], $2::TIMESTAMPTZ[], $3::TEXT[], $4::TEXT[],
+                                 $5::BIGINT[], $6::BIGINT[])
+            ON CONFLICT (merchant_id, hour, event_type, delivery_path, le_ms) DO UPDATE
+            SET count = delivery_rollup_latency.count + EXCLUDED.count
+            "#,
+        )
+        .bind(&latency.merchant_ids)
+        .bind(&latency.hours)
+        .bind(&latency.event_types)
+        .bind(&latency.paths)
+        .bind(&latency.buckets)
+        .bind(&latency.counts)
+        .execute(&mut *tx)
+        .await?;
+
+        let status_code_buckets: Vec<i32> = status_codes
+            .buckets
+            .iter()
+            .map(|code| *code as i32)
+            .collect();
+        sqlx::query(
+            r#"
+            INSERT INTO delivery_rollup_status_codes
+                (merchant_id, hour, event_type, delivery_path, status_code, count)
+            SELECT * FROM UNNEST($1::UUID[], $2::TIMESTAMPTZ[], $3::TEXT[], $4::TEXT[],
+                                 $5::INT[], $6::BIGINT[])
+            ON CONFLICT (merchant_id, hour, event_type, delivery_path, status_code) DO UPDATE
+            SET count = delivery_rollup_status_codes.count + EXCLUDED.count
+            "#,
+        )
+        .bind(&status_codes.merchant_ids)
+        .bind(&status_codes.hours)
+        .bind(&status_codes.event_types)
+        .bind(&status_codes.paths)
+        .bind(&status_code_buckets)
+        .bind(&status_codes.counts)
+        .execute(&mut *tx)
+        .await?;
+
+        self.health.write(&mut tx, health_half_life).await?;
+
+        let (partitions, offsets): (Vec<i32>, Vec<i64>) = self.offsets.iter().unzip();
+        sqlx::query(
+            r#"
+            INSERT INTO delivery_analytics_offsets (topic, partition, next_offset)
+            SELECT $1, * FROM UNNEST($2::INT[], $3::BIGINT[])
+            ON CONFLICT (topic, partition) DO UPDATE
+            SET next_offset = EXCLUDED.next_offset, updated_at = NOW()
+            "#,
+        )
+        .bind(topic)
+        .bind(&partitions)
+        .bind(&offsets)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await
+    }
+}
+
+/// Rows of one of the per-bucket tables, column by column for UNNEST
+#[derive(Default)]
+struct BucketRows {
+    merchant_ids: Vec<Uuid>,
+    hours: Vec<DateTime<Utc>>,
+    event_types: Vec<String>,
+    paths: Vec<String>,
+    buckets: Vec<i64>,
+    counts: Vec<i64>,
+}
+
+impl BucketRows {
+    fn push(&mut self, key: &RollupKey, bucket: i64, count: i64) {
+        self.merchant_ids.push(key.merchant_id);
+        self.hours.push(key.hour);
+        self.event_types.push(key.event_type.clone());
+        self.paths.push(key.delivery_path.clone());
+        self.buckets.push(bucket);
+        self.counts.push(count);
+    }
+}
+
+/// Delete rollup hours older than `retention_days`
+pub async fn prune(db: &PgPool, retention_days: u32) -> Result<u64, sqlx::Error> {
+    let cutoff = Utc::now() - TimeDelta::days(i64::from(retention_days));
+    let mut deleted = 0;
+    for table in [
+        "delivery_rollups",
+        "delivery_rollup_latency",
+        "delivery_rollup_status_codes",
+    ] {
+        deleted += sqlx::query(&format!("DELETE FROM {} WHERE hour < $1", table))
+            .bind(cutoff)
+            .execute(db)
+            .await?
+            .rows_affected();
+    }
+    Ok(deleted)
+}
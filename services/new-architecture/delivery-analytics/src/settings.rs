@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+// ==============================================================================
+// SETTINGS: Which topic to roll up, how often rollups are written and how
+// endpoints are scored
+// ==============================================================================
+//
+// Loaded through the shared config crate: defaults below, then
+// config/delivery-analytics.toml (or CONFIG_FILE), then the environment
+// variable of the same name in upper case.
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// The analytics API, /livez, /readyz, /startupz and /metrics
+    pub port: u16,
+    /// Required
+    pub database_url: Option<String>,
+    pub kafka_brokers: String,
+    /// What svix-caller publishes with KAFKA_DELIVERY_EVENTS_TOPIC
+    pub topic: String,
+    /// Where a partition without a stored offset starts: earliest or latest
+    pub start_from: String,
+    /// How often counted records are written to Postgres
+    pub flush_interval_ms: u64,
+    /// Records counted before a flush regardless of the interval
+    pub max_pending_records: usize,
+    /// Rollup hours older than this are deleted; 0 keeps them forever
+    pub retention_days: u32,
+    /// How fast old deliveries stop counting towards an endpoint's health:
+    /// their weight halves every this many seconds
+    pub health_half_life_secs: u64,
+    /// Endpoints scoring under this (0-100) count as unhealthy
+    pub unhealthy_score: f64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            port: 3008,
+            database_url: None,
+            kafka_brokers: "kafka:9092".to_string(),
+            topic: "webhook-delivery-events".to_string(),
+            start_from: "earliest".to_string(),
+            flush_interval_ms: 5000,
+            max_pending_records: 10_000,
+            retention_days: 90,
+            health_half_life_secs: 3600,
+            unhealthy_score: 50.0,
+        }
+    }
+}
+
+impl config::Validate for Settings {
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.database_url.is_none() {
+            problems.push("DATABASE_URL: required".to_string());
+        }
+        if self.kafka_brokers.is_empty() {
+            problems.push("KAFKA_BROKERS: required".to_string());
+        }
+        if self.topic.is_empty() {
+            problems.push("TOPIC: required".to_string());
+        }
+        if !matches!(self.start_from.as_str(), "earliest" | "latest") {
+            problems.push(format!(
+                "START_FROM: {:?} is neither earliest nor latest",
+                self.start_from
+            ));
+        }
+        if self.flush_interval_ms == 0 {
+            problems.push("FLUSH_INTERVAL_MS: must be at least 1".to_string());
+        }
+        if self.max_pending_records == 0 {
+            problems.push("MAX_PENDING_RECORDS: must be at least 1".to_string());
+        }
+        if self.health_half_life_secs == 0 {
+            problems.push("HEALTH_HALF_LIFE_SECS: must be at least 1".to_string());
+        }
+        if !(0.0..=100.0).contains(&self.unhealthy_score) {
+            problems.push(format!(
+                "UNHEALTHY_SCORE: {} is outside 0-100",
+                self.unhealthy_score
+            ));
+        }
+        problems
+    }
+}
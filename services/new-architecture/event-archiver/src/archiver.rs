@@ -0,0 +1,315 @@
+use chrono::{NaiveDate, TimeDelta, Utc};
+use event_transport::aws::AwsClient;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sqlx::postgres::PgConnection;
+use sqlx::{Connection, PgPool};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::time::Duration;
+
+use crate::metrics::Metrics;
+
+// ==============================================================================
+// ARCHIVER: Delivered events past ARCHIVE_AFTER_DAYS -> object storage
+// ==============================================================================
+//
+// Every INTERVAL_SECS, under an advisory lock (one instance archives, the
+// rest skip the run):
+//
+//   1. Read up to BATCH_SIZE domain_events rows older than the cutoff that
+//      have a succeeded (or, on the merchant path, confirmed) attempt, oldest
+//      first, each with its delivery attempts and payload snapshot as one
+//      JSON line.
+//   2. Write them as gzipped JSONL, one object per UTC day of created_at:
+//        <S3_PREFIX>/events/dt=YYYY-MM-DD/<first id>-<last id>.jsonl.gz
+//   3. Once every object is stored, delete the rows from delivery_attempts,
+//      payload_snapshots, endpoint_deliveries, digest_items and domain_events
+//      and list the objects in archived_objects, in one transaction.
+//   4. Repeat until a batch comes back short.
+//
+// Events that never got delivered stay behind (dead letters, exhausted
+// retries: someone may still act on them), and so does an event a replay
+// still points at (replay_of), until the replay itself is archived.
+//
+// Rows are only deleted after their objects are stored, so nothing is lost;
+// a failure between the two uploads them again on the next run. Keys come
+// from the ids, so the same batch overwrites its objects, but a batch cut
+// differently can put an event in two objects: readers dedupe on id.
+//
+// JSONL rather than Parquet: every query engine that reads one reads the
+// other, and gzipped lines need nothing but flate2 to write.
+
+/// pg_advisory_lock key; any constant works, as long as every archiver uses it
+const ARCHIVER_LOCK_KEY: i64 = 0x61726368697665; // "archive"
+
+#[derive(sqlx::FromRow)]
+struct ArchiveRow {
+    id: i64,
+    merchant_id: String,
+    /// UTC day of created_at
+    day: NaiveDate,
+    attempts: i64,
+    /// The event, its attempts and its snapshot as one JSON object
+    line: String,
+}
+
+/// Rows of one day of a batch
+struct Partition {
+    ids: Vec<i64>,
+    merchant_ids: BTreeSet<String>,
+    attempts: i64,
+    body: GzEncoder<Vec<u8>>,
+}
+
+/// An uploaded object, as listed in archived_objects
+struct ArchivedObject {
+    key: String,
+    day: NaiveDate,
+    first_event_id: i64,
+    last_event_id: i64,
+    events: i32,
+    attempts: i32,
+    bytes: i64,
+    merchant_ids: Vec<String>,
+}
+
+pub struct Archiver {
+    pub db: PgPool,
+    pub s3: AwsClient,
+    pub bucket: String,
+    pub prefix: String,
+    pub archive_after_days: u32,
+    pub batch_size: i64,
+    pub interval: Duration,
+    pub metrics: Metrics,
+}
+
+impl Archiver {
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            interval.tick().await;
+            match self.archive().await {
+                Ok(Some(0)) => self.metrics.run("idle"),
+                Ok(Some(events)) => {
+                    self.metrics.run("archived");
+                    tracing::info!("Archived {} events", events);
+                }
+                Ok(None) => {
+                    self.metrics.run("skipped");
+                    tracing::debug!("Another archiver holds the lock, skipping this run");
+                }
+                Err(e) => {
+                    self.metrics.run("failed");
+                    tracing::warn!("Archiving failed, retrying next run: {}", e);
+                }
+            }
+        }
+    }
+
+    /// One run: the events archived, or None if another instance holds the
+    /// lock
+    async fn archive(&self) -> Result<Option<usize>, String> {
+        let mut conn = self
+            .db
+            .acquire()
+            .await
+            .map_err(|e| format!("Failed to connect: {}", e))?
+            .detach();
+        let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+            .bind(ARCHIVER_LOCK_KEY)
+            .fetch_one(&mut conn)
+            .await
+            .map_err(|e| format!("Failed to take the archiver lock: {}", e))?;
+        if !locked {
+            let _ = conn.close().await;
+            return Ok(None);
+        }
+
+        let mut archived = 0;
+        let result = loop {
+            match self.batch(&mut conn).await {
+                Ok(events) => {
+                    archived += events;
+                    if (events as i64) < self.batch_size {
+                        break Ok(Some(archived));
+                    }
+                }
+                Err(e) => break Err(format!("{} (after archiving {} events)", e, archived)),
+            }
+        };
+        // Closing the connection releases the lock
+        let _ = conn.close().await;
+        result
+    }
+
+    /// Archive the next batch; how many events it held
+    async fn batch(&self, conn: &mut PgConnection) -> Result<usize, String> {
+        let cutoff = Utc::now() - TimeDelta::days(i64::from(self.archive_after_days));
+        let rows = sqlx::query_as::<_, ArchiveRow>(
+            r#"
+            SELECT e.id, e.merchant_id::TEXT AS merchant_id,
+                   (e.created_at AT TIME ZONE 'UTC')::DATE AS day,
+                   (SELECT COUNT(*) FROM delivery_attempts a WHERE a.event_id = e.id) AS attempts,
+                   (to_jsonb(e) || jsonb_build_object(
+                       'attempts', COALESCE(
+                           (SELECT jsonb_agg(to_jsonb(a) - 'event_id' ORDER BY a.id)
+                            FROM delivery_attempts a WHERE a.event_id = e.id),
+                           '[]'::JSONB),
+                       'snapshot',
+                           (SELECT to_jsonb(s) - 'event_id'
+                            FROM payload_snapshots s WHERE s.event_id = e.id)
+                   ))::TEXT AS line
+            FROM domain_events e
+            WHERE e.created_at < $1
+              AND EXISTS (
+                  SELECT 1 FROM delivery_attempts a
+                  WHERE a.event_id = e.id AND a.status IN ('succeeded', 'confirmed')
+                    AND a.delivery_path <> 'direct_endpoint')
+              AND NOT EXISTS (SELECT 1 FROM domain_events r WHERE r.replay_of = e.id)
+            ORDER BY e.id
+            LIMIT $2
+            "#,
+        )
+        .bind(cutoff)
+        .bind(self.batch_size)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to read events: {}", e))?;
+        if rows.is_empty() {
+            return Ok(0);
+        }
+
+        let mut partitions: BTreeMap<NaiveDate, Partition> = BTreeMap::new();
+        for row in &rows {
+            let partition = partitions.entry(row.day).or_insert_with(|| Partition {
+                ids: Vec::new(),
+                merchant_ids: BTreeSet::new(),
+                attempts: 0,
+                body: GzEncoder::new(Vec::new(), Compression::default()),
+            });
+            partition.ids.push(row.id);
+            partition.merchant_ids.insert(row.merchant_id.clone());
+            partition.attempts += row.attempts;
+            partition
+                .body
+                .write_all(row.line.as_bytes())
+                .and_then(|()| partition.body.write_all(b"\n"))
+                .map_err(|e| format!("Failed to compress event {}: {}", row.id, e))?;
+        }
+
+        let mut objects = Vec::new();
+        for (day, partition) in partitions {
+            // Ordered by id, so never empty and ascending
+            let first = partition.ids[0];
+            let last = partition.ids[partition.ids.len() - 1];
+            let key = format!(
+                "{}/events/dt={}/{:020}-{:020}.jsonl.gz",
+                self.prefix.trim_end_matches('/'),
+                day,
+                first,
+                last
+            );
+            let body = partition
+                .body
+                .finish()
+                .map_err(|e| format!("Failed to compress {}: {}", key, e))?;
+            let bytes = body.len();
+            self.s3
+                .put_object(
+                    &self.bucket,
+                    &key,
+                    "application/x-ndjson",
+                    Some("gzip"),
+                    body,
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+            tracing::debug!(
+                "Uploaded {} ({} events, {} bytes)",
+                key,
+                partition.ids.len(),
+                bytes
+            );
+            objects.push(ArchivedObject {
+                key,
+                day,
+                first_event_id: first,
+                last_event_id: last,
+                events: partition.ids.len() as i32,
+                attempts: partition.attempts as i32,
+                bytes: bytes as i64,
+                merchant_ids: partition.merchant_ids.into_iter().collect(),
+            });
+        }
+
+        let ids: Vec<i64> = rows.iter().map(|row| row.id).collect();
+        self.prune(conn, &ids, &objects)
+            .await
+            .map_err(|e| format!("Uploaded but failed to delete {} events: {}", ids.len(), e))?;
+
+        let attempts: i64 = rows.iter().map(|row| row.attempts).sum();
+        let bytes: i64 = objects.iter().map(|object| object.bytes).sum();
+        self.metrics.archived(ids.len(), attempts, bytes as usize);
+        Ok(rows.len())
+    }
+
+    /// Delete the archived rows and list their objects, atomically
+    async fn prune(
+        &self,
+        conn: &mut PgConnection,
+        ids: &[i64],
+        objects: &[ArchivedObject],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = conn.begin().await?;
+        sqlx::query("DELETE FROM delivery_attempts WHERE event_id = ANY($1)")
+            .bind(ids)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM payload_snapshots WHERE event_id = ANY($1)")
+            .bind(ids)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM endpoint_deliveries WHERE event_id = ANY($1)")
+            .bind(ids)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM digest_items WHERE event_id = ANY($1)")
+            .bind(ids)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM domain_events WHERE id = ANY($1)")
+            .bind(ids)
+            .execute(&mut *tx)
+            .await?;
+
+        for object in objects {
+            sqlx::query(
+                r#"
+                INSERT INTO archived_objects
+                    (key, day, first_event_id, last_event_id, events, attempts, bytes,
+                     merchant_ids)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8::TEXT[]::UUID[])
+                ON CONFLICT (key) DO UPDATE
+                SET events = EXCLUDED.events,
+                    attempts = EXCLUDED.attempts,
+                    bytes = EXCLUDED.bytes,
+                    merchant_ids = EXCLUDED.merchant_ids,
+                    archived_at = NOW()
+                "#,
+            )
+            .bind(&object.key)
+            .bind(object.day)
+            .bind(object.first_event_id)
+            .bind(object.last_event_id)
+            .bind(object.events)
+            .bind(object.attempts)
+            .bind(object.bytes)
+            .bind(&object.merchant_ids)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+}
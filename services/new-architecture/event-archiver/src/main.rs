@@ -0,0 +1,126 @@
+use axum::{middleware, routing::post, Router};
+use event_transport::aws::AwsClient;
+use health_checks::{checks, HealthRegistry};
+use http_client::HttpMetrics;
+use service_metrics::ServiceMetrics;
+use sqlx::postgres::PgPoolOptions;
+use std::time::Duration;
+use tracing::info;
+
+mod archiver;
+mod metrics;
+mod replay;
+mod settings;
+
+use archiver::Archiver;
+use metrics::Metrics;
+use replay::ReplayState;
+use settings::Settings;
+
+// ==============================================================================
+// EVENT ARCHIVER: Old delivered events -> S3, out of the operational tables
+// ==============================================================================
+//
+// domain_events, delivery_attempts and payload_snapshots only ever grow. This
+// moves delivered events older than ARCHIVE_AFTER_DAYS, with their attempts
+// and snapshots, to S3_BUCKET as gzipped JSONL partitioned by day, deletes
+// them, and lists every object it wrote in archived_objects (archiver.rs).
+// Any S3-compatible store works: AWS_ENDPOINT_URL points at MinIO or
+// LocalStack. POST /replays reads the archive back into domain_events for a
+// merchant and time range (replay.rs).
+//
+// Run as many as you like; one archives per run, the rest skip it. Health,
+// metrics and replays are on PORT (default 3009).
+
+/// A missing one means init.sql hasn't been applied to this database yet
+const REQUIRED_TABLES: &[&str] = &[
+    "domain_events",
+    "delivery_attempts",
+    "payload_snapshots",
+    "endpoint_deliveries",
+    "digest_items",
+    "archived_objects",
+    "archive_replays",
+];
+
+#[tokio::main]
+async fn main() {
+    logging::init(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    let settings: Settings = config::load_or_exit("event-archiver");
+    // Validated to be set
+    let database_url = settings.database_url.clone().unwrap_or_default();
+    let bucket = settings.s3_bucket.clone().unwrap_or_default();
+
+    // The archiver's connection (the lock holder) and probes
+    let db = PgPoolOptions::new()
+        .max_connections(4)
+        .connect(&database_url)
+        .await
+        .expect("Failed to connect to database");
+
+    let metrics = Metrics::new(ServiceMetrics::new(
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+    ));
+
+    // Validated with the settings
+    let config = settings.aws().expect("Invalid AWS settings");
+    let s3 = AwsClient::new(config, &HttpMetrics::new(metrics.service().registry()))
+        .expect("Failed to build HTTP client");
+
+    let replays = ReplayState {
+        db: db.clone(),
+        s3: s3.clone(),
+        bucket: bucket.clone(),
+        max_events: settings.replay_max_events,
+        metrics: metrics.clone(),
+    };
+
+    let archiver = Archiver {
+        db: db.clone(),
+        s3,
+        bucket: bucket.clone(),
+        prefix: settings.s3_prefix.clone(),
+        archive_after_days: settings.archive_after_days,
+        batch_size: settings.batch_size,
+        interval: Duration::from_secs(settings.interval_secs),
+        metrics: metrics.clone(),
+    };
+    info!(
+        "Archiving delivered events older than {} days to s3://{}/{} every {}s",
+        settings.archive_after_days, bucket, settings.s3_prefix, settings.interval_secs
+    );
+    tokio::spawn(archiver.run());
+
+    let migrations = db.clone();
+    let health = HealthRegistry::new()
+        .readiness("database", move || checks::postgres(db.clone()))
+        .readiness("migrations", move || {
+            checks::tables(migrations.clone(), REQUIRED_TABLES)
+        });
+
+    let app = Router::new()
+        .route(
+            "/replays",
+            post(replay::replay_archived).get(replay::list_replays),
+        )
+        .with_state(replays)
+        .merge(health_checks::router(health))
+        .merge(service_metrics::router(metrics.service().clone()))
+        .route_layer(middleware::from_fn_with_state(
+            metrics.service().clone(),
+            service_metrics::track_http,
+        ));
+
+    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", settings.port))
+        .await
+        .unwrap();
+
+    info!(
+        "EVENT ARCHIVER replays, health and metrics on port {}",
+        settings.port
+    );
+
+    axum::serve(listener, app).await.unwrap();
+}
@@ -0,0 +1,106 @@
+use prometheus::{IntCounter, IntCounterVec, IntGauge, Opts};
+use service_metrics::ServiceMetrics;
+
+// ==============================================================================
+// METRICS: How much has left the database, and whether runs are succeeding
+// ==============================================================================
+//
+// Next to the process and HTTP metrics every service has (service-metrics
+// crate) and the http_client_* family of the S3 calls (http-client crate):
+//
+//   archiver_events_archived_total         domain_events rows uploaded and deleted
+//   archiver_attempts_archived_total       delivery_attempts rows with them
+//   archiver_bytes_uploaded_total          compressed object bytes
+//   archiver_runs_total{result}            archived, idle, skipped or failed
+//   archiver_events_replayed_total         archived events hydrated by POST /replays
+//   archiver_last_success_timestamp_seconds  end of the last run that didn't fail
+
+#[derive(Clone)]
+pub struct Metrics {
+    service: ServiceMetrics,
+    events: IntCounter,
+    attempts: IntCounter,
+    bytes: IntCounter,
+    runs: IntCounterVec,
+    last_success: IntGauge,
+    replayed: IntCounter,
+}
+
+impl Metrics {
+    pub fn new(service: ServiceMetrics) -> Self {
+        let registry = service.registry();
+
+        let events = IntCounter::with_opts(Opts::new(
+            "archiver_events_archived_total",
+            "domain_events rows uploaded to object storage and deleted",
+        ))
+        .unwrap();
+        let attempts = IntCounter::with_opts(Opts::new(
+            "archiver_attempts_archived_total",
+            "delivery_attempts rows archived with their events",
+        ))
+        .unwrap();
+        let bytes = IntCounter::with_opts(Opts::new(
+            "archiver_bytes_uploaded_total",
+            "Bytes of gzipped JSONL uploaded",
+        ))
+        .unwrap();
+        let runs = IntCounterVec::new(
+            Opts::new(
+                "archiver_runs_total",
+                "Runs by result: archived, idle (nothing old enough), skipped (another \
+                 instance held the lock) or failed",
+            ),
+            &["result"],
+        )
+        .unwrap();
+        let last_success = IntGauge::with_opts(Opts::new(
+            "archiver_last_success_timestamp_seconds",
+            "Unix time at the end of the last run that didn't fail",
+        ))
+        .unwrap();
+        let replayed = IntCounter::with_opts(Opts::new(
+            "archiver_events_replayed_total",
+            "Archived events inserted into domain_events again by POST /replays",
+        ))
+        .unwrap();
+
+        registry.register(Box::new(events.clone())).unwrap();
+        registry.register(Box::new(attempts.clone())).unwrap();
+        registry.register(Box::new(bytes.clone())).unwrap();
+        registry.register(Box::new(runs.clone())).unwrap();
+        registry.register(Box::new(last_success.clone())).unwrap();
+        registry.register(Box::new(replayed.clone())).unwrap();
+
+        Metrics {
+            service,
+            events,
+            attempts,
+            bytes,
+            runs,
+            last_success,
+            replayed,
+        }
+    }
+
+    pub fn service(&self) -> &ServiceMetrics {
+        &self.service
+    }
+
+    pub fn archived(&self, events: usize, attempts: i64, bytes: usize) {
+        self.events.inc_by(events as u64);
+        self.attempts.inc_by(attempts.max(0) as u64);
+        self.bytes.inc_by(bytes as u64);
+    }
+
+    pub fn replayed(&self, events: usize) {
+        self.replayed.inc_by(events as u64);
+    }
+
+    pub fn run(&self, result: &str) {
+        self.runs.with_label_values(&[result]).inc();
+        if result != "failed" {
+            self.last_success.set(chrono::Utc::now().timestamp());
+        }
+    }
+}
@@ -0,0 +1,371 @@
+use axum::{
+    extract::{Json, Query, State},
+    http::{HeaderMap, StatusCode},
+};
+use chrono::{DateTime, Utc};
+use event_transport::aws::AwsClient;
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::io::Read;
+use uuid::Uuid;
+
+use crate::metrics::Metrics;
+
+// ==============================================================================
+// REPLAY: Archived events -> new domain_events rows, delivered again
+// ==============================================================================
+//
+//   POST /replays    hydrate a merchant's archived events in a time range
+//   GET  /replays    what was replayed, by whom, newest first
+//
+// For "we need last month's webhooks re-sent" once last month is in S3.
+// Every archived_objects object that lists the merchant and covers a day of
+// the range is downloaded and read line by line; the merchant's events with
+// created_at in [from, to) (and of event_types, if given) are inserted into
+// domain_events as new rows, oldest first, with replay_of set to the archived
+// id. That is exactly what api-service's POST /events/replay writes for
+// events still in the table, so the rest of the pipeline can't tell the two
+// apart: svix-caller submits them under a new Svix event id (the idempotency
+// key) and tags them "replay", while the merchant-facing event_id stays that
+// of the original.
+//
+// Only original events are replayed, never archived replays, and an event in
+// two objects (see archiver.rs) is replayed once. Events newer than the
+// archive cutoff are still in domain_events: a range straddling it needs
+// POST /events/replay as well. Snapshot-mode merchants get the payment as it
+// is now, since the archived snapshot isn't restored.
+//
+// The whole replay is one transaction, recorded in archive_replays under the
+// X-Operator header; a range holding more than REPLAY_MAX_EVENTS events is
+// refused rather than cut short.
+
+const OPERATOR_HEADER: &str = "x-operator";
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// Rows per INSERT
+const INSERT_CHUNK: usize = 1000;
+
+#[derive(Clone)]
+pub struct ReplayState {
+    pub db: PgPool,
+    pub s3: AwsClient,
+    pub bucket: String,
+    pub max_events: usize,
+    pub metrics: Metrics,
+}
+
+#[derive(Deserialize)]
+pub struct ReplayRequest {
+    merchant_id: String,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    /// Only these event types; every type when absent
+    #[serde(default)]
+    event_types: Option<Vec<String>>,
+    reason: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ReplayResponse {
+    replay_id: i64,
+    objects: usize,
+    replayed: usize,
+    events: Vec<ReplayedEvent>,
+}
+
+#[derive(Serialize)]
+pub struct ReplayedEvent {
+    id: i64,
+    replay_of: i64,
+}
+
+#[derive(Deserialize)]
+pub struct ReplayFilter {
+    merchant_id: Option<String>,
+    before_id: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct ReplayRow {
+    id: i64,
+    operator: String,
+    reason: Option<String>,
+    merchant_id: Uuid,
+    from_time: DateTime<Utc>,
+    to_time: DateTime<Utc>,
+    event_types: Option<Vec<String>>,
+    objects: i32,
+    event_ids: Vec<i64>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct ReplayListResponse {
+    replays: Vec<ReplayRow>,
+    next_before_id: Option<i64>,
+}
+
+/// The fields of an archived line a replay needs (archiver.rs writes the
+/// whole row, its attempts and its snapshot)
+#[derive(Deserialize)]
+struct ArchivedEvent {
+    id: i64,
+    event_type: String,
+    object_id: Uuid,
+    merchant_id: Uuid,
+    mode: String,
+    #[serde(default)]
+    payload: serde_json::Value,
+    #[serde(default)]
+    replay_of: Option<i64>,
+    #[serde(default = "default_schema_version")]
+    schema_version: i32,
+    created_at: DateTime<Utc>,
+}
+
+fn default_schema_version() -> i32 {
+    1
+}
+
+/// Same mapping as api-service: a UUID as-is, anything else name-based
+fn resolve_merchant_id(id: &str) -> Uuid {
+    Uuid::parse_str(id).unwrap_or_else(|_| Uuid::new_v5(&Uuid::NAMESPACE_DNS, id.as_bytes()))
+}
+
+fn operator(headers: &HeaderMap) -> Result<String, (StatusCode, String)> {
+    headers
+        .get(OPERATOR_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|operator| !operator.is_empty() && operator.len() <= 255)
+        .map(str::to_string)
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                "X-Operator header naming who is acting is required".to_string(),
+            )
+        })
+}
+
+fn internal_error(what: &'static str) -> impl FnOnce(sqlx::Error) -> (StatusCode, String) {
+    move |e| {
+        tracing::error!("Failed to {}: {}", what, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to {}: {}", what, e),
+        )
+    }
+}
+
+/// Objects are gzipped JSONL; one stored without compression is read as-is
+fn decompress(body: Vec<u8>) -> std::io::Result<String> {
+    if !body.starts_with(&[0x1f, 0x8b]) {
+        return String::from_utf8(body)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e));
+    }
+    let mut text = String::new();
+    GzDecoder::new(body.as_slice()).read_to_string(&mut text)?;
+    Ok(text)
+}
+
+pub async fn replay_archived(
+    State(state): State<ReplayState>,
+    headers: HeaderMap,
+    Json(req): Json<ReplayRequest>,
+) -> Result<Json<ReplayResponse>, (StatusCode, String)> {
+    let operator = operator(&headers)?;
+    if req.from >= req.to {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "from must be before to".to_string(),
+        ));
+    }
+    let merchant_id = resolve_merchant_id(&req.merchant_id);
+
+    // Objects are partitioned by UTC day of created_at
+    let keys: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT key FROM archived_objects
+        WHERE $1 = ANY(merchant_ids) AND day >= $2 AND day <= $3
+        ORDER BY day, first_event_id
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(req.from.date_naive())
+    .bind(req.to.date_naive())
+    .fetch_all(&state.db)
+    .await
+    .map_err(internal_error("list archived objects"))?;
+
+    // Keyed by archived id: oldest first, each once
+    let mut events: BTreeMap<i64, ArchivedEvent> = BTreeMap::new();
+    for key in &keys {
+        let body = state.s3.get_object(&state.bucket, key).await.map_err(|e| {
+            tracing::error!("Failed to download {}: {}", key, e);
+            (StatusCode::BAD_GATEWAY, e.to_string())
+        })?;
+        let text = decompress(body).map_err(|e| {
+            tracing::error!("Failed to decompress {}: {}", key, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to decompress {}: {}", key, e),
+            )
+        })?;
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event: ArchivedEvent = serde_json::from_str(line).map_err(|e| {
+                tracing::error!("Unreadable line {} of {}: {}", number + 1, key, e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Unreadable line {} of {}: {}", number + 1, key, e),
+                )
+            })?;
+            let selected = event.merchant_id == merchant_id
+                && event.replay_of.is_none()
+                && event.created_at >= req.from
+                && event.created_at < req.to
+                && req
+                    .event_types
+                    .as_ref()
+                    .is_none_or(|types| types.contains(&event.event_type));
+            if selected {
+                events.insert(event.id, event);
+            }
+        }
+        if events.len() > state.max_events {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "More than {} archived events in range, replay a shorter one",
+                    state.max_events
+                ),
+            ));
+        }
+    }
+
+    let mut tx = state.db.begin().await.map_err(internal_error("begin"))?;
+    let archived: Vec<ArchivedEvent> = events.into_values().collect();
+    let mut replayed = Vec::with_capacity(archived.len());
+    for chunk in archived.chunks(INSERT_CHUNK) {
+        let rows = sqlx::query_as::<_, (i64, i64)>(
+            r#"
+            INSERT INTO domain_events
+                (event_type, object_id, merchant_id, mode, payload, replay_of, schema_version)
+            SELECT event_type, object_id, merchant_id, mode, payload::JSONB, replay_of,
+                   schema_version
+            FROM UNNEST($1::TEXT[], $2::UUID[], $3::UUID[], $4::TEXT[], $5::TEXT[],
+                        $6::BIGINT[], $7::INT[])
+                WITH ORDINALITY AS e(event_type, object_id, merchant_id, mode, payload,
+                                     replay_of, schema_version, n)
+            ORDER BY n
+            RETURNING id, replay_of
+            "#,
+        )
+        .bind(chunk.iter().map(|e| e.event_type.clone()).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|e| e.object_id).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|e| e.merchant_id).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|e| e.mode.clone()).collect::<Vec<_>>())
+        .bind(
+            chunk
+                .iter()
+                .map(|e| (!e.payload.is_null()).then(|| e.payload.to_string()))
+                .collect::<Vec<_>>(),
+        )
+        .bind(chunk.iter().map(|e| e.id).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|e| e.schema_version).collect::<Vec<_>>())
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(internal_error("insert replayed events"))?;
+        replayed.extend(rows);
+    }
+    replayed.sort();
+
+    let event_ids: Vec<i64> = replayed.iter().map(|(id, _)| *id).collect();
+    let replay_id: i64 = sqlx::query_scalar(
+        r#"
+        INSERT INTO archive_replays
+            (operator, reason, merchant_id, from_time, to_time, event_types, objects, event_ids)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id
+        "#,
+    )
+    .bind(&operator)
+    .bind(&req.reason)
+    .bind(merchant_id)
+    .bind(req.from)
+    .bind(req.to)
+    .bind(&req.event_types)
+    .bind(keys.len() as i32)
+    .bind(&event_ids)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(internal_error("record the replay"))?;
+    tx.commit().await.map_err(internal_error("commit the replay"))?;
+
+    state.metrics.replayed(replayed.len());
+    tracing::info!(
+        "{} replayed {} archived events of merchant {} from {} objects (replay {})",
+        operator,
+        replayed.len(),
+        merchant_id,
+        keys.len(),
+        replay_id
+    );
+
+    Ok(Json(ReplayResponse {
+        replay_id,
+        objects: keys.len(),
+        replayed: replayed.len(),
+        events: replayed
+            .into_iter()
+            .map(|(id, replay_of)| ReplayedEvent { id, replay_of })
+            .collect(),
+    }))
+}
+
+pub async fn list_replays(
+    State(state): State<ReplayState>,
+    Query(filter): Query<ReplayFilter>,
+) -> Result<Json<ReplayListResponse>, (StatusCode, String)> {
+    let limit = filter
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let merchant_id = filter.merchant_id.as_deref().map(resolve_merchant_id);
+
+    let replays = sqlx::query_as::<_, ReplayRow>(
+        r#"
+        SELECT id, operator, reason, merchant_id, from_time, to_time, event_types, objects,
+               event_ids, created_at
+        FROM archive_replays
+        WHERE ($1::UUID IS NULL OR merchant_id = $1)
+          AND ($2::BIGINT IS NULL OR id < $2)
+        ORDER BY id DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(filter.before_id)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await
+    .map_err(internal_error("list replays"))?;
+
+    let next_before_id = if replays.len() as i64 == limit {
+        replays.last().map(|replay| replay.id)
+    } else {
+        None
+    };
+
+    Ok(Json(ReplayListResponse {
+        replays,
+        next_before_id,
+    }))
+}
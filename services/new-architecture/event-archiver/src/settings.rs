@@ -0,0 +1,105 @@
+use event_transport::aws::AwsConfig;
+use serde::{Deserialize, Serialize};
+
+// ==============================================================================
+// SETTINGS: What gets archived, when, and where to
+// ==============================================================================
+//
+// Loaded through the shared config crate: defaults below, then
+// config/event-archiver.toml (or CONFIG_FILE), then the environment variable
+// of the same name in upper case.
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// /livez, /readyz, /startupz and /metrics
+    pub port: u16,
+    /// Required
+    pub database_url: Option<String>,
+    /// Required
+    pub s3_bucket: Option<String>,
+    /// Prepended to every object key
+    pub s3_prefix: String,
+    pub aws_region: String,
+    /// MinIO's or LocalStack's URL, say; unset for AWS itself
+    pub aws_endpoint_url: Option<String>,
+    pub aws_access_key_id: Option<String>,
+    pub aws_secret_access_key: Option<String>,
+    /// With temporary credentials
+    pub aws_session_token: Option<String>,
+    /// Delivered events older than this are archived
+    pub archive_after_days: u32,
+    /// Events per round of uploads and deletes
+    pub batch_size: i64,
+    /// Between runs
+    pub interval_secs: u64,
+    /// Most archived events one POST /replays inserts
+    pub replay_max_events: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            port: 3009,
+            database_url: None,
+            s3_bucket: None,
+            s3_prefix: "webhook-archive".to_string(),
+            aws_region: "us-east-1".to_string(),
+            aws_endpoint_url: None,
+            aws_access_key_id: None,
+            aws_secret_access_key: None,
+            aws_session_token: None,
+            archive_after_days: 30,
+            batch_size: 1000,
+            interval_secs: 300,
+            replay_max_events: 100_000,
+        }
+    }
+}
+
+impl Settings {
+    /// Where S3 is and the credentials for it
+    pub fn aws(&self) -> Result<AwsConfig, Vec<String>> {
+        AwsConfig::new(
+            &self.aws_region,
+            self.aws_endpoint_url.as_deref(),
+            self.aws_access_key_id.as_deref(),
+            self.aws_secret_access_key.as_deref(),
+            self.aws_session_token.as_deref(),
+        )
+    }
+}
+
+impl config::Validate for Settings {
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.database_url.is_none() {
+            problems.push("DATABASE_URL: required".to_string());
+        }
+        if self.s3_bucket.as_deref().unwrap_or_default().is_empty() {
+            problems.push("S3_BUCKET: required".to_string());
+        }
+        if self.aws_region.is_empty() {
+            problems.push("AWS_REGION: required".to_string());
+        }
+        if let Err(aws) = self.aws() {
+            problems.extend(aws);
+        }
+        if self.archive_after_days == 0 {
+            problems.push("ARCHIVE_AFTER_DAYS: must be at least 1".to_string());
+        }
+        if !(1..=10_000).contains(&self.batch_size) {
+            problems.push(format!(
+                "BATCH_SIZE: {} is not between 1 and 10000",
+                self.batch_size
+            ));
+        }
+        if self.interval_secs == 0 {
+            problems.push("INTERVAL_SECS: must be at least 1".to_string());
+        }
+        if self.replay_max_events == 0 {
+            problems.push("REPLAY_MAX_EVENTS: must be at least 1".to_string());
+        }
+        problems
+    }
+}
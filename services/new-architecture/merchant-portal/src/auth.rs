@@ -0,0 +1,123 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::AppState;
+
+// ==============================================================================
+// MERCHANT AUTH: Every portal request acts as the merchant its key belongs to
+// ==============================================================================
+//
+// Merchants present an API key issued by api-service (POST
+// /merchants/:id/api-keys) as "Authorization: Bearer mpk_...". Keys are looked
+// up by their SHA-256, revoked ones are refused, and the merchant they belong
+// to is attached to the request; handlers only ever query that merchant's
+// rows, so one merchant's key can't reach another's endpoints or events.
+// last_used_at is refreshed at most once a minute per key.
+//
+// Changes a request makes are recorded in audit_log as made with its key
+// ("api_key:<id>"), through the transaction ApiKey::begin opens.
+
+const LAST_USED_RESOLUTION: Duration = Duration::seconds(60);
+
+/// The merchant a request was authenticated as
+#[derive(Debug, Clone, Copy)]
+pub struct Merchant(pub Uuid);
+
+/// The key a request was authenticated with
+#[derive(Debug, Clone, Copy)]
+pub struct ApiKey(pub Uuid);
+
+impl ApiKey {
+    /// A transaction whose changes the audit triggers record as this key's
+    pub async fn begin(&self, db: &PgPool) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        let mut tx = db.begin().await?;
+        sqlx::query(
+            "SELECT set_config('audit.actor', $1, true), set_config('audit.source', $2, true)",
+        )
+        .bind(format!("api_key:{}", self.0))
+        .bind(env!("CARGO_PKG_NAME"))
+        .execute(&mut *tx)
+        .await?;
+        Ok(tx)
+    }
+}
+
+/// Hex SHA-256 of a key, as stored in merchant_api_keys.key_hash
+fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub async fn require_api_key(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let presented = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|key| !key.is_empty());
+    let Some(presented) = presented else {
+        return Err((StatusCode::UNAUTHORIZED, "Missing API key".to_string()));
+    };
+
+    let key = sqlx::query_as::<_, (Uuid, Uuid, Option<DateTime<Utc>>)>(
+        r#"
+        SELECT id, merchant_id, last_used_at
+        FROM merchant_api_keys
+        WHERE key_hash = $1 AND revoked_at IS NULL
+        "#,
+    )
+    .bind(hash_key(presented))
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to look up API key: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to look up API key: {}", e),
+        )
+    })?;
+
+    let Some((key_id, merchant_id, last_used_at)) = key else {
+        warn!("Rejected invalid API key for {}", request.uri().path());
+        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
+    };
+
+    if last_used_at.is_none_or(|at| Utc::now() - at > LAST_USED_RESOLUTION) {
+        let db = state.db.clone();
+        tokio::spawn(async move {
+            let result =
+                sqlx::query("UPDATE merchant_api_keys SET last_used_at = NOW() WHERE id = $1")
+                    .bind(key_id)
+                    .execute(&db)
+                    .await;
+            if let Err(e) = result {
+                tracing::warn!("Failed to update last_used_at of API key {}: {}", key_id, e);
+            }
+        });
+    }
+
+    tracing::debug!(
+        "Request to {} from merchant {}",
+        request.uri().path(),
+        merchant_id
+    );
+    request.extensions_mut().insert(Merchant(merchant_id));
+    request.extensions_mut().insert(ApiKey(key_id));
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,368 @@
+use axum::{
+    extract::{Extension, Json, Path, Query, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::auth::{ApiKey, Merchant};
+use crate::AppState;
+
+// ==============================================================================
+// DELIVERIES: The merchant's recent events and what became of them
+// ==============================================================================
+//
+// A delivery is a domain event with its delivery_attempts. Its status comes
+// from the latest attempt on any path but 'merchant' (confirmations say the
+// merchant processed it, not that it arrived) and 'direct_endpoint' (one
+// endpoint's part; the 'direct' row after it speaks for all of them):
+//
+//   pending     no attempt yet
+//   succeeded   the latest attempt succeeded (or was a dry run)
+//   failed      anything else: failed, failing or exhausted at the endpoint,
+//               rate limited, skipped for lack of a Svix application
+//
+// The detail view lists every attempt with the endpoint's response where the
+// path recorded one (direct delivery and Svix's operational webhooks), and
+// for direct deliveries where each endpoint stands (endpoint_deliveries).
+//
+// Retrying a failed delivery replays its event, the way POST /events/replay on
+// api-service does: a new domain_events row with replay_of set, delivered
+// under its own id so receivers that dedupe on webhook-id still see it. A
+// replay is always of the original, never of another replay. Retries are
+// recorded in audit_log like api-service's replays, as made with the key.
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// The status expression over the `latest` lateral join below
+const STATUS_SQL: &str = r#"
+    CASE
+        WHEN latest.status IS NULL THEN 'pending'
+        WHEN latest.status IN ('succeeded', 'dry_run') THEN 'succeeded'
+        ELSE 'failed'
+    END
+"#;
+
+/// The latest non-merchant attempt of each event `e`
+const LATEST_ATTEMPT_SQL: &str = r#"
+    LEFT JOIN LATERAL (
+        SELECT a.status, a.response_status, a.created_at
+        FROM delivery_attempts a
+        WHERE a.event_id = e.id AND a.delivery_path NOT IN ('merchant', 'direct_endpoint')
+        ORDER BY a.id DESC
+        LIMIT 1
+    ) latest ON TRUE
+"#;
+
+#[derive(Deserialize)]
+pub struct DeliveryFilter {
+    /// pending, succeeded or failed
+    status: Option<String>,
+    event_type: Option<String>,
+    /// Events about one payment
+    object_id: Option<Uuid>,
+    /// Keyset pagination: only return events with id < before_id
+    before_id: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct DeliveryRow {
+    event_id: i64,
+    event_type: String,
+    object_id: Uuid,
+    mode: String,
+    replay_of: Option<i64>,
+    created_at: Option<DateTime<Utc>>,
+    status: String,
+    attempt_count: i64,
+    /// What the endpoint answered on the latest attempt, if recorded
+    response_status: Option<i32>,
+    last_attempt_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct AttemptRow {
+    id: i64,
+    delivery_path: String,
+    /// The endpoint a 'direct_endpoint' attempt went to
+    endpoint_id: Option<Uuid>,
+    status: String,
+    error: Option<String>,
+    response_status: Option<i32>,
+    response_body: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+}
+
+/// Where one endpoint stands with a direct delivery
+#[derive(Serialize, sqlx::FromRow)]
+pub struct EndpointDeliveryRow {
+    endpoint_id: Uuid,
+    url: Option<String>,
+    /// succeeded or failed
+    status: String,
+    attempts: i32,
+    last_error: Option<String>,
+    response_status: Option<i32>,
+    /// When a failed endpoint gets it again
+    next_attempt_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct DeliveryListResponse {
+    data: Vec<DeliveryRow>,
+    next_before_id: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct DeliveryDetailResponse {
+    #[serde(flatten)]
+    delivery: DeliveryRow,
+    payload: Option<serde_json::Value>,
+    /// Oldest first
+    attempts: Vec<AttemptRow>,
+    /// Direct deliveries only
+    endpoints: Vec<EndpointDeliveryRow>,
+    /// Events replaying this one, e.g. earlier retries
+    replays: Vec<i64>,
+}
+
+#[derive(Serialize)]
+pub struct RetryResponse {
+    /// The new event carrying the retry
+    event_id: i64,
+    replay_of: i64,
+}
+
+fn database_error(action: &str, e: sqlx::Error) -> (StatusCode, String) {
+    tracing::error!("Failed to {}: {}", action, e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Failed to {}: {}", action, e),
+    )
+}
+
+fn delivery_query() -> String {
+    format!(
+        r#"
+        SELECT * FROM (
+            SELECT e.id AS event_id, e.event_type, e.object_id, e.mode, e.replay_of,
+                   e.created_at, {status} AS status,
+                   (SELECT COUNT(*) FROM delivery_attempts a WHERE a.event_id = e.id) AS attempt_count,
+                   latest.response_status, latest.created_at AS last_attempt_at
+            FROM domain_events e
+            {latest}
+            WHERE e.merchant_id = $1
+              AND ($2::BIGINT IS NULL OR e.id = $2)
+              AND ($3::TEXT IS NULL OR e.event_type = $3)
+              AND ($4::UUID IS NULL OR e.object_id = $4)
+              AND ($5::BIGINT IS NULL OR e.id < $5)
+        ) deliveries
+        WHERE ($6::TEXT IS NULL OR status = $6)
+        ORDER BY event_id DESC
+        LIMIT $7
+        "#,
+        status = STATUS_SQL,
+        latest = LATEST_ATTEMPT_SQL,
+    )
+}
+
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    Extension(Merchant(merchant_id)): Extension<Merchant>,
+    Query(filter): Query<DeliveryFilter>,
+) -> Result<Json<DeliveryListResponse>, (StatusCode, String)> {
+    if let Some(status) = &filter.status {
+        if !matches!(status.as_str(), "pending" | "succeeded" | "failed") {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Unknown status {}, expected pending, succeeded or failed",
+                    status
+                ),
+            ));
+        }
+    }
+    let limit = filter
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let data = sqlx::query_as::<_, DeliveryRow>(&delivery_query())
+        .bind(merchant_id)
+        .bind(None::<i64>)
+        .bind(&filter.event_type)
+        .bind(filter.object_id)
+        .bind(filter.before_id)
+        .bind(&filter.status)
+        .bind(limit)
+        .fetch_all(&state.db)
+        .await
+        .map_err(|e| database_error("list deliveries", e))?;
+
+    let next_before_id = if data.len() as i64 == limit {
+        data.last().map(|delivery| delivery.event_id)
+    } else {
+        None
+    };
+
+    Ok(Json(DeliveryListResponse {
+        data,
+        next_before_id,
+    }))
+}
+
+async fn fetch_delivery(
+    state: &AppState,
+    merchant_id: Uuid,
+    event_id: i64,
+) -> Result<DeliveryRow, (StatusCode, String)> {
+    sqlx::query_as::<_, DeliveryRow>(&delivery_query())
+        .bind(merchant_id)
+        .bind(event_id)
+        .bind(None::<String>)
+        .bind(None::<Uuid>)
+        .bind(None::<i64>)
+        .bind(None::<String>)
+        .bind(1i64)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| database_error("fetch delivery", e))?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("Event not found: {}", event_id),
+            )
+        })
+}
+
+pub async fn get_delivery(
+    State(state): State<AppState>,
+    Extension(Merchant(merchant_id)): Extension<Merchant>,
+    Path(event_id): Path<i64>,
+) -> Result<Json<DeliveryDetailResponse>, (StatusCode, String)> {
+    let delivery = fetch_delivery(&state, merchant_id, event_id).await?;
+
+    let (payload,) = sqlx::query_as::<_, (Option<serde_json::Value>,)>(
+        "SELECT payload FROM domain_events WHERE id = $1",
+    )
+    .bind(event_id)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| database_error("fetch event payload", e))?;
+
+    let attempts = sqlx::query_as::<_, AttemptRow>(
+        r#"
+        SELECT id, delivery_path, endpoint_id, status, error, response_status, response_body,
+               created_at
+        FROM delivery_attempts
+        WHERE event_id = $1
+        ORDER BY id
+        "#,
+    )
+    .bind(event_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| database_error("list attempts", e))?;
+
+    let endpoints = sqlx::query_as::<_, EndpointDeliveryRow>(
+        r#"
+        SELECT d.endpoint_id, m.url, d.status, d.attempts, d.last_error, d.response_status,
+               d.next_attempt_at, d.updated_at
+        FROM endpoint_deliveries d
+        LEFT JOIN merchant_endpoints m ON m.id = d.endpoint_id
+        WHERE d.event_id = $1
+        ORDER BY d.created_at, d.endpoint_id
+        "#,
+    )
+    .bind(event_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| database_error("list endpoint deliveries", e))?;
+
+    let replays = sqlx::query_as::<_, (i64,)>(
+        "SELECT id FROM domain_events WHERE replay_of = $1 AND merchant_id = $2 ORDER BY id",
+    )
+    .bind(delivery.replay_of.unwrap_or(event_id))
+    .bind(merchant_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| database_error("list replays", e))?
+    .into_iter()
+    .map(|(id,)| id)
+    .filter(|id| *id != event_id)
+    .collect();
+
+    Ok(Json(DeliveryDetailResponse {
+        delivery,
+        payload,
+        attempts,
+        endpoints,
+        replays,
+    }))
+}
+
+/// Only failed deliveries can be retried; pending ones are still on their way
+pub async fn retry_delivery(
+    State(state): State<AppState>,
+    Extension(Merchant(merchant_id)): Extension<Merchant>,
+    Extension(key): Extension<ApiKey>,
+    Path(event_id): Path<i64>,
+) -> Result<(StatusCode, Json<RetryResponse>), (StatusCode, String)> {
+    let delivery = fetch_delivery(&state, merchant_id, event_id).await?;
+    if delivery.status != "failed" {
+        return Err((
+            StatusCode::CONFLICT,
+            format!(
+                "Event {} is {}, only failed deliveries can be retried",
+                event_id, delivery.status
+            ),
+        ));
+    }
+
+    let mut tx = key
+        .begin(&state.db)
+        .await
+        .map_err(|e| database_error("retry delivery", e))?;
+    let (new_event_id, replay_of) = sqlx::query_as::<_, (i64, i64)>(
+        r#"
+        INSERT INTO domain_events (event_type, object_id, merchant_id, mode, payload, replay_of, schema_version)
+        SELECT event_type, object_id, merchant_id, mode, payload, COALESCE(replay_of, id), schema_version
+        FROM domain_events
+        WHERE id = $1 AND merchant_id = $2
+        RETURNING id, replay_of
+        "#,
+    )
+    .bind(event_id)
+    .bind(merchant_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| database_error("retry delivery", e))?;
+    sqlx::query("SELECT record_audit('replay', $1, $2, 'replay', NULL, $3)")
+        .bind(format!("{}-{}", new_event_id, new_event_id))
+        .bind(merchant_id)
+        .bind(serde_json::json!({ "retry_of": event_id, "events": 1 }))
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| database_error("record retry", e))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| database_error("retry delivery", e))?;
+    info!(
+        "Merchant {} retried event {} as event {}",
+        merchant_id, event_id, new_event_id
+    );
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(RetryResponse {
+            event_id: new_event_id,
+            replay_of,
+        }),
+    ))
+}
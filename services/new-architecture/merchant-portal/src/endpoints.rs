@@ -0,0 +1,443 @@
+use axum::{
+    extract::{Extension, Json, Path, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Duration, Utc};
+use event_filter::Filter;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+use webhook_signing::WEBHOOK_HEADERS;
+
+use crate::auth::{ApiKey, Merchant};
+use crate::{truncate_body, AppState};
+
+// ==============================================================================
+// ENDPOINTS: The merchant's webhook URLs, their secrets, and test events
+// ==============================================================================
+//
+// The same merchant_endpoints rows api-service manages for operators, scoped
+// to the authenticated merchant. Secrets are only ever returned by create and
+// rotate-secret; listings show when the previous secret stops signing. A
+// disabled endpoint keeps its secrets but gets no deliveries. One with a
+// filter (event-filter crate, e.g. `payment.amount > 10000`) only gets the
+// events it's true for; test events are sent regardless.
+//
+// POST /v1/endpoints/:id/test signs an event with the endpoint's active
+// secrets and POSTs it straight to the URL, returning what the endpoint
+// answered, so a merchant can check their receiver before real traffic
+// depends on it.
+
+/// How long the old secret keeps signing after a rotation unless overridden
+const DEFAULT_GRACE_PERIOD_SECS: i64 = 24 * 60 * 60;
+
+const DEFAULT_TEST_EVENT_TYPE: &str = "webhook.test";
+
+#[derive(Deserialize)]
+pub struct CreateEndpointRequest {
+    url: String,
+    #[serde(default)]
+    filter: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CreateEndpointResponse {
+    id: Uuid,
+    url: String,
+    secret: String,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateEndpointRequest {
+    url: Option<String>,
+    disabled: Option<bool>,
+    /// An empty one removes the filter
+    filter: Option<String>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct EndpointResponse {
+    id: Uuid,
+    url: String,
+    disabled: bool,
+    previous_secret_expires_at: Option<DateTime<Utc>>,
+    filter: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct RotateSecretRequest {
+    grace_period_secs: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct RotateSecretResponse {
+    id: Uuid,
+    secret: String,
+    previous_secret_expires_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct TestEventRequest {
+    event_type: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct TestEventResponse {
+    /// The webhook-id the test was sent with
+    msg_id: Uuid,
+    event_type: String,
+    status_code: u16,
+    /// Cut to the first MAX_RESPONSE_BODY_BYTES
+    response_body: String,
+    signatures: usize,
+    duration_ms: u128,
+}
+
+/// Public web servers only (http-client's egress policy), so an endpoint
+/// can't point the delivery path at our own network. Checked again on every
+/// delivery, in case the name moves.
+async fn validate_url(state: &AppState, url: &str) -> Result<(), (StatusCode, String)> {
+    state.egress.check_destination(url).await.map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Endpoint URL not allowed: {}", e),
+        )
+    })
+}
+
+/// The filter as it's stored; "" for none, so an update can remove it
+fn parse_filter(filter: &str) -> Result<String, (StatusCode, String)> {
+    let filter = filter.trim();
+    if filter.is_empty() {
+        return Ok(String::new());
+    }
+    Filter::parse(filter)
+        .map(|filter| filter.as_str().to_string())
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid filter: {}", e)))
+}
+
+fn database_error(action: &str, e: sqlx::Error) -> (StatusCode, String) {
+    tracing::error!("Failed to {}: {}", action, e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Failed to {}: {}", action, e),
+    )
+}
+
+fn not_found(endpoint_id: Uuid) -> (StatusCode, String) {
+    (
+        StatusCode::NOT_FOUND,
+        format!("Endpoint not found: {}", endpoint_id),
+    )
+}
+
+pub async fn list_endpoints(
+    State(state): State<AppState>,
+    Extension(Merchant(merchant_id)): Extension<Merchant>,
+) -> Result<Json<Vec<EndpointResponse>>, (StatusCode, String)> {
+    let endpoints = sqlx::query_as::<_, EndpointResponse>(
+        r#"
+        SELECT id, url, disabled, previous_secret_expires_at, filter, created_at, updated_at
+        FROM merchant_endpoints
+        WHERE merchant_id = $1
+        ORDER BY created_at
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| database_error("list endpoints", e))?;
+
+    Ok(Json(endpoints))
+}
+
+pub async fn create_endpoint(
+    State(state): State<AppState>,
+    Extension(Merchant(merchant_id)): Extension<Merchant>,
+    Extension(key): Extension<ApiKey>,
+    Json(req): Json<CreateEndpointRequest>,
+) -> Result<(StatusCode, Json<CreateEndpointResponse>), (StatusCode, String)> {
+    validate_url(&state, &req.url).await?;
+    let filter = req.filter.as_deref().map(parse_filter).transpose()?;
+    let secret = webhook_signing::generate_secret();
+
+    let mut tx = key
+        .begin(&state.db)
+        .await
+        .map_err(|e| database_error("create endpoint", e))?;
+    let (id,) = sqlx::query_as::<_, (Uuid,)>(
+        r#"
+        INSERT INTO merchant_endpoints (merchant_id, url, secret, filter)
+        VALUES ($1, $2, $3, NULLIF($4, ''))
+        RETURNING id
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(&req.url)
+    .bind(&secret)
+    .bind(&filter)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| database_error("create endpoint", e))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| database_error("create endpoint", e))?;
+    info!("Endpoint {} created by merchant {}", id, merchant_id);
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateEndpointResponse {
+            id,
+            url: req.url,
+            secret,
+        }),
+    ))
+}
+
+pub async fn get_endpoint(
+    State(state): State<AppState>,
+    Extension(Merchant(merchant_id)): Extension<Merchant>,
+    Path(endpoint_id): Path<Uuid>,
+) -> Result<Json<EndpointResponse>, (StatusCode, String)> {
+    sqlx::query_as::<_, EndpointResponse>(
+        r#"
+        SELECT id, url, disabled, previous_secret_expires_at, filter, created_at, updated_at
+        FROM merchant_endpoints
+        WHERE id = $1 AND merchant_id = $2
+        "#,
+    )
+    .bind(endpoint_id)
+    .bind(merchant_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| database_error("fetch endpoint", e))?
+    .map(Json)
+    .ok_or_else(|| not_found(endpoint_id))
+}
+
+/// Change the URL or the filter, or disable/re-enable the endpoint
+pub async fn update_endpoint(
+    State(state): State<AppState>,
+    Extension(Merchant(merchant_id)): Extension<Merchant>,
+    Extension(key): Extension<ApiKey>,
+    Path(endpoint_id): Path<Uuid>,
+    Json(req): Json<UpdateEndpointRequest>,
+) -> Result<Json<EndpointResponse>, (StatusCode, String)> {
+    if let Some(url) = &req.url {
+        validate_url(&state, url).await?;
+    }
+    let filter = req.filter.as_deref().map(parse_filter).transpose()?;
+
+    let mut tx = key
+        .begin(&state.db)
+        .await
+        .map_err(|e| database_error("update endpoint", e))?;
+    let endpoint = sqlx::query_as::<_, EndpointResponse>(
+        r#"
+        UPDATE merchant_endpoints
+        SET url = COALESCE($1, url),
+            disabled = COALESCE($2, disabled),
+            filter = CASE WHEN $3::TEXT IS NULL THEN filter ELSE NULLIF($3, '') END,
+            updated_at = NOW()
+        WHERE id = $4 AND merchant_id = $5
+        RETURNING id, url, disabled, previous_secret_expires_at, filter, created_at, updated_at
+        "#,
+    )
+    .bind(req.url.as_deref())
+    .bind(req.disabled)
+    .bind(&filter)
+    .bind(endpoint_id)
+    .bind(merchant_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| database_error("update endpoint", e))?
+    .ok_or_else(|| not_found(endpoint_id))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| database_error("update endpoint", e))?;
+    info!(
+        "Endpoint {} updated by merchant {} (disabled: {})",
+        endpoint_id, merchant_id, endpoint.disabled
+    );
+    Ok(Json(endpoint))
+}
+
+pub async fn delete_endpoint(
+    State(state): State<AppState>,
+    Extension(Merchant(merchant_id)): Extension<Merchant>,
+    Extension(key): Extension<ApiKey>,
+    Path(endpoint_id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let mut tx = key
+        .begin(&state.db)
+        .await
+        .map_err(|e| database_error("delete endpoint", e))?;
+    let result = sqlx::query("DELETE FROM merchant_endpoints WHERE id = $1 AND merchant_id = $2")
+        .bind(endpoint_id)
+        .bind(merchant_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| database_error("delete endpoint", e))?;
+
+    if result.rows_affected() == 0 {
+        return Err(not_found(endpoint_id));
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| database_error("delete endpoint", e))?;
+    info!(
+        "Endpoint {} deleted by merchant {}",
+        endpoint_id, merchant_id
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Rotating again inside the grace window replaces the previous secret, so only
+/// the two most recent secrets are ever valid at once.
+pub async fn rotate_secret(
+    State(state): State<AppState>,
+    Extension(Merchant(merchant_id)): Extension<Merchant>,
+    Extension(key): Extension<ApiKey>,
+    Path(endpoint_id): Path<Uuid>,
+    req: Option<Json<RotateSecretRequest>>,
+) -> Result<Json<RotateSecretResponse>, (StatusCode, String)> {
+    let grace_period_secs = req
+        .and_then(|Json(req)| req.grace_period_secs)
+        .unwrap_or(DEFAULT_GRACE_PERIOD_SECS);
+
+    if grace_period_secs < 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "grace_period_secs must not be negative".to_string(),
+        ));
+    }
+
+    let secret = webhook_signing::generate_secret();
+    let previous_secret_expires_at = Utc::now() + Duration::seconds(grace_period_secs);
+
+    let mut tx = key
+        .begin(&state.db)
+        .await
+        .map_err(|e| database_error("rotate secret", e))?;
+    let result = sqlx::query(
+        r#"
+        UPDATE merchant_endpoints
+        SET previous_secret = secret,
+            secret = $1,
+            previous_secret_expires_at = $2,
+            updated_at = NOW()
+        WHERE id = $3 AND merchant_id = $4
+        "#,
+    )
+    .bind(&secret)
+    .bind(previous_secret_expires_at)
+    .bind(endpoint_id)
+    .bind(merchant_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| database_error("rotate secret", e))?;
+
+    if result.rows_affected() == 0 {
+        return Err(not_found(endpoint_id));
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| database_error("rotate secret", e))?;
+    info!(
+        "Merchant {} rotated the secret of endpoint {} (previous secret valid until {})",
+        merchant_id, endpoint_id, previous_secret_expires_at
+    );
+
+    Ok(Json(RotateSecretResponse {
+        id: endpoint_id,
+        secret,
+        previous_secret_expires_at,
+    }))
+}
+
+/// Sign and deliver a test event; disabled endpoints can be tested too, so
+/// they can be checked before they're re-enabled
+pub async fn send_test_event(
+    State(state): State<AppState>,
+    Extension(Merchant(merchant_id)): Extension<Merchant>,
+    Path(endpoint_id): Path<Uuid>,
+    req: Option<Json<TestEventRequest>>,
+) -> Result<Json<TestEventResponse>, (StatusCode, String)> {
+    let event_type = req
+        .and_then(|Json(req)| req.event_type)
+        .unwrap_or_else(|| DEFAULT_TEST_EVENT_TYPE.to_string());
+
+    let endpoint = sqlx::query_as::<_, (String, String, Option<String>, Option<DateTime<Utc>>)>(
+        r#"
+        SELECT url, secret, previous_secret, previous_secret_expires_at
+        FROM merchant_endpoints
+        WHERE id = $1 AND merchant_id = $2
+        "#,
+    )
+    .bind(endpoint_id)
+    .bind(merchant_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| database_error("fetch endpoint", e))?;
+
+    let Some((url, secret, previous_secret, previous_secret_expires_at)) = endpoint else {
+        return Err(not_found(endpoint_id));
+    };
+
+    let msg_id = Uuid::new_v4();
+    let body = serde_json::to_vec(&serde_json::json!({
+        "event_id": msg_id,
+        "event_type": event_type,
+        "merchant_id": merchant_id,
+        "test": true,
+        "payment": {},
+    }))
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let secrets = webhook_signing::active_secrets(
+        &secret,
+        previous_secret.as_deref(),
+        previous_secret_expires_at,
+    );
+    let timestamp = Utc::now().timestamp();
+    let signature = webhook_signing::sign(&secrets, &msg_id.to_string(), timestamp, &body)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    let request = state
+        .http
+        .post(&url)
+        .header("content-type", "application/json")
+        .header(WEBHOOK_HEADERS.id, msg_id.to_string())
+        .header(WEBHOOK_HEADERS.timestamp, timestamp.to_string())
+        .header(WEBHOOK_HEADERS.signature, signature)
+        .body(body)
+        .timeout(state.test_webhook_timeout);
+    let started = std::time::Instant::now();
+    let response = state.http.send(request).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to deliver test event: {}", e),
+        )
+    })?;
+    let status_code = response.status().as_u16();
+    let response_body = truncate_body(response.text().await.unwrap_or_default());
+
+    info!(
+        "Test event to endpoint {} of merchant {} returned {}",
+        endpoint_id, merchant_id, status_code
+    );
+
+    Ok(Json(TestEventResponse {
+        msg_id,
+        event_type,
+        status_code,
+        response_body,
+        signatures: secrets.len(),
+        duration_ms: started.elapsed().as_millis(),
+    }))
+}
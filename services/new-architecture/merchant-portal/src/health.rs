@@ -0,0 +1,32 @@
+use health_checks::{checks, HealthRegistry};
+use sqlx::PgPool;
+
+// ==============================================================================
+// HEALTH: /livez for "is the process alive", /readyz for "should I get traffic"
+// ==============================================================================
+//
+// Everything the portal serves comes from Postgres, so readiness is the
+// database answering, the pool having room, and the portal's tables existing.
+
+/// A missing one means init.sql hasn't been applied to this database yet
+const REQUIRED_TABLES: &[&str] = &[
+    "merchants",
+    "merchant_api_keys",
+    "merchant_endpoints",
+    "domain_events",
+    "delivery_attempts",
+    "endpoint_deliveries",
+    "audit_log",
+];
+
+pub fn registry(db: &PgPool, max_pool_utilization: f64) -> HealthRegistry {
+    let (database, pool, migrations) = (db.clone(), db.clone(), db.clone());
+    HealthRegistry::new()
+        .readiness("database", move || checks::postgres(database.clone()))
+        .readiness("pool", move || {
+            checks::pool(pool.clone(), max_pool_utilization)
+        })
+        .readiness("migrations", move || {
+            checks::tables(migrations.clone(), REQUIRED_TABLES)
+        })
+}
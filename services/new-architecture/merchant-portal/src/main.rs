@@ -0,0 +1,147 @@
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use http_client::{EgressPolicy, HttpClient, HttpClientConfig, HttpMetrics};
+use service_metrics::ServiceMetrics;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+mod auth;
+mod deliveries;
+mod endpoints;
+mod health;
+mod settings;
+
+use settings::Settings;
+
+// ==============================================================================
+// MERCHANT PORTAL: The API a merchant dashboard is built on
+// ==============================================================================
+//
+// Everything under /v1 is authenticated with a merchant API key (auth.rs) and
+// scoped to that merchant:
+//
+//   /v1/endpoints                      list, create
+//   /v1/endpoints/:id                  get, update (url, disabled, filter), delete
+//   /v1/endpoints/:id/rotate-secret    new secret, old one valid for a grace period
+//   /v1/endpoints/:id/test             send a signed test event, see the response
+//   /v1/deliveries                     recent events and their delivery status
+//   /v1/deliveries/:event_id           one event with every attempt and response
+//   /v1/deliveries/:event_id/retry     replay a failed delivery
+//
+// The portal reads and writes the same tables as the rest of the pipeline,
+// and has no delivery path of its own: a retry is a new outbox row, picked up
+// like any other event.
+
+/// Longer response bodies are cut before they're returned
+const MAX_RESPONSE_BODY_BYTES: usize = 4096;
+
+#[derive(Clone)]
+struct AppState {
+    db: PgPool,
+    /// Test events to merchant endpoints (http-client crate), under `egress`
+    http: HttpClient,
+    test_webhook_timeout: Duration,
+    /// Where endpoint URLs may point
+    egress: Arc<EgressPolicy>,
+}
+
+fn truncate_body(mut body: String) -> String {
+    if body.len() > MAX_RESPONSE_BODY_BYTES {
+        let mut end = MAX_RESPONSE_BODY_BYTES;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        body.truncate(end);
+    }
+    body
+}
+
+#[tokio::main]
+async fn main() {
+    logging::init(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    let settings: Settings = config::load_or_exit("merchant-portal");
+    // Validated to be set
+    let database_url = settings.database_url.clone().unwrap_or_default();
+
+    let pool = PgPoolOptions::new()
+        .max_connections(20)
+        .connect(&database_url)
+        .await
+        .expect("Failed to connect to database");
+
+    let health = health::registry(&pool, settings.readiness_max_pool_utilization);
+
+    // Validated to parse
+    let egress = settings.egress_policy().expect("Invalid egress policy");
+
+    let metrics = ServiceMetrics::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    let http = HttpClient::new(
+        HttpClientConfig {
+            egress: Some(egress.clone()),
+            ..HttpClientConfig::new("test_event")
+        },
+        &HttpMetrics::new(metrics.registry()),
+    )
+    .expect("Failed to build HTTP client");
+
+    let state = AppState {
+        db: pool,
+        http,
+        test_webhook_timeout: Duration::from_secs(settings.test_webhook_timeout_secs),
+        egress: Arc::new(egress),
+    };
+
+    let portal_routes = Router::new()
+        .route(
+            "/v1/endpoints",
+            get(endpoints::list_endpoints).post(endpoints::create_endpoint),
+        )
+        .route(
+            "/v1/endpoints/:id",
+            get(endpoints::get_endpoint)
+                .patch(endpoints::update_endpoint)
+                .delete(endpoints::delete_endpoint),
+        )
+        .route(
+            "/v1/endpoints/:id/rotate-secret",
+            post(endpoints::rotate_secret),
+        )
+        .route("/v1/endpoints/:id/test", post(endpoints::send_test_event))
+        .route("/v1/deliveries", get(deliveries::list_deliveries))
+        .route("/v1/deliveries/:event_id", get(deliveries::get_delivery))
+        .route(
+            "/v1/deliveries/:event_id/retry",
+            post(deliveries::retry_delivery),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_api_key,
+        ));
+
+    let app = Router::new()
+        .merge(portal_routes)
+        .merge(health_checks::router(health))
+        .merge(service_metrics::router(metrics.clone()))
+        .route_layer(middleware::from_fn_with_state(
+            metrics,
+            service_metrics::track_http,
+        ))
+        .with_state(state);
+
+    let port = settings.port;
+
+    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port))
+        .await
+        .unwrap();
+
+    info!("MERCHANT PORTAL listening on port {}", port);
+
+    axum::serve(listener, app).await.unwrap();
+}
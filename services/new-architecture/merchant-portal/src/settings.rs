@@ -0,0 +1,68 @@
+use http_client::EgressPolicy;
+use serde::{Deserialize, Serialize};
+
+// ==============================================================================
+// SETTINGS: Everything main.rs reads at startup
+// ==============================================================================
+//
+// Loaded through the shared config crate: defaults below, then
+// config/merchant-portal.toml (or CONFIG_FILE), then the environment variable
+// of the same name in upper case.
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Required
+    pub database_url: Option<String>,
+    /// /readyz fails once this fraction of the pool is checked out
+    pub readiness_max_pool_utilization: f64,
+    /// How long a test event waits for the merchant's endpoint
+    pub test_webhook_timeout_secs: u64,
+    pub port: u16,
+    /// CIDRs endpoint URLs may reach although they aren't public, e.g. a
+    /// local network in development (http-client's egress policy)
+    pub egress_allowed_networks: String,
+    /// Ports and ranges endpoint URLs may use
+    pub egress_allowed_ports: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            database_url: None,
+            readiness_max_pool_utilization: 0.9,
+            test_webhook_timeout_secs: 5,
+            port: 3007,
+            egress_allowed_networks: String::new(),
+            egress_allowed_ports: http_client::ALLOWED_PORTS.to_string(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn egress_policy(&self) -> Result<EgressPolicy, String> {
+        EgressPolicy::parse(&self.egress_allowed_networks, &self.egress_allowed_ports)
+    }
+}
+
+impl config::Validate for Settings {
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.database_url.is_none() {
+            problems.push("DATABASE_URL: required".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.readiness_max_pool_utilization) {
+            problems.push(format!(
+                "READINESS_MAX_POOL_UTILIZATION: {} is not between 0 and 1",
+                self.readiness_max_pool_utilization
+            ));
+        }
+        if self.test_webhook_timeout_secs == 0 {
+            problems.push("TEST_WEBHOOK_TIMEOUT_SECS: must be at least 1".to_string());
+        }
+        if let Err(e) = self.egress_policy() {
+            problems.push(format!("EGRESS_ALLOWED_NETWORKS/EGRESS_ALLOWED_PORTS: {}", e));
+        }
+        problems
+    }
+}
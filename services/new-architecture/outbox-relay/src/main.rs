@@ -0,0 +1,202 @@
+use axum::{middleware, Router};
+use event_transport::amqp::AmqpPublisher;
+use event_transport::aws::AwsClient;
+use event_transport::nats::{JetStreamPublisher, NatsMetrics};
+use event_transport::redis_streams::RedisPublisher;
+use event_transport::sqs::{SnsPublisher, SqsPublisher};
+use event_transport::EventPublisher;
+use health_checks::{checks, HealthRegistry};
+use http_client::HttpMetrics;
+use kafka_producer::{Producer, ProducerConfig, ProducerMetrics};
+use redaction::Redactor;
+use service_metrics::ServiceMetrics;
+use sqlx::postgres::PgPoolOptions;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+mod metrics;
+mod relay;
+mod settings;
+
+use metrics::Metrics;
+use relay::Relay;
+use settings::Settings;
+
+// ==============================================================================
+// OUTBOX RELAY: domain_events -> Kafka, without Sequin
+// ==============================================================================
+//
+// api-service writes each payment and its domain_events row in one
+// transaction (the outbox); this publishes the rows to KAFKA_TOPIC in id
+// order, keyed by merchant_id, for Restate's subscription to hand to
+// svix-caller (relay.rs). It takes the place of Sequin's WAL -> Kafka sink,
+// which needed a replication slot and browser setup (SEQUIN_SETUP.md).
+//
+// TRANSPORT=nats publishes to a JetStream stream of the same name instead,
+// for deployments without Kafka; webhook-consumer then takes the place of
+// Restate's Kafka subscription. TRANSPORT=sqs sends to an SQS queue of that
+// name (topic.fifo), and TRANSPORT=sns publishes to an SNS topic the queue
+// subscribes to, for deployments on AWS-managed messaging. TRANSPORT=amqp
+// publishes to a RabbitMQ exchange of that name, routed by merchant_id, and
+// TRANSPORT=redis appends to a Redis stream of that name, for local
+// development and small deployments.
+//
+// Run as many as you like; one holds the lock and publishes, the rest stand
+// by. Health and metrics are on PORT (default 3003).
+
+#[tokio::main]
+async fn main() {
+    logging::init(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    let settings: Settings = config::load_or_exit("outbox-relay");
+    // Validated to be set
+    let database_url = settings.database_url.clone().unwrap_or_default();
+
+    // The relay's own connection (the lock holder), the listener, and probes
+    let db = PgPoolOptions::new()
+        .max_connections(4)
+        .connect(&database_url)
+        .await
+        .expect("Failed to connect to database");
+
+    let metrics = Metrics::new(ServiceMetrics::new(
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+    ));
+    // The broker the readiness check connects to
+    let (publisher, brokers): (Arc<dyn EventPublisher>, Vec<String>) = match settings
+        .transport
+        .as_str()
+    {
+        "nats" => {
+            let publisher = JetStreamPublisher::connect(
+                &settings.nats_url,
+                &[&settings.kafka_topic],
+                &NatsMetrics::new(metrics.service().registry()),
+            )
+            .await
+            .expect("Failed to set up JetStream");
+            (Arc::new(publisher), addresses(&settings.nats_url))
+        }
+        "amqp" => {
+            let publisher = AmqpPublisher::connect(&settings.amqp_url, &[&settings.kafka_topic])
+                .await
+                .expect("Failed to set up RabbitMQ");
+            (Arc::new(publisher), vec![url_address(&settings.amqp_url)])
+        }
+        "redis" => {
+            let publisher = RedisPublisher::connect(&settings.redis_url, settings.redis_max_len)
+                .await
+                .expect("Failed to set up Redis Streams");
+            (Arc::new(publisher), vec![url_address(&settings.redis_url)])
+        }
+        "sqs" | "sns" => {
+            // Validated with the settings
+            let config = settings.aws().expect("Invalid AWS settings");
+            let endpoint = config.endpoint(&settings.transport);
+            let client = AwsClient::new(config, &HttpMetrics::new(metrics.service().registry()))
+                .expect("Failed to build HTTP client");
+            let topics = [settings.kafka_topic.as_str()];
+            let publisher: Arc<dyn EventPublisher> = if settings.transport == "sqs" {
+                Arc::new(
+                    SqsPublisher::connect(client, &topics, settings.sqs_fifo)
+                        .await
+                        .expect("Failed to set up SQS"),
+                )
+            } else {
+                Arc::new(
+                    SnsPublisher::connect(client, &topics, settings.sqs_fifo)
+                        .await
+                        .expect("Failed to set up SNS"),
+                )
+            };
+            (publisher, vec![url_address(&endpoint)])
+        }
+        _ => {
+            let producer = Producer::new(
+                &ProducerConfig::new(&settings.kafka_brokers, "outbox-relay"),
+                &ProducerMetrics::new(metrics.service().registry()),
+            )
+            .expect("Invalid Kafka configuration");
+            (Arc::new(producer), addresses(&settings.kafka_brokers))
+        }
+    };
+
+    // Validated to load; if the file broke since, stop rather than run
+    // without redaction
+    let redactor = settings
+        .redaction_rules
+        .as_deref()
+        .map(Redactor::load)
+        .transpose()
+        .expect("Invalid redaction rules");
+    if let Some(path) = &settings.redaction_rules {
+        info!("Redacting payloads with {}", path.display());
+    }
+
+    let relay = Relay {
+        db: db.clone(),
+        publisher,
+        topic: settings.kafka_topic.clone(),
+        batch_size: settings.batch_size,
+        poll_interval: Duration::from_millis(settings.poll_interval_ms),
+        max_backoff: Duration::from_millis(settings.max_backoff_ms),
+        redactor,
+        metrics: metrics.clone(),
+    };
+    tokio::spawn(relay.run());
+
+    let health = HealthRegistry::new()
+        .readiness("database", move || checks::postgres(db.clone()))
+        .readiness(&settings.transport, move || checks::tcp(brokers.clone()));
+
+    let app = Router::new()
+        .merge(health_checks::router(health))
+        .merge(service_metrics::router(metrics.service().clone()))
+        .route_layer(middleware::from_fn_with_state(
+            metrics.service().clone(),
+            service_metrics::track_http,
+        ));
+
+    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", settings.port))
+        .await
+        .unwrap();
+
+    info!("OUTBOX RELAY health and metrics on port {}", settings.port);
+
+    axum::serve(listener, app).await.unwrap();
+}
+
+/// host:port of each server in a comma-separated list, schemes dropped
+fn addresses(servers: &str) -> Vec<String> {
+    servers
+        .split(',')
+        .map(|server| {
+            let server = server.trim();
+            server
+                .split_once("://")
+                .map_or(server, |(_, address)| address)
+                .to_string()
+        })
+        .collect()
+}
+
+/// host:port of a URL, credentials and path dropped, the port defaulted
+/// from the scheme
+fn url_address(url: &str) -> String {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("https", url));
+    let rest = rest.rsplit_once('@').map_or(rest, |(_, host)| host);
+    let host = rest.split('/').next().unwrap_or_default();
+    if host.contains(':') {
+        return host.to_string();
+    }
+    let port = match scheme {
+        "http" => 80,
+        "amqp" => 5672,
+        "amqps" => 5671,
+        "redis" | "rediss" => 6379,
+        _ => 443,
+    };
+    format!("{}:{}", host, port)
+}
@@ -0,0 +1,109 @@
+use prometheus::{Gauge, IntCounter, IntGauge, Opts};
+use service_metrics::ServiceMetrics;
+
+// ==============================================================================
+// METRICS: How far behind the outbox is
+// ==============================================================================
+//
+// Next to the process and HTTP metrics every service has (service-metrics
+// crate) and the transport's kafka_* or nats_* families (kafka-producer,
+// event-transport crates):
+//
+//   outbox_rows_published_total          rows marked published
+//   outbox_publish_failures_total        batches that stopped at a failure
+//   outbox_backlog_rows                  unpublished rows, when last idle
+//   outbox_oldest_unpublished_seconds    age of the oldest of them
+//   outbox_leader                        1 on the instance holding the lock
+//   outbox_values_redacted_total         payload values and matches replaced
+//                                        by REDACTION_RULES
+
+#[derive(Clone)]
+pub struct Metrics {
+    service: ServiceMetrics,
+    published: IntCounter,
+    failures: IntCounter,
+    backlog: IntGauge,
+    oldest: Gauge,
+    leader: IntGauge,
+    redacted: IntCounter,
+}
+
+impl Metrics {
+    pub fn new(service: ServiceMetrics) -> Self {
+        let registry = service.registry();
+
+        let published = IntCounter::with_opts(Opts::new(
+            "outbox_rows_published_total",
+            "domain_events rows published to the broker and marked",
+        ))
+        .unwrap();
+        let failures = IntCounter::with_opts(Opts::new(
+            "outbox_publish_failures_total",
+            "Batches that stopped at a row the broker didn't acknowledge",
+        ))
+        .unwrap();
+        let backlog = IntGauge::with_opts(Opts::new(
+            "outbox_backlog_rows",
+            "Unpublished domain_events rows",
+        ))
+        .unwrap();
+        let oldest = Gauge::with_opts(Opts::new(
+            "outbox_oldest_unpublished_seconds",
+            "Age of the oldest unpublished row, 0 when there is none",
+        ))
+        .unwrap();
+        let leader = IntGauge::with_opts(Opts::new(
+            "outbox_leader",
+            "1 while this instance holds the relay lock",
+        ))
+        .unwrap();
+
+        let redacted = IntCounter::with_opts(Opts::new(
+            "outbox_values_redacted_total",
+            "Payload values and matches replaced before publishing (REDACTION_RULES)",
+        ))
+        .unwrap();
+
+        registry.register(Box::new(published.clone())).unwrap();
+        registry.register(Box::new(failures.clone())).unwrap();
+        registry.register(Box::new(backlog.clone())).unwrap();
+        registry.register(Box::new(oldest.clone())).unwrap();
+        registry.register(Box::new(leader.clone())).unwrap();
+        registry.register(Box::new(redacted.clone())).unwrap();
+
+        Metrics {
+            service,
+            published,
+            failures,
+            backlog,
+            oldest,
+            leader,
+            redacted,
+        }
+    }
+
+    pub fn service(&self) -> &ServiceMetrics {
+        &self.service
+    }
+
+    pub fn published(&self, rows: usize) {
+        self.published.inc_by(rows as u64);
+    }
+
+    pub fn failed(&self) {
+        self.failures.inc();
+    }
+
+    pub fn backlog(&self, rows: i64, oldest_secs: f64) {
+        self.backlog.set(rows);
+        self.oldest.set(oldest_secs);
+    }
+
+    pub fn leader(&self, leader: bool) {
+        self.leader.set(leader as i64);
+    }
+
+    pub fn redacted(&self, values: usize) {
+        self.redacted.inc_by(values as u64);
+    }
+}
@@ -0,0 +1,284 @@
+use chrono::{DateTime, Utc};
+use event_transport::{EventPublisher, OutgoingMessage};
+use redaction::Redactor;
+use sqlx::postgres::{PgConnection, PgListener};
+use sqlx::{Connection, PgPool};
+use std::sync::Arc;
+use std::time::Duration;
+use webhook_types::DomainEvent;
+
+use crate::metrics::Metrics;
+
+// ==============================================================================
+// RELAY: Unpublished domain_events rows -> Kafka (or JetStream), in id order
+// ==============================================================================
+//
+//   1. Take the relay lock (a Postgres advisory lock). One instance publishes,
+//      the others wait to take over: two relays reading the same rows would
+//      interleave a merchant's events on the topic.
+//   2. Read up to BATCH_SIZE rows WHERE published_at IS NULL, oldest first,
+//      leaving out rows newer than the oldest transaction still running.
+//   3. Publish them keyed by merchant_id and wait for every acknowledgement,
+//      payloads redacted on the way when REDACTION_RULES is set (redaction
+//      crate); the rows themselves keep what the trigger wrote.
+//   4. Mark the rows up to the first one the broker didn't acknowledge
+//      (published_at = NOW()); that one and everything after it go again in
+//      the next batch, after a backoff. Skipping past it would publish a
+//      merchant's later event before its earlier one.
+//   5. A full batch goes again at once; otherwise wait for the
+//      domain_event_created NOTIFY (init.sql), or POLL_INTERVAL_MS in case
+//      one was missed while the listener reconnected.
+//
+// Each row is marked once, and only after the broker has it. A crash between
+// the acknowledgement and the mark publishes those rows again on restart, so
+// the topic is at-least-once: consumers dedupe on the event id (svix-caller
+// submits to Svix under a stable message id). On JetStream the event id is
+// also the message id, so a republish within the stream's duplicate window
+// isn't stored twice.
+//
+// Ids are taken when a row is inserted, not when it commits, so a transaction
+// that took id 5 can commit after the one that took id 6. As in the event
+// feed (api-service feed.rs), step 2 only reads rows written before the
+// oldest transaction still running (their xmin against the snapshot's), and
+// holds back later ones until that transaction is over; otherwise 6 would go
+// out first and 5 after it. The payments trigger writes its event after the
+// payment row, so the transaction already has its xid when it takes the id; a
+// lone INSERT gets its xid just after, and only two of those racing within
+// that instant can still come out of order. The cost is that a long-running
+// writing transaction delays publishing for as long as it stays open.
+//
+// The lock and the reads and marks share one connection, held for as long as
+// the instance leads. If it drops, so does the lock, and the relay goes back
+// to step 1 rather than publish without it.
+
+pub const NOTIFY_CHANNEL: &str = "domain_event_created";
+
+/// pg_advisory_lock key; any constant works, as long as every relay uses it
+const RELAY_LOCK_KEY: i64 = 0x6f7574626f78; // "outbox"
+
+#[derive(sqlx::FromRow)]
+struct OutboxRow {
+    id: i64,
+    event_type: String,
+    object_id: String,
+    merchant_id: String,
+    mode: String,
+    payload: Option<serde_json::Value>,
+    replay_of: Option<i64>,
+    schema_version: i32,
+    created_at: Option<DateTime<Utc>>,
+}
+
+impl OutboxRow {
+    /// The row as a message: the shape Sequin published, which SvixCaller reads
+    fn event(&self) -> DomainEvent {
+        DomainEvent {
+            schema_version: self.schema_version as u32,
+            id: self.id as u64,
+            event_type: self.event_type.clone(),
+            object_id: self.object_id.clone(),
+            merchant_id: self.merchant_id.clone(),
+            mode: self.mode.clone(),
+            payload: self.payload.clone().unwrap_or(serde_json::Value::Null),
+            replay_of: self.replay_of.map(|id| id as u64),
+            created_at: self.created_at.map(|at| at.to_rfc3339()),
+        }
+    }
+}
+
+pub struct Relay {
+    pub db: PgPool,
+    pub publisher: Arc<dyn EventPublisher>,
+    pub topic: String,
+    pub batch_size: i64,
+    pub poll_interval: Duration,
+    pub max_backoff: Duration,
+    /// Set with REDACTION_RULES
+    pub redactor: Option<Redactor>,
+    pub metrics: Metrics,
+}
+
+impl Relay {
+    pub async fn run(self) {
+        loop {
+            let mut conn = self.lead().await;
+            self.metrics.leader(true);
+            if let Err(e) = self.relay(&mut conn).await {
+                tracing::error!("Lost the relay connection, stepping down: {}", e);
+            }
+            self.metrics.leader(false);
+            // Closing the connection releases the lock, if it is still held
+            let _ = conn.close().await;
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// A connection holding the relay lock, waiting as long as it takes
+    async fn lead(&self) -> PgConnection {
+        let mut waiting_logged = false;
+        loop {
+            let attempt = async {
+                let mut conn = self.db.acquire().await?.detach();
+                let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+                    .bind(RELAY_LOCK_KEY)
+                    .fetch_one(&mut conn)
+                    .await?;
+                Ok::<_, sqlx::Error>(locked.then_some(conn))
+            };
+            match attempt.await {
+                Ok(Some(conn)) => {
+                    tracing::info!("Holding the relay lock, publishing to {}", self.topic);
+                    return conn;
+                }
+                Ok(None) if !waiting_logged => {
+                    tracing::info!("Another relay holds the lock, standing by");
+                    waiting_logged = true;
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Failed to take the relay lock: {}", e),
+            }
+            tokio::time::sleep(self.poll_interval * 5).await;
+        }
+    }
+
+    /// Publish until the connection fails
+    async fn relay(&self, conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+        let mut listener = match PgListener::connect_with(&self.db).await {
+            Ok(mut listener) => match listener.listen(NOTIFY_CHANNEL).await {
+                Ok(()) => Some(listener),
+                Err(e) => {
+                    tracing::warn!("LISTEN failed, polling only: {}", e);
+                    None
+                }
+            },
+            Err(e) => {
+                tracing::warn!("LISTEN failed, polling only: {}", e);
+                None
+            }
+        };
+
+        let mut failures = 0u32;
+        loop {
+            let rows = self.fetch(conn).await?;
+            let fetched = rows.len();
+
+            let published = self.publish(&rows).await;
+            if published > 0 {
+                let ids: Vec<i64> = rows[..published].iter().map(|row| row.id).collect();
+                sqlx::query("UPDATE domain_events SET published_at = NOW() WHERE id = ANY($1)")
+                    .bind(&ids)
+                    .execute(&mut *conn)
+                    .await?;
+                self.metrics.published(published);
+            }
+
+            if published < fetched {
+                failures += 1;
+                self.metrics.failed();
+                let delay = self.backoff(failures);
+                tracing::warn!(
+                    "Published {} of {} rows, retrying from event {} in {:?}",
+                    published,
+                    fetched,
+                    rows[published].id,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+            failures = 0;
+
+            if fetched as i64 == self.batch_size {
+                continue;
+            }
+            self.sample_backlog(conn).await?;
+            match &mut listener {
+                Some(listener) => {
+                    // Err: nothing arrived within the interval; poll anyway
+                    if let Ok(Err(e)) =
+                        tokio::time::timeout(self.poll_interval, listener.recv()).await
+                    {
+                        tracing::warn!("NOTIFY listener failed, reconnecting: {}", e);
+                    }
+                }
+                None => tokio::time::sleep(self.poll_interval).await,
+            }
+        }
+    }
+
+    async fn fetch(&self, conn: &mut PgConnection) -> Result<Vec<OutboxRow>, sqlx::Error> {
+        sqlx::query_as::<_, OutboxRow>(
+            r#"
+            SELECT id, event_type, object_id::TEXT AS object_id,
+                   merchant_id::TEXT AS merchant_id, mode, payload, replay_of,
+                   schema_version, created_at
+            FROM domain_events
+            WHERE published_at IS NULL
+              -- Written before every transaction still running; age() is
+              -- negative for rows frozen more than 2^31 transactions ago
+              AND (age(xmin) > age(pg_snapshot_xmin(pg_current_snapshot())::TEXT::XID)
+                   OR age(xmin) < 0)
+            ORDER BY id
+            LIMIT $1
+            "#,
+        )
+        .bind(self.batch_size)
+        .fetch_all(conn)
+        .await
+    }
+
+    /// Send every row, then count the acknowledged ones before the first
+    /// failure. Sent together, so the batch costs one round of
+    /// acknowledgements; both transports keep a key's messages in order.
+    async fn publish(&self, rows: &[OutboxRow]) -> usize {
+        let mut redacted = 0;
+        let messages: Vec<(DomainEvent, String, Vec<u8>)> = rows
+            .iter()
+            .map(|row| {
+                let mut event = row.event();
+                if let Some(redactor) = &self.redactor {
+                    redacted += redactor.apply(&event.event_type, &mut event.payload);
+                }
+                // A DomainEvent always serializes
+                let payload = serde_json::to_vec(&event).unwrap_or_default();
+                (event, format!("evt_{}", row.id), payload)
+            })
+            .collect();
+        self.metrics.redacted(redacted);
+        let sends = messages.iter().map(|(event, id, payload)| {
+            self.publisher.publish(OutgoingMessage {
+                topic: &self.topic,
+                key: &event.merchant_id,
+                id: Some(id),
+                payload,
+                headers: &[],
+            })
+        });
+        let results = futures::future::join_all(sends).await;
+        results
+            .iter()
+            .position(|result| result.is_err())
+            .unwrap_or(results.len())
+    }
+
+    async fn sample_backlog(&self, conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+        let (rows, oldest_secs): (i64, Option<f64>) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*), EXTRACT(EPOCH FROM NOW() - MIN(created_at))::FLOAT8
+            FROM domain_events
+            WHERE published_at IS NULL
+            "#,
+        )
+        .fetch_one(conn)
+        .await?;
+        self.metrics.backlog(rows, oldest_secs.unwrap_or(0.0));
+        Ok(())
+    }
+
+    /// 100ms doubling per consecutive failure, up to MAX_BACKOFF_MS
+    fn backoff(&self, failures: u32) -> Duration {
+        Duration::from_millis(100)
+            .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+}
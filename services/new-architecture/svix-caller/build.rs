@@ -0,0 +1,8 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Vendored protoc, so neither local builds nor the Docker image need it installed
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_build::configure()
+        .build_server(false)
+        .compile_protos(&["../proto/payload.proto"], &["../proto"])?;
+    Ok(())
+}
@@ -0,0 +1,265 @@
+use restate_sdk::prelude::*;
+use svix::api::{AppPortalAccessIn, EndpointIn, EndpointOut, EndpointPatch};
+
+use crate::event_types::{self, EventTypeSync};
+use crate::routing::{SvixClient, SvixRouter};
+use crate::{handler_error, svix_app_uid, svix_error};
+
+// ==============================================================================
+// SVIX ADMIN: Endpoint management without handing out the Svix token
+// ==============================================================================
+//
+// The rest of the system manages merchant endpoints through these handlers
+// instead of embedding SVIX_AUTH_TOKEN itself. They are plain Restate service
+// handlers, called through the ingress like any other:
+//
+//   curl restate:8080/SvixAdmin/list_endpoints \
+//     -H 'content-type: application/json' -d '{"merchant_id": "..."}'
+//
+// Endpoint requests name the merchant and optionally the mode, which picks the
+// same live/test application that deliveries use. Rejected requests (4xx) are
+// terminal, so the caller gets them back instead of Restate retrying them;
+// Svix outages are retried like any other invocation.
+//
+// app_portal hands the merchant dashboard a short-lived App Portal link, so
+// merchants can manage their own endpoints in Svix's embedded UI.
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct MerchantRequest {
+    pub merchant_id: String,
+    /// "test" or "live" (default)
+    #[serde(default = "webhook_types::default_mode")]
+    pub mode: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct CreateEndpointRequest {
+    pub merchant_id: String,
+    #[serde(default = "webhook_types::default_mode")]
+    pub mode: String,
+    pub url: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Event types to deliver; all of them when unset
+    #[serde(default)]
+    pub event_types: Option<Vec<String>>,
+    /// Event categories to deliver ("payment", "quota"); all when unset
+    #[serde(default)]
+    pub channels: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct EndpointRequest {
+    pub merchant_id: String,
+    #[serde(default = "webhook_types::default_mode")]
+    pub mode: String,
+    pub endpoint_id: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct EndpointInfo {
+    pub id: String,
+    pub url: String,
+    pub description: String,
+    pub disabled: bool,
+    pub event_types: Option<Vec<String>>,
+    pub channels: Option<Vec<String>>,
+}
+
+impl From<EndpointOut> for EndpointInfo {
+    fn from(endpoint: EndpointOut) -> Self {
+        EndpointInfo {
+            id: endpoint.id,
+            url: endpoint.url,
+            description: endpoint.description,
+            disabled: endpoint.disabled.unwrap_or(false),
+            event_types: endpoint.filter_types,
+            channels: endpoint.channels,
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct EndpointSecret {
+    pub endpoint_id: String,
+    /// whsec_... signing secret merchants verify deliveries with
+    pub secret: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct AppPortalLink {
+    /// Magic link into Svix's App Portal, for an iframe or a redirect
+    pub url: String,
+    /// The same session as a bare token, for the App Portal React component
+    pub token: String,
+}
+
+#[restate_sdk::service]
+pub trait SvixAdmin {
+    async fn create_endpoint(req: Json<CreateEndpointRequest>) -> HandlerResult<Json<EndpointInfo>>;
+    async fn list_endpoints(req: Json<MerchantRequest>) -> HandlerResult<Json<Vec<EndpointInfo>>>;
+    async fn disable_endpoint(req: Json<EndpointRequest>) -> HandlerResult<Json<EndpointInfo>>;
+    async fn endpoint_secret(req: Json<EndpointRequest>) -> HandlerResult<Json<EndpointSecret>>;
+    /// One-time App Portal login for the merchant's application
+    async fn app_portal(req: Json<MerchantRequest>) -> HandlerResult<Json<AppPortalLink>>;
+    /// Register the event-type catalog (event_types.rs) with Svix
+    async fn sync_event_types() -> HandlerResult<Json<Vec<EventTypeSync>>>;
+}
+
+pub struct SvixAdminImpl {
+    pub router: SvixRouter,
+}
+
+/// The account holding the merchant's applications. Unlike deliveries, a
+/// lookup failure goes straight back to the caller.
+async fn client(router: &SvixRouter, merchant_id: &str) -> Result<SvixClient, TerminalError> {
+    router
+        .client_for(merchant_id)
+        .await
+        .map_err(|e| TerminalError::new(e.to_string()))
+}
+
+fn admin_error(action: &str, e: svix::error::Error) -> HandlerError {
+    tracing::warn!("Svix admin: failed to {}: {}", action, e);
+    handler_error(svix_error(&format!("Failed to {}", action), e))
+}
+
+impl SvixAdmin for SvixAdminImpl {
+    async fn create_endpoint(
+        &self,
+        ctx: Context<'_>,
+        req: Json<CreateEndpointRequest>,
+    ) -> HandlerResult<Json<EndpointInfo>> {
+        let req = req.0;
+        let app_id = svix_app_uid(&req.merchant_id, &req.mode);
+        let router = self.router.clone();
+
+        // Journaled, so a retried invocation doesn't create a second endpoint
+        let endpoint = ctx
+            .run(|| async move {
+                let endpoint = client(&router, &req.merchant_id)
+                    .await?
+                    .endpoint()
+                    .create(
+                        app_id.clone(),
+                        EndpointIn {
+                            url: req.url,
+                            description: req.description,
+                            filter_types: req.event_types,
+                            channels: req.channels,
+                            ..EndpointIn::default()
+                        },
+                        None,
+                    )
+                    .await
+                    .map_err(|e| admin_error("create endpoint", e))?;
+                tracing::info!("Created Svix endpoint {} for {}", endpoint.id, app_id);
+                Ok(Json(EndpointInfo::from(endpoint)))
+            })
+            .name("create_endpoint")
+            .await?;
+        Ok(endpoint)
+    }
+
+    async fn list_endpoints(
+        &self,
+        _ctx: Context<'_>,
+        req: Json<MerchantRequest>,
+    ) -> HandlerResult<Json<Vec<EndpointInfo>>> {
+        let app_id = svix_app_uid(&req.0.merchant_id, &req.0.mode);
+        let endpoints = client(&self.router, &req.0.merchant_id)
+            .await?
+            .endpoint()
+            .list(app_id, None)
+            .await
+            .map_err(|e| admin_error("list endpoints", e))?;
+        Ok(Json(
+            endpoints.data.into_iter().map(EndpointInfo::from).collect(),
+        ))
+    }
+
+    async fn disable_endpoint(
+        &self,
+        ctx: Context<'_>,
+        req: Json<EndpointRequest>,
+    ) -> HandlerResult<Json<EndpointInfo>> {
+        let req = req.0;
+        let app_id = svix_app_uid(&req.merchant_id, &req.mode);
+        let router = self.router.clone();
+
+        let endpoint = ctx
+            .run(|| async move {
+                let endpoint = client(&router, &req.merchant_id)
+                    .await?
+                    .endpoint()
+                    .patch(
+                        app_id.clone(),
+                        req.endpoint_id,
+                        EndpointPatch {
+                            disabled: Some(true),
+                            ..EndpointPatch::default()
+                        },
+                    )
+                    .await
+                    .map_err(|e| admin_error("disable endpoint", e))?;
+                tracing::info!("Disabled Svix endpoint {} for {}", endpoint.id, app_id);
+                Ok(Json(EndpointInfo::from(endpoint)))
+            })
+            .name("disable_endpoint")
+            .await?;
+        Ok(endpoint)
+    }
+
+    async fn endpoint_secret(
+        &self,
+        _ctx: Context<'_>,
+        req: Json<EndpointRequest>,
+    ) -> HandlerResult<Json<EndpointSecret>> {
+        let req = req.0;
+        let app_id = svix_app_uid(&req.merchant_id, &req.mode);
+        let secret = client(&self.router, &req.merchant_id)
+            .await?
+            .endpoint()
+            .get_secret(app_id, req.endpoint_id.clone())
+            .await
+            .map_err(|e| admin_error("fetch endpoint secret", e))?;
+        Ok(Json(EndpointSecret {
+            endpoint_id: req.endpoint_id,
+            secret: secret.key,
+        }))
+    }
+
+    async fn app_portal(
+        &self,
+        _ctx: Context<'_>,
+        req: Json<MerchantRequest>,
+    ) -> HandlerResult<Json<AppPortalLink>> {
+        // Not journaled: every call is meant to mint a fresh link
+        let app_id = svix_app_uid(&req.0.merchant_id, &req.0.mode);
+        let access = client(&self.router, &req.0.merchant_id)
+            .await?
+            .authentication()
+            .app_portal_access(app_id.clone(), AppPortalAccessIn::default(), None)
+            .await
+            .map_err(|e| admin_error("create App Portal link", e))?;
+        tracing::info!("Created App Portal link for {}", app_id);
+        Ok(Json(AppPortalLink {
+            url: access.url,
+            token: access.token,
+        }))
+    }
+
+    async fn sync_event_types(&self, ctx: Context<'_>) -> HandlerResult<Json<Vec<EventTypeSync>>> {
+        let router = self.router.clone();
+        let report = ctx
+            .run(|| async move {
+                let report = event_types::sync_all(&router)
+                    .await
+                    .map_err(handler_error)?;
+                Ok(Json(report))
+            })
+            .name("sync_event_types")
+            .await?;
+        Ok(report)
+    }
+}
@@ -0,0 +1,156 @@
+use serde_json::{json, Value};
+use std::time::Duration;
+
+// ==============================================================================
+// RESTATE BOOTSTRAP: Register this deployment and its Kafka subscription
+// ==============================================================================
+//
+// Restate's Kafka ingress reads the `webhook-events` topic itself and invokes
+// SvixCaller/process for every record, keyed by the record key, so nothing
+// has to sit between Kafka and Restate. It only needs two things from the
+// admin API, which svix-caller now does on startup when RESTATE_ADMIN_URL is
+// set (replacing scripts/register-restate-handler.sh):
+//
+//   1. POST /deployments   - Restate discovers the handlers served here
+//   2. POST /subscriptions - kafka://<cluster>/<topic> -> SvixCaller/process
+//
+// The Kafka cluster itself is declared in infrastructure/restate/restate.toml.
+// Both steps are idempotent: an existing subscription is left alone, so
+// restarts don't stack up duplicates that would deliver every event twice.
+
+const HANDLER: &str = "SvixCaller/process";
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_ATTEMPTS: u32 = 30;
+
+pub struct Bootstrap {
+    client: reqwest::Client,
+    admin_url: String,
+    /// How Restate reaches this service
+    deployment_url: String,
+    /// kafka://<cluster>/<topic>
+    source: String,
+    /// Replace a deployment registered at the same URL whose handlers have
+    /// changed since; without it Restate rejects the new revision
+    force: bool,
+}
+
+impl Bootstrap {
+    /// None unless RESTATE_ADMIN_URL is set
+    pub fn from_env(port: &str) -> Result<Option<Self>, String> {
+        let Ok(admin_url) = std::env::var("RESTATE_ADMIN_URL") else {
+            return Ok(None);
+        };
+        let deployment_url = std::env::var("RESTATE_DEPLOYMENT_URL")
+            .unwrap_or_else(|_| format!("http://svix-caller:{}", port));
+        let cluster = std::env::var("KAFKA_CLUSTER").unwrap_or_else(|_| "local".to_string());
+        let topic = std::env::var("KAFKA_TOPIC").unwrap_or_else(|_| "webhook-events".to_string());
+
+        // The admin API is HTTP/1.1 only
+        let client = reqwest::Client::builder()
+            .http1_only()
+            .timeout(ATTEMPT_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+
+        Ok(Some(Bootstrap {
+            client,
+            admin_url: admin_url.trim_end_matches('/').to_string(),
+            deployment_url,
+            source: format!("kafka://{}/{}", cluster, topic),
+            force: std::env::var("RESTATE_FORCE_DEPLOYMENT").as_deref() == Ok("true"),
+        }))
+    }
+
+    /// Retried every 2s while Restate (or this service's listener) comes up
+    pub async fn run(self) {
+        for attempt in 1..=MAX_ATTEMPTS {
+            match self.register().await {
+                Ok(()) => return,
+                Err(e) => {
+                    tracing::warn!(
+                        "Restate bootstrap failed (attempt {}/{}): {}",
+                        attempt,
+                        MAX_ATTEMPTS,
+                        e
+                    );
+                    tokio::time::sleep(Duration::from_secs(2)).await;
+                }
+            }
+        }
+        tracing::error!(
+            "Giving up on Restate bootstrap; run scripts/register-restate-handler.sh by hand"
+        );
+    }
+
+    async fn register(&self) -> Result<(), String> {
+        let deployment = self
+            .post(
+                "deployments",
+                json!({ "uri": self.deployment_url, "force": self.force }),
+            )
+            .await?;
+        tracing::info!(
+            "Registered {} with Restate (deployment {})",
+            self.deployment_url,
+            deployment["id"].as_str().unwrap_or("unknown")
+        );
+
+        let sink = format!("service://{}", HANDLER);
+        let existing = self.get("subscriptions").await?;
+        let subscribed = existing["subscriptions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .any(|subscription| {
+                subscription["source"] == self.source.as_str() && subscription["sink"] == sink
+            });
+        if subscribed {
+            tracing::info!("Restate already subscribes {} to {}", HANDLER, self.source);
+            return Ok(());
+        }
+
+        self.post(
+            "subscriptions",
+            json!({
+                "source": self.source,
+                "sink": sink,
+                "options": { "auto.offset.reset": "earliest" },
+            }),
+        )
+        .await?;
+        tracing::info!("Subscribed {} to {}", HANDLER, self.source);
+        Ok(())
+    }
+
+    async fn get(&self, path: &str) -> Result<Value, String> {
+        let url = format!("{}/{}", self.admin_url, path);
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| format!("GET {}: {}", url, e))?;
+        Self::json(&url, response).await
+    }
+
+    async fn post(&self, path: &str, body: Value) -> Result<Value, String> {
+        let url = format!("{}/{}", self.admin_url, path);
+        let response = self
+            .client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("POST {}: {}", url, e))?;
+        Self::json(&url, response).await
+    }
+
+    async fn json(url: &str, response: reqwest::Response) -> Result<Value, String> {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            return Err(format!("{} returned {}: {}", url, status, body));
+        }
+        serde_json::from_str(&body).map_err(|e| format!("Invalid response from {}: {}", url, e))
+    }
+}
@@ -0,0 +1,65 @@
+use serde_json::Value;
+use sqlx::PgPool;
+use std::time::Duration;
+
+use crate::errors::CallError;
+
+// ==============================================================================
+// CONFIRMATIONS: Waiting for the merchant to say it processed the event
+// ==============================================================================
+//
+// Svix (or a direct delivery) succeeding only means the merchant's endpoint
+// answered 2xx. Merchants with merchants.confirmation_timeout_secs set also
+// acknowledge that they processed the event:
+//
+//   1. svix-caller creates a Restate awakeable and puts its id in the body as
+//      `confirmation_id` (before the payload template, which can move it)
+//   2. the webhook is delivered as usual
+//   3. the merchant calls api-service's POST /confirm/<confirmation_id>, which
+//      resolves the awakeable through Restate's ingress
+//   4. svix-caller records 'confirmed', or 'unconfirmed' once the timeout
+//      passes, in delivery_attempts (delivery_path 'merchant')
+//
+// The wait happens in the merchant's exclusive process invocation, so its next
+// event isn't sent before this one is confirmed or timed out: confirmed
+// processing is also strictly ordered. Keep the timeout short.
+
+pub const FIELD: &str = "confirmation_id";
+
+/// None unless the merchant asked for confirmations (or there's no database)
+pub async fn timeout(
+    db: Option<&PgPool>,
+    merchant_id: &str,
+) -> Result<Option<Duration>, CallError> {
+    let Some(db) = db else {
+        return Ok(None);
+    };
+
+    let timeout_secs = sqlx::query_scalar::<_, Option<i32>>(
+        "SELECT confirmation_timeout_secs FROM merchants WHERE id = $1::UUID",
+    )
+    .bind(merchant_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| {
+        CallError::Retryable(format!(
+            "Failed to load confirmation settings of merchant {}: {}",
+            merchant_id, e
+        ))
+    })?;
+
+    Ok(timeout_secs
+        .flatten()
+        .filter(|secs| *secs > 0)
+        .map(|secs| Duration::from_secs(secs as u64)))
+}
+
+/// Add the awakeable id to the message body
+pub fn attach(body: &mut Value, confirmation_id: &str) {
+    if let Value::Object(fields) = body {
+        fields.insert(
+            FIELD.to_string(),
+            Value::String(confirmation_id.to_string()),
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use restate_sdk::prelude::*;
+use sqlx::PgPool;
+
+use crate::{DomainEvent, SvixCallerClient};
+
+// ==============================================================================
+// DEAD LETTERS: Events that failed for good, kept so they can be sent again
+// ==============================================================================
+//
+// A terminal failure (payload rejected, payment missing, no Svix application)
+// ends the invocation, and without a record the event would be gone. Each one
+// is written to svix_dead_letters with the event, the message body if it got
+// that far, and the error.
+//
+// Once the cause is fixed (application created, payment restored) the entries
+// are re-driven through the ingress:
+//
+//   curl restate:8080/DeadLetters/redrive \
+//     -H 'content-type: application/json' -d '{"merchant_id": "..."}'
+//
+// Each event goes back through SvixCaller/process under its merchant's key,
+// fetching the payload afresh. With sealing on (sealing.rs) entries are
+// stored and re-driven sealed; process opens them. Failing again writes a new entry. Operators
+// normally go through api-service's /admin/dead-letters instead, which calls
+// this with the ids it selected and records who asked (dead_letter_actions).
+
+/// Entries re-driven per call when the request doesn't say
+const DEFAULT_REDRIVE_LIMIT: i64 = 100;
+
+/// Best-effort, like delivery_attempts: the error is already logged and the
+/// invocation fails either way
+pub async fn record(
+    db: &PgPool,
+    event: &DomainEvent,
+    payload: Option<&serde_json::Value>,
+    error: &str,
+) {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO svix_dead_letters (event_id, merchant_id, event, payload, error)
+        VALUES ($1, $2::UUID, $3, $4, $5)
+        "#,
+    )
+    .bind(event.id as i64)
+    .bind(&event.merchant_id)
+    .bind(sqlx::types::Json(event))
+    .bind(payload)
+    .bind(error)
+    .execute(db)
+    .await;
+
+    match result {
+        Ok(_) => tracing::warn!("Event {} dead-lettered: {}", event.id, error),
+        Err(e) => tracing::error!("Failed to dead-letter event {}: {}", event.id, e),
+    }
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct RedriveRequest {
+    /// Specific entries; every pending one (up to `limit`) when unset
+    #[serde(default)]
+    pub ids: Option<Vec<i64>>,
+    #[serde(default)]
+    pub merchant_id: Option<String>,
+    #[serde(default)]
+    pub limit: Option<i64>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct RedriveResult {
+    /// Dead-letter ids whose events were sent again
+    pub redriven: Vec<i64>,
+}
+
+#[restate_sdk::service]
+pub trait DeadLetters {
+    async fn redrive(req: Json<RedriveRequest>) -> HandlerResult<Json<RedriveResult>>;
+}
+
+pub struct DeadLettersImpl {
+    pub db: PgPool,
+}
+
+impl DeadLetters for DeadLettersImpl {
+    async fn redrive(
+        &self,
+        ctx: Context<'_>,
+        req: Json<RedriveRequest>,
+    ) -> HandlerResult<Json<RedriveResult>> {
+        let req = req.0;
+
+        // Journaled, so a retry re-sends exactly the entries picked the first time
+        let db = self.db.clone();
+        let Json(pending) = ctx
+            .run(|| async move {
+                let rows = sqlx::query_as::<_, (i64, sqlx::types::Json<DomainEvent>)>(
+                    r#"
+                    SELECT id, event
+                    FROM svix_dead_letters
+                    WHERE redriven_at IS NULL AND purged_at IS NULL
+                      AND ($1::BIGINT[] IS NULL OR id = ANY($1))
+                      AND ($2::UUID IS NULL OR merchant_id = $2::UUID)
+                    ORDER BY id
+                    LIMIT $3
+                    "#,
+                )
+                .bind(&req.ids)
+                .bind(&req.merchant_id)
+                .bind(req.limit.unwrap_or(DEFAULT_REDRIVE_LIMIT))
+                .fetch_all(&db)
+                .await
+                .map_err(|e| format!("Failed to load dead letters: {}", e))?;
+                Ok(Json(
+                    rows.into_iter()
+                        .map(|(id, event)| (id, event.0))
+                        .collect::<Vec<_>>(),
+                ))
+            })
+            .name("load_dead_letters")
+            .await?;
+
+        let mut redriven = Vec::with_capacity(pending.len());
+        for (id, event) in pending {
+            tracing::info!("Re-driving dead letter {} (event {})", id, event.id);
+            ctx.object_client::<SvixCallerClient>(event.merchant_id.clone())
+                .process(Json(serde_json::to_value(&event)?))
+                .send();
+            redriven.push(id);
+        }
+
+        let db = self.db.clone();
+        let ids = redriven.clone();
+        ctx.run(|| async move {
+            sqlx::query("UPDATE svix_dead_letters SET redriven_at = NOW() WHERE id = ANY($1)")
+                .bind(&ids)
+                .execute(&db)
+                .await
+                .map_err(|e| format!("Failed to mark dead letters re-driven: {}", e))?;
+            Ok(())
+        })
+        .name("mark_redriven")
+        .await?;
+
+        Ok(Json(RedriveResult { redriven }))
+    }
+}
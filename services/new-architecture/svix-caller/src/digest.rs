@@ -0,0 +1,336 @@
+use restate_sdk::prelude::*;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::errors::CallError;
+use crate::sealing::Sealer;
+use crate::DomainEvent;
+
+// ==============================================================================
+// DIGESTS: Low-priority events sent as one periodic summary
+// ==============================================================================
+//
+// A merchant can take some event types in a digest rather than one webhook
+// each (merchants.digest_event_types, set through api-service). Such an
+// event runs through fetch and transform as usual, then instead of being
+// submitted it's set aside in digest_items with the body it would have been
+// delivered with, and recorded as 'digested'.
+//
+// The Digests object, keyed by merchant like SvixCaller, makes sure a flush
+// is scheduled: a durable Restate timer for the next multiple of
+// digest_interval_secs (the top of the hour for 3600). The flush turns what's
+// pending into one digest.summary event per mode, written to domain_events
+// like quota.exceeded, so it reaches the merchant through the same pipeline,
+// in order with its other events and subject to its delivery windows. Its
+// body carries the counts per type and the items, oldest first:
+//
+//   {"data": {"period_start": ..., "period_end": ..., "count": 42,
+//             "counts": {"payment.processing": 40, "payment.pending": 2},
+//             "items": [{"event_type": ..., "created_at": ..., "body": {...}}]}}
+//
+// At most MAX_DIGEST_ITEMS go into one digest; the rest go out in another
+// straight after. Replays are never digested: someone asked for them. With
+// sealing on (sealing.rs) the items and the digest's payload are stored
+// sealed.
+//
+// A flush can be forced through the ingress, e.g. before turning digests off:
+//
+//   curl -X POST restate:8080/Digests/<merchant_id>/flush
+
+pub const EVENT_TYPE: &str = "digest.summary";
+
+/// Items per digest.summary event
+const MAX_DIGEST_ITEMS: i64 = 500;
+
+/// Set once a flush is scheduled, cleared when it runs
+const SCHEDULED: &str = "flush_scheduled";
+
+/// How long the merchant collects the event's type for; None when it takes
+/// it as it comes (or there's no database)
+async fn interval(db: &PgPool, event: &DomainEvent) -> Result<Option<Duration>, CallError> {
+    let interval_secs = sqlx::query_scalar::<_, i32>(
+        r#"
+        SELECT digest_interval_secs
+        FROM merchants
+        WHERE id = $1::UUID AND $2 = ANY(digest_event_types)
+        "#,
+    )
+    .bind(&event.merchant_id)
+    .bind(&event.event_type)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| {
+        CallError::Retryable(format!(
+            "Failed to load digest settings of merchant {}: {}",
+            event.merchant_id, e
+        ))
+    })?;
+    Ok(interval_secs.map(|secs| Duration::from_secs(secs as u64)))
+}
+
+/// Set the event aside for the merchant's next digest if it takes the
+/// event's type that way, returning the digest interval
+pub async fn set_aside(
+    db: Option<&PgPool>,
+    sealer: Option<&Sealer>,
+    event: &DomainEvent,
+    body: &serde_json::Value,
+) -> Result<Option<Duration>, CallError> {
+    let Some(db) = db else {
+        return Ok(None);
+    };
+    if event.replay_of.is_some() || event.event_type == EVENT_TYPE {
+        return Ok(None);
+    }
+    let Some(interval) = interval(db, event).await? else {
+        return Ok(None);
+    };
+
+    let body = match sealer {
+        Some(sealer) => sealer.seal(&event.merchant_id, body).await?,
+        None => body.clone(),
+    };
+    sqlx::query(
+        r#"
+        INSERT INTO digest_items (event_id, merchant_id, mode, event_type, body)
+        VALUES ($1, $2::UUID, $3, $4, $5)
+        ON CONFLICT (event_id) DO NOTHING
+        "#,
+    )
+    .bind(event.id as i64)
+    .bind(&event.merchant_id)
+    .bind(&event.mode)
+    .bind(&event.event_type)
+    .bind(&body)
+    .execute(db)
+    .await
+    .map_err(|e| {
+        CallError::Retryable(format!(
+            "Failed to set event {} aside for a digest: {}",
+            event.id, e
+        ))
+    })?;
+    Ok(Some(interval))
+}
+
+#[derive(sqlx::FromRow)]
+struct Item {
+    event_id: i64,
+    mode: String,
+    event_type: String,
+    body: serde_json::Value,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct FlushResult {
+    /// domain_events ids of the digest.summary events written
+    pub digests: Vec<i64>,
+    pub items: usize,
+    /// More were pending than fit; another flush follows
+    pub more: bool,
+}
+
+/// The digest.summary payload for one mode's items
+async fn summary(
+    sealer: Option<&Sealer>,
+    merchant_id: &str,
+    mode: &str,
+    items: &[Item],
+) -> Result<serde_json::Value, CallError> {
+    let mut counts = BTreeMap::<&str, u64>::new();
+    let mut entries = Vec::with_capacity(items.len());
+    for item in items {
+        *counts.entry(&item.event_type).or_default() += 1;
+        let body = match sealer {
+            Some(sealer) if payload_crypto::is_sealed(&item.body) => sealer
+                .open(merchant_id, &item.body)
+                .await
+                .map_err(CallError::Terminal)?,
+            _ => item.body.clone(),
+        };
+        entries.push(serde_json::json!({
+            "event_type": item.event_type,
+            "created_at": item.created_at.to_rfc3339(),
+            "body": body,
+        }));
+    }
+
+    let payload = serde_json::json!({
+        "merchant_id": merchant_id,
+        "mode": mode,
+        "period_start": items.first().map(|item| item.created_at.to_rfc3339()),
+        "period_end": items.last().map(|item| item.created_at.to_rfc3339()),
+        "count": items.len(),
+        "counts": counts,
+        "items": entries,
+    });
+    match sealer {
+        Some(sealer) => sealer.seal(merchant_id, &payload).await,
+        None => Ok(payload),
+    }
+}
+
+/// Write the pending items into digest.summary events, all in one
+/// transaction: an item is claimed by exactly one digest
+async fn emit(
+    db: &PgPool,
+    sealer: Option<&Sealer>,
+    merchant_id: &str,
+) -> Result<FlushResult, CallError> {
+    let failed = |e: sqlx::Error| {
+        CallError::Retryable(format!(
+            "Failed to emit digest for merchant {}: {}",
+            merchant_id, e
+        ))
+    };
+    let mut tx = db.begin().await.map_err(failed)?;
+
+    let mut items = sqlx::query_as::<_, Item>(
+        r#"
+        SELECT event_id, mode, event_type, body, created_at
+        FROM digest_items
+        WHERE merchant_id = $1::UUID AND digest_event_id IS NULL
+        ORDER BY event_id
+        LIMIT $2
+        FOR UPDATE
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(MAX_DIGEST_ITEMS + 1)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(failed)?;
+    let more = items.len() as i64 > MAX_DIGEST_ITEMS;
+    items.truncate(MAX_DIGEST_ITEMS as usize);
+
+    let mut by_mode = BTreeMap::<String, Vec<Item>>::new();
+    for item in items {
+        by_mode.entry(item.mode.clone()).or_default().push(item);
+    }
+
+    let mut result = FlushResult {
+        more,
+        ..FlushResult::default()
+    };
+    for (mode, items) in by_mode {
+        let payload = summary(sealer, merchant_id, &mode, &items).await?;
+        let digest_id = sqlx::query_scalar::<_, i64>(
+            r#"
+            INSERT INTO domain_events (event_type, object_id, merchant_id, mode, payload)
+            VALUES ($1, $2::UUID, $2::UUID, $3, $4)
+            RETURNING id
+            "#,
+        )
+        .bind(EVENT_TYPE)
+        .bind(merchant_id)
+        .bind(&mode)
+        .bind(&payload)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(failed)?;
+
+        // The items are done with once they're in a digest, which the
+        // archiver goes by (a 'succeeded' attempt)
+        let ids: Vec<i64> = items.iter().map(|item| item.event_id).collect();
+        sqlx::query("UPDATE digest_items SET digest_event_id = $1 WHERE event_id = ANY($2)")
+            .bind(digest_id)
+            .bind(&ids)
+            .execute(&mut *tx)
+            .await
+            .map_err(failed)?;
+        sqlx::query(
+            r#"
+            INSERT INTO delivery_attempts (event_id, merchant_id, delivery_path, status)
+            SELECT id, $2::UUID, 'digest', 'succeeded' FROM UNNEST($1::BIGINT[]) AS id
+            "#,
+        )
+        .bind(&ids)
+        .bind(merchant_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(failed)?;
+
+        tracing::info!(
+            "Digest event {} for merchant {} ({}) carries {} events",
+            digest_id,
+            merchant_id,
+            mode,
+            ids.len()
+        );
+        result.digests.push(digest_id);
+        result.items += ids.len();
+    }
+
+    tx.commit().await.map_err(failed)?;
+    Ok(result)
+}
+
+/// Until the next multiple of `interval` since the epoch
+fn until_next(interval: Duration) -> Duration {
+    let interval_ms = (interval.as_millis() as u64).max(1);
+    let now_ms = Utc::now().timestamp_millis().max(0) as u64;
+    Duration::from_millis(interval_ms - now_ms % interval_ms)
+}
+
+#[restate_sdk::object]
+pub trait Digests {
+    /// An event was set aside; schedule a flush unless one already is
+    async fn schedule(interval_ms: u64) -> HandlerResult<()>;
+    /// Send everything pending now
+    async fn flush() -> HandlerResult<Json<FlushResult>>;
+}
+
+pub struct DigestsImpl {
+    pub db: PgPool,
+    pub sealer: Option<Sealer>,
+}
+
+impl Digests for DigestsImpl {
+    async fn schedule(&self, ctx: ObjectContext<'_>, interval_ms: u64) -> HandlerResult<()> {
+        if ctx.get::<bool>(SCHEDULED).await?.is_some() {
+            return Ok(());
+        }
+
+        // The clock is read once and journaled, so a retry keeps the same timer
+        let Json(delay_ms) = ctx
+            .run(|| async move {
+                let delay = until_next(Duration::from_millis(interval_ms));
+                Ok(Json(delay.as_millis() as u64))
+            })
+            .name("flush_delay")
+            .await?;
+
+        ctx.set(SCHEDULED, true);
+        ctx.object_client::<DigestsClient>(ctx.key())
+            .flush()
+            .send_after(Duration::from_millis(delay_ms));
+        tracing::info!("Digest of merchant {} flushes in {}ms", ctx.key(), delay_ms);
+        Ok(())
+    }
+
+    async fn flush(&self, ctx: ObjectContext<'_>) -> HandlerResult<Json<FlushResult>> {
+        ctx.clear(SCHEDULED);
+
+        let db = self.db.clone();
+        let sealer = self.sealer.clone();
+        let merchant_id = ctx.key().to_string();
+        let Json(result) = ctx
+            .run(|| async move {
+                let result = emit(&db, sealer.as_ref(), &merchant_id)
+                    .await
+                    .map_err(crate::handler_error)?;
+                Ok(Json(result))
+            })
+            .name("emit_digest")
+            .await?;
+
+        if result.more {
+            ctx.set(SCHEDULED, true);
+            ctx.object_client::<DigestsClient>(ctx.key()).flush().send();
+        }
+        Ok(Json(result))
+    }
+}
@@ -0,0 +1,659 @@
+use event_filter::Filter;
+use http_client::{EgressPolicy, HttpClient, HttpClientConfig, HttpMetrics};
+use payload_script::Script;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use webhook_signing::WEBHOOK_HEADERS;
+
+use crate::errors::CallError;
+use crate::metrics::Metrics;
+use crate::sealing::Sealer;
+use crate::settings::Settings;
+
+// ==============================================================================
+// DIRECT DELIVERY: Fallback path for when Svix itself is down
+// ==============================================================================
+//
+// With SVIX_DIRECT_FALLBACK=true, once Svix has answered with a 5xx (or not at
+// all) SVIX_FALLBACK_THRESHOLD times within SVIX_FALLBACK_WINDOW_SECS, events
+// are POSTed straight to the merchant's merchant_endpoints, signed locally
+// with the same Standard Webhooks scheme (webhook-signing crate). Svix is
+// skipped while the failures are recent; once they age out of the window the
+// next event goes to Svix again, and a single success clears the count.
+//
+// Only live events fall back: merchant_endpoints has no test/live split, and
+// test traffic must never reach live endpoints. Direct deliveries get no Svix
+// retries or portal history, so they are recorded in delivery_attempts with
+// delivery_path 'direct', and every endpoint's attempt on its own with
+// delivery_path 'direct_endpoint', along with what it answered.
+//
+// Every enabled endpoint of the merchant gets the event, each independently:
+//
+//   state       endpoint_deliveries keeps, per event and endpoint, whether it
+//               was delivered, how many attempts it took and when the next
+//               one is due; a retried delivery only goes to the endpoints
+//               that haven't had it yet, and only once they're due
+//   breaker     each endpoint has a client, and with it a circuit breaker,
+//               of its own (http-client's breakers are per host, which
+//               endpoints may share): one that keeps failing is skipped
+//               quickly instead of holding up the step for the full timeout
+//   events      each attempt is also published ('direct_endpoint' delivery
+//               events, with how long it took and whether it timed out), from
+//               which delivery-analytics keeps a health score per endpoint
+//               (endpoint_health)
+//   retries     a failed endpoint's next attempt is spaced by its own score:
+//               SVIX_FALLBACK_RETRY_BASE_MS for a healthy endpoint, doubling
+//               every 12.5 points below 100, up to MAX_RETRY_SPACING; one
+//               without a score is due again on Restate's next retry
+//
+// An endpoint with a filter (merchant_endpoints.filter, event-filter crate)
+// only gets the events it's true for, evaluated on the body it would get; one
+// that's false is left out of the event like a disabled endpoint. The filter
+// is read on every attempt, so a change applies to retries too; one that no
+// longer parses fails the endpoint's attempt, rather than send it events it
+// may have filtered out, until it's fixed. One with an
+// active transformation (endpoint_transformations, payload-script crate) gets
+// the body its script makes, signed as such; a script that fails fails the
+// endpoint's attempt like an error response, so fixing or rolling it back
+// lets the next retry through.
+//
+// The event stays with the step until every endpoint has it, so the
+// merchant's later events still wait behind it, in order; the step waits for
+// the first endpoint to come due.
+//
+// Deliveries leave through EGRESS_PROXY_URL when it's set (HTTP(S) or
+// SOCKS5), so merchants can allowlist the proxy's addresses instead of ours.
+// merchants.egress_proxy overrides it per merchant: another proxy URL, or
+// 'none' to connect directly.
+//
+// Endpoints that require mutual TLS have a client certificate in
+// merchant_endpoints, its private key sealed with the merchant's data key
+// (payload-crypto crate, so PAYLOAD_MASTER_KEYS must be set). They get a
+// client presenting it, rebuilt when the endpoint changes.
+//
+// Endpoint URLs are merchant input, so every client has http-client's egress
+// policy: public addresses only, on http(s) and the ports in
+// EGRESS_ALLOWED_PORTS, checked on every request and DNS lookup. A blocked
+// endpoint fails like one that's down. EGRESS_ALLOWED_NETWORKS lets CIDRs
+// through anyway, e.g. the compose network in development.
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longer response bodies are cut before they're recorded
+const MAX_RESPONSE_BODY_BYTES: usize = 4096;
+
+/// Longest wait between direct deliveries to a failing endpoint
+const MAX_RETRY_SPACING: Duration = Duration::from_secs(600);
+
+/// merchants.egress_proxy that bypasses EGRESS_PROXY_URL
+const NO_PROXY: &str = "none";
+
+#[derive(sqlx::FromRow)]
+struct EndpointRow {
+    id: String,
+    url: String,
+    secret: String,
+    previous_secret: Option<String>,
+    previous_secret_expires_at: Option<DateTime<Utc>>,
+    client_certificate: Option<String>,
+    /// PEM string, sealed
+    client_key: Option<serde_json::Value>,
+    updated_at: Option<DateTime<Utc>>,
+    filter: Option<String>,
+    transformation_version: Option<i32>,
+    /// The active version's script
+    transformation: Option<String>,
+    /// This event's state at the endpoint (endpoint_deliveries)
+    delivered: bool,
+    next_attempt_at: Option<DateTime<Utc>>,
+}
+
+/// Whether the endpoint's filter lets the event through. Filters are
+/// checked when they're saved; one that doesn't parse anymore (a stricter
+/// event-filter) is an error, which fails the endpoint's attempt.
+fn wants(
+    endpoint: &EndpointRow,
+    msg_id: &str,
+    payload: &serde_json::Value,
+) -> Result<bool, String> {
+    let Some(filter) = &endpoint.filter else {
+        return Ok(true);
+    };
+    let filter = Filter::parse(filter).map_err(|e| format!("invalid filter: {}", e))?;
+    if !filter.matches(payload) {
+        tracing::debug!("Endpoint {} filters out {}", endpoint.id, msg_id);
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// The body for this endpoint: the payload as its active transformation
+/// reshapes it, or as it is
+fn endpoint_body(
+    endpoint: &EndpointRow,
+    payload: &serde_json::Value,
+    body: &[u8],
+) -> Result<Vec<u8>, String> {
+    let (Some(version), Some(script)) = (endpoint.transformation_version, &endpoint.transformation)
+    else {
+        return Ok(body.to_vec());
+    };
+    let output = Script::compile(script)
+        .and_then(|script| script.run(payload).map_err(|failure| failure.error))
+        .map_err(|e| format!("transformation v{} failed: {}", version, e))?;
+    serde_json::to_vec(&output.body).map_err(|e| format!("transformation v{}: {}", version, e))
+}
+
+/// Which client a delivery goes out with
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct ClientKey {
+    proxy: Option<String>,
+    /// Endpoint id and when it last changed
+    endpoint: (String, Option<DateTime<Utc>>),
+}
+
+/// Recent Svix outages, shared by every invocation in this process
+struct SvixHealth {
+    failures: Mutex<VecDeque<Instant>>,
+    threshold: usize,
+    window: Duration,
+}
+
+impl SvixHealth {
+    fn prune(&self, failures: &mut VecDeque<Instant>) {
+        while failures
+            .front()
+            .is_some_and(|failed_at| failed_at.elapsed() > self.window)
+        {
+            failures.pop_front();
+        }
+    }
+}
+
+/// What a failing endpoint answered, for delivery_attempts
+#[derive(Debug, Clone)]
+pub struct EndpointResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+/// One endpoint's part in a delivery, recorded and published as
+/// 'direct_endpoint'
+#[derive(Debug, Clone)]
+pub struct EndpointAttempt {
+    pub endpoint_id: String,
+    pub succeeded: bool,
+    pub response_status: Option<u16>,
+    /// What it answered when that wasn't a success
+    pub response: Option<EndpointResponse>,
+    pub response_ms: Option<u64>,
+    pub timed_out: bool,
+    pub error: Option<String>,
+}
+
+/// A delivery some endpoints still have to get
+pub struct DeliveryFailure {
+    pub error: CallError,
+    /// This round's attempts
+    pub attempts: Vec<EndpointAttempt>,
+    /// Until the first of those endpoints is due again; None when one of them
+    /// is due on Restate's next retry
+    pub retry_in: Option<Duration>,
+}
+
+impl From<CallError> for DeliveryFailure {
+    fn from(error: CallError) -> Self {
+        DeliveryFailure {
+            error,
+            attempts: Vec::new(),
+            retry_in: None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct DirectDelivery {
+    clients: Arc<Mutex<HashMap<ClientKey, HttpClient>>>,
+    http_metrics: HttpMetrics,
+    /// EGRESS_PROXY_URL, for merchants without their own
+    default_proxy: Option<String>,
+    egress: EgressPolicy,
+    health: Arc<SvixHealth>,
+    /// Wait before retrying a delivery to a healthy endpoint
+    retry_base: Duration,
+}
+
+impl DirectDelivery {
+    /// None unless SVIX_DIRECT_FALLBACK is set; the values themselves were
+    /// checked when the settings were loaded
+    pub fn from_settings(settings: &Settings, metrics: &Metrics) -> Result<Option<Self>, String> {
+        if !settings.svix_direct_fallback {
+            return Ok(None);
+        }
+
+        let threshold = settings.svix_fallback_threshold;
+        let window = Duration::from_secs(settings.svix_fallback_window_secs);
+        let retry_base = Duration::from_millis(settings.svix_fallback_retry_base_ms);
+        let default_proxy = settings.egress_proxy_url.clone();
+        let egress = EgressPolicy::parse(
+            &settings.egress_allowed_networks,
+            &settings.egress_allowed_ports,
+        )
+        .map_err(|e| format!("Invalid egress policy: {}", e))?;
+        // Built up front so a client that can't be built stops the service here
+        delivery_client(
+            default_proxy.clone(),
+            &egress,
+            reqwest::Client::builder(),
+            metrics.http(),
+        )?;
+
+        tracing::info!(
+            "Direct delivery fallback enabled after {} Svix failures within {:?}{}",
+            threshold,
+            window,
+            if default_proxy.is_some() {
+                ", through the egress proxy"
+            } else {
+                ""
+            }
+        );
+        Ok(Some(DirectDelivery {
+            clients: Arc::new(Mutex::new(HashMap::new())),
+            http_metrics: metrics.http().clone(),
+            default_proxy,
+            egress,
+            health: Arc::new(SvixHealth {
+                failures: Mutex::new(VecDeque::new()),
+                threshold,
+                window,
+            }),
+            retry_base,
+        }))
+    }
+
+    /// Svix answered with a 5xx or not at all
+    pub fn record_svix_failure(&self) {
+        let mut failures = self.health.failures.lock().unwrap();
+        self.health.prune(&mut failures);
+        failures.push_back(Instant::now());
+    }
+
+    pub fn record_svix_success(&self) {
+        self.health.failures.lock().unwrap().clear();
+    }
+
+    /// Enough recent failures to stop waiting for Svix
+    pub fn svix_down(&self) -> bool {
+        let mut failures = self.health.failures.lock().unwrap();
+        self.health.prune(&mut failures);
+        failures.len() >= self.health.threshold
+    }
+
+    /// The proxy the merchant's deliveries leave through; None connects directly
+    async fn merchant_proxy(
+        &self,
+        db: &PgPool,
+        merchant_id: &str,
+    ) -> Result<Option<String>, CallError> {
+        let egress_proxy = sqlx::query_scalar::<_, Option<String>>(
+            "SELECT egress_proxy FROM merchants WHERE id = $1::UUID",
+        )
+        .bind(merchant_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| {
+            CallError::Retryable(format!(
+                "Failed to load egress proxy of merchant {}: {}",
+                merchant_id, e
+            ))
+        })?
+        .flatten();
+        Ok(match egress_proxy.as_deref() {
+            None => self.default_proxy.clone(),
+            Some(NO_PROXY) => None,
+            Some(url) => Some(url.to_string()),
+        })
+    }
+
+    /// The endpoint's client for this proxy, presenting its client
+    /// certificate when it has one; built on first use
+    async fn endpoint_client(
+        &self,
+        proxy: &Option<String>,
+        endpoint: &EndpointRow,
+        merchant_id: &str,
+        sealer: Option<&Sealer>,
+    ) -> Result<HttpClient, String> {
+        let key = ClientKey {
+            proxy: proxy.clone(),
+            endpoint: (endpoint.id.clone(), endpoint.updated_at),
+        };
+        let cached = self.clients.lock().unwrap().get(&key).cloned();
+        if let Some(client) = cached {
+            return Ok(client);
+        }
+
+        let mut builder = reqwest::Client::builder();
+        if let (Some(certificate), Some(sealed_key)) =
+            (&endpoint.client_certificate, &endpoint.client_key)
+        {
+            let sealer = sealer.ok_or("client key is sealed and PAYLOAD_MASTER_KEYS isn't set")?;
+            let private_key = sealer.open(merchant_id, sealed_key).await?;
+            let private_key = private_key
+                .as_str()
+                .ok_or("client key doesn't open to a PEM string")?;
+            builder = builder.identity(http_client::client_identity(certificate, private_key)?);
+        }
+        let client = delivery_client(proxy.clone(), &self.egress, builder, &self.http_metrics)?;
+
+        let mut clients = self.clients.lock().unwrap();
+        // Its client from before it changed goes, and with it any previous
+        // certificate
+        clients.retain(|cached, _| cached.endpoint.0 != endpoint.id);
+        clients.insert(key, client.clone());
+        Ok(client)
+    }
+
+    /// POST the message to every enabled endpoint of the merchant that
+    /// hasn't had it yet and is due, returning each endpoint's attempt. The
+    /// delivery fails while any endpoint still has to get it, so the step is
+    /// retried; receivers dedupe on webhook-id, which is the Svix event id.
+    pub async fn deliver(
+        &self,
+        db: &PgPool,
+        merchant_id: &str,
+        event_id: u64,
+        msg_id: &str,
+        payload: &serde_json::Value,
+        sealer: Option<&Sealer>,
+    ) -> Result<Vec<EndpointAttempt>, DeliveryFailure> {
+        let endpoints = sqlx::query_as::<_, EndpointRow>(
+            r#"
+            SELECT e.id::TEXT AS id, e.url, e.secret, e.previous_secret,
+                   e.previous_secret_expires_at, e.client_certificate, e.client_key,
+                   e.updated_at, e.filter, e.transformation_version, t.script AS transformation,
+                   COALESCE(d.status = 'succeeded', FALSE) AS delivered, d.next_attempt_at
+            FROM merchant_endpoints e
+            LEFT JOIN endpoint_transformations t
+                   ON t.endpoint_id = e.id AND t.version = e.transformation_version
+            LEFT JOIN endpoint_deliveries d ON d.endpoint_id = e.id AND d.event_id = $2
+            WHERE e.merchant_id = $1::UUID AND NOT e.disabled
+            "#,
+        )
+        .bind(merchant_id)
+        .bind(event_id as i64)
+        .fetch_all(db)
+        .await
+        .map_err(|e| CallError::Retryable(format!("Failed to load endpoints: {}", e)))?;
+
+        if endpoints.is_empty() {
+            return Err(CallError::Retryable(format!(
+                "Merchant {} has no direct endpoints, waiting for Svix",
+                merchant_id
+            ))
+            .into());
+        }
+
+        let now = Utc::now();
+        // Each with its filter's error, if it has one that doesn't parse
+        type Wanted<'a> = Vec<(&'a EndpointRow, Option<String>)>;
+        let (due, waiting): (Wanted, Wanted) = endpoints
+            .iter()
+            .filter(|endpoint| !endpoint.delivered)
+            .filter_map(|endpoint| match wants(endpoint, msg_id, payload) {
+                Ok(true) => Some((endpoint, None)),
+                Ok(false) => None,
+                Err(e) => Some((endpoint, Some(e))),
+            })
+            .partition(|(endpoint, _)| endpoint.next_attempt_at.is_none_or(|at| at <= now));
+        if due.is_empty() && waiting.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let proxy = self.merchant_proxy(db, merchant_id).await?;
+        let body = serde_json::to_vec(payload)
+            .map_err(|e| CallError::Terminal(format!("Failed to serialize payload: {}", e)))?;
+        let timestamp = now.timestamp();
+
+        let mut failed = Vec::new();
+        let mut attempts = Vec::with_capacity(due.len());
+        for (endpoint, filter_error) in &due {
+            let url = &endpoint.url;
+            let mut attempt = EndpointAttempt {
+                endpoint_id: endpoint.id.clone(),
+                succeeded: false,
+                response_status: None,
+                response: None,
+                response_ms: None,
+                timed_out: false,
+                error: None,
+            };
+            let body = match filter_error {
+                Some(e) => Err(e.clone()),
+                None => endpoint_body(endpoint, payload, &body),
+            };
+            let body = match body {
+                Ok(body) => body,
+                Err(e) => {
+                    tracing::warn!("Direct delivery of {} to {} failed: {}", msg_id, url, e);
+                    failed.push(format!("{}: {}", url, e));
+                    attempt.error = Some(e);
+                    attempts.push(attempt);
+                    continue;
+                }
+            };
+            let secrets = webhook_signing::active_secrets(
+                &endpoint.secret,
+                endpoint.previous_secret.as_deref(),
+                endpoint.previous_secret_expires_at,
+            );
+            let signature = webhook_signing::sign(&secrets, msg_id, timestamp, &body)
+                .map_err(|e| CallError::Terminal(format!("Endpoint {}: {}", url, e)))?;
+
+            let started = Instant::now();
+            let result = match self
+                .endpoint_client(&proxy, endpoint, merchant_id, sealer)
+                .await
+            {
+                Err(e) => Err(e),
+                Ok(client) => {
+                    let request = client
+                        .post(url)
+                        .header("content-type", "application/json")
+                        .header(WEBHOOK_HEADERS.id, msg_id)
+                        .header(WEBHOOK_HEADERS.timestamp, timestamp.to_string())
+                        .header(WEBHOOK_HEADERS.signature, signature)
+                        .body(body.clone());
+                    match client.send(request).await {
+                        Ok(response) => {
+                            let status = response.status().as_u16();
+                            attempt.response_status = Some(status);
+                            attempt.response_ms = Some(started.elapsed().as_millis() as u64);
+                            if response.status().is_success() {
+                                Ok(())
+                            } else {
+                                let body = truncate_body(response.text().await.unwrap_or_default());
+                                attempt.response = Some(EndpointResponse { status, body });
+                                Err(format!("responded {}", status))
+                            }
+                        }
+                        Err(e) => {
+                            attempt.timed_out = e.is_timeout();
+                            Err(e.to_string())
+                        }
+                    }
+                }
+            };
+
+            match result {
+                Ok(_) => {
+                    tracing::info!("Delivered {} directly to {}", msg_id, url);
+                    attempt.succeeded = true;
+                }
+                Err(e) => {
+                    tracing::warn!("Direct delivery of {} to {} failed: {}", msg_id, url, e);
+                    failed.push(format!("{}: {}", url, e));
+                    attempt.error = Some(e);
+                }
+            }
+            attempts.push(attempt);
+        }
+
+        let spacing = self.retry_spacing(db, &attempts).await;
+        let mut next_attempts: Vec<Option<DateTime<Utc>>> = waiting
+            .iter()
+            .map(|(endpoint, _)| endpoint.next_attempt_at)
+            .collect();
+        for attempt in &attempts {
+            let next_attempt_at = spacing
+                .get(&attempt.endpoint_id)
+                .map(|spacing| now + *spacing);
+            if !attempt.succeeded {
+                next_attempts.push(next_attempt_at);
+            }
+            record_state(db, merchant_id, event_id, attempt, next_attempt_at).await?;
+        }
+
+        if next_attempts.is_empty() {
+            return Ok(attempts);
+        }
+        let retry_in = next_attempts
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .and_then(|due_at| due_at.into_iter().min())
+            .map(|due_at| (due_at - Utc::now()).to_std().unwrap_or(Duration::ZERO));
+        let mut error = format!(
+            "Direct delivery pending at {} of {} endpoints",
+            failed.len() + waiting.len(),
+            endpoints.len()
+        );
+        if !failed.is_empty() {
+            error = format!("{}: {}", error, failed.join("; "));
+        }
+        Err(DeliveryFailure {
+            error: CallError::Retryable(error),
+            attempts,
+            retry_in,
+        })
+    }
+
+    /// How long to wait before delivering to each failed endpoint again, from
+    /// its health score; endpoints without a score (yet) are left out, and
+    /// all of them when the scores can't be read
+    async fn retry_spacing(
+        &self,
+        db: &PgPool,
+        attempts: &[EndpointAttempt],
+    ) -> HashMap<String, Duration> {
+        let ids: Vec<&str> = attempts
+            .iter()
+            .filter(|attempt| !attempt.succeeded)
+            .map(|attempt| attempt.endpoint_id.as_str())
+            .collect();
+        if ids.is_empty() {
+            return HashMap::new();
+        }
+        let scores: Vec<(String, f64)> = sqlx::query_as(
+            r#"
+            SELECT endpoint_id::TEXT, score FROM endpoint_health
+            WHERE endpoint_id::TEXT = ANY($1) AND score IS NOT NULL
+            "#,
+        )
+        .bind(&ids)
+        .fetch_all(db)
+        .await
+        .map_err(|e| tracing::warn!("Failed to read endpoint health: {}", e))
+        .unwrap_or_default();
+        scores
+            .into_iter()
+            .map(|(endpoint_id, score)| {
+                let doublings = ((100.0 - score.clamp(0.0, 100.0)) / 12.5) as u32;
+                let spacing = self
+                    .retry_base
+                    .saturating_mul(2u32.saturating_pow(doublings))
+                    .min(MAX_RETRY_SPACING);
+                (endpoint_id, spacing)
+            })
+            .collect()
+    }
+}
+
+/// The endpoint's state for this event after an attempt. Failing to write it
+/// fails the step: without it, a retry couldn't tell the endpoint apart from
+/// one that already has the event.
+async fn record_state(
+    db: &PgPool,
+    merchant_id: &str,
+    event_id: u64,
+    attempt: &EndpointAttempt,
+    next_attempt_at: Option<DateTime<Utc>>,
+) -> Result<(), CallError> {
+    sqlx::query(
+        r#"
+        INSERT INTO endpoint_deliveries
+            (event_id, endpoint_id, merchant_id, status, attempts, last_error,
+             response_status, next_attempt_at)
+        VALUES ($1, $2::UUID, $3::UUID, $4, 1, $5, $6, $7)
+        ON CONFLICT (event_id, endpoint_id) DO UPDATE SET
+            status = EXCLUDED.status,
+            attempts = endpoint_deliveries.attempts + 1,
+            last_error = EXCLUDED.last_error,
+            response_status = EXCLUDED.response_status,
+            next_attempt_at = EXCLUDED.next_attempt_at,
+            updated_at = NOW()
+        "#,
+    )
+    .bind(event_id as i64)
+    .bind(&attempt.endpoint_id)
+    .bind(merchant_id)
+    .bind(if attempt.succeeded {
+        "succeeded"
+    } else {
+        "failed"
+    })
+    .bind(attempt.error.as_deref())
+    .bind(attempt.response_status.map(i32::from))
+    .bind(next_attempt_at)
+    .execute(db)
+    .await
+    .map_err(|e| {
+        CallError::Retryable(format!(
+            "Failed to record delivery of event {} to endpoint {}: {}",
+            event_id, attempt.endpoint_id, e
+        ))
+    })?;
+    Ok(())
+}
+
+fn delivery_client(
+    proxy: Option<String>,
+    egress: &EgressPolicy,
+    builder: reqwest::ClientBuilder,
+    metrics: &HttpMetrics,
+) -> Result<HttpClient, String> {
+    HttpClient::with_builder(
+        HttpClientConfig {
+            timeout: DELIVERY_TIMEOUT,
+            proxy,
+            egress: Some(egress.clone()),
+            ..HttpClientConfig::new("direct_delivery")
+        },
+        builder,
+        metrics,
+    )
+}
+
+fn truncate_body(mut body: String) -> String {
+    if body.len() > MAX_RESPONSE_BODY_BYTES {
+        let mut end = MAX_RESPONSE_BODY_BYTES;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        body.truncate(end);
+    }
+    body
+}
@@ -0,0 +1,39 @@
+// ==============================================================================
+// ERROR CLASSIFICATION: Retry what can succeed later, fail what never will
+// ==============================================================================
+//
+// Restate retries a failed invocation until it succeeds, so an error that will
+// fail the same way every time (a rejected payload, a payment that doesn't
+// exist) has to end the invocation as a TerminalError, or the event retries
+// forever and blocks its merchant's queue. Timeouts, 5xx and 429 are worth
+// retrying. So are 401/403: they mean our credentials are misconfigured, and
+// the events should go out once that is fixed rather than be dropped.
+
+#[derive(Debug)]
+pub enum CallError {
+    Retryable(String),
+    Terminal(String),
+}
+
+impl CallError {
+    /// Classify by the HTTP status of the failed call; None means the request
+    /// never got a response (connect error, timeout) and is retried
+    pub fn from_status(status: Option<u16>, message: String) -> Self {
+        match status {
+            Some(status) if is_terminal_status(status) => CallError::Terminal(message),
+            _ => CallError::Retryable(message),
+        }
+    }
+}
+
+impl std::fmt::Display for CallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallError::Retryable(message) | CallError::Terminal(message) => f.write_str(message),
+        }
+    }
+}
+
+pub fn is_terminal_status(status: u16) -> bool {
+    (400..500).contains(&status) && !matches!(status, 401 | 403 | 408 | 429)
+}
@@ -0,0 +1,229 @@
+use serde_json::{json, Value};
+use svix::api::{EventTypeIn, EventTypeUpdate, Svix};
+
+use crate::errors::CallError;
+use crate::routing::SvixRouter;
+use crate::svix_status;
+
+// ==============================================================================
+// EVENT TYPES: The platform's catalog, mirrored into Svix
+// ==============================================================================
+//
+// Svix only knows an event type by the name on the messages it receives. With
+// the catalog registered, the merchant-facing portal lists every event with a
+// description and an example schema, and merchants can filter endpoints by
+// type before the first event of that type was ever sent.
+//
+// Synced on startup (unless SVIX_SYNC_EVENT_TYPES=false) and on demand through
+// SvixAdmin/sync_event_types. Existing types are updated in place, so editing
+// a description here and restarting is enough. Every Svix account configured
+// in routing.rs gets the full catalog.
+
+struct EventType {
+    name: &'static str,
+    description: &'static str,
+    group: &'static str,
+}
+
+/// payment.* follows the payments.status values accepted by api-service; the
+/// trigger in init.sql emits one event per status change
+const CATALOG: &[EventType] = &[
+    EventType {
+        name: "payment.pending",
+        description: "A payment was created and is awaiting processing.",
+        group: "payment",
+    },
+    EventType {
+        name: "payment.processing",
+        description: "A payment is being processed.",
+        group: "payment",
+    },
+    EventType {
+        name: "payment.succeeded",
+        description: "A payment completed successfully.",
+        group: "payment",
+    },
+    EventType {
+        name: "payment.failed",
+        description: "A payment could not be completed.",
+        group: "payment",
+    },
+    EventType {
+        name: "payment.refunded",
+        description: "A payment was refunded.",
+        group: "payment",
+    },
+    EventType {
+        name: "quota.exceeded",
+        description: "The merchant went over its monthly event quota; further \
+                      events this period are rejected.",
+        group: "account",
+    },
+    EventType {
+        name: "digest.summary",
+        description: "Events of the types the merchant takes in a digest, collected \
+                      over its digest interval, with a count per type.",
+        group: "digest",
+    },
+];
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct EventTypeSync {
+    /// Region the token belongs to, or "default"
+    pub account: String,
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+}
+
+/// JSON Schema of the message body as svix-caller submits it
+fn schema(event_type: &EventType) -> Value {
+    let body = if event_type.group == "payment" {
+        let status = event_type.name.trim_start_matches("payment.");
+        json!({
+            "payment": {
+                "type": "object",
+                "description": "The payment in the merchant's payload version (v1 or v2)",
+                "properties": {
+                    "id": { "type": "string", "format": "uuid" },
+                    "status": { "type": "string", "const": status },
+                    "mode": { "type": "string", "enum": ["live", "test"] },
+                },
+                "required": ["id", "status", "mode"],
+            }
+        })
+    } else if event_type.group == "digest" {
+        json!({
+            "data": {
+                "type": "object",
+                "properties": {
+                    "merchant_id": { "type": "string", "format": "uuid" },
+                    "mode": { "type": "string", "enum": ["live", "test"] },
+                    "period_start": { "type": "string", "format": "date-time" },
+                    "period_end": { "type": "string", "format": "date-time" },
+                    "count": { "type": "integer" },
+                    "counts": {
+                        "type": "object",
+                        "additionalProperties": { "type": "integer" },
+                    },
+                    "items": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "event_type": { "type": "string" },
+                                "created_at": { "type": "string", "format": "date-time" },
+                                "body": {
+                                    "type": "object",
+                                    "description": "The webhook body the event would have had",
+                                },
+                            },
+                        },
+                    },
+                },
+            }
+        })
+    } else {
+        json!({
+            "data": {
+                "type": "object",
+                "properties": {
+                    "merchant_id": { "type": "string", "format": "uuid" },
+                    "period": { "type": "string", "format": "date" },
+                    "event_count": { "type": "integer" },
+                    "monthly_event_quota": { "type": "integer" },
+                },
+            }
+        })
+    };
+
+    let mut properties = json!({
+        "event_id": { "type": "string" },
+        "event_type": { "type": "string", "const": event_type.name },
+    });
+    if let (Value::Object(properties), Value::Object(body)) = (&mut properties, body) {
+        properties.extend(body);
+    }
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": event_type.name,
+        "description": event_type.description,
+        "type": "object",
+        "properties": properties,
+        "required": ["event_id", "event_type"],
+    })
+}
+
+/// Sync every configured Svix account
+pub async fn sync_all(router: &SvixRouter) -> Result<Vec<EventTypeSync>, CallError> {
+    let mut reports = Vec::new();
+    for (account, svix) in router.clients() {
+        let _permit = router.permit().await;
+        reports.push(sync(account, &svix).await?);
+    }
+    Ok(reports)
+}
+
+/// Create every catalog entry in Svix, updating the ones that already exist
+async fn sync(account: String, svix: &Svix) -> Result<EventTypeSync, CallError> {
+    let mut report = EventTypeSync {
+        account,
+        ..EventTypeSync::default()
+    };
+
+    for event_type in CATALOG {
+        // Svix keys schemas by version; the body shape hasn't changed yet
+        let schemas = Some(json!({ "1": schema(event_type) }));
+        let created = svix
+            .event_type()
+            .create(
+                EventTypeIn {
+                    name: event_type.name.to_string(),
+                    description: event_type.description.to_string(),
+                    group_name: Some(event_type.group.to_string()),
+                    schemas: schemas.clone(),
+                    ..EventTypeIn::default()
+                },
+                None,
+            )
+            .await;
+
+        match created {
+            Ok(_) => report.created.push(event_type.name.to_string()),
+            Err(e) if svix_status(&e) == Some(409) => {
+                svix.event_type()
+                    .update(
+                        event_type.name.to_string(),
+                        EventTypeUpdate {
+                            description: event_type.description.to_string(),
+                            group_name: Some(event_type.group.to_string()),
+                            schemas,
+                            ..EventTypeUpdate::default()
+                        },
+                    )
+                    .await
+                    .map_err(|e| {
+                        CallError::from_status(
+                            svix_status(&e),
+                            format!("Failed to update event type {}: {}", event_type.name, e),
+                        )
+                    })?;
+                report.updated.push(event_type.name.to_string());
+            }
+            Err(e) => {
+                return Err(CallError::from_status(
+                    svix_status(&e),
+                    format!("Failed to create event type {}: {}", event_type.name, e),
+                ))
+            }
+        }
+    }
+
+    tracing::info!(
+        "Svix event types synced for {}: {} created, {} updated",
+        report.account,
+        report.created.len(),
+        report.updated.len()
+    );
+    Ok(report)
+}
@@ -0,0 +1,116 @@
+use axum::{extract::State, response::IntoResponse, routing::get, Router};
+use health_checks::{checks, CheckResult, HealthRegistry};
+use std::time::{Duration, Instant};
+use svix::api::ApplicationListOptions;
+
+use crate::metrics::Metrics;
+use crate::routing::SvixRouter;
+use crate::svix_status;
+
+// ==============================================================================
+// HEALTH: /livez, /readyz, /startupz and /metrics on a side port
+// ==============================================================================
+//
+// The Restate endpoint only speaks Restate's protocol, so Kubernetes has
+// nothing to probe there. These are served on HEALTH_PORT (default 9082), the
+// probes by the health-checks crate:
+//
+//   /livez    - the process is up (also as /healthz); touches nothing else,
+//               so a Svix outage doesn't get the pod restarted in a loop
+//   /readyz   - every configured Svix account answers an authenticated call
+//               (listing one application, which also proves the token is
+//               valid) and data-service's /health is OK
+//   /startupz - the same, until it has passed once
+//   /metrics  - Prometheus (metrics.rs)
+//
+// Probes don't wait for a Svix call slot (routing.rs): a busy instance is
+// still ready. With DRY_RUN there is no Svix to check.
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub fn registry(router: SvixRouter, dry_run: bool) -> Result<HealthRegistry, String> {
+    // data-service's HTTP base URL, also used with PAYLOAD_TRANSPORT=grpc
+    let data_service_url = std::env::var("DATA_SERVICE_URL")
+        .unwrap_or_else(|_| "http://data-service:3002".to_string());
+    let data_service_health = format!("{}/health", data_service_url.trim_end_matches('/'));
+    let client = reqwest::Client::builder()
+        .timeout(CHECK_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+
+    Ok(HealthRegistry::new()
+        .with_timeout(CHECK_TIMEOUT)
+        .readiness("svix", move || check_svix(router.clone(), dry_run))
+        .readiness("data_service", move || {
+            checks::http(client.clone(), data_service_health.clone())
+        }))
+}
+
+async fn render_metrics(State(metrics): State<Metrics>) -> impl IntoResponse {
+    metrics.render()
+}
+
+async fn check_svix(router: SvixRouter, dry_run: bool) -> CheckResult {
+    if dry_run {
+        return CheckResult::pass("dry run");
+    }
+
+    let clients = router.clients();
+    if clients.is_empty() {
+        return CheckResult::fail("no Svix token configured");
+    }
+
+    let mut ok = true;
+    let mut details = Vec::with_capacity(clients.len());
+    for (account, svix) in clients {
+        let started = Instant::now();
+        let list = svix.application().list(Some(ApplicationListOptions {
+            limit: Some(1),
+            ..ApplicationListOptions::default()
+        }));
+
+        let detail = match tokio::time::timeout(CHECK_TIMEOUT, list).await {
+            Ok(Ok(_)) => format!("{}ms", started.elapsed().as_millis()),
+            Ok(Err(e)) => {
+                ok = false;
+                match svix_status(&e) {
+                    Some(401) | Some(403) => "token rejected".to_string(),
+                    _ => e.to_string(),
+                }
+            }
+            Err(_) => {
+                ok = false;
+                format!("timed out after {:?}", CHECK_TIMEOUT)
+            }
+        };
+        details.push(format!("{}: {}", account, detail));
+    }
+
+    let detail = details.join("; ");
+    if ok {
+        CheckResult::pass(detail)
+    } else {
+        CheckResult::fail(detail)
+    }
+}
+
+pub async fn serve(port: u16, registry: HealthRegistry, metrics: Metrics) {
+    let listener = match tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!("Health endpoints disabled, bind failed: {}", e);
+            return;
+        }
+    };
+
+    let app = Router::new()
+        .route("/healthz", get(health_checks::livez))
+        .route("/metrics", get(render_metrics))
+        .with_state(metrics)
+        .merge(health_checks::router(registry));
+
+    tracing::info!("Health and metrics endpoints on port {}", port);
+    if let Err(e) = axum::serve(listener, app).await {
+        tracing::error!("Health server stopped: {}", e);
+    }
+}
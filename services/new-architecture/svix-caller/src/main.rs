@@ -1,141 +1,1808 @@
 use restate_sdk::prelude::*;
-use svix::api::{MessageIn, Svix, SvixOptions};
-use std::time::Duration;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use svix::api::{ApplicationIn, MessageIn};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::Instrument;
 use uuid::Uuid;
 
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
-pub struct DomainEvent {
-    pub id: u64,
-    pub event_type: String,
-    pub object_id: String,
-    pub merchant_id: String,
-    pub payload: serde_json::Value,
+mod admin;
+mod bootstrap;
+mod confirmation;
+mod dead_letters;
+mod digest;
+mod direct;
+mod errors;
+mod event_types;
+mod health;
+mod metrics;
+mod operational;
+mod payload;
+mod publish;
+mod routing;
+mod saga;
+mod sealing;
+mod secrets;
+mod settings;
+mod transform;
+mod windows;
+
+use admin::{SvixAdmin, SvixAdminImpl};
+use dead_letters::{DeadLetters, DeadLettersImpl};
+use digest::{Digests, DigestsImpl};
+use direct::{DeliveryFailure, DirectDelivery, EndpointAttempt};
+use errors::CallError;
+use metrics::Metrics;
+use payload::PayloadClient;
+use publish::Publisher;
+use redaction::Redactor;
+use routing::SvixRouter;
+use saga::Stage;
+use sealing::Sealer;
+use settings::Settings;
+pub use webhook_types::{DomainEvent, VersionError, WebhookPayload, SCHEMA_VERSION};
+
+/// Test-mode events go to a separate Svix application per merchant so test
+/// traffic never reaches endpoints registered for live payments.
+fn svix_app_id(event: &DomainEvent) -> String {
+    svix_app_uid(&event.merchant_id, &event.mode)
+}
+
+fn svix_app_uid(merchant_id: &str, mode: &str) -> String {
+    if mode == "test" {
+        format!("{}_test", merchant_id)
+    } else {
+        merchant_id.to_string()
+    }
+}
+
+/// Svix channel per event category ("payment", "quota", ...), so merchants
+/// can subscribe an endpoint to one category instead of listing event types
+fn svix_channels(event: &DomainEvent) -> Vec<String> {
+    let category = event
+        .event_type
+        .split_once('.')
+        .map_or(event.event_type.as_str(), |(category, _)| category);
+    vec![category.to_string()]
+}
+
+/// Searchable in the Svix dashboard. Svix tags only allow [a-zA-Z0-9-_./#],
+/// hence `payment_<id>` rather than `payment_id:<id>`. `event_<id>` is the
+/// correlation id: the outbox row, shared by an event and all its replays.
+fn svix_tags(event: &DomainEvent) -> Vec<String> {
+    let mut tags = vec![format!("event_{}", event.replay_of.unwrap_or(event.id))];
+    if event.event_type.starts_with("payment.") {
+        tags.push(format!("payment_{}", event.object_id));
+    }
+    if event.replay_of.is_some() {
+        tags.push("replay".to_string());
+    }
+    tags
 }
 
+/// Svix's documented limits on ids, channels and tags
+fn validate_message(message: &MessageIn) -> Result<(), String> {
+    let check = |what: &str, value: &str, max_len: usize, extra: &str| {
+        let valid = !value.is_empty()
+            && value.len() <= max_len
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c) || extra.contains(c));
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("invalid {} '{}'", what, value))
+        }
+    };
+
+    check("event type", &message.event_type, 256, "")?;
+    if let Some(event_id) = &message.event_id {
+        check("event id", event_id, 256, "")?;
+    }
+    let channels = message.channels.as_deref().unwrap_or_default();
+    if channels.len() > 10 {
+        return Err(format!("{} channels, at most 10", channels.len()));
+    }
+    for channel in channels {
+        check("channel", channel, 128, ":")?;
+    }
+    let tags = message.tags.as_deref().unwrap_or_default();
+    if tags.len() > 5 {
+        return Err(format!("{} tags, at most 5", tags.len()));
+    }
+    for tag in tags {
+        check("tag", tag, 128, "/#")?;
+    }
+    if !message.payload.is_object() {
+        return Err("payload is not a JSON object".to_string());
+    }
+    Ok(())
+}
+
+/// HTTP status of a failed Svix call; None when no response came back
+fn svix_status(e: &svix::error::Error) -> Option<u16> {
+    match e {
+        svix::error::Error::Http(e) => Some(e.status.as_u16()),
+        svix::error::Error::Validation(e) => Some(e.status.as_u16()),
+        _ => None,
+    }
+}
+
+fn svix_error(context: &str, e: svix::error::Error) -> CallError {
+    CallError::from_status(svix_status(&e), format!("{}: {}", context, e))
+}
+
+/// Terminal errors end the invocation, anything else makes Restate retry it
+fn handler_error(e: CallError) -> HandlerError {
+    match e {
+        CallError::Terminal(message) => {
+            tracing::error!("Giving up: {}", message);
+            TerminalError::new(message).into()
+        }
+        CallError::Retryable(message) => message.into(),
+    }
+}
+
+/// Virtual Object keyed by merchant_id: Restate runs one invocation per key
+/// at a time, so a merchant's events are submitted to Svix in the order they
+/// arrive while different merchants proceed in parallel. Kafka subscriptions
+/// use the record key as the object key, so Sequin has to key messages by
+/// merchant_id (see SEQUIN_SETUP.md).
+#[restate_sdk::object]
+trait SvixCaller {
+    /// A DomainEvent, read through read_event (schema_version negotiation)
+    async fn process(event: Json<serde_json::Value>) -> HandlerResult<String>;
+    /// Several of the merchant's events at once: payloads are fetched
+    /// concurrently, then each event is submitted like in process
+    async fn process_batch(
+        events: Json<Vec<serde_json::Value>>,
+    ) -> HandlerResult<Json<Vec<BatchItemResult>>>;
+    /// Submit the event later through a durable timer. Shared, so waiting
+    /// doesn't hold up the merchant's other events.
+    #[shared]
+    async fn schedule(req: Json<ScheduleRequest>) -> HandlerResult<String>;
+}
+
+/// Largest batch process_batch accepts
+const MAX_BATCH_EVENTS: usize = 100;
+
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
-pub struct PaymentPayload {
-    pub id: Uuid,
-    pub amount: i64,
-    pub currency: String,
+pub struct BatchItemResult {
+    pub event_id: u64,
+    /// sent_to_svix, skipped_no_app, delivered_direct, digested or failed
     pub status: String,
+    pub error: Option<String>,
+}
+
+impl BatchItemResult {
+    fn failed(event_id: u64, error: String) -> Self {
+        tracing::warn!("Batch event {} failed: {}", event_id, error);
+        BatchItemResult {
+            event_id,
+            status: "failed".to_string(),
+            error: Some(error),
+        }
+    }
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
-pub struct WebhookPayload {
-    pub event_id: String,
-    pub event_type: String,
-    pub payment: PaymentPayload,
+pub struct ScheduleRequest {
+    /// A DomainEvent, negotiated like process's input
+    pub event: serde_json::Value,
+    /// Wait this long before submitting...
+    #[serde(default)]
+    pub delay_ms: Option<u64>,
+    /// ...or submit at this RFC 3339 time; a time in the past submits now
+    #[serde(default)]
+    pub fire_at: Option<String>,
 }
 
-#[restate_sdk::service]
-trait SvixCaller {
-    async fn process(event: Json<DomainEvent>) -> HandlerResult<String>;
+/// How long until the scheduled submission
+fn schedule_delay(req: &ScheduleRequest) -> Result<Duration, String> {
+    match (req.delay_ms, req.fire_at.as_deref()) {
+        (Some(delay_ms), None) => Ok(Duration::from_millis(delay_ms)),
+        (None, Some(fire_at)) => {
+            let fire_at = DateTime::parse_from_rfc3339(fire_at)
+                .map_err(|e| format!("Invalid fire_at {}: {}", fire_at, e))?;
+            Ok((fire_at.with_timezone(&Utc) - Utc::now())
+                .to_std()
+                .unwrap_or(Duration::ZERO))
+        }
+        _ => Err("Set exactly one of delay_ms and fire_at".to_string()),
+    }
+}
+
+#[derive(Clone)]
+pub struct SvixCallerImpl {
+    /// Used to record delivery_attempts; recording is skipped when unset
+    db: Option<PgPool>,
+    payloads: PayloadClient,
+    /// Create missing Svix applications instead of skipping their events
+    auto_provision: bool,
+    /// First wait after a 429, doubled on every further one
+    rate_limit_backoff: Duration,
+    /// Set with SVIX_DIRECT_FALLBACK=true (see direct.rs)
+    direct: Option<DirectDelivery>,
+    router: SvixRouter,
+    /// Payload fetches in flight per process_batch invocation
+    batch_concurrency: usize,
+    /// DRY_RUN=true: log the Svix request instead of sending it
+    dry_run: bool,
+    metrics: Metrics,
+    /// Set with KAFKA_BROKERS (see publish.rs)
+    publisher: Option<Publisher>,
+    /// Set with PAYLOAD_MASTER_KEYS (see sealing.rs)
+    sealer: Option<Sealer>,
+    /// Set with REDACTION_RULES: applied before payloads are journaled or
+    /// dead-lettered, ahead of sealing
+    redactor: Option<Arc<Redactor>>,
 }
 
-pub struct SvixCallerImpl;
+impl SvixCallerImpl {
+    /// Best-effort: a failed insert is logged but never fails the handler,
+    /// the Svix submission is what matters for delivery.
+    async fn record_attempt(&self, event: &DomainEvent, status: &str, error: Option<&str>) {
+        self.record_attempt_via(event, "svix", status, error).await;
+    }
+
+    async fn record_attempt_via(
+        &self,
+        event: &DomainEvent,
+        delivery_path: &str,
+        status: &str,
+        error: Option<&str>,
+    ) {
+        if let Some(publisher) = &self.publisher {
+            publisher
+                .delivery_attempt(event, delivery_path, status, error)
+                .await;
+        }
+        let Some(db) = &self.db else {
+            return;
+        };
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO delivery_attempts
+                (event_id, merchant_id, delivery_path, status, error)
+            VALUES ($1, $2::UUID, $3, $4, $5)
+            "#,
+        )
+        .bind(event.id as i64)
+        .bind(&event.merchant_id)
+        .bind(delivery_path)
+        .bind(status)
+        .bind(error)
+        .execute(db)
+        .await;
+
+        if let Err(e) = result {
+            tracing::warn!("Failed to record delivery attempt for event {}: {}", event.id, e);
+        }
+    }
+    /// svix_dead_letters, and the dead-letter topic when publishing,
+    /// redacted and then sealed when those are on. Fails only when sealing
+    /// does, so the step is retried rather than the payload written in the
+    /// clear.
+    async fn dead_letter(
+        &self,
+        event: &DomainEvent,
+        payload: Option<&serde_json::Value>,
+        error: &str,
+    ) -> HandlerResult<()> {
+        let mut event = event.clone();
+        let mut payload = payload.cloned();
+        let redactor = self.redactor.as_deref();
+        redact(
+            redactor,
+            &self.metrics,
+            "dead_letter",
+            &event.event_type,
+            &mut event.payload,
+        );
+        if let Some(payload) = &mut payload {
+            redact(
+                redactor,
+                &self.metrics,
+                "dead_letter",
+                &event.event_type,
+                payload,
+            );
+        }
+        let (event, payload) = match &self.sealer {
+            Some(sealer) => {
+                let sealed_event = sealer.seal_event(&event).await.map_err(handler_error)?;
+                let sealed_payload = match &payload {
+                    Some(payload) => Some(
+                        sealer
+                            .seal(&event.merchant_id, payload)
+                            .await
+                            .map_err(handler_error)?,
+                    ),
+                    None => None,
+                };
+                (sealed_event, sealed_payload)
+            }
+            None => (event, payload),
+        };
+        if let Some(db) = &self.db {
+            dead_letters::record(db, &event, payload.as_ref(), error).await;
+        }
+        if let Some(publisher) = &self.publisher {
+            publisher.dead_letter(&event, error).await;
+        }
+        Ok(())
+    }
+
+    /// Seal a value a run step is about to journal (sealing.rs)
+    async fn seal_journaled(
+        sealer: Option<&Sealer>,
+        merchant_id: &str,
+        value: serde_json::Value,
+    ) -> HandlerResult<serde_json::Value> {
+        match sealer {
+            Some(sealer) => sealer.seal(merchant_id, &value).await.map_err(handler_error),
+            None => Ok(value),
+        }
+    }
+
+    /// Open a value a run step journaled sealed
+    async fn open_journaled(
+        &self,
+        merchant_id: &str,
+        value: serde_json::Value,
+    ) -> Result<serde_json::Value, TerminalError> {
+        match &self.sealer {
+            Some(sealer) => sealer
+                .open(merchant_id, &value)
+                .await
+                .map_err(TerminalError::new),
+            None => Ok(value),
+        }
+    }
+
+    /// Negotiate the event's schema_version. An event this build can't read
+    /// fails for good instead of being retried until a newer svix-caller is
+    /// deployed; Restate keeps the failed invocation, and with it the event.
+    fn read_event(&self, message: serde_json::Value) -> Result<DomainEvent, String> {
+        webhook_types::negotiate(message).map_err(|e| {
+            let outcome = match e {
+                VersionError::Unsupported { .. } => "unsupported_version",
+                _ => "malformed",
+            };
+            self.metrics.outcome(outcome);
+            tracing::error!("Rejected event: {}", e);
+            format!("Rejected event: {}", e)
+        })
+    }
+}
 
 impl SvixCaller for SvixCallerImpl {
-    async fn process(&self, _ctx: Context<'_>, event: Json<DomainEvent>) -> HandlerResult<String> {
-        let event = event.0;
+    async fn process(
+        &self,
+        ctx: ObjectContext<'_>,
+        event: Json<serde_json::Value>,
+    ) -> HandlerResult<String> {
+        self.metrics.invoked("process");
+        let event = self.read_event(event.0).map_err(TerminalError::new)?;
+        let event = sealing::open_event(self.sealer.as_ref(), event)
+            .await
+            .map_err(TerminalError::new)?;
+        let span = event_span(&event);
+        self.process_event(ctx, event).instrument(span).await
+    }
+
+    async fn process_batch(
+        &self,
+        mut ctx: ObjectContext<'_>,
+        events: Json<Vec<serde_json::Value>>,
+    ) -> HandlerResult<Json<Vec<BatchItemResult>>> {
+        self.metrics.invoked("process_batch");
+        let messages = events.0;
+        if messages.len() > MAX_BATCH_EVENTS {
+            return Err(TerminalError::new(format!(
+                "At most {} events per batch, got {}",
+                MAX_BATCH_EVENTS,
+                messages.len()
+            ))
+            .into());
+        }
+
+        // Events this build can't read are reported, the rest still go out
+        let mut unreadable = Vec::new();
+        let mut events = Vec::with_capacity(messages.len());
+        for message in messages {
+            let event_id = message["id"].as_u64().unwrap_or_default();
+            let event = match self.read_event(message) {
+                Ok(event) => sealing::open_event(self.sealer.as_ref(), event).await,
+                Err(e) => Err(e),
+            };
+            match event {
+                Ok(event) => events.push(event),
+                Err(e) => unreadable.push(BatchItemResult::failed(event_id, e)),
+            }
+        }
+        tracing::info!("Processing batch of {} events", events.len());
+
+        let mut ids = Vec::with_capacity(events.len());
+        for event in &events {
+            check_key(&ctx, event);
+            let event_uuid = event_uuid(&mut ctx, event);
+            ids.push((event_uuid, svix_event_id(event, event_uuid)));
+        }
+
+        // The whole batch waits for the merchant's delivery windows
+        if let Some(event) = events.first() {
+            self.await_window(&ctx, event).await?;
+        }
+
+        // All payloads in one journaled step, at most batch_concurrency at a time
+        let fetches: Vec<(usize, PayloadFetch)> = events
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| is_payment_event(event))
+            .map(|(index, event)| {
+                (
+                    index,
+                    PayloadFetch {
+                        merchant_id: event.merchant_id.clone(),
+                        payment_id: event.object_id.clone(),
+                        event_id: event.replay_of.unwrap_or(event.id),
+                        event_type: event.event_type.clone(),
+                    },
+                )
+            })
+            .collect();
+        let payloads = self.payloads.clone();
+        let concurrency = self.batch_concurrency;
+        let sealer = self.sealer.clone();
+        let redactor = self.redactor.clone();
+        let metrics = self.metrics.clone();
+        let Json(fetched) = ctx
+            .run(|| async move {
+                fetch_payloads(payloads, fetches, concurrency, redactor, metrics, sealer).await
+            })
+            .name("fetch_payloads")
+            .await?;
+        let mut fetched: HashMap<usize, FetchedPayload> = fetched.into_iter().collect();
+
+        // Submitted one by one, in the order given
+        let mut results = Vec::with_capacity(events.len());
+        for (index, (event, (event_uuid, svix_event_id))) in events.into_iter().zip(ids).enumerate()
+        {
+            let span = event_span(&event);
+            let fetched = fetched.remove(&index);
+            let result = self
+                .process_batch_event(&mut ctx, event, event_uuid, &svix_event_id, fetched)
+                .instrument(span)
+                .await?;
+            results.push(result);
+        }
+        results.extend(unreadable);
+
+        Ok(Json(results))
+    }
+
+    async fn schedule(
+        &self,
+        ctx: SharedObjectContext<'_>,
+        req: Json<ScheduleRequest>,
+    ) -> HandlerResult<String> {
+        self.metrics.invoked("schedule");
+        let req = req.0;
+        let event = self.read_event(req.event.clone()).map_err(TerminalError::new)?;
+        let event_id = event.id;
+
+        // The clock is read once and journaled, so a retry keeps the same timer
+        let timing = req.clone();
+        let Json(delay) = ctx
+            .run(|| async move {
+                let delay = schedule_delay(&timing).map_err(TerminalError::new)?;
+                Ok(Json(delay.as_millis() as u64))
+            })
+            .name("schedule_delay")
+            .await?;
+
+        // Keyed by the event's merchant like every other submission, so it is
+        // ordered with whatever that merchant has queued when the timer fires
+        ctx.object_client::<SvixCallerClient>(event.merchant_id.clone())
+            .process(Json(req.event))
+            .send_after(Duration::from_millis(delay));
+
+        tracing::info!("Event {} scheduled for submission in {}ms", event_id, delay);
+        Ok(format!("scheduled:{}:{}", event_id, delay))
+    }
+}
+
+/// Everything logged while handling the event carries these (logging crate):
+/// correlation_id is the payment id, the one id known at every hop
+fn event_span(event: &DomainEvent) -> tracing::Span {
+    tracing::info_span!(
+        "event",
+        event_id = event.id,
+        merchant_id = %event.merchant_id,
+        correlation_id = %event.object_id,
+    )
+}
+
+fn is_payment_event(event: &DomainEvent) -> bool {
+    event.event_type.starts_with("payment.")
+}
+
+fn check_key(ctx: &ObjectContext<'_>, event: &DomainEvent) {
+    if ctx.key() != event.merchant_id {
+        // Still delivered, but not ordered with the merchant's other events
+        tracing::warn!(
+            "Event {} for merchant {} arrived under key {}; check the Kafka message key",
+            event.id,
+            event.merchant_id,
+            ctx.key()
+        );
+    }
+}
+
+/// The payment id, or a random fallback from the journal, so a retried
+/// invocation submits under the same id
+fn event_uuid(ctx: &mut ObjectContext<'_>, event: &DomainEvent) -> Uuid {
+    match Uuid::parse_str(&event.object_id) {
+        Ok(uuid) => uuid,
+        Err(_) => ctx.rand_uuid(),
+    }
+}
+
+/// The Svix event id, unique per domain event: a payment gets a new outbox row
+/// on every status change, so the payment id alone would make Svix reject
+/// payment.succeeded as a duplicate of payment.processing. Replays need their
+/// own id too, otherwise Svix treats them as the original message. The
+/// merchant-facing event_id stays the same.
+/// Operational events aren't tied to a payment, so they use the row id.
+fn svix_event_id(event: &DomainEvent, event_uuid: Uuid) -> String {
+    match event.replay_of {
+        Some(original_id) => {
+            tracing::info!("Event {} is a replay of event {}", event.id, original_id);
+            format!("{}_replay_{}", event_uuid, event.id)
+        }
+        None if is_payment_event(event) => format!("{}_{}", event_uuid, event.id),
+        None => format!("evt_{}", event.id),
+    }
+}
+
+/// The message body: the fetched payment for payment events. Operational
+/// events (e.g. quota.exceeded) carry everything in the outbox row, there is
+/// no payment to enrich from data-service.
+fn webhook_body(
+    event: &DomainEvent,
+    event_uuid: Uuid,
+    svix_event_id: &str,
+    payment: Option<serde_json::Value>,
+) -> Result<serde_json::Value, String> {
+    match payment {
+        Some(payment) => serde_json::to_value(WebhookPayload {
+            schema_version: SCHEMA_VERSION,
+            event_id: event_uuid.to_string(),
+            event_type: event.event_type.clone(),
+            payment,
+        })
+        .map_err(|e| format!("Failed to serialize webhook payload: {}", e)),
+        None => Ok(serde_json::json!({
+            "schema_version": SCHEMA_VERSION,
+            "event_id": svix_event_id,
+            "event_type": event.event_type,
+            "data": event.payload,
+        })),
+    }
+}
+
+struct PayloadFetch {
+    merchant_id: String,
+    payment_id: String,
+    event_id: u64,
+    event_type: String,
+}
+
+/// Per-event result of the batch fetch step
+#[derive(serde::Serialize, serde::Deserialize)]
+enum FetchedPayload {
+    /// Sealed when sealing is on
+    Fetched(serde_json::Value),
+    /// Will never succeed (e.g. the payment doesn't exist)
+    Failed(String),
+}
+
+/// REDACTION_RULES applied to a payload on its way to the journal or the
+/// dead letters (redaction crate)
+fn redact(
+    redactor: Option<&Redactor>,
+    metrics: &Metrics,
+    stage: &str,
+    event_type: &str,
+    payload: &mut serde_json::Value,
+) {
+    if let Some(redactor) = redactor {
+        metrics.redacted(stage, redactor.apply(event_type, payload));
+    }
+}
+
+/// Fetch concurrently, keyed by position in the batch. A retryable failure
+/// fails the step, so Restate runs it again; terminal ones only fail their
+/// own event. Payloads are redacted and sealed before they reach the
+/// journal.
+async fn fetch_payloads(
+    payloads: PayloadClient,
+    fetches: Vec<(usize, PayloadFetch)>,
+    concurrency: usize,
+    redactor: Option<Arc<Redactor>>,
+    metrics: Metrics,
+    sealer: Option<Sealer>,
+) -> HandlerResult<Json<Vec<(usize, FetchedPayload)>>> {
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    for (index, fetch) in fetches {
+        let payloads = payloads.clone();
+        let permits = permits.clone();
+        let redactor = redactor.clone();
+        let metrics = metrics.clone();
+        let sealer = sealer.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let result = match payloads
+                .fetch(&fetch.payment_id, fetch.event_id, &fetch.event_type)
+                .await
+            {
+                Ok(mut payload) => {
+                    redact(
+                        redactor.as_deref(),
+                        &metrics,
+                        "fetch",
+                        &fetch.event_type,
+                        &mut payload,
+                    );
+                    match &sealer {
+                        Some(sealer) => sealer.seal(&fetch.merchant_id, &payload).await,
+                        None => Ok(payload),
+                    }
+                }
+                Err(e) => Err(e),
+            };
+            (index, result)
+        });
+    }
+
+    let mut fetched = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (index, result) = joined.map_err(|e| format!("Payload fetch task failed: {}", e))?;
+        fetched.push((
+            index,
+            match result {
+                Ok(payload) => FetchedPayload::Fetched(payload),
+                Err(CallError::Terminal(message)) => FetchedPayload::Failed(message),
+                Err(e @ CallError::Retryable(_)) => return Err(handler_error(e)),
+            },
+        ));
+    }
+    Ok(Json(fetched))
+}
+
+/// Everything the Svix step needs, cloned into each run
+#[derive(Clone)]
+struct Submission {
+    event: DomainEvent,
+    svix_event_id: String,
+    payload: serde_json::Value,
+}
+
+/// Result of the Svix step, journaled so a replay returns it without
+/// submitting again
+#[derive(serde::Serialize, serde::Deserialize)]
+enum SvixOutcome {
+    Sent,
+    /// The merchant has no Svix application (yet)
+    NoApp,
+    /// Svix answered 429; submit again after a wait
+    RateLimited,
+    /// Svix is down; delivered straight to the merchant's endpoints
+    Direct,
+    /// Svix is down and some endpoints still have to get the direct
+    /// delivery; submit again once the first of them is due
+    EndpointsFailing { retry_in_ms: u64 },
+    /// DRY_RUN: validated and logged, not sent
+    DryRun,
+    /// Set aside for the merchant's next digest (digest.rs)
+    Digested,
+}
+
+impl SvixOutcome {
+    /// The merchant's endpoints have (or Svix will have) received it
+    fn delivered(&self) -> bool {
+        matches!(self, SvixOutcome::Sent | SvixOutcome::Direct)
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            SvixOutcome::Sent => "sent_to_svix",
+            SvixOutcome::NoApp => "skipped_no_app",
+            SvixOutcome::Direct => "delivered_direct",
+            SvixOutcome::DryRun => "dry_run",
+            SvixOutcome::Digested => "digested",
+            SvixOutcome::RateLimited | SvixOutcome::EndpointsFailing { .. } => {
+                unreachable!("submit_step waits these out")
+            }
+        }
+    }
+}
+
+/// Longest single wait between rate-limited submissions
+const MAX_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(60);
+
+impl SvixCallerImpl {
+    /// handler_error, counting the retry Restate is about to make
+    fn retry_error(&self, step: &str, e: CallError) -> HandlerError {
+        if matches!(e, CallError::Retryable(_)) {
+            self.metrics.retry(step);
+        }
+        handler_error(e)
+    }
+
+    /// process, inside the event's span
+    async fn process_event(
+        &self,
+        mut ctx: ObjectContext<'_>,
+        event: DomainEvent,
+    ) -> HandlerResult<String> {
         let event_id = format!("evt_{}", event.object_id);
 
+        check_key(&ctx, &event);
         tracing::info!("Processing event via Restate + Svix: {}", event_id);
 
-        // Get configuration from environment
-        let svix_token = std::env::var("SVIX_AUTH_TOKEN")
-            .map_err(|_| "SVIX_AUTH_TOKEN not set")?;
+        let event_uuid = event_uuid(&mut ctx, &event);
+        let svix_event_id = svix_event_id(&event, event_uuid);
 
-        let data_service_url = std::env::var("DATA_SERVICE_URL")
-            .unwrap_or_else(|_| "http://data-service:3002".to_string());
+        // Outside the merchant's delivery windows the event waits here
+        self.await_window(&ctx, &event).await?;
 
-        let event_uuid = Uuid::parse_str(&event.object_id)
-            .unwrap_or_else(|_| Uuid::new_v4());
+        // Saga: fetch -> transform -> submit -> record (see saga.rs)
+        let payment = match self.fetch_payload(&ctx, &event).await {
+            Ok(payment) => payment,
+            Err(e) => {
+                self.compensate(&ctx, "", &event, None, Stage::Fetch, &e.to_string())
+                    .await?;
+                return Err(e.into());
+            }
+        };
 
-        // Fetch enriched payload from data-service
-        let payload_url = format!("{}/payload/{}", data_service_url, event.object_id);
-        let client = reqwest::Client::new();
+        let mut body = webhook_body(&event, event_uuid, &svix_event_id, payment)?;
+        // Created before delivery, so its id goes out with the webhook
+        let confirmation = match self.confirmation_timeout(&ctx, "", &event).await? {
+            Some(timeout) => {
+                let (confirmation_id, confirmed) = ctx.awakeable::<String>();
+                confirmation::attach(&mut body, &confirmation_id);
+                Some((confirmed, timeout))
+            }
+            None => None,
+        };
+        let outcome = self
+            .transform_submit_record(&ctx, "", &event, &svix_event_id, body)
+            .await?;
 
-        tracing::info!("Fetching payload from: {}", payload_url);
-        let payment_payload = client
-            .get(&payload_url)
-            .timeout(Duration::from_secs(5))
-            .send()
-            .await
-            .map_err(|e| format!("Failed to fetch payload: {}", e))?
-            .json::<PaymentPayload>()
-            .await
-            .map_err(|e| format!("Failed to parse payload: {}", e))?;
+        if let Some((confirmed, timeout)) = confirmation.filter(|_| outcome.delivered()) {
+            let confirmed = restate_sdk::select! {
+                confirmed = confirmed => {
+                    confirmed?;
+                    true
+                },
+                timed_out = ctx.sleep(timeout) => {
+                    timed_out?;
+                    false
+                },
+            };
+            self.record_confirmation(&ctx, "", &event, confirmed, timeout)
+                .await?;
+        }
+
+        Ok(format!("{}:{}", outcome.as_str(), event_uuid))
+    }
+
+    /// One event of process_batch, inside the event's span. Only errors that
+    /// should retry the whole batch are returned as errors.
+    async fn process_batch_event(
+        &self,
+        ctx: &mut ObjectContext<'_>,
+        event: DomainEvent,
+        event_uuid: Uuid,
+        svix_event_id: &str,
+        fetched: Option<FetchedPayload>,
+    ) -> HandlerResult<BatchItemResult> {
+        let event_id = event.id;
+        let prefix = format!("event_{}_", event_id);
+        let fetched = match fetched {
+            Some(FetchedPayload::Fetched(payment)) => {
+                match self.open_journaled(&event.merchant_id, payment).await {
+                    Ok(payment) => Some(FetchedPayload::Fetched(payment)),
+                    Err(e) => Some(FetchedPayload::Failed(e.to_string())),
+                }
+            }
+            fetched => fetched,
+        };
+        let payment = match fetched {
+            Some(FetchedPayload::Fetched(payment)) => Some(payment),
+            Some(FetchedPayload::Failed(error)) => {
+                self.compensate(ctx, &prefix, &event, None, Stage::Fetch, &error)
+                    .await?;
+                return Ok(BatchItemResult::failed(event_id, error));
+            }
+            None => None,
+        };
+
+        let mut body = webhook_body(&event, event_uuid, svix_event_id, payment)?;
+        let confirmation = match self.confirmation_timeout(ctx, &prefix, &event).await? {
+            Some(timeout) => {
+                let (confirmation_id, confirmed) = ctx.awakeable::<String>();
+                confirmation::attach(&mut body, &confirmation_id);
+                Some((confirmed, timeout))
+            }
+            None => None,
+        };
+        let delivered = self
+            .transform_submit_record(ctx, &prefix, &event, svix_event_id, body)
+            .await;
+
+        // Like process: the next event waits for this one's confirmation
+        let confirmation =
+            confirmation.filter(|_| delivered.as_ref().is_ok_and(SvixOutcome::delivered));
+        if let Some((confirmed, timeout)) = confirmation {
+            let confirmed = restate_sdk::select! {
+                confirmed = confirmed => {
+                    confirmed?;
+                    true
+                },
+                timed_out = ctx.sleep(timeout) => {
+                    timed_out?;
+                    false
+                },
+            };
+            self.record_confirmation(ctx, &prefix, &event, confirmed, timeout)
+                .await?;
+        }
+
+        Ok(match delivered {
+            Ok(outcome) => BatchItemResult {
+                event_id,
+                status: outcome.as_str().to_string(),
+                error: None,
+            },
+            Err(e) => BatchItemResult::failed(event_id, e.to_string()),
+        })
+    }
+
+    /// The body in the merchant's payload template (transform.rs). Journaled,
+    /// so a retry delivers what was rendered the first time even if the
+    /// template changed in between.
+    async fn transformed(
+        &self,
+        ctx: &ObjectContext<'_>,
+        prefix: &str,
+        event: &DomainEvent,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, TerminalError> {
+        let db = self.db.clone();
+        let merchant_id = event.merchant_id.clone();
+        let event_type = event.event_type.clone();
+        let redactor = self.redactor.clone();
+        let metrics = self.metrics.clone();
+        let sealer = self.sealer.clone();
+        let Json(body) = ctx
+            .run(|| async move {
+                let template = transform::load(db.as_ref(), &merchant_id)
+                    .await
+                    .map_err(handler_error)?;
+                // A digest's items are already in the template
+                let mut body = match template {
+                    Some(template) if event_type != digest::EVENT_TYPE => {
+                        transform::apply(&template, &body)
+                    }
+                    _ => body,
+                };
+                // A template can lay the payload out anew, out of reach of
+                // field paths written for the fetched one; detectors still
+                // find what it carries over
+                redact(
+                    redactor.as_deref(),
+                    &metrics,
+                    "transform",
+                    &event_type,
+                    &mut body,
+                );
+                Ok(Json(
+                    Self::seal_journaled(sealer.as_ref(), &merchant_id, body).await?,
+                ))
+            })
+            .name(format!("{}transform", prefix))
+            .await?;
+        self.open_journaled(&event.merchant_id, body).await
+    }
+
+    /// Park the event while the merchant's delivery windows are closed
+    /// (windows.rs): a durable sleep, checked again after each one. The
+    /// merchant's later events queue behind it meanwhile.
+    async fn await_window(
+        &self,
+        ctx: &ObjectContext<'_>,
+        event: &DomainEvent,
+    ) -> Result<(), TerminalError> {
+        let mut round = 0u32;
+        loop {
+            let db = self.db.clone();
+            let merchant_id = event.merchant_id.clone();
+            let name = if round == 0 {
+                "delivery_window".to_string()
+            } else {
+                format!("delivery_window_recheck_{}", round)
+            };
+            let Json(closed) = ctx
+                .run(|| async move {
+                    let closed = windows::closed(db.as_ref(), &merchant_id)
+                        .await
+                        .map_err(handler_error)?;
+                    Ok(Json(closed))
+                })
+                .name(name)
+                .await?;
+            let Some(closed) = closed else {
+                if round > 0 {
+                    tracing::info!("Delivery window open again, releasing event {}", event.id);
+                }
+                return Ok(());
+            };
+
+            if round == 0 {
+                self.metrics.parked(&closed.reason);
+                if closed.reason == "paused" {
+                    tracing::info!(
+                        "Merchant {} is paused, parking event {} until it's resumed",
+                        event.merchant_id,
+                        event.id
+                    );
+                } else {
+                    tracing::info!(
+                        "Merchant {} isn't taking events ({}), parking event {} for {:?}",
+                        event.merchant_id,
+                        closed.reason,
+                        event.id,
+                        Duration::from_millis(closed.opens_in_ms)
+                    );
+                }
+            }
+            ctx.sleep(closed.wait()).await?;
+            round += 1;
+        }
+    }
+
+    /// Journaled, so a replay makes the same choice as the first run
+    async fn confirmation_timeout(
+        &self,
+        ctx: &ObjectContext<'_>,
+        prefix: &str,
+        event: &DomainEvent,
+    ) -> Result<Option<Duration>, TerminalError> {
+        let db = self.db.clone();
+        let merchant_id = event.merchant_id.clone();
+        let Json(timeout_ms) = ctx
+            .run(|| async move {
+                let timeout = confirmation::timeout(db.as_ref(), &merchant_id)
+                    .await
+                    .map_err(handler_error)?;
+                Ok(Json(timeout.map(|timeout| timeout.as_millis() as u64)))
+            })
+            .name(format!("{}confirmation_timeout", prefix))
+            .await?;
+        Ok(timeout_ms.map(Duration::from_millis))
+    }
+
+    async fn record_confirmation(
+        &self,
+        ctx: &ObjectContext<'_>,
+        prefix: &str,
+        event: &DomainEvent,
+        confirmed: bool,
+        timeout: Duration,
+    ) -> Result<(), TerminalError> {
+        if confirmed {
+            tracing::info!("Merchant confirmed event {}", event.id);
+        } else {
+            tracing::warn!("Event {} not confirmed within {:?}", event.id, timeout);
+        }
+
+        let caller = self.clone();
+        let event = event.clone();
+        ctx.run(|| async move {
+            if confirmed {
+                caller
+                    .record_attempt_via(&event, "merchant", "confirmed", None)
+                    .await;
+            } else {
+                let error = format!("Not confirmed within {:?}", timeout);
+                caller
+                    .record_attempt_via(&event, "merchant", "unconfirmed", Some(&error))
+                    .await;
+            }
+            Ok(())
+        })
+        .name(format!("{}record_confirmation", prefix))
+        .await
+    }
+
+    /// The fetch stage. Replays reuse the original event's snapshot.
+    /// Journaled, so a retry after a crash delivers the payload fetched the
+    /// first time.
+    async fn fetch_payload(
+        &self,
+        ctx: &ObjectContext<'_>,
+        event: &DomainEvent,
+    ) -> Result<Option<serde_json::Value>, TerminalError> {
+        if !is_payment_event(event) {
+            return Ok(None);
+        }
+
+        let payloads = self.payloads.clone();
+        let payment_id = event.object_id.clone();
+        let payload_event_id = event.replay_of.unwrap_or(event.id);
+        let event_type = event.event_type.clone();
+        let merchant_id = event.merchant_id.clone();
+        let redactor = self.redactor.clone();
+        let metrics = self.metrics.clone();
+        let sealer = self.sealer.clone();
+        let Json(payment) = ctx
+            .run(|| async move {
+                let mut payload = payloads
+                    .fetch(&payment_id, payload_event_id, &event_type)
+                    .await
+                    .map_err(handler_error)?;
+                redact(
+                    redactor.as_deref(),
+                    &metrics,
+                    "fetch",
+                    &event_type,
+                    &mut payload,
+                );
+                Ok(Json(
+                    Self::seal_journaled(sealer.as_ref(), &merchant_id, payload).await?,
+                ))
+            })
+            .name("fetch_payload")
+            .await?;
+        let payment = self.open_journaled(&event.merchant_id, payment).await?;
 
         tracing::info!("Fetched payload for payment: {}", event.object_id);
+        Ok(Some(payment))
+    }
 
-        // Construct webhook payload
-        let webhook_payload = WebhookPayload {
-            event_id: event_uuid.to_string(),
-            event_type: event.event_type.clone(),
-            payment: payment_payload,
+    /// The stages after fetch, compensating the one that fails for good
+    async fn transform_submit_record(
+        &self,
+        ctx: &ObjectContext<'_>,
+        prefix: &str,
+        event: &DomainEvent,
+        svix_event_id: &str,
+        body: serde_json::Value,
+    ) -> Result<SvixOutcome, TerminalError> {
+        let payload = match self.transformed(ctx, prefix, event, body).await {
+            Ok(payload) => payload,
+            Err(e) => {
+                self.compensate(ctx, prefix, event, None, Stage::Transform, &e.to_string())
+                    .await?;
+                return Err(e);
+            }
         };
 
-        // Initialize Svix client
-        // The SDK automatically detects the region from the token (.eu suffix)
-        tracing::info!("Initializing Svix client");
-        let svix = Svix::new(svix_token, None);
+        let submission = Submission {
+            event: event.clone(),
+            svix_event_id: svix_event_id.to_string(),
+            payload,
+        };
+        if self.digested(ctx, prefix, &submission).await? {
+            let outcome = SvixOutcome::Digested;
+            self.record_outcome(ctx, prefix, &submission, &outcome)
+                .await?;
+            return Ok(outcome);
+        }
+        let outcome = match self.deliver(ctx, prefix, &submission).await {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                let payload = Some(&submission.payload);
+                self.compensate(ctx, prefix, event, payload, Stage::Submit, &e.to_string())
+                    .await?;
+                return Err(e);
+            }
+        };
 
-        // Create message in Svix
-        // Application ID is the merchant_id (each merchant has their own Svix application)
-        tracing::info!("Sending message to Svix for application: {}", event.merchant_id);
+        self.record_outcome(ctx, prefix, &submission, &outcome)
+            .await?;
+        Ok(outcome)
+    }
+
+    /// Set the event aside if the merchant takes its type in a digest
+    /// (digest.rs), and make sure the digest's flush is scheduled. Journaled,
+    /// so a replay makes the same choice without setting it aside twice.
+    async fn digested(
+        &self,
+        ctx: &ObjectContext<'_>,
+        prefix: &str,
+        submission: &Submission,
+    ) -> Result<bool, TerminalError> {
+        let db = self.db.clone();
+        let sealer = self.sealer.clone();
+        let submission_for_run = submission.clone();
+        let Json(interval_ms) = ctx
+            .run(|| async move {
+                let interval = digest::set_aside(
+                    db.as_ref(),
+                    sealer.as_ref(),
+                    &submission_for_run.event,
+                    &submission_for_run.payload,
+                )
+                .await
+                .map_err(handler_error)?;
+                Ok(Json(interval.map(|interval| interval.as_millis() as u64)))
+            })
+            .name(format!("{}digest", prefix))
+            .await?;
+        let Some(interval_ms) = interval_ms else {
+            return Ok(false);
+        };
+
+        let event = &submission.event;
+        ctx.object_client::<digest::DigestsClient>(event.merchant_id.clone())
+            .schedule(interval_ms)
+            .send();
+        tracing::info!(
+            "Event {} ({}) set aside for merchant {}'s digest",
+            event.id,
+            event.event_type,
+            event.merchant_id
+        );
+        Ok(true)
+    }
+
+    /// The record stage: how the submit stage ended, in delivery_attempts.
+    /// An event skipped for lack of a Svix application also goes to
+    /// svix_dead_letters, to be re-driven once the application exists.
+    async fn record_outcome(
+        &self,
+        ctx: &ObjectContext<'_>,
+        prefix: &str,
+        submission: &Submission,
+        outcome: &SvixOutcome,
+    ) -> Result<(), TerminalError> {
+        self.metrics.outcome(outcome.as_str());
+        let (delivery_path, status) = match outcome {
+            SvixOutcome::Sent => ("svix", "succeeded"),
+            SvixOutcome::Direct => ("direct", "succeeded"),
+            SvixOutcome::DryRun => ("svix", "dry_run"),
+            SvixOutcome::NoApp => ("svix", "skipped"),
+            SvixOutcome::Digested => ("digest", "digested"),
+            SvixOutcome::RateLimited | SvixOutcome::EndpointsFailing { .. } => {
+                unreachable!("submit_step waits these out")
+            }
+        };
+        let error = matches!(outcome, SvixOutcome::NoApp).then(|| {
+            format!(
+                "Svix application not found: {}",
+                svix_app_id(&submission.event)
+            )
+        });
+
+        let caller = self.clone();
+        let submission = submission.clone();
+        ctx.run(|| async move {
+            let event = &submission.event;
+            caller
+                .record_attempt_via(event, delivery_path, status, error.as_deref())
+                .await;
+            if let Some(error) = &error {
+                caller
+                    .dead_letter(event, Some(&submission.payload), error)
+                    .await?;
+            }
+            Ok(())
+        })
+        .name(format!("{}record", prefix))
+        .await
+    }
+
+    /// The compensation step for a stage that failed for good: the failure
+    /// goes into delivery_attempts and the event into svix_dead_letters.
+    /// Journaled, so a replayed invocation doesn't write either twice.
+    async fn compensate(
+        &self,
+        ctx: &ObjectContext<'_>,
+        prefix: &str,
+        event: &DomainEvent,
+        payload: Option<&serde_json::Value>,
+        stage: Stage,
+        error: &str,
+    ) -> Result<(), TerminalError> {
+        self.metrics.outcome("failed");
+        let error = format!("{} failed: {}", stage.as_str(), error);
+        tracing::warn!("Compensating event {}: {}", event.id, error);
+
+        let caller = self.clone();
+        let event = event.clone();
+        let payload = payload.cloned();
+        ctx.run(|| async move {
+            caller.record_attempt(&event, "failed", Some(&error)).await;
+            caller.dead_letter(&event, payload.as_ref(), &error).await
+        })
+        .name(format!("{}compensate", prefix))
+        .await
+    }
 
+    /// Submit to Svix, creating the merchant's application first if it is
+    /// missing. `prefix` keeps step names apart within a batch.
+    async fn deliver(
+        &self,
+        ctx: &ObjectContext<'_>,
+        prefix: &str,
+        submission: &Submission,
+    ) -> Result<SvixOutcome, TerminalError> {
+        let step = |name: &str| format!("{}{}", prefix, name);
+        let outcome = self
+            .submit_step(ctx, &step("svix_submit"), submission, !self.auto_provision)
+            .await?;
+
+        // New merchant: create its Svix application, then submit once more
+        if matches!(outcome, SvixOutcome::NoApp) && self.auto_provision {
+            let caller = self.clone();
+            let provisioned_event = submission.event.clone();
+            ctx.run(|| async move { caller.provision_app(&provisioned_event).await })
+                .name(step("svix_provision_app"))
+                .await?;
+
+            return self
+                .submit_step(ctx, &step("svix_submit_after_provision"), submission, true)
+                .await;
+        }
+        Ok(outcome)
+    }
+
+    /// Run the Svix step, and while Svix rate limits, suspend the invocation
+    /// with a durable sleep and run it again. The SDK's error carries the
+    /// status but not the Retry-After header, so the wait doubles from
+    /// SVIX_RATE_LIMIT_BACKOFF_MS up to MAX_RATE_LIMIT_BACKOFF. Waiting
+    /// rather than failing keeps the merchant's later events queued behind
+    /// this one, in order. Direct deliveries failing at endpoints with a
+    /// health score wait the same way, for the spacing direct.rs gives.
+    async fn submit_step(
+        &self,
+        ctx: &ObjectContext<'_>,
+        step: &str,
+        submission: &Submission,
+        last_attempt: bool,
+    ) -> Result<SvixOutcome, TerminalError> {
+        let mut round = 0u32;
+        loop {
+            let caller = self.clone();
+            let submission_for_run = submission.clone();
+            let name = if round == 0 {
+                step.to_string()
+            } else {
+                format!("{}_retry_{}", step, round)
+            };
+            let Json(outcome) = ctx
+                .run(|| async move { caller.submit(submission_for_run, last_attempt).await })
+                .name(name)
+                .await?;
+
+            let wait = match outcome {
+                SvixOutcome::RateLimited => {
+                    let wait = self
+                        .rate_limit_backoff
+                        .saturating_mul(2u32.saturating_pow(round))
+                        .min(MAX_RATE_LIMIT_BACKOFF);
+                    self.metrics.retry("svix_rate_limited");
+                    tracing::warn!(
+                        "Svix rate limited event {}, waiting {:?} before submitting again",
+                        submission.event.id,
+                        wait
+                    );
+                    wait
+                }
+                SvixOutcome::EndpointsFailing { retry_in_ms } => {
+                    let wait = Duration::from_millis(retry_in_ms);
+                    self.metrics.retry("direct_delivery");
+                    tracing::warn!(
+                        "Direct delivery of event {} failed, waiting {:?} by endpoint health",
+                        submission.event.id,
+                        wait
+                    );
+                    wait
+                }
+                outcome => return Ok(outcome),
+            };
+            ctx.sleep(wait).await?;
+            round += 1;
+        }
+    }
+
+    /// Create the Svix message. Retryable failures are recorded and return
+    /// Err so Restate runs the step again; how it finally ended is recorded
+    /// by the record stage. A missing application is only final on the
+    /// `last_attempt`.
+    async fn submit(
+        &self,
+        submission: Submission,
+        last_attempt: bool,
+    ) -> HandlerResult<Json<SvixOutcome>> {
+        let Submission {
+            event,
+            svix_event_id,
+            payload,
+        } = submission;
+        let event = &event;
+
+        // Application ID is the merchant_id (each merchant has their own Svix application)
+        let app_id = svix_app_id(event);
         let message_in = MessageIn {
             event_type: event.event_type.clone(),
-            event_id: Some(event_uuid.to_string()),
-            payload: serde_json::to_value(&webhook_payload)
-                .map_err(|e| format!("Failed to serialize webhook payload: {}", e))?,
+            event_id: Some(svix_event_id.clone()),
+            payload: payload.clone(),
+            channels: Some(svix_channels(event)),
+            tags: Some(svix_tags(event)),
             ..MessageIn::default()
         };
 
-        match svix.message()
-            .create(event.merchant_id.clone(), message_in, None)
+        // Svix would reject it with a 422, and so would every retry
+        if let Err(e) = validate_message(&message_in) {
+            return Err(handler_error(CallError::Terminal(format!(
+                "Invalid Svix message for event {}: {}",
+                event.id, e
+            ))));
+        }
+
+        if self.dry_run {
+            tracing::info!(
+                target: "svix_dry_run",
+                "Would create message in Svix application {}: {}",
+                app_id,
+                serde_json::to_string(&message_in).unwrap_or_default()
+            );
+            return Ok(Json(SvixOutcome::DryRun));
+        }
+
+        if self.fallback_ready(event) && self.direct.as_ref().is_some_and(|d| d.svix_down()) {
+            tracing::warn!("Svix is down, delivering event {} directly", event.id);
+            return self.deliver_direct(event, &svix_event_id, &payload).await;
+        }
+
+        // The merchant's region decides which Svix account to use
+        let svix = self
+            .router
+            .client_for(&event.merchant_id)
             .await
-        {
+            .map_err(handler_error)?;
+
+        tracing::info!("Sending message to Svix for application: {}", app_id);
+
+        let started = Instant::now();
+        let created = svix
+            .message()
+            .create(app_id.clone(), message_in, None)
+            .await;
+        self.metrics.observe_svix(
+            "create_message",
+            started,
+            created.as_ref().map(|_| ()).map_err(svix_status),
+        );
+
+        match created {
             Ok(_) => {
-                tracing::info!("Message sent to Svix successfully: {}", event_uuid);
+                self.metrics.observe_delivery("svix", started);
+                if let Some(direct) = &self.direct {
+                    direct.record_svix_success();
+                }
+                tracing::info!("Message sent to Svix successfully: {}", svix_event_id);
                 tracing::info!("Svix will handle delivery to merchant's endpoints");
-                Ok(format!("sent_to_svix:{}", event_uuid))
+                Ok(Json(SvixOutcome::Sent))
             }
             Err(e) => {
                 let error_msg = format!("{}", e);
+                let status = svix_status(&e);
+
+                // Same domain event submitted before (e.g. the step crashed
+                // after Svix accepted it): the id is unique per event, so
+                // this one is already sent
+                if status == Some(409) {
+                    tracing::info!("Svix already has message {}", svix_event_id);
+                    return Ok(Json(SvixOutcome::Sent));
+                }
+
+                if status == Some(429) {
+                    self.record_attempt(event, "rate_limited", Some(&error_msg)).await;
+                    return Ok(Json(SvixOutcome::RateLimited));
+                }
 
                 // Check if error is 404 Application not found
-                if error_msg.contains("404") && error_msg.contains("not_found") {
+                if status == Some(404) {
+                    if !last_attempt {
+                        tracing::info!("Svix application not found: {}", app_id);
+                        return Ok(Json(SvixOutcome::NoApp));
+                    }
                     tracing::warn!(
-                        "Svix application not found for merchant_id: {}. Skipping event. \
+                        "Svix application not found: {}. Skipping event. \
                         Create application via: curl -X POST https://api.eu.svix.com/api/v1/app \
                         -H 'Authorization: Bearer YOUR_TOKEN' \
                         -d '{{\"name\": \"Merchant Name\", \"uid\": \"{}\"}}' ",
-                        event.merchant_id,
-                        event.merchant_id
+                        app_id,
+                        app_id
                     );
-                    // Return success to prevent Restate from retrying
-                    Ok(format!("skipped_no_app:{}", event_uuid))
+                    // Return success to prevent Restate from retrying;
+                    // recorded as skipped by the record stage
+                    Ok(Json(SvixOutcome::NoApp))
                 } else {
-                    // Other errors are retryable
-                    Err(format!("Svix API error: {}", e).into())
+                    // A failure that ends the invocation is recorded by the
+                    // compensation step, every retried one here
+                    let e = svix_error("Svix API error", e);
+                    if matches!(e, CallError::Retryable(_)) {
+                        self.record_attempt(event, "failed", Some(&error_msg)).await;
+                    }
+
+                    // 5xx or no response: Svix itself is failing
+                    if status.is_none_or(|status| status >= 500) {
+                        if let Some(direct) = &self.direct {
+                            direct.record_svix_failure();
+                            if self.fallback_ready(event) && direct.svix_down() {
+                                tracing::warn!(
+                                    "Svix keeps failing, delivering event {} directly",
+                                    event.id
+                                );
+                                return self.deliver_direct(event, &svix_event_id, &payload).await;
+                            }
+                        }
+                    }
+
+                    Err(self.retry_error("svix_submit", e))
                 }
             }
         }
     }
+
+    /// Direct delivery needs the endpoints from the database, and is live only
+    fn fallback_ready(&self, event: &DomainEvent) -> bool {
+        self.direct.is_some() && self.db.is_some() && event.mode != "test"
+    }
+
+    async fn deliver_direct(
+        &self,
+        event: &DomainEvent,
+        svix_event_id: &str,
+        payload: &serde_json::Value,
+    ) -> HandlerResult<Json<SvixOutcome>> {
+        let (Some(direct), Some(db)) = (&self.direct, &self.db) else {
+            return Err("Direct delivery is not configured".into());
+        };
+
+        let started = Instant::now();
+        match direct
+            .deliver(
+                db,
+                &event.merchant_id,
+                event.id,
+                svix_event_id,
+                payload,
+                self.sealer.as_ref(),
+            )
+            .await
+        {
+            Ok(attempts) => {
+                self.metrics.observe_delivery("direct", started);
+                self.record_endpoint_attempts(event, &attempts).await;
+                tracing::info!(
+                    "Event {} delivered directly, to {} endpoints this round",
+                    event.id,
+                    attempts.len()
+                );
+                Ok(Json(SvixOutcome::Direct))
+            }
+            Err(DeliveryFailure {
+                error,
+                attempts,
+                retry_in,
+            }) => {
+                self.record_endpoint_attempts(event, &attempts).await;
+                if !matches!(error, CallError::Retryable(_)) {
+                    return Err(self.retry_error("direct_delivery", error));
+                }
+                let error_msg = error.to_string();
+                self.record_attempt_via(event, "direct", "failed", Some(&error_msg))
+                    .await;
+                match retry_in {
+                    Some(wait) => Ok(Json(SvixOutcome::EndpointsFailing {
+                        retry_in_ms: wait.as_millis() as u64,
+                    })),
+                    None => Err(self.retry_error("direct_delivery", error)),
+                }
+            }
+        }
+    }
+
+    /// Each endpoint's part in a direct delivery: a 'direct_endpoint' row in
+    /// delivery_attempts, and a delivery event for endpoint health
+    async fn record_endpoint_attempts(&self, event: &DomainEvent, attempts: &[EndpointAttempt]) {
+        for attempt in attempts {
+            if let Some(publisher) = &self.publisher {
+                publisher.endpoint_attempt(event, attempt).await;
+            }
+            let Some(db) = &self.db else {
+                continue;
+            };
+            let status = if attempt.succeeded {
+                "succeeded"
+            } else {
+                "failed"
+            };
+            let response = attempt.response.as_ref();
+            let result = sqlx::query(
+                r#"
+                INSERT INTO delivery_attempts
+                    (event_id, merchant_id, delivery_path, status, error, response_status,
+                     response_body, endpoint_id)
+                VALUES ($1, $2::UUID, 'direct_endpoint', $3, $4, $5, $6, $7::UUID)
+                "#,
+            )
+            .bind(event.id as i64)
+            .bind(&event.merchant_id)
+            .bind(status)
+            .bind(attempt.error.as_deref())
+            .bind(attempt.response_status.map(i32::from))
+            .bind(response.map(|response| response.body.as_str()))
+            .bind(&attempt.endpoint_id)
+            .execute(db)
+            .await;
+
+            if let Err(e) = result {
+                tracing::warn!(
+                    "Failed to record attempt at endpoint {} for event {}: {}",
+                    attempt.endpoint_id,
+                    event.id,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Create the Svix application for the event's merchant (uid = app id),
+    /// named after the merchant. get_or_create makes a retried step harmless.
+    async fn provision_app(&self, event: &DomainEvent) -> HandlerResult<()> {
+        let app_id = svix_app_id(event);
+        let merchant_name = self.merchant_name(&event.merchant_id).await;
+        let name = if event.mode == "test" {
+            format!("{} (test)", merchant_name)
+        } else {
+            merchant_name
+        };
+
+        tracing::info!("Creating Svix application {} ({})", app_id, name);
+        let svix = self
+            .router
+            .client_for(&event.merchant_id)
+            .await
+            .map_err(handler_error)?;
+        let started = Instant::now();
+        let created = svix
+            .application()
+            .get_or_create(
+                ApplicationIn {
+                    name,
+                    uid: Some(app_id.clone()),
+                    ..ApplicationIn::default()
+                },
+                None,
+            )
+            .await;
+        self.metrics.observe_svix(
+            "create_application",
+            started,
+            created.as_ref().map(|_| ()).map_err(svix_status),
+        );
+        created.map_err(|e| {
+            self.retry_error(
+                "svix_provision_app",
+                svix_error(&format!("Failed to create Svix application {}", app_id), e),
+            )
+        })?;
+        Ok(())
+    }
+
+    /// The merchant's name, or its id when the database isn't configured or
+    /// doesn't know the merchant
+    async fn merchant_name(&self, merchant_id: &str) -> String {
+        let Some(db) = &self.db else {
+            return merchant_id.to_string();
+        };
+
+        let name = sqlx::query_scalar::<_, String>("SELECT name FROM merchants WHERE id = $1::UUID")
+            .bind(merchant_id)
+            .fetch_optional(db)
+            .await;
+
+        match name {
+            Ok(Some(name)) => name,
+            Ok(None) => merchant_id.to_string(),
+            Err(e) => {
+                tracing::warn!("Failed to look up merchant {}: {}", merchant_id, e);
+                merchant_id.to_string()
+            }
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
-    let port = std::env::var("PORT").unwrap_or_else(|_| "9080".to_string());
+    logging::init(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    let settings: Settings = config::load_or_exit("svix-caller");
+    let port = settings.port;
 
     tracing::info!("Starting Svix Caller service on port {}", port);
     tracing::info!("This service sends webhook events to Svix Cloud for delivery");
 
+    let db = match &settings.database_url {
+        Some(database_url) => Some(
+            PgPoolOptions::new()
+                .max_connections(5)
+                .connect(database_url)
+                .await
+                .expect("Failed to connect to database"),
+        ),
+        None => {
+            tracing::warn!("DATABASE_URL not set - delivery attempts will not be recorded");
+            None
+        }
+    };
+
+    let metrics = Metrics::new(service_metrics::ServiceMetrics::new(
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+    ));
+    let payloads =
+        PayloadClient::from_env(metrics.clone()).expect("Invalid payload transport configuration");
+    let secrets = secrets::SecretLoader::from_env().expect("Invalid secrets configuration");
+    let sealer = Sealer::load(db.as_ref(), &secrets)
+        .await
+        .expect("Invalid payload sealing configuration");
+    if sealer.is_some() {
+        tracing::info!("Sealing journaled and dead-lettered payloads (PAYLOAD_MASTER_KEYS)");
+    }
+    // Validated to load; if the file broke since, stop rather than run
+    // without redaction
+    let redactor = settings
+        .redaction_rules
+        .as_deref()
+        .map(|path| Redactor::load(path).map(Arc::new))
+        .transpose()
+        .expect("Invalid redaction rules");
+    if let Some(path) = &settings.redaction_rules {
+        tracing::info!(
+            "Redacting journaled and dead-lettered payloads with {}",
+            path.display()
+        );
+    }
+    let router = SvixRouter::load(db.clone(), &secrets)
+        .await
+        .expect("Invalid Svix token configuration");
+    if let Some(interval) = secrets.refresh_interval(routing::SECRETS) {
+        let router = router.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                router.refresh(&secrets).await;
+            }
+        });
+    }
+
+    match operational::OperationalState::from_env(db.clone(), &metrics)
+        .expect("Invalid operational webhook configuration")
+    {
+        Some(state) => {
+            tokio::spawn(operational::serve(settings.operational_port, state));
+        }
+        None => tracing::warn!(
+            "SVIX_OPERATIONAL_WEBHOOK_SECRET not set - Svix delivery outcomes will not be recorded"
+        ),
+    }
+
+    // On unless explicitly disabled; without it events for merchants that have
+    // no Svix application yet are skipped
+    let auto_provision = settings.svix_auto_provision_apps;
+
+    let rate_limit_backoff = Duration::from_millis(settings.svix_rate_limit_backoff_ms);
+
+    // Everything up to the Svix call runs, so staging without a Svix account
+    // still exercises payload fetching and validation
+    let dry_run = settings.dry_run;
+    if dry_run {
+        tracing::warn!("DRY_RUN enabled - messages are logged, not sent to Svix");
+    }
+
+    // In the background, so a Svix outage doesn't hold up startup
+    if !dry_run && settings.svix_sync_event_types {
+        let router = router.clone();
+        tokio::spawn(async move {
+            if let Err(e) = event_types::sync_all(&router).await {
+                tracing::warn!("Failed to sync Svix event types: {}", e);
+            }
+        });
+    }
+
+    let health = health::registry(router.clone(), dry_run)
+        .expect("Invalid health check configuration");
+    tokio::spawn(health::serve(settings.health_port, health, metrics.clone()));
+
+    let batch_concurrency = settings.svix_batch_fetch_concurrency;
+
+    let direct = DirectDelivery::from_settings(&settings, &metrics)
+        .expect("Invalid direct delivery configuration");
+    if direct.is_some() && db.is_none() {
+        tracing::warn!("SVIX_DIRECT_FALLBACK needs DATABASE_URL - direct delivery disabled");
+    }
+
+    // Runs alongside the server below: Restate calls back into it to
+    // discover the handlers while the deployment is being registered
+    match bootstrap::Bootstrap::from_env(&port.to_string())
+        .expect("Invalid Restate bootstrap configuration")
+    {
+        Some(bootstrap) => {
+            tokio::spawn(bootstrap.run());
+        }
+        None => tracing::info!(
+            "RESTATE_ADMIN_URL not set - register with scripts/register-restate-handler.sh"
+        ),
+    }
+
+    let publisher =
+        Publisher::from_settings(&settings, &metrics).expect("Invalid Kafka configuration");
+
+    let mut endpoint = Endpoint::builder();
+    match db.clone() {
+        Some(db) => {
+            endpoint = endpoint
+                .bind(DeadLettersImpl { db: db.clone() }.serve())
+                .bind(
+                    DigestsImpl {
+                        db,
+                        sealer: sealer.clone(),
+                    }
+                    .serve(),
+                )
+        }
+        None => tracing::warn!(
+            "DATABASE_URL not set - failed events will not be dead-lettered, nor digested"
+        ),
+    }
+
     HttpServer::new(
-        Endpoint::builder()
-            .bind(SvixCallerImpl.serve())
+        endpoint
+            .bind(
+                SvixCallerImpl {
+                    db,
+                    payloads,
+                    auto_provision,
+                    rate_limit_backoff,
+                    direct,
+                    router: router.clone(),
+                    batch_concurrency,
+                    dry_run,
+                    metrics,
+                    publisher,
+                    sealer,
+                    redactor,
+                }
+                .serve(),
+            )
+            .bind(SvixAdminImpl { router }.serve())
             .build(),
     )
     .listen_and_serve(format!("0.0.0.0:{}", port).parse().unwrap())
@@ -0,0 +1,227 @@
+use axum::response::IntoResponse;
+use http_client::HttpMetrics;
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry};
+use service_metrics::ServiceMetrics;
+use std::time::Instant;
+
+use crate::errors::CallError;
+
+// ==============================================================================
+// METRICS: Prometheus exposition for the Svix path
+// ==============================================================================
+//
+// Enough to put the Svix path next to direct delivery: how long a message
+// takes to be accepted by Svix versus delivered straight to the endpoints
+// (svix_caller_delivery_duration_seconds by path), what the payload fetch in
+// front of it costs, which Svix status codes come back, and how often steps
+// are retried. Served on /metrics on HEALTH_PORT, and on OPERATIONAL_PORT
+// alongside the operational webhook counters.
+//
+// Counters are per handler attempt: an invocation Restate retries after a
+// crash or a retryable error is counted again. The process metrics every
+// service has (service-metrics crate) come with them, and so do the
+// http_client_* families of the payload and direct delivery clients.
+// svix_caller_values_redacted_total counts what REDACTION_RULES replaced, by
+// stage (fetch, transform, dead_letter), once per step run.
+// svix_caller_events_parked_total counts events held outside the merchant's
+// delivery windows or while it's paused (windows.rs), once when they're parked.
+
+#[derive(Clone)]
+pub struct Metrics {
+    service: ServiceMetrics,
+    invocations: IntCounterVec,
+    outcomes: IntCounterVec,
+    fetch_duration: HistogramVec,
+    svix_duration: HistogramVec,
+    svix_errors: IntCounterVec,
+    delivery_duration: HistogramVec,
+    retries: IntCounterVec,
+    redacted: IntCounterVec,
+    parked: IntCounterVec,
+    http: HttpMetrics,
+}
+
+impl Metrics {
+    pub fn new(service: ServiceMetrics) -> Self {
+        let registry = service.registry();
+        let latency_buckets = vec![
+            0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+        ];
+
+        let invocations = IntCounterVec::new(
+            Opts::new(
+                "svix_caller_invocations_total",
+                "Handler invocations, by handler",
+            ),
+            &["handler"],
+        )
+        .unwrap();
+        let outcomes = IntCounterVec::new(
+            Opts::new(
+                "svix_caller_event_outcomes_total",
+                "Events by how they ended (sent_to_svix, delivered_direct, failed, ...)",
+            ),
+            &["outcome"],
+        )
+        .unwrap();
+        let fetch_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "svix_caller_payload_fetch_duration_seconds",
+                "Latency of payload fetches from data-service",
+            )
+            .buckets(latency_buckets.clone()),
+            &["result"],
+        )
+        .unwrap();
+        let svix_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "svix_caller_svix_request_duration_seconds",
+                "Latency of Svix API calls",
+            )
+            .buckets(latency_buckets.clone()),
+            &["operation"],
+        )
+        .unwrap();
+        let svix_errors = IntCounterVec::new(
+            Opts::new(
+                "svix_caller_svix_errors_total",
+                "Failed Svix API calls, by status (\"none\" when there was no response)",
+            ),
+            &["operation", "status"],
+        )
+        .unwrap();
+        let delivery_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "svix_caller_delivery_duration_seconds",
+                "Time to hand a message off: accepted by Svix, or delivered directly",
+            )
+            .buckets(latency_buckets),
+            &["path"],
+        )
+        .unwrap();
+        let retries = IntCounterVec::new(
+            Opts::new(
+                "svix_caller_retries_total",
+                "Steps failed with a retryable error (or rate limited), by step",
+            ),
+            &["step"],
+        )
+        .unwrap();
+        let redacted = IntCounterVec::new(
+            Opts::new(
+                "svix_caller_values_redacted_total",
+                "Payload values and matches replaced by REDACTION_RULES, by stage",
+            ),
+            &["stage"],
+        )
+        .unwrap();
+        let parked = IntCounterVec::new(
+            Opts::new(
+                "svix_caller_events_parked_total",
+                "Events held for the merchant's delivery windows, by reason",
+            ),
+            &["reason"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(invocations.clone())).unwrap();
+        registry.register(Box::new(outcomes.clone())).unwrap();
+        registry.register(Box::new(fetch_duration.clone())).unwrap();
+        registry.register(Box::new(svix_duration.clone())).unwrap();
+        registry.register(Box::new(svix_errors.clone())).unwrap();
+        registry
+            .register(Box::new(delivery_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(retries.clone())).unwrap();
+        registry.register(Box::new(redacted.clone())).unwrap();
+        registry.register(Box::new(parked.clone())).unwrap();
+        let http = HttpMetrics::new(registry);
+
+        Metrics {
+            service,
+            invocations,
+            outcomes,
+            fetch_duration,
+            svix_duration,
+            svix_errors,
+            delivery_duration,
+            retries,
+            redacted,
+            parked,
+            http,
+        }
+    }
+
+    /// For modules that keep their own metrics in the same exposition
+    pub fn registry(&self) -> &Registry {
+        self.service.registry()
+    }
+
+    /// For the outbound HTTP clients (http-client crate)
+    pub fn http(&self) -> &HttpMetrics {
+        &self.http
+    }
+
+    pub fn invoked(&self, handler: &str) {
+        self.invocations.with_label_values(&[handler]).inc();
+    }
+
+    pub fn outcome(&self, outcome: &str) {
+        self.outcomes.with_label_values(&[outcome]).inc();
+    }
+
+    pub fn observe_fetch<T>(&self, started: Instant, result: &Result<T, CallError>) {
+        let label = match result {
+            Ok(_) => "ok",
+            Err(CallError::Retryable(_)) => {
+                self.retry("payload_fetch");
+                "retryable"
+            }
+            Err(CallError::Terminal(_)) => "terminal",
+        };
+        self.fetch_duration
+            .with_label_values(&[label])
+            .observe(started.elapsed().as_secs_f64());
+    }
+
+    /// A failed call carries its status, None when it got no response
+    pub fn observe_svix(&self, operation: &str, started: Instant, result: Result<(), Option<u16>>) {
+        self.svix_duration
+            .with_label_values(&[operation])
+            .observe(started.elapsed().as_secs_f64());
+        if let Err(status) = result {
+            let status = status.map_or_else(|| "none".to_string(), |s| s.to_string());
+            self.svix_errors
+                .with_label_values(&[operation, &status])
+                .inc();
+        }
+    }
+
+    /// A successful hand-off through `path` ("svix" or "direct")
+    pub fn observe_delivery(&self, path: &str, started: Instant) {
+        self.delivery_duration
+            .with_label_values(&[path])
+            .observe(started.elapsed().as_secs_f64());
+    }
+
+    pub fn retry(&self, step: &str) {
+        self.retries.with_label_values(&[step]).inc();
+    }
+
+    pub fn redacted(&self, stage: &str, values: usize) {
+        if values > 0 {
+            self.redacted
+                .with_label_values(&[stage])
+                .inc_by(values as u64);
+        }
+    }
+
+    /// "paused", "maintenance" or "delivery_window" (windows.rs)
+    pub fn parked(&self, reason: &str) {
+        self.parked.with_label_values(&[reason]).inc();
+    }
+
+    pub fn render(&self) -> impl IntoResponse {
+        self.service.render()
+    }
+}
@@ -0,0 +1,263 @@
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Router,
+};
+use prometheus::{IntCounterVec, Opts};
+use serde::Deserialize;
+use sqlx::PgPool;
+use webhook_signing::{Verifier, SVIX_HEADERS};
+
+use crate::metrics::Metrics;
+
+// ==============================================================================
+// OPERATIONAL WEBHOOKS: What Svix actually delivered to merchants
+// ==============================================================================
+//
+// svix-caller only knows that Svix accepted a message. Svix reports what
+// happened next through operational webhooks, which land here:
+//
+//   message.attempt.failing / .exhausted / .recovered -> delivery_attempts
+//   endpoint.disabled                                  -> log + metric
+//
+// Requests are verified with the operational webhook secret from the Svix
+// dashboard (SVIX_OPERATIONAL_WEBHOOK_SECRET); without it the endpoint isn't
+// served. Attempts are tied back to domain_events through the Svix event id
+// svix-caller submitted: "evt_<id>", "<payment>_replay_<id>", or the payment
+// id itself, which maps to the payment's latest event.
+
+#[derive(Clone)]
+pub struct OperationalState {
+    verifier: Verifier,
+    db: Option<PgPool>,
+    metrics: OperationalMetrics,
+}
+
+/// Registered with the service's metrics (metrics.rs), which /metrics here
+/// serves in full
+#[derive(Clone)]
+struct OperationalMetrics {
+    all: Metrics,
+    events: IntCounterVec,
+}
+
+impl OperationalMetrics {
+    fn new(metrics: &Metrics) -> Self {
+        let events = IntCounterVec::new(
+            Opts::new(
+                "svix_caller_operational_events_total",
+                "Svix operational webhooks received, by type",
+            ),
+            &["event_type"],
+        )
+        .unwrap();
+        metrics
+            .registry()
+            .register(Box::new(events.clone()))
+            .unwrap();
+        OperationalMetrics {
+            all: metrics.clone(),
+            events,
+        }
+    }
+}
+
+impl OperationalState {
+    /// None when SVIX_OPERATIONAL_WEBHOOK_SECRET is unset
+    pub fn from_env(db: Option<PgPool>, metrics: &Metrics) -> Result<Option<Self>, String> {
+        let Some(secret) = std::env::var("SVIX_OPERATIONAL_WEBHOOK_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())
+        else {
+            return Ok(None);
+        };
+        let verifier = Verifier::new(&[&secret])
+            .map_err(|e| format!("Invalid SVIX_OPERATIONAL_WEBHOOK_SECRET: {}", e))?;
+
+        Ok(Some(OperationalState {
+            verifier,
+            db,
+            metrics: OperationalMetrics::new(metrics),
+        }))
+    }
+}
+
+fn router(state: OperationalState) -> Router {
+    Router::new()
+        .route("/svix/operational", post(receive))
+        .route("/metrics", get(metrics_handler))
+        .with_state(state)
+}
+
+#[derive(Deserialize)]
+struct OperationalWebhook {
+    #[serde(rename = "type")]
+    event_type: String,
+    data: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageAttempt {
+    app_uid: Option<String>,
+    msg_event_id: Option<String>,
+    endpoint_id: String,
+    last_attempt: Option<LastAttempt>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LastAttempt {
+    response_status_code: Option<i32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EndpointDisabled {
+    app_uid: Option<String>,
+    endpoint_id: String,
+}
+
+async fn receive(
+    State(state): State<OperationalState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if let Err(e) = state.verifier.verify_headers(
+        &SVIX_HEADERS,
+        |name| headers.get(name).and_then(|value| value.to_str().ok()),
+        &body,
+    ) {
+        tracing::warn!("Rejected operational webhook: {}", e);
+        return Err((StatusCode::UNAUTHORIZED, e));
+    }
+
+    let webhook: OperationalWebhook = serde_json::from_slice(&body).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid operational webhook: {}", e),
+        )
+    })?;
+    state
+        .metrics
+        .events
+        .with_label_values(&[&webhook.event_type])
+        .inc();
+
+    let status = match webhook.event_type.as_str() {
+        "message.attempt.failing" => "failing",
+        "message.attempt.exhausted" => "exhausted",
+        "message.attempt.recovered" => "succeeded",
+        "endpoint.disabled" => {
+            if let Ok(data) = serde_json::from_value::<EndpointDisabled>(webhook.data) {
+                tracing::warn!(
+                    "Svix disabled endpoint {} of application {}",
+                    data.endpoint_id,
+                    data.app_uid.as_deref().unwrap_or("?")
+                );
+            }
+            return Ok(StatusCode::NO_CONTENT);
+        }
+        other => {
+            tracing::debug!("Ignoring operational webhook {}", other);
+            return Ok(StatusCode::NO_CONTENT);
+        }
+    };
+
+    let attempt: MessageAttempt = serde_json::from_value(webhook.data).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid {} payload: {}", webhook.event_type, e),
+        )
+    })?;
+    tracing::info!(
+        "Svix reports {} for {} on endpoint {} of application {}",
+        webhook.event_type,
+        attempt.msg_event_id.as_deref().unwrap_or("?"),
+        attempt.endpoint_id,
+        attempt.app_uid.as_deref().unwrap_or("?")
+    );
+
+    if let (Some(db), Some(msg_event_id)) = (&state.db, &attempt.msg_event_id) {
+        record_outcome(db, msg_event_id, status, &attempt).await.map_err(|e| {
+            tracing::error!("Failed to record operational webhook: {}", e);
+            // 5xx makes Svix redeliver it later
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to record outcome: {}", e),
+            )
+        })?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Insert into delivery_attempts for the domain event behind a Svix event id.
+/// Unknown ids (e.g. messages sent by something else) are skipped.
+async fn record_outcome(
+    db: &PgPool,
+    msg_event_id: &str,
+    status: &str,
+    attempt: &MessageAttempt,
+) -> Result<(), sqlx::Error> {
+    let response_status = attempt
+        .last_attempt
+        .as_ref()
+        .and_then(|a| a.response_status_code);
+    let error = match response_status {
+        Some(code) => format!("endpoint {} responded {}", attempt.endpoint_id, code),
+        None => format!("endpoint {}", attempt.endpoint_id),
+    };
+
+    let event_row_id = msg_event_id
+        .strip_prefix("evt_")
+        .or_else(|| msg_event_id.split_once("_replay_").map(|(_, id)| id))
+        .and_then(|id| id.parse::<i64>().ok());
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO delivery_attempts (event_id, merchant_id, delivery_path, status, error, response_status)
+        SELECT id, merchant_id, 'svix_endpoint', $3, $4, $5
+        FROM domain_events
+        WHERE ($1::BIGINT IS NOT NULL AND id = $1)
+           OR ($1::BIGINT IS NULL AND object_id::TEXT = $2)
+        ORDER BY id DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(event_row_id)
+    .bind(msg_event_id)
+    .bind(status)
+    .bind(error)
+    .bind(response_status)
+    .execute(db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        tracing::warn!("No domain event for Svix event id {}", msg_event_id);
+    }
+    Ok(())
+}
+
+async fn metrics_handler(State(state): State<OperationalState>) -> impl IntoResponse {
+    state.metrics.all.render()
+}
+
+/// Serve until the process exits; a bind failure only disables this endpoint
+pub async fn serve(port: u16, state: OperationalState) {
+    let listener = match tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!("Operational webhook endpoint disabled, bind failed: {}", e);
+            return;
+        }
+    };
+
+    tracing::info!("Svix operational webhooks on port {}", port);
+    if let Err(e) = axum::serve(listener, router(state)).await {
+        tracing::error!("Operational webhook server stopped: {}", e);
+    }
+}
@@ -0,0 +1,309 @@
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tonic::codec::CompressionEncoding;
+use tonic::transport::{Channel, Endpoint};
+
+use http_client::{HttpClient, HttpClientConfig, HttpError};
+
+use crate::errors::CallError;
+use crate::metrics::Metrics;
+
+// ==============================================================================
+// PAYLOAD TRANSPORT: Fetch enriched payloads over HTTP or gRPC
+// ==============================================================================
+//
+// data-service serves the same payloads on both. HTTP stays the default;
+// PAYLOAD_TRANSPORT=grpc switches to one long-lived HTTP/2 channel, which
+// saves connection setup and JSON handling on every delivery. Over HTTP the
+// payload arrives already rendered in the merchant's version; over gRPC it is
+// rendered here from the structured message.
+//
+// Over HTTP, PAYLOAD_ENCODING=msgpack asks for MessagePack instead of JSON;
+// data-service encodes it so that it decodes to the same JSON value.
+//
+// DATA_SERVICE_TOKEN is presented as a bearer token on both transports; it must
+// match this service's entry in data-service's SERVICE_AUTH_TOKENS.
+//
+// At most PAYLOAD_MAX_CONCURRENT_FETCHES (default 32) fetches run at once per
+// process, across all invocations; the rest wait for a slot. HTTP fetches go
+// through the http-client crate: a couple of quick retries before the step
+// fails back to Restate, and a circuit breaker that stops hammering a
+// data-service that keeps failing.
+
+pub mod proto {
+    tonic::include_proto!("payload.v1");
+}
+
+use proto::payload_service_client::PayloadServiceClient;
+use proto::GetPayloadRequest;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+const MSGPACK: &str = "application/msgpack";
+
+#[derive(Clone)]
+enum Transport {
+    Http {
+        client: HttpClient,
+        base_url: String,
+        msgpack: bool,
+    },
+    Grpc(PayloadServiceClient<Channel>),
+}
+
+#[derive(Clone)]
+pub struct PayloadClient {
+    transport: Transport,
+    token: Option<String>,
+    permits: Arc<Semaphore>,
+    metrics: Metrics,
+}
+
+impl PayloadClient {
+    pub fn from_env(metrics: Metrics) -> Result<Self, String> {
+        let token = std::env::var("DATA_SERVICE_TOKEN").ok();
+        if token.is_none() {
+            tracing::warn!("DATA_SERVICE_TOKEN not set - payload requests are unauthenticated");
+        }
+
+        let transport = match std::env::var("PAYLOAD_TRANSPORT").as_deref() {
+            Ok("grpc") => {
+                let url = std::env::var("DATA_SERVICE_GRPC_URL")
+                    .unwrap_or_else(|_| "http://data-service:50051".to_string());
+                // Connects on first use and reconnects on its own
+                let channel = Endpoint::from_shared(url.clone())
+                    .map_err(|e| format!("Invalid DATA_SERVICE_GRPC_URL {}: {}", url, e))?
+                    .timeout(FETCH_TIMEOUT)
+                    .connect_lazy();
+                tracing::info!("Fetching payloads over gRPC from {}", url);
+                Transport::Grpc(
+                    PayloadServiceClient::new(channel).accept_compressed(CompressionEncoding::Gzip),
+                )
+            }
+            Ok("http") | Err(_) => {
+                let base_url = std::env::var("DATA_SERVICE_URL")
+                    .unwrap_or_else(|_| "http://data-service:3002".to_string());
+                let msgpack = match std::env::var("PAYLOAD_ENCODING").as_deref() {
+                    Ok("msgpack") => true,
+                    Ok("json") | Err(_) => false,
+                    Ok(other) => {
+                        return Err(format!(
+                            "Unknown PAYLOAD_ENCODING '{}', expected 'json' or 'msgpack'",
+                            other
+                        ))
+                    }
+                };
+                tracing::info!(
+                    "Fetching payloads over HTTP from {} ({})",
+                    base_url,
+                    if msgpack { "msgpack" } else { "json" }
+                );
+                // Advertises gzip/br and transparently decompresses responses
+                let client = HttpClient::with_builder(
+                    HttpClientConfig {
+                        timeout: FETCH_TIMEOUT,
+                        ..HttpClientConfig::new("payload_fetch")
+                    },
+                    reqwest::Client::builder().gzip(true).brotli(true),
+                    metrics.http(),
+                )?;
+                Transport::Http {
+                    client,
+                    base_url,
+                    msgpack,
+                }
+            }
+            Ok(other) => {
+                return Err(format!(
+                    "Unknown PAYLOAD_TRANSPORT '{}', expected 'http' or 'grpc'",
+                    other
+                ))
+            }
+        };
+
+        let max_concurrent = std::env::var("PAYLOAD_MAX_CONCURRENT_FETCHES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(32usize)
+            .max(1);
+
+        Ok(PayloadClient {
+            transport,
+            token,
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+            metrics,
+        })
+    }
+
+    /// Payload for a payment as delivered for `event_id`; data-service uses
+    /// the event id to serve snapshots to merchants that opted into them, and
+    /// the event type to pick the merchant/customer data to include.
+    pub async fn fetch(
+        &self,
+        payment_id: &str,
+        event_id: u64,
+        event_type: &str,
+    ) -> Result<Value, CallError> {
+        // Never closed, so acquiring only waits
+        let _permit = self.permits.acquire().await.expect("semaphore closed");
+
+        // Timed once a slot is free, so the histogram shows data-service's
+        // latency rather than the queue in front of it
+        let started = Instant::now();
+        let result = self.request(payment_id, event_id, event_type).await;
+        self.metrics.observe_fetch(started, &result);
+        result
+    }
+
+    async fn request(
+        &self,
+        payment_id: &str,
+        event_id: u64,
+        event_type: &str,
+    ) -> Result<Value, CallError> {
+        match &self.transport {
+            Transport::Http {
+                client,
+                base_url,
+                msgpack,
+            } => {
+                let payload_url = format!("{}/payload/{}", base_url, payment_id);
+                tracing::info!("Fetching payload from: {}", payload_url);
+
+                let mut request = client.get(&payload_url).query(&[
+                    ("event_id", event_id.to_string().as_str()),
+                    ("event_type", event_type),
+                ]);
+                if let Some(token) = &self.token {
+                    request = request.bearer_auth(token);
+                }
+                if *msgpack {
+                    request = request.header(reqwest::header::ACCEPT, MSGPACK);
+                }
+
+                let fetch_error = |e: reqwest::Error| {
+                    CallError::from_status(
+                        e.status().map(|status| status.as_u16()),
+                        format!("Failed to fetch payload: {}", e),
+                    )
+                };
+                let response = match client.send(request).await {
+                    Ok(response) => response.error_for_status().map_err(fetch_error)?,
+                    Err(HttpError::Request(e)) => return Err(fetch_error(e)),
+                    Err(e) => {
+                        return Err(CallError::Retryable(format!(
+                            "Failed to fetch payload: {}",
+                            e
+                        )))
+                    }
+                };
+
+                // A body that doesn't decode is a data-service bug; retrying
+                // lets a fixed deployment pick the event up again
+                if *msgpack {
+                    let body = response.bytes().await.map_err(fetch_error)?;
+                    rmp_serde::from_slice::<Value>(&body).map_err(|e| {
+                        CallError::Retryable(format!("Failed to parse payload: {}", e))
+                    })
+                } else {
+                    response.json::<Value>().await.map_err(|e| {
+                        CallError::Retryable(format!("Failed to parse payload: {}", e))
+                    })
+                }
+            }
+            Transport::Grpc(client) => {
+                tracing::info!("Fetching payload over gRPC: {}", payment_id);
+
+                let mut request = tonic::Request::new(GetPayloadRequest {
+                    payment_id: payment_id.to_string(),
+                    event_id: Some(event_id as i64),
+                    // The merchant's configured version
+                    version: None,
+                    event_type: Some(event_type.to_string()),
+                });
+                if let Some(token) = &self.token {
+                    let value = format!("Bearer {}", token).parse().map_err(|_| {
+                        CallError::Retryable("DATA_SERVICE_TOKEN is not valid ASCII".to_string())
+                    })?;
+                    request.metadata_mut().insert("authorization", value);
+                }
+
+                let payload = client
+                    .clone()
+                    .get_payload(request)
+                    .await
+                    .map_err(grpc_error)?
+                    .into_inner();
+
+                Ok(render(payload))
+            }
+        }
+    }
+}
+
+/// The gRPC counterpart of CallError::from_status
+fn grpc_error(status: tonic::Status) -> CallError {
+    let message = format!("Failed to fetch payload: {}", status);
+    match status.code() {
+        // Missing payment, bad request, payload over the size limit
+        tonic::Code::NotFound
+        | tonic::Code::InvalidArgument
+        | tonic::Code::ResourceExhausted
+        | tonic::Code::FailedPrecondition => CallError::Terminal(message),
+        _ => CallError::Retryable(message),
+    }
+}
+
+/// The JSON shapes of data-service's versions.rs, built from the gRPC message.
+/// Fields stripped by the merchant's field policy are left out.
+fn render(payload: proto::Payload) -> Value {
+    let present = |fields: [(&str, Option<Value>); 3]| {
+        fields
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.to_string(), value?)))
+            .collect::<Map<String, Value>>()
+    };
+
+    let mut rendered = json!({
+        "id": payload.id,
+        "status": payload.status,
+        "mode": payload.mode,
+    });
+
+    if let Some(merchant) = payload.merchant {
+        rendered["merchant"] = json!({ "id": merchant.id, "name": merchant.name });
+    }
+    if let Some(customer) = payload.customer {
+        rendered["customer"] = json!({ "id": customer.id });
+        if let Some(external_ref) = customer.external_ref {
+            rendered["customer"]["external_ref"] = json!(external_ref);
+        }
+    }
+
+    if payload.version == "v2" {
+        rendered["object"] = json!("payment");
+        let amount = present([
+            ("value", payload.amount.map(Value::from)),
+            ("currency", payload.currency.map(Value::from)),
+            ("decimal", payload.amount_decimal.map(Value::from)),
+        ]);
+        if !amount.is_empty() {
+            rendered["amount"] = Value::Object(amount);
+        }
+        if let Some(updated_at) = payload.updated_at {
+            rendered["updated_at"] = json!(updated_at);
+        }
+    } else {
+        let fields = present([
+            ("amount", payload.amount.map(Value::from)),
+            ("currency", payload.currency.map(Value::from)),
+            ("amount_decimal", payload.amount_decimal.map(Value::from)),
+        ]);
+        if let Value::Object(rendered) = &mut rendered {
+            rendered.extend(fields);
+        }
+    }
+
+    rendered
+}
@@ -0,0 +1,158 @@
+use kafka_producer::{PartitionKey, Producer, ProducerConfig, ProducerMetrics};
+use sqlx::types::chrono::{DateTime, Utc};
+use webhook_types::{DeliveryEvent, DomainEvent, SCHEMA_VERSION};
+
+use crate::direct::EndpointAttempt;
+use crate::metrics::Metrics;
+use crate::settings::Settings;
+
+// ==============================================================================
+// PUBLISH: Delivery outcomes and dead letters, to Kafka
+// ==============================================================================
+//
+// With KAFKA_BROKERS set, what svix-caller writes to Postgres is also
+// published, through the kafka-producer crate, for consumers that shouldn't
+// poll the database (analytics, alerting, other regions):
+//
+//   KAFKA_DELIVERY_EVENTS_TOPIC  a DeliveryEvent per delivery_attempts row,
+//                                with the time since the event was created;
+//                                'direct_endpoint' ones also carry the
+//                                endpoint and its timing (see direct.rs)
+//   KAFKA_DEAD_LETTER_TOPIC      the DomainEvent of every svix_dead_letters
+//                                entry, with the error in the
+//                                x-dead-letter-error header
+//
+// Both are keyed by merchant_id, so a merchant's records stay in order. Like
+// the Postgres writes, publishing is best-effort: a failure is logged and
+// counted (kafka_messages_produced_total) but never fails the invocation.
+
+pub const DEAD_LETTER_ERROR_HEADER: &str = "x-dead-letter-error";
+
+#[derive(Clone)]
+pub struct Publisher {
+    producer: Producer,
+    delivery_events_topic: Option<String>,
+    dead_letter_topic: Option<String>,
+}
+
+impl Publisher {
+    /// None unless KAFKA_BROKERS and at least one topic are set
+    pub fn from_settings(settings: &Settings, metrics: &Metrics) -> Result<Option<Self>, String> {
+        let Some(brokers) = &settings.kafka_brokers else {
+            return Ok(None);
+        };
+        if settings.kafka_delivery_events_topic.is_none()
+            && settings.kafka_dead_letter_topic.is_none()
+        {
+            tracing::warn!("KAFKA_BROKERS set without a topic to publish to");
+            return Ok(None);
+        }
+
+        let producer = Producer::new(
+            &ProducerConfig::new(brokers, "svix-caller"),
+            &ProducerMetrics::new(metrics.registry()),
+        )?;
+        Ok(Some(Publisher {
+            producer,
+            delivery_events_topic: settings.kafka_delivery_events_topic.clone(),
+            dead_letter_topic: settings.kafka_dead_letter_topic.clone(),
+        }))
+    }
+
+    pub async fn delivery_attempt(
+        &self,
+        event: &DomainEvent,
+        delivery_path: &str,
+        status: &str,
+        error: Option<&str>,
+    ) {
+        self.send_delivery(delivery_event(event, delivery_path, status, error, None))
+            .await;
+    }
+
+    /// One endpoint's part in a direct delivery
+    pub async fn endpoint_attempt(&self, event: &DomainEvent, attempt: &EndpointAttempt) {
+        let status = if attempt.succeeded {
+            "succeeded"
+        } else {
+            "failed"
+        };
+        let mut delivery = delivery_event(
+            event,
+            "direct_endpoint",
+            status,
+            attempt.error.as_deref(),
+            attempt.response_status,
+        );
+        delivery.endpoint_id = Some(attempt.endpoint_id.clone());
+        delivery.response_ms = attempt.response_ms;
+        delivery.timed_out = attempt.timed_out;
+        self.send_delivery(delivery).await;
+    }
+
+    async fn send_delivery(&self, delivery: DeliveryEvent) {
+        let Some(topic) = &self.delivery_events_topic else {
+            return;
+        };
+        // Failures are logged and counted by the producer
+        let _ = self
+            .producer
+            .send_json(
+                topic,
+                PartitionKey::Merchant(&delivery.merchant_id),
+                &delivery,
+                &[],
+            )
+            .await;
+    }
+
+    pub async fn dead_letter(&self, event: &DomainEvent, error: &str) {
+        let Some(topic) = &self.dead_letter_topic else {
+            return;
+        };
+        let _ = self
+            .producer
+            .send_json(
+                topic,
+                PartitionKey::Merchant(&event.merchant_id),
+                event,
+                &[(DEAD_LETTER_ERROR_HEADER, error)],
+            )
+            .await;
+    }
+}
+
+fn delivery_event(
+    event: &DomainEvent,
+    delivery_path: &str,
+    status: &str,
+    error: Option<&str>,
+    response_status: Option<u16>,
+) -> DeliveryEvent {
+    // Replays are measured from the replay, not the original
+    let latency_ms = event
+        .created_at
+        .as_deref()
+        .and_then(|created_at| DateTime::parse_from_rfc3339(created_at).ok())
+        .and_then(|created_at| {
+            (Utc::now() - created_at.with_timezone(&Utc))
+                .num_milliseconds()
+                .try_into()
+                .ok()
+        });
+    DeliveryEvent {
+        schema_version: SCHEMA_VERSION,
+        event_id: event.id,
+        event_type: event.event_type.clone(),
+        object_id: event.object_id.clone(),
+        merchant_id: event.merchant_id.clone(),
+        delivery_path: delivery_path.to_string(),
+        status: status.to_string(),
+        error: error.map(str::to_string),
+        response_status,
+        latency_ms,
+        endpoint_id: None,
+        response_ms: None,
+        timed_out: false,
+    }
+}
@@ -0,0 +1,224 @@
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::{Arc, RwLock};
+use svix::api::{Svix, SvixOptions};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::errors::CallError;
+use crate::secrets::SecretLoader;
+
+// ==============================================================================
+// SVIX ROUTING: Which Svix account a merchant's messages go to
+// ==============================================================================
+//
+// Merchants can live in different Svix regions (EU/US) or environments, each
+// with its own token. SVIX_REGION_TOKENS maps a region to its token:
+//
+//   SVIX_REGION_TOKENS=eu=sk_xxx.eu,us=sk_yyy.us
+//
+// and merchants.svix_region picks one. Merchants without a region, and every
+// call when the database isn't configured, use SVIX_AUTH_TOKEN. The SDK picks
+// the API host from the token's region suffix.
+//
+// SVIX_SERVER_URL points every client at a self-hosted Svix server instead
+// (svix-server in docker-compose), whose tokens carry no region.
+//
+// A merchant whose region has no token is a configuration problem, retried
+// like a revoked token until the token is added.
+//
+// Both token settings are secrets (see secrets.rs) and are reloaded while the
+// service runs; every Svix call builds its client from the current tokens.
+//
+// At most SVIX_MAX_CONCURRENT_CALLS (default 32) clients are out at once per
+// process. A client holds its slot until dropped, so a burst of invocations
+// queues here instead of opening connections and running into Svix's rate
+// limit.
+
+/// Secrets the router reads, in secrets.rs terms
+pub const SECRETS: &[&str] = &["SVIX_AUTH_TOKEN", "SVIX_REGION_TOKENS"];
+
+#[derive(Default, PartialEq)]
+struct Tokens {
+    default_token: Option<String>,
+    region_tokens: HashMap<String, String>,
+}
+
+impl Tokens {
+    fn parse(secrets: &HashMap<String, String>) -> Result<Self, String> {
+        let mut region_tokens = HashMap::new();
+        if let Some(entries) = secrets.get("SVIX_REGION_TOKENS") {
+            for entry in entries.split(',').filter(|entry| !entry.trim().is_empty()) {
+                let (region, token) = entry.trim().split_once('=').ok_or_else(|| {
+                    format!(
+                        "Invalid SVIX_REGION_TOKENS entry '{}', expected region=token",
+                        entry
+                    )
+                })?;
+                region_tokens.insert(region.trim().to_lowercase(), token.trim().to_string());
+            }
+        }
+
+        Ok(Tokens {
+            default_token: secrets.get("SVIX_AUTH_TOKEN").cloned(),
+            region_tokens,
+        })
+    }
+
+    fn log(&self) {
+        if self.default_token.is_none() && self.region_tokens.is_empty() {
+            tracing::warn!("Neither SVIX_AUTH_TOKEN nor SVIX_REGION_TOKENS set");
+        }
+        if !self.region_tokens.is_empty() {
+            let mut regions: Vec<&str> = self.region_tokens.keys().map(String::as_str).collect();
+            regions.sort();
+            tracing::info!("Svix tokens configured for regions: {}", regions.join(", "));
+        }
+    }
+}
+
+/// A Svix client holding one of the concurrent call slots
+pub struct SvixClient {
+    svix: Svix,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Deref for SvixClient {
+    type Target = Svix;
+
+    fn deref(&self) -> &Svix {
+        &self.svix
+    }
+}
+
+#[derive(Clone)]
+pub struct SvixRouter {
+    tokens: Arc<RwLock<Tokens>>,
+    permits: Arc<Semaphore>,
+    /// Self-hosted Svix; None uses Svix Cloud
+    server_url: Option<String>,
+    db: Option<PgPool>,
+}
+
+impl SvixRouter {
+    pub async fn load(db: Option<PgPool>, secrets: &SecretLoader) -> Result<Self, String> {
+        let tokens = Tokens::parse(&secrets.load(SECRETS).await?)?;
+        tokens.log();
+
+        let server_url = std::env::var("SVIX_SERVER_URL")
+            .ok()
+            .filter(|url| !url.is_empty());
+        if let Some(url) = &server_url {
+            tracing::info!("Using self-hosted Svix at {}", url);
+        }
+
+        let max_concurrent = std::env::var("SVIX_MAX_CONCURRENT_CALLS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(32usize)
+            .max(1);
+
+        Ok(SvixRouter {
+            tokens: Arc::new(RwLock::new(tokens)),
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+            server_url,
+            db,
+        })
+    }
+
+    /// Pick up rotated tokens. A failed load keeps the current ones, so a
+    /// Vault hiccup doesn't take Svix delivery down with it.
+    pub async fn refresh(&self, secrets: &SecretLoader) {
+        let tokens = match secrets.load(SECRETS).await.and_then(|s| Tokens::parse(&s)) {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                tracing::warn!("Failed to refresh Svix tokens, keeping current ones: {}", e);
+                return;
+            }
+        };
+
+        let mut current = self.tokens.write().unwrap();
+        if *current != tokens {
+            tokens.log();
+            *current = tokens;
+            tracing::info!("Svix tokens rotated");
+        }
+    }
+
+    fn client(&self, token: &str) -> Svix {
+        let options = self.server_url.as_ref().map(|url| SvixOptions {
+            server_url: Some(url.clone()),
+            ..SvixOptions::default()
+        });
+        Svix::new(token.to_string(), options)
+    }
+
+    /// Waits for a free call slot; held until the permit is dropped
+    pub async fn permit(&self) -> OwnedSemaphorePermit {
+        // Never closed, so acquiring only waits
+        self.permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore closed")
+    }
+
+    /// Client for the account that holds the merchant's applications
+    pub async fn client_for(&self, merchant_id: &str) -> Result<SvixClient, CallError> {
+        let region = self.region(merchant_id).await?;
+        let permit = self.permit().await;
+        let tokens = self.tokens.read().unwrap();
+        let token = match &region {
+            Some(region) => tokens.region_tokens.get(region).ok_or_else(|| {
+                CallError::Retryable(format!(
+                    "No Svix token for region '{}' (merchant {}), add it to SVIX_REGION_TOKENS",
+                    region, merchant_id
+                ))
+            })?,
+            None => tokens
+                .default_token
+                .as_ref()
+                .ok_or_else(|| CallError::Retryable("SVIX_AUTH_TOKEN not set".to_string()))?,
+        };
+        Ok(SvixClient {
+            svix: self.client(token),
+            _permit: permit,
+        })
+    }
+
+    /// One client per configured account, e.g. to register event types in all
+    /// of them. These don't hold call slots; take a permit() around each use.
+    pub fn clients(&self) -> Vec<(String, Svix)> {
+        let tokens = self.tokens.read().unwrap();
+        let mut clients: Vec<(String, Svix)> = tokens
+            .region_tokens
+            .iter()
+            .map(|(region, token)| (region.clone(), self.client(token)))
+            .collect();
+        if let Some(token) = &tokens.default_token {
+            clients.push(("default".to_string(), self.client(token)));
+        }
+        clients
+    }
+
+    async fn region(&self, merchant_id: &str) -> Result<Option<String>, CallError> {
+        let Some(db) = &self.db else {
+            return Ok(None);
+        };
+
+        let region = sqlx::query_scalar::<_, Option<String>>(
+            "SELECT svix_region FROM merchants WHERE id = $1::UUID",
+        )
+        .bind(merchant_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| {
+            CallError::Retryable(format!(
+                "Failed to look up Svix region of merchant {}: {}",
+                merchant_id, e
+            ))
+        })?;
+
+        Ok(region.flatten().map(|region| region.to_lowercase()))
+    }
+}
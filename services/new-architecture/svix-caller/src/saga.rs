@@ -0,0 +1,48 @@
+// ==============================================================================
+// SAGA: process as a sequence of journaled steps
+// ==============================================================================
+//
+// Every event, whether it came through process or process_batch, goes
+// through the same stages. Each one is its own entry in Restate's journal,
+// so a crash resumes after the last stage that finished instead of starting
+// over:
+//
+//   fetch      payload from data-service        fetch_payload (fetch_payloads
+//                                                in a batch)
+//   transform  the merchant's payload template  transform
+//   submit     Svix, or direct delivery         svix_submit, svix_provision_app,
+//                                                svix_submit_after_provision
+//   record     the outcome in delivery_attempts record
+//
+// A stage that fails for good (a retryable error is just retried) runs the
+// compensation step, `compensate`: the failure goes into delivery_attempts
+// with the stage that failed, and the event into svix_dead_letters
+// (dead_letters.rs) so it can be re-driven. Then the invocation fails.
+//
+// Before the fetch, an event for a merchant outside its delivery windows
+// waits for them to open (delivery_window steps and durable sleeps, see
+// windows.rs); waiting isn't a stage, it can't fail.
+//
+// An event the merchant takes in a digest (digest.rs) isn't submitted: after
+// transform the digest step sets it aside, and record notes it 'digested'.
+//
+// Within a batch the step names are prefixed with event_<id>_ to keep each
+// event's stages apart.
+
+/// The stages that can fail; recording is best-effort and never does
+#[derive(Clone, Copy, Debug)]
+pub enum Stage {
+    Fetch,
+    Transform,
+    Submit,
+}
+
+impl Stage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Fetch => "fetch",
+            Stage::Transform => "transform",
+            Stage::Submit => "submit",
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use payload_crypto::{is_sealed, Keyring, LocalMasterKey};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::errors::CallError;
+use crate::secrets::SecretLoader;
+use crate::DomainEvent;
+
+// ==============================================================================
+// SEALING: Payloads encrypted wherever svix-caller keeps them
+// ==============================================================================
+//
+// With PAYLOAD_MASTER_KEYS set (a secret, see secrets.rs), every payload
+// svix-caller writes somewhere that outlives the invocation is sealed with
+// the merchant's data key (payload-crypto crate):
+//
+//   - Restate's journal: the fetched payment (fetch_payload) and the rendered
+//     body (transform) are sealed inside their run steps, so only ciphertext
+//     is journaled, and opened right after
+//   - svix_dead_letters and the dead-letter topic: the event's payload and
+//     the message body
+//   - digest_items and the digest.summary events made of them (digest.rs)
+//
+// Events arriving with a sealed payload (re-driven dead letters) are opened
+// as they are read. A payload that can't be sealed fails its step, which
+// Restate retries: nothing is written in the clear instead. One that can't
+// be opened fails the event for good, like an unreadable payment.
+
+/// Secrets sealing reads, in secrets.rs terms
+pub const SECRETS: &[&str] = &["PAYLOAD_MASTER_KEYS"];
+
+#[derive(Clone)]
+pub struct Sealer {
+    keyring: Arc<Keyring<LocalMasterKey>>,
+}
+
+impl Sealer {
+    /// None when PAYLOAD_MASTER_KEYS isn't set; sealing needs the database
+    /// the data keys live in
+    pub async fn load(db: Option<&PgPool>, secrets: &SecretLoader) -> Result<Option<Self>, String> {
+        let loaded = secrets.load(SECRETS).await?;
+        let Some(entries) = loaded.get("PAYLOAD_MASTER_KEYS") else {
+            return Ok(None);
+        };
+        let db = db.ok_or("PAYLOAD_MASTER_KEYS needs DATABASE_URL for the data keys")?;
+        let master = LocalMasterKey::parse(entries)?;
+        Ok(Some(Sealer {
+            keyring: Arc::new(Keyring::new(db.clone(), master)),
+        }))
+    }
+
+    pub async fn seal(
+        &self,
+        merchant_id: &str,
+        value: &serde_json::Value,
+    ) -> Result<serde_json::Value, CallError> {
+        self.keyring
+            .seal(merchant_id, value)
+            .await
+            .map_err(|e| CallError::Retryable(format!("Failed to seal payload: {}", e)))
+    }
+
+    pub async fn open(
+        &self,
+        merchant_id: &str,
+        value: &serde_json::Value,
+    ) -> Result<serde_json::Value, String> {
+        self.keyring
+            .open(merchant_id, value)
+            .await
+            .map_err(|e| format!("Failed to open payload: {}", e))
+    }
+
+    /// The event with its payload sealed
+    pub async fn seal_event(&self, event: &DomainEvent) -> Result<DomainEvent, CallError> {
+        let mut sealed = event.clone();
+        sealed.payload = self.seal(&event.merchant_id, &event.payload).await?;
+        Ok(sealed)
+    }
+}
+
+/// The event with its payload opened; events that aren't sealed pass as they
+/// are, with or without a sealer
+pub async fn open_event(sealer: Option<&Sealer>, mut event: DomainEvent) -> Result<DomainEvent, String> {
+    if !is_sealed(&event.payload) {
+        return Ok(event);
+    }
+    let sealer = sealer.ok_or_else(|| {
+        format!(
+            "Event {} has a sealed payload and PAYLOAD_MASTER_KEYS isn't set",
+            event.id
+        )
+    })?;
+    event.payload = sealer.open(&event.merchant_id, &event.payload).await?;
+    Ok(event)
+}
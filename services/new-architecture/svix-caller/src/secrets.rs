@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+// ==============================================================================
+// SECRETS: Where svix-caller reads its credentials from
+// ==============================================================================
+//
+// A secret such as SVIX_AUTH_TOKEN is looked up, first match wins, in:
+//
+//   1. Vault, when VAULT_ADDR and VAULT_SECRET_PATH are set: the key of the
+//      same name in that KV secret (v2 paths like secret/data/svix-caller, or
+//      v1). The Vault token comes from VAULT_TOKEN or VAULT_TOKEN_FILE.
+//   2. The file named by <NAME>_FILE, e.g. SVIX_AUTH_TOKEN_FILE pointing at a
+//      mounted Kubernetes or Docker secret.
+//   3. The <NAME> environment variable itself.
+//
+// Files and Vault are re-read every SECRETS_REFRESH_SECS (default 60, 0 turns
+// it off), so rotating a token means updating the secret, not restarting the
+// service. Plain env vars can't change under a running process and aren't
+// refreshed.
+
+const VAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Vault {
+    client: reqwest::Client,
+    /// {VAULT_ADDR}/v1/{VAULT_SECRET_PATH}
+    url: String,
+    token: String,
+}
+
+pub struct SecretLoader {
+    vault: Option<Vault>,
+    refresh_interval: Duration,
+}
+
+impl SecretLoader {
+    pub fn from_env() -> Result<Self, String> {
+        let vault = match (
+            std::env::var("VAULT_ADDR").ok(),
+            std::env::var("VAULT_SECRET_PATH").ok(),
+        ) {
+            (Some(addr), Some(path)) => {
+                let token = match std::env::var("VAULT_TOKEN_FILE") {
+                    Ok(file) => read_file(&file)?,
+                    Err(_) => std::env::var("VAULT_TOKEN")
+                        .map_err(|_| "VAULT_TOKEN or VAULT_TOKEN_FILE must be set".to_string())?,
+                };
+                let client = reqwest::Client::builder()
+                    .timeout(VAULT_TIMEOUT)
+                    .build()
+                    .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+                let url = format!(
+                    "{}/v1/{}",
+                    addr.trim_end_matches('/'),
+                    path.trim_start_matches('/')
+                );
+                tracing::info!("Reading secrets from Vault at {}", url);
+                Some(Vault { client, url, token })
+            }
+            (None, None) => None,
+            _ => return Err("VAULT_ADDR and VAULT_SECRET_PATH must be set together".to_string()),
+        };
+
+        let refresh_interval = Duration::from_secs(
+            std::env::var("SECRETS_REFRESH_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+        );
+
+        Ok(SecretLoader {
+            vault,
+            refresh_interval,
+        })
+    }
+
+    /// None when nothing could change between loads: no Vault, no secret
+    /// files, or refreshing turned off
+    pub fn refresh_interval(&self, names: &[&str]) -> Option<Duration> {
+        let dynamic = self.vault.is_some()
+            || names
+                .iter()
+                .any(|name| std::env::var(format!("{}_FILE", name)).is_ok());
+        (dynamic && !self.refresh_interval.is_zero()).then_some(self.refresh_interval)
+    }
+
+    /// Current value of each secret that is set anywhere; missing ones are
+    /// left out
+    pub async fn load(&self, names: &[&str]) -> Result<HashMap<String, String>, String> {
+        let vault = match &self.vault {
+            Some(vault) => vault.read().await?,
+            None => serde_json::Map::new(),
+        };
+
+        let mut secrets = HashMap::new();
+        for name in names {
+            let value = match vault.get(*name) {
+                Some(value) => Some(
+                    value
+                        .as_str()
+                        .ok_or_else(|| format!("Vault key {} is not a string", name))?
+                        .to_string(),
+                ),
+                None => match std::env::var(format!("{}_FILE", name)) {
+                    Ok(file) => Some(read_file(&file)?),
+                    Err(_) => std::env::var(name).ok(),
+                },
+            };
+            if let Some(value) = value.filter(|value| !value.is_empty()) {
+                secrets.insert(name.to_string(), value);
+            }
+        }
+        Ok(secrets)
+    }
+}
+
+impl Vault {
+    async fn read(&self) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+        let response = self
+            .client
+            .get(&self.url)
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await
+            .map_err(|e| format!("Vault request failed: {}", e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("Vault returned {} for {}", status, self.url));
+        }
+
+        let mut body: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid Vault response: {}", e))?;
+
+        // KV v2 nests the values one level deeper than v1
+        let data = match body["data"]["data"].take() {
+            serde_json::Value::Null => body["data"].take(),
+            data => data,
+        };
+        match data {
+            serde_json::Value::Object(values) => Ok(values),
+            _ => Err(format!("Vault secret {} has no data", self.url)),
+        }
+    }
+}
+
+fn read_file(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path)
+        .map(|contents| contents.trim().to_string())
+        .map_err(|e| format!("Failed to read secret file {}: {}", path, e))
+}
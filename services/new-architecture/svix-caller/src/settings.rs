@@ -0,0 +1,126 @@
+use http_client::{EgressPolicy, ALLOWED_PORTS};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// ==============================================================================
+// SETTINGS: Everything main.rs reads at startup
+// ==============================================================================
+//
+// Loaded through the shared config crate: defaults below, then
+// config/svix-caller.toml (or CONFIG_FILE), then the environment variable of
+// the same name in upper case. Svix routing, secrets, payload transport and
+// the rest keep their own from_env.
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// The Restate endpoint
+    pub port: u16,
+    /// Unset: delivery attempts aren't recorded, nothing is dead-lettered
+    pub database_url: Option<String>,
+    pub operational_port: u16,
+    pub health_port: u16,
+    /// Without it events for merchants that have no Svix application yet are
+    /// skipped
+    pub svix_auto_provision_apps: bool,
+    pub svix_rate_limit_backoff_ms: u64,
+    /// Log messages instead of sending them to Svix
+    pub dry_run: bool,
+    pub svix_sync_event_types: bool,
+    pub svix_batch_fetch_concurrency: usize,
+    /// Unset: nothing is published to Kafka (publish.rs)
+    pub kafka_brokers: Option<String>,
+    pub kafka_delivery_events_topic: Option<String>,
+    pub kafka_dead_letter_topic: Option<String>,
+    /// TOML rules file; payloads are redacted before they're journaled or
+    /// dead-lettered (redaction crate). Unset: kept as fetched
+    pub redaction_rules: Option<PathBuf>,
+    /// Deliver straight to merchant endpoints while Svix is down (direct.rs)
+    pub svix_direct_fallback: bool,
+    /// Svix failures within the window that count as down
+    pub svix_fallback_threshold: usize,
+    pub svix_fallback_window_secs: u64,
+    /// Retry spacing for a healthy endpoint, doubled as its score drops
+    pub svix_fallback_retry_base_ms: u64,
+    /// HTTP(S) or SOCKS5 proxy direct deliveries leave through, for merchants
+    /// without their own
+    pub egress_proxy_url: Option<String>,
+    /// CIDRs endpoints may resolve to even though they aren't public
+    pub egress_allowed_networks: String,
+    /// Ports and ranges endpoint URLs may use
+    pub egress_allowed_ports: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            port: 9080,
+            database_url: None,
+            operational_port: 9081,
+            health_port: 9082,
+            svix_auto_provision_apps: true,
+            svix_rate_limit_backoff_ms: 1000,
+            dry_run: false,
+            svix_sync_event_types: true,
+            svix_batch_fetch_concurrency: 8,
+            kafka_brokers: None,
+            kafka_delivery_events_topic: None,
+            kafka_dead_letter_topic: None,
+            redaction_rules: None,
+            svix_direct_fallback: false,
+            svix_fallback_threshold: 5,
+            svix_fallback_window_secs: 60,
+            svix_fallback_retry_base_ms: 1000,
+            egress_proxy_url: None,
+            egress_allowed_networks: String::new(),
+            egress_allowed_ports: ALLOWED_PORTS.to_string(),
+        }
+    }
+}
+
+impl config::Validate for Settings {
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.svix_batch_fetch_concurrency == 0 {
+            problems.push("SVIX_BATCH_FETCH_CONCURRENCY: must be at least 1".to_string());
+        }
+        let ports = [
+            ("PORT", self.port),
+            ("OPERATIONAL_PORT", self.operational_port),
+            ("HEALTH_PORT", self.health_port),
+        ];
+        for (i, (name, port)) in ports.iter().enumerate() {
+            if let Some((other, _)) = ports[..i].iter().find(|(_, p)| p == port) {
+                problems.push(format!("{}: {} is already used by {}", name, port, other));
+            }
+        }
+        if let Some(path) = &self.redaction_rules {
+            if let Err(e) = redaction::Redactor::load(path) {
+                problems.push(format!("REDACTION_RULES: {}", e));
+            }
+        }
+        if self.svix_fallback_threshold == 0 {
+            problems.push("SVIX_FALLBACK_THRESHOLD: must be at least 1".to_string());
+        }
+        if self.svix_fallback_window_secs == 0 {
+            problems.push("SVIX_FALLBACK_WINDOW_SECS: must be at least 1".to_string());
+        }
+        if let Some(url) = &self.egress_proxy_url {
+            match reqwest::Url::parse(url) {
+                Ok(url) if matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") => {}
+                Ok(url) => problems.push(format!(
+                    "EGRESS_PROXY_URL: unsupported scheme {}, expected http, https, socks5 or socks5h",
+                    url.scheme()
+                )),
+                Err(e) => problems.push(format!("EGRESS_PROXY_URL: {}", e)),
+            }
+        }
+        if let Err(e) = EgressPolicy::parse(&self.egress_allowed_networks, ALLOWED_PORTS) {
+            problems.push(format!("EGRESS_ALLOWED_NETWORKS: {}", e));
+        }
+        if let Err(e) = EgressPolicy::parse("", &self.egress_allowed_ports) {
+            problems.push(format!("EGRESS_ALLOWED_PORTS: {}", e));
+        }
+        problems
+    }
+}
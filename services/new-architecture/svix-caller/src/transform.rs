@@ -0,0 +1,96 @@
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+
+use crate::errors::CallError;
+
+// ==============================================================================
+// PAYLOAD TEMPLATES: The merchant's own shape for the message body
+// ==============================================================================
+//
+// merchants.payload_template (set through api-service) is a JSON document
+// rendered against the message body svix-caller built:
+//
+//   {"id": "{{payment.id}}", "amount": "{{payment.amount}}",
+//    "summary": "{{event_type}} for {{payment.id}}"}
+//
+// A string that is exactly one {{path}} takes the value at that path with its
+// JSON type; placeholders inside longer strings are filled in as text. Paths
+// are dot-separated keys, with numbers indexing arrays. A missing path renders
+// as null (or nothing, inside text). Everything else in the template is copied
+// as is.
+//
+// The template is applied before the body is split between Svix and direct
+// delivery (direct.rs), so both paths deliver the same payload.
+
+/// The merchant's template; None when it has none, or there is no database
+pub async fn load(db: Option<&PgPool>, merchant_id: &str) -> Result<Option<Value>, CallError> {
+    let Some(db) = db else {
+        return Ok(None);
+    };
+
+    let template = sqlx::query_scalar::<_, Option<Value>>(
+        "SELECT payload_template FROM merchants WHERE id = $1::UUID",
+    )
+    .bind(merchant_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| {
+        CallError::Retryable(format!(
+            "Failed to load payload template of merchant {}: {}",
+            merchant_id, e
+        ))
+    })?;
+
+    Ok(template.flatten())
+}
+
+pub fn apply(template: &Value, body: &Value) -> Value {
+    match template {
+        Value::String(text) => render_string(text, body),
+        Value::Array(items) => Value::Array(items.iter().map(|item| apply(item, body)).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), apply(value, body)))
+                .collect::<Map<String, Value>>(),
+        ),
+        literal => literal.clone(),
+    }
+}
+
+fn render_string(text: &str, body: &Value) -> Value {
+    // Whole-string placeholder: keep the value's type
+    if let Some(path) = text
+        .strip_prefix("{{")
+        .and_then(|rest| rest.strip_suffix("}}"))
+        .filter(|path| !path.contains("{{") && !path.contains("}}"))
+    {
+        return lookup(body, path.trim()).cloned().unwrap_or(Value::Null);
+    }
+
+    let mut rendered = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        match lookup(body, rest[start + 2..start + 2 + len].trim()) {
+            Some(Value::String(value)) => rendered.push_str(value),
+            Some(Value::Null) | None => {}
+            Some(value) => rendered.push_str(&value.to_string()),
+        }
+        rest = &rest[start + 2 + len + 2..];
+    }
+    rendered.push_str(rest);
+    Value::String(rendered)
+}
+
+fn lookup<'a>(body: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(body, |value, segment| match value {
+            Value::Object(fields) => fields.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+}
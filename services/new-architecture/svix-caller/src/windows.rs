@@ -0,0 +1,116 @@
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::time::Duration;
+
+use crate::errors::CallError;
+
+// ==============================================================================
+// DELIVERY WINDOWS: When a merchant takes its events
+// ==============================================================================
+//
+// Merchants can narrow down when their events are handed over (to Svix, or
+// directly to their endpoints), set through api-service:
+//
+//   daily window   merchants.delivery_window_start/_end, local time in
+//                  delivery_window_timezone; e.g. 02:00-04:00 Europe/Berlin,
+//                  or 22:00-02:00 across midnight. Postgres does the time zone
+//                  arithmetic, daylight saving time included.
+//   maintenance    merchant_maintenance_windows: nothing goes out between
+//                  starts_at and ends_at
+//   pause          merchants.delivery_paused_at: nothing goes out until the
+//                  merchant is resumed, e.g. while it migrates its receiver
+//
+// Outside them, svix-caller parks the event: the merchant's exclusive
+// invocation sleeps durably (a Restate timer) until the window opens, so the
+// merchant's later events queue up behind it and go out in order once it
+// does. It looks again at least every WINDOW_RECHECK, so a window that's
+// changed or removed meanwhile takes effect without waiting out the old one;
+// a paused merchant is looked at every PAUSE_RECHECK, so resuming drains the
+// backlog shortly after.
+// Svix's own retries of a message it already accepted aren't held back.
+
+/// Longest a parked event sleeps before checking the windows again
+pub const WINDOW_RECHECK: Duration = Duration::from_secs(900);
+
+/// How often a paused merchant is checked for being resumed
+pub const PAUSE_RECHECK: Duration = Duration::from_secs(30);
+
+/// Whether the merchant is paused, when the maintenance window it's in ends,
+/// and when its daily window next opens if it's closed now
+const CLOSED_SQL: &str = r#"
+    WITH m AS (
+        SELECT delivery_window_start AS starts, delivery_window_end AS ends,
+               delivery_window_timezone AS tz,
+               NOW() AT TIME ZONE delivery_window_timezone AS local
+        FROM merchants
+        WHERE id = $1::UUID AND delivery_window_timezone IS NOT NULL
+    )
+    SELECT
+        EXISTS (SELECT 1 FROM merchants
+                WHERE id = $1::UUID AND delivery_paused_at IS NOT NULL),
+        (SELECT MAX(ends_at) FROM merchant_maintenance_windows
+         WHERE merchant_id = $1::UUID AND starts_at <= NOW() AND ends_at > NOW()),
+        (SELECT CASE
+             WHEN starts < ends AND local::TIME >= starts AND local::TIME < ends THEN NULL
+             WHEN starts > ends AND (local::TIME >= starts OR local::TIME < ends) THEN NULL
+             WHEN local::TIME < starts THEN (local::DATE + starts) AT TIME ZONE tz
+             ELSE (local::DATE + 1 + starts) AT TIME ZONE tz
+         END
+         FROM m)
+"#;
+
+/// The merchant's windows are closed; journaled by the handler
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Closed {
+    /// "paused", "maintenance" or "delivery_window"
+    pub reason: String,
+    /// Until the window opens, from when it was checked
+    pub opens_in_ms: u64,
+}
+
+impl Closed {
+    pub fn wait(&self) -> Duration {
+        Duration::from_millis(self.opens_in_ms).min(WINDOW_RECHECK)
+    }
+}
+
+/// None while the merchant takes events (or there's no database). A pause
+/// comes before maintenance, and during maintenance that ends outside the
+/// daily window only the maintenance is reported; the rest is checked again
+/// after it.
+pub async fn closed(db: Option<&PgPool>, merchant_id: &str) -> Result<Option<Closed>, CallError> {
+    let Some(db) = db else {
+        return Ok(None);
+    };
+
+    let (paused, maintenance_ends_at, window_opens_at): (
+        bool,
+        Option<DateTime<Utc>>,
+        Option<DateTime<Utc>>,
+    ) = sqlx::query_as(CLOSED_SQL)
+        .bind(merchant_id)
+        .fetch_one(db)
+        .await
+        .map_err(|e| {
+            CallError::Retryable(format!(
+                "Failed to load delivery windows of merchant {}: {}",
+                merchant_id, e
+            ))
+        })?;
+
+    if paused {
+        return Ok(Some(Closed {
+            reason: "paused".to_string(),
+            opens_in_ms: PAUSE_RECHECK.as_millis() as u64,
+        }));
+    }
+    let (reason, opens_at) = match (maintenance_ends_at, window_opens_at) {
+        (Some(ends_at), _) => ("maintenance", ends_at),
+        (None, Some(opens_at)) => ("delivery_window", opens_at),
+        (None, None) => return Ok(None),
+    };
+    Ok(Some(Closed {
+        reason: reason.to_string(),
+        opens_in_ms: (opens_at - Utc::now()).num_milliseconds().max(0) as u64,
+    }))
+}
@@ -0,0 +1,289 @@
+use event_transport::amqp::{self, AmqpSubscriber};
+use event_transport::aws::AwsClient;
+use event_transport::nats::{self, JetStreamSubscriber};
+use event_transport::redis_streams::{self, RedisSubscriber};
+use event_transport::sqs::{self, SqsSubscriber};
+use event_transport::{EventSubscriber, ReceivedMessage};
+use http_client::HttpClient;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{StatusCode, Url};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinSet;
+
+use crate::metrics::Metrics;
+use crate::scheduler::Scheduler;
+
+// ==============================================================================
+// CONSUMER: JetStream, SQS, RabbitMQ or Redis Streams -> Restate ingress
+// ==============================================================================
+//
+// For each message, in its merchant's turn (scheduler.rs; in stream order
+// with the default MAX_ACK_PENDING=1):
+//
+//   POST {RESTATE_INGRESS_URL}/SvixCaller/{merchant_id}/process/send
+//
+// with the DomainEvent as the body and its message id (evt_<id>) as Restate's
+// idempotency-key, so a redelivered message doesn't start a second
+// invocation. /send returns once Restate has durably accepted the call; from
+// there on, retries are Restate's, as with the Kafka subscription.
+//
+//   2xx        ack
+//   4xx        reject: no redelivery will fix it (a bad key or body). 429
+//              and 408 are the exceptions and count as failures
+//   otherwise  retry, redelivered after a backoff doubling from 100ms per
+//              delivery, up to MAX_BACKOFF_MS. The consumer also waits that
+//              long, since every message after it would fail the same way
+//
+// JetStream rejects with a term and retries with a nak; with
+// MAX_ACK_PENDING=1 (the default) nothing else is delivered while a message
+// waits, so a merchant's events reach SvixCaller in the order they were
+// published. SQS retries by setting the message's visibility timeout to the
+// backoff, and rejects by moving it to the dead-letter queue; its redrive
+// policy does the same after MAX_RECEIVE_COUNT receives. A FIFO queue holds
+// back the rest of a merchant's messages meanwhile. RabbitMQ retries by
+// holding the message for the backoff and requeueing it at the head of the
+// quorum queue, and rejects it to the dead-letter exchange, as the queue
+// does after MAX_DELIVER deliveries. Redis Streams leaves a failing entry
+// pending and claims it again once the backoff has passed (before reading
+// anything new), and rejects it to <topic>:dead.
+//
+// With MAX_ACK_PENDING above 1, up to that many messages are read ahead and
+// queued per merchant, and HANDOFF_CONCURRENCY handoffs run at once, taking
+// turns between merchants. A merchant's own handoffs still run one at a
+// time. A message for a merchant that already has its share waiting is held
+// and reading pauses until the merchant has room, while the merchants
+// already queued keep taking turns; it keeps its place in the merchant's
+// order, which handing it back to the broker wouldn't.
+
+/// Where messages come from (SOURCE)
+pub enum Source {
+    Nats(nats::SubscriberConfig),
+    Sqs(AwsClient, sqs::SubscriberConfig),
+    Amqp(amqp::SubscriberConfig),
+    Redis(redis_streams::SubscriberConfig),
+}
+
+impl Source {
+    async fn connect(&self) -> Result<Box<dyn EventSubscriber>, String> {
+        Ok(match self {
+            Source::Nats(config) => Box::new(JetStreamSubscriber::connect(config).await?),
+            Source::Sqs(client, config) => {
+                Box::new(SqsSubscriber::connect(client.clone(), config).await?)
+            }
+            Source::Amqp(config) => Box::new(AmqpSubscriber::connect(config).await?),
+            Source::Redis(config) => Box::new(RedisSubscriber::connect(config).await?),
+        })
+    }
+}
+
+pub struct Consumer {
+    pub source: Source,
+    pub client: HttpClient,
+    pub ingress_url: Url,
+    pub object: String,
+    pub handler: String,
+    pub max_backoff: Duration,
+    pub metrics: Metrics,
+    /// MAX_ACK_PENDING: messages read but not yet settled
+    pub read_ahead: usize,
+    pub handoff_concurrency: usize,
+    pub tenant_weights: HashMap<String, u32>,
+    pub tenant_max_buffered: usize,
+}
+
+/// A message read ahead, holding its place in the read-ahead until settled
+type Pending = (Box<dyn ReceivedMessage>, OwnedSemaphorePermit);
+
+impl Consumer {
+    pub async fn run(self) {
+        let consumer = Arc::new(self);
+        let mut failures = 0u32;
+        loop {
+            let subscriber = match consumer.source.connect().await {
+                Ok(subscriber) => subscriber,
+                Err(e) => {
+                    failures += 1;
+                    let delay = consumer.backoff(failures);
+                    tracing::warn!("{}, retrying in {:?}", e, delay);
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+            };
+            failures = 0;
+
+            consumer.clone().consume(subscriber).await;
+            tracing::warn!("Subscription ended, reconnecting");
+        }
+    }
+
+    /// Read ahead into the merchants' queues and hand off in turns, until
+    /// the subscription ends and everything read is settled
+    async fn consume(self: Arc<Self>, mut subscriber: Box<dyn EventSubscriber>) {
+        let permits = Arc::new(Semaphore::new(self.read_ahead));
+        let (read, mut received) = mpsc::channel::<Pending>(1);
+        let reader = tokio::spawn(async move {
+            loop {
+                let Ok(permit) = permits.clone().acquire_owned().await else {
+                    return;
+                };
+                match subscriber.next().await {
+                    Some(Ok(message)) => {
+                        if read.send((message, permit)).await.is_err() {
+                            return;
+                        }
+                    }
+                    Some(Err(e)) => tracing::warn!("Failed to receive: {}", e),
+                    None => return,
+                }
+            }
+        });
+
+        let mut scheduler = Scheduler::new(self.tenant_weights.clone(), self.tenant_max_buffered);
+        let mut handoffs = JoinSet::new();
+        let mut reading = true;
+        // A message whose merchant's queue was full; nothing more is read
+        // until it's queued
+        let mut held: Option<(String, Pending)> = None;
+        loop {
+            if let Some((tenant, pending)) = held.take() {
+                match scheduler.push(&tenant, pending) {
+                    Ok(()) => self.metrics.backlog(&tenant, scheduler.backlog(&tenant)),
+                    Err(pending) => held = Some((tenant, pending)),
+                }
+            }
+
+            while handoffs.len() < self.handoff_concurrency {
+                let Some((tenant, (message, permit))) = scheduler.next() else {
+                    break;
+                };
+                self.metrics.backlog(&tenant, scheduler.backlog(&tenant));
+                let consumer = self.clone();
+                handoffs.spawn(async move {
+                    consumer.handle(message).await;
+                    drop(permit);
+                    tenant
+                });
+            }
+
+            tokio::select! {
+                next = received.recv(), if reading && held.is_none() => match next {
+                    Some((message, permit)) => {
+                        let tenant = message.key().to_string();
+                        match scheduler.push(&tenant, (message, permit)) {
+                            Ok(()) => self.metrics.backlog(&tenant, scheduler.backlog(&tenant)),
+                            Err(pending) => {
+                                tracing::debug!(
+                                    "Merchant {} has a full queue, pausing reads",
+                                    tenant
+                                );
+                                self.metrics.paused(&tenant);
+                                held = Some((tenant, pending));
+                            }
+                        }
+                    }
+                    None => reading = false,
+                },
+                Some(done) = handoffs.join_next() => match done {
+                    Ok(tenant) => scheduler.done(&tenant),
+                    Err(e) => tracing::error!("Handoff task failed: {}", e),
+                },
+                else => break,
+            }
+        }
+        reader.abort();
+    }
+
+    async fn handle(&self, message: Box<dyn ReceivedMessage>) {
+        if message.key().is_empty() {
+            tracing::error!(
+                "Message {} has no merchant key, dropping it",
+                message.reference()
+            );
+            self.settle(message.as_ref(), "terminated").await;
+            return;
+        }
+
+        let mut url = self.ingress_url.clone();
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments
+                .pop_if_empty()
+                .extend([&self.object, message.key(), &self.handler, "send"]);
+        }
+        let mut request = self
+            .client
+            .post(url.as_str())
+            .header(CONTENT_TYPE, "application/json")
+            .body(message.payload().to_vec());
+        if let Some(id) = message.id() {
+            request = request.header("idempotency-key", id);
+        }
+
+        let failure = match self.client.send(request).await {
+            Ok(response) if response.status().is_success() => {
+                self.settle(message.as_ref(), "handed_off").await;
+                return;
+            }
+            Ok(response) if refused(response.status()) => {
+                let status = response.status();
+                let body = response.text().await.unwrap_or_default();
+                tracing::error!(
+                    "Restate refused message {} for {} ({}): {}",
+                    message.reference(),
+                    message.key(),
+                    status,
+                    body
+                );
+                self.settle(message.as_ref(), "terminated").await;
+                return;
+            }
+            Ok(response) => format!("Restate returned {}", response.status()),
+            Err(e) => format!("Restate unreachable: {}", e),
+        };
+
+        let delay = self.backoff(message.deliveries().max(1) as u32);
+        tracing::warn!(
+            "{} for message {} (delivery {}), redelivering in {:?}",
+            failure,
+            message.reference(),
+            message.deliveries(),
+            delay
+        );
+        if let Err(e) = message.retry(delay).await {
+            // Redelivered after ACK_WAIT_SECS instead
+            tracing::warn!("Failed to retry message {}: {}", message.reference(), e);
+        }
+        self.metrics.outcome("retried");
+        tokio::time::sleep(delay).await;
+    }
+
+    async fn settle(&self, message: &dyn ReceivedMessage, outcome: &str) {
+        let result = if outcome == "terminated" {
+            message.reject().await
+        } else {
+            message.ack().await
+        };
+        if let Err(e) = result {
+            // Redelivered after ACK_WAIT_SECS; the idempotency key keeps a
+            // handed-off message from being invoked twice
+            tracing::warn!("Failed to settle message {}: {}", message.reference(), e);
+        }
+        self.metrics.outcome(outcome);
+    }
+
+    /// 100ms doubling per delivery, up to MAX_BACKOFF_MS
+    fn backoff(&self, deliveries: u32) -> Duration {
+        Duration::from_millis(100)
+            .saturating_mul(2u32.saturating_pow(deliveries.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+}
+
+/// A 4xx Restate won't change its mind about
+fn refused(status: StatusCode) -> bool {
+    status.is_client_error()
+        && status != StatusCode::TOO_MANY_REQUESTS
+        && status != StatusCode::REQUEST_TIMEOUT
+}
@@ -0,0 +1,179 @@
+use axum::{middleware, Router};
+use event_transport::aws::AwsClient;
+use event_transport::{amqp, nats, redis_streams, sqs};
+use health_checks::{checks, HealthRegistry};
+use http_client::{HttpClient, HttpClientConfig, HttpMetrics};
+use service_metrics::ServiceMetrics;
+use std::time::Duration;
+use tracing::info;
+
+mod consumer;
+mod metrics;
+mod scheduler;
+mod settings;
+
+use consumer::{Consumer, Source};
+use metrics::Metrics;
+use settings::Settings;
+
+// ==============================================================================
+// WEBHOOK CONSUMER: Brokers other than Kafka -> SvixCaller
+// ==============================================================================
+//
+// With Kafka, Restate's subscription reads webhook-events and invokes
+// SvixCaller/process itself. Restate only subscribes to Kafka, so with
+// outbox-relay on TRANSPORT=nats, sqs, sns, amqp or redis this does that
+// job: a durable JetStream consumer (SOURCE=nats), an SQS queue poller
+// (SOURCE=sqs), a RabbitMQ queue consumer (SOURCE=amqp) or a Redis consumer
+// group member (SOURCE=redis) that hands each event to the same handler through Restate's ingress, acking only
+// once Restate has accepted it (consumer.rs). Merchants take turns at the
+// handoffs, so one with a burst doesn't hold up the rest (scheduler.rs).
+//
+// Health and metrics are on PORT (default 3006).
+
+#[tokio::main]
+async fn main() {
+    logging::init(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    let settings: Settings = config::load_or_exit("webhook-consumer");
+    // Both validated
+    let ingress_url = reqwest::Url::parse(&settings.restate_ingress_url).unwrap();
+    let (object, handler) = settings.restate_target.split_once('/').unwrap();
+
+    let metrics = Metrics::new(ServiceMetrics::new(
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+    ));
+    // Registered once, shared by the ingress and AWS clients
+    let http_metrics = HttpMetrics::new(metrics.service().registry());
+    // POSTs aren't retried by the client; redelivery is the retry
+    let client = HttpClient::new(HttpClientConfig::new("restate_ingress"), &http_metrics)
+        .expect("Failed to build HTTP client");
+
+    let ack_wait = Duration::from_secs(settings.ack_wait_secs);
+    // The broker the readiness check connects to
+    let (source, brokers) = if settings.source == "sqs" {
+        // Validated with the settings
+        let config = settings.aws().expect("Invalid AWS settings");
+        let endpoint = reqwest::Url::parse(&config.endpoint("sqs")).expect("Invalid AWS endpoint");
+        let aws = AwsClient::new(config, &http_metrics).expect("Failed to build HTTP client");
+        let source = Source::Sqs(
+            aws,
+            sqs::SubscriberConfig {
+                queue: sqs::QueueConfig {
+                    topic: settings.topic.clone(),
+                    fifo: settings.sqs_fifo,
+                    visibility_timeout: ack_wait,
+                    max_receive_count: settings.max_receive_count,
+                },
+                sns_topic: settings.sns_subscribe,
+            },
+        );
+        (source, vec![address(&endpoint)])
+    } else if settings.source == "amqp" {
+        let url = reqwest::Url::parse(&settings.amqp_url).expect("Invalid AMQP_URL");
+        let source = Source::Amqp(amqp::SubscriberConfig {
+            url: settings.amqp_url.clone(),
+            topic: settings.topic.clone(),
+            queue: settings.durable_name.clone(),
+            max_deliver: settings.max_deliver,
+            // Validated to fit
+            prefetch: settings.max_ack_pending as u16,
+        });
+        let port = url
+            .port()
+            .unwrap_or(if url.scheme() == "amqps" { 5671 } else { 5672 });
+        let host = url.host_str().unwrap_or_default();
+        (source, vec![format!("{}:{}", host, port)])
+    } else if settings.source == "redis" {
+        let url = reqwest::Url::parse(&settings.redis_url).expect("Invalid REDIS_URL");
+        let source = Source::Redis(redis_streams::SubscriberConfig {
+            url: settings.redis_url.clone(),
+            topic: settings.topic.clone(),
+            group: settings.durable_name.clone(),
+            // The container's hostname: unique per instance, and stable
+            // across restarts, so an instance reclaims its own pending entries
+            consumer: std::env::var("HOSTNAME").unwrap_or_else(|_| "webhook-consumer".to_string()),
+            ack_wait,
+            max_deliver: settings.max_deliver,
+            batch: settings.max_ack_pending as usize,
+        });
+        let port = url.port().unwrap_or(6379);
+        let host = url.host_str().unwrap_or_default();
+        (source, vec![format!("{}:{}", host, port)])
+    } else {
+        let source = Source::Nats(nats::SubscriberConfig {
+            url: settings.nats_url.clone(),
+            topic: settings.topic.clone(),
+            durable_name: settings.durable_name.clone(),
+            ack_wait,
+            max_deliver: settings.max_deliver,
+            max_ack_pending: settings.max_ack_pending,
+        });
+        let servers = settings
+            .nats_url
+            .split(',')
+            .map(|server| {
+                let server = server.trim();
+                server
+                    .split_once("://")
+                    .map_or(server, |(_, address)| address)
+                    .to_string()
+            })
+            .collect();
+        (source, servers)
+    };
+
+    let consumer = Consumer {
+        source,
+        client,
+        ingress_url: ingress_url.clone(),
+        object: object.to_string(),
+        handler: handler.to_string(),
+        max_backoff: Duration::from_millis(settings.max_backoff_ms),
+        metrics: metrics.clone(),
+        read_ahead: settings.max_ack_pending as usize,
+        handoff_concurrency: settings.handoff_concurrency,
+        // Validated
+        tenant_weights: settings.tenant_weights().unwrap(),
+        tenant_max_buffered: settings.tenant_max_buffered,
+    };
+    info!(
+        "Handing {} to {}{}",
+        settings.topic, ingress_url, settings.restate_target
+    );
+    tokio::spawn(consumer.run());
+
+    let restate = vec![address(&ingress_url)];
+    let health = HealthRegistry::new()
+        .readiness(&settings.source, move || checks::tcp(brokers.clone()))
+        .readiness("restate", move || checks::tcp(restate.clone()));
+
+    let app = Router::new()
+        .merge(health_checks::router(health))
+        .merge(service_metrics::router(metrics.service().clone()))
+        .route_layer(middleware::from_fn_with_state(
+            metrics.service().clone(),
+            service_metrics::track_http,
+        ));
+
+    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", settings.port))
+        .await
+        .unwrap();
+
+    info!(
+        "WEBHOOK CONSUMER health and metrics on port {}",
+        settings.port
+    );
+
+    axum::serve(listener, app).await.unwrap();
+}
+
+/// host:port of a URL, the port defaulted from the scheme
+fn address(url: &reqwest::Url) -> String {
+    format!(
+        "{}:{}",
+        url.host_str().unwrap_or_default(),
+        url.port_or_known_default().unwrap_or(80)
+    )
+}
@@ -0,0 +1,95 @@
+use prometheus::{IntCounterVec, IntGaugeVec, Opts};
+use service_metrics::ServiceMetrics;
+
+// ==============================================================================
+// METRICS: What happened to each message
+// ==============================================================================
+//
+// Next to the process and HTTP metrics every service has (service-metrics
+// crate) and the ingress calls' http_client_* families (http-client crate):
+//
+//   webhook_consumer_messages_total{outcome}
+//
+//     handed_off   Restate accepted the invocation; acked
+//     retried      Restate unreachable or failing; delivered again later
+//     terminated   Restate refused it (4xx); JetStream won't deliver it
+//                  again, the others move it to the dead-letter queue
+//
+//   webhook_consumer_tenant_backlog{merchant_id}
+//
+//     Messages read and waiting for their merchant's turn; a merchant drops
+//     out once it has none
+//
+//   webhook_consumer_tenant_paused_total{merchant_id}
+//
+//     Times reading paused because the merchant's queue was full
+//     (scheduler.rs)
+
+#[derive(Clone)]
+pub struct Metrics {
+    service: ServiceMetrics,
+    messages: IntCounterVec,
+    backlog: IntGaugeVec,
+    paused: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new(service: ServiceMetrics) -> Self {
+        let messages = IntCounterVec::new(
+            Opts::new(
+                "webhook_consumer_messages_total",
+                "Messages handled, by outcome",
+            ),
+            &["outcome"],
+        )
+        .unwrap();
+        let backlog = IntGaugeVec::new(
+            Opts::new(
+                "webhook_consumer_tenant_backlog",
+                "Messages waiting for their merchant's turn",
+            ),
+            &["merchant_id"],
+        )
+        .unwrap();
+        let paused = IntCounterVec::new(
+            Opts::new(
+                "webhook_consumer_tenant_paused_total",
+                "Times reading paused because the merchant's queue was full",
+            ),
+            &["merchant_id"],
+        )
+        .unwrap();
+        let registry = service.registry();
+        registry.register(Box::new(messages.clone())).unwrap();
+        registry.register(Box::new(backlog.clone())).unwrap();
+        registry.register(Box::new(paused.clone())).unwrap();
+        Metrics {
+            service,
+            messages,
+            backlog,
+            paused,
+        }
+    }
+
+    pub fn service(&self) -> &ServiceMetrics {
+        &self.service
+    }
+
+    pub fn outcome(&self, outcome: &str) {
+        self.messages.with_label_values(&[outcome]).inc();
+    }
+
+    pub fn backlog(&self, merchant_id: &str, backlog: usize) {
+        if backlog == 0 {
+            let _ = self.backlog.remove_label_values(&[merchant_id]);
+        } else {
+            self.backlog
+                .with_label_values(&[merchant_id])
+                .set(backlog as i64);
+        }
+    }
+
+    pub fn paused(&self, merchant_id: &str) {
+        self.paused.with_label_values(&[merchant_id]).inc();
+    }
+}
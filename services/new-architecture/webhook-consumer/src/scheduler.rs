@@ -0,0 +1,257 @@
+use std::collections::{HashMap, VecDeque};
+
+// ==============================================================================
+// SCHEDULER: Weighted fair turns between merchants
+// ==============================================================================
+//
+// Messages read ahead (up to MAX_ACK_PENDING) wait in one queue per merchant,
+// and a merchant has at most one handoff in flight, so its events reach
+// Restate in the order they were read. The free handoff slots go round the
+// merchants with something queued and nothing in flight, in turns: a
+// merchant's turn is as many handoffs in a row as its weight (TENANT_WEIGHTS,
+// 1 when not listed), after which it goes to the back of the line. Weights
+// only matter when more merchants are waiting than slots are free; they
+// shorten a merchant's wait for a slot, and never run two of its handoffs at
+// once.
+//
+// A merchant's queue holds at most TENANT_MAX_BUFFERED messages per unit of
+// weight. push hands back a message past that, and the consumer holds on to
+// it and stops reading until the merchant has room (consumer.rs). It isn't
+// returned to the broker, where the merchant's later messages could overtake
+// it.
+
+struct Tenant<T> {
+    queue: VecDeque<T>,
+    in_flight: bool,
+    /// Handoffs left in its current turn
+    deficit: u32,
+}
+
+pub struct Scheduler<T> {
+    weights: HashMap<String, u32>,
+    max_buffered: usize,
+    tenants: HashMap<String, Tenant<T>>,
+    /// Merchants with something queued and nothing in flight, in turn order
+    ready: VecDeque<String>,
+}
+
+impl<T> Scheduler<T> {
+    pub fn new(weights: HashMap<String, u32>, max_buffered: usize) -> Self {
+        Scheduler {
+            weights,
+            max_buffered,
+            tenants: HashMap::new(),
+            ready: VecDeque::new(),
+        }
+    }
+
+    fn weight(&self, tenant: &str) -> u32 {
+        self.weights.get(tenant).copied().unwrap_or(1)
+    }
+
+    /// Queue the message, or hand it back when the merchant's queue is full
+    pub fn push(&mut self, tenant: &str, message: T) -> Result<(), T> {
+        let max_buffered = self.max_buffered * self.weight(tenant) as usize;
+        let entry = self.tenants.entry(tenant.to_string()).or_insert(Tenant {
+            queue: VecDeque::new(),
+            in_flight: false,
+            deficit: 0,
+        });
+        if entry.queue.len() >= max_buffered {
+            return Err(message);
+        }
+        entry.queue.push_back(message);
+        if entry.queue.len() == 1 && !entry.in_flight {
+            self.ready.push_back(tenant.to_string());
+        }
+        Ok(())
+    }
+
+    /// The next message to hand off, from the merchant whose turn it is
+    pub fn next(&mut self) -> Option<(String, T)> {
+        let tenant = self.ready.pop_front()?;
+        let weight = self.weight(&tenant);
+        let entry = self.tenants.get_mut(&tenant)?;
+        if entry.deficit == 0 {
+            entry.deficit = weight;
+        }
+        entry.deficit -= 1;
+        entry.in_flight = true;
+        let message = entry.queue.pop_front()?;
+        Some((tenant, message))
+    }
+
+    /// The merchant's handoff is over: it carries on with its turn, or goes
+    /// to the back of the line
+    pub fn done(&mut self, tenant: &str) {
+        let Some(entry) = self.tenants.get_mut(tenant) else {
+            return;
+        };
+        entry.in_flight = false;
+        if entry.queue.is_empty() {
+            self.tenants.remove(tenant);
+        } else if entry.deficit > 0 {
+            self.ready.push_front(tenant.to_string());
+        } else {
+            self.ready.push_back(tenant.to_string());
+        }
+    }
+
+    /// Messages queued for the merchant, not counting one in flight
+    pub fn backlog(&self, tenant: &str) -> usize {
+        self.tenants
+            .get(tenant)
+            .map_or(0, |entry| entry.queue.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler(weights: &[(&str, u32)], max_buffered: usize) -> Scheduler<u32> {
+        let weights = weights
+            .iter()
+            .map(|(tenant, weight)| (tenant.to_string(), *weight))
+            .collect();
+        Scheduler::new(weights, max_buffered)
+    }
+
+    /// Hands off one at a time, each finishing before the next starts
+    fn drain(scheduler: &mut Scheduler<u32>) -> Vec<String> {
+        let mut order = Vec::new();
+        while let Some((tenant, _)) = scheduler.next() {
+            scheduler.done(&tenant);
+            order.push(tenant);
+        }
+        order
+    }
+
+    #[test]
+    fn turns_are_as_long_as_the_weight() {
+        let mut scheduler = scheduler(&[("a", 3)], 100);
+        for n in 0..6 {
+            scheduler.push("a", n).unwrap();
+            scheduler.push("b", n).unwrap();
+        }
+        assert_eq!(
+            drain(&mut scheduler).concat(),
+            ["aaab", "aaab", "bbbb"].concat()
+        );
+    }
+
+    #[test]
+    fn handoffs_share_out_by_weight_while_everyone_has_a_backlog() {
+        let mut scheduler = scheduler(&[("a", 3), ("b", 2)], 1000);
+        for n in 0..300 {
+            for tenant in ["a", "b", "c"] {
+                scheduler.push(tenant, n).unwrap();
+            }
+        }
+        // The first 180 handoffs: 30 rounds of 3 + 2 + 1
+        let order = drain(&mut scheduler);
+        let count = |tenant: &str| order[..180].iter().filter(|t| *t == tenant).count();
+        assert_eq!((count("a"), count("b"), count("c")), (90, 60, 30));
+    }
+
+    #[test]
+    fn a_merchants_messages_come_out_in_order() {
+        let mut scheduler = scheduler(&[("a", 2)], 100);
+        for n in 0..10 {
+            scheduler.push("a", n).unwrap();
+            scheduler.push("b", 100 + n).unwrap();
+        }
+        let mut seen: HashMap<String, Vec<u32>> = HashMap::new();
+        while let Some((tenant, message)) = scheduler.next() {
+            scheduler.done(&tenant);
+            seen.entry(tenant).or_default().push(message);
+        }
+        assert_eq!(seen["a"], (0..10).collect::<Vec<_>>());
+        assert_eq!(seen["b"], (100..110).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn one_handoff_in_flight_per_merchant() {
+        let mut scheduler = scheduler(&[("a", 5)], 100);
+        scheduler.push("a", 1).unwrap();
+        scheduler.push("a", 2).unwrap();
+        scheduler.push("b", 3).unwrap();
+
+        assert_eq!(scheduler.next(), Some(("a".to_string(), 1)));
+        assert_eq!(scheduler.next(), Some(("b".to_string(), 3)));
+        // a's next waits for its first, whatever its weight
+        assert_eq!(scheduler.next(), None);
+        scheduler.push("a", 4).unwrap();
+        assert_eq!(scheduler.next(), None);
+
+        scheduler.done("a");
+        assert_eq!(scheduler.next(), Some(("a".to_string(), 2)));
+    }
+
+    #[test]
+    fn done_carries_on_the_turn_ahead_of_others_waiting() {
+        let mut scheduler = scheduler(&[("a", 2)], 100);
+        for n in 0..3 {
+            scheduler.push("a", n).unwrap();
+        }
+        scheduler.push("b", 10).unwrap();
+
+        assert_eq!(scheduler.next(), Some(("a".to_string(), 0)));
+        scheduler.push("c", 20).unwrap();
+        scheduler.done("a");
+        // One handoff left in a's turn: before b and c
+        assert_eq!(scheduler.next(), Some(("a".to_string(), 1)));
+        scheduler.done("a");
+        // Turn over: behind them
+        assert_eq!(scheduler.next(), Some(("b".to_string(), 10)));
+        assert_eq!(scheduler.next(), Some(("c".to_string(), 20)));
+        assert_eq!(scheduler.next(), Some(("a".to_string(), 2)));
+    }
+
+    #[test]
+    fn done_ends_a_weight_one_turn() {
+        let mut scheduler = scheduler(&[], 100);
+        scheduler.push("a", 0).unwrap();
+        scheduler.push("a", 1).unwrap();
+        scheduler.push("b", 10).unwrap();
+
+        assert_eq!(scheduler.next(), Some(("a".to_string(), 0)));
+        scheduler.done("a");
+        assert_eq!(scheduler.next(), Some(("b".to_string(), 10)));
+        assert_eq!(scheduler.next(), Some(("a".to_string(), 1)));
+    }
+
+    #[test]
+    fn done_with_nothing_queued_drops_the_merchant() {
+        let mut scheduler = scheduler(&[("a", 3)], 100);
+        scheduler.push("a", 0).unwrap();
+        assert!(scheduler.next().is_some());
+        scheduler.done("a");
+        assert_eq!(scheduler.backlog("a"), 0);
+        assert!(scheduler.tenants.is_empty());
+        assert_eq!(scheduler.next(), None);
+
+        // Unknown merchants are ignored
+        scheduler.done("z");
+        assert_eq!(scheduler.next(), None);
+    }
+
+    #[test]
+    fn queues_hold_max_buffered_times_the_weight() {
+        let mut scheduler = scheduler(&[("a", 3)], 2);
+        for n in 0..6 {
+            scheduler.push("a", n).unwrap();
+        }
+        assert_eq!(scheduler.push("a", 6), Err(6));
+        assert_eq!(scheduler.backlog("a"), 6);
+
+        scheduler.push("b", 0).unwrap();
+        scheduler.push("b", 1).unwrap();
+        assert_eq!(scheduler.push("b", 2), Err(2));
+
+        // The one in flight doesn't count
+        assert_eq!(scheduler.next(), Some(("a".to_string(), 0)));
+        scheduler.push("a", 6).unwrap();
+        assert_eq!(scheduler.push("a", 7), Err(7));
+    }
+}
@@ -0,0 +1,53 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::{error, warn};
+
+use crate::AppState;
+
+// ==============================================================================
+// CRASH INJECTION: Dying on request, for reproducible loss demos
+// ==============================================================================
+//
+// Only built with `--features crash-injection`. POST /admin/crash?after_ms=N
+// aborts the process N milliseconds later (default 0), with no shutdown
+// handling at all, like a kill -9 or an OOM kill. Whatever is queued or
+// mid-flight at that moment is lost, and GET /stats just before tells how
+// much that was.
+//
+// Unlike `docker kill` from a script, the moment is fixed relative to the
+// payments the test has sent, so runs lose comparable numbers of webhooks.
+
+#[derive(Deserialize)]
+pub struct CrashParams {
+    after_ms: Option<u64>,
+}
+
+pub async fn crash(
+    State(state): State<AppState>,
+    Query(params): Query<CrashParams>,
+) -> (StatusCode, String) {
+    let after_ms = params.after_ms.unwrap_or(0);
+    warn!(
+        "Crash requested in {}ms ({} webhooks pending now)",
+        after_ms,
+        state.stats.snapshot().pending
+    );
+
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(after_ms)).await;
+        error!(
+            "Crashing on request with {} webhooks pending",
+            state.stats.snapshot().pending
+        );
+        std::process::abort();
+    });
+
+    (
+        StatusCode::ACCEPTED,
+        format!("Crashing in {}ms\n", after_ms),
+    )
+}
@@ -1,14 +1,33 @@
 use axum::{
     extract::{Json, State},
     http::StatusCode,
+    middleware,
     routing::{get, post},
     Router,
 };
+use http_client::{HttpClient, HttpClientConfig, HttpMetrics};
 use serde::{Deserialize, Serialize};
+use service_metrics::ServiceMetrics;
 use std::sync::Arc;
-use tokio::sync::mpsc;
-use tracing::{error, info};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
 use uuid::Uuid;
+use webhook_signing::WEBHOOK_HEADERS;
+use webhook_types::{PaymentPayload, WebhookPayload};
+
+#[cfg(feature = "crash-injection")]
+mod crash;
+mod queue;
+mod retry;
+mod settings;
+mod stats;
+
+use queue::FileQueue;
+use retry::RetryPolicy;
+use settings::Settings;
+use stats::{DeliveryStats, StatsSnapshot};
 
 // ==============================================================================
 // OLD ARCHITECTURE: IN-MEMORY WEBHOOK DELIVERY (UNRELIABLE)
@@ -24,6 +43,9 @@ use uuid::Uuid;
 #[derive(Clone)]
 struct AppState {
     webhook_sender: mpsc::Sender<WebhookEvent>,
+    stats: Arc<DeliveryStats>,
+    /// Set with QUEUE_FILE (see queue.rs)
+    queue: Option<Arc<FileQueue>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -34,7 +56,7 @@ struct Payment {
     status: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 struct WebhookEvent {
     id: Uuid,
     payment_id: Uuid,
@@ -58,22 +80,106 @@ struct PaymentResponse {
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    logging::init(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    let settings: Settings = config::load_or_exit("old-architecture");
 
-    let (webhook_tx, webhook_rx) = mpsc::channel(1000);
+    // QUEUE_CAPACITY webhooks wait in the channel, sent by WORKER_COUNT
+    // workers
+    let capacity = settings.queue_capacity;
+    let worker_count = settings.worker_count;
+    let (webhook_tx, webhook_rx) = mpsc::channel(capacity);
+    let webhook_rx = Arc::new(Mutex::new(webhook_rx));
+
+    let stats = Arc::new(DeliveryStats::default());
+
+    // QUEUE_FILE: persist the queue to a local file (the halfway fix)
+    let (queue, replay) = match settings.queue_file {
+        Some(path) => {
+            let (queue, replay) = FileQueue::open(path)
+                .await
+                .expect("Failed to open queue file");
+            (Some(Arc::new(queue)), replay)
+        }
+        None => (None, Vec::new()),
+    };
 
     let state = AppState {
-        webhook_sender: webhook_tx,
+        webhook_sender: webhook_tx.clone(),
+        stats: stats.clone(),
+        queue: queue.clone(),
     };
 
-    // Spawn webhook worker
-    // CRITICAL: This worker runs in-process. If the pod crashes,
+    // Spawn webhook workers
+    // CRITICAL: These workers run in-process. If the pod crashes,
     // all pending webhooks are lost forever (no persistence)
-    tokio::spawn(webhook_worker(webhook_rx));
+    let retry_policy = RetryPolicy::new(
+        settings.webhook_max_attempts,
+        Duration::from_millis(settings.webhook_retry_backoff_ms),
+    );
+    let merchant_url: Arc<str> = settings.merchant_webhook_url.into();
+    let signing_secret: Option<Arc<str>> = settings.webhook_signing_secret.map(Into::into);
+    let metrics = ServiceMetrics::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    // One pool for all workers. No breaker: the retry policy already paces
+    // attempts, and failing them fast would only lose webhooks sooner
+    let client = HttpClient::new(
+        HttpClientConfig {
+            timeout: Duration::from_secs(5),
+            max_retries: 0,
+            breaker_threshold: 0,
+            proxy: settings.egress_proxy_url,
+            ..HttpClientConfig::new("webhook")
+        },
+        &HttpMetrics::new(metrics.registry()),
+    )
+    .expect("Failed to build HTTP client");
+    info!(
+        "{} webhook workers, queue capacity {}, retry policy: {:?}",
+        worker_count, capacity, retry_policy
+    );
+    let workers: Vec<_> = (0..worker_count)
+        .map(|_| {
+            tokio::spawn(webhook_worker(
+                client.clone(),
+                webhook_rx.clone(),
+                stats.clone(),
+                queue.clone(),
+                retry_policy,
+                merchant_url.clone(),
+                signing_secret.clone(),
+            ))
+        })
+        .collect();
+
+    // Only with QUEUE_FILE: what the last run didn't finish
+    for event in replay {
+        stats.queued();
+        if webhook_tx.send(event).await.is_err() {
+            stats.failed();
+        }
+    }
+    drop(webhook_tx);
+
+    // GRACEFUL_DRAIN_MS: on SIGTERM, keep sending what's queued for up to
+    // this long before exiting. 0 exits right away.
+    let drain_timeout = Some(Duration::from_millis(settings.graceful_drain_ms))
+        .filter(|timeout| !timeout.is_zero());
 
     let app = Router::new()
         .route("/health", get(health_check))
-        .route("/payments", post(create_payment))
+        .route("/stats", get(get_stats))
+        .route("/payments", post(create_payment));
+    #[cfg(feature = "crash-injection")]
+    let app = {
+        warn!("Crash injection enabled: POST /admin/crash aborts the process");
+        app.route("/admin/crash", post(crash::crash))
+    };
+    let app = app
+        .merge(service_metrics::router(metrics.clone()))
+        .route_layer(middleware::from_fn_with_state(
+            metrics,
+            service_metrics::track_http,
+        ))
         .with_state(state);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000")
@@ -83,17 +189,76 @@ async fn main() {
     info!("OLD ARCHITECTURE listening on port 3000");
     info!("⚠️  WARNING: This service uses IN-MEMORY webhooks and WILL LOSE DATA on crashes");
 
-    axum::serve(listener, app).await.unwrap();
+    // No new payments once the signal arrives; in-flight requests finish
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
+
+    // The router (and with it every sender) is gone, so the workers stop
+    // once the channel is empty
+    if let Some(drain_timeout) = drain_timeout {
+        let pending = stats.snapshot().pending;
+        info!(
+            "Draining {} pending webhooks for up to {:?}",
+            pending, drain_timeout
+        );
+        let started = Instant::now();
+        let drained = async {
+            for worker in workers {
+                let _ = worker.await;
+            }
+        };
+        match tokio::time::timeout(drain_timeout, drained).await {
+            Ok(_) => info!("Drained all webhooks in {:?}", started.elapsed()),
+            Err(_) => warn!("Drain deadline of {:?} passed", drain_timeout),
+        }
+    }
+
+    // PROBLEM 5: Whatever is still in the channel or mid-flight dies with us.
+    // Draining only narrows the gap: a SIGKILL, an OOM kill or a merchant
+    // slower than the deadline still loses webhooks.
+    let dropped = stats.shutdown();
+    warn!(
+        "Shutting down with {} webhooks undelivered: {:?}",
+        dropped,
+        stats.snapshot()
+    );
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl-C");
+    };
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    info!("Shutdown signal received");
 }
 
 async fn health_check() -> &'static str {
     "OK"
 }
 
+async fn get_stats(State(state): State<AppState>) -> Json<StatsSnapshot> {
+    Json(state.stats.snapshot())
+}
+
+#[tracing::instrument(skip_all, fields(correlation_id))]
 async fn create_payment(
     State(state): State<AppState>,
     Json(req): Json<CreatePaymentRequest>,
-) -> (StatusCode, Json<PaymentResponse>) {
+) -> Result<(StatusCode, Json<PaymentResponse>), (StatusCode, String)> {
     let payment = Payment {
         id: Uuid::new_v4(),
         amount: req.amount,
@@ -101,6 +266,7 @@ async fn create_payment(
         status: "succeeded".to_string(),
     };
 
+    tracing::Span::current().record("correlation_id", tracing::field::display(payment.id));
     info!("Payment created: {:?}", payment.id);
 
     // PROBLEM 1: Queue webhook in memory
@@ -113,12 +279,31 @@ async fn create_payment(
         payment: payment.clone(),
     };
 
-    if let Err(e) = state.webhook_sender.send(webhook).await {
-        error!("Failed to queue webhook: {}", e);
-        // PROBLEM 2: Lost webhook, no retry mechanism, no audit trail
+    let webhook_id = webhook.id;
+    if let Some(queue) = &state.queue {
+        if let Err(e) = queue.enqueue(&webhook).await {
+            // Still sent from memory, just not crash-safe
+            error!("Failed to persist webhook {}: {}", webhook_id, e);
+        }
+    }
+
+    // PROBLEM 2: Backpressure reaches the client; the payment itself is
+    // already made and there is nowhere else to keep its webhook
+    if let Err(e) = state.webhook_sender.try_send(webhook) {
+        state.stats.rejected();
+        if let Some(queue) = &state.queue {
+            queue.ack(webhook_id).await;
+        }
+        let reason = match e {
+            TrySendError::Full(_) => "Webhook queue is full",
+            TrySendError::Closed(_) => "Shutting down",
+        };
+        warn!("Rejected payment {}: {}", payment.id, reason);
+        return Err((StatusCode::SERVICE_UNAVAILABLE, reason.to_string()));
     }
+    state.stats.queued();
 
-    (
+    Ok((
         StatusCode::CREATED,
         Json(PaymentResponse {
             id: payment.id,
@@ -126,56 +311,126 @@ async fn create_payment(
             currency: payment.currency,
             status: payment.status,
         }),
-    )
+    ))
 }
 
-async fn webhook_worker(mut receiver: mpsc::Receiver<WebhookEvent>) {
-    let client = reqwest::Client::new();
-    let merchant_url = std::env::var("MERCHANT_WEBHOOK_URL")
-        .unwrap_or_else(|_| "http://localhost:4000/webhooks".to_string());
+async fn webhook_worker(
+    client: HttpClient,
+    receiver: Arc<Mutex<mpsc::Receiver<WebhookEvent>>>,
+    stats: Arc<DeliveryStats>,
+    queue: Option<Arc<FileQueue>>,
+    retry_policy: RetryPolicy,
+    merchant_url: Arc<str>,
+    signing_secret: Option<Arc<str>>,
+) {
+    loop {
+        let Some(event) = receiver.lock().await.recv().await else {
+            break;
+        };
+
+        // PROBLEM 3: If the process receives SIGKILL here (or SIGTERM without a drain
+        // deadline, during Kubernetes deployment), the webhook is mid-flight and lost forever
+
+        deliver(
+            &client,
+            &merchant_url,
+            signing_secret.as_deref(),
+            &event,
+            &stats,
+            retry_policy,
+        )
+        .await;
+        if let Some(queue) = &queue {
+            queue.ack(event.id).await;
+        }
 
-    while let Some(event) = receiver.recv().await {
-        // PROBLEM 3: If the process receives SIGTERM/SIGKILL here (during Kubernetes deployment),
-        // the webhook is mid-flight and lost forever
+        // Simulate processing delay
+        tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    }
+}
 
-        match send_webhook(&client, &merchant_url, &event).await {
+/// Send with retries; the span tags the attempts' logs (logging crate)
+#[tracing::instrument(skip_all, fields(event_id = %event.id, correlation_id = %event.payment_id))]
+async fn deliver(
+    client: &HttpClient,
+    merchant_url: &str,
+    signing_secret: Option<&str>,
+    event: &WebhookEvent,
+    stats: &DeliveryStats,
+    retry_policy: RetryPolicy,
+) {
+    let mut attempt = 1;
+    loop {
+        // As text: the error isn't Send, and the retry waits below
+        let result = send_webhook(client, merchant_url, signing_secret, event)
+            .await
+            .map_err(|e| e.to_string());
+        match result {
             Ok(_) => {
+                stats.sent();
                 info!("Webhook sent successfully: {:?}", event.payment_id);
+                return;
+            }
+            Err(e) if attempt < retry_policy.max_attempts => {
+                stats.retried();
+                let delay = retry_policy.delay(attempt);
+                warn!(
+                    "Webhook attempt {} failed, retrying in {:?}: {}",
+                    attempt, delay, e
+                );
+                // PROBLEM 4: Retries are only held in memory; the queue
+                // behind this webhook waits meanwhile
+                tokio::time::sleep(delay).await;
+                attempt += 1;
             }
             Err(e) => {
-                // PROBLEM 4: No persistent retry queue, simple error logging
-                error!("Failed to send webhook: {}", e);
+                stats.failed();
+                error!("Failed to send webhook after {} attempts: {}", attempt, e);
                 // Webhook is LOST - no recovery, no audit trail
+                return;
             }
         }
-
-        // Simulate processing delay
-        tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
     }
 }
 
 async fn send_webhook(
-    client: &reqwest::Client,
+    client: &HttpClient,
     url: &str,
+    signing_secret: Option<&str>,
     event: &WebhookEvent,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let body = serde_json::json!({
-        "event_id": event.id,
-        "event_type": event.event_type,
-        "payment": {
-            "id": event.payment.id,
-            "amount": event.payment.amount,
-            "currency": event.payment.currency,
-            "status": event.payment.status,
-        }
-    });
+    let payment = PaymentPayload {
+        id: event.payment.id,
+        amount: Some(event.payment.amount),
+        currency: Some(event.payment.currency.clone()),
+        status: event.payment.status.clone(),
+        mode: webhook_types::default_mode(),
+        amount_decimal: None,
+    };
+    let body = WebhookPayload {
+        schema_version: webhook_types::SCHEMA_VERSION,
+        event_id: event.id.to_string(),
+        event_type: event.event_type.clone(),
+        payment: serde_json::to_value(payment)?,
+    };
 
-    let response = client
+    let body = serde_json::to_vec(&body)?;
+
+    let mut request = client
         .post(url)
-        .json(&body)
-        .timeout(std::time::Duration::from_secs(5))
-        .send()
-        .await?;
+        .header("content-type", "application/json");
+    if let Some(secret) = signing_secret {
+        // Each attempt is signed afresh, so a retry's timestamp is current
+        let msg_id = event.id.to_string();
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        let signature = webhook_signing::sign(&[secret], &msg_id, timestamp, &body)?;
+        request = request
+            .header(WEBHOOK_HEADERS.id, msg_id)
+            .header(WEBHOOK_HEADERS.timestamp, timestamp.to_string())
+            .header(WEBHOOK_HEADERS.signature, signature);
+    }
+
+    let response = client.send(request.body(body)).await?;
 
     response.error_for_status()?;
     Ok(())
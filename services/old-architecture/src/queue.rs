@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io;
+use std::path::PathBuf;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::WebhookEvent;
+
+// ==============================================================================
+// FILE-BACKED QUEUE: The halfway fix
+// ==============================================================================
+//
+// With QUEUE_FILE set, every webhook is appended to a local JSONL log (and
+// fsynced) before it goes into the channel, and acknowledged in the same log
+// once the worker is done with it. On startup the webhooks enqueued but never
+// acknowledged are sent again, and the log is compacted down to them.
+//
+// That survives a crash or restart of the process, as long as the disk
+// survives too: a rescheduled pod on another node starts with an empty file.
+// There is one log per instance, no replication, and an ack lost in a crash
+// means the webhook is sent twice. Kafka + Restate in the new architecture
+// are what closes those gaps.
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Entry {
+    Enqueue(WebhookEvent),
+    Ack(Uuid),
+}
+
+pub struct FileQueue {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl FileQueue {
+    /// Opens the log, returning the webhooks still to send, oldest first
+    pub async fn open(path: PathBuf) -> io::Result<(Self, Vec<WebhookEvent>)> {
+        let pending = match tokio::fs::read_to_string(&path).await {
+            Ok(log) => pending_events(&log),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        // Compact: rewrite the log with only what is still pending
+        let compacted = path.with_extension("compact");
+        let mut lines = String::new();
+        for event in &pending {
+            lines.push_str(&entry_line(&Entry::Enqueue(event.clone()))?);
+        }
+        tokio::fs::write(&compacted, lines).await?;
+        tokio::fs::rename(&compacted, &path).await?;
+
+        let file = OpenOptions::new().append(true).open(&path).await?;
+        info!(
+            "Queue file {} opened, {} webhooks to replay",
+            path.display(),
+            pending.len()
+        );
+
+        Ok((
+            FileQueue {
+                path,
+                file: Mutex::new(file),
+            },
+            pending,
+        ))
+    }
+
+    /// Durable once this returns: the line is fsynced
+    pub async fn enqueue(&self, event: &WebhookEvent) -> io::Result<()> {
+        let line = entry_line(&Entry::Enqueue(event.clone()))?;
+        let mut file = self.file.lock().await;
+        file.write_all(line.as_bytes()).await?;
+        file.sync_data().await
+    }
+
+    /// Not fsynced: losing an ack only means sending the webhook again
+    pub async fn ack(&self, id: Uuid) {
+        let result = match entry_line(&Entry::Ack(id)) {
+            Ok(line) => self.file.lock().await.write_all(line.as_bytes()).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!("Failed to ack {} in {}: {}", id, self.path.display(), e);
+        }
+    }
+}
+
+fn entry_line(entry: &Entry) -> io::Result<String> {
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    Ok(line)
+}
+
+/// A torn last line (crash mid-write) is skipped
+fn pending_events(log: &str) -> Vec<WebhookEvent> {
+    let mut events = Vec::new();
+    let mut acked = HashSet::new();
+    for line in log.lines().filter(|line| !line.trim().is_empty()) {
+        match serde_json::from_str::<Entry>(line) {
+            Ok(Entry::Enqueue(event)) => events.push(event),
+            Ok(Entry::Ack(id)) => {
+                acked.insert(id);
+            }
+            Err(e) => warn!("Skipping unreadable queue entry: {}", e),
+        }
+    }
+    events.retain(|event| !acked.contains(&event.id));
+    events
+}
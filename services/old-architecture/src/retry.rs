@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+// ==============================================================================
+// RETRY POLICY: Bounded retries for the legacy worker
+// ==============================================================================
+//
+// Without retries every merchant hiccup is a lost webhook, which would make
+// the comparison with the new architecture about retries rather than
+// durability. The worker makes up to WEBHOOK_MAX_ATTEMPTS attempts (default
+// 3), waiting WEBHOOK_RETRY_BACKOFF_MS (default 200) after the first failure
+// and doubling the wait after each further one, up to MAX_BACKOFF.
+//
+// The retries live in the worker's memory like the queue itself: a crash
+// while waiting loses the webhook all the same.
+
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, backoff: Duration) -> Self {
+        RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            backoff,
+        }
+    }
+
+    /// Wait after failed attempt number `attempt` (1-based)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(MAX_BACKOFF)
+    }
+}
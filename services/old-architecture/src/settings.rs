@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// ==============================================================================
+// SETTINGS: Everything main.rs reads at startup
+// ==============================================================================
+//
+// Loaded through the shared config crate: defaults below, then
+// config/old-architecture.toml (or CONFIG_FILE), then the environment
+// variable of the same name in upper case. A bad value stops startup with the
+// full list of problems instead of silently falling back to the default.
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Persist the queue to this file (queue.rs); unset keeps it in memory
+    pub queue_file: Option<PathBuf>,
+    /// Keep sending for up to this long on SIGTERM; 0 exits right away
+    pub graceful_drain_ms: u64,
+    pub queue_capacity: usize,
+    /// More than one gives up the order webhooks were queued in
+    pub worker_count: usize,
+    pub merchant_webhook_url: String,
+    /// Sign webhooks with this endpoint secret ("whsec_..."), as the new
+    /// architecture does; unset sends them unsigned
+    pub webhook_signing_secret: Option<String>,
+    pub webhook_max_attempts: u32,
+    pub webhook_retry_backoff_ms: u64,
+    /// Send webhooks through this HTTP(S) or SOCKS5 proxy, so merchants can
+    /// allowlist its addresses; unset connects directly
+    pub egress_proxy_url: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            queue_file: None,
+            graceful_drain_ms: 0,
+            queue_capacity: 1000,
+            worker_count: 1,
+            merchant_webhook_url: "http://localhost:4000/webhooks".to_string(),
+            webhook_signing_secret: None,
+            webhook_max_attempts: 3,
+            webhook_retry_backoff_ms: 200,
+            egress_proxy_url: None,
+        }
+    }
+}
+
+impl config::Validate for Settings {
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.queue_capacity == 0 {
+            problems.push("QUEUE_CAPACITY: must be at least 1".to_string());
+        }
+        if self.worker_count == 0 {
+            problems.push("WORKER_COUNT: must be at least 1".to_string());
+        }
+        if self.webhook_max_attempts == 0 {
+            problems.push("WEBHOOK_MAX_ATTEMPTS: must be at least 1".to_string());
+        }
+        if let Some(secret) = &self.webhook_signing_secret {
+            if let Err(e) = webhook_signing::decode_secret(secret) {
+                problems.push(format!("WEBHOOK_SIGNING_SECRET: {}", e));
+            }
+        }
+        problems
+    }
+}
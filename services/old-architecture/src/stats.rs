@@ -0,0 +1,85 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// ==============================================================================
+// DELIVERY STATS: Counting what the in-memory design loses
+// ==============================================================================
+//
+// Every webhook is counted once when it goes into the channel (queued) and
+// once when it leaves the system (sent or failed); one that doesn't fit in
+// the channel is rejected with a 503 instead. The difference is pending:
+// in the channel or mid-flight, i.e. exactly what a crash right now would
+// lose. GET /stats returns the counters, so a kill test can read them just
+// before the kill and compare with what the merchant received.
+//
+// On SIGTERM/Ctrl-C whatever is still pending is counted as dropped on
+// shutdown and logged. A SIGKILL leaves no chance to count anything.
+
+#[derive(Default)]
+pub struct DeliveryStats {
+    queued: AtomicU64,
+    sent: AtomicU64,
+    failed: AtomicU64,
+    /// Failed attempts that were tried again (not webhooks)
+    retried: AtomicU64,
+    /// Answered 503: the queue was full (or closing)
+    rejected: AtomicU64,
+    dropped_on_shutdown: AtomicU64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatsSnapshot {
+    pub queued: u64,
+    pub sent: u64,
+    pub failed: u64,
+    pub retried: u64,
+    pub rejected: u64,
+    pub dropped_on_shutdown: u64,
+    /// queued - sent - failed
+    pub pending: u64,
+}
+
+impl DeliveryStats {
+    pub fn queued(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn sent(&self) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Failed every attempt
+    pub fn failed(&self) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn retried(&self) {
+        self.retried.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn rejected(&self) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count everything still pending as dropped; returns how many that was
+    pub fn shutdown(&self) -> u64 {
+        let pending = self.snapshot().pending;
+        self.dropped_on_shutdown.store(pending, Ordering::Relaxed);
+        pending
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let queued = self.queued.load(Ordering::Relaxed);
+        let sent = self.sent.load(Ordering::Relaxed);
+        let failed = self.failed.load(Ordering::Relaxed);
+        StatsSnapshot {
+            queued,
+            sent,
+            failed,
+            retried: self.retried.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            dropped_on_shutdown: self.dropped_on_shutdown.load(Ordering::Relaxed),
+            pending: queued.saturating_sub(sent + failed),
+        }
+    }
+}
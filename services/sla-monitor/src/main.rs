@@ -0,0 +1,230 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use service_metrics::ServiceMetrics;
+use sqlx::postgres::PgPoolOptions;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+mod metrics;
+mod proxy;
+mod settings;
+mod sla;
+mod sources;
+
+use metrics::Metrics;
+use settings::Settings;
+use sla::Sla;
+use sources::Creations;
+
+// ==============================================================================
+// SLA MONITOR: End-to-end delivery latency and loss, per architecture
+// ==============================================================================
+//
+// Every POLL_INTERVAL_SECS it matches each architecture's payments
+// (sources.rs) with the webhooks its merchant-simulator received, and works
+// out delivery latency percentiles and how many payments are delivered,
+// pending or lost (sla.rs). The latest measurement is served on GET /sla and
+// as gauges on /metrics; POST /reset starts a new run from now.
+//
+// The new architecture's merchants must be the ones whose webhooks go to
+// NEW_MERCHANT_URL, or their payments will count as lost.
+
+pub struct Architecture {
+    pub name: &'static str,
+    pub api_url: String,
+    merchant_url: String,
+    pub creations: Creations,
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    architectures: Arc<Vec<Architecture>>,
+    /// Start of the current run
+    since: Arc<RwLock<DateTime<Utc>>>,
+    latest: Arc<RwLock<Report>>,
+    pub http: reqwest::Client,
+    metrics: Metrics,
+    loss_after_ms: i64,
+}
+
+impl AppState {
+    pub fn architecture(&self, name: &str) -> Option<&Architecture> {
+        self.architectures.iter().find(|a| a.name == name)
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct Report {
+    since: DateTime<Utc>,
+    measured_at: Option<DateTime<Utc>>,
+    architectures: Vec<Sla>,
+}
+
+#[tokio::main]
+async fn main() {
+    logging::init(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    let settings: Settings = config::load_or_exit("sla-monitor");
+
+    // Without the database the new architecture is measured like the old
+    // one, from payments created through the proxy
+    let new_creations = match &settings.database_url {
+        Some(url) => Creations::Events(
+            PgPoolOptions::new()
+                .max_connections(2)
+                .connect_lazy(url)
+                .expect("Invalid DATABASE_URL"),
+        ),
+        None => {
+            warn!("DATABASE_URL not set: new payments are only measured through the proxy");
+            Creations::Recorded(Mutex::default())
+        }
+    };
+    let architectures = vec![
+        Architecture {
+            name: "old",
+            api_url: settings.old_api_url.clone(),
+            merchant_url: settings.old_merchant_url.clone(),
+            creations: Creations::Recorded(Mutex::default()),
+        },
+        Architecture {
+            name: "new",
+            api_url: settings.new_api_url.clone(),
+            merchant_url: settings.new_merchant_url.clone(),
+            creations: new_creations,
+        },
+    ];
+
+    let since = Utc::now();
+    let metrics = Metrics::new(ServiceMetrics::new(
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+    ));
+    let state = AppState {
+        architectures: Arc::new(architectures),
+        since: Arc::new(RwLock::new(since)),
+        latest: Arc::new(RwLock::new(Report {
+            since,
+            measured_at: None,
+            architectures: Vec::new(),
+        })),
+        http: reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .expect("Failed to build HTTP client"),
+        metrics: metrics.clone(),
+        loss_after_ms: settings.loss_after_secs as i64 * 1000,
+    };
+
+    tokio::spawn(poll(
+        state.clone(),
+        Duration::from_secs(settings.poll_interval_secs),
+    ));
+
+    let app = Router::new()
+        .route("/health", get(health_check))
+        .route("/sla", get(get_sla))
+        .route("/reset", post(reset))
+        .route("/architectures/:name/payments", post(proxy::create_payment))
+        .merge(service_metrics::router(metrics.service().clone()))
+        .route_layer(middleware::from_fn_with_state(
+            metrics.service().clone(),
+            service_metrics::track_http,
+        ))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", settings.port))
+        .await
+        .unwrap();
+
+    info!("SLA MONITOR listening on port {}", settings.port);
+
+    axum::serve(listener, app).await.unwrap();
+}
+
+async fn health_check() -> &'static str {
+    "OK"
+}
+
+async fn poll(state: AppState, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        measure(&state).await;
+    }
+}
+
+async fn measure(state: &AppState) {
+    let since = *state.since.read();
+    let mut measured = Vec::with_capacity(state.architectures.len());
+    for architecture in state.architectures.iter() {
+        let sla = match measure_one(state, architecture, since).await {
+            Ok(sla) => sla,
+            Err(e) => {
+                warn!(
+                    "Failed to measure the {} architecture: {}",
+                    architecture.name, e
+                );
+                Sla::failed(architecture.name, e)
+            }
+        };
+        state.metrics.record(&sla);
+        measured.push(sla);
+    }
+
+    // A reset while measuring makes this measurement stale
+    if *state.since.read() == since {
+        *state.latest.write() = Report {
+            since,
+            measured_at: Some(Utc::now()),
+            architectures: measured,
+        };
+    }
+}
+
+async fn measure_one(
+    state: &AppState,
+    architecture: &Architecture,
+    since: DateTime<Utc>,
+) -> Result<Sla, String> {
+    let created = architecture.creations.since(since).await?;
+    let receipts = sources::receipts(&state.http, &architecture.merchant_url).await?;
+    Ok(Sla::measure(
+        architecture.name,
+        &created,
+        &receipts,
+        Utc::now().timestamp_millis(),
+        state.loss_after_ms,
+    ))
+}
+
+async fn get_sla(State(state): State<AppState>) -> Json<Report> {
+    Json(state.latest.read().clone())
+}
+
+/// Start a new run: only payments created from now on count
+async fn reset(State(state): State<AppState>) -> impl IntoResponse {
+    let since = Utc::now();
+    *state.since.write() = since;
+    for architecture in state.architectures.iter() {
+        architecture.creations.clear();
+    }
+    state.metrics.reset();
+    *state.latest.write() = Report {
+        since,
+        measured_at: None,
+        architectures: Vec::new(),
+    };
+    info!("SLA measurements reset");
+    (StatusCode::OK, "SLA measurements reset")
+}
@@ -0,0 +1,104 @@
+use prometheus::{GaugeVec, IntGaugeVec, Opts};
+use service_metrics::ServiceMetrics;
+
+use crate::sla::Sla;
+
+// ==============================================================================
+// METRICS: The latest measurement as Prometheus gauges
+// ==============================================================================
+//
+// Set from each measurement, next to the process and HTTP metrics every
+// service has (service-metrics crate), so a dashboard can put both
+// architectures' latency percentiles and loss side by side.
+
+#[derive(Clone)]
+pub struct Metrics {
+    service: ServiceMetrics,
+    latency: GaugeVec,
+    payments: IntGaugeVec,
+    duplicates: IntGaugeVec,
+}
+
+impl Metrics {
+    pub fn new(service: ServiceMetrics) -> Self {
+        let registry = service.registry();
+
+        let latency = GaugeVec::new(
+            Opts::new(
+                "sla_delivery_latency_seconds",
+                "Payment creation to first webhook receipt, by percentile",
+            ),
+            &["architecture", "quantile"],
+        )
+        .unwrap();
+        let payments = IntGaugeVec::new(
+            Opts::new(
+                "sla_payments",
+                "Payments since the last reset by delivery state",
+            ),
+            &["architecture", "state"],
+        )
+        .unwrap();
+        let duplicates = IntGaugeVec::new(
+            Opts::new(
+                "sla_duplicate_webhooks",
+                "Webhooks received beyond the first per payment",
+            ),
+            &["architecture"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(latency.clone())).unwrap();
+        registry.register(Box::new(payments.clone())).unwrap();
+        registry.register(Box::new(duplicates.clone())).unwrap();
+
+        Metrics {
+            service,
+            latency,
+            payments,
+            duplicates,
+        }
+    }
+
+    pub fn service(&self) -> &ServiceMetrics {
+        &self.service
+    }
+
+    /// Forget the percentiles of the previous run
+    pub fn reset(&self) {
+        self.latency.reset();
+    }
+
+    pub fn record(&self, sla: &Sla) {
+        // A failed measurement keeps the last good values
+        if sla.error.is_some() {
+            return;
+        }
+        let architecture = sla.architecture;
+        for (state, count) in [
+            ("created", sla.created),
+            ("delivered", sla.delivered),
+            ("pending", sla.pending),
+            ("lost", sla.lost),
+        ] {
+            self.payments
+                .with_label_values(&[architecture, state])
+                .set(count as i64);
+        }
+        self.duplicates
+            .with_label_values(&[architecture])
+            .set(sla.duplicates as i64);
+        if let Some(latency) = &sla.latency_ms {
+            for (quantile, ms) in [
+                ("0.5", latency.p50),
+                ("0.9", latency.p90),
+                ("0.99", latency.p99),
+                ("1", latency.max),
+            ] {
+                self.latency
+                    .with_label_values(&[architecture, quantile])
+                    .set(ms as f64 / 1000.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::AppState;
+
+// ==============================================================================
+// PROXY: Creating payments through sla-monitor to record when they were made
+// ==============================================================================
+//
+// POST /architectures/<name>/payments forwards the body to that
+// architecture's POST /payments and answers with its response. For old-api,
+// which keeps no record of its payments, a 201 also records the payment id
+// with the time the request was forwarded: that is the creation time its
+// latency is measured from. Point a client at the proxy instead of the API,
+// e.g. TARGET_URL=http://localhost:3005/architectures/old for the load
+// generator. Payments created around it aren't measured.
+
+#[derive(Deserialize)]
+struct Created {
+    id: Uuid,
+}
+
+pub async fn create_payment(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    body: Bytes,
+) -> Result<Response, (StatusCode, String)> {
+    let architecture = state.architecture(&name).ok_or((
+        StatusCode::NOT_FOUND,
+        format!("Unknown architecture: {}", name),
+    ))?;
+
+    let started_ms = Utc::now().timestamp_millis();
+    let response = state
+        .http
+        .post(format!("{}/payments", architecture.api_url))
+        .header(CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to reach {}: {}", architecture.api_url, e),
+            )
+        })?;
+
+    let status = response.status();
+    let content_type = response.headers().get(CONTENT_TYPE).cloned();
+    let body = response.bytes().await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to read the response: {}", e),
+        )
+    })?;
+
+    if status == StatusCode::CREATED {
+        match serde_json::from_slice::<Created>(&body) {
+            Ok(created) => architecture.creations.record(created.id, started_ms),
+            Err(e) => tracing::warn!("Created payment without an id from {}: {}", name, e),
+        }
+    }
+
+    let mut response = (status, body).into_response();
+    if let Some(content_type) = content_type {
+        response.headers_mut().insert(CONTENT_TYPE, content_type);
+    }
+    Ok(response)
+}
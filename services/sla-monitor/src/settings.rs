@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+// ==============================================================================
+// SETTINGS: Where each architecture's payments and webhooks are
+// ==============================================================================
+//
+// Loaded through the shared config crate: defaults below, then
+// config/sla-monitor.toml (or CONFIG_FILE), then the environment variable of
+// the same name in upper case. Without DATABASE_URL only the old architecture
+// is measured.
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub port: u16,
+    /// old-api, which payments for the old architecture go through (proxy.rs)
+    pub old_api_url: String,
+    pub old_merchant_url: String,
+    /// api-service; its payments are read from domain_events instead
+    pub new_api_url: String,
+    pub new_merchant_url: String,
+    pub database_url: Option<String>,
+    pub poll_interval_secs: u64,
+    /// A payment without a webhook this long after creation counts as lost
+    pub loss_after_secs: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            port: 3005,
+            old_api_url: "http://localhost:3000".to_string(),
+            old_merchant_url: "http://localhost:4000".to_string(),
+            new_api_url: "http://localhost:3001".to_string(),
+            new_merchant_url: "http://localhost:4001".to_string(),
+            database_url: None,
+            poll_interval_secs: 5,
+            loss_after_secs: 60,
+        }
+    }
+}
+
+impl config::Validate for Settings {
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.poll_interval_secs == 0 {
+            problems.push("POLL_INTERVAL_SECS: must be at least 1".to_string());
+        }
+        if self.loss_after_secs == 0 {
+            problems.push("LOSS_AFTER_SECS: must be at least 1".to_string());
+        }
+        problems
+    }
+}
@@ -0,0 +1,115 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::sources::{Receipt, Timestamps};
+
+// ==============================================================================
+// SLA: Delivery latency and loss for one architecture
+// ==============================================================================
+//
+// Only payments created since the last reset count. For each of them:
+//
+//   delivered  a webhook arrived; latency is first receipt - creation
+//   pending    none yet, but it was created less than LOSS_AFTER_SECS ago
+//   lost       none within LOSS_AFTER_SECS
+//
+// Webhooks beyond the first for a payment are duplicates (a retry after a lost
+// response, a replay, or a second event such as pending -> succeeded).
+// Webhooks for payments created before the reset, or elsewhere, are ignored.
+// A payment counted lost that is delivered later moves to delivered on the
+// next measurement, as it would in a merchant's reconciliation.
+
+#[derive(Clone, Serialize)]
+pub struct Sla {
+    pub architecture: &'static str,
+    pub created: u64,
+    pub delivered: u64,
+    pub pending: u64,
+    pub lost: u64,
+    pub duplicates: u64,
+    /// None until a webhook arrived
+    pub latency_ms: Option<Percentiles>,
+    /// Why this measurement couldn't be taken; the counts are zero then
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Percentiles {
+    pub p50: i64,
+    pub p90: i64,
+    pub p99: i64,
+    pub max: i64,
+}
+
+impl Sla {
+    pub fn failed(architecture: &'static str, error: String) -> Self {
+        Sla {
+            architecture,
+            created: 0,
+            delivered: 0,
+            pending: 0,
+            lost: 0,
+            duplicates: 0,
+            latency_ms: None,
+            error: Some(error),
+        }
+    }
+
+    pub fn measure(
+        architecture: &'static str,
+        created: &Timestamps,
+        receipts: &[Receipt],
+        now_ms: i64,
+        loss_after_ms: i64,
+    ) -> Self {
+        // First receipt and number of receipts per payment
+        let mut received: HashMap<Uuid, (i64, u64)> = HashMap::new();
+        for receipt in receipts {
+            if !created.contains_key(&receipt.payment_id) {
+                continue;
+            }
+            let entry = received
+                .entry(receipt.payment_id)
+                .or_insert((receipt.received_at_ms, 0));
+            entry.0 = entry.0.min(receipt.received_at_ms);
+            entry.1 += 1;
+        }
+
+        let mut sla = Sla {
+            architecture,
+            created: created.len() as u64,
+            delivered: 0,
+            pending: 0,
+            lost: 0,
+            duplicates: 0,
+            latency_ms: None,
+            error: None,
+        };
+        let mut latencies = Vec::with_capacity(received.len());
+        for (payment_id, created_ms) in created {
+            match received.get(payment_id) {
+                Some((first_ms, count)) => {
+                    sla.delivered += 1;
+                    sla.duplicates += count - 1;
+                    latencies.push((first_ms - created_ms).max(0));
+                }
+                None if now_ms - created_ms > loss_after_ms => sla.lost += 1,
+                None => sla.pending += 1,
+            }
+        }
+
+        if !latencies.is_empty() {
+            latencies.sort_unstable();
+            let percentile = |p: usize| latencies[(latencies.len() * p).div_ceil(100).max(1) - 1];
+            sla.latency_ms = Some(Percentiles {
+                p50: percentile(50),
+                p90: percentile(90),
+                p99: percentile(99),
+                max: percentile(100),
+            });
+        }
+        sla
+    }
+}
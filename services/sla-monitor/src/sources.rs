@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+// ==============================================================================
+// SOURCES: When each payment was created, and when its webhooks arrived
+// ==============================================================================
+//
+// Creation times come from one of two places:
+//
+//   Events    the new architecture writes every payment's event to
+//             domain_events in the same transaction, so the first payment.*
+//             row per payment is its creation time, whoever created it
+//   Recorded  old-api keeps nothing, so its payments are created through
+//             sla-monitor's POST /architectures/old/payments (proxy.rs),
+//             which records the id and time of each 201 before answering
+//
+// Receipt times come from the architecture's merchant-simulator GET /stats
+// (received_at_ms). All services run on one host, so the clocks agree.
+
+/// Unix milliseconds per payment id
+pub type Timestamps = HashMap<Uuid, i64>;
+
+pub enum Creations {
+    Events(PgPool),
+    Recorded(Mutex<Timestamps>),
+}
+
+impl Creations {
+    pub async fn since(&self, since: DateTime<Utc>) -> Result<Timestamps, String> {
+        match self {
+            Creations::Events(db) => {
+                let rows = sqlx::query_as::<_, (Uuid, i64)>(
+                    r#"
+                    SELECT object_id, (EXTRACT(EPOCH FROM MIN(created_at)) * 1000)::BIGINT
+                    FROM domain_events
+                    WHERE event_type LIKE 'payment.%'
+                      AND replay_of IS NULL
+                      AND created_at >= $1
+                    GROUP BY object_id
+                    "#,
+                )
+                .bind(since)
+                .fetch_all(db)
+                .await
+                .map_err(|e| format!("Failed to read domain_events: {}", e))?;
+                Ok(rows.into_iter().collect())
+            }
+            Creations::Recorded(recorded) => {
+                let since_ms = since.timestamp_millis();
+                Ok(recorded
+                    .lock()
+                    .iter()
+                    .filter(|(_, created_ms)| **created_ms >= since_ms)
+                    .map(|(id, created_ms)| (*id, *created_ms))
+                    .collect())
+            }
+        }
+    }
+
+    /// Remember a payment created through the proxy; a no-op for Events
+    pub fn record(&self, payment_id: Uuid, created_ms: i64) {
+        if let Creations::Recorded(recorded) = self {
+            recorded.lock().insert(payment_id, created_ms);
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Creations::Recorded(recorded) = self {
+            recorded.lock().clear();
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Receipt {
+    pub payment_id: Uuid,
+    pub received_at_ms: i64,
+}
+
+#[derive(Deserialize)]
+struct MerchantStats {
+    webhooks: Vec<Receipt>,
+}
+
+/// Every webhook the merchant-simulator at `merchant_url` has received
+pub async fn receipts(http: &reqwest::Client, merchant_url: &str) -> Result<Vec<Receipt>, String> {
+    let stats: MerchantStats = http
+        .get(format!("{}/stats", merchant_url))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to reach {}: {}", merchant_url, e))?
+        .json()
+        .await
+        .map_err(|e| format!("Unexpected /stats from {}: {}", merchant_url, e))?;
+    Ok(stats.webhooks)
+}
@@ -53,8 +53,11 @@ SEND_PID=$!
 # Kill service at ~50% completion
 sleep 2.5
 echo ""
-echo "[CHAOS] Killing old-api container..."
-kill_and_restart_service "old-api" "$CRASH_DELAY"
+# Queued or mid-flight in old-api's memory: what the kill is about to lose
+OLD_PENDING=$(parse_pending "$(curl -s http://localhost:3000/stats)")
+echo "[CHAOS] old-api has ${OLD_PENDING:-?} webhooks pending in memory"
+echo "[CHAOS] Crashing old-api..."
+crash_and_restart_service "http://localhost:3000" "old-api" "$CRASH_DELAY"
 echo ""
 
 # Wait for payment sending to complete
@@ -0,0 +1,350 @@
+use serde::Deserialize;
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use testcontainers::core::{IntoContainerPort, Mount, WaitFor};
+use testcontainers::runners::AsyncRunner;
+use testcontainers::{ContainerAsync, GenericImage, ImageExt};
+use uuid::Uuid;
+
+// ==============================================================================
+// E2E HARNESS: The services in containers, one private network per test
+// ==============================================================================
+//
+// The shell tests in tests/ run against the docker compose stack and print
+// what they saw; these assert it. Each test gets its own Docker network and
+// containers (removed when the test ends), so tests run in parallel and
+// leave nothing behind.
+//
+// The service images are built from the repository first:
+//
+//   ./scripts/build-e2e-images.sh
+//   cargo test -p e2e -- --ignored
+//
+// The tests are #[ignore]d so a plain `cargo test` doesn't need Docker.
+//
+// Covered: the old architecture end to end (old-api -> merchant-simulator),
+// and the new one's capture side (api-service's payment + event in one
+// transaction, data-service's payloads). Sequin is configured in its UI
+// (SEQUIN_SETUP.md), so Kafka -> Restate -> Svix is still exercised by
+// tests/crash-test.sh against docker compose.
+
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Tag of the images scripts/build-e2e-images.sh builds
+const IMAGE_TAG: &str = "e2e";
+const POSTGRES_USER: &str = "dodo";
+const POSTGRES_PASSWORD: &str = "dodo_pass";
+const POSTGRES_DB: &str = "dodo_demo";
+
+/// A running container and how to reach it
+pub struct Service {
+    pub container: ContainerAsync<GenericImage>,
+    /// Name on the test's network, for the other containers
+    pub name: String,
+    port: u16,
+}
+
+impl Service {
+    /// Base URL from the host. Looked up each time: a restarted container
+    /// gets a new host port.
+    pub async fn url(&self) -> Result<String> {
+        let host = self.container.get_host().await?;
+        let port = self.container.get_host_port_ipv4(self.port.tcp()).await?;
+        Ok(format!("http://{}:{}", host, port))
+    }
+
+    /// Base URL on the test's network
+    pub fn internal_url(&self) -> String {
+        format!("http://{}:{}", self.name, self.port)
+    }
+}
+
+pub struct Harness {
+    run: String,
+    network: String,
+    pub http: reqwest::Client,
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        let run = Uuid::new_v4().simple().to_string()[..8].to_string();
+        Harness {
+            network: format!("e2e-{}", run),
+            run,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("Failed to build HTTP client"),
+        }
+    }
+
+    fn container_name(&self, service: &str) -> String {
+        format!("{}-{}", service, self.run)
+    }
+
+    async fn start(
+        &self,
+        image: GenericImage,
+        service: &str,
+        port: u16,
+        env: &[(&str, String)],
+    ) -> Result<Service> {
+        let name = self.container_name(service);
+        let mut request = image
+            .with_network(self.network.clone())
+            .with_container_name(name.clone())
+            .with_env_var("LOG_FORMAT", "text");
+        for (key, value) in env {
+            request = request.with_env_var(*key, value.clone());
+        }
+        let container = request.start().await?;
+        Ok(Service {
+            container,
+            name,
+            port,
+        })
+    }
+
+    /// A service image built by scripts/build-e2e-images.sh
+    fn service_image(image: &str, port: u16, ready: &str) -> GenericImage {
+        GenericImage::new(format!("webhook-{}", image), IMAGE_TAG.to_string())
+            .with_exposed_port(port.tcp())
+            .with_wait_for(WaitFor::message_on_stdout(ready))
+    }
+
+    /// Postgres with infrastructure/postgres/init.sql applied
+    pub async fn postgres(&self) -> Result<Service> {
+        let init_sql = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../../infrastructure/postgres/init.sql")
+            .canonicalize()?;
+        let image = GenericImage::new("postgres", "15-alpine")
+            .with_exposed_port(5432.tcp())
+            .with_wait_for(WaitFor::message_on_stdout(
+                "PostgreSQL init process complete",
+            ))
+            // The server restarts once the init scripts ran
+            .with_wait_for(WaitFor::seconds(2));
+        let name = self.container_name("postgres");
+        let container = image
+            .with_network(self.network.clone())
+            .with_container_name(name.clone())
+            .with_env_var("POSTGRES_USER", POSTGRES_USER)
+            .with_env_var("POSTGRES_PASSWORD", POSTGRES_PASSWORD)
+            .with_env_var("POSTGRES_DB", POSTGRES_DB)
+            .with_mount(Mount::bind_mount(
+                init_sql.to_string_lossy(),
+                "/docker-entrypoint-initdb.d/01-init.sql",
+            ))
+            .start()
+            .await?;
+        Ok(Service {
+            container,
+            name,
+            port: 5432,
+        })
+    }
+
+    pub async fn merchant(&self) -> Result<Service> {
+        self.start(
+            Self::service_image("merchant-simulator", 4000, "listening on port"),
+            "merchant",
+            4000,
+            &[("PORT", "4000".to_string())],
+        )
+        .await
+    }
+
+    /// old-api delivering to `merchant`, with extra settings in `env`
+    pub async fn old_api(&self, merchant: &Service, env: &[(&str, String)]) -> Result<Service> {
+        let mut env = env.to_vec();
+        env.push((
+            "MERCHANT_WEBHOOK_URL",
+            format!("{}/webhooks", merchant.internal_url()),
+        ));
+        self.start(
+            Self::service_image("old-api", 3000, "OLD ARCHITECTURE listening"),
+            "old-api",
+            3000,
+            &env,
+        )
+        .await
+    }
+
+    pub async fn api_service(&self, postgres: &Service) -> Result<Service> {
+        self.start(
+            Self::service_image("api-service", 3001, "NEW ARCHITECTURE API listening"),
+            "api-service",
+            3001,
+            &[
+                ("DATABASE_URL", database_url(&postgres.name)),
+                ("PORT", "3001".to_string()),
+            ],
+        )
+        .await
+    }
+
+    pub async fn data_service(&self, postgres: &Service) -> Result<Service> {
+        self.start(
+            Self::service_image("data-service", 3002, "DATA SERVICE listening"),
+            "data-service",
+            3002,
+            &[
+                ("DATABASE_URL", database_url(&postgres.name)),
+                ("PORT", "3002".to_string()),
+            ],
+        )
+        .await
+    }
+
+    /// POST /payments; the payment id, or None when the API refused or was
+    /// unreachable
+    pub async fn create_payment(&self, api_url: &str, merchant_id: &str) -> Option<Uuid> {
+        #[derive(Deserialize)]
+        struct Created {
+            id: Uuid,
+        }
+
+        let response = self
+            .http
+            .post(format!("{}/payments", api_url))
+            .json(&serde_json::json!({
+                "merchant_id": merchant_id,
+                "amount": 2500,
+                "currency": "USD",
+            }))
+            .send()
+            .await
+            .ok()?;
+        if response.status() != reqwest::StatusCode::CREATED {
+            return None;
+        }
+        response
+            .json::<Created>()
+            .await
+            .ok()
+            .map(|created| created.id)
+    }
+
+    pub async fn merchant_stats(&self, merchant: &Service) -> Result<MerchantStats> {
+        Ok(self
+            .http
+            .get(format!("{}/stats", merchant.url().await?))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    /// Abort old-api through POST /admin/crash (crash-injection images) and
+    /// wait for the container to go down
+    pub async fn crash(&self, old_api: &Service) -> Result<()> {
+        let url = old_api.url().await?;
+        self.http
+            .post(format!("{}/admin/crash?after_ms=0", url))
+            .send()
+            .await?
+            .error_for_status()?;
+        let http = &self.http;
+        let down = eventually(Duration::from_secs(10), || {
+            let url = url.clone();
+            async move {
+                http.get(format!("{}/health", url))
+                    .send()
+                    .await
+                    .is_err()
+                    .then_some(())
+            }
+        })
+        .await;
+        down.ok_or_else(|| format!("{} still up after the crash", old_api.name).into())
+    }
+
+    /// Start a stopped or crashed container again, and wait for /health
+    pub async fn restart(&self, service: &Service) -> Result<()> {
+        service.container.start().await?;
+        let url = service.url().await?;
+        let http = &self.http;
+        let up = eventually(Duration::from_secs(30), || {
+            let url = url.clone();
+            async move {
+                http.get(format!("{}/health", url))
+                    .send()
+                    .await
+                    .ok()
+                    .filter(|response| response.status().is_success())
+                    .map(|_| ())
+            }
+        })
+        .await;
+        up.ok_or_else(|| format!("{} didn't come back", service.name).into())
+    }
+
+    /// old-api's GET /stats
+    pub async fn old_api_stats(&self, old_api: &Service) -> Result<OldApiStats> {
+        Ok(self
+            .http
+            .get(format!("{}/stats", old_api.url().await?))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+}
+
+/// From the host, for assertions against the tables
+pub async fn connect(postgres: &Service) -> Result<sqlx::PgPool> {
+    let host = postgres.container.get_host().await?;
+    let port = postgres.container.get_host_port_ipv4(5432.tcp()).await?;
+    let url = format!(
+        "postgres://{}:{}@{}:{}/{}",
+        POSTGRES_USER, POSTGRES_PASSWORD, host, port, POSTGRES_DB
+    );
+    Ok(sqlx::PgPool::connect(&url).await?)
+}
+
+fn database_url(host: &str) -> String {
+    format!(
+        "postgres://{}:{}@{}:5432/{}",
+        POSTGRES_USER, POSTGRES_PASSWORD, host, POSTGRES_DB
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MerchantStats {
+    pub total_received: usize,
+    pub unique_payments: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OldApiStats {
+    pub queued: u64,
+    pub sent: u64,
+    pub pending: u64,
+}
+
+/// Poll `check` until it returns Some or `timeout` passes
+pub async fn eventually<T, F, Fut>(timeout: Duration, mut check: F) -> Option<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Option<T>>,
+{
+    let started = Instant::now();
+    loop {
+        if let Some(value) = check().await {
+            return Some(value);
+        }
+        if started.elapsed() > timeout {
+            return None;
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
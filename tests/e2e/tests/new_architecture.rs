@@ -0,0 +1,95 @@
+use e2e::{connect, Harness, Result};
+use std::time::Duration;
+use uuid::Uuid;
+
+// The new architecture's capture side: a payment and its event are written
+// in one transaction, so no crash can separate them. Run with
+// `cargo test -p e2e -- --ignored` after scripts/build-e2e-images.sh.
+
+const MERCHANT_ID: &str = "e2e-merchant";
+
+#[tokio::test]
+#[ignore = "needs Docker and the images from scripts/build-e2e-images.sh"]
+async fn api_crash_never_separates_a_payment_from_its_event() -> Result<()> {
+    let harness = Harness::new();
+    let postgres = harness.postgres().await?;
+    let api = harness.api_service(&postgres).await?;
+    let url = api.url().await?;
+
+    // Stop api-service while payments are being created
+    let creating = {
+        let harness = Harness::new();
+        let url = url.clone();
+        tokio::spawn(async move {
+            let mut created = Vec::new();
+            for _ in 0..200 {
+                if let Some(id) = harness.create_payment(&url, MERCHANT_ID).await {
+                    created.push(id);
+                }
+            }
+            created
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    api.container.stop().await?;
+    let created: Vec<Uuid> = creating.await?;
+    assert!(
+        !created.is_empty(),
+        "no payment was created before the stop"
+    );
+
+    let db = connect(&postgres).await?;
+    // Every payment, confirmed to the client or not, has exactly one event
+    let (payments, without_one_event): (i64, i64) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*),
+               COUNT(*) FILTER (WHERE (
+                   SELECT COUNT(*) FROM domain_events e WHERE e.object_id = p.id
+               ) <> 1)
+        FROM payments p
+        "#,
+    )
+    .fetch_one(&db)
+    .await?;
+    assert_eq!(without_one_event, 0, "of {} payments", payments);
+    assert!(payments as usize >= created.len());
+
+    let orphaned: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM domain_events e
+        WHERE e.event_type LIKE 'payment.%'
+          AND NOT EXISTS (SELECT 1 FROM payments p WHERE p.id = e.object_id)
+        "#,
+    )
+    .fetch_one(&db)
+    .await?;
+    assert_eq!(orphaned, 0, "events without a payment");
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs Docker and the images from scripts/build-e2e-images.sh"]
+async fn data_service_serves_each_new_payment() -> Result<()> {
+    let harness = Harness::new();
+    let postgres = harness.postgres().await?;
+    let api = harness.api_service(&postgres).await?;
+    let data = harness.data_service(&postgres).await?;
+    let (api_url, data_url) = (api.url().await?, data.url().await?);
+
+    for _ in 0..5 {
+        let id = harness
+            .create_payment(&api_url, MERCHANT_ID)
+            .await
+            .ok_or("api-service refused a payment")?;
+        let payload: serde_json::Value = harness
+            .http
+            .get(format!("{}/payload/{}", data_url, id))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        assert_eq!(payload["id"], id.to_string(), "{}", payload);
+    }
+    Ok(())
+}
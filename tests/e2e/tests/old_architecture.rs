@@ -0,0 +1,113 @@
+use e2e::{eventually, Harness, Result, Service};
+use std::time::Duration;
+
+// The old architecture's delivery, without and with a crash. Run with
+// `cargo test -p e2e -- --ignored` after scripts/build-e2e-images.sh.
+
+const MERCHANT_ID: &str = "e2e-merchant";
+
+async fn create_payments(harness: &Harness, api: &Service, count: usize) -> Result<usize> {
+    let url = api.url().await?;
+    let mut created = 0;
+    for _ in 0..count {
+        if harness.create_payment(&url, MERCHANT_ID).await.is_some() {
+            created += 1;
+        }
+    }
+    Ok(created)
+}
+
+/// Unique payments the merchant has seen once the count stops changing
+async fn settled_deliveries(harness: &Harness, merchant: &Service) -> Result<usize> {
+    let mut last = usize::MAX;
+    loop {
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        let received = harness.merchant_stats(merchant).await?.unique_payments;
+        if received == last {
+            return Ok(received);
+        }
+        last = received;
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs Docker and the images from scripts/build-e2e-images.sh"]
+async fn delivers_every_payment_without_faults() -> Result<()> {
+    let harness = Harness::new();
+    let merchant = harness.merchant().await?;
+    let old_api = harness.old_api(&merchant, &[]).await?;
+
+    let created = create_payments(&harness, &old_api, 50).await?;
+    assert_eq!(created, 50, "old-api refused payments");
+
+    let (harness, merchant) = (&harness, &merchant);
+    let delivered = eventually(Duration::from_secs(30), || async move {
+        let stats = harness.merchant_stats(merchant).await.ok()?;
+        (stats.unique_payments == created).then_some(stats)
+    })
+    .await;
+    assert!(delivered.is_some(), "not every webhook arrived");
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs Docker and the images from scripts/build-e2e-images.sh"]
+async fn crash_loses_the_webhooks_held_in_memory() -> Result<()> {
+    let harness = Harness::new();
+    let merchant = harness.merchant().await?;
+    let old_api = harness.old_api(&merchant, &[]).await?;
+
+    // One worker sends about one webhook per 10ms, so creating payments back
+    // to back leaves a backlog in memory
+    let created = create_payments(&harness, &old_api, 200).await?;
+    let before = harness.old_api_stats(&old_api).await?;
+    assert!(
+        before.pending > 0,
+        "backlog drained before the crash: {:?}",
+        before
+    );
+
+    harness.crash(&old_api).await?;
+    harness.restart(&old_api).await?;
+
+    let delivered = settled_deliveries(&harness, &merchant).await?;
+    assert!(
+        delivered < created,
+        "expected losses: {} of {} delivered, {} pending at the crash",
+        delivered,
+        created,
+        before.pending
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs Docker and the images from scripts/build-e2e-images.sh"]
+async fn queue_file_replays_webhooks_after_a_crash() -> Result<()> {
+    let harness = Harness::new();
+    let merchant = harness.merchant().await?;
+    let old_api = harness
+        .old_api(
+            &merchant,
+            &[("QUEUE_FILE", "/tmp/webhook-queue.jsonl".to_string())],
+        )
+        .await?;
+
+    let created = create_payments(&harness, &old_api, 200).await?;
+    let before = harness.old_api_stats(&old_api).await?;
+    assert!(
+        before.pending > 0,
+        "backlog drained before the crash: {:?}",
+        before
+    );
+
+    // The container keeps its filesystem across the restart, like a pod
+    // with a local volume
+    harness.crash(&old_api).await?;
+    harness.restart(&old_api).await?;
+
+    // At least once: a webhook sent but not yet marked done goes out twice
+    let delivered = settled_deliveries(&harness, &merchant).await?;
+    assert_eq!(delivered, created, "webhooks lost despite QUEUE_FILE");
+    Ok(())
+}
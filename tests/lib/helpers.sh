@@ -70,6 +70,38 @@ parse_unique_payments() {
     echo "$stats" | grep -o '"unique_payments":[0-9]*' | grep -o '[0-9]*'
 }
 
+# Parse webhooks still in memory from old-api's /stats JSON
+# Usage: parse_pending <stats-json>
+parse_pending() {
+    local stats=$1
+    echo "$stats" | grep -o '"pending":[0-9]*' | grep -o '[0-9]*'
+}
+
+# Crash old-api through POST /admin/crash (built with the crash-injection
+# feature) and restart it after delay; falls back to docker kill without it
+# Usage: crash_and_restart_service <service-url> <service-name> <delay-seconds>
+crash_and_restart_service() {
+    local url=$1
+    local service=$2
+    local delay=$3
+
+    local status
+    status=$(curl -s -X POST "$url/admin/crash?after_ms=0" -o /dev/null -w "%{http_code}")
+    if [ "$status" != "202" ]; then
+        echo "[CHAOS] $url/admin/crash unavailable ($status), using docker kill"
+        kill_and_restart_service "$service" "$delay"
+        return
+    fi
+
+    echo "[CHAOS] $service crashed on request. Waiting ${delay}s before restart..."
+    sleep "$delay"
+
+    echo "[CHAOS] Restarting service: $service"
+    docker start "$service" >/dev/null
+    sleep 1  # Give it a moment to fully start
+    echo "[CHAOS] Service restarted."
+}
+
 # Kill a Docker service and restart after delay
 # Usage: kill_and_restart_service <service-name> <delay-seconds>
 kill_and_restart_service() {
@@ -0,0 +1,151 @@
+use bollard::container::{
+    KillContainerOptions, RestartContainerOptions, StartContainerOptions, StopContainerOptions,
+};
+use bollard::Docker;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+use crate::scenario::Action;
+
+// ==============================================================================
+// ACTIONS: Carrying out one step
+// ==============================================================================
+//
+// Container steps go to the Docker Engine API (bollard, honouring DOCKER_HOST),
+// merchant chaos to merchant-simulator's /chaos, health waits are plain GETs.
+// Docker is only connected to when a scenario has a container step.
+
+pub struct Executor {
+    docker: Option<Docker>,
+    http: reqwest::Client,
+}
+
+impl Executor {
+    pub fn new(needs_docker: bool) -> Result<Self, String> {
+        let docker = if needs_docker {
+            Some(
+                Docker::connect_with_local_defaults()
+                    .map_err(|e| format!("Failed to connect to Docker: {}", e))?,
+            )
+        } else {
+            None
+        };
+        Ok(Executor {
+            docker,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(5))
+                .build()
+                .map_err(|e| format!("Failed to build HTTP client: {}", e))?,
+        })
+    }
+
+    fn docker(&self) -> &Docker {
+        self.docker
+            .as_ref()
+            .expect("connected for scenarios with container steps")
+    }
+
+    pub async fn run(&self, action: &Action) -> Result<(), String> {
+        match action {
+            Action::Kill { container, signal } => self
+                .docker()
+                .kill_container(
+                    container,
+                    Some(KillContainerOptions {
+                        signal: signal.as_str(),
+                    }),
+                )
+                .await
+                .map_err(|e| e.to_string()),
+            Action::Stop {
+                container,
+                grace_secs,
+            } => self
+                .docker()
+                .stop_container(container, Some(StopContainerOptions { t: *grace_secs }))
+                .await
+                .map_err(|e| e.to_string()),
+            Action::Start { container } => self
+                .docker()
+                .start_container(container, None::<StartContainerOptions<String>>)
+                .await
+                .map_err(|e| e.to_string()),
+            Action::Restart { container } => self
+                .docker()
+                .restart_container(container, Some(RestartContainerOptions { t: 10 }))
+                .await
+                .map_err(|e| e.to_string()),
+            Action::Pause { container } => self
+                .docker()
+                .pause_container(container)
+                .await
+                .map_err(|e| e.to_string()),
+            Action::Unpause { container } => self
+                .docker()
+                .unpause_container(container)
+                .await
+                .map_err(|e| e.to_string()),
+            Action::MerchantChaos {
+                url,
+                failure_rate,
+                status,
+                delay_ms,
+            } => {
+                let body = json!({
+                    "failure_rate": failure_rate,
+                    "status": status,
+                    "delay_ms": delay_ms,
+                });
+                self.send(self.http.put(format!("{}/chaos", url)).json(&body))
+                    .await
+            }
+            Action::MerchantCalm { url } => {
+                self.send(self.http.delete(format!("{}/chaos", url))).await
+            }
+            Action::WaitHealthy { url, timeout_secs } => {
+                self.wait_healthy(url, Duration::from_secs(*timeout_secs))
+                    .await
+            }
+            Action::Annotate { .. } => Ok(()),
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<(), String> {
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("{} {}", status, body));
+        }
+        Ok(())
+    }
+
+    async fn wait_healthy(&self, url: &str, timeout: Duration) -> Result<(), String> {
+        let started = Instant::now();
+        loop {
+            let last = match self.http.get(url).send().await {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => response.status().to_string(),
+                Err(e) => e.to_string(),
+            };
+            if started.elapsed() > timeout {
+                return Err(format!("not healthy after {:?}: {}", timeout, last));
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+}
+
+impl Action {
+    pub fn needs_docker(&self) -> bool {
+        matches!(
+            self,
+            Action::Kill { .. }
+                | Action::Stop { .. }
+                | Action::Start { .. }
+                | Action::Restart { .. }
+                | Action::Pause { .. }
+                | Action::Unpause { .. }
+        )
+    }
+}
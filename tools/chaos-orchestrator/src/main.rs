@@ -0,0 +1,162 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::time::Instant;
+
+mod actions;
+mod scenario;
+mod timeline;
+
+use actions::Executor;
+use scenario::Scenario;
+use timeline::Timeline;
+
+// ==============================================================================
+// CHAOS ORCHESTRATOR: Resilience demos as repeatable scenario files
+// ==============================================================================
+//
+// Runs a scenario (scenario.rs) against the docker compose stack: kills,
+// stops, restarts and pauses containers, turns merchant-simulator failures on
+// and off, and waits for services to come back, each at a fixed offset, and
+// records when everything happened (timeline.rs).
+//
+//   cargo run -p chaos-orchestrator -- tools/chaos-orchestrator/scenarios/old-api-crash.toml
+//   cargo run -p chaos-orchestrator -- <scenario> --dry-run
+//   cargo run -p chaos-orchestrator -- <scenario> --timeline results/run.json
+//
+// Start the load (tools/load-generator) alongside; the scenario only injects
+// faults. Exits with 1 if a step failed or the run was interrupted.
+
+struct Args {
+    scenario: PathBuf,
+    timeline: Option<PathBuf>,
+    dry_run: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut scenario = None;
+    let mut timeline = None;
+    let mut dry_run = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "--timeline" => {
+                timeline = Some(PathBuf::from(args.next().ok_or("--timeline needs a file")?))
+            }
+            _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+            _ if scenario.is_none() => scenario = Some(PathBuf::from(arg)),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+    Ok(Args {
+        scenario: scenario.ok_or("No scenario file given")?,
+        timeline,
+        dry_run,
+    })
+}
+
+#[tokio::main]
+async fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        eprintln!("Usage: chaos-orchestrator <scenario.toml> [--timeline <file>] [--dry-run]");
+        std::process::exit(2);
+    });
+    let scenario = Scenario::load(&args.scenario).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+
+    println!("Scenario: {}", scenario.name);
+    if let Some(description) = &scenario.description {
+        println!("{}", description);
+    }
+    println!();
+
+    if args.dry_run {
+        for step in &scenario.steps {
+            println!("[{:>8.1}s] step    {}", step.at_secs, step.action);
+        }
+        for action in &scenario.cleanup {
+            println!("[     end] cleanup {}", action);
+        }
+        return;
+    }
+
+    let needs_docker = scenario
+        .steps
+        .iter()
+        .map(|step| &step.action)
+        .chain(&scenario.cleanup)
+        .any(|action| action.needs_docker());
+    let executor = Executor::new(needs_docker).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let mut timeline = Timeline::new(&scenario.name);
+    let started = Instant::now();
+
+    let interrupted = tokio::select! {
+        _ = run_steps(&scenario, &executor, &mut timeline, started) => false,
+        _ = tokio::signal::ctrl_c() => true,
+    };
+    if interrupted {
+        println!("Interrupted, cleaning up");
+        timeline.interrupted();
+    }
+
+    for action in &scenario.cleanup {
+        let began = Instant::now();
+        let result = executor.run(action).await;
+        timeline.record(
+            "cleanup",
+            action,
+            began - started,
+            began.elapsed(),
+            result.err(),
+        );
+    }
+
+    let path = args.timeline.unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "results/chaos-{}.json",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ))
+    });
+    match timeline.write(&path) {
+        Ok(()) => println!("\nTimeline written to {}", path.display()),
+        Err(e) => eprintln!("\n{}", e),
+    }
+
+    if timeline.failed() {
+        std::process::exit(1);
+    }
+}
+
+/// Run the steps on schedule; stops at the first failure unless the scenario
+/// says to continue
+async fn run_steps(
+    scenario: &Scenario,
+    executor: &Executor,
+    timeline: &mut Timeline,
+    started: Instant,
+) {
+    for step in &scenario.steps {
+        tokio::time::sleep_until(started + Duration::from_secs_f64(step.at_secs)).await;
+        let began = Instant::now();
+        let result = executor.run(&step.action).await;
+        let failed = result.is_err();
+        timeline.record(
+            "step",
+            &step.action,
+            began - started,
+            began.elapsed(),
+            result.err(),
+        );
+        if failed && !scenario.continue_on_error {
+            println!("Step failed, skipping to cleanup");
+            return;
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+// ==============================================================================
+// SCENARIO: A scripted sequence of faults, read from TOML
+// ==============================================================================
+//
+//   name = "old-api crash under load"
+//
+//   [[steps]]
+//   at_secs = 5
+//   action = "kill"
+//   container = "old-api"
+//
+//   [[cleanup]]
+//   action = "start"
+//   container = "old-api"
+//
+// Steps run in at_secs order, each at its offset from the start (or right
+// after the previous one, if that took longer). Cleanup steps run at the end
+// whatever happened, also after a failed step or Ctrl-C, so a scenario never
+// leaves Kafka paused or a merchant failing. Containers are named as in
+// docker-compose.yml (container_name).
+
+#[derive(Debug, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub steps: Vec<Step>,
+    #[serde(default)]
+    pub cleanup: Vec<Action>,
+    /// Keep going after a failed step instead of skipping to cleanup
+    #[serde(default)]
+    pub continue_on_error: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Step {
+    #[serde(default)]
+    pub at_secs: f64,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// docker kill: SIGKILL unless another signal is given
+    Kill {
+        container: String,
+        #[serde(default = "sigkill")]
+        signal: String,
+    },
+    /// docker stop: SIGTERM, then SIGKILL after grace_secs
+    Stop {
+        container: String,
+        #[serde(default = "default_grace_secs")]
+        grace_secs: i64,
+    },
+    Start {
+        container: String,
+    },
+    Restart {
+        container: String,
+    },
+    /// docker pause: the processes freeze, connections stay open (e.g. Kafka
+    /// stops answering without its clients seeing a disconnect)
+    Pause {
+        container: String,
+    },
+    Unpause {
+        container: String,
+    },
+    /// merchant-simulator's PUT /chaos
+    MerchantChaos {
+        url: String,
+        #[serde(default)]
+        failure_rate: f64,
+        #[serde(default = "default_failure_status")]
+        status: u16,
+        #[serde(default)]
+        delay_ms: u64,
+    },
+    /// merchant-simulator's DELETE /chaos
+    MerchantCalm {
+        url: String,
+    },
+    /// Poll until the URL answers 2xx
+    WaitHealthy {
+        url: String,
+        #[serde(default = "default_wait_secs")]
+        timeout_secs: u64,
+    },
+    /// Just a mark on the timeline
+    Annotate {
+        text: String,
+    },
+}
+
+fn sigkill() -> String {
+    "SIGKILL".to_string()
+}
+
+fn default_grace_secs() -> i64 {
+    10
+}
+
+fn default_failure_status() -> u16 {
+    500
+}
+
+fn default_wait_secs() -> u64 {
+    60
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Kill { container, signal } => write!(f, "kill {} ({})", container, signal),
+            Action::Stop {
+                container,
+                grace_secs,
+            } => write!(f, "stop {} ({}s grace)", container, grace_secs),
+            Action::Start { container } => write!(f, "start {}", container),
+            Action::Restart { container } => write!(f, "restart {}", container),
+            Action::Pause { container } => write!(f, "pause {}", container),
+            Action::Unpause { container } => write!(f, "unpause {}", container),
+            Action::MerchantChaos {
+                url,
+                failure_rate,
+                status,
+                delay_ms,
+            } => write!(
+                f,
+                "merchant chaos at {}: {:.0}% fail with {}, {}ms delay",
+                url,
+                failure_rate * 100.0,
+                status,
+                delay_ms
+            ),
+            Action::MerchantCalm { url } => write!(f, "merchant chaos off at {}", url),
+            Action::WaitHealthy { url, timeout_secs } => {
+                write!(f, "wait for {} (up to {}s)", url, timeout_secs)
+            }
+            Action::Annotate { text } => write!(f, "note: {}", text),
+        }
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut scenario: Scenario =
+            toml::from_str(&text).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+
+        let mut problems = Vec::new();
+        for (i, step) in scenario.steps.iter().enumerate() {
+            if !step.at_secs.is_finite() || step.at_secs < 0.0 {
+                problems.push(format!("steps[{}]: at_secs must be 0 or more", i));
+            }
+        }
+        let actions = scenario
+            .steps
+            .iter()
+            .map(|step| &step.action)
+            .chain(&scenario.cleanup);
+        for action in actions {
+            if let Action::MerchantChaos { failure_rate, .. } = action {
+                if !(0.0..=1.0).contains(failure_rate) {
+                    problems.push(format!("{}: failure_rate must be between 0 and 1", action));
+                }
+            }
+        }
+        if !problems.is_empty() {
+            return Err(format!(
+                "Invalid {}:\n  - {}",
+                path.display(),
+                problems.join("\n  - ")
+            ));
+        }
+
+        scenario
+            .steps
+            .sort_by(|a, b| a.at_secs.total_cmp(&b.at_secs));
+        Ok(scenario)
+    }
+}
@@ -0,0 +1,97 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::scenario::Action;
+
+// ==============================================================================
+// TIMELINE: What was done when, to line up with the results
+// ==============================================================================
+//
+// Every step and cleanup step is printed as it finishes and kept with its wall
+// clock time, so the moments of a kill or a pause can be matched against the
+// services' JSON logs, sla-monitor's measurements or a Grafana panel. The
+// whole run is written out as JSON at the end.
+
+#[derive(Serialize)]
+pub struct Timeline {
+    scenario: String,
+    started_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+    /// Stopped early with Ctrl-C
+    interrupted: bool,
+    entries: Vec<Entry>,
+}
+
+#[derive(Serialize)]
+struct Entry {
+    /// When the action began, since the start of the scenario
+    offset_ms: u128,
+    at: DateTime<Utc>,
+    phase: &'static str,
+    #[serde(flatten)]
+    action: Action,
+    took_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Timeline {
+    pub fn new(scenario: &str) -> Self {
+        Timeline {
+            scenario: scenario.to_string(),
+            started_at: Utc::now(),
+            finished_at: None,
+            interrupted: false,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn record(
+        &mut self,
+        phase: &'static str,
+        action: &Action,
+        offset: Duration,
+        took: Duration,
+        error: Option<String>,
+    ) {
+        let outcome = match &error {
+            Some(e) => format!("FAILED: {}", e),
+            None => "ok".to_string(),
+        };
+        println!(
+            "[{:>8.1}s] {:<7} {} ... {}",
+            offset.as_secs_f64(),
+            phase,
+            action,
+            outcome
+        );
+        self.entries.push(Entry {
+            offset_ms: offset.as_millis(),
+            at: Utc::now() - chrono::Duration::from_std(took).unwrap_or_default(),
+            phase,
+            action: action.clone(),
+            took_ms: took.as_millis(),
+            error,
+        });
+    }
+
+    pub fn interrupted(&mut self) {
+        self.interrupted = true;
+    }
+
+    pub fn failed(&self) -> bool {
+        self.interrupted || self.entries.iter().any(|entry| entry.error.is_some())
+    }
+
+    pub fn write(&mut self, path: &Path) -> Result<(), String> {
+        self.finished_at = Some(Utc::now());
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_vec_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
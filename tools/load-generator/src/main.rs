@@ -0,0 +1,126 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::time::{Instant, MissedTickBehavior};
+
+mod payload;
+mod profile;
+mod report;
+mod settings;
+
+use payload::PaymentMix;
+use profile::Profile;
+use report::{Outcome, Stats};
+use settings::Settings;
+
+// ==============================================================================
+// LOAD GENERATOR: The same payment load against either architecture
+// ==============================================================================
+//
+// Creates payments through POST /payments at the rate the profile asks for
+// (profile.rs), with merchants, amounts and currencies drawn from the
+// configured mix (payload.rs), and reports what the API achieved (report.rs):
+// a progress line every few seconds, then throughput, error rates by cause and
+// latency percentiles. Run it with the same profile against old-api and
+// api-service to compare them:
+//
+//   TARGET_URL=http://localhost:3000 LABEL=old cargo run -p load-generator
+//   TARGET_URL=http://localhost:3001 LABEL=new cargo run -p load-generator
+//   CONFIG_FILE=tools/load-generator/profiles/spike.toml cargo run -p load-generator
+//
+// Exits with 1 if no payment was created at all.
+
+/// How often the scheduler wakes up to send what is due
+const TICK: Duration = Duration::from_millis(5);
+
+#[tokio::main]
+async fn main() {
+    let settings: Settings = config::load_or_exit("load-generator");
+    let profile = Profile::from_settings(&settings);
+    let mix = PaymentMix::from_settings(&settings);
+    let stats = Arc::new(Stats::default());
+    let in_flight = Arc::new(Semaphore::new(settings.max_in_flight));
+    let http = reqwest::Client::builder()
+        .timeout(Duration::from_millis(settings.request_timeout_ms))
+        .pool_max_idle_per_host(settings.max_in_flight)
+        .build()
+        .expect("Failed to build HTTP client");
+    let url = format!("{}/payments", settings.target_url.trim_end_matches('/'));
+
+    println!(
+        "Load against {}: {:.0} payments over {}s",
+        url,
+        profile.expected(),
+        profile.duration().as_secs()
+    );
+
+    let mut rng = StdRng::from_entropy();
+    let mut ticker = tokio::time::interval(TICK);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let report_every = Duration::from_secs(settings.report_interval_secs);
+    let started = Instant::now();
+    let mut last_tick = started;
+    let mut last_report = started;
+    // Payments owed so far; the fraction carries over to the next tick
+    let mut due = 0.0;
+
+    loop {
+        ticker.tick().await;
+        let now = Instant::now();
+        let elapsed = now - started;
+        let Some(rate) = profile.rate_at(elapsed) else {
+            break;
+        };
+        due += rate * (now - last_tick).as_secs_f64();
+        last_tick = now;
+
+        while due >= 1.0 {
+            due -= 1.0;
+            let Ok(permit) = in_flight.clone().try_acquire_owned() else {
+                stats.skipped();
+                continue;
+            };
+            stats.sent();
+            let request = http.post(&url).json(&mix.next(&mut rng));
+            let stats = stats.clone();
+            tokio::spawn(async move {
+                let sent_at = Instant::now();
+                let outcome = match request.send().await {
+                    Ok(response) => Outcome::Status(response.status().as_u16()),
+                    Err(e) if e.is_timeout() => Outcome::Error("timeout"),
+                    Err(e) if e.is_connect() => Outcome::Error("connect"),
+                    Err(_) => Outcome::Error("other"),
+                };
+                stats.finished(outcome, sent_at.elapsed());
+                drop(permit);
+            });
+        }
+
+        if now - last_report >= report_every {
+            println!("{}", stats.progress_line(elapsed, now - last_report, rate));
+            last_report = now;
+        }
+    }
+
+    // Let the requests still in flight finish (or time out)
+    let _ = in_flight
+        .acquire_many(settings.max_in_flight as u32)
+        .await
+        .expect("semaphore is never closed");
+    let report = stats.report(settings.label(), started.elapsed(), profile.expected());
+    report.print();
+
+    if let Some(path) = &settings.report_file {
+        let json = serde_json::to_vec_pretty(&report).expect("report serializes");
+        match std::fs::write(path, json) {
+            Ok(()) => println!("Report written to {}", path.display()),
+            Err(e) => eprintln!("Failed to write {}: {}", path.display(), e),
+        }
+    }
+
+    if report.succeeded == 0 {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,56 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use serde_json::{json, Value};
+
+use crate::settings::Settings;
+
+// ==============================================================================
+// PAYLOADS: The POST /payments bodies, drawn from the configured mix
+// ==============================================================================
+//
+// Merchant ids are names (load-merchant-<n>): api-service maps them to stable
+// UUIDs, old-api ignores them. Zipf-skewed merchants concentrate the load the
+// way a few large merchants do, which is what per-merchant ordering and quotas
+// in the new architecture react to.
+
+pub struct PaymentMix {
+    merchants: WeightedIndex<f64>,
+    amount_min: i64,
+    amount_max: i64,
+    currencies: Vec<String>,
+    async_settlement_ratio: f64,
+    test_mode_ratio: f64,
+}
+
+impl PaymentMix {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let weights =
+            (1..=settings.merchants).map(|rank| 1.0 / (rank as f64).powf(settings.merchant_skew));
+        PaymentMix {
+            merchants: WeightedIndex::new(weights).expect("merchants validated to be at least 1"),
+            amount_min: settings.amount_min,
+            amount_max: settings.amount_max,
+            currencies: settings.currencies.clone(),
+            async_settlement_ratio: settings.async_settlement_ratio,
+            test_mode_ratio: settings.test_mode_ratio,
+        }
+    }
+
+    pub fn next(&self, rng: &mut impl Rng) -> Value {
+        let merchant = self.merchants.sample(rng) + 1;
+        let currency = &self.currencies[rng.gen_range(0..self.currencies.len())];
+        let mut body = json!({
+            "merchant_id": format!("load-merchant-{}", merchant),
+            "amount": rng.gen_range(self.amount_min..=self.amount_max),
+            "currency": currency,
+        });
+        // Only sent when asked for, so old-api sees the body it always did
+        if self.async_settlement_ratio > 0.0 {
+            body["async_settlement"] = rng.gen_bool(self.async_settlement_ratio).into();
+        }
+        if self.test_mode_ratio > 0.0 && rng.gen_bool(self.test_mode_ratio) {
+            body["mode"] = "test".into();
+        }
+        body
+    }
+}
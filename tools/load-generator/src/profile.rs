@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use crate::settings::{Ramp, Settings};
+
+// ==============================================================================
+// PROFILE: The target rate over time
+// ==============================================================================
+//
+// Every ramp is turned into a list of segments, each moving linearly from one
+// rate to another (or holding one). The scheduler asks for the rate at the
+// current time and sends that many payments per second, whatever the API's
+// response times: an open model, so a slow API shows up as latency and
+// skipped requests instead of quietly lowering the load.
+
+struct Segment {
+    duration: Duration,
+    from: f64,
+    to: f64,
+}
+
+pub struct Profile {
+    segments: Vec<Segment>,
+}
+
+impl Profile {
+    pub fn from_settings(settings: &Settings) -> Self {
+        if !settings.stages.is_empty() {
+            return Profile {
+                segments: settings
+                    .stages
+                    .iter()
+                    .map(|stage| Segment {
+                        duration: Duration::from_secs(stage.duration_secs),
+                        from: stage.rps,
+                        to: stage.ramp_to.unwrap_or(stage.rps),
+                    })
+                    .collect(),
+            };
+        }
+
+        let target = settings.target_rps;
+        let ramp = Duration::from_secs(settings.ramp_secs);
+        let hold = Duration::from_secs(settings.duration_secs) - ramp;
+        let mut segments = Vec::new();
+        match settings.ramp {
+            _ if ramp.is_zero() => {}
+            Ramp::Constant => segments.push(Segment {
+                duration: ramp,
+                from: target,
+                to: target,
+            }),
+            Ramp::Linear => segments.push(Segment {
+                duration: ramp,
+                from: 0.0,
+                to: target,
+            }),
+            Ramp::Step => {
+                let steps = settings.ramp_steps;
+                for step in 1..=steps {
+                    let rate = target * step as f64 / steps as f64;
+                    segments.push(Segment {
+                        duration: ramp / steps,
+                        from: rate,
+                        to: rate,
+                    });
+                }
+            }
+        }
+        segments.push(Segment {
+            duration: hold,
+            from: target,
+            to: target,
+        });
+        Profile { segments }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.segments.iter().map(|segment| segment.duration).sum()
+    }
+
+    /// Payments per second at `elapsed`; None once the profile is over
+    pub fn rate_at(&self, elapsed: Duration) -> Option<f64> {
+        let mut start = Duration::ZERO;
+        for segment in &self.segments {
+            let end = start + segment.duration;
+            if elapsed < end {
+                let progress = (elapsed - start).as_secs_f64() / segment.duration.as_secs_f64();
+                return Some(segment.from + (segment.to - segment.from) * progress);
+            }
+            start = end;
+        }
+        None
+    }
+
+    /// Payments the profile asks for in total
+    pub fn expected(&self) -> f64 {
+        self.segments
+            .iter()
+            .map(|segment| (segment.from + segment.to) / 2.0 * segment.duration.as_secs_f64())
+            .sum()
+    }
+}
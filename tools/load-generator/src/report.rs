@@ -0,0 +1,213 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// ==============================================================================
+// REPORT: What was sent, what came back, and how fast
+// ==============================================================================
+//
+// Every request ends as a status code or a client error (timeout, connect,
+// other); requests the scheduler couldn't send because max_in_flight were
+// already waiting are skipped. Only 201 Created counts as a success: 503 from
+// old-api's full queue and 402/429 from api-service's quotas are the API
+// pushing back, and an error rate that hides them would flatter it.
+//
+// Latencies are kept whole (one u32 per request) so the percentiles are exact.
+
+#[derive(Default)]
+struct Counts {
+    sent: u64,
+    succeeded: u64,
+    skipped: u64,
+    statuses: BTreeMap<u16, u64>,
+    errors: BTreeMap<&'static str, u64>,
+}
+
+impl Counts {
+    fn failed(&self) -> u64 {
+        self.statuses
+            .iter()
+            .filter(|(status, _)| **status != 201)
+            .map(|(_, count)| count)
+            .sum::<u64>()
+            + self.errors.values().sum::<u64>()
+    }
+}
+
+#[derive(Default)]
+struct Recorded {
+    total: Counts,
+    /// Since the last progress line
+    interval: Counts,
+    latencies_us: Vec<u32>,
+}
+
+#[derive(Default)]
+pub struct Stats {
+    recorded: Mutex<Recorded>,
+}
+
+pub enum Outcome {
+    Status(u16),
+    Error(&'static str),
+}
+
+impl Stats {
+    pub fn sent(&self) {
+        let mut recorded = self.recorded.lock().unwrap();
+        recorded.total.sent += 1;
+        recorded.interval.sent += 1;
+    }
+
+    pub fn skipped(&self) {
+        let mut recorded = self.recorded.lock().unwrap();
+        recorded.total.skipped += 1;
+        recorded.interval.skipped += 1;
+    }
+
+    pub fn finished(&self, outcome: Outcome, latency: Duration) {
+        let mut recorded = self.recorded.lock().unwrap();
+        let recorded = &mut *recorded;
+        for counts in [&mut recorded.total, &mut recorded.interval] {
+            match outcome {
+                Outcome::Status(status) => {
+                    *counts.statuses.entry(status).or_default() += 1;
+                    if status == 201 {
+                        counts.succeeded += 1;
+                    }
+                }
+                Outcome::Error(kind) => *counts.errors.entry(kind).or_default() += 1,
+            }
+        }
+        recorded
+            .latencies_us
+            .push(latency.as_micros().min(u32::MAX as u128) as u32);
+    }
+
+    /// One line for the interval that just ended, and start the next one
+    pub fn progress_line(&self, elapsed: Duration, interval: Duration, target_rps: f64) -> String {
+        let mut recorded = self.recorded.lock().unwrap();
+        let counts = std::mem::take(&mut recorded.interval);
+        let secs = interval.as_secs_f64();
+        format!(
+            "[{:>5}s] target {:>8.1}/s | sent {:>8.1}/s | ok {:>8.1}/s | failed {:>6} | skipped {:>6}",
+            elapsed.as_secs(),
+            target_rps,
+            counts.sent as f64 / secs,
+            counts.succeeded as f64 / secs,
+            counts.failed(),
+            counts.skipped,
+        )
+    }
+
+    pub fn report(&self, label: &str, elapsed: Duration, expected: f64) -> Report {
+        let mut recorded = self.recorded.lock().unwrap();
+        recorded.latencies_us.sort_unstable();
+        let latencies = &recorded.latencies_us;
+        let percentile = |p: f64| -> f64 {
+            if latencies.is_empty() {
+                return 0.0;
+            }
+            let rank = ((p / 100.0) * latencies.len() as f64).ceil() as usize;
+            latencies[rank.clamp(1, latencies.len()) - 1] as f64 / 1000.0
+        };
+        let total = &recorded.total;
+        let finished = total.succeeded + total.failed();
+        let secs = elapsed.as_secs_f64();
+        Report {
+            label: label.to_string(),
+            duration_secs: secs,
+            expected: expected.round() as u64,
+            sent: total.sent,
+            skipped: total.skipped,
+            succeeded: total.succeeded,
+            failed: total.failed(),
+            statuses: total
+                .statuses
+                .iter()
+                .map(|(status, count)| (status.to_string(), *count))
+                .collect(),
+            errors: total
+                .errors
+                .iter()
+                .map(|(kind, count)| (kind.to_string(), *count))
+                .collect(),
+            throughput_rps: total.succeeded as f64 / secs,
+            error_rate: if finished == 0 {
+                0.0
+            } else {
+                total.failed() as f64 / finished as f64
+            },
+            latency_ms: Latency {
+                p50: percentile(50.0),
+                p90: percentile(90.0),
+                p99: percentile(99.0),
+                p999: percentile(99.9),
+                max: percentile(100.0),
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    pub label: String,
+    pub duration_secs: f64,
+    /// Payments the profile asked for
+    pub expected: u64,
+    pub sent: u64,
+    pub skipped: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    pub statuses: BTreeMap<String, u64>,
+    pub errors: BTreeMap<String, u64>,
+    /// Payments created per second
+    pub throughput_rps: f64,
+    /// Failed over finished requests (skipped ones never started)
+    pub error_rate: f64,
+    pub latency_ms: Latency,
+}
+
+#[derive(Serialize)]
+pub struct Latency {
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub p999: f64,
+    pub max: f64,
+}
+
+impl Report {
+    pub fn print(&self) {
+        println!();
+        println!("════════════════════════════════════════════════════════════════");
+        println!("LOAD REPORT: {}", self.label);
+        println!("════════════════════════════════════════════════════════════════");
+        println!("Duration:    {:.1}s", self.duration_secs);
+        println!(
+            "Requests:    {} expected, {} sent, {} skipped (max_in_flight reached)",
+            self.expected, self.sent, self.skipped
+        );
+        println!(
+            "Created:     {} ({:.1} payments/s)",
+            self.succeeded, self.throughput_rps
+        );
+        println!(
+            "Failed:      {} ({:.2}% of finished requests)",
+            self.failed,
+            self.error_rate * 100.0
+        );
+        for (status, count) in &self.statuses {
+            println!("  HTTP {}:   {}", status, count);
+        }
+        for (kind, count) in &self.errors {
+            println!("  {}: {}", kind, count);
+        }
+        let latency = &self.latency_ms;
+        println!(
+            "Latency:     p50 {:.1}ms | p90 {:.1}ms | p99 {:.1}ms | p99.9 {:.1}ms | max {:.1}ms",
+            latency.p50, latency.p90, latency.p99, latency.p999, latency.max
+        );
+    }
+}
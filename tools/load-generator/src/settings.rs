@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// ==============================================================================
+// SETTINGS: What to send, how fast and for how long
+// ==============================================================================
+//
+// Loaded through the shared config crate like the services' settings:
+// defaults below, then config/load-generator.toml (or CONFIG_FILE, e.g. one
+// of profiles/*.toml), then TARGET_URL, TARGET_RPS, DURATION_SECS, ... from
+// the environment. Lists (currencies, stages) only come from the file.
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Base URL of the payments API: api-service (3001) or old-api (3000),
+    /// both take the same POST /payments
+    pub target_url: String,
+    /// Name for the report, e.g. "old" or "new"; defaults to the URL
+    pub label: Option<String>,
+
+    /// Rate reached after the ramp, in payments per second
+    pub target_rps: f64,
+    pub duration_secs: u64,
+    pub ramp: Ramp,
+    /// How long the ramp takes to reach target_rps (linear and step)
+    pub ramp_secs: u64,
+    /// Number of equal steps (step)
+    pub ramp_steps: u32,
+    /// Replaces the four settings above when set: run each stage in turn
+    pub stages: Vec<Stage>,
+
+    /// Requests waiting for a response at once; a request due while all are
+    /// busy is skipped and counted, rather than sent late
+    pub max_in_flight: usize,
+    pub request_timeout_ms: u64,
+
+    /// Payments are spread over this many merchants (load-merchant-<n>)
+    pub merchants: usize,
+    /// Zipf exponent of the spread: 0 is uniform, 1 makes the first merchant
+    /// get about as much as the next few together
+    pub merchant_skew: f64,
+    /// Amounts are uniform in [amount_min, amount_max], in minor units
+    pub amount_min: i64,
+    pub amount_max: i64,
+    /// Picked uniformly; list a code more than once to weight it
+    pub currencies: Vec<String>,
+    /// Share of payments created with async_settlement (api-service only)
+    pub async_settlement_ratio: f64,
+    /// Share of payments created in test mode (api-service only)
+    pub test_mode_ratio: f64,
+
+    /// Seconds between progress lines
+    pub report_interval_secs: u64,
+    /// Also write the final report here, as JSON
+    pub report_file: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ramp {
+    /// target_rps from the start
+    Constant,
+    /// From 0 to target_rps over ramp_secs
+    Linear,
+    /// ramp_steps equal increases over ramp_secs
+    Step,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Stage {
+    pub duration_secs: u64,
+    pub rps: f64,
+    /// Move linearly from rps to this by the end of the stage
+    pub ramp_to: Option<f64>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            target_url: "http://localhost:3001".to_string(),
+            label: None,
+            target_rps: 50.0,
+            duration_secs: 60,
+            ramp: Ramp::Constant,
+            ramp_secs: 0,
+            ramp_steps: 5,
+            stages: Vec::new(),
+            max_in_flight: 512,
+            request_timeout_ms: 10_000,
+            merchants: 10,
+            merchant_skew: 0.0,
+            amount_min: 100,
+            amount_max: 100_000,
+            currencies: vec!["USD".to_string()],
+            async_settlement_ratio: 0.0,
+            test_mode_ratio: 0.0,
+            report_interval_secs: 5,
+            report_file: None,
+        }
+    }
+}
+
+impl Settings {
+    pub fn label(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.target_url)
+    }
+}
+
+fn ratio(name: &str, value: f64, problems: &mut Vec<String>) {
+    if !(0.0..=1.0).contains(&value) {
+        problems.push(format!("{}: must be between 0 and 1", name));
+    }
+}
+
+impl config::Validate for Settings {
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.stages.is_empty() {
+            if self.target_rps <= 0.0 {
+                problems.push("TARGET_RPS: must be above 0".to_string());
+            }
+            if self.duration_secs == 0 {
+                problems.push("DURATION_SECS: must be at least 1".to_string());
+            }
+            if self.ramp_secs > self.duration_secs {
+                problems.push("RAMP_SECS: longer than DURATION_SECS".to_string());
+            }
+            if self.ramp == Ramp::Step && self.ramp_steps == 0 {
+                problems.push("RAMP_STEPS: must be at least 1".to_string());
+            }
+        }
+        for (i, stage) in self.stages.iter().enumerate() {
+            let rates = [Some(stage.rps), stage.ramp_to];
+            if stage.duration_secs == 0 || rates.into_iter().flatten().any(|r| r < 0.0) {
+                problems.push(format!(
+                    "stages[{}]: needs duration_secs >= 1 and rates >= 0",
+                    i
+                ));
+            }
+        }
+        if self.max_in_flight == 0 {
+            problems.push("MAX_IN_FLIGHT: must be at least 1".to_string());
+        }
+        if self.merchants == 0 {
+            problems.push("MERCHANTS: must be at least 1".to_string());
+        }
+        if self.merchant_skew < 0.0 {
+            problems.push("MERCHANT_SKEW: must be 0 or more".to_string());
+        }
+        if self.amount_min < 1 || self.amount_min > self.amount_max {
+            problems.push("AMOUNT_MIN: must be at least 1 and at most AMOUNT_MAX".to_string());
+        }
+        if self.currencies.is_empty() {
+            problems.push("currencies: at least one".to_string());
+        }
+        ratio(
+            "ASYNC_SETTLEMENT_RATIO",
+            self.async_settlement_ratio,
+            &mut problems,
+        );
+        ratio("TEST_MODE_RATIO", self.test_mode_ratio, &mut problems);
+        if self.report_interval_secs == 0 {
+            problems.push("REPORT_INTERVAL_SECS: must be at least 1".to_string());
+        }
+        problems
+    }
+}
@@ -0,0 +1,139 @@
+use reqwest::Method;
+use serde_json::Value;
+use std::time::Duration;
+
+// ==============================================================================
+// CLIENT: The admin APIs webhookctl talks to
+// ==============================================================================
+//
+// api-service for everything kept in Postgres (events, delivery attempts,
+// dead letters, merchants, direct endpoints, replays), event-archiver for
+// replays of archived events, and Restate's ingress for the svix-caller
+// services that own Svix (SvixAdmin). Responses are kept as JSON values: the
+// CLI shows what the services return rather than keeping its own copy of
+// their types. POSTs to api-service and event-archiver carry X-Operator,
+// which dead-letter actions and archive replays are recorded under.
+
+pub struct Client {
+    http: reqwest::Client,
+    api_url: String,
+    archiver_url: String,
+    restate_url: String,
+    operator: Option<String>,
+}
+
+impl Client {
+    pub fn new(
+        api_url: &str,
+        archiver_url: &str,
+        restate_url: &str,
+        operator: Option<String>,
+    ) -> Self {
+        Client {
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .expect("Failed to build HTTP client"),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            archiver_url: archiver_url.trim_end_matches('/').to_string(),
+            restate_url: restate_url.trim_end_matches('/').to_string(),
+            operator,
+        }
+    }
+
+    /// GET from api-service, with the query parameters that are set
+    pub async fn get(&self, path: &str, query: &[(&str, Option<String>)]) -> Result<Value, String> {
+        self.get_from(&self.api_url, path, query).await
+    }
+
+    /// POST to api-service
+    pub async fn post(&self, path: &str, body: Option<Value>) -> Result<Value, String> {
+        self.post_to(&self.api_url, path, body).await
+    }
+
+    /// GET from event-archiver
+    pub async fn archiver_get(
+        &self,
+        path: &str,
+        query: &[(&str, Option<String>)],
+    ) -> Result<Value, String> {
+        self.get_from(&self.archiver_url, path, query).await
+    }
+
+    /// POST to event-archiver
+    pub async fn archiver_post(&self, path: &str, body: Option<Value>) -> Result<Value, String> {
+        self.post_to(&self.archiver_url, path, body).await
+    }
+
+    async fn get_from(
+        &self,
+        base_url: &str,
+        path: &str,
+        query: &[(&str, Option<String>)],
+    ) -> Result<Value, String> {
+        let query: Vec<(&str, &String)> = query
+            .iter()
+            .filter_map(|(key, value)| value.as_ref().map(|value| (*key, value)))
+            .collect();
+        let request = self
+            .http
+            .get(format!("{}{}", base_url, path))
+            .query(&query);
+        send(Method::GET, path, request).await
+    }
+
+    async fn post_to(
+        &self,
+        base_url: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<Value, String> {
+        let mut request = self.http.post(format!("{}{}", base_url, path));
+        if let Some(operator) = &self.operator {
+            request = request.header("x-operator", operator);
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        send(Method::POST, path, request).await
+    }
+
+    /// Call a Restate handler (`Service/handler`) through the ingress
+    pub async fn restate(&self, handler: &str, body: Value) -> Result<Value, String> {
+        let request = self
+            .http
+            .post(format!("{}/{}", self.restate_url, handler))
+            .json(&body);
+        send(Method::POST, handler, request).await
+    }
+}
+
+async fn send(
+    method: Method,
+    path: &str,
+    request: reqwest::RequestBuilder,
+) -> Result<Value, String> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("{} {} failed: {}", method, path, e))?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("{} {} failed: {}", method, path, e))?;
+    if !status.is_success() {
+        return Err(format!(
+            "{} {} answered {}: {}",
+            method,
+            path,
+            status,
+            body.trim()
+        ));
+    }
+    if body.trim().is_empty() {
+        return Ok(Value::Null);
+    }
+    serde_json::from_str(&body)
+        .map_err(|e| format!("{} {} returned invalid JSON: {}", method, path, e))
+}
@@ -0,0 +1,608 @@
+use serde_json::{json, Value};
+
+use crate::client::Client;
+use crate::output::{cell, json as print_json, next_page, table, truncate};
+
+// ==============================================================================
+// COMMANDS: One function per subcommand
+// ==============================================================================
+//
+// Each makes its requests, then prints either the raw responses (--json) or
+// a table / summary. Merchant ids can be UUIDs or the names api-service maps
+// to UUIDs, as everywhere else.
+
+pub struct EventQuery {
+    pub merchant: Option<String>,
+    pub event_type: Option<String>,
+    pub mode: Option<String>,
+    pub delivered: Option<bool>,
+    pub before: Option<i64>,
+    pub limit: i64,
+}
+
+pub async fn list_events(client: &Client, json: bool, query: EventQuery) -> Result<(), String> {
+    let response = client
+        .get(
+            "/admin/events",
+            &[
+                ("merchant_id", query.merchant),
+                ("event_type", query.event_type),
+                ("mode", query.mode),
+                ("delivered", query.delivered.map(|d| d.to_string())),
+                ("before_id", query.before.map(|b| b.to_string())),
+                ("limit", Some(query.limit.to_string())),
+            ],
+        )
+        .await?;
+    if json {
+        print_json(&response);
+        return Ok(());
+    }
+
+    let rows: Vec<Vec<String>> = list(&response, "events")
+        .iter()
+        .map(|event| {
+            vec![
+                cell(event, "id"),
+                cell(event, "created_at"),
+                cell(event, "event_type"),
+                cell(event, "object_id"),
+                cell(event, "merchant_id"),
+                cell(event, "mode"),
+                yes_no(event, "delivered"),
+                cell(event, "replay_of"),
+            ]
+        })
+        .collect();
+    table(
+        &[
+            "ID",
+            "CREATED",
+            "TYPE",
+            "OBJECT",
+            "MERCHANT",
+            "MODE",
+            "DELIVERED",
+            "REPLAY OF",
+        ],
+        &rows,
+    );
+    next_page(&response, "--before");
+    Ok(())
+}
+
+pub async fn show_event(client: &Client, json: bool, id: i64) -> Result<(), String> {
+    let event = client.get(&format!("/admin/events/{}", id), &[]).await?;
+    if json {
+        print_json(&event);
+        return Ok(());
+    }
+
+    println!("Event {}", cell(&event, "id"));
+    for field in [
+        "event_type",
+        "object_id",
+        "merchant_id",
+        "mode",
+        "created_at",
+        "replay_of",
+    ] {
+        println!("  {:<12} {}", field, cell(&event, field));
+    }
+    println!("  {:<12} {}", "delivered", yes_no(&event, "delivered"));
+    println!("\nPayload:");
+    print_json(event.get("payload").unwrap_or(&Value::Null));
+
+    println!("\nDelivery attempts:");
+    let rows: Vec<Vec<String>> = list(&event, "attempts")
+        .iter()
+        .map(|attempt| {
+            vec![
+                cell(attempt, "id"),
+                cell(attempt, "created_at"),
+                cell(attempt, "delivery_path"),
+                cell(attempt, "status"),
+                truncate(cell(attempt, "error"), 80),
+            ]
+        })
+        .collect();
+    table(&["ID", "AT", "PATH", "STATUS", "ERROR"], &rows);
+    Ok(())
+}
+
+pub struct DeadLetterQuery {
+    pub merchant: Option<String>,
+    pub all: bool,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub error: Option<String>,
+    pub before: Option<i64>,
+    pub limit: i64,
+}
+
+pub async fn list_dead_letters(
+    client: &Client,
+    json: bool,
+    query: DeadLetterQuery,
+) -> Result<(), String> {
+    let response = client
+        .get(
+            "/admin/dead-letters",
+            &[
+                ("merchant_id", query.merchant),
+                ("all", query.all.then(|| "true".to_string())),
+                ("from", query.from),
+                ("to", query.to),
+                ("error", query.error),
+                ("before_id", query.before.map(|b| b.to_string())),
+                ("limit", Some(query.limit.to_string())),
+            ],
+        )
+        .await?;
+    if json {
+        print_json(&response);
+        return Ok(());
+    }
+
+    let rows: Vec<Vec<String>> = list(&response, "dead_letters")
+        .iter()
+        .map(|entry| {
+            vec![
+                cell(entry, "id"),
+                cell(entry, "created_at"),
+                cell(entry, "event_id"),
+                entry
+                    .get("event")
+                    .map(|event| cell(event, "event_type"))
+                    .unwrap_or_else(|| "-".to_string()),
+                cell(entry, "merchant_id"),
+                dead_letter_state(entry),
+                truncate(cell(entry, "error"), 60),
+            ]
+        })
+        .collect();
+    table(
+        &[
+            "ID", "CREATED", "EVENT", "TYPE", "MERCHANT", "STATE", "ERROR",
+        ],
+        &rows,
+    );
+    next_page(&response, "--before");
+    Ok(())
+}
+
+pub async fn show_dead_letter(client: &Client, json: bool, id: i64) -> Result<(), String> {
+    let entry = client
+        .get(&format!("/admin/dead-letters/{}", id), &[])
+        .await?;
+    if json {
+        print_json(&entry);
+        return Ok(());
+    }
+
+    println!("Dead letter {}", cell(&entry, "id"));
+    for field in [
+        "event_id",
+        "merchant_id",
+        "created_at",
+        "redriven_at",
+        "purged_at",
+    ] {
+        println!("  {:<12} {}", field, cell(&entry, field));
+    }
+    println!("  {:<12} {}", "error", cell(&entry, "error"));
+    println!("\nEvent:");
+    print_json(entry.get("event").unwrap_or(&Value::Null));
+
+    println!("\nActions:");
+    action_table(list(&entry, "actions"));
+    Ok(())
+}
+
+/// Which pending dead letters a redrive or purge applies to
+pub struct DeadLetterSelection {
+    pub ids: Vec<i64>,
+    pub merchant: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub limit: Option<i64>,
+    pub reason: Option<String>,
+}
+
+impl DeadLetterSelection {
+    fn body(self) -> Result<Value, String> {
+        if self.ids.is_empty()
+            && self.merchant.is_none()
+            && self.from.is_none()
+            && self.to.is_none()
+        {
+            return Err("select entries with --id, --merchant, --from or --to".to_string());
+        }
+        Ok(json!({
+            "ids": (!self.ids.is_empty()).then_some(self.ids),
+            "merchant_id": self.merchant,
+            "from": self.from,
+            "to": self.to,
+            "limit": self.limit,
+            "reason": self.reason,
+        }))
+    }
+}
+
+/// Send entries through svix-caller again, recorded under --operator
+pub async fn redrive(
+    client: &Client,
+    json: bool,
+    selection: DeadLetterSelection,
+) -> Result<(), String> {
+    let response = client
+        .post("/admin/dead-letters/redrive", Some(selection.body()?))
+        .await?;
+    if json {
+        print_json(&response);
+        return Ok(());
+    }
+
+    let ids: Vec<String> = list(&response, "redriven")
+        .iter()
+        .map(Value::to_string)
+        .collect();
+    println!(
+        "Re-drove {} dead letter(s) {} (action {})",
+        ids.len(),
+        ids.join(", "),
+        cell(&response, "action_id")
+    );
+    Ok(())
+}
+
+pub async fn purge(
+    client: &Client,
+    json: bool,
+    selection: DeadLetterSelection,
+) -> Result<(), String> {
+    let response = client
+        .post("/admin/dead-letters/purge", Some(selection.body()?))
+        .await?;
+    if json {
+        print_json(&response);
+        return Ok(());
+    }
+
+    let ids: Vec<String> = list(&response, "purged")
+        .iter()
+        .map(Value::to_string)
+        .collect();
+    println!(
+        "Purged {} dead letter(s) {} (action {})",
+        ids.len(),
+        ids.join(", "),
+        cell(&response, "action_id")
+    );
+    Ok(())
+}
+
+pub async fn dead_letter_history(
+    client: &Client,
+    json: bool,
+    operator: Option<String>,
+    action: Option<String>,
+    before: Option<i64>,
+    limit: i64,
+) -> Result<(), String> {
+    let response = client
+        .get(
+            "/admin/dead-letters/actions",
+            &[
+                ("operator", operator),
+                ("action", action),
+                ("before_id", before.map(|b| b.to_string())),
+                ("limit", Some(limit.to_string())),
+            ],
+        )
+        .await?;
+    if json {
+        print_json(&response);
+        return Ok(());
+    }
+
+    action_table(list(&response, "actions"));
+    next_page(&response, "--before");
+    Ok(())
+}
+
+fn action_table(actions: &[Value]) {
+    let rows: Vec<Vec<String>> = actions
+        .iter()
+        .map(|action| {
+            let ids: Vec<String> = list(action, "dead_letter_ids")
+                .iter()
+                .map(Value::to_string)
+                .collect();
+            vec![
+                cell(action, "id"),
+                cell(action, "created_at"),
+                cell(action, "action"),
+                cell(action, "operator"),
+                truncate(ids.join(","), 40),
+                truncate(cell(action, "reason"), 40),
+            ]
+        })
+        .collect();
+    table(
+        &["ID", "AT", "ACTION", "OPERATOR", "DEAD LETTERS", "REASON"],
+        &rows,
+    );
+}
+
+fn dead_letter_state(entry: &Value) -> String {
+    if entry.get("purged_at").is_some_and(|at| !at.is_null()) {
+        "purged".to_string()
+    } else if entry.get("redriven_at").is_some_and(|at| !at.is_null()) {
+        "redriven".to_string()
+    } else {
+        "pending".to_string()
+    }
+}
+
+pub async fn replay(
+    client: &Client,
+    json: bool,
+    payment: Option<String>,
+    merchant: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<(), String> {
+    if payment.is_none() && (from.is_none() || to.is_none()) {
+        return Err("give --payment, or --from and --to (optionally with --merchant)".to_string());
+    }
+    let body = json!({
+        "payment_id": payment,
+        "merchant_id": merchant,
+        "from": from,
+        "to": to,
+    });
+    let response = client.post("/events/replay", Some(body)).await?;
+    if json {
+        print_json(&response);
+        return Ok(());
+    }
+
+    println!("Replayed {} event(s)", cell(&response, "replayed"));
+    let rows: Vec<Vec<String>> = list(&response, "events")
+        .iter()
+        .map(|event| {
+            vec![
+                cell(event, "id"),
+                cell(event, "replay_of"),
+                cell(event, "event_type"),
+                cell(event, "object_id"),
+            ]
+        })
+        .collect();
+    table(&["NEW ID", "REPLAY OF", "TYPE", "OBJECT"], &rows);
+    Ok(())
+}
+
+pub async fn replay_archive(
+    client: &Client,
+    json: bool,
+    merchant: &str,
+    from: &str,
+    to: &str,
+    event_types: Vec<String>,
+    reason: Option<String>,
+) -> Result<(), String> {
+    let body = json!({
+        "merchant_id": merchant,
+        "from": from,
+        "to": to,
+        "event_types": (!event_types.is_empty()).then_some(event_types),
+        "reason": reason,
+    });
+    let response = client.archiver_post("/replays", Some(body)).await?;
+    if json {
+        print_json(&response);
+        return Ok(());
+    }
+
+    println!(
+        "Replayed {} archived event(s) from {} object(s) (replay {})",
+        cell(&response, "replayed"),
+        cell(&response, "objects"),
+        cell(&response, "replay_id")
+    );
+    let rows: Vec<Vec<String>> = list(&response, "events")
+        .iter()
+        .map(|event| vec![cell(event, "id"), cell(event, "replay_of")])
+        .collect();
+    table(&["NEW ID", "REPLAY OF"], &rows);
+    Ok(())
+}
+
+pub async fn archive_history(
+    client: &Client,
+    json: bool,
+    merchant: Option<String>,
+    before: Option<i64>,
+    limit: i64,
+) -> Result<(), String> {
+    let response = client
+        .archiver_get(
+            "/replays",
+            &[
+                ("merchant_id", merchant),
+                ("before_id", before.map(|b| b.to_string())),
+                ("limit", Some(limit.to_string())),
+            ],
+        )
+        .await?;
+    if json {
+        print_json(&response);
+        return Ok(());
+    }
+
+    let rows: Vec<Vec<String>> = list(&response, "replays")
+        .iter()
+        .map(|replay| {
+            vec![
+                cell(replay, "id"),
+                cell(replay, "created_at"),
+                cell(replay, "operator"),
+                cell(replay, "merchant_id"),
+                format!("{} .. {}", cell(replay, "from_time"), cell(replay, "to_time")),
+                list(replay, "event_ids").len().to_string(),
+                truncate(cell(replay, "reason"), 40),
+            ]
+        })
+        .collect();
+    table(
+        &["ID", "AT", "OPERATOR", "MERCHANT", "RANGE", "EVENTS", "REASON"],
+        &rows,
+    );
+    next_page(&response, "--before");
+    Ok(())
+}
+
+pub async fn show_merchant(
+    client: &Client,
+    json: bool,
+    merchant: &str,
+    svix_mode: Option<String>,
+) -> Result<(), String> {
+    let details = client.get(&format!("/merchants/{}", merchant), &[]).await?;
+    let usage = client
+        .get(&format!("/merchants/{}/usage", merchant), &[])
+        .await?;
+    let endpoints = client
+        .get(&format!("/merchants/{}/endpoints", merchant), &[])
+        .await?;
+    let svix_endpoints = match svix_mode {
+        Some(mode) => Some(
+            client
+                .restate(
+                    "SvixAdmin/list_endpoints",
+                    json!({ "merchant_id": cell(&details, "id"), "mode": mode }),
+                )
+                .await?,
+        ),
+        None => None,
+    };
+    if json {
+        print_json(&json!({
+            "merchant": details,
+            "usage": usage,
+            "endpoints": endpoints,
+            "svix_endpoints": svix_endpoints,
+        }));
+        return Ok(());
+    }
+
+    println!(
+        "Merchant {} ({})",
+        cell(&details, "name"),
+        cell(&details, "id")
+    );
+    for field in [
+        "mode",
+        "payload_mode",
+        "payload_version",
+        "confirmation_timeout_secs",
+        "svix_region",
+        "created_at",
+    ] {
+        println!("  {:<26} {}", field, cell(&details, field));
+    }
+    println!(
+        "  {:<26} {}",
+        "payload_fields",
+        cell(&details, "payload_fields")
+    );
+    println!(
+        "  {:<26} {}",
+        "payload_template",
+        truncate(cell(&details, "payload_template"), 60)
+    );
+    println!(
+        "\nUsage {}: {} events (quota {})",
+        cell(&usage, "period"),
+        cell(&usage, "event_count"),
+        match cell(&usage, "monthly_event_quota").as_str() {
+            "-" => "unlimited".to_string(),
+            quota => quota.to_string(),
+        }
+    );
+
+    println!("\nDirect endpoints:");
+    let rows: Vec<Vec<String>> = as_list(&endpoints)
+        .iter()
+        .map(|endpoint| {
+            vec![
+                cell(endpoint, "id"),
+                cell(endpoint, "url"),
+                yes_no(endpoint, "disabled"),
+                cell(endpoint, "previous_secret_expires_at"),
+            ]
+        })
+        .collect();
+    table(&["ID", "URL", "DISABLED", "OLD SECRET UNTIL"], &rows);
+
+    if let Some(svix_endpoints) = &svix_endpoints {
+        println!("\nSvix endpoints:");
+        let rows: Vec<Vec<String>> = as_list(svix_endpoints)
+            .iter()
+            .map(|endpoint| {
+                vec![
+                    cell(endpoint, "id"),
+                    cell(endpoint, "url"),
+                    yes_no(endpoint, "disabled"),
+                    cell(endpoint, "event_types"),
+                    cell(endpoint, "channels"),
+                ]
+            })
+            .collect();
+        table(&["ID", "URL", "DISABLED", "EVENT TYPES", "CHANNELS"], &rows);
+    }
+    Ok(())
+}
+
+pub async fn test_webhook(
+    client: &Client,
+    json: bool,
+    merchant: &str,
+    endpoint: &str,
+) -> Result<(), String> {
+    let response = client
+        .post(
+            &format!("/merchants/{}/endpoints/{}/test", merchant, endpoint),
+            None,
+        )
+        .await?;
+    if json {
+        print_json(&response);
+        return Ok(());
+    }
+
+    println!(
+        "Endpoint answered {} ({} signature(s) sent)",
+        cell(&response, "status_code"),
+        cell(&response, "signatures")
+    );
+    Ok(())
+}
+
+fn list<'a>(value: &'a Value, field: &str) -> &'a [Value] {
+    value.get(field).map(as_list).unwrap_or_default()
+}
+
+fn as_list(value: &Value) -> &[Value] {
+    value.as_array().map(Vec::as_slice).unwrap_or_default()
+}
+
+fn yes_no(value: &Value, field: &str) -> String {
+    match value.get(field).and_then(Value::as_bool) {
+        Some(true) => "yes".to_string(),
+        Some(false) => "no".to_string(),
+        None => "-".to_string(),
+    }
+}
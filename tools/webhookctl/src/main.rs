@@ -0,0 +1,369 @@
+use clap::{Args, Parser, Subcommand};
+
+mod client;
+mod commands;
+mod output;
+
+use client::Client;
+
+// ==============================================================================
+// WEBHOOKCTL: Operator CLI over the services' admin APIs
+// ==============================================================================
+//
+// Everything an operator does by hand with curl and psql today, as one tool:
+//
+//   webhookctl events list --merchant <id> --undelivered
+//   webhookctl events show <event_id>          event + delivery attempts
+//   webhookctl dlq list                        dead letters pending re-drive
+//   webhookctl dlq redrive --merchant <id> --from <time> --reason <why>
+//   webhookctl dlq purge --id <id>
+//   webhookctl dlq history                     who re-drove or purged what
+//   webhookctl replay --payment <payment_id>
+//   webhookctl archive replay --merchant <id> --from <time> --to <time>
+//   webhookctl archive history                 archive replays, newest first
+//   webhookctl merchant show <id>              settings, usage, endpoints
+//   webhookctl merchant test-webhook <id> <endpoint_id>
+//
+// It only talks HTTP (client.rs), so it needs no database credentials.
+// Re-drives, purges and archive replays are recorded under --operator
+// (default $USER).
+
+#[derive(Parser)]
+#[command(
+    name = "webhookctl",
+    about = "Inspect and operate the webhook pipeline"
+)]
+struct Cli {
+    /// api-service base URL
+    #[arg(
+        long,
+        global = true,
+        env = "WEBHOOKCTL_API_URL",
+        default_value = "http://localhost:3001"
+    )]
+    api_url: String,
+    /// event-archiver base URL, for replays of archived events
+    #[arg(
+        long,
+        global = true,
+        env = "WEBHOOKCTL_ARCHIVER_URL",
+        default_value = "http://localhost:3009"
+    )]
+    archiver_url: String,
+    /// Restate ingress, for the handlers svix-caller serves
+    #[arg(
+        long,
+        global = true,
+        env = "WEBHOOKCTL_RESTATE_URL",
+        default_value = "http://localhost:8080"
+    )]
+    restate_url: String,
+    /// Who is acting, recorded with re-drives, purges and archive replays
+    /// (default $USER)
+    #[arg(long, global = true, env = "WEBHOOKCTL_OPERATOR")]
+    operator: Option<String>,
+    /// Print the raw JSON responses
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// The domain_events outbox and its delivery attempts
+    #[command(subcommand)]
+    Events(EventsCommand),
+    /// Events svix-caller gave up on
+    #[command(subcommand)]
+    Dlq(DlqCommand),
+    /// Re-emit events from domain_events as new rows (replay_of set)
+    Replay(ReplayArgs),
+    /// Events event-archiver moved to object storage
+    #[command(subcommand)]
+    Archive(ArchiveCommand),
+    /// Merchant settings and endpoints
+    #[command(subcommand)]
+    Merchant(MerchantCommand),
+}
+
+#[derive(Subcommand)]
+enum EventsCommand {
+    /// Newest first
+    List {
+        #[arg(long)]
+        merchant: Option<String>,
+        #[arg(long = "type")]
+        event_type: Option<String>,
+        /// test or live
+        #[arg(long)]
+        mode: Option<String>,
+        /// Only events without a successful delivery attempt
+        #[arg(long, conflicts_with = "delivered")]
+        undelivered: bool,
+        /// Only events with a successful delivery attempt
+        #[arg(long)]
+        delivered: bool,
+        #[arg(long)]
+        before: Option<i64>,
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+    },
+    /// One event with every delivery attempt
+    Show { id: i64 },
+}
+
+#[derive(Subcommand)]
+enum DlqCommand {
+    /// Newest first
+    List {
+        #[arg(long)]
+        merchant: Option<String>,
+        /// Include entries already re-driven or purged
+        #[arg(long)]
+        all: bool,
+        /// RFC 3339, e.g. 2024-05-01T00:00:00Z
+        #[arg(long)]
+        from: Option<String>,
+        #[arg(long)]
+        to: Option<String>,
+        /// Only entries whose error contains this
+        #[arg(long)]
+        error: Option<String>,
+        #[arg(long)]
+        before: Option<i64>,
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+    },
+    /// One entry with its event and every action on it
+    Show { id: i64 },
+    /// Send pending entries through svix-caller again
+    Redrive(DlqSelection),
+    /// Drop pending entries; they stay readable with --all
+    Purge(DlqSelection),
+    /// Re-drives and purges, newest first
+    History {
+        #[arg(long = "by")]
+        operator: Option<String>,
+        /// redrive or purge
+        #[arg(long)]
+        action: Option<String>,
+        #[arg(long)]
+        before: Option<i64>,
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+    },
+}
+
+#[derive(Args)]
+struct DlqSelection {
+    /// Dead-letter ids
+    #[arg(long = "id")]
+    ids: Vec<i64>,
+    #[arg(long)]
+    merchant: Option<String>,
+    /// Created at or after, RFC 3339
+    #[arg(long)]
+    from: Option<String>,
+    /// Created before, RFC 3339
+    #[arg(long)]
+    to: Option<String>,
+    /// At most this many (api-service defaults to 100)
+    #[arg(long)]
+    limit: Option<i64>,
+    /// Recorded with the action
+    #[arg(long)]
+    reason: Option<String>,
+}
+
+impl From<DlqSelection> for commands::DeadLetterSelection {
+    fn from(args: DlqSelection) -> Self {
+        commands::DeadLetterSelection {
+            ids: args.ids,
+            merchant: args.merchant,
+            from: args.from,
+            to: args.to,
+            limit: args.limit,
+            reason: args.reason,
+        }
+    }
+}
+
+#[derive(Args)]
+struct ReplayArgs {
+    /// Every event of one payment
+    #[arg(long)]
+    payment: Option<String>,
+    #[arg(long)]
+    merchant: Option<String>,
+    /// RFC 3339, e.g. 2024-05-01T00:00:00Z
+    #[arg(long)]
+    from: Option<String>,
+    #[arg(long)]
+    to: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum ArchiveCommand {
+    /// Re-emit a merchant's archived events as new rows (replay_of set)
+    Replay {
+        #[arg(long)]
+        merchant: String,
+        /// RFC 3339, e.g. 2024-05-01T00:00:00Z
+        #[arg(long)]
+        from: String,
+        #[arg(long)]
+        to: String,
+        /// Only this event type; repeat for several
+        #[arg(long = "type")]
+        event_types: Vec<String>,
+        /// Recorded with the replay
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Archive replays, newest first
+    History {
+        #[arg(long)]
+        merchant: Option<String>,
+        #[arg(long)]
+        before: Option<i64>,
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum MerchantCommand {
+    /// Settings, this month's usage and endpoints
+    Show {
+        merchant: String,
+        /// Also list the merchant's Svix endpoints (SvixAdmin/list_endpoints)
+        #[arg(long)]
+        svix: bool,
+        /// Svix application: test or live
+        #[arg(long, default_value = "live")]
+        mode: String,
+    },
+    /// Send a signed test webhook to a direct endpoint
+    TestWebhook { merchant: String, endpoint: String },
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let operator = cli.operator.or_else(|| std::env::var("USER").ok());
+    let client = Client::new(&cli.api_url, &cli.archiver_url, &cli.restate_url, operator);
+    let json = cli.json;
+
+    let result = match cli.command {
+        Command::Events(EventsCommand::List {
+            merchant,
+            event_type,
+            mode,
+            undelivered,
+            delivered,
+            before,
+            limit,
+        }) => {
+            let delivered = match (delivered, undelivered) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            };
+            commands::list_events(
+                &client,
+                json,
+                commands::EventQuery {
+                    merchant,
+                    event_type,
+                    mode,
+                    delivered,
+                    before,
+                    limit,
+                },
+            )
+            .await
+        }
+        Command::Events(EventsCommand::Show { id }) => {
+            commands::show_event(&client, json, id).await
+        }
+        Command::Dlq(DlqCommand::List {
+            merchant,
+            all,
+            from,
+            to,
+            error,
+            before,
+            limit,
+        }) => {
+            commands::list_dead_letters(
+                &client,
+                json,
+                commands::DeadLetterQuery {
+                    merchant,
+                    all,
+                    from,
+                    to,
+                    error,
+                    before,
+                    limit,
+                },
+            )
+            .await
+        }
+        Command::Dlq(DlqCommand::Show { id }) => {
+            commands::show_dead_letter(&client, json, id).await
+        }
+        Command::Dlq(DlqCommand::Redrive(selection)) => {
+            commands::redrive(&client, json, selection.into()).await
+        }
+        Command::Dlq(DlqCommand::Purge(selection)) => {
+            commands::purge(&client, json, selection.into()).await
+        }
+        Command::Dlq(DlqCommand::History {
+            operator,
+            action,
+            before,
+            limit,
+        }) => commands::dead_letter_history(&client, json, operator, action, before, limit).await,
+        Command::Replay(args) => {
+            commands::replay(
+                &client,
+                json,
+                args.payment,
+                args.merchant,
+                args.from,
+                args.to,
+            )
+            .await
+        }
+        Command::Archive(ArchiveCommand::Replay {
+            merchant,
+            from,
+            to,
+            event_types,
+            reason,
+        }) => {
+            commands::replay_archive(&client, json, &merchant, &from, &to, event_types, reason)
+                .await
+        }
+        Command::Archive(ArchiveCommand::History {
+            merchant,
+            before,
+            limit,
+        }) => commands::archive_history(&client, json, merchant, before, limit).await,
+        Command::Merchant(MerchantCommand::Show {
+            merchant,
+            svix,
+            mode,
+        }) => commands::show_merchant(&client, json, &merchant, svix.then_some(mode)).await,
+        Command::Merchant(MerchantCommand::TestWebhook { merchant, endpoint }) => {
+            commands::test_webhook(&client, json, &merchant, &endpoint).await
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,64 @@
+use serde_json::Value;
+
+// ==============================================================================
+// OUTPUT: Tables for lists, indented JSON for single records
+// ==============================================================================
+//
+// --json prints every response exactly as the service returned it, for jq.
+
+pub fn json(value: &Value) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+    );
+}
+
+/// A field as one table cell: strings without quotes, missing ones as "-"
+pub fn cell(value: &Value, field: &str) -> String {
+    match value.get(field) {
+        None | Some(Value::Null) => "-".to_string(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Cut long cells (errors, payloads) to fit a terminal line
+pub fn truncate(text: String, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text;
+    }
+    let cut: String = text.chars().take(max.saturating_sub(1)).collect();
+    format!("{}…", cut)
+}
+
+pub fn table(headers: &[&str], rows: &[Vec<String>]) {
+    if rows.is_empty() {
+        println!("(none)");
+        return;
+    }
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(headers.to_vec());
+    for row in rows {
+        line(row.iter().map(String::as_str).collect());
+    }
+}
+
+/// Where the next page starts, when there is one
+pub fn next_page(response: &Value, flag: &str) {
+    if let Some(next) = response.get("next_before_id").and_then(Value::as_i64) {
+        println!("\nMore with {} {}", flag, next);
+    }
+}